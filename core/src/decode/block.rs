@@ -0,0 +1,400 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::script::Instruction;
+use bitcoin::block::Header;
+use bitcoin::consensus::Decodable;
+use bitcoin::hashes::Hash;
+use bitcoin::io::Read;
+use bitcoin::{merkle_tree, Block, Script, Transaction, Txid, VarInt, Wtxid};
+use crate::decode::coinbase::bip34_height;
+use crate::decode::merkle::witness_root;
+use crate::utils::step::{check_work_units, step_json, to_result, Progress, RawValue};
+use crate::utils::encoding::HexReader;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::amount::{with_amount_format, AmountFormat};
+use crate::utils::json::to_json_with_amounts;
+use crate::utils::metrics::timed;
+
+/// Printable runs shorter than this in the coinbase scriptSig are treated as noise.
+const MIN_TAG_LEN: usize = 4;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeaderInfo {
+    pub version: i32,
+    #[serde(alias = "prev_blockhash")]
+    pub prev_blockhash: String,
+    #[serde(alias = "merkle_root")]
+    pub merkle_root: String,
+    pub time: u32,
+    pub bits: String,
+    pub nonce: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTransaction {
+    pub txid: String,
+    pub wtxid: String,
+    pub vsize: usize,
+    #[serde(with = "crate::utils::amount", alias = "output_total")]
+    pub output_total: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinbaseInfo {
+    #[serde(alias = "script_sig")]
+    pub script_sig: String,
+    pub height: Option<u64>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockSummary {
+    pub hash: String,
+    pub header: BlockHeaderInfo,
+    pub size: usize,
+    pub weight: u64,
+    #[serde(alias = "tx_count")]
+    pub tx_count: usize,
+    #[serde(alias = "merkle_root_valid")]
+    pub merkle_root_valid: bool,
+    /// `valid`, `invalid`, `missing`, or `not_required` when no transaction has a witness.
+    #[serde(alias = "witness_commitment")]
+    pub witness_commitment: String,
+    pub coinbase: Option<CoinbaseInfo>,
+    pub transactions: Vec<BlockTransaction>,
+}
+
+/// Decodes a serialized block. Fees are not reported because they need every input's
+/// prevout. The hex is decoded while streaming and each transaction is summarized and
+/// dropped as it's read, so neither a byte copy of the block nor its parsed
+/// transactions are held next to the hex.
+#[wasm_bindgen]
+pub fn decode_block(block_hex: &str, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("decode_block", block_hex.len(), || {
+        let mut reader = HexReader::new(block_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Failed to deserialize block: {}", e)))?;
+        let length = reader.remaining();
+        let tally = read_block(&mut reader, length)?;
+        if reader.remaining() != 0 {
+            return Err(trailing_data(reader.remaining()));
+        }
+        summary_json(tally, amount_format, compact)
+    })
+}
+
+/// `decode_block` for a block already in binary, e.g. a `fetch` response's bytes or a
+/// `HexDecoder`'s output, which skips the hex string and its doubled size entirely.
+#[wasm_bindgen]
+pub fn decode_block_bytes(block: &[u8], amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("decode_block_bytes", block.len(), || {
+        let mut cursor = block;
+        let tally = read_block(&mut cursor, block.len())?;
+        if !cursor.is_empty() {
+            return Err(trailing_data(cursor.len()));
+        }
+        summary_json(tally, amount_format, compact)
+    })
+}
+
+fn summary_json(tally: BlockTally, amount_format: AmountFormat, compact: Option<bool>) -> Result<String, JsValue> {
+    to_json_with_amounts(&tally.summary(), amount_format, compact)
+}
+
+/// Reads the header and every transaction of a `length`-byte block from `reader`.
+fn read_block<R: Read + ?Sized>(reader: &mut R, length: usize) -> Result<BlockTally, JsValue> {
+    let (mut tally, tx_count) = read_block_start(reader, length)?;
+    for _ in 0..tx_count {
+        tally.add(read_transaction(reader, tally.tx_count())?);
+    }
+    Ok(tally)
+}
+
+/// The header and transaction count, checked against the bytes left for transactions.
+fn read_block_start<R: Read + ?Sized>(reader: &mut R, length: usize) -> Result<(BlockTally, usize), JsValue> {
+    let header = Header::consensus_decode(reader).map_err(block_error)?;
+    let tx_count = VarInt::consensus_decode(reader).map_err(block_error)?;
+    let remaining = length.saturating_sub(Header::SIZE + tx_count.size());
+    // Every transaction takes at least 60 bytes, so a larger count can't be honest.
+    if tx_count.0 > (remaining / 60) as u64 {
+        return Err(JsValue::from_str(&format!(
+            "Failed to deserialize block: {} transactions don't fit in the remaining {} bytes",
+            tx_count.0, remaining
+        )));
+    }
+    Ok((BlockTally::new(header, tx_count.0 as usize), tx_count.0 as usize))
+}
+
+fn read_transaction<R: Read + ?Sized>(reader: &mut R, index: usize) -> Result<Transaction, JsValue> {
+    Transaction::consensus_decode(reader)
+        .map_err(|e| JsValue::from_str(&format!("Failed to deserialize block: transaction {}: {}", index, e)))
+}
+
+fn trailing_data(bytes: usize) -> JsValue {
+    JsValue::from_str(&format!("Failed to deserialize block: {} bytes of trailing data", bytes))
+}
+
+/// `decode_block` in steps: the header is read up front, then each
+/// `step(max_work_units)` decodes up to that many transactions. The merkle root and
+/// witness commitment are checked in the final step, whose `result` is the same summary.
+#[wasm_bindgen]
+pub struct BlockDecoder {
+    bytes: Vec<u8>,
+    offset: usize,
+    tx_count: usize,
+    tally: Option<BlockTally>,
+    amount_format: AmountFormat,
+    result: Option<Box<RawValue>>,
+}
+
+#[wasm_bindgen]
+impl BlockDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(block_hex: &str, amount_format: Option<String>) -> Result<BlockDecoder, JsValue> {
+        let bytes = hex_to_bytes(block_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Failed to deserialize block: {}", e)))?;
+        BlockDecoder::from_bytes(bytes, amount_format)
+    }
+
+    /// A decoder over the block's binary form, taking the bytes without a hex copy.
+    pub fn from_bytes(bytes: Vec<u8>, amount_format: Option<String>) -> Result<BlockDecoder, JsValue> {
+        let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let mut cursor = bytes.as_slice();
+        let (tally, tx_count) = read_block_start(&mut cursor, bytes.len())?;
+        Ok(BlockDecoder { offset: bytes.len() - cursor.len(), tx_count, tally: Some(tally), amount_format, bytes, result: None })
+    }
+
+    pub fn step(&mut self, max_work_units: u32, compact: Option<bool>) -> Result<String, JsValue> {
+        let units = check_work_units(max_work_units)?;
+        if let Some(tally) = &mut self.tally {
+            let mut cursor = &self.bytes[self.offset..];
+            for _ in 0..units.min(self.tx_count - tally.tx_count()) {
+                tally.add(read_transaction(&mut cursor, tally.tx_count())?);
+            }
+            self.offset = self.bytes.len() - cursor.len();
+
+            if tally.tx_count() == self.tx_count {
+                if !cursor.is_empty() {
+                    return Err(trailing_data(cursor.len()));
+                }
+                let tally = self.tally.take().expect("tally present until the last step");
+                self.result = Some(with_amount_format(self.amount_format, || to_result(&tally.summary()))?);
+                self.bytes = Vec::new();
+            }
+        }
+        let completed = self.tally.as_ref().map_or(self.tx_count, BlockTally::tx_count);
+        step_json(Progress::of(completed, self.tx_count), self.result.as_deref(), compact)
+    }
+}
+
+/// What the block summary needs from each transaction, gathered as they're decoded so
+/// the block's transactions never have to be held at once. Only the coinbase is kept.
+struct BlockTally {
+    header: Header,
+    first: Option<Transaction>,
+    txids: Vec<Txid>,
+    wtxids: Vec<Wtxid>,
+    has_witness: bool,
+    transactions_size: usize,
+    transactions_weight: u64,
+    transactions: Vec<BlockTransaction>,
+}
+
+impl BlockTally {
+    fn new(header: Header, capacity: usize) -> BlockTally {
+        BlockTally {
+            header,
+            first: None,
+            txids: Vec::with_capacity(capacity),
+            wtxids: Vec::with_capacity(capacity),
+            has_witness: false,
+            transactions_size: 0,
+            transactions_weight: 0,
+            transactions: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn tx_count(&self) -> usize {
+        self.txids.len()
+    }
+
+    fn add(&mut self, tx: Transaction) {
+        let (txid, wtxid) = (tx.compute_txid(), tx.compute_wtxid());
+        self.transactions.push(BlockTransaction {
+            txid: txid.to_string(),
+            wtxid: wtxid.to_string(),
+            vsize: tx.vsize(),
+            output_total: tx.output.iter().map(|output| output.value.to_sat()).sum(),
+        });
+        self.txids.push(txid);
+        self.wtxids.push(wtxid);
+        self.has_witness |= tx.input.iter().any(|input| !input.witness.is_empty());
+        self.transactions_size += tx.total_size();
+        self.transactions_weight += tx.weight().to_wu();
+        if self.first.is_none() {
+            self.first = Some(tx);
+        }
+    }
+
+    /// The same checks as `Block::check_merkle_root` and `Block::check_witness_commitment`,
+    /// from the hashes collected along the way.
+    fn summary(self) -> BlockSummary {
+        let tx_count = self.txids.len();
+        let base_size = Header::SIZE + VarInt(tx_count as u64).size();
+        let merkle_root = merkle_tree::calculate_root(self.txids.iter().map(|txid| txid.to_raw_hash().into()));
+        // The header and the coinbase are all BIP34 needs.
+        let head = Block { header: self.header, txdata: self.first.into_iter().collect() };
+        let commitment = head.txdata.first().and_then(commitment_output);
+        let witness_commitment = if !self.has_witness {
+            "not_required"
+        } else if commitment_matches(&head, commitment, self.wtxids) {
+            "valid"
+        } else if commitment.is_some() {
+            "invalid"
+        } else {
+            "missing"
+        };
+
+        BlockSummary {
+            hash: head.block_hash().to_string(),
+            header: BlockHeaderInfo {
+                version: head.header.version.to_consensus(),
+                prev_blockhash: head.header.prev_blockhash.to_string(),
+                merkle_root: head.header.merkle_root.to_string(),
+                time: head.header.time,
+                bits: format!("{:08x}", head.header.bits.to_consensus()),
+                nonce: head.header.nonce,
+            },
+            size: base_size + self.transactions_size,
+            weight: base_size as u64 * 4 + self.transactions_weight,
+            tx_count,
+            merkle_root_valid: merkle_root == Some(head.header.merkle_root),
+            witness_commitment: witness_commitment.to_string(),
+            coinbase: head.txdata.first().filter(|tx| tx.is_coinbase()).map(|tx| coinbase_info(&head, tx)),
+            transactions: self.transactions,
+        }
+    }
+}
+
+/// Whether the coinbase's commitment matches the witness root, given a coinbase witness
+/// of one 32-byte reserved value.
+fn commitment_matches(head: &Block, commitment: Option<(usize, [u8; 32])>, wtxids: Vec<Wtxid>) -> bool {
+    let (Some(coinbase), Some((_, found))) = (head.txdata.first().filter(|tx| tx.is_coinbase()), commitment) else {
+        return false;
+    };
+    let reserved: Vec<&[u8]> = coinbase.input[0].witness.iter().collect();
+    match (reserved.as_slice(), witness_root(wtxids)) {
+        ([reserved], Some(root)) if reserved.len() == 32 => Block::compute_witness_commitment(&root, reserved).to_byte_array() == found,
+        _ => false,
+    }
+}
+
+fn block_error(e: bitcoin::consensus::encode::Error) -> JsValue {
+    JsValue::from_str(&format!("Failed to deserialize block: {}", e))
+}
+
+pub fn parse_block_hex(block_hex: &str) -> Result<Block, String> {
+    bitcoin::consensus::encode::deserialize_hex(block_hex.trim()).map_err(|e| format!("Failed to deserialize block: {}", e))
+}
+
+pub fn parse_block_bytes(bytes: &[u8]) -> Result<Block, String> {
+    bitcoin::consensus::deserialize(bytes).map_err(|e| format!("Failed to deserialize block: {}", e))
+}
+
+/// The BIP34 height (version 2+ blocks) and the printable ASCII runs miners put in the
+/// scriptSig's pushes, e.g. "/Foundry USA Pool/". The height push is skipped so its
+/// bytes don't read as a tag. A scriptSig that doesn't parse is scanned as raw bytes.
+fn coinbase_info(block: &Block, coinbase: &Transaction) -> CoinbaseInfo {
+    let script_sig = &coinbase.input[0].script_sig;
+    // `Block::bip34_block_height` only reads pushes, but heights 1 to 16 are OP_1 to OP_16.
+    let height = (block.header.version.to_consensus() >= 2).then(|| bip34_height(script_sig)).flatten();
+    CoinbaseInfo { script_sig: bytes_to_hex(script_sig.as_bytes()), height, tags: coinbase_tags(script_sig, height.is_some()) }
+}
+
+/// Printable ASCII runs in a coinbase scriptSig's pushes, skipping the first push when
+/// it's the BIP34 height.
+pub fn coinbase_tags(script_sig: &Script, skip_height: bool) -> Vec<String> {
+    let pushes: Option<Vec<&[u8]>> = script_sig
+        .instructions()
+        .skip(skip_height as usize)
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(bytes)) => Some(Ok(bytes.as_bytes())),
+            Ok(Instruction::Op(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<_, _>>()
+        .ok();
+    let segments = pushes.unwrap_or_else(|| vec![script_sig.as_bytes()]);
+
+    let mut tags = Vec::new();
+    for segment in segments {
+        let mut run = String::new();
+        for &byte in segment {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                run.push(byte as char);
+            } else {
+                push_tag(&mut tags, &mut run);
+            }
+        }
+        push_tag(&mut tags, &mut run);
+    }
+    tags
+}
+
+fn push_tag(tags: &mut Vec<String>, run: &mut String) {
+    let tag = run.trim();
+    if tag.len() >= MIN_TAG_LEN {
+        tags.push(tag.to_string());
+    }
+    run.clear();
+}
+
+/// The BIP141 commitment: the last coinbase output that is OP_RETURN, a 36-byte push and the header 0xaa21a9ed.
+pub fn commitment_output(coinbase: &Transaction) -> Option<(usize, [u8; 32])> {
+    coinbase.output.iter().enumerate().rev().find_map(|(index, output)| {
+        let script = output.script_pubkey.as_bytes();
+        if script.len() >= 38 && script[..6] == [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed] {
+            Some((index, script[6..38].try_into().expect("32 bytes")))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The mainnet genesis block.
+    const GENESIS_BLOCK: &str = concat!(
+        "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e",
+        "67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c01",
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d01",
+        "04455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f662073",
+        "65636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe55482719",
+        "67f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a",
+        "4c702b6bf11d5fac00000000"
+    );
+
+    #[test]
+    fn hex_and_binary_decoding_agree() {
+        let from_hex = decode_block(GENESIS_BLOCK, None, Some(true)).unwrap();
+        let bytes = hex_to_bytes(GENESIS_BLOCK, HexMode::Strict).unwrap();
+        assert_eq!(decode_block_bytes(&bytes, None, Some(true)).unwrap(), from_hex);
+        assert!(from_hex.contains("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"), "{}", from_hex);
+    }
+
+    #[test]
+    fn hex_reader_streams_the_block() {
+        // The hex path reads through a borrowed reader; nothing the size of the block
+        // is decoded up front.
+        let mut reader = HexReader::new(GENESIS_BLOCK, HexMode::Lenient).unwrap();
+        let length = reader.remaining();
+        let tally = read_block(&mut reader, length).unwrap();
+        assert_eq!(tally.tx_count(), 1);
+        assert_eq!(reader.remaining(), 0);
+    }
+}
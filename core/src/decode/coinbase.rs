@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::script::Instruction;
+use bitcoin::{Network, Script, Transaction};
+use crate::decode::block::{coinbase_tags, commitment_output};
+use crate::transaction::decode_tx_hex;
+use crate::utils::bytes_to_hex;
+use crate::utils::network::network_or_default;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::to_json_with_amounts;
+
+const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+const HALVING_INTERVAL: u64 = 210_000;
+const REGTEST_HALVING_INTERVAL: u64 = 150;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WitnessCommitmentOutput {
+    #[serde(alias = "output_index")]
+    pub output_index: usize,
+    pub commitment: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinbaseDetails {
+    pub txid: String,
+    #[serde(alias = "script_sig")]
+    pub script_sig: String,
+    /// The scriptSig's first push read as a BIP34 height. Blocks from before BIP34
+    /// (below 227,931 on mainnet) don't commit to one, so this may be a stray number.
+    pub height: Option<u64>,
+    pub tags: Vec<String>,
+    #[serde(alias = "witness_commitment")]
+    pub witness_commitment: Option<WitnessCommitmentOutput>,
+    /// The coinbase input's 32-byte witness, which the commitment hashes with the witness root.
+    #[serde(alias = "witness_reserved_value")]
+    pub witness_reserved_value: Option<String>,
+    /// What the miner claimed: the block subsidy plus the fees of every other transaction.
+    #[serde(with = "crate::utils::amount", alias = "output_total")]
+    pub output_total: u64,
+    /// The subsidy at `height` on the chosen network, when the height is known.
+    pub subsidy: Option<u64>,
+    /// `output_total` minus the subsidy. Missing when a miner claimed less than the subsidy.
+    #[serde(alias = "implied_fees")]
+    pub implied_fees: Option<u64>,
+}
+
+/// Whether the transaction is a coinbase: a single input spending the null outpoint.
+#[wasm_bindgen]
+pub fn is_coinbase(tx_hex: &str) -> Result<bool, JsValue> {
+    Ok(decode_tx_hex(tx_hex)?.is_coinbase())
+}
+
+/// The coinbase-specific fields of a coinbase transaction. `network` picks the halving
+/// schedule for `subsidy` (regtest halves every 150 blocks).
+#[wasm_bindgen]
+pub fn decode_coinbase(tx_hex: &str, network: Option<String>, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let tx = decode_tx_hex(tx_hex)?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    if !tx.is_coinbase() {
+        return Err(JsValue::from_str(&format!(
+            "Not a coinbase transaction: input 0 spends {}",
+            tx.input[0].previous_output
+        )));
+    }
+
+    let details = coinbase_details(&tx, network.network);
+    to_json_with_amounts(&details, amount_format, compact)
+}
+
+pub fn coinbase_details(tx: &Transaction, network: Network) -> CoinbaseDetails {
+    let input = &tx.input[0];
+    let height = bip34_height(&input.script_sig);
+    let output_total: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    let subsidy = height.map(|height| block_subsidy(height, network));
+    let reserved = match input.witness.len() {
+        1 if input.witness[0].len() == 32 => Some(bytes_to_hex(&input.witness[0])),
+        _ => None,
+    };
+
+    CoinbaseDetails {
+        txid: tx.compute_txid().to_string(),
+        script_sig: bytes_to_hex(input.script_sig.as_bytes()),
+        height,
+        tags: coinbase_tags(&input.script_sig, height.is_some()),
+        witness_commitment: commitment_output(tx).map(|(output_index, commitment)| WitnessCommitmentOutput {
+            output_index,
+            commitment: bytes_to_hex(&commitment),
+        }),
+        witness_reserved_value: reserved,
+        output_total,
+        subsidy,
+        implied_fees: subsidy.and_then(|subsidy| output_total.checked_sub(subsidy)),
+    }
+}
+
+/// BIP34 pushes the height as a minimal script number; Bitcoin Core writes heights 1
+/// to 16 as OP_1 to OP_16.
+pub fn bip34_height(script_sig: &Script) -> Option<u64> {
+    match script_sig.instructions_minimal().next()?.ok()? {
+        Instruction::Op(op) if (0x51..=0x60).contains(&op.to_u8()) => Some((op.to_u8() - 0x50) as u64),
+        Instruction::PushBytes(bytes) => {
+            let height = bitcoin::script::read_scriptint(bytes.as_bytes()).ok()?;
+            u64::try_from(height).ok()
+        }
+        Instruction::Op(_) => None,
+    }
+}
+
+pub fn block_subsidy(height: u64, network: Network) -> u64 {
+    let interval = match network {
+        Network::Regtest => REGTEST_HALVING_INTERVAL,
+        _ => HALVING_INTERVAL,
+    };
+    match height / interval {
+        halvings if halvings >= 64 => 0,
+        halvings => INITIAL_SUBSIDY >> halvings,
+    }
+}
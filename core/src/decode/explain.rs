@@ -0,0 +1,271 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Function;
+use bitcoin::absolute::LOCK_TIME_THRESHOLD;
+use bitcoin::relative;
+use bitcoin::{Network, Script, Transaction, TxOut};
+use crate::decode::inputs::{spend_type_from_prevout, spend_type_from_shape};
+use crate::privacy::privacy_report;
+use crate::script::output_script_type;
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::transaction::locktime::looks_like_anti_fee_sniping;
+use crate::transaction::provider::resolve_prevouts;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+/// Plain-English sentences describing what a transaction does, meant to be shown as-is
+/// to someone confirming it. When prevouts are missing, the facts that depend on
+/// them (amounts spent, fee, change) say that they couldn't be determined.
+#[wasm_bindgen]
+pub fn explain_transaction(tx_hex: &str, prevouts_json: Option<String>, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("explain_transaction", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let prevouts: Option<Vec<TxOut>> = match prevouts_json {
+            Some(json) if !json.trim().is_empty() => Some(parse_prevouts(&json, tx.input.len())?),
+            _ => None,
+        };
+
+        to_json(&explain(&tx, prevouts.as_deref(), &network), compact)
+    })
+}
+
+/// Same as `explain_transaction`, with prevouts missing from `prevouts_json` looked up
+/// through `provider`, a `(txid, vout) => Promise` callback, so the fee and change
+/// can always be worked out.
+#[wasm_bindgen]
+pub async fn explain_transaction_with_provider(
+    tx_hex: String,
+    prevouts_json: Option<String>,
+    network: Option<String>,
+    provider: Function,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(&tx_hex)?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let prevouts = if tx.is_coinbase() {
+        None
+    } else {
+        Some(resolve_prevouts(&tx, prevouts_json.as_deref(), Some(provider)).await?)
+    };
+
+    to_json(&explain(&tx, prevouts.as_deref(), &network), compact)
+}
+
+pub fn explain(tx: &Transaction, prevouts: Option<&[TxOut]>, network: &NetworkParams) -> Vec<String> {
+    let unit = if network.network == Network::Bitcoin { "BTC" } else { "tBTC" };
+    let mut lines = Vec::new();
+
+    if tx.is_coinbase() {
+        lines.push("Creates new coins (coinbase transaction)".to_string());
+    } else {
+        for (index, input) in tx.input.iter().enumerate() {
+            let outpoint = format!("{}:{}", input.previous_output.txid, input.previous_output.vout);
+            lines.push(match prevouts.map(|prevouts| &prevouts[index]) {
+                Some(prevout) => format!(
+                    "Spends {} from {}",
+                    format_btc(prevout.value.to_sat(), unit),
+                    destination(&prevout.script_pubkey, spend_type_from_prevout(input, &prevout.script_pubkey), network)
+                ),
+                None => match type_label(spend_type_from_shape(input)) {
+                    Some(label) => format!("Spends coin {} ({}); its amount and address are unknown", outpoint, label),
+                    None => format!("Spends coin {}; its amount, address and type are unknown", outpoint),
+                },
+            });
+        }
+    }
+
+    let change = change_output(tx, prevouts);
+    for (index, output) in tx.output.iter().enumerate() {
+        let amount = format_btc(output.value.to_sat(), unit);
+        let script = &output.script_pubkey;
+        lines.push(if script.is_op_return() {
+            let data = script.len().saturating_sub(1);
+            if output.value.to_sat() == 0 {
+                format!("Records {} bytes of data on-chain (OP_RETURN)", data)
+            } else {
+                format!("Burns {} with {} bytes of data (OP_RETURN, unspendable)", amount, data)
+            }
+        } else {
+            match change {
+                Some((change, true)) if change == index => {
+                    format!("Change of {} returns to {}", amount, destination(script, output_script_type(script), network))
+                }
+                Some((change, false)) if change == index => format!(
+                    "Pays {} to {}, which looks like change but can't be confirmed",
+                    amount,
+                    destination(script, output_script_type(script), network)
+                ),
+                _ => format!("Pays {} to {}", amount, destination(script, output_script_type(script), network)),
+            }
+        });
+    }
+
+    let payments = tx.output.iter().filter(|output| !output.script_pubkey.is_op_return()).count();
+    if prevouts.is_none() && !tx.is_coinbase() && payments > 1 {
+        lines.push("Which output is change can't be determined without the spent coins".to_string());
+    }
+
+    if !tx.is_coinbase() {
+        lines.push(fee_line(tx, prevouts));
+    }
+
+    if tx.is_explicitly_rbf() {
+        lines.push("Signals RBF: it can be replaced by a version paying a higher fee until it confirms".to_string());
+    } else if !tx.is_coinbase() {
+        lines.push("Does not signal RBF".to_string());
+    }
+
+    lines.extend(lock_lines(tx));
+
+    lines
+}
+
+/// `Some((index, true))` when an output pays back to a script one of the inputs spent. Otherwise
+/// the privacy heuristics' guess, `Some((index, false))`.
+fn change_output(tx: &Transaction, prevouts: Option<&[TxOut]>) -> Option<(usize, bool)> {
+    let prevouts = prevouts?;
+    let returning: Vec<usize> = (0..tx.output.len())
+        .filter(|&i| prevouts.iter().any(|prevout| prevout.script_pubkey == tx.output[i].script_pubkey))
+        .collect();
+    match returning.as_slice() {
+        [index] if tx.output.len() > 1 => return Some((*index, true)),
+        [] => {}
+        _ => return None,
+    }
+    privacy_report(tx, Some(prevouts)).likely_change.map(|index| (index, false))
+}
+
+fn fee_line(tx: &Transaction, prevouts: Option<&[TxOut]>) -> String {
+    let Some(prevouts) = prevouts else {
+        return "Fee: unknown, because the spent coins' amounts weren't provided".to_string();
+    };
+    let spent: u64 = prevouts.iter().map(|prevout| prevout.value.to_sat()).sum();
+    let paid: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    if paid > spent {
+        return format!("Invalid: pays out {} sats more than it spends", group_digits(paid - spent));
+    }
+
+    let fee = spent - paid;
+    let signed = tx.input.iter().all(|input| !input.script_sig.is_empty() || !input.witness.is_empty());
+    if signed {
+        format!("Fee: {} sats ({:.1} sat/vB)", group_digits(fee), fee as f64 / tx.vsize() as f64)
+    } else {
+        format!("Fee: {} sats (the fee rate is known once the transaction is signed)", group_digits(fee))
+    }
+}
+
+fn lock_lines(tx: &Transaction) -> Vec<String> {
+    let mut lines = Vec::new();
+    let lock_time = tx.lock_time.to_consensus_u32();
+    if lock_time != 0 {
+        let what = if lock_time < LOCK_TIME_THRESHOLD {
+            format!("block {}", group_digits(lock_time as u64))
+        } else {
+            format_timestamp(lock_time)
+        };
+        if looks_like_anti_fee_sniping(tx) {
+            lines.push(format!(
+                "Locked until {}, which is most likely the chain tip when it was built (anti-fee-sniping), so it doesn't delay confirmation",
+                what
+            ));
+        } else if tx.is_lock_time_enabled() {
+            lines.push(format!("Locked until {}", what));
+        } else {
+            lines.push(format!("Lock time {} is ignored because no input enables it", what));
+        }
+    }
+
+    if tx.version.0 >= 2 {
+        for (index, input) in tx.input.iter().enumerate() {
+            match input.sequence.to_relative_lock_time() {
+                Some(relative::LockTime::Blocks(height)) => lines.push(format!(
+                    "Input {} can't confirm until its coin is {} blocks old",
+                    index,
+                    group_digits(height.value() as u64)
+                )),
+                Some(relative::LockTime::Time(time)) => lines.push(format!(
+                    "Input {} can't confirm until its coin is {} old",
+                    index,
+                    format_duration(time.value() as u64 * 512)
+                )),
+                None => {}
+            }
+        }
+    }
+    lines
+}
+
+fn destination(script: &Script, script_type: &str, network: &NetworkParams) -> String {
+    let label = type_label(script_type);
+    match (network.address(script), label) {
+        (Some(address), Some(label)) => format!("{} ({})", address, label),
+        (Some(address), None) => address,
+        (None, Some("P2PK")) => "a bare public key (P2PK)".to_string(),
+        (None, _) => "a script with no address form (nonstandard)".to_string(),
+    }
+}
+
+fn type_label(script_type: &str) -> Option<&'static str> {
+    match script_type {
+        "p2pkh" => Some("P2PKH"),
+        "p2wpkh" => Some("P2WPKH"),
+        "p2sh-p2wpkh" => Some("P2SH-P2WPKH"),
+        "p2sh-p2wsh" => Some("P2SH-P2WSH"),
+        "p2sh" => Some("P2SH"),
+        "p2wsh" => Some("P2WSH"),
+        "p2tr" | "p2tr-keypath" | "p2tr-scriptpath" => Some("P2TR"),
+        "p2pk" => Some("P2PK"),
+        _ => None,
+    }
+}
+
+/// 1_500_000 sats reads "0.015 tBTC": trailing zeros trimmed and thousands grouped.
+fn format_btc(sats: u64, unit: &str) -> String {
+    let fraction = format!("{:08}", sats % 100_000_000);
+    let fraction = fraction.trim_end_matches('0');
+    let whole = group_digits(sats / 100_000_000);
+    if fraction.is_empty() {
+        format!("{} {}", whole, unit)
+    } else {
+        format!("{}.{} {}", whole, fraction, unit)
+    }
+}
+
+fn group_digits(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+fn format_duration(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    match (hours / 24, hours % 24) {
+        (0, 0) => format!("{} minutes", seconds / 60),
+        (0, hours) => format!("{} hours", hours),
+        (days, 0) => format!("{} days", days),
+        (days, hours) => format!("{} days {} hours", days, hours),
+    }
+}
+
+/// "2026-10-14 12:00 UTC". The date comes from the days-since-epoch civil calendar algorithm.
+fn format_timestamp(unix_time: u32) -> String {
+    let days = unix_time as i64 / 86_400;
+    let seconds = unix_time as i64 % 86_400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, seconds / 3600, seconds % 3600 / 60)
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::script::Instruction;
+use bitcoin::taproot::ControlBlock;
+use bitcoin::{Script, TxIn, TxOut, Witness};
+use crate::transaction::historic::{ecdsa_signature, input_quirks, InputQuirk};
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::json::to_json;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputClassification {
+    pub index: usize,
+    #[serde(alias = "spend_type")]
+    pub spend_type: String,
+    pub source: String,
+    #[serde(alias = "prevout_confirmed")]
+    pub prevout_confirmed: bool,
+    pub note: Option<String>,
+    /// Non-minimal pushes, pre-BIP66 signatures and other encodings modern wallets
+    /// don't produce.
+    #[serde(default)]
+    pub quirks: Vec<InputQuirk>,
+}
+
+#[wasm_bindgen]
+pub fn classify_inputs(tx_hex: &str, prevouts_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(tx_hex)?;
+
+    let prevouts: Option<Vec<TxOut>> = match prevouts_json {
+        Some(json) => Some(parse_prevouts(&json, tx.input.len())?),
+        None => None,
+    };
+
+    let results: Vec<InputClassification> = tx
+        .input
+        .iter()
+        .enumerate()
+        .map(|(index, txin)| {
+            classify_input(index, txin, prevouts.as_ref().map(|p| p[index].script_pubkey.as_script()))
+        })
+        .collect();
+
+    to_json(&results, compact)
+}
+
+pub fn classify_input(index: usize, txin: &TxIn, prevout_script: Option<&Script>) -> InputClassification {
+    let shape = spend_type_from_shape(txin);
+
+    let Some(script) = prevout_script else {
+        return InputClassification {
+            index,
+            spend_type: shape.to_string(),
+            source: "shape".to_string(),
+            prevout_confirmed: false,
+            note: None,
+            quirks: input_quirks(txin),
+        };
+    };
+
+    let from_prevout = spend_type_from_prevout(txin, script);
+    let note = if shape != "unknown" && shape != from_prevout {
+        Some(format!(
+            "input shape suggests {} but prevout script is {}",
+            shape, from_prevout
+        ))
+    } else {
+        None
+    };
+
+    InputClassification {
+        index,
+        spend_type: from_prevout.to_string(),
+        source: "prevout".to_string(),
+        prevout_confirmed: from_prevout != "unknown" && shape == from_prevout,
+        note,
+        quirks: input_quirks(txin),
+    }
+}
+
+pub fn spend_type_from_shape(txin: &TxIn) -> &'static str {
+    if txin.previous_output.is_null() {
+        return "coinbase";
+    }
+
+    let witness = &txin.witness;
+
+    if txin.script_sig.is_empty() {
+        if witness.is_empty() {
+            return "unknown";
+        }
+        if is_taproot_script_path(witness) {
+            return "p2tr-scriptpath";
+        }
+        let items = witness_without_annex(witness);
+        if items.len() == 1 && (items[0].len() == 64 || items[0].len() == 65) {
+            return "p2tr-keypath";
+        }
+        if items.len() == 2 && is_der_signature(items[0]) && items[1].len() == 33 {
+            return "p2wpkh";
+        }
+        return "p2wsh";
+    }
+
+    let Some(pushes) = script_sig_pushes(&txin.script_sig) else {
+        return "unknown";
+    };
+
+    if pushes.len() == 1 && !witness.is_empty() {
+        let redeem = Script::from_bytes(&pushes[0]);
+        if redeem.is_p2wpkh() {
+            return "p2sh-p2wpkh";
+        }
+        if redeem.is_p2wsh() {
+            return "p2sh-p2wsh";
+        }
+    }
+
+    if witness.is_empty() {
+        if pushes.len() == 2 && is_der_signature(&pushes[0]) && (pushes[1].len() == 33 || pushes[1].len() == 65) {
+            return "p2pkh";
+        }
+        if pushes.len() == 1 && is_der_signature(&pushes[0]) {
+            return "p2pk";
+        }
+        if let Some(last) = pushes.last() {
+            if !last.is_empty() && !is_der_signature(last) && is_parseable_script(last) {
+                return "p2sh";
+            }
+        }
+    }
+
+    "unknown"
+}
+
+pub fn spend_type_from_prevout(txin: &TxIn, script: &Script) -> &'static str {
+    if txin.previous_output.is_null() {
+        "coinbase"
+    } else if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2wpkh() {
+        "p2wpkh"
+    } else if script.is_p2wsh() {
+        "p2wsh"
+    } else if script.is_p2tr() {
+        if is_taproot_script_path(&txin.witness) {
+            "p2tr-scriptpath"
+        } else {
+            "p2tr-keypath"
+        }
+    } else if script.is_p2sh() {
+        match script_sig_pushes(&txin.script_sig).as_deref() {
+            Some([redeem]) if Script::from_bytes(redeem).is_p2wpkh() => "p2sh-p2wpkh",
+            Some([redeem]) if Script::from_bytes(redeem).is_p2wsh() => "p2sh-p2wsh",
+            _ => "p2sh",
+        }
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else {
+        "unknown"
+    }
+}
+
+pub fn script_sig_pushes(script_sig: &Script) -> Option<Vec<Vec<u8>>> {
+    script_sig
+        .instructions()
+        .map(|ins| match ins {
+            Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes().to_vec()),
+            Ok(Instruction::Op(op)) if op.to_u8() == 0x00 => Some(Vec::new()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn witness_without_annex(witness: &Witness) -> Vec<&[u8]> {
+    let mut items: Vec<&[u8]> = witness.iter().collect();
+    if items.len() >= 2 && items.last().and_then(|item| item.first()) == Some(&0x50) {
+        items.pop();
+    }
+    items
+}
+
+fn is_taproot_script_path(witness: &Witness) -> bool {
+    let items = witness_without_annex(witness);
+    items.len() >= 2 && ControlBlock::decode(items[items.len() - 1]).is_ok()
+        && bitcoin::PublicKey::from_slice(items[items.len() - 1]).is_err()
+}
+
+/// Lax DER counts too, so pre-BIP66 spends are still recognized.
+fn is_der_signature(item: &[u8]) -> bool {
+    ecdsa_signature(item).is_some()
+}
+
+fn is_parseable_script(item: &[u8]) -> bool {
+    Script::from_bytes(item).instructions().all(|ins| ins.is_ok())
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::block::WitnessCommitment;
+use bitcoin::hashes::Hash;
+use bitcoin::{merkle_tree, Block, TxMerkleNode, Txid, WitnessMerkleNode, Wtxid};
+use crate::decode::block::{commitment_output, parse_block_bytes, parse_block_hex};
+use crate::utils::bytes_to_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::metrics::timed;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WitnessCommitmentCheck {
+    pub valid: bool,
+    #[serde(alias = "witness_root")]
+    pub witness_root: String,
+    #[serde(alias = "reserved_value")]
+    pub reserved_value: Option<String>,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+    #[serde(alias = "output_index")]
+    pub output_index: Option<usize>,
+    pub reason: Option<String>,
+}
+
+/// Merkle root of txids given in display order (as explorers show them). Hashing uses
+/// the internal byte order. An odd level pairs its last hash with itself.
+#[wasm_bindgen]
+pub fn compute_merkle_root(txids_json: &str) -> Result<String, JsValue> {
+    timed("compute_merkle_root", txids_json.len(), || {
+        let txids: Vec<Txid> = parse_hashes(txids_json, "txids").map_err(|e| JsValue::from_str(&e))?;
+        let root: TxMerkleNode = merkle_tree::calculate_root(txids.into_iter().map(|txid| txid.to_raw_hash().into()))
+            .ok_or_else(|| JsValue::from_str("txids must not be empty"))?;
+        Ok(root.to_string())
+    })
+}
+
+/// Witness merkle root per BIP141. The first entry stands for the coinbase and counts as all
+/// zeros, whatever value was passed, so a block's wtxid list can be used as-is.
+#[wasm_bindgen]
+pub fn compute_witness_merkle_root(wtxids_json: &str) -> Result<String, JsValue> {
+    let wtxids: Vec<Wtxid> = parse_hashes(wtxids_json, "wtxids").map_err(|e| JsValue::from_str(&e))?;
+    witness_root(wtxids)
+        .map(|root| root.to_string())
+        .ok_or_else(|| JsValue::from_str("wtxids must not be empty"))
+}
+
+/// Recomputes the witness root from the block's transactions and compares
+/// SHA256d(root || reserved value) with the commitment in the coinbase.
+#[wasm_bindgen]
+pub fn verify_witness_commitment(block_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let block = parse_block_hex(block_hex).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&check_commitment(&block), compact)
+}
+
+/// `verify_witness_commitment` for a block in binary.
+#[wasm_bindgen]
+pub fn verify_witness_commitment_bytes(block: &[u8], compact: Option<bool>) -> Result<String, JsValue> {
+    let block = parse_block_bytes(block).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&check_commitment(&block), compact)
+}
+
+pub fn check_commitment(block: &Block) -> WitnessCommitmentCheck {
+    let root = witness_root(block.txdata.iter().map(|tx| tx.compute_wtxid())).unwrap_or(WitnessMerkleNode::all_zeros());
+    let mut check = WitnessCommitmentCheck {
+        valid: false,
+        witness_root: root.to_string(),
+        reserved_value: None,
+        expected: None,
+        found: None,
+        output_index: None,
+        reason: None,
+    };
+
+    let Some(coinbase) = block.txdata.first().filter(|tx| tx.is_coinbase()) else {
+        check.reason = Some("block has no coinbase transaction".to_string());
+        return check;
+    };
+    let has_witness = block.txdata.iter().any(|tx| tx.input.iter().any(|input| !input.witness.is_empty()));
+    let Some((index, found)) = commitment_output(coinbase) else {
+        check.valid = !has_witness;
+        check.reason = Some(if has_witness {
+            "coinbase has no witness commitment output, but the block has witness data".to_string()
+        } else {
+            "no witness commitment, and none is required because no transaction has witness data".to_string()
+        });
+        return check;
+    };
+    check.output_index = Some(index);
+    check.found = Some(bytes_to_hex(&found));
+
+    let reserved: Vec<&[u8]> = coinbase.input[0].witness.iter().collect();
+    let [reserved] = reserved.as_slice() else {
+        check.reason = Some("coinbase witness must be exactly one 32-byte reserved value".to_string());
+        return check;
+    };
+    if reserved.len() != 32 {
+        check.reason = Some("coinbase witness must be exactly one 32-byte reserved value".to_string());
+        return check;
+    }
+    check.reserved_value = Some(bytes_to_hex(reserved));
+
+    let expected: WitnessCommitment = Block::compute_witness_commitment(&root, reserved);
+    check.expected = Some(bytes_to_hex(expected.as_byte_array()));
+    check.valid = expected.to_byte_array() == found;
+    if !check.valid {
+        check.reason = Some("commitment does not match the transactions' witnesses".to_string());
+    }
+    check
+}
+
+pub fn witness_root(wtxids: impl IntoIterator<Item = Wtxid>) -> Option<WitnessMerkleNode> {
+    let hashes = wtxids
+        .into_iter()
+        .enumerate()
+        .map(|(i, wtxid)| if i == 0 { Wtxid::all_zeros() } else { wtxid }.to_raw_hash().into());
+    merkle_tree::calculate_root(hashes)
+}
+
+fn parse_hashes<T: FromStr>(json: &str, what: &str) -> Result<Vec<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    let hashes: Vec<String> = parse_json_list(json, what)?;
+    hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| {
+            if hash.trim().len() != 64 {
+                return Err(format!("{}[{}]: expected 64 hex characters", what, i));
+            }
+            T::from_str(hash.trim()).map_err(|e| format!("{}[{}]: {}", what, i, e))
+        })
+        .collect()
+}
@@ -0,0 +1,438 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::absolute::LockTime;
+use bitcoin::block::{Header, Version};
+use bitcoin::hashes::Hash;
+use bitcoin::script::{Builder, PushBytesBuf};
+use bitcoin::transaction::Version as TxVersion;
+use bitcoin::{merkle_tree, Amount, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxMerkleNode, TxOut, Witness};
+use crate::decode::coinbase::block_subsidy;
+use crate::decode::merkle::{check_commitment, witness_root};
+use crate::decode::pow::{parse_bits, target_be_bytes};
+use crate::transaction::serialization::checked_serialize;
+use crate::transaction::{address_script, decode_tx_hex};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, parse_json_value, to_json};
+use crate::utils::network::network_or_default;
+use crate::utils::step::{check_work_units, step_json, to_result, Progress, RawValue};
+
+/// The `OP_RETURN OP_PUSHBYTES_36 aa21a9ed` prefix of a BIP141 commitment output.
+const COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+const DEFAULT_EXTRA_NONCE_BYTES: usize = 8;
+/// Consensus bounds on a coinbase scriptSig (`bad-cb-length`).
+const MIN_COINBASE_SCRIPT_SIG: usize = 2;
+const MAX_COINBASE_SCRIPT_SIG: usize = 100;
+const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinbaseBuild {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    pub txid: String,
+    pub wtxid: String,
+    #[serde(alias = "script_sig")]
+    pub script_sig: String,
+    pub height: u32,
+    #[serde(alias = "extra_nonce")]
+    pub extra_nonce: String,
+    /// The commitment output's 32-byte commitment, when there is one.
+    #[serde(alias = "witness_commitment")]
+    pub witness_commitment: Option<String>,
+    /// The subsidy at `height`, so a reward above it can be told apart from fees.
+    pub subsidy: u64,
+}
+
+/// Header fields, under this library's names or `getblocktemplate`'s.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HeaderFields {
+    version: i32,
+    #[serde(alias = "prev_blockhash", alias = "previousblockhash")]
+    prev_blockhash: String,
+    #[serde(alias = "curtime")]
+    time: u32,
+    bits: String,
+    #[serde(default)]
+    nonce: u32,
+}
+
+/// A transaction as hex, or a `getblocktemplate` transaction object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TemplateTransaction {
+    Hex(String),
+    Template { data: String },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockAssembly {
+    #[serde(alias = "block_hex")]
+    pub block_hex: String,
+    pub hash: String,
+    #[serde(alias = "header_hex")]
+    pub header_hex: String,
+    #[serde(alias = "merkle_root")]
+    pub merkle_root: String,
+    #[serde(alias = "tx_count")]
+    pub tx_count: usize,
+    pub size: usize,
+    pub weight: u64,
+    /// `valid`, or `not_required` when the coinbase has no commitment and nothing has a witness.
+    #[serde(alias = "witness_commitment")]
+    pub witness_commitment: String,
+    /// Whether the header already meets its target; usually false until the nonce is ground.
+    #[serde(alias = "meets_target")]
+    pub meets_target: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonceSearchResult {
+    pub found: bool,
+    /// The winning nonce, or the last one tried.
+    pub nonce: u32,
+    pub hash: String,
+    #[serde(alias = "header_hex")]
+    pub header_hex: String,
+    /// The whole block with the nonce filled in, when the grinder was given one.
+    #[serde(alias = "block_hex")]
+    pub block_hex: Option<String>,
+    pub attempts: u64,
+}
+
+/// A coinbase transaction for a block at `height` paying `reward_sat` (subsidy plus
+/// fees, `getblocktemplate`'s `coinbasevalue`) to `payout_address`. The scriptSig is the
+/// BIP34 height push followed by `extra_nonce_hex` (8 zero bytes by default), the space
+/// miners vary once the header nonce runs out. `witness_commitment_hex` is the 32-byte
+/// commitment or the whole output script, as in `default_witness_commitment`; with it
+/// the coinbase gets the commitment output and the all-zero reserved value as witness.
+#[wasm_bindgen]
+pub fn build_coinbase(
+    height: u32,
+    reward_sat: u64,
+    payout_address: &str,
+    witness_commitment_hex: Option<String>,
+    extra_nonce_hex: Option<String>,
+    network: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    if reward_sat > Amount::MAX_MONEY.to_sat() {
+        return Err(JsValue::from_str("reward_sat is above the 21M BTC supply"));
+    }
+    let payout = address_script(payout_address, Some(&network))?;
+    let extra_nonce = match extra_nonce_hex {
+        Some(hex) => hex_to_bytes(&hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid extra nonce hex: {}", e)))?,
+        None => vec![0; DEFAULT_EXTRA_NONCE_BYTES],
+    };
+    let commitment = witness_commitment_hex.map(|hex| parse_commitment(&hex)).transpose().map_err(|e| JsValue::from_str(&e))?;
+
+    let mut script_sig = Builder::new().push_int(i64::from(height));
+    if !extra_nonce.is_empty() {
+        let push = PushBytesBuf::try_from(extra_nonce.clone()).map_err(|_| JsValue::from_str("The extra nonce is too long"))?;
+        script_sig = script_sig.push_slice(push);
+    }
+    let script_sig = script_sig.into_script();
+    if !(MIN_COINBASE_SCRIPT_SIG..=MAX_COINBASE_SCRIPT_SIG).contains(&script_sig.len()) {
+        return Err(JsValue::from_str(&format!(
+            "The coinbase scriptSig is {} bytes; consensus needs {} to {}, so adjust the extra nonce",
+            script_sig.len(),
+            MIN_COINBASE_SCRIPT_SIG,
+            MAX_COINBASE_SCRIPT_SIG
+        )));
+    }
+
+    let mut output = vec![TxOut { value: Amount::from_sat(reward_sat), script_pubkey: payout }];
+    let mut witness = Witness::new();
+    if let Some(commitment) = commitment {
+        let mut script = COMMITMENT_PREFIX.to_vec();
+        script.extend_from_slice(&commitment);
+        output.push(TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::from_bytes(script) });
+        witness.push([0u8; 32]);
+    }
+    let tx = Transaction {
+        version: TxVersion::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn { previous_output: OutPoint::null(), script_sig: script_sig.clone(), sequence: Sequence::MAX, witness }],
+        output,
+    };
+
+    let result = CoinbaseBuild {
+        tx_hex: bytes_to_hex(&checked_serialize(&tx).map_err(|e| JsValue::from_str(&e))?),
+        txid: tx.compute_txid().to_string(),
+        wtxid: tx.compute_wtxid().to_string(),
+        script_sig: bytes_to_hex(script_sig.as_bytes()),
+        height,
+        extra_nonce: bytes_to_hex(&extra_nonce),
+        witness_commitment: commitment.map(|commitment| bytes_to_hex(&commitment)),
+        subsidy: block_subsidy(u64::from(height), network.network),
+    };
+    to_json(&result, compact)
+}
+
+/// Puts a block together from header fields (`version`, `prev_blockhash`, `time`, `bits`
+/// and optionally `nonce`, or `getblocktemplate`'s `previousblockhash` and `curtime`),
+/// the coinbase and the other transactions in order, as hex or template objects with
+/// `data`. The merkle root is computed and the witness commitment checked, so a block
+/// that comes back only needs its nonce ground before `submitblock`.
+#[wasm_bindgen]
+pub fn assemble_block(header_fields_json: &str, coinbase_hex: &str, txs_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let fields: HeaderFields = parse_json_value(header_fields_json, "header fields").map_err(|e| JsValue::from_str(&e))?;
+    let prev_blockhash: BlockHash = fields
+        .prev_blockhash
+        .trim()
+        .parse()
+        .map_err(|e| JsValue::from_str(&format!("Invalid previous block hash: {}", e)))?;
+    let bits = parse_bits(&fields.bits).map_err(|e| JsValue::from_str(&e))?;
+    target_be_bytes(bits).map_err(|e| JsValue::from_str(&e))?;
+
+    let coinbase = decode_tx_hex(coinbase_hex)?;
+    if !coinbase.is_coinbase() {
+        return Err(JsValue::from_str("The first transaction must be a coinbase"));
+    }
+    let entries: Vec<TemplateTransaction> = parse_json_list(txs_json, "txs").map_err(|e| JsValue::from_str(&e))?;
+    let mut txdata = vec![coinbase];
+    let mut txids = HashSet::new();
+    txids.insert(txdata[0].compute_txid());
+    for (index, entry) in entries.into_iter().enumerate() {
+        let hex = match entry {
+            TemplateTransaction::Hex(hex) | TemplateTransaction::Template { data: hex } => hex,
+        };
+        let tx = decode_tx_hex(&hex).map_err(|e| JsValue::from_str(&format!("txs[{}]: {}", index, e.as_string().unwrap_or_default())))?;
+        if tx.is_coinbase() {
+            return Err(JsValue::from_str(&format!("txs[{}] is a coinbase; only the first transaction may be", index)));
+        }
+        if !txids.insert(tx.compute_txid()) {
+            return Err(JsValue::from_str(&format!("txs[{}] ({}) is in the block twice", index, tx.compute_txid())));
+        }
+        txdata.push(tx);
+    }
+
+    let merkle_root: TxMerkleNode = merkle_tree::calculate_root(txdata.iter().map(|tx| tx.compute_txid().to_raw_hash().into()))
+        .expect("the block has a coinbase");
+    let header = Header {
+        version: Version::from_consensus(fields.version),
+        prev_blockhash,
+        merkle_root,
+        time: fields.time,
+        bits: CompactTarget::from_consensus(bits),
+        nonce: fields.nonce,
+    };
+    let block = Block { header, txdata };
+
+    let weight = block.weight().to_wu();
+    if weight > MAX_BLOCK_WEIGHT {
+        return Err(JsValue::from_str(&format!("The block weighs {} WU, above the {} WU limit", weight, MAX_BLOCK_WEIGHT)));
+    }
+    let check = check_commitment(&block);
+    if !check.valid {
+        let root = witness_root(block.txdata.iter().map(|tx| tx.compute_wtxid())).expect("the block has a coinbase");
+        let expected = Block::compute_witness_commitment(&root, &[0u8; 32]);
+        return Err(JsValue::from_str(&format!(
+            "Witness commitment problem: {}. Build the coinbase with witness_commitment_hex {}",
+            check.reason.unwrap_or_default(),
+            bytes_to_hex(expected.as_byte_array())
+        )));
+    }
+
+    let result = BlockAssembly {
+        block_hex: bytes_to_hex(&bitcoin::consensus::serialize(&block)),
+        hash: block.block_hash().to_string(),
+        header_hex: bytes_to_hex(&bitcoin::consensus::serialize(&block.header)),
+        merkle_root: merkle_root.to_string(),
+        tx_count: block.txdata.len(),
+        size: block.total_size(),
+        weight,
+        witness_commitment: if check.output_index.is_some() { "valid" } else { "not_required" }.to_string(),
+        meets_target: meets_target(&block.header.block_hash(), bits),
+    };
+    to_json(&result, compact)
+}
+
+/// Tries header nonces for one that meets the header's target, starting at the nonce it
+/// has. Each `step(max_work_units)` hashes up to that many headers; the job ends when a
+/// nonce works or `max_iterations` have been tried, in which case change the time or
+/// the coinbase extra nonce and start again. Regtest's target is met by about every
+/// other hash. `header_hex` may also be a whole block from `assemble_block`, which is
+/// then returned with the nonce filled in, ready for `submitblock`.
+#[wasm_bindgen]
+pub struct NonceGrinder {
+    header: Header,
+    /// The serialized block after its header, when a whole block was given.
+    body: Option<Vec<u8>>,
+    target: [u8; 32],
+    max_iterations: u32,
+    attempts: u64,
+    result: Option<Box<RawValue>>,
+}
+
+#[wasm_bindgen]
+impl NonceGrinder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(header_hex: &str, max_iterations: u32) -> Result<NonceGrinder, JsValue> {
+        if max_iterations == 0 {
+            return Err(JsValue::from_str("max_iterations must be at least 1"));
+        }
+        let bytes = hex_to_bytes(header_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid header hex: {}", e)))?;
+        if bytes.len() < Header::SIZE {
+            return Err(JsValue::from_str(&format!("A block header is {} bytes, got {}", Header::SIZE, bytes.len())));
+        }
+        let header: Header = bitcoin::consensus::deserialize(&bytes[..Header::SIZE])
+            .map_err(|e| JsValue::from_str(&format!("Failed to deserialize header: {}", e)))?;
+        let target = target_be_bytes(header.bits.to_consensus()).map_err(|e| JsValue::from_str(&e))?;
+        let body = (bytes.len() > Header::SIZE).then(|| bytes[Header::SIZE..].to_vec());
+
+        Ok(NonceGrinder { header, body, target, max_iterations, attempts: 0, result: None })
+    }
+
+    pub fn step(&mut self, max_work_units: u32, compact: Option<bool>) -> Result<String, JsValue> {
+        let units = check_work_units(max_work_units)?;
+        for _ in 0..units {
+            if self.result.is_some() {
+                break;
+            }
+            let hash = self.header.block_hash();
+            self.attempts += 1;
+            let found = hash_meets(&hash, &self.target);
+            if found || self.attempts == u64::from(self.max_iterations) {
+                self.result = Some(to_result(&self.finish(found, hash))?);
+            } else {
+                self.header.nonce = self.header.nonce.wrapping_add(1);
+            }
+        }
+        step_json(Progress { completed: self.attempts, total: Some(u64::from(self.max_iterations)), expected: None }, self.result.as_deref(), compact)
+    }
+}
+
+impl NonceGrinder {
+    fn finish(&self, found: bool, hash: BlockHash) -> NonceSearchResult {
+        let header = bitcoin::consensus::serialize(&self.header);
+        NonceSearchResult {
+            found,
+            nonce: self.header.nonce,
+            hash: hash.to_string(),
+            header_hex: bytes_to_hex(&header),
+            block_hex: self.body.as_ref().map(|body| bytes_to_hex(&[header.as_slice(), body].concat())),
+            attempts: self.attempts,
+        }
+    }
+}
+
+/// A 32-byte commitment, or a whole commitment output script.
+fn parse_commitment(hex: &str) -> Result<[u8; 32], String> {
+    let bytes = hex_to_bytes(hex, HexMode::Lenient).map_err(|e| format!("Invalid witness commitment hex: {}", e))?;
+    let commitment = match bytes.len() {
+        32 => &bytes[..],
+        38 if bytes[..6] == COMMITMENT_PREFIX => &bytes[6..],
+        _ => return Err("The witness commitment must be 32 bytes, or a 6a24aa21a9ed output script".to_string()),
+    };
+    Ok(commitment.try_into().expect("32 bytes"))
+}
+
+fn meets_target(hash: &BlockHash, bits: u32) -> bool {
+    target_be_bytes(bits).is_ok_and(|target| hash_meets(hash, &target))
+}
+
+/// Block hashes are compared as little-endian numbers.
+fn hash_meets(hash: &BlockHash, target: &[u8; 32]) -> bool {
+    let mut hash_be = hash.to_byte_array();
+    hash_be.reverse();
+    hash_be <= *target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+    use bitcoin::{Address, CompressedPublicKey, Network, Txid, Wtxid};
+    use crate::decode::merkle::witness_root;
+    use crate::decode::pow::{check_header_meets_target, HeaderWorkCheck};
+    use crate::script::interpreter::verify_input;
+
+    const REGTEST_GENESIS: &str = "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206";
+    const FEE: u64 = 1_000;
+
+    /// A signed P2WPKH spend, so the block needs a witness commitment, with the output it spends.
+    fn signed_spend() -> (Transaction, TxOut) {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[6; 32]).unwrap();
+        let public = CompressedPublicKey(secret.public_key(&secp));
+        let prevout = TxOut { value: Amount::from_sat(100_000), script_pubkey: ScriptBuf::new_p2wpkh(&public.wpubkey_hash()) };
+        let mut tx = Transaction {
+            version: TxVersion::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::from_byte_array([3; 32]), 0), ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(100_000 - FEE), script_pubkey: prevout.script_pubkey.clone() }],
+        };
+        let sighash = SighashCache::new(&tx)
+            .p2wpkh_signature_hash(0, &prevout.script_pubkey, prevout.value, EcdsaSighashType::All)
+            .unwrap();
+        let signature = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa(&Message::from(sighash), &secret));
+        tx.input[0].witness = Witness::p2wpkh(&signature, &public.0);
+        (tx, prevout)
+    }
+
+    fn step_result(json: &str) -> Option<serde_json::Value> {
+        let step: serde_json::Value = serde_json::from_str(json).unwrap();
+        step.get("result").filter(|result| !result.is_null()).cloned()
+    }
+
+    #[test]
+    fn mines_a_regtest_block_with_a_signed_spend() {
+        let (spend, prevout) = signed_spend();
+        if let Err(failure) = verify_input(&spend, 0, std::slice::from_ref(&prevout)) {
+            panic!("the spend fails in {}: {}", failure.phase, failure.reason);
+        }
+
+        // The coinbase's wtxid counts as zero, so the commitment can be made before the coinbase.
+        let root = witness_root([Wtxid::all_zeros(), spend.compute_wtxid()]).unwrap();
+        let commitment = Block::compute_witness_commitment(&root, &[0u8; 32]);
+        let payout = Address::p2wpkh(&CompressedPublicKey(SecretKey::from_slice(&[7; 32]).unwrap().public_key(&Secp256k1::new())), Network::Regtest);
+        let coinbase: CoinbaseBuild = serde_json::from_str(
+            &build_coinbase(
+                1,
+                block_subsidy(1, Network::Regtest) + FEE,
+                &payout.to_string(),
+                Some(bytes_to_hex(commitment.as_byte_array())),
+                None,
+                Some("regtest".to_string()),
+                None,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let fields = serde_json::json!({"version": 0x2000_0000, "previousblockhash": REGTEST_GENESIS, "curtime": 1_700_000_000, "bits": "207fffff"});
+        let txs = serde_json::json!([{"data": bitcoin::consensus::encode::serialize_hex(&spend)}]);
+        let assembly: BlockAssembly =
+            serde_json::from_str(&assemble_block(&fields.to_string(), &coinbase.tx_hex, &txs.to_string(), None).unwrap()).unwrap();
+        assert_eq!(assembly.tx_count, 2);
+        assert_eq!(assembly.witness_commitment, "valid");
+
+        let mut grinder = NonceGrinder::new(&assembly.block_hex, 1_000).unwrap();
+        let result = loop {
+            if let Some(result) = step_result(&grinder.step(16, None).unwrap()) {
+                break serde_json::from_value::<NonceSearchResult>(result).unwrap();
+            }
+        };
+        assert!(result.found, "no nonce in {} attempts", result.attempts);
+
+        // Everything submitblock would check without the chain: proof of work, merkle root,
+        // witness commitment, BIP34 height and the coinbase's value, then the spend again.
+        let check: HeaderWorkCheck = serde_json::from_str(&check_header_meets_target(&result.header_hex, None).unwrap()).unwrap();
+        assert!(check.valid, "{:?}", check.reason);
+        let block: Block = bitcoin::consensus::encode::deserialize_hex(&result.block_hex.unwrap()).unwrap();
+        assert_eq!(block.block_hash().to_string(), result.hash);
+        assert!(block.check_merkle_root());
+        assert!(block.check_witness_commitment());
+        // Core compares the scriptSig's start with `CScript() << nHeight`, which is OP_1 at height 1.
+        assert_eq!(block.txdata[0].input[0].script_sig.as_bytes()[0], 0x51);
+        let coinbase_value: u64 = block.txdata[0].output.iter().map(|output| output.value.to_sat()).sum();
+        assert_eq!(coinbase_value, block_subsidy(1, Network::Regtest) + FEE);
+        assert!(verify_input(&block.txdata[1], 0, &[prevout]).is_ok());
+    }
+}
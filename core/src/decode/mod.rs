@@ -0,0 +1,25 @@
+pub mod witness;
+pub mod inputs;
+pub mod transaction;
+pub mod weight;
+pub mod explain;
+pub mod block;
+pub mod coinbase;
+pub mod merkle;
+pub mod mining;
+pub mod pow;
+pub mod scan;
+pub mod stats;
+
+pub use witness::decode_witness;
+pub use inputs::classify_inputs;
+pub use transaction::decode_transaction;
+pub use weight::weight_breakdown;
+pub use explain::{explain_transaction, explain_transaction_with_provider};
+pub use block::{decode_block, decode_block_bytes, BlockDecoder};
+pub use coinbase::{is_coinbase, decode_coinbase};
+pub use merkle::{compute_merkle_root, compute_witness_merkle_root, verify_witness_commitment, verify_witness_commitment_bytes};
+pub use mining::{build_coinbase, assemble_block, NonceGrinder};
+pub use pow::{bits_to_target, target_to_difficulty, check_header_meets_target};
+pub use scan::scan_transactions;
+pub use stats::{block_script_stats, block_script_stats_bytes};
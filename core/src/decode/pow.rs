@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::block::Header;
+use bitcoin::hashes::Hash;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+/// Difficulty 1 is the target of compact bits 0x1d00ffff, the genesis block's.
+const DIFFICULTY_ONE_BITS: u32 = 0x1d00ffff;
+const DIFFICULTY_DECIMALS: u32 = 8;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderWorkCheck {
+    pub valid: bool,
+    pub hash: String,
+    pub bits: String,
+    pub target: Option<String>,
+    pub difficulty: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Expands compact bits into the 256-bit target (big-endian hex). Consensus (`SetCompact`)
+/// rejects encodings with the sign bit set or a mantissa shifted past 256 bits, so they
+/// are errors here too.
+#[wasm_bindgen]
+pub fn bits_to_target(bits_hex: &str) -> Result<String, JsValue> {
+    let bits = parse_bits(bits_hex).map_err(|e| JsValue::from_str(&e))?;
+    let target = compact_to_target(bits).map_err(|e| JsValue::from_str(&e))?;
+    Ok(bytes_to_hex(&target.to_be_bytes()))
+}
+
+/// Difficulty (difficulty-1 target ÷ target) as a decimal string. From 1 upwards it's exact
+/// to eight decimal places, with no f64 rounding. Below 1 it has eight significant digits.
+#[wasm_bindgen]
+pub fn target_to_difficulty(target_hex: &str) -> Result<String, JsValue> {
+    let target = parse_target(target_hex).map_err(|e| JsValue::from_str(&e))?;
+    difficulty(&target).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Checks an 80-byte header's proof of work the way `CheckProofOfWork` does: the bits
+/// must decode to a positive target that doesn't overflow, and the header hash, read
+/// as a little-endian number, must not exceed it. Each network's proof-of-work limit
+/// isn't checked.
+#[wasm_bindgen]
+pub fn check_header_meets_target(header_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(header_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid header hex: {}", e)))?;
+    if bytes.len() != 80 {
+        return Err(JsValue::from_str(&format!("Block header must be 80 bytes, got {}", bytes.len())));
+    }
+    let header: Header = bitcoin::consensus::deserialize(&bytes)
+        .map_err(|e| JsValue::from_str(&format!("Failed to deserialize header: {}", e)))?;
+
+    let hash = header.block_hash();
+    let bits = header.bits.to_consensus();
+    let mut check = HeaderWorkCheck {
+        valid: false,
+        hash: hash.to_string(),
+        bits: format!("{:08x}", bits),
+        target: None,
+        difficulty: None,
+        reason: None,
+    };
+
+    match compact_to_target(bits) {
+        Ok(target) if target.is_zero() => check.reason = Some("bits encode a zero target".to_string()),
+        Ok(target) => {
+            let mut hash_be = hash.to_byte_array();
+            hash_be.reverse();
+            check.valid = U256::from_be_bytes(hash_be) <= target;
+            if !check.valid {
+                check.reason = Some("header hash is above the target".to_string());
+            }
+            check.target = Some(bytes_to_hex(&target.to_be_bytes()));
+            check.difficulty = difficulty(&target).ok();
+        }
+        Err(e) => check.reason = Some(e),
+    }
+
+    to_json(&check, compact)
+}
+
+pub fn parse_bits(bits_hex: &str) -> Result<u32, String> {
+    let hex = bits_hex.trim().trim_start_matches("0x");
+    if hex.is_empty() || hex.len() > 8 {
+        return Err("bits must be 1 to 8 hex digits, e.g. 1d00ffff".to_string());
+    }
+    u32::from_str_radix(hex, 16).map_err(|e| format!("Invalid bits hex: {}", e))
+}
+
+fn parse_target(target_hex: &str) -> Result<U256, String> {
+    let hex = target_hex.trim().trim_start_matches("0x");
+    if hex.is_empty() || hex.len() > 64 {
+        return Err("target must be 1 to 64 hex digits".to_string());
+    }
+    let padded = format!("{:0>64}", hex);
+    let bytes = hex_to_bytes(&padded, HexMode::Strict).map_err(|e| format!("Invalid target hex: {}", e))?;
+    Ok(U256::from_be_bytes(bytes.try_into().expect("64 hex digits")))
+}
+
+/// The target of `bits` as big-endian bytes, which compare the same way the numbers do.
+/// A zero target is refused, since no hash could meet it.
+pub fn target_be_bytes(bits: u32) -> Result<[u8; 32], String> {
+    let target = compact_to_target(bits)?;
+    if target.is_zero() {
+        return Err("bits encode a zero target".to_string());
+    }
+    Ok(target.to_be_bytes())
+}
+
+/// Bitcoin Core's `arith_uint256::SetCompact`, with the negative and overflow cases as errors.
+fn compact_to_target(bits: u32) -> Result<U256, String> {
+    let size = bits >> 24;
+    let mut word = bits & 0x007f_ffff;
+    if word != 0 && bits & 0x0080_0000 != 0 {
+        return Err(format!("bits {:08x} encode a negative target", bits));
+    }
+    if word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32)) {
+        return Err(format!("bits {:08x} overflow 256 bits", bits));
+    }
+
+    let mut target = [0u8; 32];
+    if size <= 3 {
+        word >>= 8 * (3 - size);
+        target[29..].copy_from_slice(&word.to_be_bytes()[1..]);
+    } else {
+        // The mantissa's top bytes may fall off the top when size is 33 or 34; the overflow check guarantees they're zero.
+        for (i, byte) in word.to_be_bytes()[1..].iter().enumerate() {
+            let position = 32 + i as i64 - size as i64;
+            if (0..32).contains(&position) {
+                target[position as usize] = *byte;
+            }
+        }
+    }
+    Ok(U256::from_be_bytes(target))
+}
+
+fn difficulty(target: &U256) -> Result<String, String> {
+    if target.is_zero() {
+        return Err("Difficulty is undefined for a zero target".to_string());
+    }
+    let one = compact_to_target(DIFFICULTY_ONE_BITS).expect("difficulty-1 bits are valid");
+    // diff1 is below 2^224 and 10^8 below 2^27, so the scaled numerator fits in 256 bits.
+    let scaled = one.mul_small(10u64.pow(DIFFICULTY_DECIMALS)).div(target);
+    let digits = scaled.to_decimal();
+    let digits = format!("{:0>width$}", digits, width = DIFFICULTY_DECIMALS as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - DIFFICULTY_DECIMALS as usize);
+    if whole == "0" {
+        // Below 1 (e.g. regtest) the exact digits start too late, so give eight significant digits instead.
+        let value = one.to_f64() / target.to_f64();
+        let decimals = DIFFICULTY_DECIMALS as usize + (-value.log10()).ceil().max(0.0) as usize;
+        let text = format!("{:.*}", decimals, value);
+        return Ok(text.trim_end_matches('0').trim_end_matches('.').to_string());
+    }
+    let fraction = fraction.trim_end_matches('0');
+    Ok(if fraction.is_empty() { whole.to_string() } else { format!("{}.{}", whole, fraction) })
+}
+
+/// Just enough unsigned 256-bit arithmetic for targets, as little-endian u64 limbs.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct U256([u64; 4]);
+
+impl U256 {
+    fn from_be_bytes(bytes: [u8; 32]) -> U256 {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = 32 - 8 * (i + 1);
+            *limb = u64::from_be_bytes(bytes[start..start + 8].try_into().expect("8 bytes"));
+        }
+        U256(limbs)
+    }
+
+    fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let start = 32 - 8 * (i + 1);
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&limb| limb == 0)
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.0[index / 64] >> (index % 64) & 1 == 1
+    }
+
+    fn shl1(self) -> U256 {
+        let mut limbs = [0u64; 4];
+        let mut carry = 0;
+        for (i, &limb) in self.0.iter().enumerate() {
+            limbs[i] = limb << 1 | carry;
+            carry = limb >> 63;
+        }
+        U256(limbs)
+    }
+
+    fn sub(self, other: &U256) -> U256 {
+        let mut limbs = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let (value, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (value, b2) = value.overflowing_sub(borrow as u64);
+            *limb = value;
+            borrow = b1 || b2;
+        }
+        U256(limbs)
+    }
+
+    fn mul_small(self, factor: u64) -> U256 {
+        let mut limbs = [0u64; 4];
+        let mut carry = 0u128;
+        for (i, &limb) in self.0.iter().enumerate() {
+            let product = limb as u128 * factor as u128 + carry;
+            limbs[i] = product as u64;
+            carry = product >> 64;
+        }
+        U256(limbs)
+    }
+
+    /// Binary long division; `divisor` must be non-zero.
+    fn div(self, divisor: &U256) -> U256 {
+        let mut quotient = U256([0; 4]);
+        let mut remainder = U256([0; 4]);
+        for index in (0..256).rev() {
+            // A carry out of the top bit means the remainder exceeds any 256-bit divisor.
+            let carry = remainder.bit(255);
+            remainder = remainder.shl1();
+            remainder.0[0] |= self.bit(index) as u64;
+            quotient = quotient.shl1();
+            if carry || remainder >= *divisor {
+                remainder = remainder.sub(divisor);
+                quotient.0[0] |= 1;
+            }
+        }
+        quotient
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0.iter().rev().fold(0.0, |value, &limb| value * 18_446_744_073_709_551_616.0 + limb as f64)
+    }
+
+    fn to_decimal(self) -> String {
+        let mut digits = Vec::new();
+        let mut value = self;
+        while !value.is_zero() {
+            let mut remainder = 0u128;
+            for limb in value.0.iter_mut().rev() {
+                let current = remainder << 64 | *limb as u128;
+                *limb = (current / 10) as u64;
+                remainder = current % 10;
+            }
+            digits.push(b'0' + remainder as u8);
+        }
+        if digits.is_empty() {
+            return "0".to_string();
+        }
+        digits.reverse();
+        String::from_utf8(digits).expect("ASCII digits")
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &U256) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &U256) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    #[test]
+    fn genesis_bits_decode_to_difficulty_one() {
+        let target = compact_to_target(0x1d00ffff).unwrap();
+        assert_eq!(bytes_to_hex(&target.to_be_bytes()), format!("00000000ffff{}", "0".repeat(52)));
+        assert_eq!(difficulty(&target).unwrap(), "1");
+
+        let check: HeaderWorkCheck = serde_json::from_str(&check_header_meets_target(GENESIS_HEADER, None).unwrap()).unwrap();
+        assert!(check.valid, "{:?}", check.reason);
+        assert_eq!(check.hash, "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+        assert_eq!(check.bits, "1d00ffff");
+    }
+
+    #[test]
+    fn sign_bit_is_a_negative_target() {
+        assert_eq!(compact_to_target(0x04923456).err().unwrap(), "bits 04923456 encode a negative target");
+        // With a zero mantissa the sign bit is ignored, as in SetCompact.
+        assert!(compact_to_target(0x04800000).unwrap().is_zero());
+    }
+
+    #[test]
+    fn oversized_exponent_overflows() {
+        assert_eq!(compact_to_target(0xff123456).err().unwrap(), "bits ff123456 overflow 256 bits");
+        // 0x22 leaves room for a one-byte mantissa but not a two-byte one.
+        assert!(compact_to_target(0x22000012).is_ok());
+        assert!(compact_to_target(0x22001234).is_err());
+    }
+
+    #[test]
+    fn header_with_bad_bits_is_invalid_not_an_error() {
+        let header = GENESIS_HEADER.replace("ffff001d", "56349204");
+        let check: HeaderWorkCheck = serde_json::from_str(&check_header_meets_target(&header, None).unwrap()).unwrap();
+        assert!(!check.valid);
+        assert_eq!(check.reason.as_deref(), Some("bits 04923456 encode a negative target"));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{OutPoint, ScriptBuf, Transaction};
+use crate::decode::block::parse_block_hex;
+use crate::transaction::{parse_outpoint, parse_tx_hex};
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::metrics::timed;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedOutpoint {
+    pub txid: String,
+    pub vout: u32,
+    /// Lets spends of this coin be reported against its script.
+    #[serde(default, alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanResult {
+    #[serde(alias = "transactions_scanned")]
+    pub transactions_scanned: usize,
+    /// Watched scripts that were paid, in watch-list order.
+    pub scripts: Vec<ScriptActivity>,
+    /// Inputs spending a watched outpoint or an output received earlier in the batch.
+    pub spends: Vec<WatchedSpend>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptActivity {
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    pub received: Vec<ReceivedOutput>,
+    #[serde(alias = "received_total")]
+    pub received_total: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReceivedOutput {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount")]
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedSpend {
+    pub txid: String,
+    #[serde(alias = "input_index")]
+    pub input_index: usize,
+    #[serde(alias = "spent_txid")]
+    pub spent_txid: String,
+    #[serde(alias = "spent_vout")]
+    pub spent_vout: u32,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
+}
+
+/// Finds every output paying a watched script and every input spending a watched
+/// outpoint in a block (hex) or a JSON array of raw transactions. Each output and input
+/// costs one hash lookup, so watch lists in the tens of thousands scan as fast as short
+/// ones. Transactions are scanned in order, so a coin received and spent within the
+/// same batch shows up in both lists.
+#[wasm_bindgen]
+pub fn scan_transactions(
+    txs_json_or_block_hex: &str,
+    watched_scripts_json: &str,
+    watched_outpoints_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    timed("scan_transactions", txs_json_or_block_hex.len() + watched_scripts_json.len(), || {
+        let txs = parse_batch(txs_json_or_block_hex).map_err(|e| JsValue::from_str(&e))?;
+        let scripts: Vec<String> = parse_json_list(watched_scripts_json, "watched scripts").map_err(|e| JsValue::from_str(&e))?;
+
+        let mut watched: HashMap<ScriptBuf, usize> = HashMap::with_capacity(scripts.len());
+        let mut activity = Vec::with_capacity(scripts.len());
+        for (index, script) in scripts.iter().enumerate() {
+            let bytes = hex_to_bytes(script, HexMode::Lenient)
+                .map_err(|e| JsValue::from_str(&format!("watched scripts[{}]: invalid script hex: {}", index, e)))?;
+            let script = ScriptBuf::from_bytes(bytes);
+            if !watched.contains_key(&script) {
+                watched.insert(script.clone(), activity.len());
+                activity.push(ScriptActivity { script_pubkey: script.to_hex_string(), received: Vec::new(), received_total: 0 });
+            }
+        }
+
+        // The script each watched coin pays, when known.
+        let mut outpoints: HashMap<OutPoint, Option<String>> = HashMap::new();
+        if let Some(json) = watched_outpoints_json.filter(|json| !json.trim().is_empty()) {
+            let list: Vec<WatchedOutpoint> = parse_json_list(&json, "watched outpoints").map_err(|e| JsValue::from_str(&e))?;
+            for entry in list {
+                outpoints.insert(parse_outpoint(&entry.txid, entry.vout)?, entry.script_pubkey.map(|script| script.trim().to_ascii_lowercase()));
+            }
+        }
+
+        let mut spends = Vec::new();
+        for tx in &txs {
+            let txid = tx.compute_txid();
+            if !outpoints.is_empty() && !tx.is_coinbase() {
+                for (input_index, input) in tx.input.iter().enumerate() {
+                    if let Some(script_pubkey) = outpoints.get(&input.previous_output) {
+                        spends.push(WatchedSpend {
+                            txid: txid.to_string(),
+                            input_index,
+                            spent_txid: input.previous_output.txid.to_string(),
+                            spent_vout: input.previous_output.vout,
+                            script_pubkey: script_pubkey.clone(),
+                        });
+                    }
+                }
+            }
+            for (vout, output) in tx.output.iter().enumerate() {
+                let Some(&group) = watched.get(&output.script_pubkey) else { continue };
+                let entry = &mut activity[group];
+                entry.received.push(ReceivedOutput { txid: txid.to_string(), vout: vout as u32, amount: output.value.to_sat() });
+                entry.received_total += output.value.to_sat();
+                outpoints.insert(OutPoint { txid, vout: vout as u32 }, Some(entry.script_pubkey.clone()));
+            }
+        }
+
+        let result = ScanResult {
+            transactions_scanned: txs.len(),
+            scripts: activity.into_iter().filter(|entry| !entry.received.is_empty()).collect(),
+            spends,
+        };
+        to_json(&result, compact)
+    })
+}
+
+/// A JSON array of raw transaction hex, or a serialized block.
+fn parse_batch(input: &str) -> Result<Vec<Transaction>, String> {
+    let input = input.trim();
+    if !input.starts_with('[') {
+        return parse_block_hex(input).map(|block| block.txdata);
+    }
+    let txs: Vec<String> = parse_json_list(input, "transactions")?;
+    txs.iter()
+        .enumerate()
+        .map(|(index, tx_hex)| parse_tx_hex(tx_hex).map_err(|e| format!("transactions[{}]: {}", index, e)))
+        .collect()
+}
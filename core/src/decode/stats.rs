@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::Block;
+use crate::decode::block::{parse_block_bytes, parse_block_hex};
+use crate::decode::inputs::spend_type_from_shape;
+use crate::script::output_script_type;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::to_json_with_amounts;
+
+/// Output counts and values by script type, and input counts by spend type. Inputs are
+/// classified from their scriptSig and witness alone (`classify_inputs` without prevouts),
+/// so a spend whose shape is ambiguous counts as `unknownInputs`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockScriptStats {
+    #[serde(alias = "tx_count")]
+    pub tx_count: u64,
+    #[serde(alias = "output_count")]
+    pub output_count: u64,
+    #[serde(with = "crate::utils::amount", alias = "output_value")]
+    pub output_value: u64,
+    #[serde(alias = "p2pkh_outputs")]
+    pub p2pkh_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "p2pkh_value")]
+    pub p2pkh_value: u64,
+    #[serde(alias = "p2sh_outputs")]
+    pub p2sh_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "p2sh_value")]
+    pub p2sh_value: u64,
+    #[serde(alias = "p2wpkh_outputs")]
+    pub p2wpkh_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "p2wpkh_value")]
+    pub p2wpkh_value: u64,
+    #[serde(alias = "p2wsh_outputs")]
+    pub p2wsh_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "p2wsh_value")]
+    pub p2wsh_value: u64,
+    #[serde(alias = "p2tr_outputs")]
+    pub p2tr_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "p2tr_value")]
+    pub p2tr_value: u64,
+    #[serde(alias = "p2pk_outputs")]
+    pub p2pk_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "p2pk_value")]
+    pub p2pk_value: u64,
+    #[serde(alias = "op_return_outputs")]
+    pub op_return_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "op_return_value")]
+    pub op_return_value: u64,
+    #[serde(alias = "nonstandard_outputs")]
+    pub nonstandard_outputs: u64,
+    #[serde(with = "crate::utils::amount", alias = "nonstandard_value")]
+    pub nonstandard_value: u64,
+    #[serde(alias = "input_count")]
+    pub input_count: u64,
+    #[serde(alias = "coinbase_inputs")]
+    pub coinbase_inputs: u64,
+    #[serde(alias = "p2pkh_inputs")]
+    pub p2pkh_inputs: u64,
+    #[serde(alias = "p2pk_inputs")]
+    pub p2pk_inputs: u64,
+    #[serde(alias = "p2sh_inputs")]
+    pub p2sh_inputs: u64,
+    #[serde(alias = "p2sh_p2wpkh_inputs")]
+    pub p2sh_p2wpkh_inputs: u64,
+    #[serde(alias = "p2sh_p2wsh_inputs")]
+    pub p2sh_p2wsh_inputs: u64,
+    #[serde(alias = "p2wpkh_inputs")]
+    pub p2wpkh_inputs: u64,
+    #[serde(alias = "p2wsh_inputs")]
+    pub p2wsh_inputs: u64,
+    #[serde(alias = "p2tr_keypath_inputs")]
+    pub p2tr_keypath_inputs: u64,
+    #[serde(alias = "p2tr_scriptpath_inputs")]
+    pub p2tr_scriptpath_inputs: u64,
+    #[serde(alias = "unknown_inputs")]
+    pub unknown_inputs: u64,
+}
+
+/// Tallies a block's outputs and inputs by script type in one pass, for charts such as
+/// taproot adoption over time.
+#[wasm_bindgen]
+pub fn block_script_stats(block_hex: &str, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let block = parse_block_hex(block_hex).map_err(|e| JsValue::from_str(&e))?;
+    script_stats_json(&block, amount_format, compact)
+}
+
+/// `block_script_stats` for a block in binary.
+#[wasm_bindgen]
+pub fn block_script_stats_bytes(block: &[u8], amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let block = parse_block_bytes(block).map_err(|e| JsValue::from_str(&e))?;
+    script_stats_json(&block, amount_format, compact)
+}
+
+fn script_stats_json(block: &Block, amount_format: AmountFormat, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut stats = BlockScriptStats { tx_count: block.txdata.len() as u64, ..Default::default() };
+    for tx in &block.txdata {
+        for output in &tx.output {
+            let value = output.value.to_sat();
+            stats.output_count += 1;
+            stats.output_value += value;
+            let (count, total) = match output_script_type(&output.script_pubkey) {
+                "p2pkh" => (&mut stats.p2pkh_outputs, &mut stats.p2pkh_value),
+                "p2sh" => (&mut stats.p2sh_outputs, &mut stats.p2sh_value),
+                "p2wpkh" => (&mut stats.p2wpkh_outputs, &mut stats.p2wpkh_value),
+                "p2wsh" => (&mut stats.p2wsh_outputs, &mut stats.p2wsh_value),
+                "p2tr" => (&mut stats.p2tr_outputs, &mut stats.p2tr_value),
+                "p2pk" => (&mut stats.p2pk_outputs, &mut stats.p2pk_value),
+                "op_return" => (&mut stats.op_return_outputs, &mut stats.op_return_value),
+                _ => (&mut stats.nonstandard_outputs, &mut stats.nonstandard_value),
+            };
+            *count += 1;
+            *total += value;
+        }
+
+        for input in &tx.input {
+            stats.input_count += 1;
+            *match spend_type_from_shape(input) {
+                "coinbase" => &mut stats.coinbase_inputs,
+                "p2pkh" => &mut stats.p2pkh_inputs,
+                "p2pk" => &mut stats.p2pk_inputs,
+                "p2sh" => &mut stats.p2sh_inputs,
+                "p2sh-p2wpkh" => &mut stats.p2sh_p2wpkh_inputs,
+                "p2sh-p2wsh" => &mut stats.p2sh_p2wsh_inputs,
+                "p2wpkh" => &mut stats.p2wpkh_inputs,
+                "p2wsh" => &mut stats.p2wsh_inputs,
+                "p2tr-keypath" => &mut stats.p2tr_keypath_inputs,
+                "p2tr-scriptpath" => &mut stats.p2tr_scriptpath_inputs,
+                _ => &mut stats.unknown_inputs,
+            } += 1;
+        }
+    }
+
+    to_json_with_amounts(&stats, amount_format, compact)
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::transaction::decode_tx_hex;
+use crate::transaction::locktime::looks_like_anti_fee_sniping;
+use crate::transaction::version::describe_version;
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionSummary {
+    pub txid: String,
+    pub wtxid: String,
+    pub version: i32,
+    #[serde(alias = "version_description")]
+    pub version_description: String,
+    #[serde(alias = "lock_time")]
+    pub lock_time: u32,
+    #[serde(alias = "anti_fee_sniping")]
+    pub anti_fee_sniping: bool,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: u64,
+    pub segwit: bool,
+    #[serde(alias = "input_count")]
+    pub input_count: usize,
+    #[serde(alias = "output_count")]
+    pub output_count: usize,
+}
+
+#[wasm_bindgen]
+pub fn decode_transaction(tx_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("decode_transaction", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+
+        let summary = TransactionSummary {
+            txid: tx.compute_txid().to_string(),
+            wtxid: tx.compute_wtxid().to_string(),
+            version: tx.version.0,
+            version_description: describe_version(tx.version).to_string(),
+            lock_time: tx.lock_time.to_consensus_u32(),
+            anti_fee_sniping: looks_like_anti_fee_sniping(&tx),
+            size: tx.total_size(),
+            vsize: tx.vsize(),
+            weight: tx.weight().to_wu(),
+            segwit: tx.input.iter().any(|input| !input.witness.is_empty()),
+            input_count: tx.input.len(),
+            output_count: tx.output.len(),
+        };
+
+        to_json(&summary, compact)
+    })
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::{Transaction, TxIn, TxOut};
+use crate::transaction::decode_tx_hex;
+use crate::utils::varint::encode_compact_size;
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightPart {
+    #[serde(alias = "base_weight")]
+    pub base_weight: u64,
+    #[serde(alias = "witness_weight")]
+    pub witness_weight: u64,
+    pub weight: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedWeight {
+    pub index: usize,
+    #[serde(alias = "base_weight")]
+    pub base_weight: u64,
+    #[serde(alias = "witness_weight")]
+    pub witness_weight: u64,
+    pub weight: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightBreakdown {
+    pub weight: u64,
+    pub vsize: u64,
+    pub overhead: WeightPart,
+    pub inputs: Vec<IndexedWeight>,
+    pub outputs: Vec<IndexedWeight>,
+}
+
+/// Splits a transaction's weight into overhead, inputs and outputs. The parts add up to
+/// exactly `weight`: base bytes count 4 weight units and witness bytes count 1.
+#[wasm_bindgen]
+pub fn weight_breakdown(tx_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("weight_breakdown", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+        to_json(&breakdown(&tx), compact)
+    })
+}
+
+pub fn breakdown(tx: &Transaction) -> WeightBreakdown {
+    let segwit = tx.input.iter().any(|input| !input.witness.is_empty());
+
+    // Version, locktime and the two counts; the segwit marker and flag are witness data.
+    let overhead_base = 4 + 4 + compact_size_len(tx.input.len()) + compact_size_len(tx.output.len());
+    let overhead = part(overhead_base, if segwit { 2 } else { 0 });
+
+    let inputs = tx
+        .input
+        .iter()
+        .enumerate()
+        .map(|(index, input)| indexed(index, input_part(input, segwit)))
+        .collect();
+    let outputs = tx
+        .output
+        .iter()
+        .enumerate()
+        .map(|(index, output)| indexed(index, output_part(output)))
+        .collect();
+
+    WeightBreakdown {
+        weight: tx.weight().to_wu(),
+        vsize: tx.vsize() as u64,
+        overhead,
+        inputs,
+        outputs,
+    }
+}
+
+fn input_part(input: &TxIn, segwit: bool) -> WeightPart {
+    let script_sig = input.script_sig.len();
+    let base = 32 + 4 + compact_size_len(script_sig) + script_sig as u64 + 4;
+    // In a segwit transaction every input carries a witness, even if it is just the empty count.
+    let witness = if segwit { serialize(&input.witness).len() as u64 } else { 0 };
+    part(base, witness)
+}
+
+fn output_part(output: &TxOut) -> WeightPart {
+    let script = output.script_pubkey.len();
+    part(8 + compact_size_len(script) + script as u64, 0)
+}
+
+fn part(base_bytes: u64, witness_bytes: u64) -> WeightPart {
+    WeightPart {
+        base_weight: base_bytes * 4,
+        witness_weight: witness_bytes,
+        weight: base_bytes * 4 + witness_bytes,
+    }
+}
+
+fn indexed(index: usize, part: WeightPart) -> IndexedWeight {
+    IndexedWeight {
+        index,
+        base_weight: part.base_weight,
+        witness_weight: part.witness_weight,
+        weight: part.weight,
+    }
+}
+
+fn compact_size_len(value: usize) -> u64 {
+    encode_compact_size(value as u64).len() as u64
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::taproot::ControlBlock;
+use bitcoin::{PublicKey, Script};
+use crate::script::parse_multisig;
+use crate::transaction::{check_input_index, decode_tx_hex};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WitnessElement {
+    pub index: usize,
+    pub size: usize,
+    pub hex: String,
+    pub kind: String,
+    pub description: String,
+}
+
+#[wasm_bindgen]
+pub fn decode_witness(witness_json_or_tx_hex: &str, input_index: usize, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("decode_witness", witness_json_or_tx_hex.len(), || {
+        let input = witness_json_or_tx_hex.trim();
+
+        let items: Vec<Vec<u8>> = if input.starts_with('[') {
+            let hex_items: Vec<String> = serde_json::from_str(input)
+                .map_err(|e| JsValue::from_str(&format!("Invalid witness JSON: {}", e)))?;
+            hex_items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    hex_to_bytes(item, HexMode::Lenient)
+                        .map_err(|e| JsValue::from_str(&format!("Invalid witness item {}: {}", i, e)))
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            let tx = decode_tx_hex(input)?;
+            check_input_index(&tx, input_index)?;
+            tx.input[input_index].witness.to_vec()
+        };
+
+        to_json(&annotate_witness(&items), compact)
+    })
+}
+
+pub fn annotate_witness(items: &[Vec<u8>]) -> Vec<WitnessElement> {
+    let mut labels: Vec<Option<(&'static str, String)>> = vec![None; items.len()];
+
+    // Structural positions first: annex, then control block + tapscript, then witness script.
+    let mut end = items.len();
+    if end >= 2 && items[end - 1].first() == Some(&0x50) {
+        labels[end - 1] = Some(("annex", "annex".to_string()));
+        end -= 1;
+    }
+
+    if end >= 2 && classify_element(&items[end - 1]).is_none() {
+        if let Ok(control_block) = ControlBlock::decode(&items[end - 1]) {
+            labels[end - 1] = Some((
+                "control_block",
+                format!(
+                    "control block (leaf version 0x{:02x}, {} merkle nodes)",
+                    control_block.leaf_version.to_consensus(),
+                    control_block.merkle_branch.len()
+                ),
+            ));
+            labels[end - 2] = Some(("tapscript", describe_script("tapscript", &items[end - 2])));
+        } else if looks_like_script(&items[end - 1]) {
+            labels[end - 1] = Some((
+                "witness_script",
+                describe_script("witness script", &items[end - 1]),
+            ));
+        }
+    }
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let (kind, description) = labels[index]
+                .clone()
+                .or_else(|| classify_element(item))
+                .unwrap_or_else(|| {
+                    if item.is_empty() {
+                        ("empty", "empty element".to_string())
+                    } else {
+                        ("data", format!("data push ({} {})", item.len(), if item.len() == 1 { "byte" } else { "bytes" }))
+                    }
+                });
+            WitnessElement {
+                index,
+                size: item.len(),
+                hex: bytes_to_hex(item),
+                kind: kind.to_string(),
+                description,
+            }
+        })
+        .collect()
+}
+
+fn classify_element(item: &[u8]) -> Option<(&'static str, String)> {
+    if let Ok(sig) = bitcoin::ecdsa::Signature::from_slice(item) {
+        return Some(("signature_ecdsa", format!("DER signature ({})", sig.sighash_type)));
+    }
+
+    if item.len() == 64 || item.len() == 65 {
+        if let Ok(sig) = bitcoin::taproot::Signature::from_slice(item) {
+            let description = if item.len() == 64 {
+                "Schnorr signature".to_string()
+            } else {
+                format!("Schnorr signature ({})", sig.sighash_type)
+            };
+            return Some(("signature_schnorr", description));
+        }
+    }
+
+    if let Ok(pubkey) = PublicKey::from_slice(item) {
+        let form = if pubkey.compressed { "compressed" } else { "uncompressed" };
+        return Some(("pubkey", format!("{} pubkey", form)));
+    }
+
+    None
+}
+
+fn looks_like_script(item: &[u8]) -> bool {
+    let script = Script::from_bytes(item);
+    let mut has_opcode = false;
+    for instruction in script.instructions() {
+        match instruction {
+            Ok(bitcoin::script::Instruction::Op(_)) => has_opcode = true,
+            Ok(_) => {}
+            Err(_) => return false,
+        }
+    }
+    has_opcode
+}
+
+fn describe_script(label: &str, item: &[u8]) -> String {
+    match parse_multisig(Script::from_bytes(item)) {
+        Some(info) => format!("{} ({}-of-{} multisig)", label, info.threshold, info.pubkeys.len()),
+        None => label.to_string(),
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::descriptor::{derive_script, parse_descriptors};
+use std::convert::Infallible;
+use bitcoin::bip32::DerivationPath;
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey, DescriptorXKey};
+use miniscript::{translate_hash_clone, TranslatePk, Translator};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, require_address_network, NetworkParams};
+use crate::utils::step::{check_work_units, step_json, to_result, Progress, RawValue};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+const MAX_ADDRESSES: u32 = 1000;
+const MAX_STEPPED_ADDRESSES: u32 = 100_000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedAddress {
+    pub index: Option<u32>,
+    pub address: Option<String>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+}
+
+/// Derives `count` addresses from `start` on one branch of a descriptor. Multipath
+/// descriptors (`<0;1>/*`) and bare xpubs have a receive and a change branch, picked by
+/// `chain`; other descriptors have just the one. A descriptor without a wildcard has a
+/// single address, returned with a null index.
+#[wasm_bindgen]
+pub fn descriptor_to_addresses(
+    descriptor: &str,
+    start: u32,
+    count: u32,
+    network: Option<String>,
+    chain: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    timed("descriptor_to_addresses", descriptor.len(), || {
+        if count > MAX_ADDRESSES {
+            return Err(JsValue::from_str(&format!("At most {} addresses can be derived at once", MAX_ADDRESSES)));
+        }
+        let (branch, network, indexes) = address_range(descriptor, start, count, network, chain).map_err(|e| JsValue::from_str(&e))?;
+
+        let mut addresses = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            addresses.push(derive_address(&branch, &network, index).map_err(|e| JsValue::from_str(&e))?);
+        }
+
+        to_json(&addresses, compact)
+    })
+}
+
+/// `descriptor_to_addresses` in steps of up to `max_work_units` addresses, for ranges
+/// too long to derive in one call (up to 100,000). The final step's `result` is the
+/// same address list.
+#[wasm_bindgen]
+pub struct AddressDeriver {
+    branch: Descriptor<DescriptorPublicKey>,
+    network: NetworkParams,
+    indexes: Vec<Option<u32>>,
+    addresses: Vec<DerivedAddress>,
+    result: Option<Box<RawValue>>,
+}
+
+#[wasm_bindgen]
+impl AddressDeriver {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        descriptor: &str,
+        start: u32,
+        count: u32,
+        network: Option<String>,
+        chain: Option<String>,
+    ) -> Result<AddressDeriver, JsValue> {
+        if count > MAX_STEPPED_ADDRESSES {
+            return Err(JsValue::from_str(&format!("At most {} addresses can be derived by one job", MAX_STEPPED_ADDRESSES)));
+        }
+        let (branch, network, indexes) = address_range(descriptor, start, count, network, chain).map_err(|e| JsValue::from_str(&e))?;
+        Ok(AddressDeriver { branch, network, addresses: Vec::with_capacity(indexes.len()), indexes, result: None })
+    }
+
+    pub fn step(&mut self, max_work_units: u32, compact: Option<bool>) -> Result<String, JsValue> {
+        let units = check_work_units(max_work_units)?;
+        if self.result.is_none() {
+            let end = self.indexes.len().min(self.addresses.len() + units);
+            for position in self.addresses.len()..end {
+                let address = derive_address(&self.branch, &self.network, self.indexes[position]).map_err(|e| JsValue::from_str(&e))?;
+                self.addresses.push(address);
+            }
+            if self.addresses.len() == self.indexes.len() {
+                self.result = Some(to_result(&self.addresses)?);
+            }
+        }
+        step_json(Progress::of(self.addresses.len(), self.indexes.len()), self.result.as_deref(), compact)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressMatch {
+    pub found: bool,
+    /// `receive`, `change`, or the branch number past those.
+    pub chain: Option<String>,
+    /// Null for a match on a descriptor without a wildcard.
+    pub index: Option<u32>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    /// How many indexes were derived on each branch.
+    pub searched: u32,
+}
+
+/// Looks for `address` among indexes 0 through `max_index` of every branch of
+/// `descriptor`, receive and change alike. A low index on either branch is found before
+/// a higher one on the other. Scripts are compared, so the address's case doesn't matter.
+#[wasm_bindgen]
+pub fn find_address_in_descriptor(descriptor: &str, address: &str, max_index: u32, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("find_address_in_descriptor", descriptor.len() + address.len(), || {
+        if max_index >= MAX_STEPPED_ADDRESSES {
+            return Err(JsValue::from_str(&format!("max_index must be below {}", MAX_STEPPED_ADDRESSES)));
+        }
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let target = require_address_network(address.trim(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+        let secp = Secp256k1::verification_only();
+        let branches: Vec<_> = parse_descriptors(descriptor)
+            .map_err(|e| JsValue::from_str(&e))?
+            .iter()
+            .map(|branch| derive_to_wildcard(branch, &secp))
+            .collect();
+        let wildcard = branches.iter().any(Descriptor::has_wildcard);
+
+        let mut result = AddressMatch {
+            found: false,
+            chain: None,
+            index: None,
+            script_pubkey: bytes_to_hex(target.as_bytes()),
+            searched: 0,
+        };
+        let last = if wildcard { max_index } else { 0 };
+        'search: for index in 0..=last {
+            result.searched = index + 1;
+            for (chain, branch) in branches.iter().enumerate() {
+                if index > 0 && !branch.has_wildcard() {
+                    continue;
+                }
+                let script = branch
+                    .derived_descriptor(&secp, index)
+                    .map_err(|e| JsValue::from_str(&format!("Cannot derive index {}: {}", index, e)))?
+                    .script_pubkey();
+                if script == target {
+                    result.found = true;
+                    result.chain = Some(chain_name(chain));
+                    result.index = branch.has_wildcard().then_some(index);
+                    break 'search;
+                }
+            }
+        }
+
+        to_json(&result, compact)
+    })
+}
+
+/// The branch `chain` picks and the indexes to derive on it.
+type AddressRange = (Descriptor<DescriptorPublicKey>, NetworkParams, Vec<Option<u32>>);
+
+fn address_range(
+    descriptor: &str,
+    start: u32,
+    count: u32,
+    network: Option<String>,
+    chain: Option<String>,
+) -> Result<AddressRange, String> {
+    let network = network_or_default(network)?;
+    let mut branches = parse_descriptors(descriptor)?;
+    let chain = parse_chain(chain.as_deref())?;
+    if chain >= branches.len() {
+        return Err(format!(
+            "Chain {} doesn't exist: the descriptor has {} branch{}",
+            chain,
+            branches.len(),
+            if branches.len() == 1 { "" } else { "es" }
+        ));
+    }
+    let branch = branches.swap_remove(chain);
+
+    let indexes: Vec<Option<u32>> = if branch.has_wildcard() {
+        (0..count)
+            .map(|offset| {
+                start
+                    .checked_add(offset)
+                    .filter(|&index| index < 1 << 31)
+                    .map(Some)
+                    .ok_or_else(|| "Derivation indexes must stay below 2^31".to_string())
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        vec![None]
+    };
+    Ok((branch, network, indexes))
+}
+
+fn derive_address(
+    branch: &Descriptor<DescriptorPublicKey>,
+    network: &NetworkParams,
+    index: Option<u32>,
+) -> Result<DerivedAddress, String> {
+    let script = derive_script(branch, index.unwrap_or(0))?;
+    Ok(DerivedAddress {
+        index,
+        address: network.address(&script),
+        script_pubkey: bytes_to_hex(script.as_bytes()),
+    })
+}
+
+/// Derives each xpub down to its wildcard's parent once, so every index then costs one
+/// child derivation per key instead of the whole path. Origins only label keys and are
+/// dropped; a path with hardened steps is left as it is, to fail on derivation.
+fn derive_to_wildcard<C: Verification>(branch: &Descriptor<DescriptorPublicKey>, secp: &Secp256k1<C>) -> Descriptor<DescriptorPublicKey> {
+    struct ParentDeriver<'a, C: Verification>(&'a Secp256k1<C>);
+
+    impl<C: Verification> Translator<DescriptorPublicKey, DescriptorPublicKey, Infallible> for ParentDeriver<'_, C> {
+        fn pk(&mut self, key: &DescriptorPublicKey) -> Result<DescriptorPublicKey, Infallible> {
+            if let DescriptorPublicKey::XPub(xkey) = key {
+                if let Ok(parent) = xkey.xkey.derive_pub(self.0, &xkey.derivation_path) {
+                    return Ok(DescriptorPublicKey::XPub(DescriptorXKey {
+                        origin: None,
+                        xkey: parent,
+                        derivation_path: DerivationPath::master(),
+                        wildcard: xkey.wildcard,
+                    }));
+                }
+            }
+            Ok(key.clone())
+        }
+
+        translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, Infallible);
+    }
+
+    branch.translate_pk(&mut ParentDeriver(secp)).unwrap_or_else(|_| branch.clone())
+}
+
+pub fn chain_name(chain: usize) -> String {
+    match chain {
+        0 => "receive".to_string(),
+        1 => "change".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// `receive`/`change` name branches 0 and 1; any other branch is picked by number.
+fn parse_chain(chain: Option<&str>) -> Result<usize, String> {
+    match chain.map(|c| c.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("receive") | Some("external") => Ok(0),
+        Some("change") | Some("internal") => Ok(1),
+        Some(other) => other
+            .parse()
+            .map_err(|_| format!("Unknown chain \"{}\" (expected receive, change or a branch number)", other)),
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::utils::json::to_json;
+
+/// BIP380's character set, in groups of 32: the position within a group feeds the
+/// checksum directly and the group number is packed three symbols at a time.
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_LENGTH: usize = 8;
+
+#[derive(Serialize, Deserialize)]
+pub struct ChecksumCheck {
+    pub valid: bool,
+    /// The checksum after `#`, if there was one.
+    pub provided: Option<String>,
+    pub expected: String,
+    /// The descriptor with the correct checksum.
+    pub descriptor: String,
+    /// Why it isn't valid, in Bitcoin Core's words.
+    pub problem: Option<String>,
+}
+
+/// The 8-character checksum of a descriptor, as Bitcoin Core appends after `#`.
+#[wasm_bindgen]
+pub fn descriptor_checksum(descriptor_without_checksum: &str) -> Result<String, JsValue> {
+    let descriptor = descriptor_without_checksum.trim();
+    if descriptor.contains('#') {
+        return Err(JsValue::from_str("Descriptor already has a checksum; pass the part before '#'"));
+    }
+    checksum(descriptor).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Checks the `#checksum` of a descriptor. A missing, malformed or wrong checksum isn't
+/// an error: the result says what's wrong and gives the descriptor with the right one.
+#[wasm_bindgen]
+pub fn verify_descriptor_checksum(full_descriptor: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let descriptor = full_descriptor.trim();
+    let (body, provided) = split_checksum(descriptor);
+    let expected = checksum(body).map_err(|e| JsValue::from_str(&e))?;
+    let problem = checksum_problem(provided, &expected);
+
+    let check = ChecksumCheck {
+        valid: problem.is_none(),
+        provided: provided.map(str::to_string),
+        descriptor: format!("{}#{}", body, expected),
+        expected,
+        problem,
+    };
+    to_json(&check, compact)
+}
+
+/// Verifies the checksum if there is one; descriptors without one are accepted.
+pub fn check_checksum(descriptor: &str) -> Result<(), String> {
+    let (body, provided) = split_checksum(descriptor);
+    if provided.is_none() {
+        return Ok(());
+    }
+    let expected = checksum(body)?;
+    match checksum_problem(provided, &expected) {
+        Some(problem) => Err(format!("Invalid descriptor checksum: {}", problem)),
+        None => Ok(()),
+    }
+}
+
+/// The descriptor without its checksum, however mangled, for `fix_checksum`.
+pub fn strip_checksum(descriptor: &str) -> &str {
+    split_checksum(descriptor).0
+}
+
+fn split_checksum(descriptor: &str) -> (&str, Option<&str>) {
+    match descriptor.split_once('#') {
+        Some((body, provided)) => (body, Some(provided)),
+        None => (descriptor, None),
+    }
+}
+
+fn checksum_problem(provided: Option<&str>, expected: &str) -> Option<String> {
+    let Some(provided) = provided else {
+        return Some("Missing checksum".to_string());
+    };
+    if provided.contains('#') {
+        return Some("Multiple '#' symbols".to_string());
+    }
+    if provided.len() != CHECKSUM_LENGTH {
+        return Some(format!("Expected {} character checksum, not {} characters", CHECKSUM_LENGTH, provided.len()));
+    }
+    if provided != expected {
+        return Some(format!("Provided checksum '{}' does not match computed checksum '{}'", provided, expected));
+    }
+    None
+}
+
+/// BIP380's BCH code over GF(32): any error touching at most 4 characters is caught
+/// in descriptors up to 501 characters long.
+pub fn checksum(descriptor: &str) -> Result<String, String> {
+    let mut c: u64 = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for (position, ch) in descriptor.chars().enumerate() {
+        let value = INPUT_CHARSET
+            .find(ch)
+            .ok_or_else(|| format!("Invalid character '{}' at position {}: descriptors only use printable ASCII", ch, position))?
+            as u64;
+        c = polymod(c, value & 31);
+        class = class * 3 + (value >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..CHECKSUM_LENGTH {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Ok((0..CHECKSUM_LENGTH)
+        .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+        .collect())
+}
+
+fn polymod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+    for (bit, generator) in [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd].iter().enumerate() {
+        if c0 & (1 << bit) != 0 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bitcoin Core's descriptor_tests.cpp checksum cases.
+    const PRIVATE: &str = "sh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))";
+    const PUBLIC: &str = "sh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))";
+
+    #[test]
+    fn core_checksums() {
+        assert_eq!(checksum(PRIVATE).unwrap(), "ggrsrxfy");
+        assert_eq!(checksum(PUBLIC).unwrap(), "tjg09x5t");
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert!(check_checksum(&format!("{}#ggrsrxfy", PRIVATE)).is_ok());
+        assert!(check_checksum(&format!("{}#tjg09x5t", PUBLIC)).is_ok());
+        assert!(check_checksum(PUBLIC).is_ok());
+    }
+
+    #[test]
+    fn core_checksum_errors() {
+        let error = |suffix: &str| check_checksum(&format!("{}{}", PRIVATE, suffix)).unwrap_err();
+        assert_eq!(error("#"), "Invalid descriptor checksum: Expected 8 character checksum, not 0 characters");
+        assert_eq!(error("#ggrsrxf"), "Invalid descriptor checksum: Expected 8 character checksum, not 7 characters");
+        assert_eq!(error("#ggrsrxfyq"), "Invalid descriptor checksum: Expected 8 character checksum, not 9 characters");
+        assert_eq!(
+            error("#ggssrxfy"),
+            "Invalid descriptor checksum: Provided checksum 'ggssrxfy' does not match computed checksum 'ggrsrxfy'"
+        );
+        assert_eq!(error("#ggrsrxfy#"), "Invalid descriptor checksum: Multiple '#' symbols");
+        assert_eq!(checksum_problem(None, "ggrsrxfy").unwrap(), "Missing checksum");
+    }
+
+    #[test]
+    fn strips_a_mangled_checksum() {
+        assert_eq!(strip_checksum(&format!("{}#ggssrxfy", PRIVATE)), PRIVATE);
+        assert_eq!(strip_checksum(&format!("{}#gg#rsrxfy", PUBLIC)), PUBLIC);
+        assert!(checksum("raw(deadbeef)\u{e9}").unwrap_err().contains("position 13"));
+    }
+}
@@ -0,0 +1,325 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::secp256k1::schnorr;
+use bitcoin::{ScriptBuf, TapLeafHash, TapSighashType, XOnlyPublicKey};
+use miniscript::descriptor::{DefiniteDescriptorKey, Descriptor, Tr};
+use miniscript::{Legacy, Miniscript, MiniscriptKey, Preimage32, Satisfier, Segwitv0, Tap, ToPublicKey};
+use crate::descriptor::parse_descriptors;
+use crate::descriptor::policy::NUMS_POINT;
+use crate::transaction::dummy::{dummy_ecdsa_signature, DUMMY_SCHNORR_SIGNATURE};
+use crate::utils::varint::encode_compact_size;
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+
+// Outpoint (36) + sequence (4), as in `estimate_input_weight`.
+const TXIN_BASE_BYTES: u64 = 40;
+const TAPROOT_CONTROL_BASE_BYTES: u64 = 33;
+const TAPROOT_CONTROL_NODE_BYTES: u64 = 32;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendCost {
+    /// `key path`, `leaf <n>`, or the descriptor's type for a non-taproot spend.
+    pub path: String,
+    #[serde(alias = "script_sig_bytes")]
+    pub script_sig_bytes: u64,
+    #[serde(alias = "witness_items")]
+    pub witness_items: usize,
+    /// The serialized witness, item count included.
+    #[serde(alias = "witness_bytes")]
+    pub witness_bytes: u64,
+    /// The whole input's weight, outpoint and sequence included.
+    pub weight: u64,
+    /// `weight` in virtual bytes, rounded up as transaction vsizes are.
+    pub vsize: u64,
+    pub fee: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeafCost {
+    #[serde(alias = "leaf_index")]
+    pub leaf_index: usize,
+    pub miniscript: String,
+    pub depth: u8,
+    #[serde(alias = "control_block_bytes")]
+    pub control_block_bytes: u64,
+    /// None when the leaf can't be satisfied.
+    pub cost: Option<SpendCost>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SatisfactionCost {
+    pub descriptor: String,
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: FeeRate,
+    pub cheapest: SpendCost,
+    /// Taproot only; None when the internal key is the unspendable NUMS point.
+    #[serde(alias = "key_path")]
+    pub key_path: Option<SpendCost>,
+    pub leaves: Vec<LeafCost>,
+    pub warnings: Vec<String>,
+}
+
+/// Signs everything with `populate_dummy_signatures`' placeholders and has every preimage
+/// and timelock, so satisfying a script gives its cheapest spend at the sizes that
+/// populator, and so the fee estimates built on it, assume.
+struct PlaceholderSigner {
+    ecdsa: bitcoin::ecdsa::Signature,
+    schnorr: bitcoin::taproot::Signature,
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for PlaceholderSigner {
+    fn lookup_ecdsa_sig(&self, _: &Pk) -> Option<bitcoin::ecdsa::Signature> {
+        Some(self.ecdsa)
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, _: &Pk, _: &TapLeafHash) -> Option<bitcoin::taproot::Signature> {
+        Some(self.schnorr)
+    }
+
+    fn lookup_sha256(&self, _: &Pk::Sha256) -> Option<Preimage32> {
+        Some([0; 32])
+    }
+
+    fn lookup_hash256(&self, _: &Pk::Hash256) -> Option<Preimage32> {
+        Some([0; 32])
+    }
+
+    fn lookup_ripemd160(&self, _: &Pk::Ripemd160) -> Option<Preimage32> {
+        Some([0; 32])
+    }
+
+    fn lookup_hash160(&self, _: &Pk::Hash160) -> Option<Preimage32> {
+        Some([0; 32])
+    }
+
+    fn check_older(&self, _: bitcoin::relative::LockTime) -> bool {
+        true
+    }
+
+    fn check_after(&self, _: bitcoin::absolute::LockTime) -> bool {
+        true
+    }
+}
+
+/// What spending one output of `script_or_descriptor` will cost: the cheapest witness's
+/// size, the input's weight, and its fee at `fee_rate_sat_vb` (1 by default). Taproot
+/// descriptors also get the key path and every leaf priced, control block included.
+/// `script_or_descriptor` is a descriptor (the first of a multipath one, at index 0), or a
+/// miniscript expression or raw script hex placed in `context`: `wsh` (default), `sh-wsh`,
+/// `sh`, or `tap` as the single leaf behind an unspendable internal key.
+#[wasm_bindgen]
+pub fn estimate_satisfaction_cost(
+    script_or_descriptor: &str,
+    context: Option<String>,
+    fee_rate_sat_vb: Option<f64>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    timed("estimate_satisfaction_cost", script_or_descriptor.len(), || {
+        let fee_rate = fee_rate_sat_vb.unwrap_or(1.0);
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate).map_err(|e| JsValue::from_str(&e))?;
+        let descriptor =
+            descriptor_for(script_or_descriptor.trim(), context.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let cost = satisfaction_cost(&descriptor, fee_rate).map_err(|e| JsValue::from_str(&e))?;
+        to_json(&cost, compact)
+    })
+}
+
+fn descriptor_for(input: &str, context: Option<&str>) -> Result<Descriptor<DefiniteDescriptorKey>, String> {
+    let descriptor = match parse_descriptors(input) {
+        Ok(descriptors) => descriptors.into_iter().next().ok_or_else(|| "The descriptor is empty".to_string())?,
+        Err(descriptor_error) => {
+            let context = parse_context(context.unwrap_or("wsh"))?;
+            let expression = if !input.is_empty() && input.bytes().all(|b| b.is_ascii_hexdigit()) {
+                decode_script(input, context)?
+            } else {
+                input.to_string()
+            };
+            let wrapped = match context {
+                "wsh" => format!("wsh({})", expression),
+                "sh-wsh" => format!("sh(wsh({}))", expression),
+                "sh" => format!("sh({})", expression),
+                _ => format!("tr({},{})", NUMS_POINT, expression),
+            };
+            parse_descriptors(&wrapped)
+                .map_err(|e| format!("Not a descriptor ({}), nor a {} script: {}", descriptor_error, context, e))?
+                .into_iter()
+                .next()
+                .ok_or_else(|| "The descriptor is empty".to_string())?
+        }
+    };
+    descriptor.at_derivation_index(0).map_err(|e| format!("Cannot derive index 0: {}", e))
+}
+
+fn parse_context(context: &str) -> Result<&'static str, String> {
+    match context.trim().to_ascii_lowercase().as_str() {
+        "wsh" | "p2wsh" | "segwitv0" => Ok("wsh"),
+        "sh-wsh" | "p2sh-p2wsh" => Ok("sh-wsh"),
+        "sh" | "p2sh" | "legacy" => Ok("sh"),
+        "tap" | "tr" | "taproot" | "p2tr" => Ok("tap"),
+        other => Err(format!("Unknown context \"{}\" (expected wsh, sh-wsh, sh or tap)", other)),
+    }
+}
+
+/// A raw script as miniscript text, so it can be wrapped like an expression.
+fn decode_script(hex: &str, context: &str) -> Result<String, String> {
+    let script = ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid script hex: {}", e))?;
+    let decoded = match context {
+        "tap" => Miniscript::<XOnlyPublicKey, Tap>::parse_insane(&script).map(|ms| ms.to_string()),
+        "sh" => Miniscript::<bitcoin::PublicKey, Legacy>::parse_insane(&script).map(|ms| ms.to_string()),
+        _ => Miniscript::<bitcoin::PublicKey, Segwitv0>::parse_insane(&script).map(|ms| ms.to_string()),
+    };
+    decoded.map_err(|e| format!("The script isn't miniscript, so its satisfaction can't be worked out: {}", e))
+}
+
+fn satisfaction_cost(descriptor: &Descriptor<DefiniteDescriptorKey>, fee_rate: FeeRate) -> Result<SatisfactionCost, String> {
+    let signer = PlaceholderSigner {
+        ecdsa: bitcoin::ecdsa::Signature::from_slice(&dummy_ecdsa_signature(None)?).map_err(|e| e.to_string())?,
+        schnorr: bitcoin::taproot::Signature {
+            signature: schnorr::Signature::from_slice(&DUMMY_SCHNORR_SIGNATURE).map_err(|e| e.to_string())?,
+            sighash_type: TapSighashType::Default,
+        },
+    };
+    let script_type = script_type(descriptor);
+    let mut warnings = Vec::new();
+
+    let (cheapest, key_path, leaves) = match descriptor {
+        Descriptor::Tr(tr) => {
+            let (key_path, leaves) = taproot_costs(tr, &signer, fee_rate, &mut warnings);
+            let cheapest = key_path
+                .iter()
+                .chain(leaves.iter().filter_map(|leaf| leaf.cost.as_ref()))
+                .min_by_key(|cost| cost.weight)
+                .cloned()
+                .ok_or_else(|| "No spending path of this descriptor can be satisfied".to_string())?;
+            (cheapest, key_path, leaves)
+        }
+        _ => {
+            let (witness, script_sig) = match descriptor.get_satisfaction(&signer) {
+                Ok(satisfaction) => satisfaction,
+                Err(_) => {
+                    let satisfaction = descriptor
+                        .get_satisfaction_mall(&signer)
+                        .map_err(|e| format!("This descriptor can't be satisfied: {}", e))?;
+                    warnings.push("Only a malleable satisfaction exists; a third party could change its size".to_string());
+                    satisfaction
+                }
+            };
+            (spend_cost(&script_type, script_sig.len() as u64, &witness, fee_rate), None, Vec::new())
+        }
+    };
+
+    Ok(SatisfactionCost {
+        descriptor: descriptor.to_string(),
+        script_type,
+        fee_rate,
+        cheapest,
+        key_path,
+        leaves,
+        warnings,
+    })
+}
+
+fn taproot_costs(
+    tr: &Tr<DefiniteDescriptorKey>,
+    signer: &PlaceholderSigner,
+    fee_rate: FeeRate,
+    warnings: &mut Vec<String>,
+) -> (Option<SpendCost>, Vec<LeafCost>) {
+    let nums = XOnlyPublicKey::from_str(NUMS_POINT).expect("valid NUMS point");
+    let key_path = (tr.internal_key().to_x_only_pubkey() != nums)
+        .then(|| spend_cost("key path", 0, &[signer.schnorr.to_vec()], fee_rate));
+
+    let leaves = tr
+        .iter_scripts()
+        .enumerate()
+        .map(|(leaf_index, (depth, ms))| {
+            let control_block_bytes = TAPROOT_CONTROL_BASE_BYTES + depth as u64 * TAPROOT_CONTROL_NODE_BYTES;
+            let satisfaction = ms.satisfy(signer).or_else(|_| {
+                let satisfaction = ms.satisfy_malleable(signer)?;
+                warnings.push(format!("Leaf {} only has a malleable satisfaction", leaf_index));
+                Ok::<_, miniscript::Error>(satisfaction)
+            });
+            let cost = satisfaction.ok().map(|mut witness| {
+                witness.push(ms.encode().into_bytes());
+                witness.push(vec![0; control_block_bytes as usize]);
+                spend_cost(&format!("leaf {}", leaf_index), 0, &witness, fee_rate)
+            });
+            LeafCost { leaf_index, miniscript: ms.to_string(), depth, control_block_bytes, cost }
+        })
+        .collect();
+    (key_path, leaves)
+}
+
+fn spend_cost(path: &str, script_sig_bytes: u64, witness: &[Vec<u8>], fee_rate: FeeRate) -> SpendCost {
+    let witness_bytes = if witness.is_empty() {
+        0
+    } else {
+        compact_size_len(witness.len() as u64)
+            + witness.iter().map(|item| compact_size_len(item.len() as u64) + item.len() as u64).sum::<u64>()
+    };
+    let weight = 4 * (TXIN_BASE_BYTES + compact_size_len(script_sig_bytes) + script_sig_bytes) + witness_bytes;
+    let vsize = weight.div_ceil(4);
+    SpendCost {
+        path: path.to_string(),
+        script_sig_bytes,
+        witness_items: witness.len(),
+        witness_bytes,
+        weight,
+        vsize,
+        fee: fee_rate.fee_for_vbytes(vsize as f64).to_sat(),
+    }
+}
+
+/// `sh-wsh`, `wpkh`, `tr`, ...: the descriptor's wrappers, outermost first.
+fn script_type(descriptor: &Descriptor<DefiniteDescriptorKey>) -> String {
+    let text = descriptor.to_string();
+    if text.starts_with("sh(wsh(") {
+        "sh-wsh".to_string()
+    } else if text.starts_with("sh(wpkh(") {
+        "sh-wpkh".to_string()
+    } else {
+        text.split('(').next().unwrap_or_default().to_string()
+    }
+}
+
+fn compact_size_len(value: u64) -> u64 {
+    encode_compact_size(value).len() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The secp256k1 generator, a valid key that needs no derivation.
+    const KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn cost(descriptor: &str) -> SatisfactionCost {
+        let descriptor = descriptor_for(descriptor, None).unwrap();
+        satisfaction_cost(&descriptor, FeeRate::from_sat_per_vb(10.0).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn vsize_is_rounded_up() {
+        // 164 for the outpoint, sequence and empty scriptSig, plus a 107-byte witness.
+        let wpkh = cost(&format!("wpkh({})", KEY)).cheapest;
+        assert_eq!((wpkh.witness_bytes, wpkh.weight, wpkh.vsize, wpkh.fee), (107, 271, 68, 680));
+
+        let tr = cost(&format!("tr({})", &KEY[2..]));
+        let key_path = tr.key_path.unwrap();
+        assert_eq!((key_path.weight, key_path.vsize, key_path.fee), (230, 58, 580));
+    }
+
+    #[test]
+    fn vsize_is_an_integer_in_json() {
+        let json = serde_json::to_value(cost(&format!("tr({})", &KEY[2..])).cheapest).unwrap();
+        assert_eq!(json["vsize"], serde_json::json!(58));
+        assert!(json.get("vbytes").is_none());
+    }
+}
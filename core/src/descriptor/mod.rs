@@ -0,0 +1,71 @@
+use std::str::FromStr;
+use bitcoin::ScriptBuf;
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use crate::wallet::path::parse_path;
+use crate::utils::sanitize::{sanitize, InputKind};
+
+pub mod addresses;
+pub mod checksum;
+pub mod cost;
+pub mod multipath;
+pub mod multisig_config;
+pub mod policy;
+
+pub use addresses::{descriptor_to_addresses, find_address_in_descriptor, AddressDeriver};
+pub use checksum::{descriptor_checksum, verify_descriptor_checksum};
+pub use cost::estimate_satisfaction_cost;
+pub use multipath::export_descriptors;
+pub use multisig_config::{parse_multisig_config, export_multisig_config};
+pub use policy::{compile_policy, analyze_miniscript};
+
+/// Parses a descriptor, or a bare xpub/tpub which is treated as `wpkh(key/0/*)` and `wpkh(key/1/*)`.
+/// Multipath descriptors (`<0;1>`) are split into one descriptor per branch. A checksum
+/// is optional, but must be right if present.
+pub fn parse_descriptors(input: &str) -> Result<Vec<Descriptor<DescriptorPublicKey>>, String> {
+    let input = sanitize(input, InputKind::Descriptor)?;
+    let input = input.trim();
+    if is_bare_xkey(input) {
+        return Ok(vec![
+            parse_single(&format!("wpkh({}/0/*)", input))?,
+            parse_single(&format!("wpkh({}/1/*)", input))?,
+        ]);
+    }
+
+    checksum::check_checksum(input)?;
+    check_origins(input)?;
+    let descriptor = Descriptor::<DescriptorPublicKey>::from_str(input)
+        .map_err(|e| format!("Invalid descriptor: {}", e))?;
+    descriptor
+        .into_single_descriptors()
+        .map_err(|e| format!("Invalid descriptor: {}", e))
+}
+
+pub fn derive_script(descriptor: &Descriptor<DescriptorPublicKey>, index: u32) -> Result<ScriptBuf, String> {
+    let definite = descriptor
+        .at_derivation_index(index)
+        .map_err(|e| format!("Cannot derive index {}: {}", index, e))?;
+    Ok(definite.script_pubkey())
+}
+
+/// Runs each `[fingerprint/path]` key origin's path through the shared path parser, so
+/// a bad origin gets the same error as a bad path anywhere else.
+fn check_origins(input: &str) -> Result<(), String> {
+    let mut rest = input;
+    while let Some(start) = rest.find('[') {
+        let end = rest[start..].find(']').ok_or_else(|| "Invalid descriptor: key origin is missing its ]".to_string())?;
+        let origin = &rest[start + 1..start + end];
+        if let Some((_, path)) = origin.split_once('/') {
+            parse_path(path, false).map_err(|e| format!("Invalid key origin [{}]: {}", origin, e))?;
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+fn parse_single(descriptor: &str) -> Result<Descriptor<DescriptorPublicKey>, String> {
+    Descriptor::<DescriptorPublicKey>::from_str(descriptor).map_err(|e| format!("Invalid extended public key: {}", e))
+}
+
+fn is_bare_xkey(input: &str) -> bool {
+    (input.starts_with("xpub") || input.starts_with("tpub")) && input.bytes().all(|b| b.is_ascii_alphanumeric())
+}
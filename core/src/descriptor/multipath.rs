@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use miniscript::descriptor::{DerivPaths, Descriptor, DescriptorMultiXKey, DescriptorPublicKey};
+use miniscript::{translate_hash_clone, ForEachKey, TranslatePk, TranslateErr, Translator};
+use crate::descriptor::checksum::strip_checksum;
+use crate::descriptor::parse_descriptors;
+use crate::utils::json::to_json;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Drop a missing, malformed or wrong checksum instead of refusing the descriptor.
+    #[serde(default, alias = "fix_checksum")]
+    pub fix_checksum: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedDescriptors {
+    pub descriptors: Vec<String>,
+    pub multipath: bool,
+}
+
+/// Writes a wallet's descriptors with checksums, as one BIP389 multipath line
+/// (`.../<0;1>/*`) by default or one line per branch with `format: "pair"`. The input
+/// is a descriptor, a bare xpub, or a JSON array of branch descriptors (receive first)
+/// that differ only in one derivation step.
+#[wasm_bindgen]
+pub fn export_descriptors(descriptors: &str, options_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let options: ExportOptions = match options_json {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+        _ => ExportOptions { format: None, fix_checksum: false },
+    };
+    let multipath = match options.format.as_deref().map(|f| f.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("multipath") => true,
+        Some("pair") | Some("legacy") => false,
+        Some(other) => {
+            return Err(JsValue::from_str(&format!(
+                "Unknown format \"{}\" (expected multipath or pair)",
+                other
+            )))
+        }
+    };
+
+    let branches = parse_branches(descriptors, options.fix_checksum).map_err(|e| JsValue::from_str(&e))?;
+    let exported = if multipath && branches.len() > 1 {
+        ExportedDescriptors {
+            descriptors: vec![merge_branches(&branches).map_err(|e| JsValue::from_str(&e))?.to_string()],
+            multipath: true,
+        }
+    } else {
+        ExportedDescriptors {
+            descriptors: branches.iter().map(|branch| branch.to_string()).collect(),
+            multipath: false,
+        }
+    };
+
+    to_json(&exported, compact)
+}
+
+fn parse_branches(input: &str, fix_checksum: bool) -> Result<Vec<Descriptor<DescriptorPublicKey>>, String> {
+    let parse = |descriptor: &str| parse_descriptors(if fix_checksum { strip_checksum(descriptor.trim()) } else { descriptor });
+    if !input.trim_start().starts_with('[') || serde_json::from_str::<serde_json::Value>(input).is_err() {
+        return parse(input);
+    }
+    let list: Vec<String> = serde_json::from_str(input).map_err(|e| format!("Invalid descriptors JSON: {}", e))?;
+    let mut branches = Vec::new();
+    for (index, descriptor) in list.iter().enumerate() {
+        branches.extend(parse(descriptor).map_err(|e| format!("Descriptor {}: {}", index, e))?);
+    }
+    if branches.is_empty() {
+        return Err("At least one descriptor is required".to_string());
+    }
+    Ok(branches)
+}
+
+/// Folds single-path branches into one multipath descriptor. Each key position must hold
+/// the same key in every branch, or the same xpub whose paths differ in exactly one step.
+/// Splitting the result again has to give back the branches, which rules out branches
+/// that differ in anything but their keys.
+fn merge_branches(branches: &[Descriptor<DescriptorPublicKey>]) -> Result<Descriptor<DescriptorPublicKey>, String> {
+    let key_lists: Vec<Vec<DescriptorPublicKey>> = branches
+        .iter()
+        .map(|branch| {
+            let mut keys = Vec::new();
+            branch.for_each_key(|key| {
+                keys.push(key.clone());
+                true
+            });
+            keys
+        })
+        .collect();
+    if key_lists.iter().any(|keys| keys.len() != key_lists[0].len()) {
+        return Err("The branches have different numbers of keys, so they aren't one wallet".to_string());
+    }
+
+    let merged_keys = (0..key_lists[0].len())
+        .map(|position| merge_key(key_lists.iter().map(|keys| &keys[position]).collect()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = branches[0]
+        .translate_pk(&mut KeyReplacer { keys: merged_keys.into_iter() })
+        .map_err(|e| match e {
+            TranslateErr::TranslatorErr(e) => e,
+            TranslateErr::OuterError(e) => e.to_string(),
+        })?;
+
+    let split = merged.clone().into_single_descriptors().map_err(|e| e.to_string())?;
+    if split.iter().map(|d| d.to_string()).ne(branches.iter().map(|d| d.to_string())) {
+        return Err("The branches differ in more than their derivation steps, so they can't be one multipath descriptor".to_string());
+    }
+    Ok(merged)
+}
+
+fn merge_key(keys: Vec<&DescriptorPublicKey>) -> Result<DescriptorPublicKey, String> {
+    if keys.iter().all(|key| *key == keys[0]) {
+        return Ok(keys[0].clone());
+    }
+    let xkeys = keys
+        .iter()
+        .map(|key| match key {
+            DescriptorPublicKey::XPub(xkey) => Ok(xkey),
+            _ => Err(format!("Key {} differs between branches but isn't an xpub with a path", key)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = xkeys[0];
+    if xkeys.iter().any(|x| x.xkey != first.xkey || x.origin != first.origin || x.wildcard != first.wildcard) {
+        return Err(format!("Key {} is a different key in each branch", keys[0]));
+    }
+    let paths: Vec<Vec<u32>> = xkeys.iter().map(|x| x.derivation_path.to_u32_vec()).collect();
+    let same_length = paths.iter().all(|path| path.len() == paths[0].len());
+    let differing = (0..paths[0].len())
+        .filter(|&step| paths.iter().any(|path| path.get(step) != paths[0].get(step)))
+        .count();
+    if !same_length || differing != 1 {
+        return Err(format!("Key {} must differ between branches in exactly one derivation step", keys[0]));
+    }
+
+    Ok(DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+        origin: first.origin.clone(),
+        xkey: first.xkey,
+        derivation_paths: DerivPaths::new(xkeys.iter().map(|x| x.derivation_path.clone()).collect())
+            .expect("at least two branches"),
+        wildcard: first.wildcard,
+    }))
+}
+
+/// Swaps in precomputed keys, in the order `for_each_key` visits them.
+struct KeyReplacer<I: Iterator<Item = DescriptorPublicKey>> {
+    keys: I,
+}
+
+impl<I: Iterator<Item = DescriptorPublicKey>> Translator<DescriptorPublicKey, DescriptorPublicKey, String> for KeyReplacer<I> {
+    fn pk(&mut self, _pk: &DescriptorPublicKey) -> Result<DescriptorPublicKey, String> {
+        self.keys.next().ok_or_else(|| "Descriptor has more keys than expected".to_string())
+    }
+
+    translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, String);
+}
@@ -0,0 +1,484 @@
+use std::collections::{BTreeSet, HashSet};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub};
+use bitcoin::Network;
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey, ShInner, Wildcard, WshInner};
+use crate::descriptor::{derive_script, parse_descriptors};
+use crate::utils::json::{parse_json_value, to_json};
+use crate::utils::metrics::timed;
+use crate::utils::network::{parse_network_params, NetworkParams};
+use crate::utils::sanitize::{sanitize, InputKind};
+use crate::wallet::path::{format_path, parse_path};
+use crate::wallet::xkey::standard_xpub;
+
+/// Coldcard refuses longer wallet names.
+const MAX_NAME_LEN: usize = 20;
+/// The most cosigners Coldcard registers.
+const MAX_COSIGNERS: usize = 15;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultisigCosigner {
+    pub fingerprint: String,
+    pub derivation: String,
+    /// Always in the plain `xpub`/`tpub` form.
+    pub xpub: String,
+    /// The key as the file had it, when that was a SLIP-132 form such as `Zpub`.
+    #[serde(alias = "original_key")]
+    pub original_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultisigConfig {
+    pub name: Option<String>,
+    pub threshold: usize,
+    pub total: usize,
+    /// `P2WSH`, `P2SH-P2WSH` or `P2SH`, as Coldcard writes it.
+    pub format: String,
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    pub network: String,
+    /// The derivation every cosigner shares, or `null` when they differ.
+    pub derivation: Option<String>,
+    pub cosigners: Vec<MultisigCosigner>,
+    /// The wallet as a `sortedmulti` multipath descriptor with its checksum.
+    pub descriptor: String,
+    /// Receive address 0, to compare with what the coordinator and each device show.
+    #[serde(alias = "first_address")]
+    pub first_address: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// The input to `export_multisig_config`: either `cosigners` with `threshold` and `format`,
+/// or a `descriptor`. A `parse_multisig_config` result works as it is.
+#[derive(Deserialize)]
+pub struct MultisigPolicy {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub threshold: Option<usize>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub cosigners: Option<Vec<PolicyCosigner>>,
+    #[serde(default)]
+    pub descriptor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PolicyCosigner {
+    pub fingerprint: String,
+    pub derivation: String,
+    pub xpub: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    P2sh,
+    P2shP2wsh,
+    P2wsh,
+}
+
+impl Format {
+    fn parse(text: &str) -> Option<Format> {
+        match text.trim().to_ascii_uppercase().as_str() {
+            "P2SH" => Some(Format::P2sh),
+            // Sparrow writes the wrapped form inside out.
+            "P2SH-P2WSH" | "P2WSH-P2SH" => Some(Format::P2shP2wsh),
+            "P2WSH" => Some(Format::P2wsh),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::P2sh => "P2SH",
+            Format::P2shP2wsh => "P2SH-P2WSH",
+            Format::P2wsh => "P2WSH",
+        }
+    }
+
+    fn script_type(self) -> &'static str {
+        match self {
+            Format::P2sh => "p2sh",
+            Format::P2shP2wsh => "p2sh-p2wsh",
+            Format::P2wsh => "p2wsh",
+        }
+    }
+
+    fn wrap(self, multi: &str) -> String {
+        match self {
+            Format::P2sh => format!("sh({})", multi),
+            Format::P2shP2wsh => format!("sh(wsh({}))", multi),
+            Format::P2wsh => format!("wsh({})", multi),
+        }
+    }
+
+    /// BIP45 for bare P2SH, BIP48 (`m/48'/coin'/account'/script'`) for the segwit forms.
+    fn standard_path(self, mainnet: bool) -> String {
+        let coin = if mainnet { 0 } else { 1 };
+        match self {
+            Format::P2sh => "m/45'".to_string(),
+            Format::P2shP2wsh => format!("m/48'/{}'/account'/1'", coin),
+            Format::P2wsh => format!("m/48'/{}'/account'/2'", coin),
+        }
+    }
+
+    fn is_standard_path(self, path: &DerivationPath, mainnet: bool) -> bool {
+        let hardened = |index| ChildNumber::Hardened { index };
+        let steps: Vec<ChildNumber> = path.into_iter().copied().collect();
+        let script = match self {
+            Format::P2sh => return steps == [hardened(45)],
+            Format::P2shP2wsh => 1,
+            Format::P2wsh => 2,
+        };
+        matches!(steps.as_slice(), [purpose, coin, account, last]
+            if *purpose == hardened(48) && *coin == hardened(if mainnet { 0 } else { 1 }) && account.is_hardened() && *last == hardened(script))
+    }
+}
+
+/// A cosigner as the export writes it: master fingerprint, derivation and xpub.
+type KeyEntry = (Fingerprint, DerivationPath, Xpub);
+
+/// One `FINGERPRINT: xpub` line with the derivation in force where it appears.
+struct Cosigner {
+    line: usize,
+    fingerprint: Fingerprint,
+    path: DerivationPath,
+    xpub: Xpub,
+    key: String,
+    key_network: &'static str,
+    key_script_type: &'static str,
+}
+
+/// Reads the multisig wallet file Coldcard, Sparrow and other coordinators exchange
+/// (`Name:`, `Policy: 2 of 3`, `Derivation:`, `Format:` and one `FINGERPRINT: xpub`
+/// line per cosigner), checks it the way a signing device would, and gives the wallet
+/// as a descriptor with its first receive address. `network` defaults to the one the
+/// keys are encoded for.
+#[wasm_bindgen]
+pub fn parse_multisig_config(text: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("parse_multisig_config", text.len(), || {
+        let network = network.map(|name| parse_network_params(&name)).transpose().map_err(|e| JsValue::from_str(&e))?;
+        to_json(&read_config(text, network.as_ref()).map_err(|e| JsValue::from_str(&e))?, compact)
+    })
+}
+
+/// Writes a multisig wallet file for registering on a Coldcard (which Sparrow and other
+/// coordinators also import). The result is read back before it's returned, so a file
+/// that comes out of here passes the same checks as `parse_multisig_config`.
+#[wasm_bindgen]
+pub fn export_multisig_config(policy_json: &str) -> Result<String, JsValue> {
+    timed("export_multisig_config", policy_json.len(), || write_config(policy_json).map_err(|e| JsValue::from_str(&e)))
+}
+
+fn read_config(text: &str, network: Option<&NetworkParams>) -> Result<MultisigConfig, String> {
+    let text = sanitize(text, InputKind::Text)?;
+    let mut name = None;
+    let mut policy = None;
+    let mut format = None;
+    let mut derivation: Option<DerivationPath> = None;
+    let mut cosigners = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .ok_or_else(|| format!("Line {}: expected \"Setting: value\" or \"FINGERPRINT: xpub\", got \"{}\"", number, line))?;
+        match key.to_ascii_lowercase().as_str() {
+            "name" => name = Some(value.to_string()),
+            "policy" => policy = Some(parse_policy(value).map_err(|e| format!("Line {}: {}", number, e))?),
+            "format" => {
+                format = Some(Format::parse(value).ok_or_else(|| {
+                    format!("Line {}: unknown format \"{}\" (expected P2WSH, P2SH-P2WSH or P2SH)", number, value)
+                })?)
+            }
+            "derivation" => derivation = Some(parse_path(value, false).map_err(|e| format!("Line {}: {}", number, e))?.path),
+            fingerprint if fingerprint.len() == 8 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                let path = derivation
+                    .clone()
+                    .ok_or_else(|| format!("Line {}: key {} comes before any Derivation line", number, key))?;
+                let written = sanitize(value, InputKind::Key)?;
+                let standard = network.map_or_else(|| written.to_string(), |params| params.standard_extended_key(&written));
+                let (xpub, key_network, key_script_type) = standard_xpub(&standard).map_err(|e| format!("Line {}: {}", number, e))?;
+                cosigners.push(Cosigner {
+                    line: number,
+                    fingerprint: Fingerprint::from_str(fingerprint).expect("8 hex digits"),
+                    path,
+                    xpub,
+                    key: written.to_string(),
+                    key_network,
+                    key_script_type,
+                });
+            }
+            _ => warnings.push(format!("line {}: ignored unknown setting \"{}\"", number, key)),
+        }
+    }
+
+    let (threshold, total) = policy.ok_or_else(|| "Invalid multisig config: no Policy line (e.g. \"Policy: 2 of 3\")".to_string())?;
+    let format = format.unwrap_or_else(|| {
+        warnings.push("no Format line, so P2SH is assumed, as Coldcard does".to_string());
+        Format::P2sh
+    });
+    let first = cosigners.first().ok_or_else(|| "Invalid multisig config: no FINGERPRINT: xpub lines".to_string())?;
+    let mainnet = first.key_network == "mainnet";
+    let params = match network {
+        Some(params) => params.clone(),
+        None => NetworkParams::from_network(if mainnet { Network::Bitcoin } else { Network::Testnet }),
+    };
+
+    let mut problems = Vec::new();
+    if cosigners.len() != total {
+        problems.push(format!("the policy is {} of {} but the file lists {} keys", threshold, total, cosigners.len()));
+    }
+    if mainnet != (params.network == Network::Bitcoin) {
+        problems.push(format!("the keys are {} keys, not {} ones", first.key_network, params.target_name()));
+    }
+    let mut fingerprints = HashSet::new();
+    let mut xpubs = HashSet::new();
+    let mut unusual_paths = BTreeSet::new();
+    for cosigner in &cosigners {
+        let label = format!("key {} on line {}", cosigner.fingerprint, cosigner.line);
+        let depth = cosigner.path.len();
+        if !fingerprints.insert(cosigner.fingerprint) {
+            problems.push(format!("{} repeats a fingerprint; each cosigner needs its own seed", label));
+        }
+        if !xpubs.insert(cosigner.xpub) {
+            problems.push(format!("{} is the same xpub as an earlier key", label));
+        }
+        if cosigner.key_network != first.key_network {
+            problems.push(format!("{} is a {} key among {} ones", label, cosigner.key_network, first.key_network));
+        }
+        if cosigner.xpub.depth as usize != depth {
+            problems.push(format!(
+                "{} has derivation {} ({} steps) but its xpub is at depth {}",
+                label,
+                format_path(&cosigner.path, None),
+                depth,
+                cosigner.xpub.depth
+            ));
+        } else if cosigner.path.as_ref().last().is_some_and(|&last| last != cosigner.xpub.child_number) {
+            problems.push(format!(
+                "{} has derivation {} but its xpub is child {} of its parent",
+                label,
+                format_path(&cosigner.path, None),
+                cosigner.xpub.child_number
+            ));
+        }
+        // Only a depth 0 or 1 xpub shows whose master key it came from.
+        let master = match depth {
+            0 => Some(cosigner.xpub.fingerprint()),
+            1 => Some(cosigner.xpub.parent_fingerprint),
+            _ => None,
+        };
+        if master.is_some_and(|master| master != cosigner.fingerprint) {
+            problems.push(format!("{}'s xpub doesn't come from a master key with that fingerprint", label));
+        }
+
+        if cosigner.key_script_type != "p2pkh" && cosigner.key_script_type != format.script_type() {
+            warnings.push(format!(
+                "{} is written with the SLIP-132 prefix for {}, but the wallet is {}",
+                label,
+                cosigner.key_script_type,
+                format.name()
+            ));
+        }
+        if !format.is_standard_path(&cosigner.path, mainnet) {
+            unusual_paths.insert(format_path(&cosigner.path, None));
+        }
+    }
+    for path in unusual_paths {
+        warnings.push(format!(
+            "{} isn't the usual {} for {}; that's fine as long as each device was set up with it",
+            path,
+            format.standard_path(mainnet),
+            format.name()
+        ));
+    }
+    if !problems.is_empty() {
+        return Err(format!("Invalid multisig config: {}", problems.join("; ")));
+    }
+    if total > MAX_COSIGNERS {
+        warnings.push(format!("Coldcard registers at most {} cosigners", MAX_COSIGNERS));
+    }
+    match &name {
+        None => warnings.push("no Name line; Coldcard needs one to register the wallet".to_string()),
+        Some(name) if name.len() > MAX_NAME_LEN || !name.bytes().all(|b| b.is_ascii_graphic() || b == b' ') => {
+            warnings.push(format!("Coldcard only takes names of up to {} plain ASCII characters", MAX_NAME_LEN))
+        }
+        Some(_) => {}
+    }
+
+    let keys: Vec<String> = cosigners
+        .iter()
+        .map(|cosigner| {
+            let origin = format_path(&cosigner.path, None);
+            format!("[{}{}]{}/<0;1>/*", cosigner.fingerprint, &origin[1..], cosigner.xpub)
+        })
+        .collect();
+    let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&format.wrap(&format!("sortedmulti({},{})", threshold, keys.join(","))))
+        .map_err(|e| format!("Invalid multisig config: {}", e))?;
+    let receive = descriptor
+        .clone()
+        .into_single_descriptors()
+        .map_err(|e| format!("Invalid multisig config: {}", e))?
+        .remove(0);
+    let first_address = params.address(&derive_script(&receive, 0)?);
+    let shared = cosigners.iter().all(|cosigner| cosigner.path == first.path).then(|| format_path(&first.path, None));
+
+    Ok(MultisigConfig {
+        name,
+        threshold,
+        total,
+        format: format.name().to_string(),
+        script_type: format.script_type().to_string(),
+        network: params.target_name().to_string(),
+        derivation: shared,
+        cosigners: cosigners
+            .iter()
+            .map(|cosigner| MultisigCosigner {
+                fingerprint: cosigner.fingerprint.to_string(),
+                derivation: format_path(&cosigner.path, None),
+                xpub: cosigner.xpub.to_string(),
+                original_key: (cosigner.key != cosigner.xpub.to_string()).then(|| cosigner.key.clone()),
+            })
+            .collect(),
+        descriptor: descriptor.to_string(),
+        first_address,
+        warnings,
+    })
+}
+
+/// `2 of 3`, or `2/3`.
+fn parse_policy(value: &str) -> Result<(usize, usize), String> {
+    let lowered = value.to_ascii_lowercase();
+    let invalid = || format!("policy \"{}\" isn't of the form \"M of N\"", value);
+    let (m, n) = lowered.split_once(" of ").or_else(|| lowered.split_once('/')).ok_or_else(invalid)?;
+    let m: usize = m.trim().parse().map_err(|_| invalid())?;
+    let n: usize = n.trim().parse().map_err(|_| invalid())?;
+    if m == 0 || m > n {
+        return Err(format!("policy {} of {} needs at least one signature and no more than there are keys", m, n));
+    }
+    Ok((m, n))
+}
+
+fn write_config(policy_json: &str) -> Result<String, String> {
+    let policy: MultisigPolicy = parse_json_value(policy_json, "policy")?;
+    let (threshold, format, cosigners) = match (policy.cosigners, policy.descriptor) {
+        (Some(cosigners), _) => {
+            let threshold = policy.threshold.ok_or_else(|| "The policy needs a threshold".to_string())?;
+            let format = match policy.format.as_deref() {
+                Some(format) => Format::parse(format)
+                    .ok_or_else(|| format!("Unknown format \"{}\" (expected P2WSH, P2SH-P2WSH or P2SH)", format))?,
+                None => Format::P2wsh,
+            };
+            let cosigners = cosigners
+                .iter()
+                .enumerate()
+                .map(|(index, cosigner)| {
+                    let fingerprint = Fingerprint::from_str(cosigner.fingerprint.trim())
+                        .map_err(|_| format!("Cosigner {}: fingerprint \"{}\" isn't 8 hex digits", index, cosigner.fingerprint))?;
+                    let path = parse_path(&cosigner.derivation, false).map_err(|e| format!("Cosigner {}: {}", index, e))?.path;
+                    let (xpub, _, _) = standard_xpub(&sanitize(&cosigner.xpub, InputKind::Key)?).map_err(|e| format!("Cosigner {}: {}", index, e))?;
+                    Ok((fingerprint, path, xpub))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            (threshold, format, cosigners)
+        }
+        (None, Some(descriptor)) => {
+            let (threshold, format, cosigners) = from_descriptor(&descriptor)?;
+            if policy.threshold.is_some_and(|given| given != threshold) {
+                return Err(format!("The threshold doesn't match the descriptor's {}", threshold));
+            }
+            if policy.format.as_deref().is_some_and(|given| Format::parse(given) != Some(format)) {
+                return Err(format!("The format doesn't match the descriptor's {}", format.name()));
+            }
+            (threshold, format, cosigners)
+        }
+        (None, None) => return Err("The policy needs either cosigners or a descriptor".to_string()),
+    };
+
+    let name = policy.name.unwrap_or_else(|| format!("{}-of-{} multisig", threshold, cosigners.len()));
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !name.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+        return Err(format!("Coldcard only takes names of 1 to {} plain ASCII characters, not \"{}\"", MAX_NAME_LEN, name));
+    }
+
+    let mut text = format!(
+        "# Coldcard Multisig setup file (exported by BitLab)\n#\nName: {}\nPolicy: {} of {}\n",
+        name,
+        threshold,
+        cosigners.len()
+    );
+    let shared = cosigners.iter().all(|(_, path, _)| *path == cosigners[0].1);
+    if shared {
+        if let Some((_, path, _)) = cosigners.first() {
+            text.push_str(&format!("Derivation: {}\n", format_path(path, None)));
+        }
+    }
+    text.push_str(&format!("Format: {}\n", format.name()));
+    for (fingerprint, path, xpub) in &cosigners {
+        text.push('\n');
+        if !shared {
+            text.push_str(&format!("Derivation: {}\n", format_path(path, None)));
+        }
+        text.push_str(&format!("{}: {}", fingerprint.to_string().to_uppercase(), xpub));
+        if !shared {
+            text.push('\n');
+        }
+    }
+    if shared {
+        text.push('\n');
+    }
+    read_config(&text, None)?;
+    Ok(text)
+}
+
+/// The threshold, format and keys of a `sortedmulti` descriptor whose keys all have origins
+/// and end in `/<0;1>/*` (or `/0/*`), which is all a Coldcard file can describe.
+fn from_descriptor(descriptor: &str) -> Result<(usize, Format, Vec<KeyEntry>), String> {
+    let branches = parse_descriptors(descriptor)?;
+    let unsupported = || "Only wsh, sh(wsh) and sh descriptors of sortedmulti fit a Coldcard multisig file".to_string();
+    let (threshold, format, keys) = match &branches[0] {
+        Descriptor::Wsh(wsh) => match wsh.as_inner() {
+            WshInner::SortedMulti(multi) => (multi.k(), Format::P2wsh, multi.pks().to_vec()),
+            _ => return Err(unsupported()),
+        },
+        Descriptor::Sh(sh) => match sh.as_inner() {
+            ShInner::SortedMulti(multi) => (multi.k(), Format::P2sh, multi.pks().to_vec()),
+            ShInner::Wsh(wsh) => match wsh.as_inner() {
+                WshInner::SortedMulti(multi) => (multi.k(), Format::P2shP2wsh, multi.pks().to_vec()),
+                _ => return Err(unsupported()),
+            },
+            _ => return Err(unsupported()),
+        },
+        _ => return Err(unsupported()),
+    };
+
+    let receive = DerivationPath::from(vec![ChildNumber::Normal { index: 0 }]);
+    let cosigners = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| match key {
+            DescriptorPublicKey::XPub(xkey) if xkey.derivation_path == receive && xkey.wildcard == Wildcard::Unhardened => {
+                let (fingerprint, path) = xkey
+                    .origin
+                    .clone()
+                    .ok_or_else(|| format!("Key {} has no [fingerprint/path] origin, which the file needs", index))?;
+                Ok((fingerprint, path, xkey.xkey))
+            }
+            _ => Err(format!("Key {} isn't an xpub ending in /<0;1>/*, which is all the file can describe", index)),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if branches.len() > 2 {
+        return Err("A Coldcard wallet has a receive and a change branch, not more".to_string());
+    }
+    Ok((threshold, format, cosigners))
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{hash160, ripemd160, sha256};
+use bitcoin::{PublicKey, XOnlyPublicKey};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey, WshInner};
+use miniscript::policy::{Concrete, Liftable, Semantic};
+use miniscript::{hash256, Miniscript, MiniscriptKey, ScriptContext, Segwitv0, Tap, Translator};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::json::to_json;
+
+/// BIP341's provably unspendable "H" point, used as the internal key when no single key can take the key path.
+pub const NUMS_POINT: &str = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+const MAX_SPENDING_PATHS: usize = 64;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompiledPolicy {
+    pub context: String,
+    pub descriptor: String,
+    pub miniscript: Option<String>,
+    #[serde(alias = "internal_key")]
+    pub internal_key: Option<String>,
+    pub leaves: Vec<String>,
+    #[serde(alias = "script_hex")]
+    pub script_hex: Option<String>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
+    pub address: Option<String>,
+    #[serde(alias = "max_satisfaction_weight")]
+    pub max_satisfaction_weight: Option<u64>,
+    #[serde(alias = "spending_paths")]
+    pub spending_paths: Vec<Vec<String>>,
+    #[serde(alias = "spending_paths_truncated")]
+    pub spending_paths_truncated: bool,
+    #[serde(alias = "placeholder_keys")]
+    pub placeholder_keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MiniscriptAnalysis {
+    pub miniscript: String,
+    pub context: String,
+    pub sane: bool,
+    #[serde(alias = "sanity_error")]
+    pub sanity_error: Option<String>,
+    #[serde(alias = "requires_signature")]
+    pub requires_signature: bool,
+    #[serde(alias = "non_malleable")]
+    pub non_malleable: bool,
+    #[serde(alias = "within_resource_limits")]
+    pub within_resource_limits: bool,
+    #[serde(alias = "has_mixed_timelocks")]
+    pub has_mixed_timelocks: bool,
+    #[serde(alias = "has_repeated_keys")]
+    pub has_repeated_keys: bool,
+    #[serde(alias = "max_satisfaction_size")]
+    pub max_satisfaction_size: Option<usize>,
+    #[serde(alias = "script_hex")]
+    pub script_hex: Option<String>,
+    pub policy: Option<String>,
+    #[serde(alias = "spending_paths")]
+    pub spending_paths: Vec<Vec<String>>,
+    #[serde(alias = "spending_paths_truncated")]
+    pub spending_paths_truncated: bool,
+}
+
+/// Compiles a concrete policy such as `or(pk(A),and(pk(B),older(144)))` for `wsh` or `tap`.
+/// Keys may be real keys (hex, xpub with origin/derivation) or names resolved through
+/// `keys_json`. Names left unresolved still compile, but without a script or address.
+#[wasm_bindgen]
+pub fn compile_policy(
+    policy_string: &str,
+    context: &str,
+    network: Option<String>,
+    keys_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let keys: HashMap<String, String> = match keys_json {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid keys JSON (expected {{\"name\": \"key\"}}): {}", e)))?,
+        _ => HashMap::new(),
+    };
+    let context = parse_context(context).map_err(|e| JsValue::from_str(&e))?;
+
+    let policy = Concrete::<String>::from_str(policy_string.trim())
+        .map_err(|e| JsValue::from_str(&format!("Invalid policy: {}", e)))?;
+    policy.is_valid().map_err(|e| JsValue::from_str(&format!("Invalid policy: {}", e)))?;
+
+    let mut placeholders: Vec<String> = policy
+        .keys()
+        .into_iter()
+        .filter(|name| !keys.contains_key(*name) && parse_key(name, &network).is_err())
+        .cloned()
+        .collect();
+    placeholders.sort();
+    placeholders.dedup();
+
+    let result = if placeholders.is_empty() {
+        let resolved = policy.translate_pk(&mut KeyResolver { keys: &keys, network: &network }).map_err(|e| JsValue::from_str(&e))?;
+        let nums = DescriptorPublicKey::from_str(NUMS_POINT).expect("valid NUMS point");
+        let descriptor = compile_descriptor(&resolved, context, nums).map_err(|e| JsValue::from_str(&e))?;
+        let mut compiled = describe(&resolved, &descriptor, context);
+        add_script_details(&mut compiled, &descriptor, &network).map_err(|e| JsValue::from_str(&e))?;
+        compiled
+    } else {
+        let descriptor = compile_descriptor(&policy, context, "NUMS".to_string()).map_err(|e| JsValue::from_str(&e))?;
+        let mut compiled = describe(&policy, &descriptor, context);
+        compiled.placeholder_keys = placeholders;
+        compiled
+    };
+
+    to_json(&result, compact)
+}
+
+/// Type-checks a miniscript expression and reports the analysis flags plus every way to spend it.
+/// Insane expressions are analyzed rather than rejected, with the reason in `sanity_error`.
+#[wasm_bindgen]
+pub fn analyze_miniscript(ms_string: &str, context: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let context = parse_context(context.as_deref().unwrap_or("wsh")).map_err(|e| JsValue::from_str(&e))?;
+    let ms_string = ms_string.trim();
+
+    let analysis = match context {
+        "wsh" => analyze::<Segwitv0>(ms_string, context, |s| {
+            Miniscript::<PublicKey, Segwitv0>::from_str_insane(s).ok().map(|ms| ms.encode())
+        }),
+        _ => analyze::<Tap>(ms_string, context, |s| {
+            Miniscript::<XOnlyPublicKey, Tap>::from_str_insane(s).ok().map(|ms| ms.encode())
+        }),
+    }
+    .map_err(|e| JsValue::from_str(&e))?;
+
+    to_json(&analysis, compact)
+}
+
+fn parse_context(context: &str) -> Result<&'static str, String> {
+    match context.trim().to_ascii_lowercase().as_str() {
+        "wsh" | "segwitv0" | "p2wsh" => Ok("wsh"),
+        "tap" | "tr" | "taproot" | "p2tr" => Ok("tap"),
+        other => Err(format!("Unknown context \"{}\" (expected wsh or tap)", other)),
+    }
+}
+
+fn compile_descriptor<Pk: MiniscriptKey>(
+    policy: &Concrete<Pk>,
+    context: &str,
+    unspendable: Pk,
+) -> Result<Descriptor<Pk>, String> {
+    match context {
+        "wsh" => {
+            let ms = policy.compile::<Segwitv0>().map_err(|e| format!("Cannot compile policy: {}", e))?;
+            Descriptor::new_wsh(ms).map_err(|e| format!("Cannot compile policy: {}", e))
+        }
+        _ => policy.compile_tr(Some(unspendable)).map_err(|e| format!("Cannot compile policy: {}", e)),
+    }
+}
+
+fn describe<Pk: MiniscriptKey>(policy: &Concrete<Pk>, descriptor: &Descriptor<Pk>, context: &str) -> CompiledPolicy {
+    let (miniscript, internal_key, leaves) = match descriptor {
+        Descriptor::Wsh(wsh) => {
+            let miniscript = match wsh.as_inner() {
+                WshInner::Ms(ms) => ms.to_string(),
+                WshInner::SortedMulti(multi) => multi.to_string(),
+            };
+            (Some(miniscript), None, Vec::new())
+        }
+        Descriptor::Tr(tr) => (
+            None,
+            Some(tr.internal_key().to_string()),
+            tr.iter_scripts().map(|(_, ms)| ms.to_string()).collect(),
+        ),
+        _ => (None, None, Vec::new()),
+    };
+    let (spending_paths, spending_paths_truncated) = match policy.lift() {
+        Ok(semantic) => spending_paths(&semantic.normalized()),
+        Err(_) => (Vec::new(), false),
+    };
+
+    CompiledPolicy {
+        context: context.to_string(),
+        descriptor: descriptor.to_string(),
+        miniscript,
+        internal_key,
+        leaves,
+        script_hex: None,
+        script_pubkey: None,
+        address: None,
+        max_satisfaction_weight: descriptor.max_weight_to_satisfy().ok().map(|weight| weight.to_wu()),
+        spending_paths,
+        spending_paths_truncated,
+        placeholder_keys: Vec::new(),
+    }
+}
+
+/// Wildcard keys are derived at index 0 for the script and address.
+fn add_script_details(
+    compiled: &mut CompiledPolicy,
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    network: &NetworkParams,
+) -> Result<(), String> {
+    let definite = descriptor
+        .at_derivation_index(0)
+        .map_err(|e| format!("Cannot derive descriptor: {}", e))?;
+    compiled.script_hex = definite.explicit_script().ok().map(|script| bytes_to_hex(script.as_bytes()));
+    compiled.script_pubkey = Some(bytes_to_hex(definite.script_pubkey().as_bytes()));
+    compiled.address = Some(
+        network
+            .address(&definite.script_pubkey())
+            .ok_or_else(|| "Cannot derive address".to_string())?,
+    );
+    Ok(())
+}
+
+fn analyze<Ctx: ScriptContext>(
+    ms_string: &str,
+    context: &str,
+    encode: impl Fn(&str) -> Option<bitcoin::ScriptBuf>,
+) -> Result<MiniscriptAnalysis, String> {
+    let ms = Miniscript::<String, Ctx>::from_str_insane(ms_string).map_err(|e| format!("Invalid miniscript: {}", e))?;
+    let sanity_error = ms.sanity_check().err().map(|e| e.to_string());
+    let semantic = ms.lift().ok().map(|policy| policy.normalized());
+    let (spending_paths, spending_paths_truncated) = semantic.as_ref().map(spending_paths).unwrap_or_default();
+
+    Ok(MiniscriptAnalysis {
+        miniscript: ms.to_string(),
+        context: context.to_string(),
+        sane: sanity_error.is_none(),
+        sanity_error,
+        requires_signature: ms.requires_sig(),
+        non_malleable: ms.is_non_malleable(),
+        within_resource_limits: ms.within_resource_limits(),
+        has_mixed_timelocks: ms.has_mixed_timelocks(),
+        has_repeated_keys: ms.has_repeated_keys(),
+        max_satisfaction_size: ms.max_satisfaction_size().ok(),
+        script_hex: encode(ms_string).map(|script| bytes_to_hex(script.as_bytes())),
+        policy: semantic.map(|policy| policy.to_string()),
+        spending_paths,
+        spending_paths_truncated,
+    })
+}
+
+/// Every minimal set of conditions that satisfies the policy, e.g. `[["pk(A)"], ["pk(B)", "older(144)"]]`.
+fn spending_paths<Pk: MiniscriptKey>(policy: &Semantic<Pk>) -> (Vec<Vec<String>>, bool) {
+    let mut truncated = false;
+    let paths = expand(policy, &mut truncated);
+    (paths, truncated)
+}
+
+fn expand<Pk: MiniscriptKey>(policy: &Semantic<Pk>, truncated: &mut bool) -> Vec<Vec<String>> {
+    match policy {
+        Semantic::Unsatisfiable => Vec::new(),
+        Semantic::Trivial => vec![Vec::new()],
+        Semantic::Thresh(thresh) => {
+            let children: Vec<Vec<Vec<String>>> = thresh.iter().map(|child| expand(child, truncated)).collect();
+            let mut paths = Vec::new();
+            for combination in combinations(children.len(), thresh.k()) {
+                let mut partial: Vec<Vec<String>> = vec![Vec::new()];
+                for &child in &combination {
+                    partial = partial
+                        .iter()
+                        .flat_map(|prefix| {
+                            children[child].iter().map(move |path| {
+                                let mut joined = prefix.clone();
+                                joined.extend(path.iter().cloned());
+                                joined
+                            })
+                        })
+                        .collect();
+                    if partial.len() > MAX_SPENDING_PATHS {
+                        partial.truncate(MAX_SPENDING_PATHS);
+                        *truncated = true;
+                    }
+                }
+                paths.extend(partial);
+                if paths.len() >= MAX_SPENDING_PATHS {
+                    paths.truncate(MAX_SPENDING_PATHS);
+                    *truncated = true;
+                    break;
+                }
+            }
+            paths
+        }
+        atom => vec![vec![atom.to_string()]],
+    }
+}
+
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    if k > n {
+        return Vec::new();
+    }
+    let mut result = Vec::new();
+    let mut current: Vec<usize> = (0..k).collect();
+    loop {
+        result.push(current.clone());
+        if result.len() >= MAX_SPENDING_PATHS {
+            return result;
+        }
+        let Some(position) = (0..k).rev().find(|&i| current[i] < n - k + i) else {
+            return result;
+        };
+        current[position] += 1;
+        for i in position + 1..k {
+            current[i] = current[i - 1] + 1;
+        }
+    }
+}
+
+/// Extended keys may use the network's custom version bytes.
+fn parse_key(key: &str, network: &NetworkParams) -> Result<DescriptorPublicKey, String> {
+    DescriptorPublicKey::from_str(&network.standard_extended_key(key)).map_err(|e| e.to_string())
+}
+
+/// Resolves policy key names through the supplied map, or parses them as keys directly.
+struct KeyResolver<'a> {
+    keys: &'a HashMap<String, String>,
+    network: &'a NetworkParams,
+}
+
+impl Translator<String, DescriptorPublicKey, String> for KeyResolver<'_> {
+    fn pk(&mut self, pk: &String) -> Result<DescriptorPublicKey, String> {
+        match self.keys.get(pk) {
+            Some(key) => parse_key(key, self.network).map_err(|e| format!("Invalid key for {}: {}", pk, e)),
+            None => parse_key(pk, self.network).map_err(|e| format!("Invalid key {}: {}", pk, e)),
+        }
+    }
+
+    fn sha256(&mut self, hash: &String) -> Result<sha256::Hash, String> {
+        sha256::Hash::from_str(hash).map_err(|e| format!("Invalid sha256 hash {}: {}", hash, e))
+    }
+
+    fn hash256(&mut self, hash: &String) -> Result<hash256::Hash, String> {
+        hash256::Hash::from_str(hash).map_err(|e| format!("Invalid hash256 hash {}: {}", hash, e))
+    }
+
+    fn ripemd160(&mut self, hash: &String) -> Result<ripemd160::Hash, String> {
+        ripemd160::Hash::from_str(hash).map_err(|e| format!("Invalid ripemd160 hash {}: {}", hash, e))
+    }
+
+    fn hash160(&mut self, hash: &String) -> Result<hash160::Hash, String> {
+        hash160::Hash::from_str(hash).map_err(|e| format!("Invalid hash160 hash {}: {}", hash, e))
+    }
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{Address, NetworkKind, PrivateKey, PublicKey, Transaction};
+use crate::script::output_script_type;
+use crate::utils::base64::{decode_base64, encode_base64};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::network::address_network_label;
+use crate::utils::sanitize::{sanitize_with_warnings, InputKind};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+#[cfg(feature = "wallet")]
+use crate::wallet::electrum::electrum_seed_type;
+#[cfg(feature = "wallet")]
+use crate::wallet::mnemonic::{check_phrase, available_languages, WORD_COUNTS};
+use crate::wallet::xkey::xkey_flavour;
+
+const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+/// One reading of the input. Fields that don't apply to `type` are `null`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectedType {
+    #[serde(rename = "type")]
+    pub input_type: String,
+    /// 1 when the input parses and checksums as this type; lower for a partial match or
+    /// when several types parse equally well.
+    pub score: f64,
+    /// The input re-encoded the standard way, when that's cheap. Secrets come back as
+    /// they'd be entered, so don't log the result.
+    pub canonical: Option<String>,
+    pub network: Option<String>,
+    #[serde(alias = "word_count")]
+    pub word_count: Option<usize>,
+    pub language: Option<String>,
+    #[serde(alias = "seed_type")]
+    pub seed_type: Option<String>,
+    pub compressed: Option<bool>,
+    #[serde(alias = "script_type")]
+    pub script_type: Option<String>,
+    /// The SLIP-132 prefix of an extended key (e.g. `zpub`).
+    pub prefix: Option<String>,
+    /// `hex` or `base64`, for PSBTs.
+    pub format: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDetection {
+    /// The best-scoring candidate's type, or `unknown`.
+    #[serde(rename = "type")]
+    pub input_type: String,
+    /// More than one candidate scored 0.5 or more, so the UI should ask.
+    pub ambiguous: bool,
+    /// Best first.
+    pub candidates: Vec<DetectedType>,
+    /// Invisible characters removed from the input before detection.
+    pub warnings: Vec<String>,
+}
+
+/// Classifies pasted text for a universal import box: a BIP39 or Electrum mnemonic, a
+/// WIF or hex private key, an extended key, a descriptor, an address, a PSBT, a raw
+/// transaction, a public key or a txid. Input that fits several stays ranked rather than
+/// being settled silently, e.g. 64 hex digits are both a private key and a txid.
+#[wasm_bindgen]
+pub fn detect_input_type(text: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("detect_input_type", text.len(), || {
+        let (cleaned, warnings) = sanitize_with_warnings(text, InputKind::Text).map_err(|e| JsValue::from_str(&e))?;
+        let mut candidates = detect(cleaned.trim());
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        to_json(&InputDetection {
+            input_type: candidates.first().map_or_else(|| "unknown".to_string(), |candidate| candidate.input_type.clone()),
+            ambiguous: candidates.iter().filter(|candidate| candidate.score >= 0.5).count() > 1,
+            candidates,
+            warnings,
+        }, compact)
+    })
+}
+
+fn detect(text: &str) -> Vec<DetectedType> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    if let Some(descriptor) = detect_descriptor(text) {
+        return vec![descriptor];
+    }
+    if text.split_whitespace().nth(1).is_some() {
+        return detect_phrase(text);
+    }
+    if let Some(psbt) = detect_psbt(text) {
+        return vec![psbt];
+    }
+    if text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return detect_hex(text);
+    }
+    if let Some((prefix, network, script_type, private)) = xkey_flavour(text) {
+        return vec![DetectedType {
+            script_type: Some(script_type.to_string()),
+            prefix: Some(prefix),
+            network: Some(network.to_string()),
+            canonical: Some(text.to_string()),
+            ..candidate(if private { "xprv" } else { "xpub" }, 1.0)
+        }];
+    }
+    if let Ok(key) = PrivateKey::from_wif(text) {
+        return vec![DetectedType {
+            canonical: Some(key.to_wif()),
+            network: Some(wif_network(key.network).to_string()),
+            compressed: Some(key.compressed),
+            ..candidate("wif", 1.0)
+        }];
+    }
+    if let Ok(address) = text.parse::<Address<NetworkUnchecked>>() {
+        let label = address_network_label(&address);
+        let script = address.assume_checked_ref().script_pubkey();
+        return vec![DetectedType {
+            canonical: Some(address.assume_checked_ref().to_string()),
+            network: Some(label.to_string()),
+            script_type: Some(output_script_type(&script).to_string()),
+            ..candidate("address", 1.0)
+        }];
+    }
+    Vec::new()
+}
+
+#[cfg(feature = "wallet")]
+fn detect_phrase(text: &str) -> Vec<DetectedType> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut candidates = Vec::new();
+    let (matches, error) = check_phrase(text, &available_languages());
+    for (language, spelled) in &matches {
+        candidates.push(DetectedType {
+            canonical: Some(spelled.join(language.separator())),
+            word_count: Some(spelled.len()),
+            language: Some(language.name().to_string()),
+            ..candidate("bip39_mnemonic", 1.0)
+        });
+    }
+    if let Some(seed_type) = electrum_seed_type(text) {
+        let lowered: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+        candidates.push(DetectedType {
+            canonical: Some(lowered.join(" ")),
+            word_count: Some(words.len()),
+            seed_type: Some(seed_type.name().to_string()),
+            ..candidate("electrum_mnemonic", 1.0)
+        });
+    }
+    // Several languages, or BIP39 and Electrum at once, are equally good readings.
+    if candidates.len() > 1 {
+        let score = 1.0 / candidates.len() as f64;
+        candidates.iter_mut().for_each(|candidate| candidate.score = score);
+    }
+    let looks_like_words = words.iter().all(|word| word.chars().all(char::is_alphabetic));
+    if candidates.is_empty() && looks_like_words && WORD_COUNTS.contains(&words.len()) {
+        candidates.push(DetectedType {
+            word_count: Some(words.len()),
+            note: error.map(|error| format!("not a valid mnemonic: {}", error)),
+            ..candidate("bip39_mnemonic", 0.3)
+        });
+    }
+    candidates
+}
+
+/// Without the wordlists, a phrase of mnemonic length is recognized but not checked.
+#[cfg(not(feature = "wallet"))]
+fn detect_phrase(text: &str) -> Vec<DetectedType> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let looks_like_words = words.iter().all(|word| word.chars().all(char::is_alphabetic));
+    if !looks_like_words || ![12, 15, 18, 21, 24].contains(&words.len()) {
+        return Vec::new();
+    }
+    vec![DetectedType {
+        word_count: Some(words.len()),
+        note: Some("a word list; this build can't check it".to_string()),
+        ..candidate("bip39_mnemonic", 0.5)
+    }]
+}
+
+fn detect_hex(hex: &str) -> Vec<DetectedType> {
+    let Ok(bytes) = hex_to_bytes(hex, HexMode::Strict) else {
+        return Vec::new();
+    };
+    let canonical = Some(bytes_to_hex(&bytes));
+    match bytes.len() {
+        32 => {
+            // 32 leading zero bits are what a block hash has and a random key almost never does.
+            let (key_score, txid_score) = if bytes[..4] == [0; 4] { (0.2, 0.8) } else { (0.5, 0.5) };
+            let mut candidates = Vec::new();
+            if SecretKey::from_slice(&bytes).is_ok() {
+                candidates.push(DetectedType { canonical: canonical.clone(), ..candidate("hex_private_key", key_score) });
+            }
+            candidates.push(DetectedType {
+                canonical,
+                note: Some("a txid or block hash in display byte order, or another 32-byte hash".to_string()),
+                ..candidate("txid", txid_score)
+            });
+            candidates
+        }
+        33 | 65 if PublicKey::from_slice(&bytes).is_ok() => vec![DetectedType {
+            canonical,
+            compressed: Some(bytes.len() == 33),
+            ..candidate("public_key", 1.0)
+        }],
+        _ => match bitcoin::consensus::deserialize::<Transaction>(&bytes) {
+            Ok(tx) => vec![DetectedType {
+                canonical,
+                note: Some(format!("txid {}", tx.compute_txid())),
+                ..candidate("transaction", 1.0)
+            }],
+            Err(_) => Vec::new(),
+        },
+    }
+}
+
+fn detect_psbt(text: &str) -> Option<DetectedType> {
+    let (format, bytes) = if text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        ("hex", hex_to_bytes(text, HexMode::Strict).ok()?)
+    } else {
+        ("base64", decode_base64(text).ok()?)
+    };
+    if !bytes.starts_with(&PSBT_MAGIC) {
+        return None;
+    }
+    Some(DetectedType {
+        canonical: Some(encode_base64(&bytes, false)),
+        format: Some(format.to_string()),
+        ..candidate("psbt", 1.0)
+    })
+}
+
+#[cfg(feature = "wallet")]
+fn detect_descriptor(text: &str) -> Option<DetectedType> {
+    let prefix_len = text.find('(')?;
+    if prefix_len == 0 || !text[..prefix_len].bytes().all(|b| b.is_ascii_lowercase() || b == b'_') {
+        return None;
+    }
+    Some(match crate::descriptor::parse_descriptors(text) {
+        Ok(descriptors) => DetectedType {
+            canonical: Some(descriptors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")),
+            note: (descriptors.len() > 1).then(|| format!("a multipath descriptor with {} branches", descriptors.len())),
+            ..candidate("descriptor", 1.0)
+        },
+        Err(error) => DetectedType { note: Some(format!("not a valid descriptor: {}", error)), ..candidate("descriptor", 0.3) },
+    })
+}
+
+/// Without the descriptor parser, descriptor syntax is recognized but not checked.
+#[cfg(not(feature = "wallet"))]
+fn detect_descriptor(text: &str) -> Option<DetectedType> {
+    let prefix_len = text.find('(')?;
+    if prefix_len == 0 || !text[..prefix_len].bytes().all(|b| b.is_ascii_lowercase() || b == b'_') || !text.contains(')') {
+        return None;
+    }
+    Some(DetectedType { note: Some("descriptor syntax; this build can't check it".to_string()), ..candidate("descriptor", 0.5) })
+}
+
+fn wif_network(network: NetworkKind) -> &'static str {
+    match network {
+        NetworkKind::Main => "mainnet",
+        NetworkKind::Test => "testnet/signet/regtest",
+    }
+}
+
+fn candidate(input_type: &str, score: f64) -> DetectedType {
+    DetectedType {
+        input_type: input_type.to_string(),
+        score,
+        canonical: None,
+        network: None,
+        word_count: None,
+        language: None,
+        seed_type: None,
+        compressed: None,
+        script_type: None,
+        prefix: None,
+        format: None,
+        note: None,
+    }
+}
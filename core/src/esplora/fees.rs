@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use crate::utils::json::to_json;
+use crate::utils::units::FeeRate;
+
+/// Core's default `-minrelaytxfee`, what a transaction pays when the whole mempool
+/// fits in the target anyway.
+const MIN_RELAY_FEE: f64 = 1.0;
+const BLOCK_VBYTES: f64 = 1_000_000.0;
+
+/// Mempool vsize paying at least `fee_rate` and less than the previous (higher) bin's rate.
+struct Bin {
+    fee_rate: f64,
+    vsize: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramEstimate {
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: FeeRate,
+    #[serde(alias = "target_vbytes")]
+    pub target_vbytes: u64,
+    /// The most mempool vsize that can be ahead of a transaction paying `fee_rate_sat_vb`.
+    #[serde(alias = "vbytes_ahead")]
+    pub vbytes_ahead: u64,
+    #[serde(alias = "mempool_vbytes")]
+    pub mempool_vbytes: u64,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MempoolPosition {
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: FeeRate,
+    /// Mempool vsize certainly paying more, and what could be: the bin the rate falls
+    /// in may pay either side of it.
+    #[serde(alias = "vbytes_ahead_min")]
+    pub vbytes_ahead_min: u64,
+    #[serde(alias = "vbytes_ahead_max")]
+    pub vbytes_ahead_max: u64,
+    /// The projected block it would land in at worst, 1 being the next.
+    #[serde(alias = "blocks_from_tip")]
+    pub blocks_from_tip: u64,
+    #[serde(alias = "mempool_vbytes")]
+    pub mempool_vbytes: u64,
+}
+
+/// The lowest feerate that puts a transaction within the first `target_vbytes_from_tip`
+/// vbytes of the mempool (1,000,000 per block). Feerates inside a histogram bin aren't
+/// known, so the answer is a bin boundary and overshoots by at most one bin.
+#[wasm_bindgen]
+pub fn estimate_feerate_from_histogram(histogram_json: &str, target_vbytes_from_tip: f64, compact: Option<bool>) -> Result<String, JsValue> {
+    if !target_vbytes_from_tip.is_finite() || target_vbytes_from_tip <= 0.0 {
+        return Err(JsValue::from_str("target_vbytes_from_tip must be a positive number of vbytes"));
+    }
+    let bins = parse_histogram(histogram_json).map_err(|e| JsValue::from_str(&e))?;
+    let total: f64 = bins.iter().map(|bin| bin.vsize).sum();
+
+    let mut warnings = Vec::new();
+    let (fee_rate, ahead) = if total <= target_vbytes_from_tip {
+        (MIN_RELAY_FEE, total)
+    } else {
+        // Paying a bin's lower bound keeps everything down to and including that bin
+        // possibly ahead; take the lowest bound for which that still fits.
+        let mut best = None;
+        let mut ahead = 0.0;
+        for bin in &bins {
+            ahead += bin.vsize;
+            if ahead > target_vbytes_from_tip {
+                break;
+            }
+            best = Some((bin.fee_rate, ahead));
+        }
+        best.unwrap_or_else(|| {
+            let top = &bins[0];
+            warnings.push(format!(
+                "The target falls inside the top bin ({} vB paying {} sat/vB or more), whose upper end the histogram doesn't give; paying {} sat/vB may not be enough",
+                top.vsize, top.fee_rate, top.fee_rate
+            ));
+            (top.fee_rate, top.vsize)
+        })
+    };
+
+    let estimate = HistogramEstimate {
+        fee_rate: FeeRate::from_sat_per_vb(fee_rate)?,
+        target_vbytes: target_vbytes_from_tip as u64,
+        vbytes_ahead: ahead as u64,
+        mempool_vbytes: total as u64,
+        warnings,
+    };
+    to_json(&estimate, compact)
+}
+
+/// Where a transaction paying `fee_rate_sat_vb` would sit in the mempool right now.
+#[wasm_bindgen]
+pub fn position_in_mempool(histogram_json: &str, fee_rate_sat_vb: f64, compact: Option<bool>) -> Result<String, JsValue> {
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+    let bins = parse_histogram(histogram_json).map_err(|e| JsValue::from_str(&e))?;
+
+    let mut ahead_min = 0.0;
+    let mut ahead_max = 0.0;
+    let mut upper = f64::INFINITY;
+    for bin in &bins {
+        if bin.fee_rate > fee_rate.sat_per_vb() {
+            ahead_min += bin.vsize;
+            ahead_max += bin.vsize;
+        } else if fee_rate.sat_per_vb() < upper {
+            ahead_max += bin.vsize;
+        }
+        upper = bin.fee_rate;
+    }
+
+    let position = MempoolPosition {
+        fee_rate,
+        vbytes_ahead_min: ahead_min as u64,
+        vbytes_ahead_max: ahead_max as u64,
+        blocks_from_tip: (ahead_max / BLOCK_VBYTES).floor() as u64 + 1,
+        mempool_vbytes: bins.iter().map(|bin| bin.vsize).sum::<f64>() as u64,
+    };
+    to_json(&position, compact)
+}
+
+/// Accepts Esplora's `fee_histogram` (`[[fee_rate, vsize], ...]`), the whole `/mempool`
+/// response carrying it, or mempool.space's projected blocks (`/api/v1/fees/mempool-blocks`),
+/// each of which becomes one bin at its minimum feerate. Bins come back highest first,
+/// with equal rates merged.
+fn parse_histogram(json: &str) -> Result<Vec<Bin>, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid histogram JSON: {}", e))?;
+    let entries = match &value {
+        Value::Object(object) => match object.get("fee_histogram") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err("Histogram object must carry a fee_histogram array".to_string()),
+        },
+        Value::Array(entries) => entries,
+        _ => return Err("Histogram must be a JSON array or an object with fee_histogram".to_string()),
+    };
+
+    let mut bins = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let number = |value: Option<&Value>, what: &str| {
+            value
+                .and_then(Value::as_f64)
+                .filter(|n| n.is_finite() && *n >= 0.0)
+                .ok_or_else(|| format!("histogram[{}]: {} must be a non-negative number", index, what))
+        };
+        let bin = match entry {
+            Value::Array(pair) if pair.len() == 2 => Bin { fee_rate: number(pair.first(), "fee rate")?, vsize: number(pair.get(1), "vsize")? },
+            Value::Object(block) => Bin {
+                fee_rate: number(block.get("feeRange").and_then(|range| range.get(0)), "feeRange[0]")?,
+                vsize: number(block.get("blockVSize"), "blockVSize")?,
+            },
+            _ => {
+                return Err(format!(
+                    "histogram[{}] must be a [fee_rate, vsize] pair or a projected block with feeRange and blockVSize",
+                    index
+                ))
+            }
+        };
+        bins.push(bin);
+    }
+
+    bins.sort_by(|a, b| b.fee_rate.total_cmp(&a.fee_rate));
+    let mut merged: Vec<Bin> = Vec::with_capacity(bins.len());
+    for bin in bins {
+        match merged.last_mut() {
+            Some(last) if last.fee_rate == bin.fee_rate => last.vsize += bin.vsize,
+            _ => merged.push(bin),
+        }
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blockstream Esplora's `/mempool`, as recorded in its API docs.
+    const MEMPOOL: &str = r#"{
+        "count": 8134,
+        "vsize": 3444604,
+        "total_fee": 29204625,
+        "fee_histogram": [[53.01, 102131], [38.56, 110990], [34.12, 138976], [24.34, 112619], [3.16, 246346], [2.92, 239701], [1.1, 775272]]
+    }"#;
+
+    fn estimate(target: f64) -> HistogramEstimate {
+        serde_json::from_str(&estimate_feerate_from_histogram(MEMPOOL, target, None).unwrap()).unwrap()
+    }
+
+    fn position(fee_rate: f64) -> MempoolPosition {
+        serde_json::from_str(&position_in_mempool(MEMPOOL, fee_rate, None).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_the_recorded_histogram() {
+        let bins = parse_histogram(MEMPOOL).unwrap();
+        let rates: Vec<f64> = bins.iter().map(|bin| bin.fee_rate).collect();
+        assert_eq!(rates, [53.01, 38.56, 34.12, 24.34, 3.16, 2.92, 1.1]);
+        assert_eq!(bins.iter().map(|bin| bin.vsize).sum::<f64>(), 1_726_035.0);
+        // The bare array, reordered and with a split bin, reads the same.
+        let bare = parse_histogram("[[1.1, 775272], [2.92, 239701], [53.01, 100000], [53.01, 2131]]").unwrap();
+        assert_eq!(bare.len(), 3);
+        assert_eq!((bare[0].fee_rate, bare[0].vsize), (53.01, 102_131.0));
+    }
+
+    #[test]
+    fn bucket_rates_for_targets() {
+        let next_block = estimate(1_000_000.0);
+        assert_eq!((next_block.fee_rate.sat_per_vb(), next_block.vbytes_ahead), (2.92, 950_763));
+        assert_eq!(next_block.mempool_vbytes, 1_726_035);
+        assert!(next_block.warnings.is_empty());
+
+        let half_block = estimate(500_000.0);
+        assert_eq!((half_block.fee_rate.sat_per_vb(), half_block.vbytes_ahead), (24.34, 464_716));
+
+        // Inside the top bin the histogram can't say more than its lower bound.
+        let top = estimate(100_000.0);
+        assert_eq!((top.fee_rate.sat_per_vb(), top.vbytes_ahead), (53.01, 102_131));
+        assert_eq!(top.warnings.len(), 1);
+
+        let everything = estimate(2_000_000.0);
+        assert_eq!((everything.fee_rate.sat_per_vb(), everything.vbytes_ahead), (MIN_RELAY_FEE, 1_726_035));
+    }
+
+    #[test]
+    fn positions_in_the_recorded_mempool() {
+        let mid = position(30.0);
+        assert_eq!((mid.vbytes_ahead_min, mid.vbytes_ahead_max, mid.blocks_from_tip), (352_097, 464_716, 1));
+
+        let low = position(2.0);
+        assert_eq!((low.vbytes_ahead_min, low.vbytes_ahead_max, low.blocks_from_tip), (950_763, 1_726_035, 2));
+
+        // The top bin has no upper end, so all of it may still pay more.
+        let high = position(60.0);
+        assert_eq!((high.vbytes_ahead_min, high.vbytes_ahead_max), (0, 102_131));
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::esplora::{EsploraTx, EsploraVout};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::to_json_with_amounts;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletEvent {
+    pub txid: String,
+    pub direction: String,
+    #[serde(with = "crate::utils::amount::signed", alias = "net_amount")]
+    pub net_amount: i64,
+    pub fee: Option<u64>,
+    pub counterparties: Vec<String>,
+    pub confirmed: bool,
+    pub height: Option<u32>,
+    pub timestamp: Option<u64>,
+}
+
+#[wasm_bindgen]
+pub fn parse_address_history(esplora_txs_json: &str, owned_scripts_json: &str, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let txs: Vec<EsploraTx> = serde_json::from_str(esplora_txs_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid Esplora transactions JSON: {}", e)))?;
+    let owned: Vec<String> = serde_json::from_str(owned_scripts_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid owned scripts JSON: {}", e)))?;
+    let owned: HashSet<String> = owned.into_iter().map(|script| script.trim().to_ascii_lowercase()).collect();
+
+    let events: Vec<WalletEvent> = txs.iter().filter_map(|tx| wallet_event(tx, &owned)).collect();
+
+    to_json_with_amounts(&events, amount_format, compact)
+}
+
+/// Net effect of `tx` on the owned scripts, or `None` if it touches none of them.
+pub fn wallet_event(tx: &EsploraTx, owned: &HashSet<String>) -> Option<WalletEvent> {
+    let is_owned = |output: &EsploraVout| owned.contains(&output.scriptpubkey.to_ascii_lowercase());
+    let prevouts: Vec<&EsploraVout> = tx.vin.iter().filter_map(|vin| vin.prevout.as_ref()).collect();
+
+    let owned_in: u64 = prevouts.iter().filter(|p| is_owned(p)).map(|p| p.value).sum();
+    let owned_out: u64 = tx.vout.iter().filter(|o| is_owned(o)).map(|o| o.value).sum();
+    let any_input_owned = prevouts.iter().any(|p| is_owned(p));
+    let any_output_owned = tx.vout.iter().any(is_owned);
+    if !any_input_owned && !any_output_owned {
+        return None;
+    }
+
+    // The fee is only ours to report when we funded every input.
+    let all_inputs_owned = !tx.vin.is_empty()
+        && tx.vin.iter().all(|vin| !vin.is_coinbase && vin.prevout.as_ref().is_some_and(is_owned));
+    let fee = all_inputs_owned
+        .then(|| prevouts.iter().map(|p| p.value).sum::<u64>().saturating_sub(tx.vout.iter().map(|o| o.value).sum()));
+
+    let payment_outputs: Vec<&EsploraVout> = tx.vout.iter().filter(|o| !o.scriptpubkey.starts_with("6a")).collect();
+    let net_amount = owned_out as i64 - owned_in as i64;
+    let direction = if all_inputs_owned && payment_outputs.iter().all(|o| is_owned(o)) {
+        "self_transfer"
+    } else if net_amount < 0 {
+        "sent"
+    } else {
+        "received"
+    };
+
+    let counterparties: Vec<&EsploraVout> = if direction == "sent" {
+        payment_outputs.into_iter().filter(|o| !is_owned(o)).collect()
+    } else {
+        prevouts.into_iter().filter(|p| !is_owned(p)).collect()
+    };
+    let mut addresses: Vec<String> = Vec::new();
+    for address in counterparties.iter().filter_map(|o| o.scriptpubkey_address.clone()) {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    Some(WalletEvent {
+        txid: tx.txid.clone(),
+        direction: direction.to_string(),
+        net_amount,
+        fee,
+        counterparties: addresses,
+        confirmed: tx.status.confirmed,
+        height: tx.status.block_height,
+        timestamp: tx.status.block_time,
+    })
+}
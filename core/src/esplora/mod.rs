@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+pub mod fees;
+pub mod history;
+pub mod utxos;
+
+pub use fees::{estimate_feerate_from_histogram, position_in_mempool};
+pub use history::parse_address_history;
+pub use utxos::adapt_esplora_utxos;
+
+/// The subset of Esplora's transaction JSON (`/tx/:txid`, `/address/:addr/txs`) used here.
+#[derive(Serialize, Deserialize)]
+pub struct EsploraTx {
+    pub txid: String,
+    #[serde(default)]
+    pub vin: Vec<EsploraVin>,
+    #[serde(default)]
+    pub vout: Vec<EsploraVout>,
+    #[serde(default)]
+    pub status: EsploraStatus,
+    #[serde(default)]
+    pub fee: Option<u64>,
+    #[serde(default)]
+    pub weight: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EsploraVin {
+    #[serde(default)]
+    pub txid: Option<String>,
+    #[serde(default)]
+    pub vout: Option<u32>,
+    #[serde(default)]
+    pub sequence: Option<u32>,
+    #[serde(default)]
+    pub is_coinbase: bool,
+    pub prevout: Option<EsploraVout>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EsploraVout {
+    pub scriptpubkey: String,
+    pub scriptpubkey_address: Option<String>,
+    pub value: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct EsploraStatus {
+    #[serde(default)]
+    pub confirmed: bool,
+    pub block_height: Option<u32>,
+    pub block_time: Option<u64>,
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::esplora::EsploraStatus;
+use crate::transaction::TransactionInput;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::network::require_address_network;
+
+/// One entry of Esplora's `/address/:addr/utxo` (and `/scripthash/:hash/utxo`) response.
+#[derive(Deserialize)]
+pub struct EsploraUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+    #[serde(default)]
+    pub status: Option<EsploraStatus>,
+    /// The confirmation fields beside the others instead of under `status`, as some
+    /// mempool.space-style backends send them.
+    #[serde(default)]
+    pub confirmed: Option<bool>,
+    #[serde(default)]
+    pub block_height: Option<u32>,
+    /// Backends that return the script save us assuming it.
+    #[serde(default)]
+    pub scriptpubkey: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdaptedUtxos {
+    pub utxos: Vec<TransactionInput>,
+    /// `txid:vout` of the entries not yet in a block. They carry no `height`, which is
+    /// how coin selection and balances recognise them.
+    pub unconfirmed: Vec<String>,
+    #[serde(with = "crate::utils::amount", alias = "confirmed_total")]
+    pub confirmed_total: u64,
+    #[serde(with = "crate::utils::amount", alias = "unconfirmed_total")]
+    pub unconfirmed_total: u64,
+}
+
+/// Converts an Esplora or mempool.space UTXO list for one address into
+/// `TransactionInput`s. The explorer leaves out the scriptPubKey, so it's filled in
+/// from `address_or_script` (an address on any network, or script hex) and the
+/// confirmation height goes into `height`.
+#[wasm_bindgen]
+pub fn adapt_esplora_utxos(esplora_utxos_json: &str, address_or_script: &str, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let script_pubkey = owner_script(address_or_script).map_err(|e| JsValue::from_str(&e))?;
+    let entries: Vec<EsploraUtxo> = parse_json_list(esplora_utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+
+    let mut adapted = AdaptedUtxos { utxos: Vec::with_capacity(entries.len()), unconfirmed: Vec::new(), confirmed_total: 0, unconfirmed_total: 0 };
+    for (index, entry) in entries.into_iter().enumerate() {
+        if let Some(given) = &entry.scriptpubkey {
+            if !given.trim().eq_ignore_ascii_case(&script_pubkey) {
+                return Err(JsValue::from_str(&format!(
+                    "utxos[{}] ({}:{}) pays script {}, not {}",
+                    index, entry.txid, entry.vout, given.trim(), script_pubkey
+                )));
+            }
+        }
+        let status = entry.status.as_ref();
+        let confirmed = entry.confirmed.or(status.map(|status| status.confirmed)).unwrap_or(false);
+        let height = confirmed.then(|| entry.block_height.or(status.and_then(|status| status.block_height))).flatten();
+        if confirmed && height.is_none() {
+            return Err(JsValue::from_str(&format!(
+                "utxos[{}] ({}:{}) is confirmed but has no block_height",
+                index, entry.txid, entry.vout
+            )));
+        }
+
+        let total = if confirmed { &mut adapted.confirmed_total } else { &mut adapted.unconfirmed_total };
+        *total = total.checked_add(entry.value).ok_or_else(|| JsValue::from_str("UTXO values overflow"))?;
+        if !confirmed {
+            adapted.unconfirmed.push(format!("{}:{}", entry.txid, entry.vout));
+        }
+        adapted.utxos.push(TransactionInput {
+            txid: entry.txid,
+            vout: entry.vout,
+            amount: entry.value,
+            script_pubkey: script_pubkey.clone(),
+            input_type: None,
+            is_coinbase: false,
+            height,
+            frozen: false,
+            must_spend: false,
+        });
+    }
+
+    to_json_with_amounts(&adapted, amount_format, compact)
+}
+
+/// The owner's scriptPubKey hex. An address is tried first, since some are valid hex.
+fn owner_script(address_or_script: &str) -> Result<String, String> {
+    let given = address_or_script.trim();
+    match require_address_network(given, None) {
+        Ok(script) => Ok(bytes_to_hex(script.as_bytes())),
+        Err(address_error) => match hex_to_bytes(given, HexMode::Lenient) {
+            Ok(bytes) if !bytes.is_empty() => Ok(bytes_to_hex(&bytes)),
+            _ => Err(format!("Expected an address or scriptPubKey hex: {}", address_error)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    const SCRIPT: &str = "0014751e76e8199196d454941c45d1b3a323f1433bd6";
+
+    /// Blockstream Esplora's `/address/:addr/utxo`, with its extra status fields.
+    const ESPLORA: &str = r#"[
+        {"txid":"3b1f4a2c6a0e6a5a0c6b7f7c1f1e0c0d5b2d7d6a9c3e1f0a4b5c6d7e8f901234","vout":1,
+         "status":{"confirmed":true,"block_height":840000,"block_hash":"0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5","block_time":1713571767},
+         "value":125000},
+        {"txid":"a0c1e2f3b4a5968778695a4b3c2d1e0f00112233445566778899aabbccddeeff","vout":0,
+         "status":{"confirmed":false},
+         "value":3000}
+    ]"#;
+
+    /// The mempool.space-style variant: confirmation fields beside the others, plus the
+    /// scriptPubKey.
+    const MEMPOOL_SPACE: &str = r#"[
+        {"txid":"3b1f4a2c6a0e6a5a0c6b7f7c1f1e0c0d5b2d7d6a9c3e1f0a4b5c6d7e8f901234","vout":1,"value":125000,
+         "confirmed":true,"block_height":840000,"scriptpubkey":"0014751E76E8199196D454941C45D1B3A323F1433BD6"},
+        {"txid":"a0c1e2f3b4a5968778695a4b3c2d1e0f00112233445566778899aabbccddeeff","vout":0,"value":3000,
+         "confirmed":false}
+    ]"#;
+
+    fn adapt(fixture: &str, owner: &str) -> AdaptedUtxos {
+        serde_json::from_str(&adapt_esplora_utxos(fixture, owner, None, None).unwrap()).unwrap()
+    }
+
+    fn check(adapted: &AdaptedUtxos) {
+        assert_eq!(adapted.utxos.len(), 2);
+        let confirmed = &adapted.utxos[0];
+        assert_eq!((confirmed.vout, confirmed.amount, confirmed.height), (1, 125_000, Some(840_000)));
+        assert!(adapted.utxos.iter().all(|utxo| utxo.script_pubkey == SCRIPT));
+        assert_eq!(adapted.utxos[1].height, None);
+        assert_eq!(adapted.unconfirmed, ["a0c1e2f3b4a5968778695a4b3c2d1e0f00112233445566778899aabbccddeeff:0"]);
+        assert_eq!((adapted.confirmed_total, adapted.unconfirmed_total), (125_000, 3_000));
+    }
+
+    #[test]
+    fn esplora_fixture() {
+        check(&adapt(ESPLORA, ADDRESS));
+        check(&adapt(ESPLORA, SCRIPT));
+    }
+
+    #[test]
+    fn mempool_space_fixture() {
+        check(&adapt(MEMPOOL_SPACE, ADDRESS));
+    }
+
+    #[test]
+    fn owner_is_an_address_or_script() {
+        assert_eq!(owner_script(ADDRESS).unwrap(), SCRIPT);
+        assert_eq!(owner_script(&SCRIPT.to_uppercase()).unwrap(), SCRIPT);
+        assert!(owner_script("").is_err());
+        assert!(owner_script("not an address").is_err());
+    }
+}
@@ -1,10 +1,64 @@
 mod utils;
 mod wallet;
 mod transaction;
+mod script;
+mod p2p;
+mod detect;
+mod selftest;
+#[cfg(feature = "wallet")]
+mod descriptor;
+#[cfg(feature = "decode")]
+mod decode;
+#[cfg(feature = "wallet")]
+mod selection;
+#[cfg(feature = "http")]
+mod esplora;
+#[cfg(feature = "psbt")]
+mod psbt;
+#[cfg(feature = "decode")]
+mod privacy;
+#[cfg(any(feature = "test_vectors", all(test, feature = "wallet", feature = "signing", feature = "psbt")))]
+mod vectors;
 
-pub use wallet::{generate_private_key, derive_addresses_from_key};
-pub use transaction::{build_transaction, sign_transaction, calculate_txid};
-pub use utils::wasm_log;
+pub use wallet::{derive_addresses_from_key, derive_addresses_from_key_compat, export_wif, convert_pubkey_compression, address_from_pubkey, taproot_tweak_pubkey, taproot_tweak_privkey, xonly_from_pubkey, commit_to_data, verify_commitment, commit_privkey_tweak, verify_taproot_address, musig_aggregate_keys, export_labels_bip329, import_labels_bip329, inspect_xkey, xprv_to_xpub, derive_child, derive_children, parse_derivation_path};
+#[cfg(feature = "wallet")]
+pub use wallet::{generate_private_key, private_key_from_dice, mnemonic_from_dice, from_coin_flips, split_seed_slip39, combine_slip39_shares, mnemonic_to_seedqr, seedqr_to_mnemonic, validate_mnemonic, mnemonic_to_seed, validate_electrum_mnemonic, electrum_mnemonic_to_seed, electrum_derive_addresses, diagnose_restore, encrypt_secret, decrypt_secret, calibrate_kdf, VanitySearch, Wallet, WatchWallet};
+#[cfg(feature = "wallet")]
+pub use descriptor::{compile_policy, analyze_miniscript, descriptor_to_addresses, find_address_in_descriptor, export_descriptors, parse_multisig_config, export_multisig_config, descriptor_checksum, verify_descriptor_checksum, estimate_satisfaction_cost, AddressDeriver};
+#[cfg(feature = "signing")]
+pub use wallet::{prove_address, verify_address_proof, create_proof_of_reserves, verify_proof_of_reserves, build_htlc, claim_htlc, refund_htlc, adaptor_sign, adaptor_verify, adaptor_adapt, adaptor_extract_secret, host_data_commitment, commit_signing_nonce, sign_with_commitment, verify_commitment_in_signature};
+#[cfg(all(feature = "wallet", feature = "signing"))]
+pub use wallet::{create_recovery_wallet, spend_recovery_primary, spend_recovery_delayed};
+pub use transaction::{build_transaction, build_transaction_detailed, make_outpoint, make_input, make_output, sort_transaction_bip69, calculate_txid, calculate_txids, TxidBatch, check_truc, locktime_from_height, locktime_from_timestamp, describe_locktime, is_final, median_time_past, evaluate_locks, estimate_input_weight, input_weight_table, populate_dummy_signatures, transaction_vsize, check_rbf, bump_options, UnconfirmedGraph, check_standardness, local_accept_check, validate_address, validate_addresses, suggest_address_correction, address_for_qr, bitcoin_uri_for_qr, diff_transactions, export_signing_record, verify_signing_record, diagnose_multisig_witness, restrict_outputs, RestrictedBuilder, build_historic_transaction, screen_outputs, validate_serialization, set_serialization_checks, set_witness, get_witness, push_witness_item, remove_witness_item};
+#[cfg(feature = "signing")]
+pub use transaction::{assemble_crowdfund, create_pledge, create_offer, fill_offer, merge_transactions, sign_transaction, sign_all_inputs, sign_all_inputs_with_provider, sign_all_inputs_with_signer, sign_p2wsh_input, sign_historic_input, BatchSigner};
+pub use p2p::{bloom_filter_contains, create_bloom_filter, filter_matches_transaction, build_p2p_message, parse_p2p_message, build_version_message, build_verack_message, build_tx_message, build_inv_message, build_getdata_message};
+pub use script::{asm_to_script, script_to_asm, batch_verify, execute_script, extract_script_items, sort_pubkeys_bip67, create_multisig, verify_multisig_address, build_op_return, parse_op_return, decode_runestone, script_stats};
+#[cfg(feature = "http")]
+pub use esplora::{adapt_esplora_utxos, estimate_feerate_from_histogram, parse_address_history, position_in_mempool};
+#[cfg(feature = "psbt")]
+pub use psbt::{psbt_to_base64, psbt_to_hex, psbt_from_binary, detect_psbt_format, decode_psbt, update_psbt, add_global_xpubs, create_psbt_v2, psbt_v2_add_input, psbt_v2_add_output, psbt_v2_to_v0, psbt_v0_to_v2, parse_payjoin_uri, payjoin_request_url, payjoin_original_psbt, validate_payjoin_proposal, get_proprietary_field, set_proprietary_field, build_locktime_psbt, package_report, SigningSession};
+#[cfg(all(feature = "psbt", feature = "signing"))]
+pub use psbt::sign_locktime_psbt;
+#[cfg(all(feature = "psbt", feature = "wallet"))]
+pub use psbt::psbt_signing_summary;
+#[cfg(feature = "decode")]
+pub use privacy::{analyze_privacy, guess_change_output, cluster_inputs};
+#[cfg(all(feature = "decode", feature = "http"))]
+pub use privacy::find_reuse;
+#[cfg(feature = "decode")]
+pub use decode::{decode_witness, classify_inputs, decode_transaction, weight_breakdown, explain_transaction, explain_transaction_with_provider, decode_block, decode_block_bytes, BlockDecoder, is_coinbase, decode_coinbase, compute_merkle_root, compute_witness_merkle_root, verify_witness_commitment, verify_witness_commitment_bytes, build_coinbase, assemble_block, NonceGrinder, bits_to_target, target_to_difficulty, check_header_meets_target, block_script_stats, block_script_stats_bytes, scan_transactions};
+#[cfg(feature = "wallet")]
+pub use selection::{select_coins, InFlightTracker, compute_balance, build_batch_payment, plan_consolidation, dust_report, max_send_amount, build_sweep_transaction, build_split_payment};
+#[cfg(all(feature = "wallet", feature = "signing"))]
+pub use selection::sweep_foreign_key;
+#[cfg(feature = "test_vectors")]
+pub use vectors::test_vectors;
+pub use detect::detect_input_type;
+pub use selftest::self_test;
+pub use utils::{wasm_log, normalize_hex, HexDecoder, reverse_hash_hex, txid_to_internal, internal_to_txid, varint_encode, varint_decode, base64_encode, base64_decode, canonicalize_json, sanitize_input, enable_metrics, disable_metrics, reset_metrics, get_metrics, network_info, Amount};
+#[cfg(feature = "wallet")]
+pub use utils::{set_test_seed, clear_test_seed, test_seed_active};
 
 use wasm_bindgen::prelude::*;
 
@@ -15,7 +69,8 @@ pub fn satoshi_to_btc(satoshi: u64) -> f64 {
 
 #[wasm_bindgen]
 pub fn btc_to_satoshi(btc: f64) -> u64 {
-    (btc * 100_000_000.0) as u64
+    // Round rather than truncate: 0.29 BTC is 28999999.999999996 satoshis as a float.
+    (btc * 100_000_000.0).round() as u64
 }
 
 #[wasm_bindgen]
@@ -0,0 +1,399 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::script::Instruction;
+use bitcoin::{OutPoint, Script, Transaction};
+use crate::script::parse_multisig;
+use crate::transaction::decode_tx_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::varint::{decode_compact_size, encode_compact_size};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+
+/// BIP37 limits a peer enforces on `filterload`.
+const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+const MAX_HASH_FUNCS: u32 = 50;
+const LN2SQUARED: f64 = std::f64::consts::LN_2 * std::f64::consts::LN_2;
+/// Spaces the seeds of the filter's hash functions apart.
+const SEED_MULTIPLIER: u32 = 0xfba4c795;
+
+const BLOOM_UPDATE_NONE: u8 = 0;
+const BLOOM_UPDATE_ALL: u8 = 1;
+const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+const BLOOM_UPDATE_MASK: u8 = 3;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BloomFilterInfo {
+    /// The `filterload` message payload.
+    #[serde(alias = "filter_hex")]
+    pub filter_hex: String,
+    #[serde(alias = "size_bytes")]
+    pub size_bytes: usize,
+    #[serde(alias = "hash_funcs")]
+    pub hash_funcs: u32,
+    pub tweak: u32,
+    pub flags: String,
+    pub elements: usize,
+    /// What this size and hash count give for `elements` entries, after rounding.
+    #[serde(alias = "false_positive_rate")]
+    pub false_positive_rate: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterMatch {
+    pub matched: bool,
+    /// What matched, in the order a node checks: txid, output pushes, then spent
+    /// outpoints and scriptSig pushes. A node stops at the first input match.
+    pub reasons: Vec<String>,
+    /// The filter after the node's update: with flags "all" or "p2pubkey_only" a matched
+    /// output's outpoint is inserted, so its spend matches too.
+    #[serde(alias = "filter_hex")]
+    pub filter_hex: String,
+    pub updated: bool,
+}
+
+/// A `filterload` filter: the bit field, its hash count, tweak and update flags.
+pub struct BloomFilter {
+    data: Vec<u8>,
+    hash_funcs: u32,
+    tweak: u32,
+    flags: u8,
+}
+
+/// Builds a BIP37 filter sized like Bitcoin Core's for `elements_json` (hex strings, or
+/// "txid:vout" outpoints) at `false_positive_rate`, and inserts them. `flags` is "none"
+/// (the default), "all" or "p2pubkey_only".
+#[wasm_bindgen]
+pub fn create_bloom_filter(
+    elements_json: &str,
+    false_positive_rate: f64,
+    n_tweak: u32,
+    flags: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let elements: Vec<String> = parse_json_list(elements_json, "elements").map_err(|e| JsValue::from_str(&e))?;
+    if elements.is_empty() {
+        return Err(JsValue::from_str("A filter needs at least one element to be sized for"));
+    }
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+        return Err(JsValue::from_str("false_positive_rate must be between 0 and 1"));
+    }
+    let flags = parse_flags(flags.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+
+    let mut filter = BloomFilter::new(elements.len() as u32, false_positive_rate, n_tweak, flags);
+    for (index, element) in elements.iter().enumerate() {
+        let bytes = parse_element(element).map_err(|e| JsValue::from_str(&format!("elements[{}]: {}", index, e)))?;
+        filter.insert(&bytes);
+    }
+
+    let bits = filter.data.len() as f64 * 8.0;
+    let k = filter.hash_funcs as f64;
+    // Too small a filter rounds down to no bits, which a node treats as matching everything.
+    let rate = if filter.data.is_empty() { 1.0 } else { (1.0 - (-k * elements.len() as f64 / bits).exp()).powf(k) };
+    let info = BloomFilterInfo {
+        filter_hex: bytes_to_hex(&filter.serialize()),
+        size_bytes: filter.data.len(),
+        hash_funcs: filter.hash_funcs,
+        tweak: filter.tweak,
+        flags: flags_name(flags).to_string(),
+        elements: elements.len(),
+        false_positive_rate: rate,
+    };
+    to_json(&info, compact)
+}
+
+/// Whether the filter (a `filterload` payload) may contain the element, given as hex or
+/// as a "txid:vout" outpoint. False positives are the point of a bloom filter.
+#[wasm_bindgen]
+pub fn bloom_filter_contains(filter_hex: &str, element_hex: &str) -> Result<bool, JsValue> {
+    let filter = BloomFilter::parse(filter_hex).map_err(|e| JsValue::from_str(&e))?;
+    let element = parse_element(element_hex).map_err(|e| JsValue::from_str(&format!("Invalid element: {}", e)))?;
+    Ok(filter.contains(&element))
+}
+
+/// Runs a node's BIP37 relevance check on a transaction: the txid, every data push in
+/// the outputs' scriptPubKeys, then each input's spent outpoint and scriptSig pushes.
+#[wasm_bindgen]
+pub fn filter_matches_transaction(filter_hex: &str, tx_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut filter = BloomFilter::parse(filter_hex).map_err(|e| JsValue::from_str(&e))?;
+    let tx = decode_tx_hex(tx_hex)?;
+    let before = filter.data.clone();
+    let reasons = filter.relevant_and_update(&tx);
+
+    let matched = FilterMatch {
+        matched: !reasons.is_empty(),
+        reasons,
+        updated: filter.data != before,
+        filter_hex: bytes_to_hex(&filter.serialize()),
+    };
+    to_json(&matched, compact)
+}
+
+impl BloomFilter {
+    /// Bitcoin Core's sizing, integer truncations included, so the same inputs give
+    /// a byte-identical filter.
+    pub fn new(elements: u32, false_positive_rate: f64, tweak: u32, flags: u8) -> BloomFilter {
+        let bits = ((-1.0 / LN2SQUARED * elements as f64 * false_positive_rate.ln()) as u32).min(MAX_BLOOM_FILTER_SIZE as u32 * 8);
+        let size = (bits / 8) as usize;
+        let hash_funcs = ((((size * 8) as u32 / elements) as f64 * std::f64::consts::LN_2) as u32).min(MAX_HASH_FUNCS);
+        BloomFilter { data: vec![0; size], hash_funcs, tweak, flags }
+    }
+
+    pub fn parse(filter_hex: &str) -> Result<BloomFilter, String> {
+        let bytes = hex_to_bytes(filter_hex, HexMode::Lenient).map_err(|e| format!("Invalid filter hex: {}", e))?;
+        let length = decode_compact_size(&bytes).map_err(|e| format!("Invalid filter: {}", e))?;
+        let start = length.bytes_consumed;
+        let size = length.value as usize;
+        if size > MAX_BLOOM_FILTER_SIZE {
+            return Err(format!("Invalid filter: {} bytes is over the {}-byte limit", size, MAX_BLOOM_FILTER_SIZE));
+        }
+        if bytes.len() != start + size + 9 {
+            return Err(format!(
+                "Invalid filter: expected {} bytes (length, {} data bytes, hash count, tweak, flags), got {}",
+                start + size + 9,
+                size,
+                bytes.len()
+            ));
+        }
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().expect("4 bytes"));
+        let hash_funcs = word(start + size);
+        if hash_funcs > MAX_HASH_FUNCS {
+            return Err(format!("Invalid filter: {} hash functions is over the limit of {}", hash_funcs, MAX_HASH_FUNCS));
+        }
+        Ok(BloomFilter {
+            data: bytes[start..start + size].to_vec(),
+            hash_funcs,
+            tweak: word(start + size + 4),
+            flags: bytes[start + size + 8],
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = encode_compact_size(self.data.len() as u64);
+        bytes.extend_from_slice(&self.data);
+        bytes.extend_from_slice(&self.hash_funcs.to_le_bytes());
+        bytes.extend_from_slice(&self.tweak.to_le_bytes());
+        bytes.push(self.flags);
+        bytes
+    }
+
+    fn bit(&self, hash_num: u32, element: &[u8]) -> usize {
+        let seed = hash_num.wrapping_mul(SEED_MULTIPLIER).wrapping_add(self.tweak);
+        murmur3(seed, element) as usize % (self.data.len() * 8)
+    }
+
+    pub fn insert(&mut self, element: &[u8]) {
+        if self.data.is_empty() {
+            return;
+        }
+        for hash_num in 0..self.hash_funcs {
+            let bit = self.bit(hash_num, element);
+            self.data[bit >> 3] |= 1 << (bit & 7);
+        }
+    }
+
+    /// An empty filter matches everything, as in Core.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        (0..self.hash_funcs).all(|hash_num| {
+            let bit = self.bit(hash_num, element);
+            self.data[bit >> 3] & (1 << (bit & 7)) != 0
+        })
+    }
+
+    /// Core's `CBloomFilter::IsRelevantAndUpdate`, returning what matched.
+    pub fn relevant_and_update(&mut self, tx: &Transaction) -> Vec<String> {
+        if self.data.is_empty() {
+            return vec!["empty filter matches everything".to_string()];
+        }
+        let mut reasons = Vec::new();
+        let txid = tx.compute_txid();
+        if self.contains(txid.as_byte_array()) {
+            reasons.push("txid".to_string());
+        }
+
+        for (vout, output) in tx.output.iter().enumerate() {
+            let Some(push) = pushes(&output.script_pubkey).find(|push| self.contains(push)) else { continue };
+            reasons.push(format!("output {} scriptPubKey push {}", vout, bytes_to_hex(push)));
+            let insert = match self.flags & BLOOM_UPDATE_MASK {
+                BLOOM_UPDATE_ALL => true,
+                BLOOM_UPDATE_P2PUBKEY_ONLY => output.script_pubkey.is_p2pk() || parse_multisig(&output.script_pubkey).is_some(),
+                _ => false,
+            };
+            if insert {
+                self.insert(&serialize(&OutPoint { txid, vout: vout as u32 }));
+            }
+        }
+        if !reasons.is_empty() {
+            return reasons;
+        }
+
+        for (index, input) in tx.input.iter().enumerate() {
+            if self.contains(&serialize(&input.previous_output)) {
+                return vec![format!("input {} spends {}", index, input.previous_output)];
+            }
+            if let Some(push) = pushes(&input.script_sig).find(|push| self.contains(push)) {
+                return vec![format!("input {} scriptSig push {}", index, bytes_to_hex(push))];
+            }
+        }
+        Vec::new()
+    }
+}
+
+/// Non-empty data pushes up to the first byte that doesn't parse, as Core's `GetOp` loop
+/// sees them.
+fn pushes(script: &Script) -> impl Iterator<Item = &[u8]> {
+    script.instructions().map_while(Result::ok).filter_map(|instruction| match instruction {
+        Instruction::PushBytes(push) if !push.is_empty() => Some(push.as_bytes()),
+        _ => None,
+    })
+}
+
+/// An element as hex, or an outpoint as "txid:vout" serialized the way a node hashes it
+/// (txid in internal byte order, then the index little-endian).
+fn parse_element(element: &str) -> Result<Vec<u8>, String> {
+    let element = element.trim();
+    if element.contains(':') {
+        let outpoint = OutPoint::from_str(element).map_err(|e| format!("invalid outpoint: {}", e))?;
+        return Ok(serialize(&outpoint));
+    }
+    hex_to_bytes(element, HexMode::Lenient).map_err(|e| format!("invalid hex: {}", e))
+}
+
+fn parse_flags(flags: Option<&str>) -> Result<u8, String> {
+    match flags.map(|flags| flags.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("none") => Ok(BLOOM_UPDATE_NONE),
+        Some("all") => Ok(BLOOM_UPDATE_ALL),
+        Some("p2pubkey_only") => Ok(BLOOM_UPDATE_P2PUBKEY_ONLY),
+        Some(other) => Err(format!("Unknown flags \"{}\" (expected none, all or p2pubkey_only)", other)),
+    }
+}
+
+fn flags_name(flags: u8) -> &'static str {
+    match flags & BLOOM_UPDATE_MASK {
+        BLOOM_UPDATE_ALL => "all",
+        BLOOM_UPDATE_P2PUBKEY_ONLY => "p2pubkey_only",
+        _ => "none",
+    }
+}
+
+/// MurmurHash3 (x86, 32-bit), the hash BIP37 specifies.
+pub fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut h1 = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        h1 ^= mix(u32::from_le_bytes(chunk.try_into().expect("4 bytes")));
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k1 = tail.iter().rev().fold(0u32, |k, &byte| (k << 8) | byte as u32);
+        h1 ^= mix(k1);
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85ebca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2ae35);
+    h1 ^ (h1 >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hash160;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::PrivateKey;
+
+    /// The elements of Bitcoin Core's `bloom_create_insert_serialize` tests.
+    const ELEMENTS: [&str; 3] = [
+        "99108ad8ed9bb6274d3980bab5a85c048f0950c8",
+        "b5a2c786d9ef4658287ced5914b37a1b4aa32eee",
+        "b9300670b4c5366e95b2699e8b18bc75e5f729c5",
+    ];
+
+    fn filter_with(elements: &[Vec<u8>], false_positive_rate: f64, tweak: u32) -> BloomFilter {
+        let mut filter = BloomFilter::new(elements.len() as u32, false_positive_rate, tweak, BLOOM_UPDATE_ALL);
+        for element in elements {
+            filter.insert(element);
+        }
+        filter
+    }
+
+    fn core_elements() -> Vec<Vec<u8>> {
+        ELEMENTS.iter().map(|element| hex_to_bytes(element, HexMode::Strict).unwrap()).collect()
+    }
+
+    #[test]
+    fn murmur3_vectors() {
+        // Bitcoin Core's hash_tests.cpp.
+        let vectors: [(u32, u32, &str); 14] = [
+            (0x00000000, 0x00000000, ""),
+            (0x6a396f08, 0xfba4c795, ""),
+            (0x81f16f39, 0xffffffff, ""),
+            (0x514e28b7, 0x00000000, "00"),
+            (0xea3f0b17, 0xfba4c795, "00"),
+            (0xfd6cf10d, 0x00000000, "ff"),
+            (0x16c6b7ab, 0x00000000, "0011"),
+            (0x8eb51c3d, 0x00000000, "001122"),
+            (0xb4471bf8, 0x00000000, "00112233"),
+            (0xe2301fa8, 0x00000000, "0011223344"),
+            (0xfc2e4a15, 0x00000000, "001122334455"),
+            (0xb074502c, 0x00000000, "00112233445566"),
+            (0x8034d2a0, 0x00000000, "0011223344556677"),
+            (0xb4698def, 0x00000000, "001122334455667788"),
+        ];
+        for (expected, seed, data) in vectors {
+            assert_eq!(murmur3(seed, &hex_to_bytes(data, HexMode::Strict).unwrap()), expected, "seed {:#x}, data {}", seed, data);
+        }
+    }
+
+    #[test]
+    fn core_insert_serialize() {
+        let filter = filter_with(&core_elements(), 0.01, 0);
+        assert_eq!(bytes_to_hex(&filter.serialize()), "03614e9b050000000000000001");
+        assert!(filter.contains(&core_elements()[0]));
+        assert!(!filter.contains(&hex_to_bytes("19108ad8ed9bb6274d3980bab5a85c048f0950c8", HexMode::Strict).unwrap()));
+    }
+
+    #[test]
+    fn core_insert_serialize_with_tweak() {
+        let filter = filter_with(&core_elements(), 0.01, 2_147_483_649);
+        assert_eq!(bytes_to_hex(&filter.serialize()), "03ce4299050000000100008001");
+    }
+
+    #[test]
+    fn core_insert_key() {
+        let key = PrivateKey::from_wif("5Kg1gnAjaLfKiwhhPpGS3QfRg2m6awQvaj98JCZBZQ5SuS2F15C").unwrap();
+        let pubkey = key.public_key(&Secp256k1::new()).to_bytes();
+        let elements = [pubkey.clone(), hash160::Hash::hash(&pubkey).to_byte_array().to_vec()];
+        assert_eq!(bytes_to_hex(&filter_with(&elements, 0.001, 0).serialize()), "038fc16b080000000000000001");
+    }
+
+    #[test]
+    fn create_bloom_filter_matches_core() {
+        let json = create_bloom_filter(&serde_json::to_string(&ELEMENTS).unwrap(), 0.01, 0, Some("all".to_string()), None).unwrap();
+        let info: BloomFilterInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(info.filter_hex, "03614e9b050000000000000001");
+        assert_eq!((info.size_bytes, info.hash_funcs, info.flags.as_str()), (3, 5, "all"));
+        assert!(bloom_filter_contains(&info.filter_hex, ELEMENTS[2]).unwrap());
+    }
+
+    #[test]
+    fn parse_round_trips() {
+        let filter = BloomFilter::parse("03ce4299050000000100008001").unwrap();
+        assert_eq!((filter.hash_funcs, filter.tweak, filter.flags), (5, 2_147_483_649, BLOOM_UPDATE_ALL));
+        assert_eq!(bytes_to_hex(&filter.serialize()), "03ce4299050000000100008001");
+        assert!(BloomFilter::parse("03ce42990500000001000080").err().unwrap().contains("expected 13 bytes"));
+    }
+}
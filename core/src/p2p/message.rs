@@ -0,0 +1,439 @@
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::Network;
+use crate::transaction::decode_tx_hex;
+use crate::utils::json::{parse_json_list, parse_json_value, to_json};
+use crate::utils::network::{network_name, network_or_default, NetworkParams};
+use crate::utils::varint::{decode_compact_size, encode_compact_size};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+
+const HEADER_SIZE: usize = 24;
+const COMMAND_SIZE: usize = 12;
+/// Core's `MAX_SIZE`: longer lengths in a header are refused before the payload arrives.
+const MAX_PAYLOAD_SIZE: usize = 0x0200_0000;
+const MAX_INV_SIZE: u64 = 50_000;
+const PROTOCOL_VERSION: i32 = 70016;
+
+const INVENTORY_TYPES: [(&str, u32); 8] = [
+    ("tx", 1),
+    ("block", 2),
+    ("filtered_block", 3),
+    ("cmpct_block", 4),
+    ("wtx", 5),
+    ("witness_tx", 0x4000_0001),
+    ("witness_block", 0x4000_0002),
+    ("witness_filtered_block", 0x4000_0003),
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedMessage {
+    /// "complete", or "incomplete" while more bytes are needed.
+    pub status: String,
+    #[serde(alias = "needed_bytes")]
+    pub needed_bytes: usize,
+    /// Header fields, once all 24 header bytes are in.
+    pub magic: Option<String>,
+    /// The named network using `magic`, if any.
+    pub network: Option<String>,
+    pub command: Option<String>,
+    pub length: Option<usize>,
+    pub checksum: Option<String>,
+    #[serde(alias = "checksum_valid")]
+    pub checksum_valid: Option<bool>,
+    #[serde(alias = "payload_hex")]
+    pub payload_hex: Option<String>,
+    /// What the payload says, for version, inv, getdata, notfound and tx.
+    pub decoded: Option<DecodedPayload>,
+    /// Header and payload bytes, and whatever followed them in the buffer.
+    #[serde(alias = "message_size")]
+    pub message_size: Option<usize>,
+    #[serde(alias = "remaining_hex")]
+    pub remaining_hex: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DecodedPayload {
+    Version(VersionPayload),
+    Inventory { items: Vec<InventoryItem> },
+    Tx { txid: String, wtxid: String, size: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionPayload {
+    pub version: i32,
+    pub services: u64,
+    pub timestamp: i64,
+    pub receiver: NetAddress,
+    pub sender: NetAddress,
+    /// A string, since random nonces don't fit in a JS number.
+    pub nonce: String,
+    #[serde(alias = "user_agent")]
+    pub user_agent: String,
+    #[serde(alias = "start_height")]
+    pub start_height: i32,
+    /// BIP37's fRelay; peers that leave it out relay.
+    pub relay: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NetAddress {
+    #[serde(default)]
+    pub services: u64,
+    /// IPv4 or IPv6; IPv4 is sent as an IPv4-mapped IPv6 address.
+    pub ip: String,
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct InventoryItem {
+    /// tx, block, filtered_block, cmpct_block, wtx, witness_tx, witness_block,
+    /// witness_filtered_block, or the numeric type.
+    #[serde(rename = "type")]
+    pub inv_type: serde_json::Value,
+    /// In display byte order, as explorers show txids and block hashes.
+    pub hash: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct VersionOptions {
+    pub version: Option<i32>,
+    pub services: u64,
+    /// Unix seconds; the current time when left out.
+    pub timestamp: Option<i64>,
+    pub receiver: Option<NetAddress>,
+    pub sender: Option<NetAddress>,
+    pub nonce: u64,
+    #[serde(alias = "user_agent")]
+    pub user_agent: Option<String>,
+    #[serde(alias = "start_height")]
+    pub start_height: i32,
+    pub relay: Option<bool>,
+}
+
+/// Wraps a payload in the P2P envelope: network magic, NUL-padded command, payload
+/// length and the first four bytes of its double SHA-256.
+#[wasm_bindgen]
+pub fn build_p2p_message(network: Option<String>, command: &str, payload_hex: &str) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let payload = hex_to_bytes(payload_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid payload hex: {}", e)))?;
+    frame(&params, command, &payload).map(|message| bytes_to_hex(&message)).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Splits the first message off a buffer of received bytes. When the buffer ends
+/// mid-message the result is "incomplete" with `needed_bytes` to wait for; otherwise
+/// `remaining_hex` is the start of the next message. A bad checksum is reported, not
+/// thrown, so the caller can drop the message and carry on.
+#[wasm_bindgen]
+pub fn parse_p2p_message(bytes_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(bytes_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid message hex: {}", e)))?;
+    let parsed = parse_message(&bytes).map_err(|e| JsValue::from_str(&e))?;
+
+    to_json(&parsed, compact)
+}
+
+/// A complete `version` message. Unset options follow what a light client announces:
+/// protocol 70016, no services, the current time, unroutable addresses, a zero nonce
+/// and `relay` on.
+#[wasm_bindgen]
+pub fn build_version_message(network: Option<String>, options_json: Option<String>) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let options: VersionOptions = match options_json.filter(|json| !json.trim().is_empty()) {
+        Some(json) => parse_json_value(&json, "options").map_err(|e| JsValue::from_str(&e))?,
+        None => VersionOptions::default(),
+    };
+    let payload = version_payload(options).map_err(|e| JsValue::from_str(&e))?;
+    framed(&params, "version", &payload)
+}
+
+#[wasm_bindgen]
+pub fn build_verack_message(network: Option<String>) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    framed(&params, "verack", &[])
+}
+
+/// A `tx` message relaying a serialized transaction, witness included.
+#[wasm_bindgen]
+pub fn build_tx_message(network: Option<String>, tx_hex: &str) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    decode_tx_hex(tx_hex)?;
+    let payload = hex_to_bytes(tx_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid tx hex: {}", e)))?;
+    framed(&params, "tx", &payload)
+}
+
+/// An `inv` announcing `items_json`: `[{"type": "tx", "hash": "<txid>"}, ...]`.
+#[wasm_bindgen]
+pub fn build_inv_message(network: Option<String>, items_json: &str) -> Result<String, JsValue> {
+    inventory_message(network, "inv", items_json)
+}
+
+/// A `getdata` requesting `items_json`, in the same form as `build_inv_message`. Ask
+/// for witness_tx and witness_block to get witness data.
+#[wasm_bindgen]
+pub fn build_getdata_message(network: Option<String>, items_json: &str) -> Result<String, JsValue> {
+    inventory_message(network, "getdata", items_json)
+}
+
+fn inventory_message(network: Option<String>, command: &str, items_json: &str) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let items: Vec<InventoryItem> = parse_json_list(items_json, "items").map_err(|e| JsValue::from_str(&e))?;
+    if items.len() as u64 > MAX_INV_SIZE {
+        return Err(JsValue::from_str(&format!("{} carries at most {} items, got {}", command, MAX_INV_SIZE, items.len())));
+    }
+
+    let mut payload = encode_compact_size(items.len() as u64);
+    for (index, item) in items.iter().enumerate() {
+        let inv_type = inventory_type(&item.inv_type).map_err(|e| JsValue::from_str(&format!("items[{}].type: {}", index, e)))?;
+        let hash = sha256d::Hash::from_str(item.hash.trim())
+            .map_err(|e| JsValue::from_str(&format!("items[{}].hash: invalid hash: {}", index, e)))?;
+        payload.extend_from_slice(&inv_type.to_le_bytes());
+        payload.extend_from_slice(hash.as_byte_array());
+    }
+    framed(&params, command, &payload)
+}
+
+fn framed(params: &NetworkParams, command: &str, payload: &[u8]) -> Result<String, JsValue> {
+    frame(params, command, payload).map(|message| bytes_to_hex(&message)).map_err(|e| JsValue::from_str(&e))
+}
+
+pub fn frame(params: &NetworkParams, command: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+    if command.is_empty() || command.len() > COMMAND_SIZE || !command.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(format!("Command must be 1 to {} printable ASCII characters, got \"{}\"", COMMAND_SIZE, command));
+    }
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        return Err(format!("Payload of {} bytes is over the {}-byte limit", payload.len(), MAX_PAYLOAD_SIZE));
+    }
+
+    let mut message = Vec::with_capacity(HEADER_SIZE + payload.len());
+    message.extend_from_slice(&params.magic());
+    message.extend_from_slice(command.as_bytes());
+    message.resize(4 + COMMAND_SIZE, 0);
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(&checksum(payload));
+    message.extend_from_slice(payload);
+    Ok(message)
+}
+
+pub fn parse_message(bytes: &[u8]) -> Result<ParsedMessage, String> {
+    let mut parsed = ParsedMessage {
+        status: "incomplete".to_string(),
+        needed_bytes: HEADER_SIZE.saturating_sub(bytes.len()),
+        magic: None,
+        network: None,
+        command: None,
+        length: None,
+        checksum: None,
+        checksum_valid: None,
+        payload_hex: None,
+        decoded: None,
+        message_size: None,
+        remaining_hex: String::new(),
+    };
+    if bytes.len() < HEADER_SIZE {
+        return Ok(parsed);
+    }
+
+    let magic: [u8; 4] = bytes[..4].try_into().expect("4 bytes");
+    let command = parse_command(&bytes[4..4 + COMMAND_SIZE])?;
+    let length = u32::from_le_bytes(bytes[16..20].try_into().expect("4 bytes")) as usize;
+    if length > MAX_PAYLOAD_SIZE {
+        return Err(format!("Header announces a {}-byte payload, over the {}-byte limit", length, MAX_PAYLOAD_SIZE));
+    }
+    let provided = &bytes[20..HEADER_SIZE];
+    parsed.magic = Some(bytes_to_hex(&magic));
+    parsed.network = network_for_magic(magic).map(|network| network_name(network).to_string());
+    parsed.length = Some(length);
+    parsed.checksum = Some(bytes_to_hex(provided));
+    parsed.message_size = Some(HEADER_SIZE + length);
+
+    let end = HEADER_SIZE + length;
+    if bytes.len() < end {
+        parsed.needed_bytes = end - bytes.len();
+        parsed.command = Some(command);
+        return Ok(parsed);
+    }
+
+    let payload = &bytes[HEADER_SIZE..end];
+    let checksum_valid = checksum(payload) == provided;
+    parsed.status = "complete".to_string();
+    parsed.needed_bytes = 0;
+    parsed.checksum_valid = Some(checksum_valid);
+    parsed.payload_hex = Some(bytes_to_hex(payload));
+    parsed.remaining_hex = bytes_to_hex(&bytes[end..]);
+    if checksum_valid {
+        parsed.decoded = decode_payload(&command, payload).map_err(|e| format!("Invalid {} payload: {}", command, e))?;
+    }
+    parsed.command = Some(command);
+    Ok(parsed)
+}
+
+/// ASCII followed only by NUL padding, as Core's `IsCommandValid` requires.
+fn parse_command(field: &[u8]) -> Result<String, String> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    let (command, padding) = field.split_at(end);
+    if command.is_empty() || !command.iter().all(u8::is_ascii_graphic) || padding.iter().any(|&b| b != 0) {
+        return Err(format!("Invalid command field {}: not a NUL-padded ASCII name", bytes_to_hex(field)));
+    }
+    Ok(String::from_utf8(command.to_vec()).expect("ASCII"))
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = sha256d::Hash::hash(payload).to_byte_array();
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn network_for_magic(magic: [u8; 4]) -> Option<Network> {
+    [Network::Bitcoin, Network::Testnet, Network::Testnet4, Network::Signet, Network::Regtest]
+        .into_iter()
+        .find(|&network| NetworkParams::from_network(network).magic() == magic)
+}
+
+fn inventory_type(value: &serde_json::Value) -> Result<u32, String> {
+    if let Some(number) = value.as_u64() {
+        return u32::try_from(number).map_err(|_| format!("{} doesn't fit in 32 bits", number));
+    }
+    let name = value.as_str().ok_or("must be a type name or number")?;
+    INVENTORY_TYPES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name.trim()))
+        .map(|&(_, number)| number)
+        .ok_or_else(|| format!("unknown inventory type \"{}\"", name))
+}
+
+fn inventory_name(inv_type: u32) -> serde_json::Value {
+    match INVENTORY_TYPES.iter().find(|&&(_, number)| number == inv_type) {
+        Some((name, _)) => serde_json::Value::from(*name),
+        None => serde_json::Value::from(inv_type),
+    }
+}
+
+fn version_payload(options: VersionOptions) -> Result<Vec<u8>, String> {
+    let unroutable = NetAddress { services: 0, ip: "::".to_string(), port: 0 };
+    let timestamp = options.timestamp.unwrap_or_else(|| (js_sys::Date::now() / 1000.0) as i64);
+    let user_agent = options.user_agent.unwrap_or_else(|| format!("/bitlab-wasm:{}/", env!("CARGO_PKG_VERSION")));
+
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&options.version.unwrap_or(PROTOCOL_VERSION).to_le_bytes());
+    payload.extend_from_slice(&options.services.to_le_bytes());
+    payload.extend_from_slice(&timestamp.to_le_bytes());
+    write_address(&mut payload, options.receiver.as_ref().unwrap_or(&unroutable)).map_err(|e| format!("receiver: {}", e))?;
+    write_address(&mut payload, options.sender.as_ref().unwrap_or(&unroutable)).map_err(|e| format!("sender: {}", e))?;
+    payload.extend_from_slice(&options.nonce.to_le_bytes());
+    payload.extend_from_slice(&encode_compact_size(user_agent.len() as u64));
+    payload.extend_from_slice(user_agent.as_bytes());
+    payload.extend_from_slice(&options.start_height.to_le_bytes());
+    payload.push(options.relay.unwrap_or(true) as u8);
+    Ok(payload)
+}
+
+/// Services, then the IPv6 (or IPv4-mapped) address and the port in network byte order.
+fn write_address(payload: &mut Vec<u8>, address: &NetAddress) -> Result<(), String> {
+    let ip = IpAddr::from_str(address.ip.trim()).map_err(|_| format!("invalid IP address \"{}\"", address.ip))?;
+    let ip = match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    };
+    payload.extend_from_slice(&address.services.to_le_bytes());
+    payload.extend_from_slice(&ip.octets());
+    payload.extend_from_slice(&address.port.to_be_bytes());
+    Ok(())
+}
+
+fn decode_payload(command: &str, payload: &[u8]) -> Result<Option<DecodedPayload>, String> {
+    let decoded = match command {
+        "version" => DecodedPayload::Version(decode_version(payload)?),
+        "inv" | "getdata" | "notfound" => DecodedPayload::Inventory { items: decode_inventory(payload)? },
+        "tx" => {
+            let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(payload).map_err(|e| e.to_string())?;
+            DecodedPayload::Tx { txid: tx.compute_txid().to_string(), wtxid: tx.compute_wtxid().to_string(), size: payload.len() }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(decoded))
+}
+
+fn decode_version(payload: &[u8]) -> Result<VersionPayload, String> {
+    let mut reader = Reader { bytes: payload, position: 0 };
+    let version = i32::from_le_bytes(reader.array()?);
+    let services = u64::from_le_bytes(reader.array()?);
+    let timestamp = i64::from_le_bytes(reader.array()?);
+    let receiver = reader.address()?;
+    let sender = reader.address()?;
+    let nonce = u64::from_le_bytes(reader.array()?);
+    let agent_length = reader.compact_size()?;
+    if agent_length > 256 {
+        return Err(format!("user agent of {} bytes is over the 256-byte limit", agent_length));
+    }
+    let user_agent = String::from_utf8_lossy(reader.take(agent_length as usize)?).into_owned();
+    let start_height = i32::from_le_bytes(reader.array()?);
+    let relay = match reader.remaining() {
+        [] => true,
+        rest => rest[0] != 0,
+    };
+    Ok(VersionPayload { version, services, timestamp, receiver, sender, nonce: nonce.to_string(), user_agent, start_height, relay })
+}
+
+fn decode_inventory(payload: &[u8]) -> Result<Vec<InventoryItem>, String> {
+    let mut reader = Reader { bytes: payload, position: 0 };
+    let count = reader.compact_size()?;
+    if count > MAX_INV_SIZE {
+        return Err(format!("{} items is over the limit of {}", count, MAX_INV_SIZE));
+    }
+    let mut items = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let inv_type = u32::from_le_bytes(reader.array()?);
+        let hash = sha256d::Hash::from_byte_array(reader.array()?);
+        items.push(InventoryItem { inv_type: inventory_name(inv_type), hash: hash.to_string() });
+    }
+    if !reader.remaining().is_empty() {
+        return Err(format!("{} bytes follow the last item", reader.remaining().len()));
+    }
+    Ok(items)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len()).ok_or("ends early")?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("N bytes"))
+    }
+
+    fn compact_size(&mut self) -> Result<u64, String> {
+        let decoded = decode_compact_size(self.remaining())?;
+        self.position += decoded.bytes_consumed;
+        Ok(decoded.value)
+    }
+
+    fn address(&mut self) -> Result<NetAddress, String> {
+        let services = u64::from_le_bytes(self.array()?);
+        let ip = Ipv6Addr::from(self.array::<16>()?);
+        let port = u16::from_be_bytes(self.array()?);
+        let ip = match ip.to_ipv4_mapped() {
+            Some(v4) => v4.to_string(),
+            None => ip.to_string(),
+        };
+        Ok(NetAddress { services, ip, port })
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+}
@@ -0,0 +1,5 @@
+pub mod bloom;
+pub mod message;
+
+pub use bloom::{bloom_filter_contains, create_bloom_filter, filter_matches_transaction};
+pub use message::{build_getdata_message, build_inv_message, build_p2p_message, build_tx_message, build_verack_message, build_version_message, parse_p2p_message};
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{ScriptBuf, Transaction, TxOut};
+use crate::privacy::{input_scripts, input_types, is_round, output_types};
+use crate::transaction::ordering::bip69_order;
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::to_json_with_amounts;
+use crate::utils::metrics::timed;
+
+/// Confidence is capped here: every rule has wallets that break it.
+const MAX_CONFIDENCE: f64 = 0.95;
+
+pub const CHANGE_NOTE: &str =
+    "Heuristic guess: each rule is one chain analysis uses, and wallets that randomize script types, amounts or output order defeat them";
+
+/// One rule pointing at one output.
+pub struct ChangeHit {
+    pub vout: usize,
+    pub heuristic: &'static str,
+    pub weight: f64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HeuristicHit {
+    pub vout: usize,
+    pub heuristic: String,
+    pub weight: f64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeCandidate {
+    pub vout: usize,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    /// Sum of the weights of the rules that fired for this output.
+    pub score: f64,
+    pub heuristics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeGuess {
+    pub txid: String,
+    /// Always `"heuristic"`: the result is a guess, not something the transaction proves.
+    pub method: String,
+    #[serde(alias = "change_vout")]
+    pub change_vout: Option<usize>,
+    /// 0 to 0.95: how far the best candidate's score is ahead of the runner-up's.
+    pub confidence: f64,
+    pub heuristics: Vec<HeuristicHit>,
+    pub candidates: Vec<ChangeCandidate>,
+    pub note: String,
+    pub warnings: Vec<String>,
+}
+
+/// Which output of `tx_hex` is most likely change, by the rules the privacy analyzer uses
+/// plus two weaker ones, with the rules that fired. Several rules agreeing, and none
+/// pointing elsewhere, is what makes the confidence high.
+#[wasm_bindgen]
+pub fn guess_change_output(tx_hex: &str, prevouts_json: Option<String>, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("guess_change_output", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+        let prevouts: Option<Vec<TxOut>> = match prevouts_json {
+            Some(json) => Some(parse_prevouts(&json, tx.input.len())?),
+            None => None,
+        };
+
+        to_json_with_amounts(&guess_change(&tx, prevouts.as_deref()), amount_format, compact)
+    })
+}
+
+pub fn guess_change(tx: &Transaction, prevouts: Option<&[TxOut]>) -> ChangeGuess {
+    let input_scripts = input_scripts(tx, prevouts);
+    let input_types = input_types(tx, &input_scripts);
+    let output_types = output_types(tx);
+    let hits = change_hits(tx, &input_scripts, &input_types, &output_types);
+
+    let mut warnings = Vec::new();
+    let candidates: Vec<ChangeCandidate> = candidate_outputs(tx)
+        .into_iter()
+        .map(|vout| {
+            let fired: Vec<&ChangeHit> = hits.iter().filter(|hit| hit.vout == vout).collect();
+            ChangeCandidate {
+                vout,
+                amount: tx.output[vout].value.to_sat(),
+                script_type: output_types[vout].clone(),
+                score: round_score(fired.iter().fold(0.0, |score, hit| score + hit.weight)),
+                heuristics: fired.iter().map(|hit| hit.heuristic.to_string()).collect(),
+            }
+        })
+        .collect();
+    if candidates.len() < 2 {
+        warnings.push("fewer than two spendable outputs, so there is no change to tell apart".to_string());
+    }
+    if input_scripts.iter().any(Option::is_none) {
+        warnings.push("some spent scripts couldn't be determined; pass prevouts_json to use every input".to_string());
+    }
+
+    let mut ranked: Vec<&ChangeCandidate> = candidates.iter().collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let (change_vout, confidence) = match ranked.as_slice() {
+        [best, rest @ ..] if best.score > 0.0 => {
+            let runner_up = rest.first().map_or(0.0, |candidate| candidate.score);
+            match best.score - runner_up {
+                margin if margin > 0.0 => (Some(best.vout), round_score(margin.min(MAX_CONFIDENCE))),
+                _ => {
+                    warnings.push("the rules point at more than one output equally, so none is picked".to_string());
+                    (None, 0.0)
+                }
+            }
+        }
+        _ => (None, 0.0),
+    };
+
+    ChangeGuess {
+        txid: tx.compute_txid().to_string(),
+        method: "heuristic".to_string(),
+        change_vout,
+        confidence,
+        heuristics: hits
+            .into_iter()
+            .map(|hit| HeuristicHit {
+                vout: hit.vout,
+                heuristic: hit.heuristic.to_string(),
+                weight: hit.weight,
+                reason: hit.reason,
+            })
+            .collect(),
+        candidates,
+        note: CHANGE_NOTE.to_string(),
+        warnings,
+    }
+}
+
+/// Every rule that singles out an output as change. Each one only fires when it tells
+/// the outputs apart; a rule every output satisfies says nothing.
+pub fn change_hits(tx: &Transaction, input_scripts: &[Option<ScriptBuf>], input_types: &[String], output_types: &[String]) -> Vec<ChangeHit> {
+    let candidates = candidate_outputs(tx);
+    let mut hits = Vec::new();
+    if candidates.len() < 2 {
+        return hits;
+    }
+
+    let input_type: HashSet<&String> = input_types.iter().collect();
+    if let [input_type] = input_type.into_iter().collect::<Vec<_>>().as_slice() {
+        let matching: Vec<usize> = candidates.iter().copied().filter(|&i| &output_types[i] == *input_type).collect();
+        if let [vout] = matching.as_slice() {
+            hits.push(ChangeHit {
+                vout: *vout,
+                heuristic: "script_type_match",
+                weight: 0.35,
+                reason: format!("it is the only output of the inputs' type ({})", input_type),
+            });
+        }
+    }
+
+    let non_round: Vec<usize> = candidates.iter().copied().filter(|&i| !is_round(tx.output[i].value.to_sat())).collect();
+    if let [vout] = non_round.as_slice() {
+        hits.push(ChangeHit {
+            vout: *vout,
+            heuristic: "non_round_amount",
+            weight: 0.25,
+            reason: "it is the only output with a non-round amount".to_string(),
+        });
+    }
+
+    let spent: HashSet<&ScriptBuf> = input_scripts.iter().flatten().collect();
+    let pays_back: Vec<usize> = candidates.iter().copied().filter(|&i| spent.contains(&tx.output[i].script_pubkey)).collect();
+    if pays_back.len() < candidates.len() {
+        for &vout in &pays_back {
+            hits.push(ChangeHit {
+                vout,
+                heuristic: "pays_input_script",
+                weight: 0.6,
+                reason: "it pays back to an address the inputs spend from".to_string(),
+            });
+        }
+    }
+
+    // With only two outputs and no BIP69 sorting to explain their order, the last one is
+    // where most wallets put change. A sorted transaction's order carries no information.
+    let (_, output_order) = bip69_order(tx);
+    let sorted = output_order.iter().enumerate().all(|(i, &j)| i == j);
+    if let [_, last] = candidates.as_slice() {
+        if !sorted {
+            hits.push(ChangeHit {
+                vout: *last,
+                heuristic: "position",
+                weight: 0.1,
+                reason: "it is the last of two outputs, where most wallets put change".to_string(),
+            });
+        }
+    }
+
+    hits
+}
+
+fn candidate_outputs(tx: &Transaction) -> Vec<usize> {
+    (0..tx.output.len()).filter(|&i| !tx.output[i].script_pubkey.is_op_return()).collect()
+}
+
+fn round_score(score: f64) -> f64 {
+    (score * 100.0).round() / 100.0
+}
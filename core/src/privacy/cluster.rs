@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{ScriptBuf, Transaction};
+use crate::privacy::input_script_from_shape;
+use crate::script::output_script_type;
+use crate::transaction::parse_tx_hex;
+use crate::utils::bytes_to_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::metrics::timed;
+use crate::utils::network::network_or_default;
+
+/// A transaction whose outputs repeat one amount at least this often, with at least as
+/// many inputs, looks like a CoinJoin, where inputs belong to different people.
+const COINJOIN_EQUAL_OUTPUTS: usize = 3;
+
+pub const CLUSTER_NOTE: &str =
+    "Heuristic: common-input-ownership assumes one owner per transaction, which CoinJoins and payjoins break on purpose";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterScript {
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    pub address: Option<String>,
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputCluster {
+    pub scripts: Vec<ClusterScript>,
+    /// Spent outputs whose script couldn't be determined (taproot key-path spends whose
+    /// funding transaction isn't in the set), as `txid:vout`.
+    #[serde(alias = "unresolved_outpoints")]
+    pub unresolved_outpoints: Vec<String>,
+    /// Transactions whose inputs joined this cluster.
+    pub txids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SkippedTx {
+    pub txid: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InputClusters {
+    /// Always `"common_input_ownership"`: clusters are inferred, not proven.
+    pub method: String,
+    pub transactions: usize,
+    /// Largest first.
+    pub clusters: Vec<InputCluster>,
+    pub skipped: Vec<SkippedTx>,
+    pub note: String,
+}
+
+/// Groups the scripts spent across `txs_json` by common-input-ownership: scripts spent
+/// together in one transaction are taken to share an owner, and the groups are the
+/// connected components of that relation. CoinJoin-shaped transactions are left out.
+#[wasm_bindgen]
+pub fn cluster_inputs(txs_json: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("cluster_inputs", txs_json.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let hexes: Vec<String> = parse_json_list(txs_json, "transactions").map_err(|e| JsValue::from_str(&e))?;
+        let mut txs: Vec<(String, Transaction)> = Vec::with_capacity(hexes.len());
+        let mut seen = BTreeSet::new();
+        for (index, hex) in hexes.iter().enumerate() {
+            let tx = parse_tx_hex(hex).map_err(|e| JsValue::from_str(&format!("Transaction {}: {}", index, e)))?;
+            let txid = tx.compute_txid().to_string();
+            // The same transaction can show up twice, e.g. once per address history it belongs to.
+            if seen.insert(txid.clone()) {
+                txs.push((txid, tx));
+            }
+        }
+
+        // Inputs that can't be read from the witness are resolved against outputs elsewhere in the set.
+        let mut created: HashMap<String, &ScriptBuf> = HashMap::new();
+        for (txid, tx) in &txs {
+            for (vout, output) in tx.output.iter().enumerate() {
+                created.insert(format!("{}:{}", txid, vout), &output.script_pubkey);
+            }
+        }
+
+        let mut nodes = Nodes::default();
+        let mut skipped = Vec::new();
+        for (txid, tx) in &txs {
+            if tx.is_coinbase() {
+                continue;
+            }
+            if let Some(reason) = coinjoin_reason(tx) {
+                skipped.push(SkippedTx { txid: txid.clone(), reason });
+                continue;
+            }
+            let ids: Vec<usize> = tx
+                .input
+                .iter()
+                .map(|txin| {
+                    let outpoint = txin.previous_output.to_string();
+                    let node = match input_script_from_shape(txin).or_else(|| created.get(&outpoint).map(|script| (*script).clone())) {
+                        Some(script) => Node::Script(script),
+                        None => Node::Outpoint(outpoint),
+                    };
+                    nodes.id(node)
+                })
+                .collect();
+            for &id in &ids {
+                nodes.union(ids[0], id);
+                nodes.spent_in[id].insert(txid.clone());
+            }
+        }
+
+        let mut components: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for id in 0..nodes.all.len() {
+            let root = nodes.find(id);
+            components.entry(root).or_default().push(id);
+        }
+        let mut clusters: Vec<InputCluster> = components
+            .into_values()
+            .map(|members| {
+                let mut cluster = InputCluster { scripts: Vec::new(), unresolved_outpoints: Vec::new(), txids: Vec::new() };
+                let mut txids = BTreeSet::new();
+                for id in members {
+                    match &nodes.all[id] {
+                        Node::Script(script) => cluster.scripts.push(ClusterScript {
+                            script_pubkey: bytes_to_hex(script.as_bytes()),
+                            address: network.address(script),
+                            script_type: output_script_type(script).to_string(),
+                        }),
+                        Node::Outpoint(outpoint) => cluster.unresolved_outpoints.push(outpoint.clone()),
+                    }
+                    txids.extend(nodes.spent_in[id].iter().cloned());
+                }
+                cluster.scripts.sort_by(|a, b| a.script_pubkey.cmp(&b.script_pubkey));
+                cluster.unresolved_outpoints.sort();
+                cluster.txids = txids.into_iter().collect();
+                cluster
+            })
+            .collect();
+        clusters.sort_by(|a, b| {
+            let size = |cluster: &InputCluster| cluster.scripts.len() + cluster.unresolved_outpoints.len();
+            size(b).cmp(&size(a)).then_with(|| a.txids.cmp(&b.txids))
+        });
+
+        to_json(&InputClusters {
+            method: "common_input_ownership".to_string(),
+            transactions: txs.len(),
+            clusters,
+            skipped,
+            note: CLUSTER_NOTE.to_string(),
+        }, compact)
+    })
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Node {
+    Script(ScriptBuf),
+    Outpoint(String),
+}
+
+/// Union-find over spent scripts and unresolved outpoints.
+#[derive(Default)]
+struct Nodes {
+    ids: HashMap<Node, usize>,
+    all: Vec<Node>,
+    parent: Vec<usize>,
+    spent_in: Vec<BTreeSet<String>>,
+}
+
+impl Nodes {
+    fn id(&mut self, node: Node) -> usize {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = self.all.len();
+        self.ids.insert(node.clone(), id);
+        self.all.push(node);
+        self.parent.push(id);
+        self.spent_in.push(BTreeSet::new());
+        id
+    }
+
+    fn find(&mut self, mut id: usize) -> usize {
+        while self.parent[id] != id {
+            self.parent[id] = self.parent[self.parent[id]];
+            id = self.parent[id];
+        }
+        id
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b.max(a)] = a.min(b);
+        }
+    }
+}
+
+fn coinjoin_reason(tx: &Transaction) -> Option<String> {
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for output in tx.output.iter().filter(|output| !output.script_pubkey.is_op_return()) {
+        *counts.entry(output.value.to_sat()).or_default() += 1;
+    }
+    let (&amount, &count) = counts.iter().max_by_key(|(&amount, &count)| (count, amount))?;
+    (count >= COINJOIN_EQUAL_OUTPUTS && tx.input.len() >= count).then(|| {
+        format!("{} outputs of {} sats and {} inputs look like a CoinJoin, whose inputs have different owners", count, amount, tx.input.len())
+    })
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::Hash;
+use bitcoin::{PubkeyHash, Script, ScriptBuf, Transaction, TxIn, TxOut, WPubkeyHash};
+use crate::decode::inputs::{script_sig_pushes, spend_type_from_shape};
+use crate::script::output_script_type;
+use crate::transaction::ordering::bip69_order;
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use change::{change_hits, ChangeHit};
+
+pub mod change;
+pub mod cluster;
+#[cfg(feature = "http")]
+pub mod reuse;
+
+pub use change::guess_change_output;
+pub use cluster::cluster_inputs;
+#[cfg(feature = "http")]
+pub use reuse::find_reuse;
+
+/// Amounts that are a multiple of this (0.001 BTC) read as human-chosen payments.
+pub const ROUND_AMOUNT_SAT: u64 = 100_000;
+
+#[derive(Serialize, Deserialize)]
+pub struct PrivacyFinding {
+    pub kind: String,
+    pub severity: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivacyReport {
+    pub findings: Vec<PrivacyFinding>,
+    #[serde(alias = "input_types")]
+    pub input_types: Vec<String>,
+    #[serde(alias = "output_types")]
+    pub output_types: Vec<String>,
+    #[serde(alias = "distinct_input_scripts")]
+    pub distinct_input_scripts: Option<usize>,
+    #[serde(alias = "likely_change")]
+    pub likely_change: Option<usize>,
+}
+
+#[wasm_bindgen]
+pub fn analyze_privacy(tx_hex: &str, prevouts_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("analyze_privacy", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+        let prevouts: Option<Vec<TxOut>> = match prevouts_json {
+            Some(json) => Some(parse_prevouts(&json, tx.input.len())?),
+            None => None,
+        };
+
+        to_json(&privacy_report(&tx, prevouts.as_deref()), compact)
+    })
+}
+
+pub fn privacy_report(tx: &Transaction, prevouts: Option<&[TxOut]>) -> PrivacyReport {
+    let mut findings = Vec::new();
+
+    let input_scripts = input_scripts(tx, prevouts);
+    let input_types = input_types(tx, &input_scripts);
+    let output_types = output_types(tx);
+
+    let known_inputs: HashSet<&ScriptBuf> = input_scripts.iter().flatten().collect();
+    for (index, output) in tx.output.iter().enumerate() {
+        if known_inputs.contains(&output.script_pubkey) {
+            findings.push(finding(
+                "address_reuse",
+                "high",
+                format!("output {} pays back to an address this transaction spends from", index),
+            ));
+        }
+    }
+
+    let mut output_counts: HashMap<&ScriptBuf, usize> = HashMap::new();
+    for output in &tx.output {
+        *output_counts.entry(&output.script_pubkey).or_default() += 1;
+    }
+    if output_counts.values().any(|&count| count > 1) {
+        findings.push(finding("address_reuse", "medium", "the same address receives more than one output".to_string()));
+    }
+
+    let payments: Vec<(usize, &TxOut)> = tx
+        .output
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| !output.script_pubkey.is_op_return())
+        .collect();
+    for (index, output) in payments.iter().filter(|(_, output)| is_round(output.value.to_sat())) {
+        findings.push(finding(
+            "round_amount",
+            "low",
+            format!("output {} is a round amount ({} sats), which marks it as the payment", index, output.value.to_sat()),
+        ));
+    }
+
+    let likely_change = likely_change(&change_hits(tx, &input_scripts, &input_types, &output_types));
+    if let Some((index, reason)) = &likely_change {
+        findings.push(finding(
+            "identifiable_change",
+            "medium",
+            format!("output {} is identifiable as change: {}", index, reason),
+        ));
+    }
+
+    let distinct_types: HashSet<&String> = input_types.iter().filter(|t| t.as_str() != "unknown").collect();
+    if distinct_types.len() > 1 {
+        let mut names: Vec<&str> = distinct_types.iter().map(|t| t.as_str()).collect();
+        names.sort();
+        findings.push(finding(
+            "mixed_input_types",
+            "medium",
+            format!("inputs mix script types ({}), which narrows down the wallet", names.join(", ")),
+        ));
+    }
+
+    let distinct_input_scripts = if input_scripts.iter().all(Option::is_some) { Some(known_inputs.len()) } else { None };
+    if tx.input.len() > 1 {
+        match distinct_input_scripts {
+            Some(1) => findings.push(finding(
+                "common_input_ownership",
+                "low",
+                format!("{} inputs are spent from the same address", tx.input.len()),
+            )),
+            Some(count) => findings.push(finding(
+                "common_input_ownership",
+                "medium",
+                format!("spending {} inputs together links {} addresses as belonging to one wallet", tx.input.len(), count),
+            )),
+            None => findings.push(finding(
+                "common_input_ownership",
+                "medium",
+                format!("spending {} inputs together links their addresses as belonging to one wallet", tx.input.len()),
+            )),
+        }
+    }
+
+    if tx.input.len() > 1 || payments.len() > 1 {
+        let (input_order, output_order) = bip69_order(tx);
+        let sorted = input_order.iter().enumerate().all(|(i, &j)| i == j)
+            && output_order.iter().enumerate().all(|(i, &j)| i == j);
+        if sorted {
+            findings.push(finding("bip69", "info", "inputs and outputs follow BIP69 order, a recognizable wallet behaviour".to_string()));
+        } else {
+            findings.push(finding(
+                "bip69",
+                "info",
+                "order is not BIP69; if the wallet always puts change in the same position, the position reveals it".to_string(),
+            ));
+        }
+    }
+
+    PrivacyReport {
+        findings,
+        input_types,
+        output_types,
+        distinct_input_scripts,
+        likely_change: likely_change.map(|(index, _)| index),
+    }
+}
+
+/// The report only names change on the two rules that single out one output by
+/// themselves: the only output matching the inputs' script type, or else the only
+/// non-round amount. `guess_change_output` weighs the weaker rules as well.
+fn likely_change(hits: &[ChangeHit]) -> Option<(usize, String)> {
+    ["script_type_match", "non_round_amount"]
+        .iter()
+        .find_map(|&heuristic| hits.iter().find(|hit| hit.heuristic == heuristic))
+        .map(|hit| (hit.vout, hit.reason.clone()))
+}
+
+/// Each input's spent script, from the prevouts when given. Without them the script can
+/// still be rebuilt for most single-key and script-hash spends, since the witness or
+/// scriptSig reveals the key or script.
+pub fn input_scripts(tx: &Transaction, prevouts: Option<&[TxOut]>) -> Vec<Option<ScriptBuf>> {
+    tx.input
+        .iter()
+        .enumerate()
+        .map(|(i, txin)| match prevouts {
+            Some(prevouts) => Some(prevouts[i].script_pubkey.clone()),
+            None => input_script_from_shape(txin),
+        })
+        .collect()
+}
+
+pub fn input_types(tx: &Transaction, input_scripts: &[Option<ScriptBuf>]) -> Vec<String> {
+    tx.input
+        .iter()
+        .zip(input_scripts)
+        .map(|(txin, script)| match script {
+            Some(script) => output_script_type(script).to_string(),
+            None => output_type_of_spend(spend_type_from_shape(txin)).to_string(),
+        })
+        .collect()
+}
+
+pub fn output_types(tx: &Transaction) -> Vec<String> {
+    tx.output.iter().map(|output| output_script_type(&output.script_pubkey).to_string()).collect()
+}
+
+pub fn is_round(amount: u64) -> bool {
+    amount > 0 && amount.is_multiple_of(ROUND_AMOUNT_SAT)
+}
+
+pub fn input_script_from_shape(txin: &TxIn) -> Option<ScriptBuf> {
+    let pushes = script_sig_pushes(&txin.script_sig);
+    match spend_type_from_shape(txin) {
+        "p2wpkh" => {
+            let pubkey = txin.witness.nth(1)?;
+            Some(ScriptBuf::new_p2wpkh(&WPubkeyHash::hash(pubkey)))
+        }
+        "p2wsh" => Some(ScriptBuf::new_p2wsh(&Script::from_bytes(txin.witness.last()?).wscript_hash())),
+        "p2pkh" => Some(ScriptBuf::new_p2pkh(&PubkeyHash::hash(pushes?.get(1)?))),
+        "p2sh-p2wpkh" | "p2sh-p2wsh" | "p2sh" => {
+            Some(ScriptBuf::new_p2sh(&Script::from_bytes(pushes?.last()?).script_hash()))
+        }
+        _ => None,
+    }
+}
+
+fn output_type_of_spend(spend_type: &str) -> &str {
+    match spend_type {
+        "p2sh-p2wpkh" | "p2sh-p2wsh" => "p2sh",
+        "p2tr-keypath" | "p2tr-scriptpath" => "p2tr",
+        other => other,
+    }
+}
+
+fn finding(kind: &str, severity: &str, message: String) -> PrivacyFinding {
+    PrivacyFinding {
+        kind: kind.to_string(),
+        severity: severity.to_string(),
+        message,
+    }
+}
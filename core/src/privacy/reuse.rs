@@ -0,0 +1,165 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::ScriptBuf;
+use crate::esplora::EsploraTx;
+use crate::privacy::input_script_from_shape;
+use crate::transaction::parse_tx_hex;
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+/// A history entry: raw transaction hex, or a decoded transaction in Esplora's format.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum HistoryTx {
+    Raw(String),
+    Decoded(EsploraTx),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReusedScript {
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    pub address: Option<String>,
+    #[serde(alias = "times_received")]
+    pub times_received: usize,
+    #[serde(alias = "received_in")]
+    pub received_in: Vec<String>,
+    #[serde(alias = "spent_in")]
+    pub spent_in: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReuseReport {
+    #[serde(alias = "scripts_seen")]
+    pub scripts_seen: usize,
+    pub reused: Vec<ReusedScript>,
+}
+
+#[derive(Default)]
+struct ScriptUsage {
+    times_received: usize,
+    received_in: BTreeSet<String>,
+    spent_in: BTreeSet<String>,
+}
+
+/// Reports scripts that receive more than once, or that a transaction both spends from and pays to.
+#[wasm_bindgen]
+pub fn find_reuse(txs_json: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("find_reuse", txs_json.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let entries: Vec<HistoryTx> = serde_json::from_str(txs_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid transactions JSON: {}", e)))?;
+
+        let txs: Vec<NormalizedTx> = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| normalize(entry).map_err(|e| JsValue::from_str(&format!("Transaction {}: {}", index, e))))
+            .collect::<Result<_, _>>()?;
+
+        // Raw inputs that can't be read from the witness are resolved against outputs elsewhere in the set.
+        let mut created: HashMap<(&str, u32), &ScriptBuf> = HashMap::new();
+        for tx in &txs {
+            for (vout, script) in tx.outputs.iter().enumerate() {
+                created.insert((tx.txid.as_str(), vout as u32), script);
+            }
+        }
+
+        let mut usage: HashMap<ScriptBuf, ScriptUsage> = HashMap::new();
+        let mut counted: HashSet<&str> = HashSet::new();
+        // The same transaction can show up twice, e.g. once per address history it belongs to.
+        for tx in txs.iter().filter(|tx| counted.insert(tx.txid.as_str())) {
+            for script in tx.outputs.iter().filter(|script| !script.is_op_return()) {
+                let entry = usage.entry(script.clone()).or_default();
+                entry.times_received += 1;
+                entry.received_in.insert(tx.txid.clone());
+            }
+            for input in &tx.inputs {
+                let script = input.script.clone().or_else(|| {
+                    input
+                        .outpoint
+                        .as_ref()
+                        .and_then(|(txid, vout)| created.get(&(txid.as_str(), *vout)).map(|s| (*s).clone()))
+                });
+                if let Some(script) = script {
+                    usage.entry(script).or_default().spent_in.insert(tx.txid.clone());
+                }
+            }
+        }
+
+        let scripts_seen = usage.len();
+        let mut reused: Vec<ReusedScript> = usage
+            .into_iter()
+            .filter(|(_, usage)| usage.times_received > 1 || usage.spent_in.iter().any(|txid| usage.received_in.contains(txid)))
+            .map(|(script, usage)| ReusedScript {
+                script_pubkey: bytes_to_hex(script.as_bytes()),
+                address: script_address(&script, &network),
+                times_received: usage.times_received,
+                received_in: usage.received_in.into_iter().collect(),
+                spent_in: usage.spent_in.into_iter().collect(),
+            })
+            .collect();
+        reused.sort_by(|a, b| b.times_received.cmp(&a.times_received).then_with(|| a.script_pubkey.cmp(&b.script_pubkey)));
+
+        to_json(&ReuseReport { scripts_seen, reused }, compact)
+    })
+}
+
+struct NormalizedTx {
+    txid: String,
+    inputs: Vec<NormalizedInput>,
+    outputs: Vec<ScriptBuf>,
+}
+
+struct NormalizedInput {
+    script: Option<ScriptBuf>,
+    outpoint: Option<(String, u32)>,
+}
+
+fn normalize(entry: &HistoryTx) -> Result<NormalizedTx, String> {
+    match entry {
+        HistoryTx::Raw(hex) => {
+            let tx = parse_tx_hex(hex)?;
+            Ok(NormalizedTx {
+                txid: tx.compute_txid().to_string(),
+                inputs: tx
+                    .input
+                    .iter()
+                    .filter(|txin| !txin.previous_output.is_null())
+                    .map(|txin| NormalizedInput {
+                        script: input_script_from_shape(txin),
+                        outpoint: Some((txin.previous_output.txid.to_string(), txin.previous_output.vout)),
+                    })
+                    .collect(),
+                outputs: tx.output.iter().map(|output| output.script_pubkey.clone()).collect(),
+            })
+        }
+        HistoryTx::Decoded(tx) => {
+            let script = |hex: &str| ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid scriptpubkey: {}", e));
+            let inputs = tx
+                .vin
+                .iter()
+                .filter(|vin| !vin.is_coinbase)
+                .map(|vin| {
+                    Ok(NormalizedInput {
+                        script: vin.prevout.as_ref().map(|prevout| script(&prevout.scriptpubkey)).transpose()?,
+                        outpoint: None,
+                    })
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(NormalizedTx {
+                txid: tx.txid.clone(),
+                inputs,
+                outputs: tx.vout.iter().map(|vout| script(&vout.scriptpubkey)).collect::<Result<_, _>>()?,
+            })
+        }
+    }
+}
+
+fn script_address(script: &ScriptBuf, network: &NetworkParams) -> Option<String> {
+    network.address(script)
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::psbt::raw;
+use bitcoin::Psbt;
+use crate::psbt::v2::{effective_modifiable, parse_versioned_psbt, HAS_SIGHASH_SINGLE, INPUTS_MODIFIABLE, OUTPUTS_MODIFIABLE};
+use crate::psbt::xpub::{format_path, global_xpubs, GlobalXpub};
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::to_json_with_amounts;
+use crate::utils::metrics::timed;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedPsbt {
+    pub txid: String,
+    pub version: u32,
+    #[serde(alias = "tx_version")]
+    pub tx_version: i32,
+    #[serde(alias = "lock_time")]
+    pub lock_time: u32,
+    /// Version 2 only: the locktime used when no input requires one.
+    #[serde(default, alias = "fallback_lock_time")]
+    pub fallback_lock_time: Option<u32>,
+    /// Version 2 only: what a Constructor may still change.
+    #[serde(default)]
+    pub modifiable: Option<TxModifiable>,
+    #[serde(alias = "global_xpubs")]
+    pub global_xpubs: Vec<GlobalXpub>,
+    pub inputs: Vec<DecodedPsbtInput>,
+    pub outputs: Vec<DecodedPsbtOutput>,
+    pub fee: Option<u64>,
+    pub proprietary: Vec<ProprietaryField>,
+    pub unknown: Vec<UnknownField>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedPsbtInput {
+    pub index: usize,
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount")]
+    pub amount: Option<u64>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
+    #[serde(alias = "sighash_type")]
+    pub sighash_type: Option<String>,
+    #[serde(alias = "bip32_derivations")]
+    pub bip32_derivations: Vec<KeyOrigin>,
+    #[serde(alias = "partial_signatures")]
+    pub partial_signatures: usize,
+    pub finalized: bool,
+    #[serde(default, alias = "required_time_lock_time")]
+    pub required_time_lock_time: Option<u32>,
+    #[serde(default, alias = "required_height_lock_time")]
+    pub required_height_lock_time: Option<u32>,
+    pub proprietary: Vec<ProprietaryField>,
+    pub unknown: Vec<UnknownField>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxModifiable {
+    pub inputs: bool,
+    pub outputs: bool,
+    #[serde(alias = "has_sighash_single")]
+    pub has_sighash_single: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedPsbtOutput {
+    pub index: usize,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    #[serde(alias = "bip32_derivations")]
+    pub bip32_derivations: Vec<KeyOrigin>,
+    pub proprietary: Vec<ProprietaryField>,
+    pub unknown: Vec<UnknownField>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyOrigin {
+    pub pubkey: String,
+    pub fingerprint: String,
+    pub path: String,
+}
+
+/// A BIP174 proprietary-use (0xfc) entry, all hex but the subtype.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProprietaryField {
+    pub prefix: String,
+    pub subtype: u8,
+    #[serde(alias = "key_data")]
+    pub key_data: String,
+    pub value: String,
+}
+
+/// An entry whose key type this library doesn't interpret, kept as it was read.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnknownField {
+    #[serde(alias = "key_type")]
+    pub key_type: u8,
+    #[serde(alias = "key_data")]
+    pub key_data: String,
+    pub value: String,
+}
+
+#[wasm_bindgen]
+pub fn decode_psbt(psbt_base64: &str, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("decode_psbt", psbt_base64.len(), || {
+        let versioned = parse_versioned_psbt(psbt_base64).map_err(|e| JsValue::from_str(&e))?;
+        let mut decoded = describe_psbt(&versioned.psbt);
+        decoded.version = versioned.version();
+        if let Some(fields) = &versioned.v2 {
+            let flags = effective_modifiable(fields, &versioned.psbt);
+            decoded.fallback_lock_time = fields.fallback_locktime;
+            decoded.modifiable = Some(TxModifiable {
+                inputs: flags & INPUTS_MODIFIABLE != 0,
+                outputs: flags & OUTPUTS_MODIFIABLE != 0,
+                has_sighash_single: flags & HAS_SIGHASH_SINGLE != 0,
+            });
+            for (input, required) in decoded.inputs.iter_mut().zip(&fields.inputs) {
+                input.required_time_lock_time = required.time;
+                input.required_height_lock_time = required.height;
+            }
+        }
+        to_json_with_amounts(&decoded, amount_format, compact)
+    })
+}
+
+pub fn describe_psbt(psbt: &Psbt) -> DecodedPsbt {
+    let tx = &psbt.unsigned_tx;
+
+    let inputs: Vec<DecodedPsbtInput> = psbt
+        .inputs
+        .iter()
+        .zip(&tx.input)
+        .enumerate()
+        .map(|(index, (input, txin))| {
+            let vout = txin.previous_output.vout;
+            let utxo = input
+                .witness_utxo
+                .clone()
+                .or_else(|| input.non_witness_utxo.as_ref().and_then(|prev| prev.output.get(vout as usize).cloned()));
+            DecodedPsbtInput {
+                index,
+                txid: txin.previous_output.txid.to_string(),
+                vout,
+                amount: utxo.as_ref().map(|u| u.value.to_sat()),
+                script_pubkey: utxo.as_ref().map(|u| bytes_to_hex(u.script_pubkey.as_bytes())),
+                sighash_type: input.sighash_type.map(|s| s.to_string()),
+                bip32_derivations: key_origins(&input.bip32_derivation),
+                partial_signatures: input.partial_sigs.len() + input.tap_key_sig.iter().count() + input.tap_script_sigs.len(),
+                finalized: input.final_script_sig.is_some() || input.final_script_witness.is_some(),
+                required_time_lock_time: None,
+                required_height_lock_time: None,
+                proprietary: proprietary_fields(&input.proprietary),
+                unknown: unknown_fields(&input.unknown),
+            }
+        })
+        .collect();
+
+    let outputs = psbt
+        .outputs
+        .iter()
+        .zip(&tx.output)
+        .enumerate()
+        .map(|(index, (output, txout))| DecodedPsbtOutput {
+            index,
+            amount: txout.value.to_sat(),
+            script_pubkey: bytes_to_hex(txout.script_pubkey.as_bytes()),
+            bip32_derivations: key_origins(&output.bip32_derivation),
+            proprietary: proprietary_fields(&output.proprietary),
+            unknown: unknown_fields(&output.unknown),
+        })
+        .collect();
+
+    // The fee is only known once every input carries its UTXO.
+    let input_total: Option<u64> = inputs.iter().map(|input| input.amount).sum();
+    let output_total: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+
+    DecodedPsbt {
+        txid: tx.compute_txid().to_string(),
+        version: psbt.version,
+        tx_version: tx.version.0,
+        lock_time: tx.lock_time.to_consensus_u32(),
+        fallback_lock_time: None,
+        modifiable: None,
+        global_xpubs: global_xpubs(psbt),
+        inputs,
+        outputs,
+        fee: input_total.and_then(|total| total.checked_sub(output_total)),
+        proprietary: proprietary_fields(&psbt.proprietary),
+        unknown: unknown_fields(&psbt.unknown),
+    }
+}
+
+fn proprietary_fields(map: &BTreeMap<raw::ProprietaryKey, Vec<u8>>) -> Vec<ProprietaryField> {
+    map.iter()
+        .map(|(key, value)| ProprietaryField {
+            prefix: bytes_to_hex(&key.prefix),
+            subtype: key.subtype,
+            key_data: bytes_to_hex(&key.key),
+            value: bytes_to_hex(value),
+        })
+        .collect()
+}
+
+fn unknown_fields(map: &BTreeMap<raw::Key, Vec<u8>>) -> Vec<UnknownField> {
+    map.iter()
+        .map(|(key, value)| UnknownField { key_type: key.type_value, key_data: bytes_to_hex(&key.key), value: bytes_to_hex(value) })
+        .collect()
+}
+
+fn key_origins(map: &BTreeMap<bitcoin::secp256k1::PublicKey, bitcoin::bip32::KeySource>) -> Vec<KeyOrigin> {
+    map.iter()
+        .map(|(pubkey, (fingerprint, path))| KeyOrigin {
+            pubkey: bytes_to_hex(&pubkey.serialize()),
+            fingerprint: fingerprint.to_string(),
+            path: format_path(path),
+        })
+        .collect()
+}
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::absolute::{LockTime, LOCK_TIME_THRESHOLD};
+use bitcoin::relative;
+use bitcoin::{Amount, Psbt, ScriptBuf, Sequence, Transaction, TxOut};
+#[cfg(feature = "signing")]
+use bitcoin::secp256k1::Secp256k1;
+#[cfg(feature = "signing")]
+use bitcoin::sighash::SighashCache;
+#[cfg(feature = "signing")]
+use bitcoin::{Network, Witness};
+use crate::psbt::parse_psbt;
+use crate::psbt::payjoin::{finalized_tx, input_utxo, psbt_utxos};
+use crate::psbt::v2::VersionedPsbt;
+#[cfg(feature = "signing")]
+use crate::psbt::v2::parse_versioned_psbt;
+use crate::transaction::locktime::{absolute_lock, LockStatus, BLOCK_INTERVAL_SECS};
+use crate::transaction::serialization::checked_serialize;
+#[cfg(feature = "signing")]
+use crate::transaction::sign::{key_signature, key_spend, key_spend_sighash, key_spend_signature, KeySignature, KeySpend};
+use crate::transaction::{unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::utils::bytes_to_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::network::network_or_default;
+#[cfg(feature = "signing")]
+use crate::wallet::parse_private_key;
+
+#[cfg(feature = "signing")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocktimeSigning {
+    pub psbt: String,
+    #[serde(alias = "signed_inputs")]
+    pub signed_inputs: Vec<usize>,
+    #[serde(alias = "finalized_inputs")]
+    pub finalized_inputs: Vec<usize>,
+    /// Every input is finalized, so `tx_hex` holds the transaction to store.
+    pub complete: bool,
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageInput {
+    pub index: usize,
+    pub outpoint: String,
+    pub sequence: u32,
+    /// Whether the sequence is below 0xffffffff, so this input doesn't switch the
+    /// locktime off.
+    #[serde(alias = "enables_locktime")]
+    pub enables_locktime: bool,
+    /// A BIP68 lock the input also carries, e.g. "144 blocks". It counts from the spent
+    /// coin's confirmation, which the PSBT doesn't record.
+    #[serde(alias = "relative_lock")]
+    pub relative_lock: Option<String>,
+    /// witness_utxo, non_witness_utxo, both, or missing.
+    pub utxo: String,
+    pub signed: bool,
+    pub finalized: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageReport {
+    pub txid: String,
+    pub locktime: u32,
+    /// "height", "time", or "none" without a locktime.
+    pub unit: String,
+    /// The locktime against `tip_height` and `mtp`. None for a timestamp locktime when
+    /// no `mtp` was given.
+    pub lock: Option<LockStatus>,
+    /// The first block height that can include the transaction.
+    #[serde(alias = "broadcastable_height")]
+    pub broadcastable_height: Option<u32>,
+    /// None when that can't be told without the median time past.
+    #[serde(alias = "broadcastable_now")]
+    pub broadcastable_now: Option<bool>,
+    pub inputs: Vec<PackageInput>,
+    /// Inputs with no UTXO data at all.
+    #[serde(alias = "missing_utxos")]
+    pub missing_utxos: Vec<usize>,
+    pub fee: Option<u64>,
+    /// Every input is finalized.
+    pub complete: bool,
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: Option<String>,
+    pub problems: Vec<String>,
+    pub summary: String,
+}
+
+/// An unsigned PSBT for a transaction that can't be mined before `locktime`, a block
+/// height or (from 500000000) a unix time. Every input gets sequence 0xfffffffe, so the
+/// locktime binds and the transaction doesn't signal RBF. Segwit and P2SH inputs get
+/// their witness_utxo from `inputs_json`; legacy inputs need non_witness_utxo added with
+/// `update_psbt`, and `package_report` lists them until then.
+#[wasm_bindgen]
+pub fn build_locktime_psbt(inputs_json: &str, outputs_json: &str, locktime: u32, network: Option<String>) -> Result<String, JsValue> {
+    let network_params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let inputs: Vec<TransactionInput> = parse_json_list(inputs_json, "inputs").map_err(|e| JsValue::from_str(&e))?;
+    let outputs: Vec<TransactionOutput> = parse_json_list(outputs_json, "outputs").map_err(|e| JsValue::from_str(&e))?;
+    if locktime == 0 {
+        return Err(JsValue::from_str("A locktime of 0 doesn't delay the transaction"));
+    }
+
+    let mut tx = unsigned_transaction(&inputs, &outputs, Some(&network_params))?;
+    tx.lock_time = LockTime::from_consensus(locktime);
+    for txin in &mut tx.input {
+        txin.script_sig = ScriptBuf::new();
+        txin.sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+    }
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| JsValue::from_str(&format!("Cannot build PSBT: {}", e)))?;
+
+    for (input, utxo) in psbt.inputs.iter_mut().zip(&inputs) {
+        let script_pubkey = ScriptBuf::from_hex(&utxo.script_pubkey)
+            .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e)))?;
+        if script_pubkey.is_witness_program() || script_pubkey.is_p2sh() {
+            input.witness_utxo = Some(TxOut { value: Amount::from_sat(utxo.amount), script_pubkey });
+        }
+    }
+
+    Ok(VersionedPsbt { psbt, v2: None }.to_base64())
+}
+
+/// Signs every single-key input of `private_key` in a PSBT from `build_locktime_psbt`
+/// (P2PKH, P2SH-P2WPKH, P2WPKH, P2TR key path), after checking the locktime is set and
+/// that no input's sequence would let the transaction ignore it. With `finalize`, the
+/// default, signed inputs get their final scriptSig and witness, and once every input is
+/// final the transaction comes back as `tx_hex` for storing, though nodes will reject it
+/// until the locktime passes.
+#[cfg(feature = "signing")]
+#[wasm_bindgen]
+pub fn sign_locktime_psbt(psbt: &str, private_key: &str, finalize: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let private_key = parse_private_key(private_key, None, Network::Testnet)?;
+    let mut versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    let finalize = finalize.unwrap_or(true);
+
+    if let Some(problem) = locktime_problems(&versioned.psbt.unsigned_tx).into_iter().next() {
+        return Err(JsValue::from_str(&format!("Refusing to sign: {}", problem)));
+    }
+    let prevouts = psbt_utxos(&versioned.psbt)
+        .into_iter()
+        .enumerate()
+        .map(|(index, utxo)| {
+            utxo.ok_or_else(|| format!("Input {} has no UTXO data; add witness_utxo or non_witness_utxo with update_psbt", index))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    let psbt = &mut versioned.psbt;
+    let secret_key = private_key.inner;
+    let mut cache = SighashCache::new(psbt.unsigned_tx.clone());
+    let mut signed_inputs = Vec::new();
+    let mut finalized_inputs = Vec::new();
+    for (index, prevout) in prevouts.iter().enumerate() {
+        if is_finalized(&psbt.inputs[index]) {
+            finalized_inputs.push(index);
+            continue;
+        }
+        // Inputs for other keys are left for their signers.
+        let Ok(spend) = key_spend(&secp, &prevout.script_pubkey, &secret_key.public_key(&secp), private_key.compressed, "private key") else {
+            continue;
+        };
+        let sighash = key_spend_sighash(&mut cache, index, prevout, &prevouts, &spend)
+            .map_err(|e| JsValue::from_str(&format!("Failed to sign input {}: {}", index, e)))?;
+        let signature = key_signature(&secp, &spend, &sighash, &secret_key);
+        signed_inputs.push(index);
+
+        let input = &mut psbt.inputs[index];
+        if finalize {
+            let signature = key_spend_signature(&spend, signature)
+                .map_err(|e| JsValue::from_str(&format!("Failed to sign input {}: {}", index, e)))?;
+            finalize_input(input, signature.script_sig, signature.witness);
+            finalized_inputs.push(index);
+            continue;
+        }
+        match (&spend, signature) {
+            (KeySpend::P2tr(internal_key), KeySignature::Schnorr(signature)) => {
+                input.tap_key_sig = Some(signature);
+                input.tap_internal_key = Some(*internal_key);
+            }
+            (KeySpend::P2wpkh(pubkey), KeySignature::Ecdsa(signature)) => {
+                input.partial_sigs.insert((*pubkey).into(), signature);
+            }
+            (KeySpend::P2shP2wpkh(pubkey), KeySignature::Ecdsa(signature)) => {
+                input.redeem_script = Some(ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()));
+                input.partial_sigs.insert((*pubkey).into(), signature);
+            }
+            (KeySpend::P2pkh(pubkey), KeySignature::Ecdsa(signature)) => {
+                input.partial_sigs.insert(*pubkey, signature);
+            }
+            _ => return Err(JsValue::from_str(&format!("Failed to sign input {}: signature algorithm doesn't match the script type", index))),
+        }
+    }
+    finalized_inputs.sort_unstable();
+
+    let complete = finalized_inputs.len() == psbt.inputs.len();
+    let tx_hex = complete
+        .then(|| checked_serialize(&finalized_tx(psbt, None)))
+        .transpose()
+        .map_err(|e| JsValue::from_str(&e))?
+        .map(|bytes| bytes_to_hex(&bytes));
+    let result = LocktimeSigning { psbt: versioned.to_base64(), signed_inputs, finalized_inputs, complete, tx_hex };
+    to_json(&result, compact)
+}
+
+/// When a pre-signed package becomes broadcastable and what it still lacks. A height
+/// locktime is measured against `tip_height`, with a date estimate at 10-minute blocks;
+/// a timestamp locktime needs `mtp`, the tip's median time past, to say more than the
+/// time itself. Missing UTXO data, final sequences and unsigned inputs are listed under
+/// `problems`.
+#[wasm_bindgen]
+pub fn package_report(psbt_base64: &str, tip_height: u32, mtp: Option<u32>, compact: Option<bool>) -> Result<String, JsValue> {
+    let psbt = parse_psbt(psbt_base64).map_err(|e| JsValue::from_str(&e))?;
+    let tx = &psbt.unsigned_tx;
+    let locktime = tx.lock_time.to_consensus_u32();
+    let is_height = locktime < LOCK_TIME_THRESHOLD;
+    let mut problems = locktime_problems(tx);
+
+    let lock = match (locktime, is_height, mtp) {
+        (0, _, _) | (_, false, None) => None,
+        (_, _, mtp) => Some(absolute_lock(tx, locktime, tip_height, mtp.unwrap_or(0))),
+    };
+    let broadcastable_height = (locktime != 0 && is_height).then(|| locktime + 1);
+    let broadcastable_now = match &lock {
+        Some(lock) => Some(lock.satisfied),
+        None => (locktime == 0).then_some(true),
+    };
+
+    let mut inputs = Vec::with_capacity(tx.input.len());
+    let mut missing_utxos = Vec::new();
+    for (index, (input, txin)) in psbt.inputs.iter().zip(&tx.input).enumerate() {
+        let utxo = match (input.witness_utxo.is_some(), input.non_witness_utxo.is_some()) {
+            (true, true) => "both",
+            (true, false) => "witness_utxo",
+            (false, true) => "non_witness_utxo",
+            (false, false) => "missing",
+        };
+        match input_utxo(input, txin) {
+            None => {
+                missing_utxos.push(index);
+                problems.push(format!("input {} has no UTXO data", index));
+            }
+            Some(prevout) if input.non_witness_utxo.is_none() && !prevout.script_pubkey.is_witness_program() && !prevout.script_pubkey.is_p2sh() => {
+                problems.push(format!("input {} is legacy and needs non_witness_utxo", index));
+            }
+            Some(_) => {}
+        }
+        let finalized = is_finalized(input);
+        if !finalized {
+            problems.push(format!("input {} is not finalized", index));
+        }
+        inputs.push(PackageInput {
+            index,
+            outpoint: txin.previous_output.to_string(),
+            sequence: txin.sequence.to_consensus_u32(),
+            enables_locktime: txin.sequence.enables_absolute_lock_time(),
+            relative_lock: (tx.version.0 >= 2).then(|| txin.sequence.to_relative_lock_time()).flatten().map(describe_relative_lock),
+            utxo: utxo.to_string(),
+            signed: finalized || !input.partial_sigs.is_empty() || input.tap_key_sig.is_some(),
+            finalized,
+        });
+    }
+
+    let prevouts = psbt_utxos(&psbt);
+    let fee = prevouts
+        .iter()
+        .map(|utxo| utxo.as_ref().map(|utxo| utxo.value.to_sat()))
+        .sum::<Option<u64>>()
+        .and_then(|total| total.checked_sub(tx.output.iter().map(|output| output.value.to_sat()).sum()));
+    let complete = inputs.iter().all(|input| input.finalized);
+    let tx_hex = complete
+        .then(|| checked_serialize(&finalized_tx(&psbt, None)))
+        .transpose()
+        .map_err(|e| JsValue::from_str(&e))?
+        .map(|bytes| bytes_to_hex(&bytes));
+
+    let report = PackageReport {
+        txid: tx.compute_txid().to_string(),
+        locktime,
+        unit: if locktime == 0 { "none" } else if is_height { "height" } else { "time" }.to_string(),
+        summary: summary(locktime, tip_height, lock.as_ref()),
+        lock,
+        broadcastable_height,
+        broadcastable_now,
+        inputs,
+        missing_utxos,
+        fee,
+        complete,
+        tx_hex,
+        problems,
+    };
+    to_json(&report, compact)
+}
+
+/// What would let the transaction confirm before its locktime. Consensus only needs one
+/// non-final input, but a single input left at 0xffffffff is usually a mistake in a
+/// package meant to wait, so every input is held to it.
+fn locktime_problems(tx: &Transaction) -> Vec<String> {
+    let mut problems = Vec::new();
+    if tx.lock_time.to_consensus_u32() == 0 {
+        problems.push("the transaction has no locktime".to_string());
+    }
+    for (index, txin) in tx.input.iter().enumerate() {
+        if txin.sequence == Sequence::MAX {
+            problems.push(format!("input {} has sequence 0xffffffff, which opts it out of the locktime", index));
+        }
+    }
+    problems
+}
+
+fn is_finalized(input: &bitcoin::psbt::Input) -> bool {
+    input.final_script_sig.is_some() || input.final_script_witness.is_some()
+}
+
+/// Sets the final fields and drops the ones BIP174 says a finalizer clears.
+#[cfg(feature = "signing")]
+fn finalize_input(input: &mut bitcoin::psbt::Input, script_sig: ScriptBuf, witness: Witness) {
+    input.final_script_sig = (!script_sig.is_empty()).then_some(script_sig);
+    input.final_script_witness = (!witness.is_empty()).then_some(witness);
+    input.partial_sigs.clear();
+    input.sighash_type = None;
+    input.redeem_script = None;
+    input.witness_script = None;
+    input.bip32_derivation.clear();
+    input.tap_key_sig = None;
+    input.tap_script_sigs.clear();
+    input.tap_scripts.clear();
+    input.tap_key_origins.clear();
+    input.tap_internal_key = None;
+    input.tap_merkle_root = None;
+}
+
+fn describe_relative_lock(lock: relative::LockTime) -> String {
+    match lock {
+        relative::LockTime::Blocks(height) => format!("{} blocks", height.value()),
+        relative::LockTime::Time(time) => format!("{} seconds", time.value() as u32 * 512),
+    }
+}
+
+fn summary(locktime: u32, tip_height: u32, lock: Option<&LockStatus>) -> String {
+    if locktime == 0 {
+        return "no locktime; broadcastable now".to_string();
+    }
+    if locktime < LOCK_TIME_THRESHOLD {
+        let Some(blocks) = lock.and_then(|lock| lock.remaining_blocks) else {
+            return format!("broadcastable now: the tip {} is past locktime height {}", tip_height, locktime);
+        };
+        return format!(
+            "broadcastable from block {}, {} blocks after the tip {} (about {} days at 10-minute blocks)",
+            locktime as u64 + 1,
+            blocks,
+            tip_height,
+            blocks * BLOCK_INTERVAL_SECS / 86_400
+        );
+    }
+    match lock.and_then(|lock| lock.remaining_seconds) {
+        None if lock.is_some() => format!("broadcastable now: the median time past is past {}", locktime),
+        None => format!("broadcastable once the median time past passes unix time {}; pass mtp for an estimate", locktime),
+        Some(seconds) => format!(
+            "broadcastable once the median time past passes unix time {}, about {} days (roughly {} blocks) away",
+            locktime,
+            seconds / 86_400,
+            seconds.div_ceil(BLOCK_INTERVAL_SECS)
+        ),
+    }
+}
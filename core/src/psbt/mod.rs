@@ -0,0 +1,164 @@
+use wasm_bindgen::prelude::*;
+use bitcoin::Psbt;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::base64::decode_base64;
+use v2::{deserialize_versioned_psbt, parse_versioned_psbt};
+use crate::utils::metrics::timed;
+
+pub mod decode;
+pub mod locktime;
+pub mod payjoin;
+pub mod proprietary;
+pub mod session;
+#[cfg(feature = "wallet")]
+pub mod summary;
+pub mod update;
+pub mod v2;
+pub mod xpub;
+
+pub use decode::decode_psbt;
+pub use locktime::{build_locktime_psbt, package_report};
+#[cfg(feature = "signing")]
+pub use locktime::sign_locktime_psbt;
+pub use payjoin::{parse_payjoin_uri, payjoin_request_url, payjoin_original_psbt, validate_payjoin_proposal};
+pub use proprietary::{get_proprietary_field, set_proprietary_field};
+pub use session::SigningSession;
+#[cfg(feature = "wallet")]
+pub use summary::psbt_signing_summary;
+pub use update::update_psbt;
+pub use v2::{create_psbt_v2, psbt_v2_add_input, psbt_v2_add_output, psbt_v2_to_v0, psbt_v0_to_v2};
+pub use xpub::add_global_xpubs;
+
+/// BIP174 magic bytes: "psbt" followed by the 0xff separator.
+pub const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+
+#[wasm_bindgen]
+pub fn psbt_to_base64(psbt_hex: &str) -> Result<String, JsValue> {
+    timed("psbt_to_base64", psbt_hex.len(), || {
+        let psbt = parse_versioned_psbt(psbt_hex).map_err(|e| JsValue::from_str(&e))?;
+        Ok(psbt.to_base64())
+    })
+}
+
+#[wasm_bindgen]
+pub fn psbt_to_hex(psbt_base64: &str) -> Result<String, JsValue> {
+    timed("psbt_to_hex", psbt_base64.len(), || {
+        let psbt = parse_versioned_psbt(psbt_base64).map_err(|e| JsValue::from_str(&e))?;
+        Ok(bytes_to_hex(&psbt.serialize()))
+    })
+}
+
+/// Converts the contents of a binary `.psbt` file to base64.
+#[wasm_bindgen]
+pub fn psbt_from_binary(bytes: &[u8]) -> Result<String, JsValue> {
+    timed("psbt_from_binary", bytes.len(), || {
+        let psbt = deserialize_versioned_psbt(bytes).map_err(|e| JsValue::from_str(&e))?;
+        Ok(psbt.to_base64())
+    })
+}
+
+/// Returns "hex", "base64" or "binary", whichever decoding yields the PSBT magic.
+#[wasm_bindgen]
+pub fn detect_psbt_format(psbt: &str) -> Result<String, JsValue> {
+    let (format, _) = decode_psbt_bytes(psbt).map_err(|e| JsValue::from_str(&e))?;
+    Ok(format.to_string())
+}
+
+/// Accepts a PSBT as hex, base64 or a binary string, so every PSBT-taking function
+/// can be handed whatever the other tool produced. Version 2 PSBTs come back in
+/// their v0 form; functions that write the PSBT back use `parse_versioned_psbt`.
+pub fn parse_psbt(input: &str) -> Result<Psbt, String> {
+    parse_versioned_psbt(input).map(|versioned| versioned.psbt)
+}
+
+pub fn decode_psbt_bytes(input: &str) -> Result<(&'static str, Vec<u8>), String> {
+    let trimmed = input.trim();
+
+    if let Ok(bytes) = hex_to_bytes(trimmed, HexMode::Strict) {
+        if bytes.starts_with(&PSBT_MAGIC) {
+            return Ok(("hex", bytes));
+        }
+    }
+    if let Ok(bytes) = decode_base64(trimmed) {
+        if bytes.starts_with(&PSBT_MAGIC) {
+            return Ok(("base64", bytes));
+        }
+    }
+    // A binary file read into a JS string arrives with one char per byte.
+    if input.chars().all(|c| (c as u32) <= 0xff) {
+        let bytes: Vec<u8> = input.chars().map(|c| c as u8).collect();
+        if bytes.starts_with(&PSBT_MAGIC) {
+            return Ok(("binary", bytes));
+        }
+    }
+
+    Err("not a PSBT (missing magic)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid};
+    use bitcoin::hashes::Hash;
+    use crate::utils::base64::encode_base64;
+
+    /// A one-input, one-output unsigned v0 PSBT.
+    fn psbt_bytes() -> Vec<u8> {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint { txid: Txid::all_zeros(), vout: 1 }, ..Default::default() }],
+            output: vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+            }],
+        };
+        Psbt::from_unsigned_tx(tx).unwrap().serialize()
+    }
+
+    #[test]
+    fn detects_each_encoding() {
+        let bytes = psbt_bytes();
+        let hex = bytes_to_hex(&bytes);
+        let base64 = encode_base64(&bytes, false);
+        let binary: String = bytes.iter().map(|&byte| byte as char).collect();
+
+        for (input, format) in [(&hex, "hex"), (&hex.to_uppercase(), "hex"), (&base64, "base64"), (&binary, "binary")] {
+            let (detected, decoded) = decode_psbt_bytes(input).unwrap();
+            assert_eq!((detected, &decoded), (format, &bytes));
+            assert_eq!(parse_psbt(input).unwrap().serialize(), bytes);
+        }
+        assert_eq!(decode_psbt_bytes(&format!("  {}\n", base64)).unwrap().0, "base64");
+    }
+
+    #[test]
+    fn converts_between_hex_and_base64() {
+        let bytes = psbt_bytes();
+        let hex = bytes_to_hex(&bytes);
+        let base64 = psbt_to_base64(&hex).unwrap();
+        assert_eq!(base64, encode_base64(&bytes, false));
+        assert_eq!(psbt_to_hex(&base64).unwrap(), hex);
+        assert_eq!(detect_psbt_format(&base64).unwrap(), "base64");
+    }
+
+    #[test]
+    fn rejects_blobs_without_the_magic() {
+        let raw_tx = "02000000010000000000000000000000000000000000000000000000000000000000000000ffffffff00ffffffff0000000000";
+        for input in ["", "   ", "psbt", "70736274", "70736274fe", "cHNidA==", raw_tx, "not base64 or hex!"] {
+            assert_eq!(decode_psbt_bytes(input).unwrap_err(), "not a PSBT (missing magic)", "{:?}", input);
+        }
+        let raw_tx_base64 = encode_base64(&hex_to_bytes(raw_tx, HexMode::Strict).unwrap(), false);
+        assert_eq!(parse_psbt(&raw_tx_base64).unwrap_err(), "not a PSBT (missing magic)");
+    }
+
+    #[test]
+    fn magic_with_a_broken_body_is_a_parse_error() {
+        let mut truncated = psbt_bytes();
+        truncated.truncate(truncated.len() - 3);
+        let error = parse_psbt(&bytes_to_hex(&truncated)).unwrap_err();
+        assert_ne!(error, "not a PSBT (missing magic)");
+        assert!(parse_psbt("70736274ff").is_err());
+    }
+}
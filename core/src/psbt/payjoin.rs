@@ -0,0 +1,551 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{Amount, Denomination, OutPoint, Psbt, ScriptBuf, Transaction, TxIn, TxOut};
+use crate::psbt::parse_psbt;
+use crate::script::output_script_type;
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::base64::encode_base64;
+use crate::utils::json::{parse_json_value, to_json};
+use crate::utils::network::require_address_network;
+use crate::utils::units::FeeRate;
+use crate::utils::varint::encode_compact_size;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayjoinUri {
+    pub address: String,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount")]
+    pub amount: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    pub pj: Option<String>,
+    #[serde(alias = "output_substitution")]
+    pub output_substitution: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PayjoinParams {
+    #[serde(alias = "payment_address")]
+    pub payment_address: Option<String>,
+    #[serde(alias = "additional_fee_output_index")]
+    pub additional_fee_output_index: Option<usize>,
+    #[serde(alias = "max_additional_fee_contribution")]
+    pub max_additional_fee_contribution: Option<u64>,
+    #[serde(default, alias = "disable_output_substitution")]
+    pub disable_output_substitution: bool,
+    #[serde(rename = "minFeeRateSatVb", alias = "min_fee_rate_sat_vb", alias = "min_fee_rate")]
+    pub min_fee_rate: Option<FeeRate>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayjoinCheck {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    #[serde(alias = "original_fee")]
+    pub original_fee: Option<u64>,
+    #[serde(alias = "proposal_fee")]
+    pub proposal_fee: Option<u64>,
+    #[serde(alias = "fee_contribution")]
+    pub fee_contribution: u64,
+    #[serde(alias = "receiver_inputs")]
+    pub receiver_inputs: usize,
+    #[serde(rename = "estimatedFeeRateSatVb", alias = "estimated_fee_rate_sat_vb", alias = "estimated_fee_rate")]
+    pub estimated_fee_rate: Option<FeeRate>,
+}
+
+/// Reads a BIP21 URI, including the BIP78 `pj` endpoint and `pjos` flag.
+#[wasm_bindgen]
+pub fn parse_payjoin_uri(uri: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let parsed = parse_bip21(uri).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&parsed, compact)
+}
+
+/// The URL to POST the original PSBT to: the `pj` endpoint plus the BIP78 query parameters.
+#[wasm_bindgen]
+pub fn payjoin_request_url(pj_endpoint: &str, params_json: Option<String>) -> Result<String, JsValue> {
+    let params: PayjoinParams = match params_json {
+        Some(json) => parse_json_value(&json, "params").map_err(|e| JsValue::from_str(&e))?,
+        None => PayjoinParams::default(),
+    };
+    request_url(pj_endpoint, &params).map_err(|e| JsValue::from_str(&e))
+}
+
+/// BIP78 wants the original as a finalized PSBT with UTXO data, i.e. the signed
+/// transaction the sender would otherwise broadcast.
+#[wasm_bindgen]
+pub fn payjoin_original_psbt(signed_tx_hex: &str, prevouts_json: &str) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(signed_tx_hex)?;
+    let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+    let psbt = original_psbt(&tx, prevouts).map_err(|e| JsValue::from_str(&e))?;
+    Ok(encode_base64(&psbt.serialize(), false))
+}
+
+/// Runs the BIP78 sender checklist on the receiver's proposal. Only sign the proposal
+/// when `valid` is true; otherwise broadcast the original transaction instead.
+#[wasm_bindgen]
+pub fn validate_payjoin_proposal(original_psbt: &str, proposal_psbt: &str, params_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let original = parse_psbt(original_psbt).map_err(|e| JsValue::from_str(&format!("Original PSBT: {}", e)))?;
+    let proposal = parse_psbt(proposal_psbt).map_err(|e| JsValue::from_str(&format!("Proposal PSBT: {}", e)))?;
+    let params: PayjoinParams = parse_json_value(params_json, "params").map_err(|e| JsValue::from_str(&e))?;
+
+    let check = check_proposal(&original, &proposal, &params).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&check, compact)
+}
+
+pub fn parse_bip21(uri: &str) -> Result<PayjoinUri, String> {
+    let uri = uri.trim();
+    let rest = match uri.get(..8) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("bitcoin:") => &uri[8..],
+        _ => return Err("Not a BIP21 URI (expected bitcoin:...)".to_string()),
+    };
+    let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+    require_address_network(address, None)?;
+
+    let mut parsed = PayjoinUri {
+        address: address.to_string(),
+        amount: None,
+        label: None,
+        message: None,
+        pj: None,
+        output_substitution: true,
+    };
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+        match key.to_ascii_lowercase().as_str() {
+            "amount" => {
+                let amount = Amount::from_str_in(&value, Denomination::Bitcoin)
+                    .map_err(|e| format!("Invalid amount \"{}\": {}", value, e))?;
+                parsed.amount = Some(amount.to_sat());
+            }
+            "label" => parsed.label = Some(value),
+            "message" => parsed.message = Some(value),
+            "pj" => parsed.pj = Some(value),
+            "pjos" => parsed.output_substitution = value != "0",
+            other if other.starts_with("req-") => {
+                return Err(format!("URI requires unsupported parameter \"{}\"", key));
+            }
+            _ => {}
+        }
+    }
+    Ok(parsed)
+}
+
+pub fn request_url(pj_endpoint: &str, params: &PayjoinParams) -> Result<String, String> {
+    let endpoint = pj_endpoint.trim();
+    let lower = endpoint.to_ascii_lowercase();
+    let host = lower.split("://").nth(1).unwrap_or("").split(['/', '?', ':']).next().unwrap_or("");
+    let onion = lower.starts_with("http://") && host.ends_with(".onion");
+    if !lower.starts_with("https://") && !onion {
+        return Err("Payjoin endpoint must use https (or http to a .onion host)".to_string());
+    }
+
+    let mut query = vec!["v=1".to_string()];
+    match (params.additional_fee_output_index, params.max_additional_fee_contribution) {
+        (Some(index), Some(max)) => {
+            query.push(format!("additionalfeeoutputindex={}", index));
+            query.push(format!("maxadditionalfeecontribution={}", max));
+        }
+        (None, None) => {}
+        _ => {
+            return Err("additional_fee_output_index and max_additional_fee_contribution must be given together".to_string())
+        }
+    }
+    if params.disable_output_substitution {
+        query.push("disableoutputsubstitution=true".to_string());
+    }
+    if let Some(rate) = params.min_fee_rate {
+        query.push(format!("minfeerate={}", rate.sat_per_vb()));
+    }
+
+    let separator = if endpoint.contains('?') { '&' } else { '?' };
+    Ok(format!("{}{}{}", endpoint, separator, query.join("&")))
+}
+
+pub fn original_psbt(tx: &Transaction, prevouts: Vec<TxOut>) -> Result<Psbt, String> {
+    let mut unsigned = tx.clone();
+    for input in &mut unsigned.input {
+        input.script_sig = ScriptBuf::new();
+        input.witness.clear();
+    }
+    let mut psbt = Psbt::from_unsigned_tx(unsigned).map_err(|e| format!("Cannot build PSBT: {}", e))?;
+
+    for (index, (txin, prevout)) in tx.input.iter().zip(prevouts).enumerate() {
+        if txin.script_sig.is_empty() && txin.witness.is_empty() {
+            return Err(format!("Input {} is not signed; payjoin needs the fully signed original", index));
+        }
+        if !prevout.script_pubkey.is_witness_program() && !prevout.script_pubkey.is_p2sh() {
+            return Err(format!("Input {} is not segwit; payjoin originals need witness UTXOs", index));
+        }
+        let input = &mut psbt.inputs[index];
+        input.witness_utxo = Some(prevout);
+        if !txin.script_sig.is_empty() {
+            input.final_script_sig = Some(txin.script_sig.clone());
+        }
+        if !txin.witness.is_empty() {
+            input.final_script_witness = Some(txin.witness.clone());
+        }
+    }
+    Ok(psbt)
+}
+
+pub fn check_proposal(original: &Psbt, proposal: &Psbt, params: &PayjoinParams) -> Result<PayjoinCheck, String> {
+    if original.unsigned_tx.input.is_empty() {
+        return Err("Original PSBT has no inputs".to_string());
+    }
+    let original_utxos = psbt_utxos(original)
+        .into_iter()
+        .enumerate()
+        .map(|(index, utxo)| utxo.ok_or_else(|| format!("Original input {} has no UTXO data", index)))
+        .collect::<Result<Vec<TxOut>, String>>()?;
+    let original_tx = &original.unsigned_tx;
+    let proposal_tx = &proposal.unsigned_tx;
+    let payment_script = match &params.payment_address {
+        Some(address) => Some(require_address_network(address, None)?),
+        None => None,
+    };
+
+    let mut errors = Vec::new();
+    if proposal_tx.version != original_tx.version {
+        errors.push("transaction version changed".to_string());
+    }
+    if proposal_tx.lock_time != original_tx.lock_time {
+        errors.push("locktime changed".to_string());
+    }
+
+    // Inputs: ours must come back stripped and unchanged, theirs must be finalized.
+    let our_inputs: Vec<OutPoint> = original_tx.input.iter().map(|txin| txin.previous_output).collect();
+    let mut found = HashSet::new();
+    let mut proposal_input_total = Some(0u64);
+    let mut input_types = HashSet::new();
+    let mut receiver_inputs = 0;
+    for (index, (txin, input)) in proposal_tx.input.iter().zip(&proposal.inputs).enumerate() {
+        if !input.bip32_derivation.is_empty() || !input.tap_key_origins.is_empty() {
+            errors.push(format!("proposal input {} has key paths", index));
+        }
+        if !input.partial_sigs.is_empty() || input.tap_key_sig.is_some() || !input.tap_script_sigs.is_empty() {
+            errors.push(format!("proposal input {} has partial signatures", index));
+        }
+        let finalized = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+
+        let utxo = match our_inputs.iter().position(|outpoint| *outpoint == txin.previous_output) {
+            Some(ours) => {
+                found.insert(ours);
+                if txin.sequence != original_tx.input[ours].sequence {
+                    errors.push(format!("sequence of our input {} changed", ours));
+                }
+                if finalized {
+                    errors.push(format!("our input {} is finalized in the proposal", ours));
+                }
+                if input.witness_utxo.is_some() || input.non_witness_utxo.is_some() {
+                    errors.push(format!("our input {} carries UTXO data in the proposal", ours));
+                }
+                Some(original_utxos[ours].clone())
+            }
+            None => {
+                receiver_inputs += 1;
+                if !finalized {
+                    errors.push(format!("receiver input {} is not finalized", index));
+                }
+                let utxo = input_utxo(input, txin);
+                if utxo.is_none() {
+                    errors.push(format!("receiver input {} has no UTXO data", index));
+                }
+                utxo
+            }
+        };
+        if let Some(utxo) = &utxo {
+            input_types.insert(output_script_type(&utxo.script_pubkey));
+        }
+        proposal_input_total = proposal_input_total.zip(utxo).map(|(total, utxo)| total + utxo.value.to_sat());
+    }
+    for (index, outpoint) in our_inputs.iter().enumerate() {
+        if !found.contains(&index) {
+            errors.push(format!("our input {} ({}) is missing from the proposal", index, outpoint));
+        }
+    }
+    if proposal_tx.input.windows(2).any(|pair| pair[0].sequence != pair[1].sequence) {
+        errors.push("proposal inputs use mixed sequence numbers".to_string());
+    }
+    let original_types: HashSet<&str> = original_utxos.iter().map(|utxo| output_script_type(&utxo.script_pubkey)).collect();
+    if original_types.len() == 1 && input_types.len() > 1 {
+        errors.push("proposal introduces mixed input types".to_string());
+    }
+
+    // Outputs: every one of ours must survive; only the fee output may shrink.
+    let mut used = vec![false; proposal_tx.output.len()];
+    let mut fee_contribution = 0u64;
+    for (index, output) in proposal.outputs.iter().enumerate() {
+        if !output.bip32_derivation.is_empty() || !output.tap_key_origins.is_empty() {
+            errors.push(format!("proposal output {} has key paths", index));
+        }
+    }
+    for (index, original_output) in original_tx.output.iter().enumerate() {
+        let is_payment = payment_script.as_ref() == Some(&original_output.script_pubkey);
+        if is_payment && !params.disable_output_substitution {
+            continue;
+        }
+        let matched = proposal_tx
+            .output
+            .iter()
+            .enumerate()
+            .position(|(i, output)| !used[i] && output.script_pubkey == original_output.script_pubkey);
+        let Some(matched) = matched else {
+            errors.push(format!("original output {} is missing from the proposal", index));
+            continue;
+        };
+        used[matched] = true;
+
+        let (before, after) = (original_output.value.to_sat(), proposal_tx.output[matched].value.to_sat());
+        if after >= before {
+            continue;
+        }
+        if params.additional_fee_output_index == Some(index) {
+            fee_contribution = before - after;
+        } else {
+            errors.push(format!("original output {} decreased from {} to {} sats", index, before, after));
+        }
+    }
+
+    let original_input_total: u64 = original_utxos.iter().map(|utxo| utxo.value.to_sat()).sum();
+    let original_fee = original_input_total.checked_sub(output_total(original_tx));
+    let proposal_fee = proposal_input_total.and_then(|total| total.checked_sub(output_total(proposal_tx)));
+
+    if fee_contribution > params.max_additional_fee_contribution.unwrap_or(0) {
+        errors.push(format!(
+            "fee output lost {} sats, above the allowed contribution of {}",
+            fee_contribution,
+            params.max_additional_fee_contribution.unwrap_or(0)
+        ));
+    }
+
+    let mut estimated_fee_rate = None;
+    match (original_fee, proposal_fee) {
+        (Some(original_fee), Some(proposal_fee)) => {
+            if proposal_fee < original_fee {
+                errors.push(format!("proposal fee {} is lower than the original fee {}", proposal_fee, original_fee));
+            }
+            if fee_contribution > proposal_fee.saturating_sub(original_fee) {
+                errors.push(format!(
+                    "our contribution of {} sats exceeds the fee increase of {}",
+                    fee_contribution,
+                    proposal_fee.saturating_sub(original_fee)
+                ));
+            }
+
+            let original_vsize = finalized_tx(original, None).vsize() as f64;
+            let original_rate = original_fee as f64 / original_vsize;
+            let our_input_vsize = finalized_input_vbytes(original, 0);
+            let added_inputs = proposal_tx.input.len().saturating_sub(original_tx.input.len()) as f64;
+            let allowed = (original_rate * our_input_vsize * added_inputs).ceil() as u64;
+            if fee_contribution > allowed {
+                errors.push(format!(
+                    "our contribution of {} sats pays for more than the {} added input(s) ({} sats at the original feerate)",
+                    fee_contribution, added_inputs, allowed
+                ));
+            }
+
+            // Our signatures are not in the proposal yet; size them from the original.
+            let proposal_vsize = finalized_tx(proposal, Some(original)).vsize() as f64;
+            let rate = proposal_fee as f64 / proposal_vsize;
+            estimated_fee_rate = FeeRate::from_sat_per_vb((rate * 100.0).round() / 100.0).ok();
+            if let Some(min) = params.min_fee_rate {
+                if rate < min.sat_per_vb() {
+                    errors.push(format!("proposal feerate of about {:.2} sat/vB is below the minimum {}", rate, min));
+                }
+            }
+        }
+        (None, _) => errors.push("original PSBT spends less than it pays".to_string()),
+        (_, None) => errors.push("proposal fee cannot be computed".to_string()),
+    }
+
+    Ok(PayjoinCheck {
+        valid: errors.is_empty(),
+        errors,
+        original_fee,
+        proposal_fee,
+        fee_contribution,
+        receiver_inputs,
+        estimated_fee_rate,
+    })
+}
+
+pub fn input_utxo(input: &bitcoin::psbt::Input, txin: &TxIn) -> Option<TxOut> {
+    input.witness_utxo.clone().or_else(|| {
+        input
+            .non_witness_utxo
+            .as_ref()
+            .and_then(|prev| prev.output.get(txin.previous_output.vout as usize).cloned())
+    })
+}
+
+pub fn psbt_utxos(psbt: &Psbt) -> Vec<Option<TxOut>> {
+    psbt.inputs.iter().zip(&psbt.unsigned_tx.input).map(|(input, txin)| input_utxo(input, txin)).collect()
+}
+
+fn output_total(tx: &Transaction) -> u64 {
+    tx.output.iter().map(|output| output.value.to_sat()).sum()
+}
+
+/// The transaction with every final scriptSig and witness filled in; inputs missing them
+/// borrow the ones `donor` has for the same outpoint.
+pub fn finalized_tx(psbt: &Psbt, donor: Option<&Psbt>) -> Transaction {
+    let mut tx = psbt.unsigned_tx.clone();
+    for (index, txin) in tx.input.iter_mut().enumerate() {
+        let mut source = &psbt.inputs[index];
+        if source.final_script_sig.is_none() && source.final_script_witness.is_none() {
+            if let Some(donor) = donor {
+                if let Some(position) = donor.unsigned_tx.input.iter().position(|d| d.previous_output == txin.previous_output) {
+                    source = &donor.inputs[position];
+                }
+            }
+        }
+        txin.script_sig = source.final_script_sig.clone().unwrap_or_default();
+        txin.witness = source.final_script_witness.clone().unwrap_or_default();
+    }
+    tx
+}
+
+fn finalized_input_vbytes(psbt: &Psbt, index: usize) -> f64 {
+    let input = &psbt.inputs[index];
+    let script_sig_len = input.final_script_sig.as_ref().map_or(0, |script| script.len());
+    let base = 40 + encode_compact_size(script_sig_len as u64).len() + script_sig_len;
+    let witness = input.final_script_witness.as_ref().map_or(0, |witness| witness.size());
+    (base * 4 + witness) as f64 / 4.0
+}
+
+fn percent_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3).ok_or_else(|| format!("Truncated percent escape in \"{}\"", value))?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| format!("Invalid percent escape %{} in \"{}\"", hex, value))?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("URI parameter \"{}\" is not UTF-8", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::transaction::Version;
+    use bitcoin::{Address, CompressedPublicKey, Network, Sequence, Txid, Witness};
+
+    const SEQUENCE: Sequence = Sequence(0xffff_fffd);
+
+    fn address(byte: u8) -> Address {
+        let secret = SecretKey::from_slice(&[byte; 32]).unwrap();
+        Address::p2wpkh(&CompressedPublicKey(secret.public_key(&Secp256k1::new())), Network::Bitcoin)
+    }
+
+    fn utxo(byte: u8, sats: u64) -> TxOut {
+        TxOut { value: Amount::from_sat(sats), script_pubkey: address(byte).script_pubkey() }
+    }
+
+    fn txin(byte: u8) -> TxIn {
+        TxIn { previous_output: OutPoint { txid: Txid::from_byte_array([byte; 32]), vout: 0 }, sequence: SEQUENCE, ..Default::default() }
+    }
+
+    /// A P2WPKH witness of realistic size; the checks never verify it.
+    fn witness() -> Witness {
+        Witness::from_slice(&[vec![0x30; 72], vec![0x02; 33]])
+    }
+
+    fn tx(input: Vec<TxIn>, output: Vec<TxOut>) -> Transaction {
+        Transaction { version: Version::TWO, lock_time: LockTime::ZERO, input, output }
+    }
+
+    /// The sender pays 50,000 sats to the receiver (key 2) from one 100,000 sat coin
+    /// (key 1), with 49,000 back as change and a 1,000 sat fee.
+    fn original() -> Psbt {
+        let mut psbt = Psbt::from_unsigned_tx(tx(vec![txin(0xaa)], vec![utxo(2, 50_000), utxo(1, 49_000)])).unwrap();
+        psbt.inputs[0].witness_utxo = Some(utxo(1, 100_000));
+        psbt.inputs[0].final_script_witness = Some(witness());
+        psbt
+    }
+
+    /// The receiver's answer: its own finalized 200,000 sat coin (key 3) added, paid
+    /// back into its output less 400 sats of extra fee, and the sender's input stripped.
+    fn proposal(edit: impl FnOnce(&mut Transaction)) -> Psbt {
+        let mut unsigned = tx(vec![txin(0xaa), txin(0xbb)], vec![utxo(2, 249_600), utxo(1, 49_000)]);
+        edit(&mut unsigned);
+        let mut psbt = Psbt::from_unsigned_tx(unsigned).unwrap();
+        if let Some(receiver) = psbt.unsigned_tx.input.iter().position(|txin| txin.previous_output.txid == Txid::from_byte_array([0xbb; 32])) {
+            psbt.inputs[receiver].witness_utxo = Some(utxo(3, 200_000));
+            psbt.inputs[receiver].final_script_witness = Some(witness());
+        }
+        psbt
+    }
+
+    fn validate(proposal: &Psbt, params: &str) -> PayjoinCheck {
+        let encode = |psbt: &Psbt| encode_base64(&psbt.serialize(), false);
+        let json = validate_payjoin_proposal(&encode(&original()), &encode(proposal), params, None).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn params() -> String {
+        format!(r#"{{"paymentAddress": "{}"}}"#, address(2))
+    }
+
+    fn assert_rejected(check: &PayjoinCheck, error: &str) {
+        assert!(!check.valid);
+        assert!(check.errors.iter().any(|e| e == error), "{:?}", check.errors);
+    }
+
+    #[test]
+    fn accepts_a_valid_proposal() {
+        let check = validate(&proposal(|_| {}), &params());
+        assert!(check.valid, "{:?}", check.errors);
+        assert_eq!((check.original_fee, check.proposal_fee), (Some(1_000), Some(1_400)));
+        assert_eq!((check.fee_contribution, check.receiver_inputs), (0, 1));
+    }
+
+    #[test]
+    fn rejects_a_removed_sender_output() {
+        let check = validate(&proposal(|tx| drop(tx.output.remove(1))), &params());
+        assert_rejected(&check, "original output 1 is missing from the proposal");
+    }
+
+    #[test]
+    fn rejects_a_changed_sender_output() {
+        let check = validate(&proposal(|tx| tx.output[1].value = Amount::from_sat(48_000)), &params());
+        assert_rejected(&check, "original output 1 decreased from 49000 to 48000 sats");
+    }
+
+    #[test]
+    fn rejects_a_lowered_sender_sequence() {
+        let check = validate(&proposal(|tx| tx.input[0].sequence = Sequence(0xffff_fffc)), &params());
+        assert_rejected(&check, "sequence of our input 0 changed");
+    }
+
+    #[test]
+    fn rejects_added_sender_inputs() {
+        // Another of the sender's coins, left for the sender to sign.
+        let check = validate(&proposal(|tx| tx.input.push(txin(0xcc))), &params());
+        assert_rejected(&check, "receiver input 2 is not finalized");
+        assert_rejected(&check, "receiver input 2 has no UTXO data");
+    }
+
+    #[test]
+    fn rejects_a_fee_contribution_over_the_limit() {
+        let params = format!(
+            r#"{{"paymentAddress": "{}", "additionalFeeOutputIndex": 1, "maxAdditionalFeeContribution": 100}}"#,
+            address(2)
+        );
+        let check = validate(&proposal(|tx| tx.output[1].value = Amount::from_sat(48_700)), &params);
+        assert_eq!(check.fee_contribution, 300);
+        assert_eq!(check.errors, ["fee output lost 300 sats, above the allowed contribution of 100"]);
+    }
+}
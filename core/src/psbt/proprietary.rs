@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+use bitcoin::psbt::raw::ProprietaryKey;
+use bitcoin::Psbt;
+use crate::psbt::v2::parse_versioned_psbt;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+
+/// Reads a proprietary-use (0xfc) field, returning its value as hex or `undefined` if
+/// the PSBT has none. `scope` is "global" (the default), "input:<n>" or "output:<n>".
+#[wasm_bindgen]
+pub fn get_proprietary_field(
+    psbt: &str,
+    prefix: &str,
+    subtype: u8,
+    keydata: &str,
+    scope: Option<String>,
+) -> Result<Option<String>, JsValue> {
+    let mut versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    let key = proprietary_key(prefix, subtype, keydata).map_err(|e| JsValue::from_str(&e))?;
+    let map = proprietary_map(&mut versioned.psbt, scope.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    Ok(map.get(&key).map(|value| bytes_to_hex(value)))
+}
+
+/// Writes a proprietary-use field in `scope` (as for `get_proprietary_field`),
+/// replacing any value it had, and returns the PSBT as base64 in its own version.
+/// A missing `value` removes the field instead. Every other field is left as it was.
+#[wasm_bindgen]
+pub fn set_proprietary_field(
+    psbt: &str,
+    prefix: &str,
+    subtype: u8,
+    keydata: &str,
+    value: Option<String>,
+    scope: Option<String>,
+) -> Result<String, JsValue> {
+    let mut versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    let key = proprietary_key(prefix, subtype, keydata).map_err(|e| JsValue::from_str(&e))?;
+    let value = value
+        .map(|value| hex_to_bytes(&value, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid value hex: {}", e))))
+        .transpose()?;
+    let map = proprietary_map(&mut versioned.psbt, scope.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    match value {
+        Some(value) => map.insert(key, value),
+        None => map.remove(&key),
+    };
+    Ok(versioned.to_base64())
+}
+
+fn proprietary_key(prefix: &str, subtype: u8, keydata: &str) -> Result<ProprietaryKey, String> {
+    let prefix = hex_to_bytes(prefix, HexMode::Lenient).map_err(|e| format!("Invalid prefix hex: {}", e))?;
+    if prefix.is_empty() {
+        return Err("Prefix must not be empty: it identifies whose field this is".to_string());
+    }
+    let key = hex_to_bytes(keydata, HexMode::Lenient).map_err(|e| format!("Invalid key data hex: {}", e))?;
+    Ok(ProprietaryKey { prefix, subtype, key })
+}
+
+fn proprietary_map<'a>(psbt: &'a mut Psbt, scope: Option<&str>) -> Result<&'a mut BTreeMap<ProprietaryKey, Vec<u8>>, String> {
+    let scope = scope.map(str::trim).filter(|scope| !scope.is_empty()).unwrap_or("global");
+    if scope == "global" {
+        return Ok(&mut psbt.proprietary);
+    }
+    let invalid = || format!("Invalid scope '{}' (expected global, input:<n> or output:<n>)", scope);
+    let (kind, index) = scope.split_once(':').ok_or_else(invalid)?;
+    let index: usize = index.parse().map_err(|_| invalid())?;
+    match kind {
+        "input" => {
+            let count = psbt.inputs.len();
+            psbt.inputs
+                .get_mut(index)
+                .map(|input| &mut input.proprietary)
+                .ok_or_else(|| format!("Input index {} out of range (PSBT has {} inputs)", index, count))
+        }
+        "output" => {
+            let count = psbt.outputs.len();
+            psbt.outputs
+                .get_mut(index)
+                .map(|output| &mut output.proprietary)
+                .ok_or_else(|| format!("Output index {} out of range (PSBT has {} outputs)", index, count))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::psbt::raw;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid};
+    use crate::psbt::decode::{decode_psbt, DecodedPsbt};
+    use crate::psbt::SigningSession;
+
+    /// "HWV" as hex, a made-up hardware vendor prefix.
+    const VENDOR: &str = "485756";
+
+    /// As hex, which every PSBT-taking function accepts.
+    fn unsigned_psbt() -> String {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint { txid: Txid::all_zeros(), vout: 0 }, ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(50_000), script_pubkey: ScriptBuf::new_op_return([0x01]) }],
+        };
+        bytes_to_hex(&Psbt::from_unsigned_tx(tx).unwrap().serialize())
+    }
+
+    #[test]
+    fn combining_keeps_both_sides_fields() {
+        let base = unsigned_psbt();
+        let ours = set_proprietary_field(&base, VENDOR, 1, "aa", Some("0102".to_string()), None).unwrap();
+        let mut theirs = parse_versioned_psbt(
+            &set_proprietary_field(&base, VENDOR, 2, "", Some("ff".to_string()), Some("input:0".to_string())).unwrap(),
+        )
+        .unwrap();
+        let unknown = raw::Key { type_value: 0xee, key: vec![0x07] };
+        theirs.psbt.inputs[0].unknown.insert(unknown.clone(), vec![0x08, 0x09]);
+
+        let mut session = SigningSession::new(&ours).unwrap();
+        session.add_psbt(&theirs.to_base64()).unwrap();
+        let combined = session.psbt();
+
+        assert_eq!(get_proprietary_field(&combined, VENDOR, 1, "aa", None).unwrap().as_deref(), Some("0102"));
+        assert_eq!(get_proprietary_field(&combined, VENDOR, 2, "", Some("input:0".to_string())).unwrap().as_deref(), Some("ff"));
+        assert_eq!(parse_versioned_psbt(&combined).unwrap().psbt.inputs[0].unknown.get(&unknown), Some(&vec![0x08, 0x09]));
+    }
+
+    #[test]
+    fn decode_lists_proprietary_fields() {
+        let output = Some("output:0".to_string());
+        let psbt = set_proprietary_field(&unsigned_psbt(), VENDOR, 3, "beef", Some("c0ffee".to_string()), output).unwrap();
+        let decoded: DecodedPsbt = serde_json::from_str(&decode_psbt(&psbt, None, None).unwrap()).unwrap();
+        let field = &decoded.outputs[0].proprietary[0];
+        assert_eq!((field.prefix.as_str(), field.subtype, field.key_data.as_str(), field.value.as_str()), (VENDOR, 3, "beef", "c0ffee"));
+        assert!(decoded.proprietary.is_empty() && decoded.inputs[0].proprietary.is_empty());
+    }
+
+    #[test]
+    fn setting_a_field_leaves_the_rest_alone() {
+        let base = unsigned_psbt();
+        let first = set_proprietary_field(&base, VENDOR, 1, "", Some("01".to_string()), None).unwrap();
+        let second = set_proprietary_field(&first, VENDOR, 2, "", Some("02".to_string()), None).unwrap();
+        assert_eq!(get_proprietary_field(&second, VENDOR, 1, "", None).unwrap().as_deref(), Some("01"));
+        let removed = set_proprietary_field(&second, VENDOR, 2, "", None, None).unwrap();
+        assert_eq!(removed, first);
+        assert_eq!(get_proprietary_field(&removed, VENDOR, 2, "", None).unwrap(), None);
+    }
+}
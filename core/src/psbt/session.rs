@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::KeySource;
+use bitcoin::psbt::Input;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Psbt;
+use crate::psbt::v2::{parse_versioned_psbt, VersionedPsbt};
+use crate::script::parse_multisig;
+use crate::utils::bytes_to_hex;
+use crate::utils::json::to_json;
+
+const SESSION_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputSigningState {
+    pub index: usize,
+    pub required: usize,
+    #[serde(alias = "signed_by")]
+    pub signed_by: Vec<String>,
+    pub missing: Vec<String>,
+    pub complete: bool,
+    pub finalized: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SigningSessionState {
+    pub version: u32,
+    pub txid: String,
+    pub psbt: String,
+    pub complete: bool,
+    pub inputs: Vec<InputSigningState>,
+}
+
+/// Multisig coordination state backed by a PSBT. Each cosigner's PSBT is merged in
+/// with `add_psbt`. Progress is always recomputed from the PSBT, so the JSON form is
+/// only a convenience for moving a session between tools.
+#[wasm_bindgen]
+pub struct SigningSession {
+    psbt: VersionedPsbt,
+}
+
+#[wasm_bindgen]
+impl SigningSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(psbt: &str) -> Result<SigningSession, JsValue> {
+        let psbt = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+        Ok(SigningSession { psbt })
+    }
+
+    pub fn to_json(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let inputs = self.inputs();
+        let state = SigningSessionState {
+            version: SESSION_VERSION,
+            txid: self.psbt.psbt.unsigned_tx.compute_txid().to_string(),
+            psbt: self.psbt(),
+            complete: inputs.iter().all(|input| input.complete),
+            inputs,
+        };
+
+        to_json(&state, compact)
+    }
+
+    pub fn from_json(state_json: &str) -> Result<SigningSession, JsValue> {
+        let state: SigningSessionState = serde_json::from_str(state_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid signing session JSON: {}", e)))?;
+        if state.version != SESSION_VERSION {
+            return Err(JsValue::from_str(&format!("Unsupported signing session version {}", state.version)));
+        }
+        SigningSession::new(&state.psbt)
+    }
+
+    /// The session's PSBT as base64, ready to hand to the next cosigner.
+    pub fn psbt(&self) -> String {
+        self.psbt.to_base64()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.inputs().iter().all(|input| input.complete)
+    }
+
+    /// Per-input progress as JSON.
+    pub fn summary(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        to_json(&self.inputs(), compact)
+    }
+
+    /// One line per input, e.g. "input 0: 1 of 2 signatures, missing f00dbabe",
+    /// followed by an overall line.
+    pub fn status(&self) -> String {
+        let inputs = self.inputs();
+        let mut lines: Vec<String> = inputs
+            .iter()
+            .map(|input| {
+                let progress = if input.finalized {
+                    "finalized".to_string()
+                } else {
+                    let signed = format!("{} of {} signatures", input.signed_by.len(), input.required);
+                    match (input.complete, input.missing.is_empty()) {
+                        (true, _) => format!("{}, complete", signed),
+                        (false, true) => format!("{}, signers unknown", signed),
+                        (false, false) => format!("{}, missing {}", signed, input.missing.join(", ")),
+                    }
+                };
+                format!("input {}: {}", input.index, progress)
+            })
+            .collect();
+
+        let complete = inputs.iter().filter(|input| input.complete).count();
+        lines.push(if complete == inputs.len() {
+            format!("all {} inputs signed, ready to finalize", inputs.len())
+        } else {
+            format!("{} of {} inputs signed", complete, inputs.len())
+        });
+        lines.join("\n")
+    }
+
+    /// Merges a cosigner's PSBT into the session.
+    pub fn add_psbt(&mut self, psbt: &str) -> Result<(), JsValue> {
+        let other = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+        self.combine(other).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Merges another session for the same transaction. It fails without changing anything
+    /// if both sides hold different data for the same slot (e.g. two signatures
+    /// from one key).
+    pub fn merge(&mut self, other: &SigningSession) -> Result<(), JsValue> {
+        self.combine(other.psbt.clone()).map_err(|e| JsValue::from_str(&e))
+    }
+}
+
+impl SigningSession {
+    fn combine(&mut self, other: VersionedPsbt) -> Result<(), String> {
+        let ours = self.psbt.psbt.unsigned_tx.compute_txid();
+        let theirs = other.psbt.unsigned_tx.compute_txid();
+        if ours != theirs {
+            return Err(format!("Sessions are for different transactions ({} vs {})", ours, theirs));
+        }
+
+        let conflicts = find_conflicts(&self.psbt.psbt, &other.psbt);
+        if !conflicts.is_empty() {
+            return Err(format!("Conflicting session data: {}", conflicts.join("; ")));
+        }
+        self.psbt.combine_fields(&other);
+        self.psbt.psbt.combine(other.psbt).map_err(|e| format!("Failed to merge PSBTs: {}", e))
+    }
+
+    fn inputs(&self) -> Vec<InputSigningState> {
+        self.psbt.psbt.inputs.iter().enumerate().map(|(index, input)| input_state(index, input)).collect()
+    }
+}
+
+fn input_state(index: usize, input: &Input) -> InputSigningState {
+    let finalized = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+
+    if input.tap_internal_key.is_some() || input.tap_key_sig.is_some() || !input.tap_script_sigs.is_empty() {
+        // Taproot: a key-path signature completes the input; script-path spends are counted as seen.
+        let signed_by: Vec<String> = input
+            .tap_script_sigs
+            .keys()
+            .map(|(key, _)| {
+                input
+                    .tap_key_origins
+                    .get(key)
+                    .map(|(_, source)| source.0.to_string())
+                    .unwrap_or_else(|| bytes_to_hex(&key.serialize()))
+            })
+            .chain(input.tap_key_sig.map(|_| "key path".to_string()))
+            .collect();
+        let complete = finalized || input.tap_key_sig.is_some();
+        return InputSigningState { index, required: 1, signed_by, missing: Vec::new(), complete, finalized };
+    }
+
+    let script = input.witness_script.as_ref().or(input.redeem_script.as_ref());
+    let (required, keys): (usize, Vec<PublicKey>) = match script.and_then(|script| parse_multisig(script)) {
+        Some(info) => (
+            info.threshold,
+            info.pubkeys.iter().filter_map(|bytes| PublicKey::from_slice(bytes).ok()).collect(),
+        ),
+        None => (1, input.bip32_derivation.keys().copied().collect()),
+    };
+
+    let signer = |key: &PublicKey| signer_name(key, input.bip32_derivation.get(key));
+    let signed_by: Vec<String> = input.partial_sigs.keys().map(|key| signer(&key.inner)).collect();
+    let complete = finalized || signed_by.len() >= required;
+    let missing = if complete {
+        Vec::new()
+    } else {
+        keys.iter()
+            .filter(|key| !input.partial_sigs.keys().any(|signed| signed.inner == **key))
+            .map(signer)
+            .collect()
+    };
+
+    InputSigningState { index, required, signed_by, missing, complete, finalized }
+}
+
+/// Cosigners are named by master fingerprint when the PSBT carries their derivation.
+fn signer_name(key: &PublicKey, source: Option<&KeySource>) -> String {
+    match source {
+        Some((fingerprint, _)) => fingerprint.to_string(),
+        None => bytes_to_hex(&key.serialize()),
+    }
+}
+
+fn find_conflicts(a: &Psbt, b: &Psbt) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for (index, (ours, theirs)) in a.inputs.iter().zip(&b.inputs).enumerate() {
+        for (key, signature) in &ours.partial_sigs {
+            if theirs.partial_sigs.get(key).is_some_and(|other| other != signature) {
+                let name = signer_name(&key.inner, ours.bip32_derivation.get(&key.inner));
+                conflicts.push(format!("input {}: two different signatures from {}", index, name));
+            }
+        }
+        if let (Some(x), Some(y)) = (ours.sighash_type, theirs.sighash_type) {
+            if x != y {
+                conflicts.push(format!("input {}: sighash type {} vs {}", index, x, y));
+            }
+        }
+        if let (Some(x), Some(y)) = (&ours.tap_key_sig, &theirs.tap_key_sig) {
+            if x != y {
+                conflicts.push(format!("input {}: two different key-path signatures", index));
+            }
+        }
+        if let (Some(x), Some(y)) = (&ours.final_script_witness, &theirs.final_script_witness) {
+            if x != y {
+                conflicts.push(format!("input {}: different final witnesses", index));
+            }
+        }
+        if let (Some(x), Some(y)) = (&ours.final_script_sig, &theirs.final_script_sig) {
+            if x != y {
+                conflicts.push(format!("input {}: different final scriptSigs", index));
+            }
+        }
+    }
+    conflicts
+}
@@ -0,0 +1,339 @@
+use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, KeySource};
+use bitcoin::{NetworkKind, Psbt, ScriptBuf, TxOut};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use miniscript::ForEachKey;
+use crate::descriptor::addresses::chain_name;
+use crate::descriptor::{derive_script, parse_descriptors};
+use crate::psbt::parse_psbt;
+use crate::psbt::payjoin::input_utxo;
+use crate::transaction::weight::estimated_signed_vsize;
+use crate::transaction::{check_amount_total, TransactionInput};
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::metrics::timed;
+use crate::utils::network::{network_name, network_or_default, NetworkParams};
+use crate::utils::units::{Amount, FeeRate};
+
+/// A fee above this share of what's sent gets a warning.
+const ABSURD_FEE_PERCENT: f64 = 10.0;
+/// Bitcoin Core refuses to broadcast above 0.1 BTC/kvB by default, which is 10,000
+/// sat/vB. Hardware wallets warn long before that.
+const ABSURD_FEE_RATE: f64 = 1000.0;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SummaryOutput {
+    pub index: usize,
+    /// `payment`, `change` or `data`.
+    pub kind: String,
+    pub address: Option<String>,
+    #[serde(with = "crate::utils::amount")]
+    pub amount: u64,
+    /// For change: the branch and index it re-derived at, and the descriptor it came from.
+    pub chain: Option<String>,
+    #[serde(alias = "derivation_index")]
+    pub derivation_index: Option<u32>,
+    #[serde(alias = "descriptor_index")]
+    pub descriptor_index: Option<usize>,
+    /// What a hardware wallet would show for it.
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PsbtSigningSummary {
+    pub network: String,
+    pub outputs: Vec<SummaryOutput>,
+    /// None when an input has no UTXO data.
+    #[serde(alias = "total_input")]
+    pub total_input: Option<u64>,
+    /// Everything leaving the wallet apart from the fee.
+    #[serde(alias = "total_outgoing")]
+    pub total_outgoing: u64,
+    #[serde(alias = "total_change")]
+    pub total_change: u64,
+    pub fee: Option<u64>,
+    /// The fee as a percentage of `total_outgoing`; None for a transaction that only
+    /// pays the wallet itself.
+    #[serde(alias = "fee_percent")]
+    pub fee_percent: Option<f64>,
+    /// Once signed, estimated from the inputs' script types.
+    #[serde(alias = "estimated_vsize")]
+    pub estimated_vsize: u64,
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: Option<FeeRate>,
+    pub warnings: Vec<String>,
+}
+
+/// One branch of a wallet descriptor, with the key origins its scripts derive from.
+struct Branch {
+    descriptor_index: usize,
+    descriptor: Descriptor<DescriptorPublicKey>,
+    /// Each key's master fingerprint and full path up to the wildcard, and whether it has one.
+    keys: Vec<(Fingerprint, DerivationPath, bool)>,
+}
+
+/// The summary a hardware wallet shows before signing: every output as a payment to an
+/// external address or change back to the wallet, the total sent, the fee and its share
+/// of the amount sent. An output only counts as change when its script re-derives from
+/// one of `wallet_descriptors_json` (a JSON array of descriptors or bare xpubs) at the
+/// index its own BIP32 or taproot derivation names. Anything else is a payment, including
+/// an output whose derivation claims the wallet's fingerprint but doesn't re-derive, which
+/// is how a compromised coordinator would disguise a theft as change. Warnings cover
+/// change that can't be verified, keys for another network, UTXO amounts that can't be
+/// checked and absurd fees.
+#[wasm_bindgen]
+pub fn psbt_signing_summary(psbt_base64: &str, wallet_descriptors_json: &str, network: Option<String>, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("psbt_signing_summary", psbt_base64.len() + wallet_descriptors_json.len(), || {
+        let psbt = parse_psbt(psbt_base64).map_err(|e| JsValue::from_str(&e))?;
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let descriptors: Vec<String> = parse_json_list(wallet_descriptors_json, "wallet descriptors").map_err(|e| JsValue::from_str(&e))?;
+        let mut branches = Vec::new();
+        for (descriptor_index, descriptor) in descriptors.iter().enumerate() {
+            let parsed = parse_descriptors(descriptor).map_err(|e| JsValue::from_str(&format!("wallet descriptors[{}]: {}", descriptor_index, e)))?;
+            branches.extend(parsed.into_iter().map(|descriptor| Branch::new(descriptor_index, descriptor)));
+        }
+        let summary = summarize(&psbt, &branches, &network).map_err(|e| JsValue::from_str(&e))?;
+        to_json_with_amounts(&summary, amount_format, compact)
+    })
+}
+
+impl Branch {
+    fn new(descriptor_index: usize, descriptor: Descriptor<DescriptorPublicKey>) -> Branch {
+        let mut keys = Vec::new();
+        descriptor.for_each_key(|key| {
+            if let Some(path) = key.full_derivation_path() {
+                keys.push((key.master_fingerprint(), path, key.has_wildcard()));
+            }
+            true
+        });
+        Branch { descriptor_index, descriptor, keys }
+    }
+
+    /// The index `origin` puts this branch's scripts at, if it's one of its keys' paths.
+    fn index_for(&self, (fingerprint, path): &KeySource) -> Option<Option<u32>> {
+        self.keys.iter().find_map(|(key_fingerprint, prefix, wildcard)| {
+            if key_fingerprint != fingerprint || !path.as_ref().starts_with(prefix.as_ref()) {
+                return None;
+            }
+            match (&path.as_ref()[prefix.len()..], wildcard) {
+                ([], false) => Some(None),
+                ([ChildNumber::Normal { index } | ChildNumber::Hardened { index }], true) => Some(Some(*index)),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Fails only on amounts no valid transaction could hold, which would overflow the totals.
+fn summarize(psbt: &Psbt, branches: &[Branch], network: &NetworkParams) -> Result<PsbtSigningSummary, String> {
+    let tx = &psbt.unsigned_tx;
+    let mut warnings = network_warnings(psbt, branches, network);
+    let wallet_fingerprints: BTreeSet<Fingerprint> = branches.iter().flat_map(|branch| branch.keys.iter().map(|(fingerprint, _, _)| *fingerprint)).collect();
+    let mut derived: HashMap<(usize, Option<u32>), Option<ScriptBuf>> = HashMap::new();
+
+    let mut outputs = Vec::with_capacity(tx.output.len());
+    for (index, (output, txout)) in psbt.outputs.iter().zip(&tx.output).enumerate() {
+        let origins: Vec<&KeySource> = output.bip32_derivation.values().chain(output.tap_key_origins.values().map(|(_, origin)| origin)).collect();
+        let amount = txout.value.to_sat();
+        let address = network.address(&txout.script_pubkey);
+
+        // The script has to come out of a descriptor at the index the output's own
+        // derivation gives: a matching fingerprint or path alone proves nothing.
+        let change = origins.iter().find_map(|origin| {
+            branches.iter().enumerate().find_map(|(branch_index, branch)| {
+                let derivation_index = branch.index_for(origin)?;
+                let script = derived
+                    .entry((branch_index, derivation_index))
+                    .or_insert_with(|| derive_script(&branch.descriptor, derivation_index.unwrap_or(0)).ok())
+                    .as_ref()?;
+                (*script == txout.script_pubkey).then(|| (branch, derivation_index, chain_of(&origin.1, derivation_index)))
+            })
+        });
+
+        let summary = match change {
+            Some((branch, derivation_index, chain)) => {
+                if chain.as_deref() == Some("receive") {
+                    warnings.push(format!("Output {} is change on a receive address, which a wallet doesn't normally do", index));
+                }
+                let at = match (&chain, derivation_index) {
+                    (Some(chain), Some(derivation_index)) => format!(", {} address {}", chain, derivation_index),
+                    _ => String::new(),
+                };
+                SummaryOutput {
+                    index,
+                    kind: "change".to_string(),
+                    description: format!("Change back to your wallet (verified via derivation{}): {} sats", at, amount),
+                    address,
+                    amount,
+                    chain,
+                    derivation_index,
+                    descriptor_index: Some(branch.descriptor_index),
+                }
+            }
+            None => {
+                if origins.iter().any(|(fingerprint, _)| wallet_fingerprints.contains(fingerprint)) {
+                    warnings.push(format!(
+                        "Output {} carries a derivation from your wallet's keys, but its script doesn't re-derive from your descriptors: \
+                         it is NOT change and is treated as a payment",
+                        index
+                    ));
+                }
+                let (kind, description) = match (&address, txout.script_pubkey.is_op_return()) {
+                    (_, true) => ("data", format!("OP_RETURN data ({} bytes): {} sats", txout.script_pubkey.len(), amount)),
+                    (Some(address), false) => ("payment", format!("Payment to external address {}: {} sats", address, amount)),
+                    (None, false) => {
+                        ("payment", format!("Payment to a script with no address ({}): {} sats", bytes_to_hex(txout.script_pubkey.as_bytes()), amount))
+                    }
+                };
+                SummaryOutput {
+                    index,
+                    kind: kind.to_string(),
+                    address,
+                    amount,
+                    chain: None,
+                    derivation_index: None,
+                    descriptor_index: None,
+                    description,
+                }
+            }
+        };
+        outputs.push(summary);
+    }
+
+    let output_total = check_amount_total(tx.output.iter().map(|output| output.value.to_sat()), "outputs")?;
+    let total_change = check_amount_total(outputs.iter().filter(|output| output.kind == "change").map(|output| output.amount), "change outputs")?;
+    let total_outgoing = check_amount_total(outputs.iter().filter(|output| output.kind != "change").map(|output| output.amount), "outgoing outputs")?;
+    if total_change == 0 && outputs.len() > 1 {
+        warnings.push(
+            "No output could be verified as change. If you expect change, the PSBT is missing that output's derivation \
+             or it doesn't belong to your wallet: check every output as a payment"
+                .to_string(),
+        );
+    }
+
+    let utxos: Vec<Option<TxOut>> = psbt.inputs.iter().zip(&tx.input).map(|(input, txin)| input_utxo(input, txin)).collect();
+    warnings.extend(utxo_warnings(psbt, &utxos));
+    let total_input = utxos
+        .iter()
+        .map(|utxo| utxo.as_ref().map(|utxo| utxo.value.to_sat()))
+        .collect::<Option<Vec<u64>>>()
+        .map(|amounts| check_amount_total(amounts, "inputs"))
+        .transpose()?;
+    let fee = total_input.and_then(|total| total.checked_sub(output_total));
+    if total_input.is_some_and(|total| total < output_total) {
+        warnings.push("The outputs add up to more than the inputs, so this transaction is invalid".to_string());
+    }
+
+    let inputs: Vec<TransactionInput> = tx
+        .input
+        .iter()
+        .zip(&utxos)
+        .map(|(txin, utxo)| TransactionInput {
+            txid: txin.previous_output.txid.to_string(),
+            vout: txin.previous_output.vout,
+            amount: utxo.as_ref().map_or(0, |utxo| utxo.value.to_sat()),
+            script_pubkey: utxo.as_ref().map(|utxo| utxo.script_pubkey.to_hex_string()).unwrap_or_default(),
+            input_type: None,
+            is_coinbase: false,
+            height: None,
+            frozen: false,
+            must_spend: false,
+        })
+        .collect();
+    let estimated_vsize = estimated_signed_vsize(tx, &inputs);
+    let fee_rate = fee.map(|fee| FeeRate::from_fee(Amount::from_sat(fee), estimated_vsize as f64));
+    let fee_percent = fee.filter(|_| total_outgoing > 0).map(|fee| fee as f64 * 100.0 / total_outgoing as f64);
+    match (fee, fee_percent) {
+        (Some(fee), Some(percent)) if percent > ABSURD_FEE_PERCENT => {
+            warnings.push(format!("The fee of {} sats is {:.1}% of the {} sats being sent", fee, percent, total_outgoing))
+        }
+        (Some(fee), None) if fee > total_change => warnings.push(format!("The fee of {} sats is more than the {} sats coming back to you", fee, total_change)),
+        _ => {}
+    }
+    if let Some(rate) = fee_rate.filter(|rate| rate.sat_per_vb() > ABSURD_FEE_RATE) {
+        warnings.push(format!("The fee rate is about {:.0} sat/vB, far above anything needed to confirm", rate.sat_per_vb()));
+    }
+
+    Ok(PsbtSigningSummary {
+        network: network.target_name().to_string(),
+        outputs,
+        total_input,
+        total_outgoing,
+        total_change,
+        fee,
+        fee_percent,
+        estimated_vsize,
+        fee_rate,
+        warnings,
+    })
+}
+
+/// `receive` or `change` from the path step before the index, as BIP44 and its
+/// successors lay wallets out.
+fn chain_of(path: &DerivationPath, derivation_index: Option<u32>) -> Option<String> {
+    derivation_index?;
+    let steps = path.as_ref();
+    match steps.len().checked_sub(2).map(|position| steps[position]) {
+        Some(ChildNumber::Normal { index }) if index <= 1 => Some(chain_name(index as usize)),
+        _ => None,
+    }
+}
+
+/// Descriptor and PSBT global keys for a different network than the one summarized.
+fn network_warnings(psbt: &Psbt, branches: &[Branch], network: &NetworkParams) -> Vec<String> {
+    let expected = NetworkKind::from(network.network);
+    let kind = |kind: NetworkKind| if kind == NetworkKind::Main { "mainnet" } else { "test network" };
+    let mut warnings = Vec::new();
+    let mut reported = BTreeSet::new();
+    for branch in branches {
+        branch.descriptor.for_each_key(|key| {
+            let key_network = match key {
+                DescriptorPublicKey::XPub(xpub) => Some(xpub.xkey.network),
+                DescriptorPublicKey::MultiXPub(xpub) => Some(xpub.xkey.network),
+                DescriptorPublicKey::Single(_) => None,
+            };
+            if key_network.is_some_and(|key_network| key_network != expected) && reported.insert(branch.descriptor_index) {
+                warnings.push(format!(
+                    "Wallet descriptor {} has {} keys, but this summary is for {}",
+                    branch.descriptor_index,
+                    kind(key_network.unwrap_or(expected)),
+                    network_name(network.network)
+                ));
+            }
+            true
+        });
+    }
+    if psbt.xpub.keys().any(|xpub| xpub.network != expected) {
+        warnings.push(format!("The PSBT's global xpubs include keys for a network other than {}", network_name(network.network)));
+    }
+    warnings
+}
+
+/// Inputs whose amount the fee depends on but can't be trusted or isn't there.
+fn utxo_warnings(psbt: &Psbt, utxos: &[Option<TxOut>]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (index, ((input, txin), utxo)) in psbt.inputs.iter().zip(&psbt.unsigned_tx.input).zip(utxos).enumerate() {
+        let Some(utxo) = utxo else {
+            warnings.push(format!("Input {} has no UTXO data, so the fee can't be verified", index));
+            continue;
+        };
+        if let Some(previous) = &input.non_witness_utxo {
+            if previous.compute_txid() != txin.previous_output.txid {
+                warnings.push(format!("Input {}'s previous transaction isn't the one it spends, so its amount can't be trusted", index));
+            }
+        } else if !utxo.script_pubkey.is_p2tr() {
+            // Only taproot signatures commit to every input's amount.
+            warnings.push(format!(
+                "Input {} gives its amount without the previous transaction, which only a taproot signature would catch if it lied",
+                index
+            ));
+        }
+    }
+    warnings
+}
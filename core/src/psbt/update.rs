@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{Fingerprint, KeySource};
+use bitcoin::psbt::PsbtSighashType;
+use bitcoin::secp256k1;
+use bitcoin::{Amount, Psbt, ScriptBuf, Transaction, TxOut};
+use crate::psbt::v2::parse_versioned_psbt;
+use crate::transaction::parse_tx_hex;
+use crate::transaction::prevouts::Prevout;
+use crate::transaction::parse_sighash_type;
+use crate::wallet::keys::parse_public_key;
+use crate::wallet::path::parse_path;
+use crate::utils::json::parse_json_value;
+use crate::utils::metrics::timed;
+
+#[derive(Deserialize)]
+pub struct PsbtUpdates {
+    #[serde(default)]
+    pub inputs: Vec<InputUpdate>,
+    #[serde(default)]
+    pub outputs: Vec<OutputUpdate>,
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputUpdate {
+    pub index: usize,
+    #[serde(alias = "witness_utxo")]
+    pub witness_utxo: Option<Prevout>,
+    #[serde(alias = "non_witness_utxo")]
+    pub non_witness_utxo: Option<String>,
+    #[serde(alias = "redeem_script")]
+    pub redeem_script: Option<String>,
+    #[serde(alias = "witness_script")]
+    pub witness_script: Option<String>,
+    #[serde(alias = "sighash_type")]
+    pub sighash_type: Option<String>,
+    #[serde(default, alias = "bip32_derivations")]
+    pub bip32_derivations: Vec<Bip32Derivation>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputUpdate {
+    pub index: usize,
+    #[serde(default, alias = "bip32_derivations")]
+    pub bip32_derivations: Vec<Bip32Derivation>,
+}
+
+#[derive(Deserialize)]
+pub struct Bip32Derivation {
+    pub pubkey: String,
+    pub fingerprint: String,
+    pub path: String,
+}
+
+/// BIP174 Updater: attaches UTXO, script and key-origin data to an existing PSBT.
+/// Fields that already hold a different value are left alone unless `force` is set.
+#[wasm_bindgen]
+pub fn update_psbt(psbt_base64: &str, updates_json: &str) -> Result<String, JsValue> {
+    timed("update_psbt", psbt_base64.len() + updates_json.len(), || {
+        let mut versioned = parse_versioned_psbt(psbt_base64).map_err(|e| JsValue::from_str(&e))?;
+        let updates: PsbtUpdates = parse_json_value(updates_json, "updates").map_err(|e| JsValue::from_str(&e))?;
+
+        for update in &updates.inputs {
+            apply_input_update(&mut versioned.psbt, update, updates.force).map_err(|e| JsValue::from_str(&e))?;
+        }
+        for update in &updates.outputs {
+            apply_output_update(&mut versioned.psbt, update, updates.force).map_err(|e| JsValue::from_str(&e))?;
+        }
+
+        Ok(versioned.to_base64())
+    })
+}
+
+fn apply_input_update(psbt: &mut Psbt, update: &InputUpdate, force: bool) -> Result<(), String> {
+    let index = update.index;
+    let outpoint = psbt
+        .unsigned_tx
+        .input
+        .get(index)
+        .ok_or_else(|| format!("Input index {} out of range (PSBT has {} inputs)", index, psbt.inputs.len()))?
+        .previous_output;
+    let input = &mut psbt.inputs[index];
+    let what = |field: &str| format!("input {} {}", index, field);
+
+    if let Some(hex) = &update.non_witness_utxo {
+        let prev_tx = parse_tx_hex(hex).map_err(|e| format!("Invalid non_witness_utxo for input {}: {}", index, e))?;
+        if prev_tx.compute_txid() != outpoint.txid {
+            return Err(format!(
+                "non_witness_utxo for input {} has txid {}, but the input spends {}",
+                index,
+                prev_tx.compute_txid(),
+                outpoint.txid
+            ));
+        }
+        if outpoint.vout as usize >= prev_tx.output.len() {
+            return Err(format!("non_witness_utxo for input {} has no output {}", index, outpoint.vout));
+        }
+        set_field(&mut input.non_witness_utxo, prev_tx, force, &what("nonWitnessUtxo"))?;
+    }
+
+    if let Some(prevout) = &update.witness_utxo {
+        let script_pubkey = ScriptBuf::from_hex(&prevout.script_pubkey)
+            .map_err(|e| format!("Invalid witness_utxo script for input {}: {}", index, e))?;
+        let txout = TxOut {
+            value: Amount::from_sat(prevout.amount),
+            script_pubkey,
+        };
+        set_field(&mut input.witness_utxo, txout, force, &what("witnessUtxo"))?;
+    }
+
+    if let Some(hex) = &update.redeem_script {
+        let script = ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid redeem_script for input {}: {}", index, e))?;
+        set_field(&mut input.redeem_script, script, force, &what("redeemScript"))?;
+    }
+
+    if let Some(hex) = &update.witness_script {
+        let script = ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid witness_script for input {}: {}", index, e))?;
+        set_field(&mut input.witness_script, script, force, &what("witnessScript"))?;
+    }
+
+    if let Some(sighash) = &update.sighash_type {
+        let sighash_type = PsbtSighashType::from(parse_sighash_type(sighash)?);
+        set_field(&mut input.sighash_type, sighash_type, force, &what("sighash_type"))?;
+    }
+
+    merge_derivations(&mut input.bip32_derivation, &update.bip32_derivations, force, &what("bip32 derivation"))?;
+
+    let utxo = spent_output(input.witness_utxo.as_ref(), input.non_witness_utxo.as_ref(), outpoint.vout, index)?;
+    if let Some(utxo) = utxo {
+        check_scripts(&utxo.script_pubkey, input.redeem_script.as_ref(), input.witness_script.as_ref(), index)?;
+    }
+    Ok(())
+}
+
+fn apply_output_update(psbt: &mut Psbt, update: &OutputUpdate, force: bool) -> Result<(), String> {
+    let output_count = psbt.outputs.len();
+    let output = psbt
+        .outputs
+        .get_mut(update.index)
+        .ok_or_else(|| format!("Output index {} out of range (PSBT has {} outputs)", update.index, output_count))?;
+    let what = format!("output {} bip32 derivation", update.index);
+    merge_derivations(&mut output.bip32_derivation, &update.bip32_derivations, force, &what)
+}
+
+fn set_field<T: PartialEq>(slot: &mut Option<T>, value: T, force: bool, what: &str) -> Result<(), String> {
+    if let Some(existing) = slot {
+        if *existing != value && !force {
+            return Err(format!("{} already has a different value; set force to overwrite", what));
+        }
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn merge_derivations(
+    map: &mut BTreeMap<secp256k1::PublicKey, KeySource>,
+    derivations: &[Bip32Derivation],
+    force: bool,
+    what: &str,
+) -> Result<(), String> {
+    for derivation in derivations {
+        let (pubkey, origin) = parse_derivation(derivation)?;
+        let mut slot = map.get(&pubkey).cloned();
+        set_field(&mut slot, origin, force, &format!("{} for {}", what, derivation.pubkey))?;
+        if let Some(origin) = slot {
+            map.insert(pubkey, origin);
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_derivation(derivation: &Bip32Derivation) -> Result<(secp256k1::PublicKey, KeySource), String> {
+    let pubkey = parse_public_key(&derivation.pubkey)
+        .map_err(|e| e.as_string().unwrap_or_else(|| "Invalid public key".to_string()))?;
+    if !pubkey.compressed {
+        return Err(format!("BIP32 derivation key {} must be compressed", derivation.pubkey));
+    }
+    let origin = parse_key_source(&derivation.fingerprint, &derivation.path)?;
+    Ok((pubkey.inner, origin))
+}
+
+pub fn parse_key_source(fingerprint: &str, path: &str) -> Result<KeySource, String> {
+    let fingerprint = Fingerprint::from_str(fingerprint)
+        .map_err(|e| format!("Invalid fingerprint \"{}\": {}", fingerprint, e))?;
+    Ok((fingerprint, parse_path(path, false)?.path))
+}
+
+fn spent_output(
+    witness_utxo: Option<&TxOut>,
+    non_witness_utxo: Option<&Transaction>,
+    vout: u32,
+    index: usize,
+) -> Result<Option<TxOut>, String> {
+    let from_tx = non_witness_utxo.and_then(|tx| tx.output.get(vout as usize)).cloned();
+    match (witness_utxo, from_tx) {
+        (Some(witness), Some(full)) if *witness != full => Err(format!(
+            "witness_utxo for input {} does not match output {} of its non_witness_utxo",
+            index, vout
+        )),
+        (Some(witness), _) => Ok(Some(witness.clone())),
+        (None, full) => Ok(full),
+    }
+}
+
+/// Catches scripts attached to the wrong input before a signer refuses the whole PSBT.
+fn check_scripts(
+    script_pubkey: &ScriptBuf,
+    redeem_script: Option<&ScriptBuf>,
+    witness_script: Option<&ScriptBuf>,
+    index: usize,
+) -> Result<(), String> {
+    let mut program = script_pubkey.clone();
+    if let Some(redeem) = redeem_script {
+        if !script_pubkey.is_p2sh() {
+            return Err(format!("input {} has a redeem_script but does not spend a P2SH output", index));
+        }
+        if ScriptBuf::new_p2sh(&redeem.script_hash()) != *script_pubkey {
+            return Err(format!("redeem_script for input {} does not hash to the spent scriptPubKey", index));
+        }
+        program = redeem.clone();
+    }
+    if let Some(witness) = witness_script {
+        if !program.is_p2wsh() {
+            return Err(format!("input {} has a witness_script but does not spend a P2WSH program", index));
+        }
+        if ScriptBuf::new_p2wsh(&witness.wscript_hash()) != program {
+            return Err(format!("witness_script for input {} does not hash to its P2WSH program", index));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,652 @@
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::{Input, Output};
+use bitcoin::{Amount, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use crate::psbt::{decode_psbt_bytes, PSBT_MAGIC};
+use crate::transaction::parse_tx_hex;
+use crate::transaction::prevouts::Prevout;
+use crate::transaction::version::parse_version;
+use crate::utils::base64::encode_base64;
+use crate::utils::json::parse_json_value;
+use crate::utils::network::{network_or_default, require_address_network};
+use crate::utils::varint::{decode_compact_size, encode_compact_size};
+use crate::utils::metrics::timed;
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+/// PSBT_GLOBAL_TX_MODIFIABLE bits.
+pub const INPUTS_MODIFIABLE: u8 = 0x01;
+pub const OUTPUTS_MODIFIABLE: u8 = 0x02;
+pub const HAS_SIGHASH_SINGLE: u8 = 0x04;
+
+/// Locktimes below this are block heights, at or above it Unix times.
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// One key-value map, keyed by the full key (type byte and key data).
+type RawMap = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// The BIP370 fields a v0 PSBT has no room for. The transaction itself lives in the
+/// PSBT's unsigned transaction, whose locktime is recomputed from these whenever
+/// inputs change.
+#[derive(Clone, Default, PartialEq)]
+pub struct V2Fields {
+    pub fallback_locktime: Option<u32>,
+    pub modifiable: Option<u8>,
+    pub inputs: Vec<RequiredLocktimes>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct RequiredLocktimes {
+    pub time: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// A PSBT as read, remembering whether it was version 2 so it is written back the same way.
+#[derive(Clone)]
+pub struct VersionedPsbt {
+    pub psbt: Psbt,
+    pub v2: Option<V2Fields>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CreateOptions {
+    #[serde(alias = "tx_version")]
+    pub tx_version: Option<u32>,
+    #[serde(alias = "fallback_locktime")]
+    pub fallback_locktime: Option<u32>,
+    #[serde(alias = "inputs_modifiable")]
+    pub inputs_modifiable: Option<bool>,
+    #[serde(alias = "outputs_modifiable")]
+    pub outputs_modifiable: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewInput {
+    pub txid: String,
+    pub vout: u32,
+    pub sequence: Option<u32>,
+    #[serde(alias = "required_time_locktime")]
+    pub required_time_locktime: Option<u32>,
+    #[serde(alias = "required_height_locktime")]
+    pub required_height_locktime: Option<u32>,
+    #[serde(alias = "witness_utxo")]
+    pub witness_utxo: Option<Prevout>,
+    #[serde(alias = "non_witness_utxo")]
+    pub non_witness_utxo: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewOutput {
+    pub address: Option<String>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ToV0Options {
+    pub lossy: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToV2Options {
+    #[serde(alias = "inputs_modifiable")]
+    pub inputs_modifiable: bool,
+    #[serde(alias = "outputs_modifiable")]
+    pub outputs_modifiable: bool,
+}
+
+/// BIP370 Creator: an empty v2 PSBT that inputs and outputs are then added to. Both are
+/// modifiable unless turned off in the options.
+#[wasm_bindgen]
+pub fn create_psbt_v2(options_json: Option<String>) -> Result<String, JsValue> {
+    timed("create_psbt_v2", 0, || {
+        let options: CreateOptions = match options_json {
+            Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+            _ => CreateOptions::default(),
+        };
+        let version = parse_version(options.tx_version).map_err(|e| JsValue::from_str(&e))?;
+        let mut modifiable = 0;
+        if options.inputs_modifiable.unwrap_or(true) {
+            modifiable |= INPUTS_MODIFIABLE;
+        }
+        if options.outputs_modifiable.unwrap_or(true) {
+            modifiable |= OUTPUTS_MODIFIABLE;
+        }
+
+        let tx = Transaction {
+            version,
+            lock_time: LockTime::from_consensus(options.fallback_locktime.unwrap_or(0)),
+            input: Vec::new(),
+            output: Vec::new(),
+        };
+        let psbt = Psbt::from_unsigned_tx(tx).map_err(|e| JsValue::from_str(&format!("Invalid PSBT: {}", e)))?;
+        let versioned = VersionedPsbt {
+            psbt,
+            v2: Some(V2Fields { fallback_locktime: options.fallback_locktime, modifiable: Some(modifiable), inputs: Vec::new() }),
+        };
+        Ok(versioned.to_base64())
+    })
+}
+
+/// BIP370 Constructor: appends an input, if the PSBT's flags and signatures allow it.
+/// Appending keeps every existing input paired with the same output, which
+/// SIGHASH_SINGLE signatures rely on.
+#[wasm_bindgen]
+pub fn psbt_v2_add_input(psbt: &str, input_json: &str) -> Result<String, JsValue> {
+    let mut versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    let input: NewInput = parse_json_value(input_json, "input").map_err(|e| JsValue::from_str(&e))?;
+    add_input(&mut versioned, input).map_err(|e| JsValue::from_str(&e))?;
+    Ok(versioned.to_base64())
+}
+
+/// BIP370 Constructor: appends an output paying `address` or `script_pubkey`.
+#[wasm_bindgen]
+pub fn psbt_v2_add_output(psbt: &str, output_json: &str, network: Option<String>) -> Result<String, JsValue> {
+    let mut versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    let output: NewOutput = parse_json_value(output_json, "output").map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+
+    let script_pubkey = match (&output.address, &output.script_pubkey) {
+        (Some(address), None) => require_address_network(address, Some(&network)).map_err(|e| JsValue::from_str(&e))?,
+        (None, Some(script)) => ScriptBuf::from_hex(script)
+            .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e)))?,
+        _ => return Err(JsValue::from_str("An output needs exactly one of address or scriptPubkey")),
+    };
+    let fields = require_v2(&versioned).map_err(|e| JsValue::from_str(&e))?;
+    if effective_modifiable(fields, &versioned.psbt) & OUTPUTS_MODIFIABLE == 0 {
+        return Err(JsValue::from_str("This PSBT doesn't allow adding outputs: its outputs-modifiable flag is clear"));
+    }
+
+    versioned.psbt.unsigned_tx.output.push(TxOut { value: Amount::from_sat(output.amount), script_pubkey });
+    versioned.psbt.outputs.push(Output::default());
+    Ok(versioned.to_base64())
+}
+
+/// Converts a v2 PSBT to v0 for tools that only read BIP174. The modifiable flags and
+/// per-input locktime requirements have no v0 form, so a PSBT still carrying them is
+/// refused unless `lossy` is set.
+#[wasm_bindgen]
+pub fn psbt_v2_to_v0(psbt: &str, options_json: Option<String>) -> Result<String, JsValue> {
+    let options: ToV0Options = match options_json {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+        _ => ToV0Options::default(),
+    };
+    let versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    let Some(fields) = &versioned.v2 else {
+        return Err(JsValue::from_str("PSBT is already version 0"));
+    };
+
+    let mut dropped = Vec::new();
+    let flags = effective_modifiable(fields, &versioned.psbt);
+    if flags & INPUTS_MODIFIABLE != 0 {
+        dropped.push("inputs are modifiable".to_string());
+    }
+    if flags & OUTPUTS_MODIFIABLE != 0 {
+        dropped.push("outputs are modifiable".to_string());
+    }
+    for (index, required) in fields.inputs.iter().enumerate() {
+        if let Some(time) = required.time {
+            dropped.push(format!("input {} requires a time locktime of {}", index, time));
+        }
+        if let Some(height) = required.height {
+            dropped.push(format!("input {} requires a height locktime of {}", index, height));
+        }
+    }
+    if !dropped.is_empty() && !options.lossy {
+        return Err(JsValue::from_str(&format!(
+            "Converting to version 0 would drop: {}. Pass {{\"lossy\": true}} to convert anyway",
+            dropped.join(", ")
+        )));
+    }
+
+    Ok(encode_base64(&versioned.psbt.serialize(), false))
+}
+
+/// Converts a v0 PSBT to v2, keeping its locktime as the fallback. Inputs and outputs
+/// stay fixed unless the options make them modifiable, which existing signatures may
+/// rule out.
+#[wasm_bindgen]
+pub fn psbt_v0_to_v2(psbt: &str, options_json: Option<String>) -> Result<String, JsValue> {
+    let options: ToV2Options = match options_json {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+        _ => ToV2Options::default(),
+    };
+    let mut versioned = parse_versioned_psbt(psbt).map_err(|e| JsValue::from_str(&e))?;
+    if versioned.v2.is_some() {
+        return Err(JsValue::from_str("PSBT is already version 2"));
+    }
+
+    let mut requested = 0;
+    if options.inputs_modifiable {
+        requested |= INPUTS_MODIFIABLE;
+    }
+    if options.outputs_modifiable {
+        requested |= OUTPUTS_MODIFIABLE;
+    }
+    let lock_time = versioned.psbt.unsigned_tx.lock_time.to_consensus_u32();
+    let fields = V2Fields {
+        fallback_locktime: if lock_time == 0 { None } else { Some(lock_time) },
+        modifiable: Some(requested),
+        inputs: vec![RequiredLocktimes::default(); versioned.psbt.inputs.len()],
+    };
+    let allowed = effective_modifiable(&fields, &versioned.psbt);
+    if requested & INPUTS_MODIFIABLE != 0 && allowed & INPUTS_MODIFIABLE == 0 {
+        return Err(JsValue::from_str("Inputs can't be made modifiable: an existing signature commits to all inputs"));
+    }
+    if requested & OUTPUTS_MODIFIABLE != 0 && allowed & OUTPUTS_MODIFIABLE == 0 {
+        return Err(JsValue::from_str("Outputs can't be made modifiable: an existing signature commits to the outputs"));
+    }
+
+    versioned.v2 = Some(fields);
+    Ok(versioned.to_base64())
+}
+
+/// Like `parse_psbt`, but keeps what makes a v2 PSBT one.
+pub fn parse_versioned_psbt(input: &str) -> Result<VersionedPsbt, String> {
+    let (_, bytes) = decode_psbt_bytes(input)?;
+    deserialize_versioned_psbt(&bytes)
+}
+
+pub fn deserialize_versioned_psbt(bytes: &[u8]) -> Result<VersionedPsbt, String> {
+    if !bytes.starts_with(&PSBT_MAGIC) {
+        return Err("not a PSBT (missing magic)".to_string());
+    }
+    let mut pos = PSBT_MAGIC.len();
+    let mut global = read_map(bytes, &mut pos)?;
+    let version = match take_field(&mut global, PSBT_GLOBAL_VERSION, "global version")? {
+        Some(value) => u32::from_le_bytes(fixed(&value, "global version")?),
+        None => 0,
+    };
+
+    match version {
+        0 => {
+            let psbt = Psbt::deserialize(bytes).map_err(|e| format!("Invalid PSBT: {}", e))?;
+            Ok(VersionedPsbt { psbt, v2: None })
+        }
+        2 => from_v2(bytes, global, pos),
+        other => Err(format!("PSBT version {} isn't supported (expected 0 or 2)", other)),
+    }
+}
+
+impl VersionedPsbt {
+    pub fn version(&self) -> u32 {
+        if self.v2.is_some() { 2 } else { 0 }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let Some(fields) = &self.v2 else {
+            return self.psbt.serialize();
+        };
+        let tx = &self.psbt.unsigned_tx;
+        let v0 = self.psbt.serialize();
+        let mut pos = PSBT_MAGIC.len();
+        let mut global = read_map(&v0, &mut pos).expect("rust-bitcoin writes valid maps");
+        let mut inputs = Vec::with_capacity(tx.input.len());
+        for _ in &tx.input {
+            inputs.push(read_map(&v0, &mut pos).expect("rust-bitcoin writes valid maps"));
+        }
+        let mut outputs = Vec::with_capacity(tx.output.len());
+        for _ in &tx.output {
+            outputs.push(read_map(&v0, &mut pos).expect("rust-bitcoin writes valid maps"));
+        }
+
+        global.remove(&vec![PSBT_GLOBAL_UNSIGNED_TX]);
+        global.insert(vec![PSBT_GLOBAL_TX_VERSION], tx.version.0.to_le_bytes().to_vec());
+        if let Some(fallback) = fields.fallback_locktime {
+            global.insert(vec![PSBT_GLOBAL_FALLBACK_LOCKTIME], fallback.to_le_bytes().to_vec());
+        }
+        global.insert(vec![PSBT_GLOBAL_INPUT_COUNT], encode_compact_size(tx.input.len() as u64));
+        global.insert(vec![PSBT_GLOBAL_OUTPUT_COUNT], encode_compact_size(tx.output.len() as u64));
+        if fields.modifiable.is_some() {
+            global.insert(vec![PSBT_GLOBAL_TX_MODIFIABLE], vec![effective_modifiable(fields, &self.psbt)]);
+        }
+        global.insert(vec![PSBT_GLOBAL_VERSION], 2u32.to_le_bytes().to_vec());
+
+        for (index, (map, txin)) in inputs.iter_mut().zip(&tx.input).enumerate() {
+            map.insert(vec![PSBT_IN_PREVIOUS_TXID], txin.previous_output.txid.to_byte_array().to_vec());
+            map.insert(vec![PSBT_IN_OUTPUT_INDEX], txin.previous_output.vout.to_le_bytes().to_vec());
+            if txin.sequence != Sequence::MAX {
+                map.insert(vec![PSBT_IN_SEQUENCE], txin.sequence.0.to_le_bytes().to_vec());
+            }
+            let required = fields.inputs.get(index).copied().unwrap_or_default();
+            if let Some(time) = required.time {
+                map.insert(vec![PSBT_IN_REQUIRED_TIME_LOCKTIME], time.to_le_bytes().to_vec());
+            }
+            if let Some(height) = required.height {
+                map.insert(vec![PSBT_IN_REQUIRED_HEIGHT_LOCKTIME], height.to_le_bytes().to_vec());
+            }
+        }
+        for (map, txout) in outputs.iter_mut().zip(&tx.output) {
+            map.insert(vec![PSBT_OUT_AMOUNT], (txout.value.to_sat() as i64).to_le_bytes().to_vec());
+            map.insert(vec![PSBT_OUT_SCRIPT], txout.script_pubkey.to_bytes());
+        }
+
+        write_psbt(&global, &inputs, &outputs)
+    }
+
+    pub fn to_base64(&self) -> String {
+        encode_base64(&self.serialize(), false)
+    }
+
+    /// Merges the v2 fields of a combined PSBT for the same transaction: a flag allowing
+    /// changes survives only if both sides still allow it.
+    pub fn combine_fields(&mut self, other: &VersionedPsbt) {
+        if let (Some(ours), Some(theirs)) = (&mut self.v2, &other.v2) {
+            if let (Some(a), Some(b)) = (ours.modifiable, theirs.modifiable) {
+                let removable = INPUTS_MODIFIABLE | OUTPUTS_MODIFIABLE;
+                ours.modifiable = Some((a & b & removable) | ((a | b) & HAS_SIGHASH_SINGLE));
+            }
+        }
+    }
+}
+
+fn from_v2(bytes: &[u8], mut global: RawMap, mut pos: usize) -> Result<VersionedPsbt, String> {
+    if global.keys().any(|key| key[0] == PSBT_GLOBAL_UNSIGNED_TX) {
+        return Err("Invalid PSBT: a version 2 PSBT must not carry an unsigned transaction".to_string());
+    }
+    let tx_version = required(take_field(&mut global, PSBT_GLOBAL_TX_VERSION, "transaction version")?, "transaction version")?;
+    let tx_version = i32::from_le_bytes(fixed(&tx_version, "transaction version")?);
+    let fallback_locktime = take_field(&mut global, PSBT_GLOBAL_FALLBACK_LOCKTIME, "fallback locktime")?
+        .map(|value| fixed(&value, "fallback locktime").map(u32::from_le_bytes))
+        .transpose()?;
+    let input_count = count(take_field(&mut global, PSBT_GLOBAL_INPUT_COUNT, "input count")?, "input count")?;
+    let output_count = count(take_field(&mut global, PSBT_GLOBAL_OUTPUT_COUNT, "output count")?, "output count")?;
+    let modifiable = take_field(&mut global, PSBT_GLOBAL_TX_MODIFIABLE, "modifiable flags")?
+        .map(|value| fixed::<1>(&value, "modifiable flags").map(|flags| flags[0]))
+        .transpose()?;
+
+    let mut inputs = Vec::new();
+    let mut txins = Vec::new();
+    let mut requirements = Vec::new();
+    for index in 0..input_count {
+        let mut map = read_map(bytes, &mut pos)?;
+        let what = |field: &str| format!("input {} {}", index, field);
+        let txid = required(take_field(&mut map, PSBT_IN_PREVIOUS_TXID, &what("previous txid"))?, &what("previous txid"))?;
+        let vout = required(take_field(&mut map, PSBT_IN_OUTPUT_INDEX, &what("output index"))?, &what("output index"))?;
+        let sequence = take_field(&mut map, PSBT_IN_SEQUENCE, &what("sequence"))?
+            .map(|value| fixed(&value, &what("sequence")).map(u32::from_le_bytes))
+            .transpose()?;
+        let time = take_field(&mut map, PSBT_IN_REQUIRED_TIME_LOCKTIME, &what("required time locktime"))?
+            .map(|value| fixed(&value, &what("required time locktime")).map(u32::from_le_bytes))
+            .transpose()?;
+        let height = take_field(&mut map, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, &what("required height locktime"))?
+            .map(|value| fixed(&value, &what("required height locktime")).map(u32::from_le_bytes))
+            .transpose()?;
+        let required_locktimes = RequiredLocktimes { time, height };
+        check_required(&required_locktimes).map_err(|e| format!("Invalid PSBT: input {} {}", index, e))?;
+
+        txins.push(TxIn {
+            previous_output: OutPoint {
+                txid: Txid::from_byte_array(fixed(&txid, &what("previous txid"))?),
+                vout: u32::from_le_bytes(fixed(&vout, &what("output index"))?),
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence(sequence.unwrap_or(u32::MAX)),
+            witness: Witness::new(),
+        });
+        requirements.push(required_locktimes);
+        inputs.push(map);
+    }
+
+    let mut outputs = Vec::new();
+    let mut txouts = Vec::new();
+    for index in 0..output_count {
+        let mut map = read_map(bytes, &mut pos)?;
+        let what = |field: &str| format!("output {} {}", index, field);
+        let amount = required(take_field(&mut map, PSBT_OUT_AMOUNT, &what("amount"))?, &what("amount"))?;
+        let amount = i64::from_le_bytes(fixed(&amount, &what("amount"))?);
+        let script = required(take_field(&mut map, PSBT_OUT_SCRIPT, &what("script"))?, &what("script"))?;
+        if amount < 0 {
+            return Err(format!("Invalid PSBT: output {} amount is negative", index));
+        }
+        txouts.push(TxOut { value: Amount::from_sat(amount as u64), script_pubkey: ScriptBuf::from_bytes(script) });
+        outputs.push(map);
+    }
+    if pos != bytes.len() {
+        return Err("Invalid PSBT: data after the last output map".to_string());
+    }
+
+    let lock_time = determine_locktime(fallback_locktime, &requirements).map_err(|e| format!("Invalid PSBT: {}", e))?;
+    let tx = Transaction {
+        version: bitcoin::transaction::Version(tx_version),
+        lock_time: LockTime::from_consensus(lock_time),
+        input: txins,
+        output: txouts,
+    };
+    // Written without witness markers, the way BIP174 wants the unsigned transaction.
+    let mut unsigned = serialize(&tx.version);
+    unsigned.extend(serialize(&tx.input));
+    unsigned.extend(serialize(&tx.output));
+    unsigned.extend(serialize(&tx.lock_time));
+    global.insert(vec![PSBT_GLOBAL_UNSIGNED_TX], unsigned);
+
+    let psbt = Psbt::deserialize(&write_psbt(&global, &inputs, &outputs)).map_err(|e| format!("Invalid PSBT: {}", e))?;
+    let fields = V2Fields { fallback_locktime, modifiable, inputs: requirements };
+    Ok(VersionedPsbt { psbt, v2: Some(fields) })
+}
+
+fn add_input(versioned: &mut VersionedPsbt, input: NewInput) -> Result<(), String> {
+    let fields = require_v2(versioned)?;
+    if effective_modifiable(fields, &versioned.psbt) & INPUTS_MODIFIABLE == 0 {
+        return Err("This PSBT doesn't allow adding inputs: its inputs-modifiable flag is clear".to_string());
+    }
+    let txid: Txid = input.txid.trim().parse().map_err(|e| format!("Invalid txid: {}", e))?;
+    let outpoint = OutPoint { txid, vout: input.vout };
+    if let Some(index) = versioned.psbt.unsigned_tx.input.iter().position(|txin| txin.previous_output == outpoint) {
+        return Err(format!("Input {} is already in the PSBT (input {})", outpoint, index));
+    }
+    let required_locktimes = RequiredLocktimes { time: input.required_time_locktime, height: input.required_height_locktime };
+    check_required(&required_locktimes)?;
+
+    // Signatures commit to the locktime, so once there are any it must not move.
+    let mut requirements = fields.inputs.clone();
+    requirements.resize(versioned.psbt.inputs.len(), RequiredLocktimes::default());
+    requirements.push(required_locktimes);
+    let current = versioned.psbt.unsigned_tx.lock_time.to_consensus_u32();
+    let lock_time = determine_locktime(fields.fallback_locktime, &requirements)
+        .map_err(|e| format!("Can't add input {}: {}", outpoint, e))?;
+    if lock_time != current && versioned.psbt.inputs.iter().any(|input| !signature_sighashes(input).is_empty()) {
+        return Err(format!(
+            "Adding input {} would move the locktime from {} to {} and invalidate existing signatures",
+            outpoint, current, lock_time
+        ));
+    }
+
+    let mut psbt_input = Input::default();
+    if let Some(prevout) = &input.witness_utxo {
+        let script_pubkey = ScriptBuf::from_hex(&prevout.script_pubkey)
+            .map_err(|e| format!("Invalid witness_utxo script pubkey: {}", e))?;
+        psbt_input.witness_utxo = Some(TxOut { value: Amount::from_sat(prevout.amount), script_pubkey });
+    }
+    if let Some(hex) = &input.non_witness_utxo {
+        let prev = parse_tx_hex(hex)?;
+        if prev.compute_txid() != txid {
+            return Err(format!("non_witness_utxo is transaction {}, not {}", prev.compute_txid(), txid));
+        }
+        if prev.output.len() <= input.vout as usize {
+            return Err(format!("non_witness_utxo has {} outputs, so output {} doesn't exist", prev.output.len(), input.vout));
+        }
+        psbt_input.non_witness_utxo = Some(prev);
+    }
+
+    let psbt = &mut versioned.psbt;
+    psbt.unsigned_tx.input.push(TxIn {
+        previous_output: outpoint,
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence(input.sequence.unwrap_or(u32::MAX)),
+        witness: Witness::new(),
+    });
+    psbt.unsigned_tx.lock_time = LockTime::from_consensus(lock_time);
+    psbt.inputs.push(psbt_input);
+    if let Some(fields) = &mut versioned.v2 {
+        fields.inputs = requirements;
+    }
+    Ok(())
+}
+
+fn require_v2(versioned: &VersionedPsbt) -> Result<&V2Fields, String> {
+    versioned
+        .v2
+        .as_ref()
+        .ok_or_else(|| "Only a version 2 PSBT can be added to; convert it with psbt_v0_to_v2 first".to_string())
+}
+
+/// BIP370 locktime rule: with no requirements the fallback (default 0); otherwise the
+/// largest requirement of a kind every requiring input accepts, preferring height.
+pub fn determine_locktime(fallback: Option<u32>, requirements: &[RequiredLocktimes]) -> Result<u32, String> {
+    let requiring: Vec<&RequiredLocktimes> =
+        requirements.iter().filter(|required| required.time.is_some() || required.height.is_some()).collect();
+    if requiring.is_empty() {
+        return Ok(fallback.unwrap_or(0));
+    }
+    if requiring.iter().all(|required| required.height.is_some()) {
+        return Ok(requiring.iter().filter_map(|required| required.height).max().unwrap_or(0));
+    }
+    if requiring.iter().all(|required| required.time.is_some()) {
+        return Ok(requiring.iter().filter_map(|required| required.time).max().unwrap_or(0));
+    }
+    Err("inputs require incompatible locktimes (some only by height, others only by time)".to_string())
+}
+
+fn check_required(required: &RequiredLocktimes) -> Result<(), String> {
+    if required.time.is_some_and(|time| time < LOCKTIME_THRESHOLD) {
+        return Err(format!("required time locktime must be at least {}", LOCKTIME_THRESHOLD));
+    }
+    if required.height.is_some_and(|height| height == 0 || height >= LOCKTIME_THRESHOLD) {
+        return Err(format!("required height locktime must be between 1 and {}", LOCKTIME_THRESHOLD - 1));
+    }
+    Ok(())
+}
+
+/// The stored flags with what the signatures present rule out, as a BIP370 Signer would
+/// leave them: anything but ANYONECANPAY fixes the inputs, anything but NONE fixes the
+/// outputs, and SINGLE is recorded.
+pub fn effective_modifiable(fields: &V2Fields, psbt: &Psbt) -> u8 {
+    let mut flags = fields.modifiable.unwrap_or(0);
+    for input in &psbt.inputs {
+        for sighash in signature_sighashes(input) {
+            if sighash & 0x80 == 0 {
+                flags &= !INPUTS_MODIFIABLE;
+            }
+            match sighash & 0x1f {
+                0x02 => {}
+                0x03 => flags = (flags & !OUTPUTS_MODIFIABLE) | HAS_SIGHASH_SINGLE,
+                _ => flags &= !OUTPUTS_MODIFIABLE,
+            }
+        }
+    }
+    flags
+}
+
+fn signature_sighashes(input: &Input) -> Vec<u32> {
+    input
+        .partial_sigs
+        .values()
+        .map(|signature| signature.sighash_type.to_u32())
+        .chain(input.tap_key_sig.iter().map(|signature| signature.sighash_type as u32))
+        .chain(input.tap_script_sigs.values().map(|signature| signature.sighash_type as u32))
+        .collect()
+}
+
+fn read_map(bytes: &[u8], pos: &mut usize) -> Result<RawMap, String> {
+    let mut map = RawMap::new();
+    loop {
+        let key_len = read_compact(bytes, pos)?;
+        if key_len == 0 {
+            return Ok(map);
+        }
+        let key = read_slice(bytes, pos, key_len)?.to_vec();
+        let value_len = read_compact(bytes, pos)?;
+        let value = read_slice(bytes, pos, value_len)?.to_vec();
+        if map.insert(key.clone(), value).is_some() {
+            return Err(format!("Invalid PSBT: duplicate key {}", crate::utils::bytes_to_hex(&key)));
+        }
+    }
+}
+
+fn read_compact(bytes: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let rest = bytes.get(*pos..).unwrap_or_default();
+    if rest.is_empty() {
+        return Err("Invalid PSBT: ends inside a key-value map".to_string());
+    }
+    let decoded = decode_compact_size(rest).map_err(|e| format!("Invalid PSBT: {}", e))?;
+    *pos += decoded.bytes_consumed;
+    Ok(decoded.value as usize)
+}
+
+fn read_slice<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    let slice = pos
+        .checked_add(len)
+        .and_then(|end| bytes.get(*pos..end))
+        .ok_or("Invalid PSBT: ends inside a key-value map")?;
+    *pos += len;
+    Ok(slice)
+}
+
+fn write_psbt(global: &RawMap, inputs: &[RawMap], outputs: &[RawMap]) -> Vec<u8> {
+    let mut bytes = PSBT_MAGIC.to_vec();
+    for map in std::iter::once(global).chain(inputs).chain(outputs) {
+        for (key, value) in map {
+            bytes.extend(encode_compact_size(key.len() as u64));
+            bytes.extend(key);
+            bytes.extend(encode_compact_size(value.len() as u64));
+            bytes.extend(value);
+        }
+        bytes.push(0x00);
+    }
+    bytes
+}
+
+/// Removes a field that has no key data, refusing one that does.
+fn take_field(map: &mut RawMap, key_type: u8, what: &str) -> Result<Option<Vec<u8>>, String> {
+    if map.keys().any(|key| key[0] == key_type && key.len() > 1) {
+        return Err(format!("Invalid PSBT: {} key must not carry key data", what));
+    }
+    Ok(map.remove(&vec![key_type]))
+}
+
+fn required(value: Option<Vec<u8>>, what: &str) -> Result<Vec<u8>, String> {
+    value.ok_or_else(|| format!("Invalid PSBT: version 2 requires the {} field", what))
+}
+
+fn fixed<const N: usize>(value: &[u8], what: &str) -> Result<[u8; N], String> {
+    value
+        .try_into()
+        .map_err(|_| format!("Invalid PSBT: {} must be {} bytes, got {}", what, N, value.len()))
+}
+
+fn count(value: Option<Vec<u8>>, what: &str) -> Result<usize, String> {
+    let value = required(value, what)?;
+    let decoded = decode_compact_size(&value).map_err(|e| format!("Invalid PSBT: {}: {}", what, e))?;
+    if decoded.bytes_consumed != value.len() {
+        return Err(format!("Invalid PSBT: {} has trailing bytes", what));
+    }
+    Ok(decoded.value as usize)
+}
@@ -0,0 +1,95 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{KeySource, Xpub};
+use bitcoin::Psbt;
+use crate::psbt::v2::parse_versioned_psbt;
+use crate::psbt::update::parse_key_source;
+use crate::utils::json::parse_json_list;
+
+#[derive(Serialize, Deserialize)]
+pub struct GlobalXpub {
+    pub xpub: String,
+    pub fingerprint: String,
+    pub path: String,
+}
+
+/// Fills the PSBT_GLOBAL_XPUB map multisig hardware wallets use to check a PSBT
+/// against their registered wallet.
+#[wasm_bindgen]
+pub fn add_global_xpubs(psbt_base64: &str, xpubs_json: &str) -> Result<String, JsValue> {
+    let mut versioned = parse_versioned_psbt(psbt_base64).map_err(|e| JsValue::from_str(&e))?;
+    let entries: Vec<GlobalXpub> = parse_json_list(xpubs_json, "xpubs").map_err(|e| JsValue::from_str(&e))?;
+
+    for entry in &entries {
+        let (xpub, origin) = parse_global_xpub(entry).map_err(|e| JsValue::from_str(&e))?;
+        insert_xpub(&mut versioned.psbt, xpub, origin).map_err(|e| JsValue::from_str(&e))?;
+    }
+
+    Ok(versioned.to_base64())
+}
+
+pub fn global_xpubs(psbt: &Psbt) -> Vec<GlobalXpub> {
+    psbt.xpub
+        .iter()
+        .map(|(xpub, (fingerprint, path))| GlobalXpub {
+            xpub: xpub.to_string(),
+            fingerprint: fingerprint.to_string(),
+            path: format_path(path),
+        })
+        .collect()
+}
+
+pub fn format_path(path: &bitcoin::bip32::DerivationPath) -> String {
+    crate::wallet::path::format_path(path, None)
+}
+
+fn parse_global_xpub(entry: &GlobalXpub) -> Result<(Xpub, KeySource), String> {
+    let xpub = Xpub::from_str(entry.xpub.trim()).map_err(|e| format!("Invalid xpub \"{}\": {}", entry.xpub, e))?;
+    let (fingerprint, path) = parse_key_source(&entry.fingerprint, &entry.path)?;
+
+    if xpub.depth as usize != path.len() {
+        return Err(format!(
+            "xpub {} is at depth {}, but its path {} has {} steps",
+            entry.xpub,
+            xpub.depth,
+            entry.path,
+            path.len()
+        ));
+    }
+    match path.as_ref().last() {
+        Some(last) if *last != xpub.child_number => {
+            return Err(format!(
+                "xpub {} is child {}, but its path {} ends in {}",
+                entry.xpub, xpub.child_number, entry.path, last
+            ))
+        }
+        None if xpub.fingerprint() != fingerprint => {
+            return Err(format!(
+                "xpub {} is a master key with fingerprint {}, not {}",
+                entry.xpub,
+                xpub.fingerprint(),
+                fingerprint
+            ))
+        }
+        _ => {}
+    }
+
+    Ok((xpub, (fingerprint, path)))
+}
+
+fn insert_xpub(psbt: &mut Psbt, xpub: Xpub, origin: KeySource) -> Result<(), String> {
+    if let Some(existing) = psbt.xpub.get(&xpub) {
+        if *existing != origin {
+            return Err(format!(
+                "xpub {} is already present with fingerprint {} and path {}",
+                xpub,
+                existing.0,
+                format_path(&existing.1)
+            ));
+        }
+        return Ok(());
+    }
+    psbt.xpub.insert(xpub, origin);
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use bitcoin::opcodes::{Opcode, OP_0};
+use bitcoin::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::{Script, ScriptBuf};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::metrics::timed;
+
+// Largest magnitude accepted for decimal literals: a 5-byte script number, as used by CLTV/CSV.
+const MAX_DECIMAL_LITERAL: i64 = 0xffff_ffff;
+
+/// Compiles ASM to script hex. Data is written as hex, bare or after a lowercase `0x`;
+/// there is no other push notation, so the push opcode is always chosen here.
+#[wasm_bindgen]
+pub fn asm_to_script(asm: &str) -> Result<String, JsValue> {
+    timed("asm_to_script", asm.len(), || {
+        let script = parse_asm(asm).map_err(|e| JsValue::from_str(&e))?;
+        Ok(bytes_to_hex(script.as_bytes()))
+    })
+}
+
+#[wasm_bindgen]
+pub fn script_to_asm(script_hex: &str) -> Result<String, JsValue> {
+    timed("script_to_asm", script_hex.len(), || {
+        let bytes = hex_to_bytes(script_hex, HexMode::Lenient)
+            .map_err(|e| JsValue::from_str(&format!("Invalid script hex: {}", e)))?;
+        disassemble(Script::from_bytes(&bytes)).map_err(|e| JsValue::from_str(&e))
+    })
+}
+
+/// Parses opcode names (case-insensitive), decimal numbers and hex pushes into a script.
+/// Numbers become OP_0/OP_1NEGATE/OP_1..OP_16 or a minimal script-number push, and hex data
+/// gets the minimal push encoding required by standardness.
+pub fn parse_asm(asm: &str) -> Result<ScriptBuf, String> {
+    let opcodes = opcode_names();
+    let mut builder = Builder::new();
+
+    for (position, token) in asm.split_whitespace().enumerate() {
+        let upper = token.to_ascii_uppercase();
+        if upper.starts_with("OP_") {
+            let opcode = opcodes
+                .get(upper.as_str())
+                .ok_or_else(|| format!("Unknown opcode \"{}\" at token {}", token, position))?;
+            builder = builder.push_opcode(*opcode);
+        } else if let Some(number) = parse_decimal(token) {
+            if number.abs() > MAX_DECIMAL_LITERAL {
+                return Err(format!("Number {} at token {} is too large for a script number", number, position));
+            }
+            builder = builder.push_int(number);
+        } else {
+            let hex = token.strip_prefix("0x").unwrap_or(token);
+            let data = hex_to_bytes(hex, HexMode::Strict)
+                .map_err(|e| format!("Token {} (\"{}\") is not an opcode, number or hex: {}", position, token, e))?;
+            builder = push_minimal(builder, data)?;
+        }
+    }
+
+    Ok(builder.into_script())
+}
+
+/// Canonical ASM that `parse_asm` reads back to the same bytes for scripts with minimal pushes.
+pub fn disassemble(script: &Script) -> Result<String, String> {
+    let mut tokens = Vec::new();
+
+    for instruction in script.instructions() {
+        tokens.push(instruction_asm(&instruction.map_err(|e| format!("Invalid script: {}", e))?));
+    }
+
+    Ok(tokens.join(" "))
+}
+
+/// One instruction in `disassemble`'s notation.
+pub fn instruction_asm(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::PushBytes(bytes) if bytes.is_empty() => "0".to_string(),
+        Instruction::PushBytes(bytes) => {
+            let hex = bytes_to_hex(bytes.as_bytes());
+            // Digit-only hex would read back as a decimal literal.
+            if hex.bytes().all(|b| b.is_ascii_digit()) {
+                format!("0x{}", hex)
+            } else {
+                hex
+            }
+        }
+        Instruction::Op(op) => opcode_asm(*op),
+    }
+}
+
+fn opcode_asm(op: Opcode) -> String {
+    match op.to_u8() {
+        0x4f => "-1".to_string(),
+        code @ 0x51..=0x60 => (code - 0x50).to_string(),
+        0xb1 => "OP_CHECKLOCKTIMEVERIFY".to_string(),
+        0xb2 => "OP_CHECKSEQUENCEVERIFY".to_string(),
+        _ => op.to_string(),
+    }
+}
+
+fn push_minimal(builder: Builder, data: Vec<u8>) -> Result<Builder, String> {
+    match data.as_slice() {
+        [] => Ok(builder.push_opcode(OP_0)),
+        [n @ 1..=16] => Ok(builder.push_int(*n as i64)),
+        [0x81] => Ok(builder.push_int(-1)),
+        _ => {
+            let push = PushBytesBuf::try_from(data).map_err(|e| format!("Push too large: {}", e))?;
+            Ok(builder.push_slice(push))
+        }
+    }
+}
+
+fn parse_decimal(token: &str) -> Option<i64> {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    // Longer digit strings are treated as hex data (e.g. a hash that happens to have no letters).
+    if digits.is_empty() || digits.len() > 10 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn opcode_names() -> HashMap<String, Opcode> {
+    let mut names = HashMap::new();
+    for code in 0..=255u8 {
+        // Push opcodes are produced from the data tokens instead.
+        if (0x01..=0x4e).contains(&code) {
+            continue;
+        }
+        let opcode = Opcode::from(code);
+        names.insert(opcode.to_string(), opcode);
+    }
+
+    names.insert("OP_0".to_string(), OP_0);
+    names.insert("OP_FALSE".to_string(), OP_0);
+    names.insert("OP_TRUE".to_string(), Opcode::from(0x51));
+    names.insert("OP_1NEGATE".to_string(), Opcode::from(0x4f));
+    for n in 1..=16u8 {
+        names.insert(format!("OP_{}", n), Opcode::from(0x50 + n));
+    }
+    names.insert("OP_CHECKLOCKTIMEVERIFY".to_string(), Opcode::from(0xb1));
+    names.insert("OP_NOP2".to_string(), Opcode::from(0xb1));
+    names.insert("OP_CHECKSEQUENCEVERIFY".to_string(), Opcode::from(0xb2));
+    names.insert("OP_NOP3".to_string(), Opcode::from(0xb2));
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(script_hex: &str) -> String {
+        let asm = script_to_asm(script_hex).unwrap();
+        asm_to_script(&asm).unwrap()
+    }
+
+    #[test]
+    fn standard_templates_round_trip() {
+        for script_hex in [
+            // P2PKH, P2SH, P2WPKH, P2WSH and P2TR of the BIP173/BIP341 test keys.
+            "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+            "a914751e76e8199196d454941c45d1b3a323f1433bd687",
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+            // 1-of-2 bare multisig, CSV and an OP_RETURN.
+            "512102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f82102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f52ae",
+            "029000b275",
+            "6a0b68656c6c6f20776f726c64",
+            "",
+        ] {
+            assert_eq!(round_trip(script_hex), script_hex);
+        }
+    }
+
+    #[test]
+    fn digit_only_pushes_keep_their_prefix() {
+        assert_eq!(script_to_asm("029000b275").unwrap(), "0x9000 OP_CHECKSEQUENCEVERIFY OP_DROP");
+        assert_eq!(script_to_asm("0412345678").unwrap(), "0x12345678");
+        assert_eq!(asm_to_script("0x12345678").unwrap(), "0412345678");
+        assert_eq!(asm_to_script("12345678").unwrap(), "044e61bc00");
+    }
+
+    #[test]
+    fn non_minimal_pushes_come_back_minimal() {
+        // A one-byte push of 5 is OP_5 when minimal.
+        assert_eq!(script_to_asm("0105").unwrap(), "0x05");
+        assert_eq!(round_trip("0105"), "55");
+        assert_eq!(round_trip("0181"), "4f");
+        assert_eq!(round_trip("0100"), "0100");
+        // OP_PUSHDATA1 for data that fits a direct push.
+        assert_eq!(script_to_asm("4c02abcd").unwrap(), "abcd");
+        assert_eq!(round_trip("4c02abcd"), "02abcd");
+        assert_eq!(round_trip("4d0200abcd"), "02abcd");
+    }
+
+    #[test]
+    fn only_lowercase_0x_is_a_prefix() {
+        assert_eq!(parse_asm("0xabcd").unwrap().to_hex_string(), "02abcd");
+        assert_eq!(parse_asm("abcd").unwrap().to_hex_string(), "02abcd");
+        assert!(parse_asm("0Xabcd").is_err());
+        assert!(parse_asm("<abcd>").is_err());
+        assert!(parse_asm("[abcd]").is_err());
+        assert!(parse_asm("OP_PUSHBYTES_2 abcd").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::secp256k1::{ecdsa, schnorr, Message, PublicKey, Secp256k1, VerifyOnly, XOnlyPublicKey};
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::metrics::timed;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchItemInput {
+    /// `ecdsa` or `schnorr`.
+    scheme: String,
+    pubkey: String,
+    #[serde(alias = "message_hash")]
+    message_hash: String,
+    signature: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub scheme: String,
+    pub valid: bool,
+    /// Why the item failed when it couldn't be checked at all, such as a key off the curve.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchVerification {
+    /// True when every item verifies.
+    pub valid: bool,
+    pub count: usize,
+    /// How many of the items were Schnorr signatures checked as one batch.
+    pub batched: usize,
+    /// Indices of the items that don't verify, in order.
+    pub failed: Vec<usize>,
+    pub results: Vec<BatchItemResult>,
+}
+
+/// A signature ready to check: the key, the 32-byte digest it signs and the signature.
+pub enum BatchItem {
+    Ecdsa(PublicKey, Message, ecdsa::Signature),
+    Schnorr(XOnlyPublicKey, Message, schnorr::Signature),
+}
+
+/// Signatures collected for checking in one go, as a block or a whole transaction's
+/// inputs produce them. `verify` checks the Schnorr ones as a batch and only goes item by
+/// item to find the culprits when the batch as a whole fails; ECDSA has no batch form and
+/// is always checked per item.
+///
+/// libsecp256k1's batch verifier isn't part of the API rust-secp256k1 0.29 exposes, so
+/// the Schnorr batch is the per-item check run once over the batch for now. Callers queue
+/// through here so a real batch verifier can take its place without touching them.
+pub struct SignatureBatch {
+    secp: Secp256k1<VerifyOnly>,
+    items: Vec<BatchItem>,
+}
+
+impl Default for SignatureBatch {
+    fn default() -> SignatureBatch {
+        SignatureBatch { secp: Secp256k1::verification_only(), items: Vec::new() }
+    }
+}
+
+impl SignatureBatch {
+    pub fn push(&mut self, item: BatchItem) {
+        self.items.push(item);
+    }
+
+    pub fn schnorr_count(&self) -> usize {
+        self.items.iter().filter(|item| matches!(item, BatchItem::Schnorr(..))).count()
+    }
+
+    /// `Ok` when every item verifies, otherwise the indices of the ones that don't.
+    pub fn verify(&self) -> Result<(), Vec<usize>> {
+        let schnorr_holds = self.verify_schnorr_batch();
+        let failed: Vec<usize> = (0..self.items.len())
+            .filter(|&index| match self.items[index] {
+                BatchItem::Ecdsa(..) => !self.verify_item(index),
+                BatchItem::Schnorr(..) => !schnorr_holds && !self.verify_item(index),
+            })
+            .collect();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+
+    fn verify_schnorr_batch(&self) -> bool {
+        self.items.iter().all(|item| match item {
+            BatchItem::Schnorr(key, digest, signature) => self.secp.verify_schnorr(signature, digest, key).is_ok(),
+            BatchItem::Ecdsa(..) => true,
+        })
+    }
+
+    fn verify_item(&self, index: usize) -> bool {
+        match &self.items[index] {
+            BatchItem::Ecdsa(key, digest, signature) => self.secp.verify_ecdsa(digest, signature, key).is_ok(),
+            BatchItem::Schnorr(key, digest, signature) => self.secp.verify_schnorr(signature, digest, key).is_ok(),
+        }
+    }
+}
+
+/// Checks many signatures against their message hashes at once, for block-level work.
+/// `items_json` is an array of `{scheme, pubkey, message_hash, signature}`: `ecdsa` takes
+/// a 33- or 65-byte key and a DER or 64-byte compact signature, `schnorr` a 32-byte x-only
+/// key and a 64-byte BIP340 signature. ECDSA signatures are normalized to low S first, as
+/// consensus accepts either. Schnorr signatures are checked as a batch, and per item only
+/// when the batch fails, to say which ones are bad.
+#[wasm_bindgen]
+pub fn batch_verify(items_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("batch_verify", items_json.len(), || {
+        let inputs: Vec<BatchItemInput> = parse_json_list(items_json, "items").map_err(|e| JsValue::from_str(&e))?;
+        let mut batch = SignatureBatch::default();
+        let mut results = Vec::with_capacity(inputs.len());
+        // Items that can't be checked at all fail outright and stay out of the batch.
+        let mut positions = Vec::with_capacity(inputs.len());
+        for (index, input) in inputs.iter().enumerate() {
+            let scheme = input.scheme.trim().to_ascii_lowercase();
+            let item = parse_item(&scheme, input).map_err(|e| JsValue::from_str(&format!("items[{}]: {}", index, e)))?;
+            let error = match item {
+                Ok(item) => {
+                    batch.push(item);
+                    positions.push(index);
+                    None
+                }
+                Err(reason) => Some(reason),
+            };
+            results.push(BatchItemResult { index, scheme, valid: error.is_none(), error });
+        }
+
+        if let Err(failed) = batch.verify() {
+            for position in failed {
+                results[positions[position]].valid = false;
+            }
+        }
+        let result = BatchVerification {
+            valid: results.iter().all(|result| result.valid),
+            count: results.len(),
+            batched: batch.schnorr_count(),
+            failed: results.iter().filter(|result| !result.valid).map(|result| result.index).collect(),
+            results,
+        };
+        to_json(&result, compact)
+    })
+}
+
+/// The outer error is a malformed item; the inner one an item that is well-formed but
+/// can't verify, such as a key that isn't on the curve.
+fn parse_item(scheme: &str, input: &BatchItemInput) -> Result<Result<BatchItem, String>, String> {
+    let pubkey = hex_to_bytes(&input.pubkey, HexMode::Lenient).map_err(|e| format!("Invalid pubkey hex: {}", e))?;
+    let hash = hex_to_bytes(&input.message_hash, HexMode::Lenient).map_err(|e| format!("Invalid message_hash hex: {}", e))?;
+    let signature = hex_to_bytes(&input.signature, HexMode::Lenient).map_err(|e| format!("Invalid signature hex: {}", e))?;
+    let digest: [u8; 32] = hash
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("message_hash must be 32 bytes, got {}", hash.len()))?;
+    let digest = Message::from_digest(digest);
+
+    match scheme {
+        "ecdsa" => {
+            if pubkey.len() != 33 && pubkey.len() != 65 {
+                return Err(format!("ECDSA keys are 33 or 65 bytes, got {}", pubkey.len()));
+            }
+            let Ok(key) = PublicKey::from_slice(&pubkey) else {
+                return Ok(Err("not a valid public key".to_string()));
+            };
+            let parsed = if signature.len() == 64 {
+                ecdsa::Signature::from_compact(&signature)
+            } else {
+                ecdsa::Signature::from_der(&signature)
+            };
+            let Ok(mut parsed) = parsed else {
+                return Ok(Err("signature is neither strict DER nor 64-byte compact".to_string()));
+            };
+            parsed.normalize_s();
+            Ok(Ok(BatchItem::Ecdsa(key, digest, parsed)))
+        }
+        "schnorr" => {
+            if pubkey.len() != 32 {
+                return Err(format!("Schnorr keys are 32-byte x-only keys, got {} bytes", pubkey.len()));
+            }
+            if signature.len() != 64 {
+                return Err(format!("BIP340 signatures are 64 bytes, got {}", signature.len()));
+            }
+            let Ok(key) = XOnlyPublicKey::from_slice(&pubkey) else {
+                return Ok(Err("not a valid x-only public key".to_string()));
+            };
+            let Ok(parsed) = schnorr::Signature::from_slice(&signature) else {
+                return Ok(Err("not a valid schnorr signature".to_string()));
+            };
+            Ok(Ok(BatchItem::Schnorr(key, digest, parsed)))
+        }
+        other => Err(format!("Unknown scheme {:?}: expected ecdsa or schnorr", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::{Keypair, SecretKey};
+
+    /// `count` signatures over distinct digests with distinct keys, alternating Schnorr and ECDSA.
+    fn signed_items(count: usize) -> Vec<BatchItem> {
+        let secp = Secp256k1::new();
+        (0..count)
+            .map(|index| {
+                let secret = SecretKey::from_slice(sha256::Hash::hash(format!("key {}", index).as_bytes()).as_byte_array()).unwrap();
+                let digest = Message::from_digest(sha256::Hash::hash(format!("message {}", index).as_bytes()).to_byte_array());
+                if index % 2 == 0 {
+                    let keypair = Keypair::from_secret_key(&secp, &secret);
+                    BatchItem::Schnorr(keypair.x_only_public_key().0, digest, secp.sign_schnorr_no_aux_rand(&digest, &keypair))
+                } else {
+                    BatchItem::Ecdsa(secret.public_key(&secp), digest, secp.sign_ecdsa(&digest, &secret))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batch_reports_the_items_that_fail() {
+        let mut items = signed_items(6);
+        // Swap two Schnorr signatures and spoil one ECDSA digest.
+        if let [BatchItem::Schnorr(_, _, first), _, BatchItem::Schnorr(_, _, second), ..] = &mut items[..] {
+            std::mem::swap(first, second);
+        }
+        if let BatchItem::Ecdsa(_, digest, _) = &mut items[5] {
+            *digest = Message::from_digest([1; 32]);
+        }
+        let mut batch = SignatureBatch::default();
+        for item in items {
+            batch.push(item);
+        }
+        assert_eq!(batch.schnorr_count(), 3);
+        assert_eq!(batch.verify(), Err(vec![0, 2, 5]));
+
+        let mut batch = SignatureBatch::default();
+        for item in signed_items(6) {
+            batch.push(item);
+        }
+        assert_eq!(batch.verify(), Ok(()));
+    }
+
+    /// 1,000 signatures checked one at a time, first with a new verification context per
+    /// signature as a naive caller would, then with one shared context, against
+    /// `SignatureBatch`. Until rust-secp256k1 exposes libsecp256k1's batch verifier, all
+    /// three do the same work per signature and come out close; this is the baseline a
+    /// real batch verifier has to beat.
+    #[test]
+    #[ignore = "benchmark; run with --release -- --ignored --nocapture"]
+    fn benchmark_verifying_1000_signatures() {
+        const SIGNATURES: usize = 1_000;
+        let items = signed_items(SIGNATURES);
+        let check = |secp: &Secp256k1<VerifyOnly>, item: &BatchItem| match item {
+            BatchItem::Ecdsa(key, digest, signature) => secp.verify_ecdsa(digest, signature, key).is_ok(),
+            BatchItem::Schnorr(key, digest, signature) => secp.verify_schnorr(signature, digest, key).is_ok(),
+        };
+
+        let start = Instant::now();
+        assert!(items.iter().all(|item| check(&Secp256k1::verification_only(), item)));
+        let fresh_contexts = start.elapsed();
+
+        let secp = Secp256k1::verification_only();
+        let start = Instant::now();
+        assert!(items.iter().all(|item| check(&secp, item)));
+        let one_context = start.elapsed();
+
+        let mut batch = SignatureBatch::default();
+        for item in items {
+            batch.push(item);
+        }
+        let start = Instant::now();
+        assert_eq!(batch.verify(), Ok(()));
+        let batched = start.elapsed();
+
+        println!(
+            "{} signatures: a context each {:?}, one context {:?}, SignatureBatch {:?}",
+            SIGNATURES, fresh_contexts, one_context, batched
+        );
+    }
+
+    /// The same comparison through the interpreter: a 200-input taproot transaction checked
+    /// with `verify_input` per input, against `verify_inputs`, which batches the signatures.
+    #[cfg(feature = "signing")]
+    #[test]
+    #[ignore = "benchmark; run with --release -- --ignored --nocapture"]
+    fn benchmark_verifying_a_200_input_taproot_transaction() {
+        use bitcoin::absolute::LockTime;
+        use bitcoin::transaction::Version;
+        use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid};
+        use crate::script::interpreter::{verify_input, verify_inputs};
+        use crate::transaction::parse_tx_hex;
+        use crate::transaction::sign::{sign_all_inputs, SignAllResult};
+
+        const INPUTS: usize = 200;
+        const KEY: &str = "0505050505050505050505050505050505050505050505050505050505050505";
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_str(&secp, KEY).unwrap();
+        let prevout = TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new_p2tr(&secp, keypair.x_only_public_key().0, None) };
+        let prevouts = vec![prevout.clone(); INPUTS];
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: (0..INPUTS as u32)
+                .map(|vout| TxIn { previous_output: OutPoint::new(Txid::from_byte_array([8; 32]), vout), ..Default::default() })
+                .collect(),
+            output: vec![TxOut { value: Amount::from_sat(1_000_000), script_pubkey: prevout.script_pubkey.clone() }],
+        };
+        let prevouts_json = serde_json::json!(vec![serde_json::json!({"amountSat": 10_000, "scriptPubkey": prevout.script_pubkey.to_hex_string()}); INPUTS]).to_string();
+        let signed: SignAllResult = serde_json::from_str(&sign_all_inputs(&bitcoin::consensus::encode::serialize_hex(&tx), KEY, &prevouts_json, None).unwrap()).unwrap();
+        let tx = parse_tx_hex(&signed.tx_hex).unwrap();
+
+        let start = Instant::now();
+        assert!((0..INPUTS).all(|index| verify_input(&tx, index, &prevouts).is_ok()));
+        let per_input = start.elapsed();
+
+        let start = Instant::now();
+        assert!(verify_inputs(&tx, &prevouts).is_ok());
+        let batched = start.elapsed();
+
+        println!("{} taproot inputs: verify_input each {:?}, verify_inputs {:?}", INPUTS, per_input, batched);
+    }
+}
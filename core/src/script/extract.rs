@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::opcodes::all::*;
+use bitcoin::opcodes::Opcode;
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::{PublicKey, Script};
+use crate::script::{output_script_type, parse_multisig};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+#[derive(Serialize, Deserialize)]
+pub struct ScriptItems {
+    /// `output_script_type`'s name, or multisig / multi_a for those templates.
+    pub template: String,
+    /// Signatures required, for multisig and multi_a.
+    pub threshold: Option<usize>,
+    pub items: Vec<ScriptItem>,
+    pub trailing: Option<TrailingBytes>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScriptItem {
+    /// Instruction number and byte offset of the push.
+    pub index: usize,
+    pub offset: usize,
+    /// pubkey, xonly_pubkey, pubkey_hash, script_hash, hash or data.
+    pub kind: String,
+    pub hex: String,
+    /// Keys only: whether the bytes are a point on the curve.
+    pub valid: Option<bool>,
+    pub role: Option<String>,
+}
+
+/// Bytes from the first instruction that doesn't parse to the end of the script.
+#[derive(Serialize, Deserialize)]
+pub struct TrailingBytes {
+    pub offset: usize,
+    pub hex: String,
+    pub error: String,
+}
+
+/// Lists the keys, key and script hashes, hash-lock digests and other data pushed by a
+/// script, with their role when the script follows a known template or the opcodes
+/// around a push say what it is. Bytes that stop parsing are reported in `trailing`
+/// with everything before them still listed.
+#[wasm_bindgen]
+pub fn extract_script_items(script_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(script_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid script hex: {}", e)))?;
+    let extracted = extract_items(Script::from_bytes(&bytes));
+
+    to_json(&extracted, compact)
+}
+
+pub fn extract_items(script: &Script) -> ScriptItems {
+    let mut instructions = Vec::new();
+    let mut trailing = None;
+    let mut iter = script.instructions();
+    loop {
+        let offset = script.len() - iter.as_script().len();
+        match iter.next() {
+            Some(Ok(instruction)) => instructions.push((offset, instruction)),
+            Some(Err(e)) => {
+                trailing = Some(TrailingBytes { offset, hex: bytes_to_hex(&script.as_bytes()[offset..]), error: e.to_string() });
+                break;
+            }
+            None => break,
+        }
+    }
+
+    let (template, threshold, template_roles) = template_roles(script, &instructions);
+    let opcode_at = |index: usize| match instructions.get(index) {
+        Some((_, Instruction::Op(op))) => Some(*op),
+        _ => None,
+    };
+
+    let mut items = Vec::new();
+    for (index, (offset, instruction)) in instructions.iter().enumerate() {
+        let Instruction::PushBytes(push) = instruction else { continue };
+        let data = push.as_bytes();
+        if data.is_empty() {
+            continue;
+        }
+        let before = index.checked_sub(1).and_then(opcode_at);
+        let two_before = index.checked_sub(2).and_then(opcode_at);
+        let after = next_executed(&instructions, index + 1);
+        let template_role = template_roles.iter().find(|(position, _, _)| *position == index);
+
+        let (kind, role) = if let Some((_, kind, role)) = template_role {
+            (*kind, Some(role.clone()))
+        } else if is_pubkey_shape(data) {
+            let role = matches!(after, Some(OP_CHECKSIG | OP_CHECKSIGVERIFY)).then(|| "checksig key".to_string());
+            ("pubkey", role)
+        } else if data.len() == 32 && matches!(after, Some(OP_CHECKSIG | OP_CHECKSIGVERIFY | OP_CHECKSIGADD)) {
+            ("xonly_pubkey", Some("tapscript checksig key".to_string()))
+        } else if let Some((kind, role)) = hash_role(data.len(), before, two_before) {
+            (kind, Some(role.to_string()))
+        } else {
+            let role = match after {
+                Some(OP_CLTV) => Some("absolute locktime".to_string()),
+                Some(OP_CSV) => Some("relative locktime".to_string()),
+                _ => None,
+            };
+            ("data", role)
+        };
+
+        let valid = match kind {
+            "pubkey" => Some(PublicKey::from_slice(data).is_ok()),
+            "xonly_pubkey" => Some(XOnlyPublicKey::from_slice(data).is_ok()),
+            _ => None,
+        };
+        items.push(ScriptItem { index, offset: *offset, kind: kind.to_string(), hex: bytes_to_hex(data), valid, role });
+    }
+
+    ScriptItems { template, threshold, items, trailing }
+}
+
+type TemplateRoles = Vec<(usize, &'static str, String)>;
+
+/// The template a script follows, and the instruction index, kind and role of each
+/// push the template gives a meaning.
+fn template_roles(script: &Script, instructions: &[(usize, Instruction)]) -> (String, Option<usize>, TemplateRoles) {
+    let push_positions: Vec<usize> = instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, instruction))| matches!(instruction, Instruction::PushBytes(push) if !push.is_empty()))
+        .map(|(index, _)| index)
+        .collect();
+    let only = |kind: &'static str, role: &str| push_positions.first().map(|&position| vec![(position, kind, role.to_string())]).unwrap_or_default();
+
+    let template = output_script_type(script);
+    let roles = match template {
+        "p2pkh" => only("pubkey_hash", "p2pkh pubkey hash"),
+        "p2sh" => only("script_hash", "p2sh script hash"),
+        "p2wpkh" => only("pubkey_hash", "p2wpkh pubkey hash"),
+        "p2wsh" => only("script_hash", "p2wsh script hash"),
+        "p2tr" => only("xonly_pubkey", "taproot output key"),
+        "p2pk" => only("pubkey", "p2pk key"),
+        "op_return" => push_positions.iter().map(|&position| (position, "data", "op_return payload".to_string())).collect(),
+        _ => Vec::new(),
+    };
+    if template != "nonstandard" {
+        return (template.to_string(), None, roles);
+    }
+
+    if let Some(multisig) = parse_multisig(script) {
+        let total = multisig.pubkeys.len();
+        let roles = push_positions
+            .iter()
+            .enumerate()
+            .map(|(n, &position)| (position, "pubkey", format!("multisig key {} of {}", n + 1, total)))
+            .collect();
+        return ("multisig".to_string(), Some(multisig.threshold), roles);
+    }
+    if let Some((threshold, positions)) = parse_multi_a(instructions) {
+        let total = positions.len();
+        let roles = positions
+            .iter()
+            .enumerate()
+            .map(|(n, &position)| (position, "xonly_pubkey", format!("multi_a key {} of {}", n + 1, total)))
+            .collect();
+        return ("multi_a".to_string(), Some(threshold), roles);
+    }
+    ("nonstandard".to_string(), None, Vec::new())
+}
+
+/// `<key> CHECKSIG <key> CHECKSIGADD ... <k> NUMEQUAL`: the threshold and the key positions.
+fn parse_multi_a(instructions: &[(usize, Instruction)]) -> Option<(usize, Vec<usize>)> {
+    let (last, rest) = instructions.split_last()?;
+    if !matches!(last.1, Instruction::Op(OP_NUMEQUAL)) {
+        return None;
+    }
+    let (threshold, body) = rest.split_last()?;
+    let threshold = usize::try_from(threshold.1.script_num()?).ok()?;
+    if body.is_empty() || body.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut positions = Vec::new();
+    for (pair, chunk) in body.chunks(2).enumerate() {
+        let expected = if pair == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+        match (&chunk[0].1, &chunk[1].1) {
+            (Instruction::PushBytes(key), Instruction::Op(op)) if key.len() == 32 && *op == expected => positions.push(pair * 2),
+            _ => return None,
+        }
+    }
+    (threshold >= 1 && threshold <= positions.len()).then_some((threshold, positions))
+}
+
+/// The opcode that runs after a push at `index - 1`, stepping out of an IF branch:
+/// `<key> ELSE ... ENDIF CHECKSIG` checks the key.
+fn next_executed(instructions: &[(usize, Instruction)], mut index: usize) -> Option<Opcode> {
+    loop {
+        match instructions.get(index)?.1 {
+            Instruction::Op(OP_ENDIF) => index += 1,
+            Instruction::Op(OP_ELSE) => {
+                let mut depth = 0;
+                loop {
+                    index += 1;
+                    match instructions.get(index)?.1 {
+                        Instruction::Op(OP_IF | OP_NOTIF) => depth += 1,
+                        Instruction::Op(OP_ENDIF) if depth == 0 => break,
+                        Instruction::Op(OP_ENDIF) => depth -= 1,
+                        _ => {}
+                    }
+                }
+                index += 1;
+            }
+            Instruction::Op(op) => return Some(op),
+            Instruction::PushBytes(_) => return None,
+        }
+    }
+}
+
+fn is_pubkey_shape(data: &[u8]) -> bool {
+    match data.len() {
+        33 => matches!(data[0], 0x02 | 0x03),
+        65 => matches!(data[0], 0x04 | 0x06 | 0x07),
+        _ => false,
+    }
+}
+
+/// What a push is from the hashing opcode before it: `OP_DUP OP_HASH160 <20>` is a key
+/// hash, the other hash opcodes make it a hash-lock digest.
+fn hash_role(len: usize, before: Option<Opcode>, two_before: Option<Opcode>) -> Option<(&'static str, &'static str)> {
+    match (len, before?) {
+        (20, OP_HASH160) if two_before == Some(OP_DUP) => Some(("pubkey_hash", "pubkey hash checked against a provided key")),
+        (20, OP_HASH160) => Some(("hash", "hash160 preimage lock")),
+        (20, OP_RIPEMD160) => Some(("hash", "ripemd160 preimage lock")),
+        (32, OP_SHA256) => Some(("hash", "sha256 preimage lock")),
+        (32, OP_HASH256) => Some(("hash", "hash256 preimage lock")),
+        _ => None,
+    }
+}
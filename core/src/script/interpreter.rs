@@ -0,0 +1,1355 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash};
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::{ecdsa, schnorr, Message, Secp256k1, VerifyOnly, XOnlyPublicKey};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash};
+use bitcoin::{Amount, PublicKey, Script, ScriptBuf, Transaction, TxOut, Witness};
+use crate::script::asm::{instruction_asm, parse_asm};
+use crate::script::batch::{BatchItem, SignatureBatch};
+use crate::script::stats::{MAX_OPS_PER_SCRIPT, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE};
+use crate::transaction::decode_tx_hex;
+use crate::transaction::prevouts::Prevout;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+/// Consensus: stack plus altstack size.
+const MAX_STACK_SIZE: usize = 1000;
+const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+/// Lock times below this are block heights, above it unix times.
+const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+const SEQUENCE_DISABLE_FLAG: i64 = 1 << 31;
+const SEQUENCE_TYPE_FLAG: i64 = 1 << 22;
+const SEQUENCE_MASK: i64 = 0x0000_ffff;
+/// BIP342: tapscript's signature budget, per signature and on top of the witness size.
+const VALIDATION_WEIGHT_PER_SIGOP: i64 = 50;
+const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+/// Keeps a runaway tapscript from producing an unbounded trace.
+const MAX_TRACE_STEPS: usize = 20_000;
+
+#[derive(Serialize, Deserialize)]
+pub struct TraceStep {
+    /// `script_sig`, `script_pubkey`, `redeem_script`, `p2wpkh`, `witness_script`,
+    /// `tapscript` or `taproot_key_path`.
+    pub phase: String,
+    /// Instruction index within the phase's script.
+    pub position: usize,
+    pub opcode: String,
+    /// False for instructions skipped inside an untaken IF branch.
+    pub executed: bool,
+    pub stack: Vec<String>,
+    pub altstack: Vec<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScriptFailure {
+    pub phase: String,
+    pub position: Option<usize>,
+    pub opcode: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptExecution {
+    pub success: bool,
+    /// `checked` with a transaction context, otherwise `stubbed`.
+    pub signatures: String,
+    pub steps: Vec<TraceStep>,
+    #[serde(alias = "final_stack")]
+    pub final_stack: Vec<String>,
+    pub failure: Option<ScriptFailure>,
+}
+
+/// One signature checked against a transaction, with the digest it had to sign.
+pub struct SignatureCheck {
+    /// `ecdsa_legacy`, `ecdsa_segwit_v0`, `schnorr_key_path` or `schnorr_tapscript`.
+    pub algorithm: &'static str,
+    /// The sighash byte: the signature's last byte, or 0 for a 64-byte Schnorr signature.
+    pub sighash_flag: u8,
+    pub digest: [u8; 32],
+    pub signature: Vec<u8>,
+    pub pubkey: Vec<u8>,
+    pub valid: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpendInput {
+    #[serde(default, alias = "script_sig")]
+    script_sig: Option<String>,
+    #[serde(default)]
+    witness: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TxContextInput {
+    #[serde(alias = "tx_hex")]
+    tx_hex: String,
+    #[serde(alias = "input_index")]
+    input_index: usize,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    amount: u64,
+    #[serde(default)]
+    prevouts: Option<Vec<Prevout>>,
+}
+
+struct TxContext {
+    tx: Transaction,
+    input_index: usize,
+    amount: u64,
+    /// Every input's prevout, for taproot sighashes.
+    prevouts: Option<Vec<TxOut>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SigVersion {
+    Base,
+    WitnessV0,
+    Tapscript,
+}
+
+/// Where a failure happened; `position` and `opcode` are filled in for opcode failures.
+struct Failure {
+    phase: &'static str,
+    position: Option<usize>,
+    opcode: Option<String>,
+    reason: String,
+}
+
+impl From<Failure> for ScriptFailure {
+    fn from(failure: Failure) -> ScriptFailure {
+        ScriptFailure {
+            phase: failure.phase.to_string(),
+            position: failure.position,
+            opcode: failure.opcode,
+            reason: failure.reason,
+        }
+    }
+}
+
+impl Failure {
+    fn new(phase: &'static str, reason: impl Into<String>) -> Failure {
+        Failure { phase, position: None, opcode: None, reason: reason.into() }
+    }
+}
+
+/// Runs a spend through the script engine and traces every opcode with the stack and
+/// altstack after it. `script_sig_or_witness_json` is a scriptSig (hex or ASM), a JSON
+/// array of witness items (hex), or `{script_sig, witness}`. With `tx_context_json`
+/// (`{tx_hex, input_index, amount, prevouts?}`) signatures and timelocks are checked
+/// against the transaction; without it they're assumed to pass and noted as such.
+#[wasm_bindgen]
+pub fn execute_script(
+    script_sig_or_witness_json: &str,
+    script_pubkey_hex: &str,
+    tx_context_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    timed("execute_script", script_sig_or_witness_json.len() + script_pubkey_hex.len(), || {
+        let (script_sig, witness) = parse_spend(script_sig_or_witness_json).map_err(|e| JsValue::from_str(&e))?;
+        let script_pubkey = parse_script(script_pubkey_hex, "script pubkey").map_err(|e| JsValue::from_str(&e))?;
+        let context = match tx_context_json {
+            Some(json) if !json.trim().is_empty() => Some(parse_context(&json)?),
+            _ => None,
+        };
+
+        let mut interpreter = Interpreter::new(context.as_ref(), true);
+        let mut stack = Vec::new();
+        let outcome = interpreter.verify(&script_sig, &script_pubkey, &witness, &mut stack);
+        let execution = ScriptExecution {
+            success: outcome.is_ok(),
+            signatures: if context.is_some() { "checked" } else { "stubbed" }.to_string(),
+            steps: interpreter.steps,
+            final_stack: stack.iter().map(|item| bytes_to_hex(item)).collect(),
+            failure: outcome.err().map(ScriptFailure::from),
+        };
+
+        to_json(&execution, compact)
+    })
+}
+
+/// Checks an input's scriptSig and witness against the output it spends, without a
+/// trace. `prevouts` holds every input's spent output.
+pub fn verify_input(tx: &Transaction, input_index: usize, prevouts: &[TxOut]) -> Result<(), ScriptFailure> {
+    let input = &tx.input[input_index];
+    let spent = &prevouts[input_index];
+    let context = TxContext {
+        tx: tx.clone(),
+        input_index,
+        amount: spent.value.to_sat(),
+        prevouts: Some(prevouts.to_vec()),
+    };
+    let mut interpreter = Interpreter::new(Some(&context), false);
+    interpreter
+        .verify(&input.script_sig, &spent.script_pubkey, &input.witness, &mut Vec::new())
+        .map_err(ScriptFailure::from)
+}
+
+/// `verify_input` for every input, with their Schnorr signatures checked as one batch at
+/// the end. Fails with the first input, in order, whose spend doesn't hold, and the same
+/// failure `verify_input` gives for it.
+pub fn verify_inputs(tx: &Transaction, prevouts: &[TxOut]) -> Result<(), (usize, ScriptFailure)> {
+    let mut batch = SignatureBatch::default();
+    let mut owners = Vec::new();
+    let mut first_failure = None;
+    for index in 0..tx.input.len() {
+        let input = &tx.input[index];
+        let spent = &prevouts[index];
+        let context = TxContext {
+            tx: tx.clone(),
+            input_index: index,
+            amount: spent.value.to_sat(),
+            prevouts: Some(prevouts.to_vec()),
+        };
+        let mut interpreter = Interpreter::new(Some(&context), false);
+        interpreter.deferred = Some(Vec::new());
+        let outcome = interpreter.verify(&input.script_sig, &spent.script_pubkey, &input.witness, &mut Vec::new());
+        for item in interpreter.deferred.take().unwrap_or_default() {
+            batch.push(item);
+            owners.push(index);
+        }
+        if outcome.is_err() {
+            first_failure = Some(index);
+            break;
+        }
+    }
+
+    // Inputs before the first bad signature's ran with every signature really valid, so
+    // the earlier of the two is the first input that fails. Rerunning it undeferred
+    // gives the exact failure.
+    let bad_signature = batch.verify().err().and_then(|failed| failed.first().map(|&position| owners[position]));
+    let Some(failing) = [first_failure, bad_signature].into_iter().flatten().min() else {
+        return Ok(());
+    };
+    verify_input(tx, failing, prevouts).map_err(|failure| (failing, failure))
+}
+
+/// `verify_input`, also returning every signature check the spend made. For multisig
+/// only the signatures that matched a key are kept, unless the check failed.
+pub fn signature_checks(tx: &Transaction, input_index: usize, prevouts: &[TxOut]) -> (Vec<SignatureCheck>, Result<(), ScriptFailure>) {
+    let input = &tx.input[input_index];
+    let spent = &prevouts[input_index];
+    let context = TxContext {
+        tx: tx.clone(),
+        input_index,
+        amount: spent.value.to_sat(),
+        prevouts: Some(prevouts.to_vec()),
+    };
+    let mut interpreter = Interpreter::new(Some(&context), false);
+    let outcome = interpreter.verify(&input.script_sig, &spent.script_pubkey, &input.witness, &mut Vec::new());
+    (interpreter.checks, outcome.map_err(ScriptFailure::from))
+}
+
+fn parse_spend(input: &str) -> Result<(ScriptBuf, Witness), String> {
+    let input = input.trim();
+    let spend = if input.starts_with('[') {
+        let witness: Vec<String> = serde_json::from_str(input).map_err(|e| format!("Invalid witness JSON: {}", e))?;
+        SpendInput { script_sig: None, witness: Some(witness) }
+    } else if input.starts_with('{') {
+        serde_json::from_str(input).map_err(|e| format!("Invalid spend JSON: {}", e))?
+    } else {
+        SpendInput { script_sig: Some(input.to_string()), witness: None }
+    };
+
+    let script_sig = match spend.script_sig.as_deref() {
+        Some(script_sig) => parse_script(script_sig, "scriptSig")?,
+        None => ScriptBuf::new(),
+    };
+    let items = spend
+        .witness
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, item)| hex_to_bytes(item, HexMode::Lenient).map_err(|e| format!("Invalid witness item {}: {}", index, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((script_sig, Witness::from_slice(&items)))
+}
+
+/// Hex, or ASM as `asm_to_script` reads it.
+fn parse_script(input: &str, what: &str) -> Result<ScriptBuf, String> {
+    let input = input.trim();
+    if let Ok(bytes) = hex_to_bytes(input, HexMode::Strict) {
+        return Ok(ScriptBuf::from_bytes(bytes));
+    }
+    parse_asm(input).map_err(|e| format!("Invalid {} (expected hex or ASM): {}", what, e))
+}
+
+fn parse_context(json: &str) -> Result<TxContext, JsValue> {
+    let input: TxContextInput =
+        serde_json::from_str(json).map_err(|e| JsValue::from_str(&format!("Invalid transaction context JSON: {}", e)))?;
+    let tx = decode_tx_hex(&input.tx_hex)?;
+    if input.input_index >= tx.input.len() {
+        return Err(JsValue::from_str(&format!(
+            "input index {} out of range (transaction has {} inputs)",
+            input.input_index,
+            tx.input.len()
+        )));
+    }
+    let prevouts = match input.prevouts {
+        Some(prevouts) if prevouts.len() != tx.input.len() => {
+            return Err(JsValue::from_str(&format!(
+                "Expected {} prevouts (one per input), got {}",
+                tx.input.len(),
+                prevouts.len()
+            )))
+        }
+        Some(prevouts) => Some(
+            prevouts
+                .iter()
+                .enumerate()
+                .map(|(index, prevout)| {
+                    ScriptBuf::from_hex(&prevout.script_pubkey)
+                        .map(|script_pubkey| TxOut { value: Amount::from_sat(prevout.amount), script_pubkey })
+                        .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey for prevout {}: {}", index, e)))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+    Ok(TxContext { tx, input_index: input.input_index, amount: input.amount, prevouts })
+}
+
+struct Interpreter<'a> {
+    context: Option<&'a TxContext>,
+    cache: Option<SighashCache<&'a Transaction>>,
+    secp: Secp256k1<VerifyOnly>,
+    /// Off when only the verdict is wanted.
+    trace: bool,
+    steps: Vec<TraceStep>,
+    /// The scriptPubKey being spent, for taproot sighashes of single-input transactions.
+    script_pubkey: ScriptBuf,
+    /// BIP342 validation weight left for the tapscript being run.
+    validation_weight: i64,
+    /// Signatures checked against the transaction context, in order.
+    checks: Vec<SignatureCheck>,
+    /// When set, Schnorr signatures are queued here and assumed valid until the batch is
+    /// verified. A bad one fails its script either way, so the verdict can wait.
+    deferred: Option<Vec<BatchItem>>,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(context: Option<&'a TxContext>, trace: bool) -> Interpreter<'a> {
+        Interpreter {
+            context,
+            cache: context.map(|context| SighashCache::new(&context.tx)),
+            secp: Secp256k1::verification_only(),
+            trace,
+            steps: Vec::new(),
+            script_pubkey: ScriptBuf::new(),
+            validation_weight: 0,
+            checks: Vec::new(),
+            deferred: None,
+        }
+    }
+
+    /// Core's VerifyScript with the consensus flags (P2SH, DERSIG, CLTV, CSV, NULLDUMMY,
+    /// segwit, taproot). Policy-only rules such as CLEANSTACK for legacy scripts aren't applied.
+    fn verify(
+        &mut self,
+        script_sig: &Script,
+        script_pubkey: &Script,
+        witness: &Witness,
+        stack: &mut Vec<Vec<u8>>,
+    ) -> Result<(), Failure> {
+        self.script_pubkey = script_pubkey.to_owned();
+        self.run(script_sig, "script_sig", SigVersion::Base, None, stack)?;
+        let after_script_sig = stack.clone();
+        self.run(script_pubkey, "script_pubkey", SigVersion::Base, None, stack)?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err(Failure::new("script_pubkey", "script evaluated to false"));
+        }
+
+        let mut witness_used = false;
+        if let Some((version, program)) = witness_program(script_pubkey) {
+            if !script_sig.is_empty() {
+                return Err(Failure::new("script_sig", "a native segwit spend must have an empty scriptSig"));
+            }
+            witness_used = true;
+            self.verify_witness_program(witness, version, &program, false, stack)?;
+        } else if script_pubkey.is_p2sh() {
+            if !script_sig.is_push_only() {
+                return Err(Failure::new("script_sig", "a P2SH scriptSig may only push data"));
+            }
+            *stack = after_script_sig;
+            let redeem_script = ScriptBuf::from_bytes(
+                stack.pop().ok_or_else(|| Failure::new("script_sig", "P2SH scriptSig doesn't push a redeem script"))?,
+            );
+            self.run(&redeem_script, "redeem_script", SigVersion::Base, None, stack)?;
+            if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+                return Err(Failure::new("redeem_script", "redeem script evaluated to false"));
+            }
+            if let Some((version, program)) = witness_program(&redeem_script) {
+                let single_push = script_sig.instructions().count() == 1;
+                if !single_push {
+                    return Err(Failure::new("script_sig", "a nested segwit scriptSig must push only the redeem script"));
+                }
+                witness_used = true;
+                self.verify_witness_program(witness, version, &program, true, stack)?;
+            }
+        }
+
+        if !witness_used && !witness.is_empty() {
+            return Err(Failure::new("witness", "witness given for a script that isn't segwit"));
+        }
+        Ok(())
+    }
+
+    fn verify_witness_program(
+        &mut self,
+        witness: &Witness,
+        version: u8,
+        program: &[u8],
+        nested: bool,
+        stack: &mut Vec<Vec<u8>>,
+    ) -> Result<(), Failure> {
+        *stack = witness.iter().map(<[u8]>::to_vec).collect();
+        match (version, program.len()) {
+            (0, 20) => {
+                if stack.len() != 2 {
+                    return Err(Failure::new("witness", format!("P2WPKH needs 2 witness items, got {}", stack.len())));
+                }
+                let hash = hash160::Hash::from_slice(program).map_err(|e| Failure::new("witness", e.to_string()))?;
+                let script = ScriptBuf::new_p2pkh(&hash.into());
+                self.run(&script, "p2wpkh", SigVersion::WitnessV0, None, stack)?;
+                clean_stack(stack, "p2wpkh")
+            }
+            (0, 32) => {
+                let witness_script = ScriptBuf::from_bytes(
+                    stack.pop().ok_or_else(|| Failure::new("witness", "P2WSH witness is empty"))?,
+                );
+                if sha256::Hash::hash(witness_script.as_bytes()).as_byte_array()[..] != *program {
+                    return Err(Failure::new("witness", "witness script doesn't hash to the witness program"));
+                }
+                check_elements(stack, "witness")?;
+                self.run(&witness_script, "witness_script", SigVersion::WitnessV0, None, stack)?;
+                clean_stack(stack, "witness_script")
+            }
+            (0, length) => Err(Failure::new("witness", format!("witness v0 program of {} bytes is neither P2WPKH nor P2WSH", length))),
+            (1, 32) if !nested => self.verify_taproot(witness, program, stack),
+            _ => {
+                self.record_note("witness", format!("witness v{} program: unencumbered under current rules", version));
+                Ok(())
+            }
+        }
+    }
+
+    fn verify_taproot(&mut self, witness: &Witness, program: &[u8], stack: &mut Vec<Vec<u8>>) -> Result<(), Failure> {
+        let output_key = XOnlyPublicKey::from_slice(program).map_err(|e| Failure::new("witness", format!("invalid output key: {}", e)))?;
+        if stack.is_empty() {
+            return Err(Failure::new("witness", "taproot witness is empty"));
+        }
+        if stack.len() >= 2 && stack.last().is_some_and(|last| last.first() == Some(&0x50)) {
+            stack.pop();
+        }
+
+        if stack.len() == 1 {
+            let signature = stack.pop().unwrap_or_default();
+            let note = self.check_key_path(&signature, &output_key).map_err(|reason| Failure::new("taproot_key_path", reason))?;
+            self.record_note("taproot_key_path", note);
+            return Ok(());
+        }
+
+        let control_block = stack.pop().unwrap_or_default();
+        let script = ScriptBuf::from_bytes(stack.pop().unwrap_or_default());
+        let control_block =
+            ControlBlock::decode(&control_block).map_err(|e| Failure::new("witness", format!("invalid control block: {}", e)))?;
+        if !control_block.verify_taproot_commitment(&self.secp, output_key, &script) {
+            return Err(Failure::new("witness", "the script and control block don't commit to the output key"));
+        }
+        if control_block.leaf_version != LeafVersion::TapScript {
+            self.record_note("tapscript", "unknown leaf version: unencumbered under current rules".to_string());
+            return Ok(());
+        }
+        if let Some(position) = script.instructions().position(|instruction| matches!(instruction, Ok(Instruction::Op(op)) if is_op_success(op.to_u8()))) {
+            self.record_note("tapscript", format!("OP_SUCCESS at instruction {}: the script succeeds unconditionally", position));
+            stack.clear();
+            return Ok(());
+        }
+        check_elements(stack, "witness")?;
+        self.validation_weight = serialize(witness).len() as i64 + VALIDATION_WEIGHT_OFFSET;
+        let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+        self.run(&script, "tapscript", SigVersion::Tapscript, Some(leaf_hash), stack)?;
+        clean_stack(stack, "tapscript")
+    }
+
+    /// EvalScript: runs `script` on `stack`, recording a step per instruction.
+    fn run(
+        &mut self,
+        script: &Script,
+        phase: &'static str,
+        version: SigVersion,
+        leaf_hash: Option<TapLeafHash>,
+        stack: &mut Vec<Vec<u8>>,
+    ) -> Result<(), Failure> {
+        if version != SigVersion::Tapscript && script.len() > MAX_SCRIPT_SIZE {
+            return Err(Failure::new(phase, format!("script is {} bytes, above the {}-byte limit", script.len(), MAX_SCRIPT_SIZE)));
+        }
+        let mut state = ExecState { altstack: Vec::new(), conditions: Vec::new(), op_count: 0 };
+        for (position, instruction) in script.instructions().enumerate() {
+            let fail = |opcode: Option<String>, reason: String| Failure { phase, position: Some(position), opcode, reason };
+            let instruction = instruction.map_err(|e| fail(None, format!("malformed script: {}", e)))?;
+            let executed = state.conditions.iter().all(|&taken| taken);
+            let note = self
+                .step(&instruction, script, version, leaf_hash, executed, stack, &mut state)
+                .map_err(|reason| fail(Some(instruction_asm(&instruction)), reason))?;
+
+            if self.trace {
+                if self.steps.len() >= MAX_TRACE_STEPS {
+                    return Err(fail(Some(instruction_asm(&instruction)), format!("more than {} steps; too long to trace", MAX_TRACE_STEPS)));
+                }
+                self.steps.push(TraceStep {
+                    phase: phase.to_string(),
+                    position,
+                    opcode: instruction_asm(&instruction),
+                    executed,
+                    stack: stack.iter().map(|item| bytes_to_hex(item)).collect(),
+                    altstack: state.altstack.iter().map(|item| bytes_to_hex(item)).collect(),
+                    note,
+                });
+            }
+            if stack.len() + state.altstack.len() > MAX_STACK_SIZE {
+                return Err(fail(Some(instruction_asm(&instruction)), format!("stack holds more than {} items", MAX_STACK_SIZE)));
+            }
+        }
+        if !state.conditions.is_empty() {
+            return Err(Failure::new(phase, "unbalanced conditional: OP_IF without OP_ENDIF"));
+        }
+        Ok(())
+    }
+
+    /// One instruction. Returns a note for the trace, or why the script fails.
+    #[allow(clippy::too_many_arguments)]
+    fn step(
+        &mut self,
+        instruction: &Instruction,
+        script: &Script,
+        version: SigVersion,
+        leaf_hash: Option<TapLeafHash>,
+        executed: bool,
+        stack: &mut Vec<Vec<u8>>,
+        state: &mut ExecState,
+    ) -> Result<Option<String>, String> {
+        let op = match instruction {
+            Instruction::PushBytes(bytes) => {
+                if bytes.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(format!("push of {} bytes, above the {}-byte limit", bytes.len(), MAX_SCRIPT_ELEMENT_SIZE));
+                }
+                if executed {
+                    stack.push(bytes.as_bytes().to_vec());
+                }
+                return Ok(None);
+            }
+            Instruction::Op(op) => op.to_u8(),
+        };
+
+        if version != SigVersion::Tapscript && op > 0x60 {
+            state.op_count += 1;
+            if state.op_count > MAX_OPS_PER_SCRIPT {
+                return Err(format!("more than {} non-push opcodes", MAX_OPS_PER_SCRIPT));
+            }
+        }
+        if is_disabled(op) {
+            return Err("disabled opcode".to_string());
+        }
+        // Conditionals run even in untaken branches so nesting is tracked.
+        if !executed && !(0x63..=0x68).contains(&op) {
+            return Ok(None);
+        }
+
+        let mut note = None;
+        match op {
+            0x4f => stack.push(encode_num(-1)),
+            0x51..=0x60 => stack.push(encode_num(op as i64 - 0x50)),
+            0x61 | 0xb0 | 0xb3..=0xb9 => {}
+            0x63 | 0x64 => {
+                let mut taken = false;
+                if executed {
+                    let condition = pop(stack)?;
+                    if version == SigVersion::Tapscript && !(condition.is_empty() || condition == [1]) {
+                        return Err("tapscript requires an empty or 0x01 IF argument (MINIMALIF)".to_string());
+                    }
+                    taken = cast_to_bool(&condition) == (op == 0x63);
+                }
+                state.conditions.push(taken);
+            }
+            0x67 => {
+                let taken = state.conditions.last_mut().ok_or("OP_ELSE without OP_IF")?;
+                *taken = !*taken;
+            }
+            0x68 => {
+                state.conditions.pop().ok_or("OP_ENDIF without OP_IF")?;
+            }
+            0x69 => {
+                if !cast_to_bool(&pop(stack)?) {
+                    return Err("OP_VERIFY failed".to_string());
+                }
+            }
+            0x6a => return Err("OP_RETURN".to_string()),
+            0x6b => state.altstack.push(pop(stack)?),
+            0x6c => stack.push(state.altstack.pop().ok_or("altstack is empty")?),
+            0x6d => {
+                need(stack, 2)?;
+                stack.truncate(stack.len() - 2);
+            }
+            0x6e => copy_items(stack, 2, 2)?,
+            0x6f => copy_items(stack, 3, 3)?,
+            0x70 => copy_items(stack, 4, 2)?,
+            0x71 => {
+                need(stack, 6)?;
+                let moved: Vec<_> = stack.drain(stack.len() - 6..stack.len() - 4).collect();
+                stack.extend(moved);
+            }
+            0x72 => {
+                need(stack, 4)?;
+                let moved: Vec<_> = stack.drain(stack.len() - 4..stack.len() - 2).collect();
+                stack.extend(moved);
+            }
+            0x73 => {
+                let top = peek(stack, 0)?.to_vec();
+                if cast_to_bool(&top) {
+                    stack.push(top);
+                }
+            }
+            0x74 => stack.push(encode_num(stack.len() as i64)),
+            0x75 => {
+                pop(stack)?;
+            }
+            0x76 => copy_items(stack, 1, 1)?,
+            0x77 => {
+                need(stack, 2)?;
+                stack.remove(stack.len() - 2);
+            }
+            0x78 => copy_items(stack, 2, 1)?,
+            0x79 | 0x7a => {
+                let depth = parse_num(&pop(stack)?, 4)?;
+                if depth < 0 || depth as usize >= stack.len() {
+                    return Err(format!("{} index {} is outside the stack", if op == 0x79 { "OP_PICK" } else { "OP_ROLL" }, depth));
+                }
+                let index = stack.len() - 1 - depth as usize;
+                let item = if op == 0x79 { stack[index].clone() } else { stack.remove(index) };
+                stack.push(item);
+            }
+            0x7b => {
+                need(stack, 3)?;
+                let item = stack.remove(stack.len() - 3);
+                stack.push(item);
+            }
+            0x7c => {
+                need(stack, 2)?;
+                let length = stack.len();
+                stack.swap(length - 1, length - 2);
+            }
+            0x7d => {
+                need(stack, 2)?;
+                let top = peek(stack, 0)?.to_vec();
+                stack.insert(stack.len() - 2, top);
+            }
+            0x82 => {
+                let size = peek(stack, 0)?.len();
+                stack.push(encode_num(size as i64));
+            }
+            0x87 | 0x88 => {
+                let (b, a) = (pop(stack)?, pop(stack)?);
+                if op == 0x88 {
+                    if a != b {
+                        return Err("OP_EQUALVERIFY failed: the items differ".to_string());
+                    }
+                } else {
+                    stack.push(encode_bool(a == b));
+                }
+            }
+            0x8b..=0x92 => {
+                let n = parse_num(&pop(stack)?, 4)?;
+                stack.push(match op {
+                    0x8b => encode_num(n + 1),
+                    0x8c => encode_num(n - 1),
+                    0x8f => encode_num(-n),
+                    0x90 => encode_num(n.abs()),
+                    0x91 => encode_bool(n == 0),
+                    _ => encode_bool(n != 0),
+                });
+            }
+            0x93 | 0x94 | 0x9a..=0xa4 => {
+                let b = parse_num(&pop(stack)?, 4)?;
+                let a = parse_num(&pop(stack)?, 4)?;
+                let result = match op {
+                    0x93 => encode_num(a + b),
+                    0x94 => encode_num(a - b),
+                    0x9a => encode_bool(a != 0 && b != 0),
+                    0x9b => encode_bool(a != 0 || b != 0),
+                    0x9c | 0x9d => encode_bool(a == b),
+                    0x9e => encode_bool(a != b),
+                    0x9f => encode_bool(a < b),
+                    0xa0 => encode_bool(a > b),
+                    0xa1 => encode_bool(a <= b),
+                    0xa2 => encode_bool(a >= b),
+                    0xa3 => encode_num(a.min(b)),
+                    _ => encode_num(a.max(b)),
+                };
+                if op == 0x9d {
+                    if a != b {
+                        return Err("OP_NUMEQUALVERIFY failed".to_string());
+                    }
+                } else {
+                    stack.push(result);
+                }
+            }
+            0xa5 => {
+                let max = parse_num(&pop(stack)?, 4)?;
+                let min = parse_num(&pop(stack)?, 4)?;
+                let x = parse_num(&pop(stack)?, 4)?;
+                stack.push(encode_bool(min <= x && x < max));
+            }
+            0xa6..=0xaa => {
+                let data = pop(stack)?;
+                stack.push(match op {
+                    0xa6 => ripemd160::Hash::hash(&data).to_byte_array().to_vec(),
+                    0xa7 => sha1::Hash::hash(&data).to_byte_array().to_vec(),
+                    0xa8 => sha256::Hash::hash(&data).to_byte_array().to_vec(),
+                    0xa9 => hash160::Hash::hash(&data).to_byte_array().to_vec(),
+                    _ => sha256d::Hash::hash(&data).to_byte_array().to_vec(),
+                });
+            }
+            0xab => note = Some("signatures still commit to the whole script: OP_CODESEPARATOR isn't modelled".to_string()),
+            0xac | 0xad => {
+                let pubkey = pop(stack)?;
+                let signature = pop(stack)?;
+                let (valid, check_note) = self.check_signature(&signature, &pubkey, script, version, leaf_hash)?;
+                note = check_note;
+                if op == 0xad {
+                    if !valid {
+                        return Err("OP_CHECKSIGVERIFY failed".to_string());
+                    }
+                } else {
+                    stack.push(encode_bool(valid));
+                }
+            }
+            0xae | 0xaf => {
+                if version == SigVersion::Tapscript {
+                    return Err("OP_CHECKMULTISIG is disabled in tapscript; use OP_CHECKSIGADD".to_string());
+                }
+                let (valid, check_note) = self.check_multisig(stack, script, version, state)?;
+                note = Some(check_note);
+                if op == 0xaf {
+                    if !valid {
+                        return Err("OP_CHECKMULTISIGVERIFY failed".to_string());
+                    }
+                } else {
+                    stack.push(encode_bool(valid));
+                }
+            }
+            0xba if version == SigVersion::Tapscript => {
+                let pubkey = pop(stack)?;
+                let n = parse_num(&pop(stack)?, 4)?;
+                let signature = pop(stack)?;
+                let (valid, check_note) = self.check_signature(&signature, &pubkey, script, version, leaf_hash)?;
+                note = check_note;
+                stack.push(encode_num(n + valid as i64));
+            }
+            0xb1 => note = Some(self.check_locktime(peek(stack, 0)?)?),
+            0xb2 => note = Some(self.check_sequence(peek(stack, 0)?)?),
+            0x50 | 0x62 | 0x89 | 0x8a => return Err("reserved opcode".to_string()),
+            _ => return Err("unknown opcode".to_string()),
+        }
+        Ok(note)
+    }
+
+    /// `(valid, note)`. Failures that end the script (bad DER, tapscript's NULLFAIL) are errors.
+    fn check_signature(
+        &mut self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script: &Script,
+        version: SigVersion,
+        leaf_hash: Option<TapLeafHash>,
+    ) -> Result<(bool, Option<String>), String> {
+        if version == SigVersion::Tapscript {
+            if pubkey.is_empty() {
+                return Err("empty public key in tapscript".to_string());
+            }
+            if signature.is_empty() {
+                return Ok((false, None));
+            }
+            self.validation_weight -= VALIDATION_WEIGHT_PER_SIGOP;
+            if self.validation_weight < 0 {
+                return Err("tapscript signature budget exceeded".to_string());
+            }
+            if pubkey.len() != 32 {
+                return Ok((true, Some(format!("{}-byte public key: unknown key type, passes under current rules", pubkey.len()))));
+            }
+            let Some(context) = self.context else {
+                return Ok((true, Some(format!("would check a schnorr signature against {}", bytes_to_hex(pubkey)))));
+            };
+            let key = XOnlyPublicKey::from_slice(pubkey).map_err(|e| format!("invalid public key: {}", e))?;
+            let signature = bitcoin::taproot::Signature::from_slice(signature).map_err(|e| format!("invalid schnorr signature: {}", e))?;
+            let prevouts = self.taproot_prevouts(context)?;
+            let sighash = self
+                .cache
+                .as_mut()
+                .ok_or("no transaction context")?
+                .taproot_script_spend_signature_hash(
+                    context.input_index,
+                    &Prevouts::All(&prevouts),
+                    leaf_hash.ok_or("no tapleaf")?,
+                    signature.sighash_type,
+                )
+                .map_err(|e| format!("sighash error: {}", e))?;
+            let digest = Message::from(sighash);
+            let valid = self.check_schnorr(key, digest, signature.signature);
+            self.record_check("schnorr_tapscript", signature.sighash_type as u8, &digest, &signature.to_vec(), pubkey, valid);
+            if !valid {
+                return Err("schnorr signature doesn't verify (a failed tapscript signature must be empty)".to_string());
+            }
+            return Ok((true, Some("schnorr signature verified".to_string())));
+        }
+
+        if signature.is_empty() {
+            return Ok((false, None));
+        }
+        let Some(context) = self.context else {
+            return Ok((true, Some(format!("would check a signature against {}", bytes_to_hex(pubkey)))));
+        };
+        let (&hash_type, der) = signature.split_last().ok_or("empty signature")?;
+        let mut parsed = ecdsa::Signature::from_der(der).map_err(|_| "signature isn't strict DER (BIP66)".to_string())?;
+        let Ok(key) = PublicKey::from_slice(pubkey) else {
+            return Ok((false, Some("not a valid public key".to_string())));
+        };
+        let cache = self.cache.as_mut().ok_or("no transaction context")?;
+        let digest = match version {
+            SigVersion::Base => Message::from(
+                cache
+                    .legacy_signature_hash(context.input_index, script, hash_type as u32)
+                    .map_err(|e| format!("sighash error: {}", e))?,
+            ),
+            _ => Message::from(
+                cache
+                    .p2wsh_signature_hash(
+                        context.input_index,
+                        script,
+                        Amount::from_sat(context.amount),
+                        EcdsaSighashType::from_consensus(hash_type as u32),
+                    )
+                    .map_err(|e| format!("sighash error: {}", e))?,
+            ),
+        };
+        // Consensus accepts high-S signatures; only relay policy rejects them.
+        parsed.normalize_s();
+        let valid = self.secp.verify_ecdsa(&digest, &parsed, &key.inner).is_ok();
+        let algorithm = if version == SigVersion::Base { "ecdsa_legacy" } else { "ecdsa_segwit_v0" };
+        self.record_check(algorithm, hash_type, &digest, signature, pubkey, valid);
+        Ok((valid, Some(if valid { "signature verified" } else { "signature doesn't verify" }.to_string())))
+    }
+
+    fn check_multisig(
+        &mut self,
+        stack: &mut Vec<Vec<u8>>,
+        script: &Script,
+        version: SigVersion,
+        state: &mut ExecState,
+    ) -> Result<(bool, String), String> {
+        let key_count = parse_num(&pop(stack)?, 4)?;
+        if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&key_count) {
+            return Err(format!("{} public keys; at most {} allowed", key_count, MAX_PUBKEYS_PER_MULTISIG));
+        }
+        state.op_count += key_count as usize;
+        if state.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(format!("more than {} non-push opcodes", MAX_OPS_PER_SCRIPT));
+        }
+        let mut keys: Vec<Vec<u8>> = (0..key_count).map(|_| pop(stack)).collect::<Result<_, _>>()?;
+        keys.reverse();
+        let sig_count = parse_num(&pop(stack)?, 4)?;
+        if !(0..=key_count).contains(&sig_count) {
+            return Err(format!("{} signatures for {} keys", sig_count, key_count));
+        }
+        let mut signatures: Vec<Vec<u8>> = (0..sig_count).map(|_| pop(stack)).collect::<Result<_, _>>()?;
+        signatures.reverse();
+        let dummy = pop(stack).map_err(|_| "missing the extra item OP_CHECKMULTISIG pops".to_string())?;
+        if !dummy.is_empty() {
+            return Err("OP_CHECKMULTISIG's extra item must be empty (BIP147 NULLDUMMY)".to_string());
+        }
+
+        if self.context.is_none() {
+            let valid = signatures.iter().all(|signature| !signature.is_empty());
+            return Ok((valid, format!("would check {} signatures against {} keys", sig_count, key_count)));
+        }
+        // Each signature has to match a key after the previous signature's key.
+        let first_check = self.checks.len();
+        let mut key_index = 0;
+        let mut matched = 0;
+        while matched < signatures.len() {
+            if signatures.len() - matched > keys.len() - key_index {
+                return Ok((false, format!("{} of {} signatures verified", matched, sig_count)));
+            }
+            let (valid, _) = self.check_signature(&signatures[matched], &keys[key_index], script, version, None)?;
+            if valid {
+                matched += 1;
+            }
+            key_index += 1;
+        }
+        // Trying a signature against keys it doesn't belong to is expected, not a failure.
+        let matched_checks: Vec<SignatureCheck> = self.checks.drain(first_check..).filter(|check| check.valid).collect();
+        self.checks.extend(matched_checks);
+        Ok((true, format!("{} of {} signatures verified", matched, sig_count)))
+    }
+
+    /// BIP65 OP_CHECKLOCKTIMEVERIFY, with the argument left on the stack.
+    fn check_locktime(&self, argument: &[u8]) -> Result<String, String> {
+        let locktime = parse_num(argument, 5)?;
+        if locktime < 0 {
+            return Err("negative lock time".to_string());
+        }
+        let Some(context) = self.context else {
+            return Ok(format!("would check the transaction's lock time is at least {}", locktime));
+        };
+        let tx_locktime = context.tx.lock_time.to_consensus_u32() as i64;
+        if (locktime < LOCKTIME_THRESHOLD) != (tx_locktime < LOCKTIME_THRESHOLD) {
+            return Err("lock time type (height or time) differs from the transaction's".to_string());
+        }
+        if locktime > tx_locktime {
+            return Err(format!("lock time {} not reached: the transaction's is {}", locktime, tx_locktime));
+        }
+        if context.tx.input[context.input_index].sequence.is_final() {
+            return Err("the input's sequence is final (0xffffffff), which disables the lock time".to_string());
+        }
+        Ok(format!("lock time {} satisfied by the transaction's {}", locktime, tx_locktime))
+    }
+
+    /// BIP112 OP_CHECKSEQUENCEVERIFY, with the argument left on the stack.
+    fn check_sequence(&self, argument: &[u8]) -> Result<String, String> {
+        let sequence = parse_num(argument, 5)?;
+        if sequence < 0 {
+            return Err("negative sequence".to_string());
+        }
+        if sequence & SEQUENCE_DISABLE_FLAG != 0 {
+            return Ok("disable flag set: no relative lock time".to_string());
+        }
+        let Some(context) = self.context else {
+            return Ok(format!("would check the input's relative lock time against {}", sequence));
+        };
+        if context.tx.version.0 < 2 {
+            return Err("relative lock times need transaction version 2 or later".to_string());
+        }
+        let tx_sequence = context.tx.input[context.input_index].sequence.to_consensus_u32() as i64;
+        if tx_sequence & SEQUENCE_DISABLE_FLAG != 0 {
+            return Err("the input's sequence has the disable flag set".to_string());
+        }
+        let mask = SEQUENCE_TYPE_FLAG | SEQUENCE_MASK;
+        let (required, actual) = (sequence & mask, tx_sequence & mask);
+        if (required & SEQUENCE_TYPE_FLAG) != (actual & SEQUENCE_TYPE_FLAG) {
+            return Err("relative lock type (blocks or time) differs from the input's".to_string());
+        }
+        if required > actual {
+            return Err(format!("relative lock {} not reached: the input's sequence gives {}", required & SEQUENCE_MASK, actual & SEQUENCE_MASK));
+        }
+        Ok(format!("relative lock {} satisfied", required & SEQUENCE_MASK))
+    }
+
+    fn check_key_path(&mut self, signature: &[u8], output_key: &XOnlyPublicKey) -> Result<String, String> {
+        let Some(context) = self.context else {
+            return Ok(format!("would check a key-path signature against output key {}", output_key));
+        };
+        let signature = bitcoin::taproot::Signature::from_slice(signature).map_err(|e| format!("invalid schnorr signature: {}", e))?;
+        let prevouts = self.taproot_prevouts(context)?;
+        let sighash = self
+            .cache
+            .as_mut()
+            .ok_or("no transaction context")?
+            .taproot_key_spend_signature_hash(context.input_index, &Prevouts::All(&prevouts), signature.sighash_type)
+            .map_err(|e| format!("sighash error: {}", e))?;
+        let digest = Message::from(sighash);
+        let valid = self.check_schnorr(*output_key, digest, signature.signature);
+        self.record_check("schnorr_key_path", signature.sighash_type as u8, &digest, &signature.to_vec(), &output_key.serialize(), valid);
+        if !valid {
+            return Err("key-path signature doesn't verify".to_string());
+        }
+        Ok("key-path signature verified".to_string())
+    }
+
+    fn check_schnorr(&mut self, key: XOnlyPublicKey, digest: Message, signature: schnorr::Signature) -> bool {
+        match self.deferred.as_mut() {
+            Some(deferred) => {
+                deferred.push(BatchItem::Schnorr(key, digest, signature));
+                true
+            }
+            None => self.secp.verify_schnorr(&signature, &digest, &key).is_ok(),
+        }
+    }
+
+    /// Taproot sighashes commit to every prevout; a single-input transaction's is known.
+    fn taproot_prevouts(&self, context: &TxContext) -> Result<Vec<TxOut>, String> {
+        match &context.prevouts {
+            Some(prevouts) => Ok(prevouts.clone()),
+            None if context.tx.input.len() == 1 => {
+                Ok(vec![TxOut { value: Amount::from_sat(context.amount), script_pubkey: self.script_pubkey.clone() }])
+            }
+            None => Err("taproot signatures commit to every prevout: add prevouts to the transaction context".to_string()),
+        }
+    }
+
+    fn record_check(&mut self, algorithm: &'static str, sighash_flag: u8, digest: &Message, signature: &[u8], pubkey: &[u8], valid: bool) {
+        self.checks.push(SignatureCheck {
+            algorithm,
+            sighash_flag,
+            digest: *digest.as_ref(),
+            signature: signature.to_vec(),
+            pubkey: pubkey.to_vec(),
+            valid,
+        });
+    }
+
+    fn record_note(&mut self, phase: &str, note: String) {
+        if !self.trace {
+            return;
+        }
+        self.steps.push(TraceStep {
+            phase: phase.to_string(),
+            position: 0,
+            opcode: String::new(),
+            executed: true,
+            stack: Vec::new(),
+            altstack: Vec::new(),
+            note: Some(note),
+        });
+    }
+}
+
+struct ExecState {
+    altstack: Vec<Vec<u8>>,
+    /// One entry per open IF: whether its current branch runs.
+    conditions: Vec<bool>,
+    op_count: usize,
+}
+
+fn witness_program(script: &Script) -> Option<(u8, Vec<u8>)> {
+    let version = script.witness_version()?;
+    Some((version.to_num(), script.as_bytes()[2..].to_vec()))
+}
+
+/// Segwit scripts must leave exactly one true item.
+fn clean_stack(stack: &[Vec<u8>], phase: &'static str) -> Result<(), Failure> {
+    match stack {
+        [top] if cast_to_bool(top) => Ok(()),
+        [_] => Err(Failure::new(phase, "script evaluated to false")),
+        _ => Err(Failure::new(phase, format!("segwit scripts must leave exactly one item, {} left", stack.len()))),
+    }
+}
+
+fn check_elements(stack: &[Vec<u8>], phase: &'static str) -> Result<(), Failure> {
+    match stack.iter().position(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        Some(index) => Err(Failure::new(phase, format!("witness item {} is above the {}-byte limit", index, MAX_SCRIPT_ELEMENT_SIZE))),
+        None => Ok(()),
+    }
+}
+
+/// CAT, SUBSTR, LEFT, RIGHT, INVERT, AND, OR, XOR, 2MUL, 2DIV, MUL, DIV, MOD, LSHIFT, RSHIFT.
+fn is_disabled(op: u8) -> bool {
+    matches!(op, 0x7e..=0x81 | 0x83..=0x86 | 0x8d | 0x8e | 0x95..=0x99)
+}
+
+/// BIP342's OP_SUCCESSx opcodes, which make a tapscript succeed unconditionally.
+fn is_op_success(op: u8) -> bool {
+    matches!(op, 80 | 98 | 126..=129 | 131..=134 | 137 | 138 | 141 | 142 | 149..=153 | 187..=254)
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    stack.pop().ok_or_else(|| "stack is empty".to_string())
+}
+
+fn peek(stack: &[Vec<u8>], depth: usize) -> Result<&[u8], String> {
+    need(stack, depth + 1)?;
+    Ok(&stack[stack.len() - 1 - depth])
+}
+
+fn need(stack: &[Vec<u8>], count: usize) -> Result<(), String> {
+    if stack.len() < count {
+        return Err(format!("needs {} stack items, {} available", count, stack.len()));
+    }
+    Ok(())
+}
+
+/// Pushes copies of `count` items starting `depth` items from the top (DUP, 2DUP, OVER, ...).
+fn copy_items(stack: &mut Vec<Vec<u8>>, depth: usize, count: usize) -> Result<(), String> {
+    need(stack, depth)?;
+    let start = stack.len() - depth;
+    let copies: Vec<_> = stack[start..start + count].to_vec();
+    stack.extend(copies);
+    Ok(())
+}
+
+fn cast_to_bool(item: &[u8]) -> bool {
+    match item.split_last() {
+        None => false,
+        // Negative zero (0x80 in the last byte, zeros elsewhere) is false too.
+        Some((&last, rest)) => rest.iter().any(|&byte| byte != 0) || (last & 0x7f) != 0,
+    }
+}
+
+/// A script number: little-endian sign-magnitude of at most `max_len` bytes.
+fn parse_num(bytes: &[u8], max_len: usize) -> Result<i64, String> {
+    if bytes.len() > max_len {
+        return Err(format!("number is {} bytes, above the {}-byte limit", bytes.len(), max_len));
+    }
+    let Some((&last, _)) = bytes.split_last() else { return Ok(0) };
+    let mut value: i64 = 0;
+    for (index, &byte) in bytes.iter().enumerate() {
+        value |= (byte as i64) << (8 * index);
+    }
+    if last & 0x80 != 0 {
+        value &= !(0x80i64 << (8 * (bytes.len() - 1)));
+        value = -value;
+    }
+    Ok(value)
+}
+
+fn encode_num(value: i64) -> Vec<u8> {
+    if value == 0 {
+        return Vec::new();
+    }
+    let negative = value < 0;
+    let mut magnitude = value.unsigned_abs();
+    let mut bytes = Vec::new();
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    if bytes.last().is_some_and(|&last| last & 0x80 != 0) {
+        bytes.push(if negative { 0x80 } else { 0x00 });
+    } else if negative {
+        if let Some(last) = bytes.last_mut() {
+            *last |= 0x80;
+        }
+    }
+    bytes
+}
+
+fn encode_bool(value: bool) -> Vec<u8> {
+    if value { vec![1] } else { Vec::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CLTV, OP_CSV, OP_DROP, OP_EQUALVERIFY, OP_SHA256};
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::secp256k1::{All, SecretKey};
+    use bitcoin::transaction::Version;
+    use bitcoin::{CompressedPublicKey, OutPoint, Sequence, TxIn, Txid};
+
+    const AMOUNT: u64 = 100_000;
+
+    fn key(byte: u8) -> (SecretKey, CompressedPublicKey) {
+        let secret = SecretKey::from_slice(&[byte; 32]).unwrap();
+        (secret, CompressedPublicKey(secret.public_key(&Secp256k1::new())))
+    }
+
+    fn spending(version: Version, lock_time: u32, sequence: u32) -> Transaction {
+        Transaction {
+            version,
+            lock_time: LockTime::from_consensus(lock_time),
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: Txid::all_zeros(), vout: 0 },
+                sequence: Sequence(sequence),
+                ..Default::default()
+            }],
+            output: vec![TxOut { value: Amount::from_sat(AMOUNT - 1_000), script_pubkey: ScriptBuf::new() }],
+        }
+    }
+
+    fn sign(secp: &Secp256k1<All>, digest: Message, secret: &SecretKey) -> Vec<u8> {
+        let mut signature = secp.sign_ecdsa(&digest, secret).serialize_der().to_vec();
+        signature.push(EcdsaSighashType::All as u8);
+        signature
+    }
+
+    fn sign_p2wsh(tx: &Transaction, witness_script: &Script, secret: &SecretKey) -> String {
+        let sighash = SighashCache::new(tx)
+            .p2wsh_signature_hash(0, witness_script, Amount::from_sat(AMOUNT), EcdsaSighashType::All)
+            .unwrap();
+        bytes_to_hex(&sign(&Secp256k1::new(), Message::from(sighash), secret))
+    }
+
+    fn trace(spend: &str, script_pubkey: &Script, tx: &Transaction) -> ScriptExecution {
+        let context = format!(r#"{{"txHex": "{}", "inputIndex": 0, "amountSat": {}}}"#, serialize_hex(tx), AMOUNT);
+        let json = execute_script(spend, &script_pubkey.to_hex_string(), Some(context), None).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn witness_json(items: &[&str]) -> String {
+        serde_json::to_string(items).unwrap()
+    }
+
+    /// Asserts the spend verified, with its last step and a single true item left.
+    fn assert_success(execution: &ScriptExecution, phase: &str, last_opcode: &str) {
+        assert!(execution.success, "{:?}", execution.failure.as_ref().map(|failure| &failure.reason));
+        assert_eq!(execution.signatures, "checked");
+        let last = execution.steps.last().unwrap();
+        assert_eq!((last.phase.as_str(), last.opcode.as_str()), (phase, last_opcode));
+        assert_eq!(execution.final_stack, ["01"]);
+    }
+
+    fn step<'a>(execution: &'a ScriptExecution, phase: &str, position: usize) -> &'a TraceStep {
+        execution.steps.iter().find(|step| step.phase == phase && step.position == position).unwrap()
+    }
+
+    fn assert_failure(execution: &ScriptExecution, phase: &str, position: usize, opcode: &str, reason: &str) {
+        assert!(!execution.success);
+        let failure = execution.failure.as_ref().unwrap();
+        assert_eq!(failure.phase, phase);
+        assert_eq!(failure.position, Some(position));
+        assert_eq!(failure.opcode.as_deref(), Some(opcode));
+        assert!(failure.reason.contains(reason), "{}", failure.reason);
+    }
+
+    #[test]
+    fn p2pkh_trace() {
+        let secp = Secp256k1::new();
+        let (secret, pubkey) = key(0x11);
+        let (other_secret, other_pubkey) = key(0x22);
+        let script_pubkey = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let tx = spending(Version::ONE, 0, 0xffff_ffff);
+        let digest = Message::from(SighashCache::new(&tx).legacy_signature_hash(0, &script_pubkey, 1).unwrap());
+        let script_sig = |secret: &SecretKey, pubkey: &CompressedPublicKey| {
+            Builder::new()
+                .push_slice(PushBytesBuf::try_from(sign(&secp, digest, secret)).unwrap())
+                .push_key(&(*pubkey).into())
+                .into_script()
+                .to_hex_string()
+        };
+
+        let execution = trace(&script_sig(&secret, &pubkey), &script_pubkey, &tx);
+        assert_success(&execution, "script_pubkey", "OP_CHECKSIG");
+        assert_eq!(execution.steps.len(), 7);
+        assert_eq!(step(&execution, "script_pubkey", 4).note.as_deref(), Some("signature verified"));
+
+        // The other key's signature is fine, but its key doesn't hash to the output's.
+        let execution = trace(&script_sig(&other_secret, &other_pubkey), &script_pubkey, &tx);
+        assert_failure(&execution, "script_pubkey", 3, "OP_EQUALVERIFY", "the items differ");
+    }
+
+    #[test]
+    fn p2wpkh_trace() {
+        let secp = Secp256k1::new();
+        let (secret, pubkey) = key(0x11);
+        let (other_secret, other_pubkey) = key(0x22);
+        let script_pubkey = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash());
+        let tx = spending(Version::TWO, 0, 0xffff_fffd);
+        let sighash = SighashCache::new(&tx)
+            .p2wpkh_signature_hash(0, &script_pubkey, Amount::from_sat(AMOUNT), EcdsaSighashType::All)
+            .unwrap();
+        let witness = |secret: &SecretKey, pubkey: &CompressedPublicKey| {
+            let signature = bytes_to_hex(&sign(&secp, Message::from(sighash), secret));
+            witness_json(&[&signature, &bytes_to_hex(&pubkey.to_bytes())])
+        };
+
+        let execution = trace(&witness(&secret, &pubkey), &script_pubkey, &tx);
+        assert_success(&execution, "p2wpkh", "OP_CHECKSIG");
+        let phases: Vec<&str> = execution.steps.iter().map(|step| step.phase.as_str()).collect();
+        assert_eq!(phases, ["script_pubkey", "script_pubkey", "p2wpkh", "p2wpkh", "p2wpkh", "p2wpkh", "p2wpkh"]);
+
+        let execution = trace(&witness(&other_secret, &other_pubkey), &script_pubkey, &tx);
+        assert_failure(&execution, "p2wpkh", 3, "OP_EQUALVERIFY", "the items differ");
+    }
+
+    #[test]
+    fn multisig_2_of_3_trace() {
+        let keys = [key(0x11), key(0x22), key(0x33)];
+        let mut builder = Builder::new().push_int(2);
+        for (_, pubkey) in &keys {
+            builder = builder.push_key(&(*pubkey).into());
+        }
+        let witness_script = builder.push_int(3).push_opcode(OP_CHECKMULTISIG).into_script();
+        let script_pubkey = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let tx = spending(Version::TWO, 0, 0xffff_fffd);
+        let first = sign_p2wsh(&tx, &witness_script, &keys[0].0);
+        let third = sign_p2wsh(&tx, &witness_script, &keys[2].0);
+        let script_hex = witness_script.to_hex_string();
+
+        let execution = trace(&witness_json(&["", &first, &third, &script_hex]), &script_pubkey, &tx);
+        assert_success(&execution, "witness_script", "OP_CHECKMULTISIG");
+        assert_eq!(execution.steps.last().unwrap().position, 5);
+        assert_eq!(execution.steps.last().unwrap().note.as_deref(), Some("2 of 2 signatures verified"));
+
+        let execution = trace(&witness_json(&["01", &first, &third, &script_hex]), &script_pubkey, &tx);
+        assert_failure(&execution, "witness_script", 5, "OP_CHECKMULTISIG", "NULLDUMMY");
+    }
+
+    #[test]
+    fn cltv_trace() {
+        let (secret, pubkey) = key(0x11);
+        let witness_script = Builder::new()
+            .push_int(800_000)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_key(&pubkey.into())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let script_pubkey = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let spend = |tx: &Transaction| witness_json(&[&sign_p2wsh(tx, &witness_script, &secret), &witness_script.to_hex_string()]);
+
+        let tx = spending(Version::TWO, 800_000, 0xffff_fffe);
+        let execution = trace(&spend(&tx), &script_pubkey, &tx);
+        assert_success(&execution, "witness_script", "OP_CHECKSIG");
+        let cltv = step(&execution, "witness_script", 1);
+        assert_eq!(cltv.opcode, "OP_CHECKLOCKTIMEVERIFY");
+        assert_eq!(cltv.note.as_deref(), Some("lock time 800000 satisfied by the transaction's 800000"));
+
+        let early = spending(Version::TWO, 799_999, 0xffff_fffe);
+        let execution = trace(&spend(&early), &script_pubkey, &early);
+        assert_failure(&execution, "witness_script", 1, "OP_CHECKLOCKTIMEVERIFY", "lock time 800000 not reached: the transaction's is 799999");
+    }
+
+    #[test]
+    fn csv_trace() {
+        let (secret, pubkey) = key(0x11);
+        let witness_script = Builder::new()
+            .push_int(144)
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_key(&pubkey.into())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let script_pubkey = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let spend = |tx: &Transaction| witness_json(&[&sign_p2wsh(tx, &witness_script, &secret), &witness_script.to_hex_string()]);
+
+        let tx = spending(Version::TWO, 0, 144);
+        let execution = trace(&spend(&tx), &script_pubkey, &tx);
+        assert_success(&execution, "witness_script", "OP_CHECKSIG");
+        assert_eq!(step(&execution, "witness_script", 1).note.as_deref(), Some("relative lock 144 satisfied"));
+
+        let early = spending(Version::TWO, 0, 143);
+        let execution = trace(&spend(&early), &script_pubkey, &early);
+        assert_failure(&execution, "witness_script", 1, "OP_CHECKSEQUENCEVERIFY", "relative lock 144 not reached: the input's sequence gives 143");
+    }
+
+    #[test]
+    fn hashlock_trace() {
+        let (secret, pubkey) = key(0x11);
+        let preimage = [0x42; 32];
+        let witness_script = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(sha256::Hash::hash(&preimage).to_byte_array())
+            .push_opcode(OP_EQUALVERIFY)
+            .push_key(&pubkey.into())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let script_pubkey = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let tx = spending(Version::TWO, 0, 0xffff_fffd);
+        let signature = sign_p2wsh(&tx, &witness_script, &secret);
+        let script_hex = witness_script.to_hex_string();
+
+        let execution = trace(&witness_json(&[&signature, &bytes_to_hex(&preimage), &script_hex]), &script_pubkey, &tx);
+        assert_success(&execution, "witness_script", "OP_CHECKSIG");
+        assert_eq!(step(&execution, "witness_script", 0).stack.last().map(String::as_str), Some(sha256::Hash::hash(&preimage).to_string().as_str()));
+
+        let execution = trace(&witness_json(&[&signature, &bytes_to_hex(&[0x43; 32]), &script_hex]), &script_pubkey, &tx);
+        assert_failure(&execution, "witness_script", 2, "OP_EQUALVERIFY", "the items differ");
+    }
+}
@@ -0,0 +1,92 @@
+use bitcoin::opcodes::all::OP_CHECKMULTISIG;
+use bitcoin::script::Instruction;
+use bitcoin::Script;
+
+pub mod asm;
+pub mod batch;
+pub mod extract;
+pub mod interpreter;
+pub mod multisig;
+pub mod op_return;
+pub mod runestone;
+pub mod stats;
+
+pub use asm::{asm_to_script, script_to_asm};
+pub use batch::batch_verify;
+pub use extract::extract_script_items;
+pub use interpreter::execute_script;
+pub use multisig::{sort_pubkeys_bip67, create_multisig, verify_multisig_address};
+pub use op_return::{build_op_return, parse_op_return};
+pub use runestone::decode_runestone;
+pub use stats::script_stats;
+
+pub struct MultisigInfo {
+    pub threshold: usize,
+    pub pubkeys: Vec<Vec<u8>>,
+}
+
+/// Standard output template name, as used in reports: p2pkh, p2sh, p2wpkh, p2wsh, p2tr,
+/// p2pk, op_return, or nonstandard.
+pub fn output_script_type(script: &Script) -> &'static str {
+    if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_p2wpkh() {
+        "p2wpkh"
+    } else if script.is_p2wsh() {
+        "p2wsh"
+    } else if script.is_p2tr() {
+        "p2tr"
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else if script.is_op_return() {
+        "op_return"
+    } else {
+        "nonstandard"
+    }
+}
+
+pub fn parse_multisig(script: &Script) -> Option<MultisigInfo> {
+    let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().ok()?;
+    if instructions.len() < 4 {
+        return None;
+    }
+
+    let threshold = small_int(&instructions[0])?;
+    let total = small_int(&instructions[instructions.len() - 2])?;
+    match instructions[instructions.len() - 1] {
+        Instruction::Op(op) if op == OP_CHECKMULTISIG => {}
+        _ => return None,
+    }
+
+    let pubkeys: Vec<Vec<u8>> = instructions[1..instructions.len() - 2]
+        .iter()
+        .map(|ins| match ins {
+            Instruction::PushBytes(bytes) if bytes.len() == 33 || bytes.len() == 65 => {
+                Some(bytes.as_bytes().to_vec())
+            }
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    if pubkeys.len() != total || threshold == 0 || threshold > total {
+        return None;
+    }
+
+    Some(MultisigInfo { threshold, pubkeys })
+}
+
+fn small_int(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Op(op) => {
+            let code = op.to_u8();
+            if (0x51..=0x60).contains(&code) {
+                Some((code - 0x50) as usize)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::opcodes::all::OP_CHECKMULTISIG;
+use bitcoin::script::Builder;
+use bitcoin::{Address, Network, PublicKey, ScriptBuf};
+use crate::script::stats::check_script;
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, require_address_network, NetworkParams};
+use crate::wallet::keys::parse_public_key;
+use crate::utils::json::to_json;
+
+pub const MULTISIG_SCRIPT_TYPES: [&str; 3] = ["p2wsh", "p2sh-p2wsh", "p2sh"];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultisigAddress {
+    pub address: String,
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    pub script: String,
+    pub pubkeys: Vec<String>,
+    pub sorted: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultisigVerification {
+    pub matches: bool,
+    pub sorted: Option<bool>,
+    #[serde(alias = "script_type")]
+    pub script_type: Option<String>,
+    pub script: Option<String>,
+    pub tried: Vec<String>,
+}
+
+#[wasm_bindgen]
+pub fn sort_pubkeys_bip67(pubkeys_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let pubkeys = parse_pubkeys(pubkeys_json)?;
+    let sorted: Vec<String> = bip67_sorted(&pubkeys)?
+        .iter()
+        .map(|pubkey| bytes_to_hex(&pubkey.to_bytes()))
+        .collect();
+
+    to_json(&sorted, compact)
+}
+
+#[wasm_bindgen]
+pub fn create_multisig(
+    pubkeys_json: &str,
+    threshold: usize,
+    script_type: &str,
+    network: Option<String>,
+    sorted: Option<bool>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let mut pubkeys = parse_pubkeys(pubkeys_json)?;
+    let sorted = sorted.unwrap_or(true);
+    if sorted {
+        pubkeys = bip67_sorted(&pubkeys)?;
+    }
+
+    let script = multisig_script(&pubkeys, threshold, script_type).map_err(|e| JsValue::from_str(&e))?;
+    let address = multisig_address(&script, script_type, &network).map_err(|e| JsValue::from_str(&e))?;
+
+    let result = MultisigAddress {
+        address,
+        script_type: script_type.to_string(),
+        script: bytes_to_hex(script.as_bytes()),
+        pubkeys: pubkeys.iter().map(|pubkey| bytes_to_hex(&pubkey.to_bytes())).collect(),
+        sorted,
+    };
+
+    to_json(&result, compact)
+}
+
+#[wasm_bindgen]
+pub fn verify_multisig_address(
+    address: &str,
+    pubkeys_json: &str,
+    threshold: usize,
+    script_type: Option<String>,
+    network: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let expected = require_address_network(address, Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let given = parse_pubkeys(pubkeys_json)?;
+
+    let script_types: Vec<&str> = match &script_type {
+        Some(script_type) => vec![script_type.as_str()],
+        None => MULTISIG_SCRIPT_TYPES.to_vec(),
+    };
+
+    // BIP67 first: it is what independently constructed wallets are expected to agree on.
+    let mut orderings = Vec::new();
+    if let Ok(sorted) = bip67_sorted(&given) {
+        orderings.push((true, sorted));
+    }
+    orderings.push((false, given));
+
+    let mut result = MultisigVerification {
+        matches: false,
+        sorted: None,
+        script_type: None,
+        script: None,
+        tried: Vec::new(),
+    };
+
+    for (sorted, pubkeys) in &orderings {
+        for script_type in &script_types {
+            let script = multisig_script(pubkeys, threshold, script_type).map_err(|e| JsValue::from_str(&e))?;
+            let candidate = multisig_script_pubkey(&script, script_type).map_err(|e| JsValue::from_str(&e))?;
+            result.tried.push(format!("{} {}", if *sorted { "sorted" } else { "as given" }, script_type));
+            if candidate == expected {
+                result.matches = true;
+                result.sorted = Some(*sorted);
+                result.script_type = Some(script_type.to_string());
+                result.script = Some(bytes_to_hex(script.as_bytes()));
+                return to_json(&result, compact);
+            }
+        }
+    }
+
+    to_json(&result, compact)
+}
+
+/// BIP67: lexicographic order of the compressed serializations. Uncompressed keys are not allowed.
+pub fn bip67_sorted(pubkeys: &[PublicKey]) -> Result<Vec<PublicKey>, JsValue> {
+    if let Some(index) = pubkeys.iter().position(|pubkey| !pubkey.compressed) {
+        return Err(JsValue::from_str(&format!(
+            "BIP67 requires compressed public keys; key {} is uncompressed",
+            index
+        )));
+    }
+    let mut sorted = pubkeys.to_vec();
+    sorted.sort_by_key(|pubkey| pubkey.to_bytes());
+    Ok(sorted)
+}
+
+pub fn multisig_script(pubkeys: &[PublicKey], threshold: usize, script_type: &str) -> Result<ScriptBuf, String> {
+    let max_keys = if script_type == "p2sh" { 15 } else { 20 };
+    if pubkeys.is_empty() || pubkeys.len() > max_keys {
+        return Err(format!("{} multisig needs between 1 and {} keys, got {}", script_type, max_keys, pubkeys.len()));
+    }
+    if threshold == 0 || threshold > pubkeys.len() {
+        return Err(format!("Threshold must be between 1 and {}, got {}", pubkeys.len(), threshold));
+    }
+
+    let mut builder = Builder::new().push_int(threshold as i64);
+    for pubkey in pubkeys {
+        builder = builder.push_key(pubkey);
+    }
+    let script = builder
+        .push_int(pubkeys.len() as i64)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script();
+
+    let context = if script_type == "p2sh" { "p2sh" } else { "p2wsh" };
+    check_script(&script, context)?;
+    Ok(script)
+}
+
+pub fn multisig_address(script: &ScriptBuf, script_type: &str, network: &NetworkParams) -> Result<String, String> {
+    let script_pubkey = multisig_script_pubkey(script, script_type)?;
+    network.address(&script_pubkey).ok_or_else(|| "Cannot encode multisig address".to_string())
+}
+
+pub fn multisig_script_pubkey(script: &ScriptBuf, script_type: &str) -> Result<ScriptBuf, String> {
+    match script_type {
+        "p2wsh" => Ok(ScriptBuf::new_p2wsh(&script.wscript_hash())),
+        "p2sh-p2wsh" => Ok(ScriptBuf::new_p2sh(&ScriptBuf::new_p2wsh(&script.wscript_hash()).script_hash())),
+        "p2sh" => Address::p2sh(script, Network::Bitcoin)
+            .map(|address| address.script_pubkey())
+            .map_err(|e| format!("Invalid redeem script: {}", e)),
+        other => Err(format!("Unknown multisig script type '{}' (expected p2wsh, p2sh-p2wsh or p2sh)", other)),
+    }
+}
+
+fn parse_pubkeys(pubkeys_json: &str) -> Result<Vec<PublicKey>, JsValue> {
+    let keys: Vec<String> = serde_json::from_str(pubkeys_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid public keys JSON: {}", e)))?;
+    keys.iter().map(|key| parse_public_key(key)).collect()
+}
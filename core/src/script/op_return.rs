@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::opcodes::all::OP_RETURN;
+use bitcoin::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::Script;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+/// Default `-datacarriersize` payload limit for a standard OP_RETURN output.
+pub const MAX_OP_RETURN_PAYLOAD: usize = 80;
+
+#[derive(Deserialize)]
+pub struct OpReturnSchema {
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<OpReturnFieldSpec>,
+}
+
+#[derive(Deserialize)]
+pub struct OpReturnFieldSpec {
+    pub name: String,
+    pub size: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ParsedOpReturn {
+    pub data: String,
+    pub prefix: Option<String>,
+    pub fields: Vec<OpReturnField>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpReturnField {
+    pub name: Option<String>,
+    pub value: String,
+}
+
+/// Builds `OP_RETURN <prefix || len || field || len || field ...>` as a single push.
+#[wasm_bindgen]
+pub fn build_op_return(protocol_prefix_hex: &str, fields_json: &str) -> Result<String, JsValue> {
+    let prefix = hex_to_bytes(protocol_prefix_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid protocol prefix hex: {}", e)))?;
+    let fields: Vec<String> = serde_json::from_str(fields_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid fields JSON: {}", e)))?;
+
+    let mut payload = prefix;
+    for (index, field) in fields.iter().enumerate() {
+        let bytes = hex_to_bytes(field, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid hex in field {}: {}", index, e)))?;
+        let length = u8::try_from(bytes.len())
+            .map_err(|_| JsValue::from_str(&format!("Field {} is {} bytes; fields are limited to 255", index, bytes.len())))?;
+        payload.push(length);
+        payload.extend_from_slice(&bytes);
+    }
+
+    if payload.len() > MAX_OP_RETURN_PAYLOAD {
+        return Err(JsValue::from_str(&format!(
+            "OP_RETURN payload is {} bytes, {} over the {}-byte standardness limit",
+            payload.len(),
+            payload.len() - MAX_OP_RETURN_PAYLOAD,
+            MAX_OP_RETURN_PAYLOAD
+        )));
+    }
+
+    let push = PushBytesBuf::try_from(payload).map_err(|e| JsValue::from_str(&format!("Push too large: {}", e)))?;
+    let script = Builder::new().push_opcode(OP_RETURN).push_slice(push).into_script();
+    Ok(bytes_to_hex(script.as_bytes()))
+}
+
+/// Splits an OP_RETURN back into its prefix and length-prefixed fields. Without a schema
+/// only the raw data is returned, since the prefix length can't be inferred.
+#[wasm_bindgen]
+pub fn parse_op_return(script_hex: &str, schema_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(script_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid script hex: {}", e)))?;
+    let data = op_return_data(Script::from_bytes(&bytes)).map_err(|e| JsValue::from_str(&e))?;
+
+    let schema: Option<OpReturnSchema> = match schema_json {
+        Some(json) => Some(
+            serde_json::from_str(&json).map_err(|e| JsValue::from_str(&format!("Invalid schema JSON: {}", e)))?,
+        ),
+        None => None,
+    };
+
+    let mut parsed = ParsedOpReturn {
+        data: bytes_to_hex(&data),
+        prefix: None,
+        fields: Vec::new(),
+    };
+    if let Some(schema) = schema {
+        let (prefix, fields) = split_fields(&data, &schema).map_err(|e| JsValue::from_str(&e))?;
+        parsed.prefix = Some(prefix);
+        parsed.fields = fields;
+    }
+
+    to_json(&parsed, compact)
+}
+
+pub fn op_return_data(script: &Script) -> Result<Vec<u8>, String> {
+    let mut instructions = script.instructions();
+    match instructions.next() {
+        Some(Ok(Instruction::Op(op))) if op == OP_RETURN => {}
+        _ => return Err("Script is not an OP_RETURN output".to_string()),
+    }
+
+    let mut data = Vec::new();
+    for instruction in instructions {
+        match instruction.map_err(|e| format!("Invalid script: {}", e))? {
+            Instruction::PushBytes(bytes) => data.extend_from_slice(bytes.as_bytes()),
+            Instruction::Op(op) => return Err(format!("OP_RETURN data contains non-push opcode {}", op)),
+        }
+    }
+    Ok(data)
+}
+
+fn split_fields(data: &[u8], schema: &OpReturnSchema) -> Result<(String, Vec<OpReturnField>), String> {
+    let prefix = match &schema.prefix {
+        Some(hex) => hex_to_bytes(hex, HexMode::Lenient).map_err(|e| format!("Invalid schema prefix hex: {}", e))?,
+        None => Vec::new(),
+    };
+    if !data.starts_with(&prefix) {
+        return Err(format!("Data does not start with the expected prefix {}", bytes_to_hex(&prefix)));
+    }
+
+    let mut rest = &data[prefix.len()..];
+    let mut fields = Vec::new();
+    let mut index = 0;
+    while !rest.is_empty() {
+        let length = rest[0] as usize;
+        if rest.len() < 1 + length {
+            return Err(format!("Field {} claims {} bytes but only {} remain", index, length, rest.len() - 1));
+        }
+        let spec = schema.fields.get(index);
+        if let Some(size) = spec.and_then(|spec| spec.size) {
+            if size != length {
+                return Err(format!("Field {} is {} bytes, but the schema expects {}", index, length, size));
+            }
+        }
+        fields.push(OpReturnField {
+            name: spec.map(|spec| spec.name.clone()),
+            value: bytes_to_hex(&rest[1..1 + length]),
+        });
+        rest = &rest[1 + length..];
+        index += 1;
+    }
+
+    if !schema.fields.is_empty() && fields.len() != schema.fields.len() {
+        return Err(format!("Found {} fields, but the schema lists {}", fields.len(), schema.fields.len()));
+    }
+    Ok((bytes_to_hex(&prefix), fields))
+}
@@ -0,0 +1,479 @@
+use std::collections::{BTreeMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::opcodes::all::{OP_PUSHNUM_13, OP_RETURN};
+use bitcoin::script::Instruction;
+use bitcoin::{Script, ScriptBuf};
+use crate::transaction::parse_tx_hex;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+const TAG_BODY: u128 = 0;
+const TAG_DIVISIBILITY: u128 = 1;
+const TAG_FLAGS: u128 = 2;
+const TAG_SPACERS: u128 = 3;
+const TAG_RUNE: u128 = 4;
+const TAG_SYMBOL: u128 = 5;
+const TAG_PREMINE: u128 = 6;
+const TAG_CAP: u128 = 8;
+const TAG_AMOUNT: u128 = 10;
+const TAG_HEIGHT_START: u128 = 12;
+const TAG_HEIGHT_END: u128 = 14;
+const TAG_OFFSET_START: u128 = 16;
+const TAG_OFFSET_END: u128 = 18;
+const TAG_MINT: u128 = 20;
+const TAG_POINTER: u128 = 22;
+
+const FLAG_ETCHING: u128 = 1 << 0;
+const FLAG_TERMS: u128 = 1 << 1;
+const FLAG_TURBO: u128 = 1 << 2;
+
+const MAX_DIVISIBILITY: u128 = 38;
+/// One spacer bit per gap between the 28 letters a rune name can have.
+const MAX_SPACERS: u128 = 0b111_1111_1111_1111_1111_1111_1111;
+/// A u128 takes at most 19 LEB128 bytes, the last holding only its top two bits.
+const MAX_VARINT_BYTES: usize = 19;
+
+#[derive(Serialize, Deserialize)]
+pub struct RunestoneField {
+    pub tag: String,
+    /// The tag's name in the runes protocol, when it has one.
+    pub name: Option<String>,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RunestoneEdict {
+    /// `block:tx` of the rune's etching.
+    pub id: String,
+    pub amount: String,
+    pub output: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunestoneTerms {
+    pub cap: Option<String>,
+    pub amount: Option<String>,
+    #[serde(alias = "height_start")]
+    pub height_start: Option<u64>,
+    #[serde(alias = "height_end")]
+    pub height_end: Option<u64>,
+    #[serde(alias = "offset_start")]
+    pub offset_start: Option<u64>,
+    #[serde(alias = "offset_end")]
+    pub offset_end: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunestoneEtching {
+    /// The rune's name as a number.
+    pub rune: Option<String>,
+    pub name: Option<String>,
+    /// `name` with `•` where `spacers` puts one.
+    #[serde(alias = "spaced_name")]
+    pub spaced_name: Option<String>,
+    pub divisibility: Option<u8>,
+    pub spacers: Option<u32>,
+    pub symbol: Option<String>,
+    pub premine: Option<String>,
+    pub terms: Option<RunestoneTerms>,
+    pub turbo: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunestoneDecoding {
+    pub found: bool,
+    #[serde(alias = "output_index")]
+    pub output_index: Option<usize>,
+    pub payload: Option<String>,
+    /// The payload's LEB128 integers, as decimal strings since they can exceed 2^53.
+    pub integers: Vec<String>,
+    /// The tag/value pairs before the body, in order.
+    pub fields: Vec<RunestoneField>,
+    pub edicts: Vec<RunestoneEdict>,
+    pub etching: Option<RunestoneEtching>,
+    pub mint: Option<String>,
+    pub pointer: Option<u32>,
+    /// A runestone that breaks the decoding rules. The protocol burns the runes its
+    /// transaction spends.
+    pub cenotaph: bool,
+    pub flaws: Vec<String>,
+}
+
+/// Decodes the runes protocol message in a transaction's first `OP_RETURN OP_13`
+/// output, or in such a script given alone. This is payload decoding only: no rune ids
+/// are looked up. Without a transaction, edict outputs and the pointer can't be
+/// checked against the output count.
+#[wasm_bindgen]
+pub fn decode_runestone(tx_hex_or_script_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let (scripts, output_count) = match parse_tx_hex(tx_hex_or_script_hex) {
+        Ok(tx) => {
+            let count = tx.output.len();
+            (tx.output.into_iter().map(|output| output.script_pubkey).collect(), Some(count))
+        }
+        Err(_) => {
+            let bytes = hex_to_bytes(tx_hex_or_script_hex, HexMode::Lenient)
+                .map_err(|e| JsValue::from_str(&format!("Invalid transaction or script hex: {}", e)))?;
+            (vec![ScriptBuf::from_bytes(bytes)], None)
+        }
+    };
+
+    let decoding = decode_scripts(&scripts, output_count);
+    to_json(&decoding, compact)
+}
+
+pub fn decode_scripts(scripts: &[ScriptBuf], output_count: Option<usize>) -> RunestoneDecoding {
+    let mut decoding = RunestoneDecoding {
+        found: false,
+        output_index: None,
+        payload: None,
+        integers: Vec::new(),
+        fields: Vec::new(),
+        edicts: Vec::new(),
+        etching: None,
+        mint: None,
+        pointer: None,
+        cenotaph: false,
+        flaws: Vec::new(),
+    };
+    let Some((index, payload)) = scripts.iter().enumerate().find_map(|(index, script)| payload(script).map(|p| (index, p))) else {
+        return decoding;
+    };
+    decoding.found = true;
+    decoding.output_index = Some(index);
+
+    let payload = match payload {
+        Ok(payload) => payload,
+        Err(flaw) => {
+            decoding.flaws.push(flaw.to_string());
+            decoding.cenotaph = true;
+            return decoding;
+        }
+    };
+    decoding.payload = Some(bytes_to_hex(&payload));
+
+    let (integers, varint_flaw) = decode_integers(&payload);
+    decoding.integers = integers.iter().map(u128::to_string).collect();
+    if let Some(flaw) = varint_flaw {
+        // The protocol gives up on the whole message at a bad varint.
+        decoding.flaws.push(flaw.to_string());
+        decoding.cenotaph = true;
+        return decoding;
+    }
+
+    interpret(&integers, output_count, &mut decoding);
+    decoding.cenotaph = !decoding.flaws.is_empty();
+    decoding
+}
+
+/// The data pushed after `OP_RETURN OP_13`, or the flaw that stops it being read. None
+/// for any other script.
+fn payload(script: &Script) -> Option<Result<Vec<u8>, &'static str>> {
+    let mut instructions = script.instructions();
+    if !matches!(instructions.next(), Some(Ok(Instruction::Op(op))) if op == OP_RETURN) {
+        return None;
+    }
+    if !matches!(instructions.next(), Some(Ok(Instruction::Op(op))) if op == OP_PUSHNUM_13) {
+        return None;
+    }
+
+    let mut payload = Vec::new();
+    for instruction in instructions {
+        match instruction {
+            Ok(Instruction::PushBytes(push)) => payload.extend_from_slice(push.as_bytes()),
+            Ok(Instruction::Op(_)) => return Some(Err("opcode")),
+            Err(_) => return Some(Err("invalid_script")),
+        }
+    }
+    Some(Ok(payload))
+}
+
+/// LEB128 integers up to the first malformed one, and what was wrong with it.
+fn decode_integers(payload: &[u8]) -> (Vec<u128>, Option<&'static str>) {
+    let mut integers = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        match decode_varint(rest) {
+            Ok((value, length)) => {
+                integers.push(value);
+                rest = &rest[length..];
+            }
+            Err(flaw) => return (integers, Some(flaw)),
+        }
+    }
+    (integers, None)
+}
+
+fn decode_varint(bytes: &[u8]) -> Result<(u128, usize), &'static str> {
+    let mut value: u128 = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i >= MAX_VARINT_BYTES {
+            return Err("varint_overlong");
+        }
+        let bits = (byte & 0x7f) as u128;
+        if i == MAX_VARINT_BYTES - 1 && bits & 0x7c != 0 {
+            return Err("varint_overflow");
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err("varint_truncated")
+}
+
+/// Reads the message the way `ord` does: tag/value pairs up to the body tag, edicts
+/// after it, then the known fields are taken out and anything even left over is a flaw.
+fn interpret(integers: &[u128], output_count: Option<usize>, decoding: &mut RunestoneDecoding) {
+    let mut fields: BTreeMap<u128, VecDeque<u128>> = BTreeMap::new();
+    let mut index = 0;
+    while index < integers.len() {
+        let tag = integers[index];
+        if tag == TAG_BODY {
+            decode_edicts(&integers[index + 1..], output_count, decoding);
+            break;
+        }
+        let Some(&value) = integers.get(index + 1) else {
+            decoding.flaws.push("truncated_field".to_string());
+            break;
+        };
+        decoding.fields.push(RunestoneField {
+            tag: tag.to_string(),
+            name: tag_name(tag).map(str::to_string),
+            value: value.to_string(),
+        });
+        fields.entry(tag).or_default().push_back(value);
+        index += 2;
+    }
+
+    let mut flags = take(&mut fields, TAG_FLAGS, |[flags]| Some(flags)).unwrap_or(0);
+    let mut take_flag = |flag: u128| {
+        let set = flags & flag != 0;
+        flags &= !flag;
+        set
+    };
+    let (etching, terms, turbo) = (take_flag(FLAG_ETCHING), take_flag(FLAG_TERMS), take_flag(FLAG_TURBO));
+
+    if etching {
+        let rune = take(&mut fields, TAG_RUNE, |[rune]| Some(rune));
+        let spacers = take(&mut fields, TAG_SPACERS, |[spacers]| (spacers <= MAX_SPACERS).then_some(spacers as u32));
+        let premine = take(&mut fields, TAG_PREMINE, |[premine]| Some(premine));
+        let terms = terms.then(|| {
+            let cap = take(&mut fields, TAG_CAP, |[cap]| Some(cap));
+            let amount = take(&mut fields, TAG_AMOUNT, |[amount]| Some(amount));
+            (cap, amount, [TAG_HEIGHT_START, TAG_HEIGHT_END, TAG_OFFSET_START, TAG_OFFSET_END]
+                .map(|tag| take(&mut fields, tag, |[value]| u64::try_from(value).ok())))
+        });
+        let supply = terms.as_ref().map_or(Some(0), |(cap, amount, _)| cap.unwrap_or(0).checked_mul(amount.unwrap_or(0)));
+        if supply.and_then(|supply| supply.checked_add(premine.unwrap_or(0))).is_none() {
+            decoding.flaws.push("supply_overflow".to_string());
+        }
+
+        let name = rune.map(rune_name);
+        decoding.etching = Some(RunestoneEtching {
+            rune: rune.map(|rune| rune.to_string()),
+            spaced_name: name.as_ref().map(|name| spaced(name, spacers.unwrap_or(0))),
+            name,
+            divisibility: take(&mut fields, TAG_DIVISIBILITY, |[d]| (d <= MAX_DIVISIBILITY).then_some(d as u8)),
+            spacers,
+            symbol: take(&mut fields, TAG_SYMBOL, |[symbol]| char::from_u32(u32::try_from(symbol).ok()?)).map(String::from),
+            premine: premine.map(|premine| premine.to_string()),
+            terms: terms.map(|(cap, amount, [height_start, height_end, offset_start, offset_end])| RunestoneTerms {
+                cap: cap.map(|cap| cap.to_string()),
+                amount: amount.map(|amount| amount.to_string()),
+                height_start,
+                height_end,
+                offset_start,
+                offset_end,
+            }),
+            turbo,
+        });
+    }
+
+    decoding.mint = take(&mut fields, TAG_MINT, |[block, tx]| rune_id(block, tx)).map(|(block, tx)| format!("{}:{}", block, tx));
+    decoding.pointer = take(&mut fields, TAG_POINTER, |[pointer]| {
+        let pointer = u32::try_from(pointer).ok()?;
+        output_count.is_none_or(|count| (pointer as usize) < count).then_some(pointer)
+    });
+
+    if flags != 0 {
+        decoding.flaws.push("unrecognized_flag".to_string());
+    }
+    if fields.keys().any(|tag| tag % 2 == 0) {
+        decoding.flaws.push("unrecognized_even_tag".to_string());
+    }
+}
+
+/// Edicts are groups of four: rune id as deltas from the previous edict's, amount and
+/// output. An output equal to the output count splits the amount across all of them.
+fn decode_edicts(integers: &[u128], output_count: Option<usize>, decoding: &mut RunestoneDecoding) {
+    let (mut block, mut tx) = (0u64, 0u32);
+    for chunk in integers.chunks(4) {
+        let [block_delta, tx_delta, amount, output] = chunk else {
+            decoding.flaws.push("trailing_integers".to_string());
+            return;
+        };
+        let next = u64::try_from(*block_delta).ok().zip(u32::try_from(*tx_delta).ok()).and_then(|(block_delta, tx_delta)| {
+            let next_block = block.checked_add(block_delta)?;
+            let next_tx = if block_delta == 0 { tx.checked_add(tx_delta)? } else { tx_delta };
+            rune_id(next_block as u128, next_tx as u128)
+        });
+        let Some(next) = next else {
+            decoding.flaws.push("edict_rune_id".to_string());
+            return;
+        };
+        let Some(output) = u32::try_from(*output).ok().filter(|&output| output_count.is_none_or(|count| output as usize <= count)) else {
+            decoding.flaws.push("edict_output".to_string());
+            return;
+        };
+        (block, tx) = next;
+        decoding.edicts.push(RunestoneEdict {
+            id: format!("{}:{}", block, tx),
+            amount: amount.to_string(),
+            output,
+        });
+    }
+}
+
+/// Takes the first `N` values of a tag if `with` accepts them, leaving them otherwise.
+fn take<const N: usize, T>(fields: &mut BTreeMap<u128, VecDeque<u128>>, tag: u128, with: impl Fn([u128; N]) -> Option<T>) -> Option<T> {
+    let values = fields.get_mut(&tag)?;
+    if values.len() < N {
+        return None;
+    }
+    let mut taken = [0; N];
+    for (slot, value) in taken.iter_mut().zip(values.iter()) {
+        *slot = *value;
+    }
+    let result = with(taken)?;
+    values.drain(..N);
+    if values.is_empty() {
+        fields.remove(&tag);
+    }
+    Some(result)
+}
+
+/// Block 0 only holds the reserved id 0:0.
+fn rune_id(block: u128, tx: u128) -> Option<(u64, u32)> {
+    let (block, tx) = (u64::try_from(block).ok()?, u32::try_from(tx).ok()?);
+    (block > 0 || tx == 0).then_some((block, tx))
+}
+
+/// Bijective base 26: 0 is A, 25 is Z, 26 is AA.
+pub fn rune_name(rune: u128) -> String {
+    if rune == u128::MAX {
+        return "BCGDENLQRQWDSLRUGSNLBTMFIJAV".to_string();
+    }
+    let mut n = rune + 1;
+    let mut letters = Vec::new();
+    while n > 0 {
+        letters.push(b'A' + ((n - 1) % 26) as u8);
+        n = (n - 1) / 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).expect("ASCII letters")
+}
+
+fn spaced(name: &str, spacers: u32) -> String {
+    let mut spaced = String::with_capacity(name.len() * 2);
+    for (i, letter) in name.chars().enumerate() {
+        spaced.push(letter);
+        if i + 1 < name.len() && spacers & (1 << i) != 0 {
+            spaced.push('•');
+        }
+    }
+    spaced
+}
+
+fn tag_name(tag: u128) -> Option<&'static str> {
+    Some(match tag {
+        TAG_DIVISIBILITY => "divisibility",
+        TAG_FLAGS => "flags",
+        TAG_SPACERS => "spacers",
+        TAG_RUNE => "rune",
+        TAG_SYMBOL => "symbol",
+        TAG_PREMINE => "premine",
+        TAG_CAP => "cap",
+        TAG_AMOUNT => "amount",
+        TAG_HEIGHT_START => "height_start",
+        TAG_HEIGHT_END => "height_end",
+        TAG_OFFSET_START => "offset_start",
+        TAG_OFFSET_END => "offset_end",
+        TAG_MINT => "mint",
+        TAG_POINTER => "pointer",
+        126 => "cenotaph",
+        127 => "nop",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `OP_RETURN OP_13` and one push of `payload`.
+    fn runestone(payload: &[u8]) -> ScriptBuf {
+        let mut bytes = vec![0x6a, 0x5d, payload.len() as u8];
+        bytes.extend_from_slice(payload);
+        ScriptBuf::from_bytes(bytes)
+    }
+
+    fn decode(payload: &[u8], output_count: Option<usize>) -> RunestoneDecoding {
+        decode_scripts(&[ScriptBuf::new_op_return([0u8; 4]), runestone(payload)], output_count)
+    }
+
+    #[test]
+    fn valid_edict_is_not_a_cenotaph() {
+        // Body, then rune 840000:3, amount 1000, to output 1.
+        let decoding = decode(&[0, 0xc0, 0xa2, 0x33, 3, 0xe8, 0x07, 1], Some(2));
+        assert!(decoding.found);
+        assert_eq!(decoding.output_index, Some(1));
+        assert!(!decoding.cenotaph, "{:?}", decoding.flaws);
+        assert_eq!(decoding.edicts.len(), 1);
+        assert_eq!(decoding.edicts[0].id, "840000:3");
+        assert_eq!(decoding.edicts[0].amount, "1000");
+    }
+
+    #[test]
+    fn truncated_varint_is_a_cenotaph() {
+        // The flags field's value has its continuation bit set and nothing after it.
+        let decoding = decode(&[2, 0x81], None);
+        assert!(decoding.cenotaph);
+        assert_eq!(decoding.flaws, ["varint_truncated"]);
+        assert_eq!(decoding.integers, ["2"]);
+        assert!(decoding.fields.is_empty());
+    }
+
+    #[test]
+    fn unknown_even_tag_is_a_cenotaph_and_odd_is_ignored() {
+        let decoding = decode(&[126, 1], None);
+        assert!(decoding.cenotaph);
+        assert_eq!(decoding.flaws, ["unrecognized_even_tag"]);
+        assert_eq!(decoding.fields[0].name.as_deref(), Some("cenotaph"));
+
+        let decoding = decode(&[101, 1], None);
+        assert!(!decoding.cenotaph, "{:?}", decoding.flaws);
+        assert_eq!(decoding.fields[0].tag, "101");
+        assert_eq!(decoding.fields[0].name, None);
+    }
+
+    #[test]
+    fn invalid_edicts_are_cenotaphs() {
+        // Block 0 only has the reserved id 0:0, so 0:1 can't be a rune.
+        let decoding = decode(&[0, 0, 1, 10, 0], Some(2));
+        assert!(decoding.cenotaph);
+        assert_eq!(decoding.flaws, ["edict_rune_id"]);
+        assert!(decoding.edicts.is_empty());
+
+        // Output 2 is the split-to-all marker for two outputs; 3 is past it.
+        let decoding = decode(&[0, 1, 0, 10, 3], Some(2));
+        assert!(decoding.cenotaph);
+        assert_eq!(decoding.flaws, ["edict_output"]);
+
+        let decoding = decode(&[0, 1, 0, 10], Some(2));
+        assert_eq!(decoding.flaws, ["trailing_integers"]);
+    }
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::script::Instruction;
+use bitcoin::Script;
+use crate::script::output_script_type;
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+/// Consensus: largest single push (and, for P2SH, the serialized redeem script).
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Consensus: largest legacy or witness v0 script.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Consensus: most non-push opcodes in a legacy or witness v0 script.
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Policy: largest standard P2WSH witness script.
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3_600;
+/// Policy: most sigops in a standard P2SH redeem script.
+pub const MAX_P2SH_SIGOPS: usize = 15;
+
+pub const SCRIPT_CONTEXTS: [&str; 4] = ["bare", "p2sh", "p2wsh", "tapscript"];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStats {
+    pub context: String,
+    pub size: usize,
+    #[serde(alias = "push_count")]
+    pub push_count: usize,
+    #[serde(alias = "non_push_opcodes")]
+    pub non_push_opcodes: usize,
+    #[serde(alias = "largest_push")]
+    pub largest_push: usize,
+    #[serde(alias = "legacy_sigops")]
+    pub legacy_sigops: usize,
+    #[serde(alias = "witness_sigops")]
+    pub witness_sigops: usize,
+    #[serde(alias = "sigop_cost")]
+    pub sigop_cost: usize,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[wasm_bindgen]
+pub fn script_stats(script_hex: &str, context: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(script_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid script hex: {}", e)))?;
+    let stats = analyze_script(Script::from_bytes(&bytes), context).map_err(|e| JsValue::from_str(&e))?;
+
+    to_json(&stats, compact)
+}
+
+/// Sizes, sigops and limit checks for a script used as a bare scriptPubKey, a P2SH
+/// redeem script, a P2WSH witness script or a tapscript leaf. `errors` are consensus
+/// failures (never spendable); `warnings` are policy failures (won't relay).
+pub fn analyze_script(script: &Script, context: &str) -> Result<ScriptStats, String> {
+    if !SCRIPT_CONTEXTS.contains(&context) {
+        return Err(format!("Unknown script context \"{}\" (expected bare, p2sh, p2wsh or tapscript)", context));
+    }
+
+    let mut push_count = 0;
+    let mut non_push_opcodes = 0;
+    let mut largest_push = 0;
+    for instruction in script.instructions() {
+        match instruction.map_err(|e| format!("Invalid script: {}", e))? {
+            Instruction::PushBytes(bytes) => {
+                push_count += 1;
+                largest_push = largest_push.max(bytes.len());
+            }
+            // OP_1NEGATE and OP_1..OP_16 push numbers and don't count towards the opcode limit.
+            Instruction::Op(op) if op.to_u8() <= 0x60 => push_count += 1,
+            Instruction::Op(_) => non_push_opcodes += 1,
+        }
+    }
+
+    let legacy_sigops = script.count_sigops_legacy();
+    let accurate_sigops = script.count_sigops();
+    let (witness_sigops, sigop_cost) = match context {
+        "bare" => (0, legacy_sigops * 4),
+        "p2sh" => (0, accurate_sigops * 4),
+        "p2wsh" => (accurate_sigops, accurate_sigops),
+        // Tapscript replaces sigop counting with a per-input validation weight budget.
+        _ => (0, 0),
+    };
+
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let size = script.len();
+
+    if largest_push > MAX_SCRIPT_ELEMENT_SIZE {
+        errors.push(format!("contains a {}-byte push, above the {}-byte element limit", largest_push, MAX_SCRIPT_ELEMENT_SIZE));
+    }
+    if context != "tapscript" {
+        if size > MAX_SCRIPT_SIZE {
+            errors.push(format!("script is {} bytes, above the {}-byte limit", size, MAX_SCRIPT_SIZE));
+        }
+        if non_push_opcodes > MAX_OPS_PER_SCRIPT {
+            errors.push(format!("has {} non-push opcodes, above the limit of {}", non_push_opcodes, MAX_OPS_PER_SCRIPT));
+        }
+    }
+
+    match context {
+        "bare" if output_script_type(script) == "nonstandard" && !is_standard_bare_multisig(script) => {
+            warnings.push("bare script is not a standard output template, so it will not be relayed".to_string());
+        }
+        "p2sh" => {
+            if size > MAX_SCRIPT_ELEMENT_SIZE {
+                errors.push(format!(
+                    "redeem script is {} bytes, above the {}-byte P2SH limit",
+                    size, MAX_SCRIPT_ELEMENT_SIZE
+                ));
+            }
+            if accurate_sigops > MAX_P2SH_SIGOPS {
+                warnings.push(format!("redeem script has {} sigops, above the standard limit of {}", accurate_sigops, MAX_P2SH_SIGOPS));
+            }
+        }
+        "p2wsh" if size > MAX_STANDARD_P2WSH_SCRIPT_SIZE => {
+            warnings.push(format!(
+                "witness script is {} bytes, above the {}-byte standardness limit",
+                size, MAX_STANDARD_P2WSH_SCRIPT_SIZE
+            ));
+        }
+        _ => {}
+    }
+
+    Ok(ScriptStats {
+        context: context.to_string(),
+        size,
+        push_count,
+        non_push_opcodes,
+        largest_push,
+        legacy_sigops,
+        witness_sigops,
+        sigop_cost,
+        errors,
+        warnings,
+    })
+}
+
+/// For builders: refuses scripts that could never be spent or would not be relayed.
+pub fn check_script(script: &Script, context: &str) -> Result<(), String> {
+    let stats = analyze_script(script, context)?;
+    match stats.errors.first().or(stats.warnings.first()) {
+        Some(problem) => Err(format!("Refusing to build {} script: {}", context, problem)),
+        None => Ok(()),
+    }
+}
+
+fn is_standard_bare_multisig(script: &Script) -> bool {
+    // Policy only relays bare multisig with up to three keys.
+    crate::script::parse_multisig(script).is_some_and(|info| info.pubkeys.len() <= 3)
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::selection::input_vbytes;
+use crate::transaction::{check_amount_total, TransactionInput};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+
+pub const COINBASE_MATURITY: u32 = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Balance {
+    #[serde(with = "crate::utils::amount")]
+    pub confirmed: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub unconfirmed: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub immature: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub total: u64,
+    #[serde(default, alias = "spendable_at")]
+    pub spendable_at: Option<u64>,
+}
+
+#[wasm_bindgen]
+pub fn compute_balance(utxos_json: &str, tip_height: u32, fee_rate_sat_vb: Option<f64>, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("compute_balance", utxos_json.len(), || {
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+        check_amount_total(utxos.iter().map(|utxo| utxo.amount), "utxos").map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = fee_rate_sat_vb.map(FeeRate::from_sat_per_vb).transpose().map_err(|e| JsValue::from_str(&e))?;
+
+        let mut balance = Balance {
+            confirmed: 0,
+            unconfirmed: 0,
+            immature: 0,
+            total: 0,
+            spendable_at: fee_rate.map(|_| 0),
+        };
+
+        for utxo in &utxos {
+            balance.total += utxo.amount;
+            if is_immature(utxo, tip_height) {
+                balance.immature += utxo.amount;
+                continue;
+            }
+            match utxo.height {
+                Some(height) if height <= tip_height => balance.confirmed += utxo.amount,
+                _ => balance.unconfirmed += utxo.amount,
+            }
+            if let (Some(rate), Some(spendable)) = (fee_rate, balance.spendable_at.as_mut()) {
+                // What the UTXO contributes after paying for its own input; dust at this rate adds nothing.
+                *spendable += utxo.amount.saturating_sub(rate.fee_for_vbytes(input_vbytes(utxo)?).to_sat());
+            }
+        }
+
+        to_json_with_amounts(&balance, amount_format, compact)
+    })
+}
+
+/// Refuses to spend a coinbase output before it matures, which consensus forbids.
+/// Without a tip height its maturity can't be known, so that's refused too.
+pub fn check_maturity(utxo: &TransactionInput, tip_height: Option<u32>) -> Result<(), String> {
+    if !utxo.is_coinbase {
+        return Ok(());
+    }
+    let tip_height = tip_height.ok_or_else(|| {
+        format!("{}:{} is a coinbase output; tip_height is needed to check its maturity", utxo.txid, utxo.vout)
+    })?;
+    if is_immature(utxo, tip_height) {
+        return Err(format!(
+            "{}:{} is an immature coinbase output ({} of {} confirmations); set allow_immature_coinbase to spend it anyway",
+            utxo.txid,
+            utxo.vout,
+            crate::selection::confirmations(utxo, tip_height),
+            COINBASE_MATURITY
+        ));
+    }
+    Ok(())
+}
+
+/// A coinbase output can be spent once it has `COINBASE_MATURITY` confirmations.
+pub fn is_immature(utxo: &TransactionInput, tip_height: u32) -> bool {
+    if !utxo.is_coinbase {
+        return false;
+    }
+    match utxo.height {
+        Some(height) if height <= tip_height => tip_height - height + 1 < COINBASE_MATURITY,
+        _ => true,
+    }
+}
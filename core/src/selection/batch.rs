@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::ScriptBuf;
+use crate::selection::split::split_exact;
+use crate::selection::{output_vbytes, select, SelectedCoin, SelectionOptions, OUTPUT_VBYTES};
+use crate::transaction::{check_amount_total, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::ordering::{apply_order, parse_ordering, transaction_order};
+use crate::transaction::weight::estimated_signed_vsize;
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::network::{network_or_default, require_address_network, NetworkParams};
+use crate::utils::metrics::timed;
+use crate::utils::units::{Amount, FeeRate};
+
+#[derive(Deserialize)]
+pub struct Recipient {
+    pub address: String,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentConfirmation {
+    #[serde(alias = "output_index")]
+    pub output_index: usize,
+    pub address: String,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(with = "crate::utils::amount", alias = "original_amount")]
+    pub original_amount: u64,
+    pub label: Option<String>,
+    pub lines: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchPayment {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(with = "crate::utils::amount", alias = "total_amount")]
+    pub total_amount: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub change: u64,
+    #[serde(alias = "change_vout")]
+    pub change_vout: Option<usize>,
+    /// Older name for `change_vout`.
+    #[serde(alias = "change_index")]
+    pub change_index: Option<usize>,
+    pub recipients: Vec<PaymentConfirmation>,
+    pub selected: Vec<SelectedCoin>,
+}
+
+/// `subtract_fee_from_outputs` lists recipients (0-based, after duplicates are merged)
+/// whose amounts pay the fee instead of the coins, split by `subtract_fee_mode`.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchOptions {
+    #[serde(default, alias = "subtract_fee_from_outputs")]
+    pub subtract_fee_from_outputs: Vec<usize>,
+    #[serde(default, alias = "subtract_fee_mode")]
+    pub subtract_fee_mode: Option<String>,
+    /// Needed to check coinbase maturity when a UTXO is a coinbase output.
+    #[serde(default, alias = "tip_height")]
+    pub tip_height: Option<u32>,
+    #[serde(default, alias = "allow_immature_coinbase")]
+    pub allow_immature_coinbase: bool,
+}
+
+#[derive(Clone, Copy)]
+enum FeeSplit {
+    Proportional,
+    Even,
+}
+
+struct Payment {
+    address: String,
+    script_pubkey: ScriptBuf,
+    amount: u64,
+    label: Option<String>,
+    lines: Vec<usize>,
+}
+
+/// Payroll-style send: one output per recipient plus change. `recipients` is either a
+/// JSON array of `{address, amountSat, label}` or CSV text with `address,amount[,label]`
+/// lines. Every bad line is reported, so a spreadsheet can be fixed in one pass.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn build_batch_payment(
+    recipients: &str,
+    utxos_json: &str,
+    fee_rate_sat_vb: f64,
+    change_address: &str,
+    network: Option<String>,
+    duplicates: Option<String>,
+    ordering: Option<String>,
+    options_json: Option<String>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("build_batch_payment", recipients.len() + utxos_json.len() + change_address.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let batch_options: BatchOptions = match options_json {
+            Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+            _ => BatchOptions::default(),
+        };
+        let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+        let ordering = parse_ordering(ordering.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let merge_duplicates = match duplicates.as_deref().map(str::trim) {
+            None | Some("") | Some("error") => false,
+            Some("merge") => true,
+            Some(other) => {
+                return Err(JsValue::from_str(&format!(
+                    "Unknown duplicates mode \"{}\" (expected error or merge)",
+                    other
+                )))
+            }
+        };
+
+        let payments = parse_recipients(recipients, &network, merge_duplicates).map_err(|e| JsValue::from_str(&e))?;
+        require_address_network(change_address, Some(&network))
+            .map_err(|e| JsValue::from_str(&format!("Change address: {}", e)))?;
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+
+        let total_amount = check_amount_total(payments.iter().map(|p| p.amount), "recipients").map_err(|e| JsValue::from_str(&e))?;
+
+        let subtract_from = subtract_fee_indexes(&batch_options, payments.len()).map_err(|e| JsValue::from_str(&e))?;
+        let split = parse_fee_split(batch_options.subtract_fee_mode.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+
+        let options = SelectionOptions {
+            recipient_count: payments.len(),
+            tip_height: batch_options.tip_height,
+            allow_immature_coinbase: batch_options.allow_immature_coinbase,
+            ..SelectionOptions::default()
+        };
+        let (selection, extra_fee) = if subtract_from.is_empty() {
+            // Selection prices every recipient as a P2WPKH output; wider scripts pay the difference up front.
+            let extra_vbytes: f64 = payments
+                .iter()
+                .map(|p| (output_vbytes(&p.script_pubkey) - OUTPUT_VBYTES).max(0.0))
+                .sum();
+            let extra_fee = fee_rate.fee_for_vbytes(extra_vbytes).to_sat();
+            (select(&utxos, Amount::from_sat(total_amount + extra_fee), fee_rate, fee_rate, &options), extra_fee)
+        } else {
+            // The recipients pay the fee, so coins only have to cover the amounts, at full value.
+            (select(&utxos, Amount::from_sat(total_amount), FeeRate::ZERO, FeeRate::ZERO, &options), 0)
+        };
+        let selection = selection.map_err(|e| JsValue::from_str(&e))?;
+
+        let inputs: Vec<TransactionInput> = selection
+            .selected
+            .iter()
+            .filter_map(|coin| utxos.iter().find(|utxo| utxo.txid == coin.txid && utxo.vout == coin.vout).cloned())
+            .collect();
+        let mut outputs: Vec<TransactionOutput> = payments
+            .iter()
+            .map(|p| TransactionOutput {
+                address: Some(p.address.clone()),
+                amount: p.amount,
+                is_change: false,
+                script_pubkey: None,
+            })
+            .collect();
+        let change = if selection.changeless {
+            None
+        } else {
+            outputs.push(TransactionOutput {
+                address: Some(change_address.trim().to_string()),
+                amount: selection.change,
+                is_change: true,
+                script_pubkey: None,
+            });
+            Some(outputs.len() - 1)
+        };
+
+        let mut tx = unsigned_transaction(&inputs, &outputs, Some(&network))?;
+        let fee = if subtract_from.is_empty() {
+            selection.fee + extra_fee
+        } else {
+            // Without change, whatever the coins hold beyond the amounts already goes to the fee.
+            let leftover = selection.total_selected - total_amount - selection.change;
+            let fee = fee_rate.fee_for_vbytes(estimated_signed_vsize(&tx, &inputs) as f64).to_sat().max(leftover);
+            let remaining = deduct_fee(fee - leftover, &payments, &subtract_from, split).map_err(|e| JsValue::from_str(&e))?;
+            for (&index, amount) in subtract_from.iter().zip(remaining) {
+                tx.output[index].value = bitcoin::Amount::from_sat(amount);
+            }
+            fee
+        };
+
+        let (input_order, output_order) = transaction_order(&tx, ordering, change);
+        let amounts: Vec<u64> = tx.output.iter().map(|output| output.value.to_sat()).collect();
+        apply_order(&mut tx, &input_order, &output_order);
+        let vout_of = |index: usize| output_order.iter().position(|&i| i == index).expect("every output is ordered");
+        let change_vout = change.map(vout_of);
+        let tx_bytes = finalize(&tx, Funding::Unsigned(&inputs), &checks).map_err(|e| JsValue::from_str(&e))?;
+
+        let result = BatchPayment {
+            tx_hex: bytes_to_hex(&tx_bytes),
+            total_amount,
+            fee,
+            change: selection.change,
+            change_vout,
+            change_index: change_vout,
+            recipients: payments
+                .into_iter()
+                .enumerate()
+                .map(|(index, p)| PaymentConfirmation {
+                    output_index: vout_of(index),
+                    address: p.address,
+                    amount: amounts[index],
+                    original_amount: p.amount,
+                    label: p.label,
+                    lines: p.lines,
+                })
+                .collect(),
+            selected: selection.selected,
+        };
+
+        to_json_with_amounts(&result, amount_format, compact)
+    })
+}
+
+fn subtract_fee_indexes(options: &BatchOptions, recipient_count: usize) -> Result<Vec<usize>, String> {
+    let mut indexes = Vec::new();
+    for &index in &options.subtract_fee_from_outputs {
+        if index >= recipient_count {
+            return Err(format!(
+                "subtract_fee_from_outputs index {} is out of range for {} recipients",
+                index, recipient_count
+            ));
+        }
+        if indexes.contains(&index) {
+            return Err(format!("subtract_fee_from_outputs lists recipient {} twice", index));
+        }
+        indexes.push(index);
+    }
+    Ok(indexes)
+}
+
+fn parse_fee_split(mode: Option<&str>) -> Result<FeeSplit, String> {
+    match mode.map(|m| m.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("proportional") => Ok(FeeSplit::Proportional),
+        Some("even") => Ok(FeeSplit::Even),
+        Some(other) => Err(format!("Unknown subtract_fee_mode \"{}\" (expected proportional or even)", other)),
+    }
+}
+
+/// What each recipient in `subtract_from` receives after paying its share of `fee`, in
+/// the same order. Fails if a share would leave a recipient below its dust limit.
+fn deduct_fee(fee: u64, payments: &[Payment], subtract_from: &[usize], split: FeeSplit) -> Result<Vec<u64>, String> {
+    let amounts: Vec<u64> = subtract_from.iter().map(|&i| payments[i].amount).collect();
+    subtract_from
+        .iter()
+        .zip(split_fee(fee, &amounts, split))
+        .map(|(&index, share)| {
+            let payment = &payments[index];
+            let dust = payment.script_pubkey.minimal_non_dust().to_sat();
+            payment.amount.checked_sub(share).filter(|&amount| amount >= dust).ok_or_else(|| {
+                format!(
+                    "Recipient {} ({}) can't pay its {} sat share of the fee: it would receive {} sats, below the {} sat dust limit",
+                    index,
+                    payment.address,
+                    share,
+                    payment.amount.saturating_sub(share),
+                    dust
+                )
+            })
+        })
+        .collect()
+}
+
+/// Each output's share of `fee`, summing to exactly `fee`. Rounding leaves fewer sats
+/// than there are outputs; they go one each to the first outputs, as Bitcoin Core
+/// gives its remainder to the first.
+fn split_fee(fee: u64, amounts: &[u64], split: FeeSplit) -> Vec<u64> {
+    let weights = match split {
+        FeeSplit::Even => vec![1; amounts.len()],
+        FeeSplit::Proportional => amounts.to_vec(),
+    };
+    split_exact(fee, &weights).0
+}
+
+/// Each recipient is tagged with its CSV line number, or its 1-based position in a JSON array.
+fn parse_recipients(input: &str, network: &NetworkParams, merge_duplicates: bool) -> Result<Vec<Payment>, String> {
+    let rows: Vec<(usize, Result<Recipient, String>)> = if input.trim_start().starts_with('[') {
+        parse_json_list::<Recipient>(input, "recipients")?
+            .into_iter()
+            .enumerate()
+            .map(|(index, recipient)| (index + 1, Ok(recipient)))
+            .collect()
+    } else {
+        parse_csv(input)
+    };
+
+    let mut payments: Vec<Payment> = Vec::new();
+    let mut by_script: HashMap<ScriptBuf, usize> = HashMap::new();
+    let mut problems = Vec::new();
+    for (line, row) in rows {
+        let recipient = match row {
+            Ok(recipient) => recipient,
+            Err(e) => {
+                problems.push(format!("line {}: {}", line, e));
+                continue;
+            }
+        };
+        let script_pubkey = match require_address_network(&recipient.address, Some(network)) {
+            Ok(script_pubkey) => script_pubkey,
+            Err(e) => {
+                problems.push(format!("line {}: {}", line, e));
+                continue;
+            }
+        };
+        let dust = script_pubkey.minimal_non_dust().to_sat();
+        if recipient.amount < dust {
+            problems.push(format!(
+                "line {}: amount {} is below the {} sat dust limit for {}",
+                line, recipient.amount, dust, recipient.address
+            ));
+            continue;
+        }
+
+        match by_script.get(&script_pubkey) {
+            Some(&index) if merge_duplicates => {
+                let existing = &mut payments[index];
+                existing.amount = existing.amount.saturating_add(recipient.amount);
+                existing.lines.push(line);
+            }
+            Some(&index) => problems.push(format!(
+                "line {}: {} is already paid on line {}",
+                line, recipient.address, payments[index].lines[0]
+            )),
+            None => {
+                by_script.insert(script_pubkey.clone(), payments.len());
+                payments.push(Payment {
+                    address: recipient.address.trim().to_string(),
+                    script_pubkey,
+                    amount: recipient.amount,
+                    label: recipient.label,
+                    lines: vec![line],
+                });
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems.join("; "));
+    }
+    if payments.is_empty() {
+        return Err("No recipients given".to_string());
+    }
+    Ok(payments)
+}
+
+/// `address,amount[,label]` per line; blank lines, `#` comments and an `address,...`
+/// header row are skipped. The label keeps any further commas.
+fn parse_csv(input: &str) -> Vec<(usize, Result<Recipient, String>)> {
+    let mut rows = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.splitn(3, ',').map(|field| field.trim().trim_matches('"').trim()).collect();
+        if rows.is_empty() && fields[0].eq_ignore_ascii_case("address") {
+            continue;
+        }
+
+        let row = match fields.as_slice() {
+            [address, amount, rest @ ..] => amount
+                .parse::<u64>()
+                .map_err(|_| format!("amount \"{}\" is not a whole number of satoshis", amount))
+                .map(|amount| Recipient {
+                    address: address.to_string(),
+                    amount,
+                    label: rest.first().filter(|label| !label.is_empty()).map(|label| label.to_string()),
+                }),
+            _ => Err("expected address,amount[,label]".to_string()),
+        };
+        rows.push((line_number, row));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::deserialize_hex;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::{Address, CompressedPublicKey, Network, Transaction};
+
+    fn address(byte: u8) -> Address {
+        let secret = SecretKey::from_slice(&[byte; 32]).unwrap();
+        Address::p2wpkh(&CompressedPublicKey(secret.public_key(&Secp256k1::new())), Network::Testnet)
+    }
+
+    fn pay(amounts: &[u64], coin: u64, options: &str) -> (BatchPayment, Transaction) {
+        let recipients: Vec<String> = amounts
+            .iter()
+            .enumerate()
+            .map(|(index, amount)| format!(r#"{{"address": "{}", "amountSat": {}}}"#, address(index as u8 + 1), amount))
+            .collect();
+        let utxos = format!(
+            r#"[{{"txid": "{}", "vout": 0, "amountSat": {}, "scriptPubkey": "{}"}}]"#,
+            "ab".repeat(32),
+            coin,
+            address(0x99).script_pubkey().to_hex_string()
+        );
+        let json = build_batch_payment(
+            &format!("[{}]", recipients.join(",")),
+            &utxos,
+            5.0,
+            &address(0x77).to_string(),
+            None,
+            None,
+            None,
+            Some(options.to_string()),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let payment: BatchPayment = serde_json::from_str(&json).unwrap();
+        let tx = deserialize_hex(&payment.tx_hex).unwrap();
+        (payment, tx)
+    }
+
+    fn payments(amounts: &[u64]) -> Vec<Payment> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(index, &amount)| Payment {
+                address: address(index as u8 + 1).to_string(),
+                script_pubkey: address(index as u8 + 1).script_pubkey(),
+                amount,
+                label: None,
+                lines: vec![index + 1],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn change_is_untouched_when_recipients_pay_the_fee() {
+        let (payment, tx) = pay(&[100_000, 50_000], 300_000, r#"{"subtractFeeFromOutputs": [0]}"#);
+        let outputs: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+        assert_eq!(outputs + payment.fee, 300_000);
+        assert_eq!(payment.change, 150_000);
+        assert_eq!(tx.output[payment.change_vout.unwrap()].value.to_sat(), 150_000);
+        // 5 sat/vB on a one-input, three-output P2WPKH transaction.
+        assert!((800..900).contains(&payment.fee), "{}", payment.fee);
+    }
+
+    #[test]
+    fn reports_original_and_post_fee_amounts() {
+        let (payment, tx) = pay(&[100_000, 50_000], 300_000, r#"{"subtractFeeFromOutputs": [0]}"#);
+        let [payer, other] = &payment.recipients[..] else { panic!("two recipients") };
+        assert_eq!((payer.original_amount, payer.amount), (100_000, 100_000 - payment.fee));
+        assert_eq!((other.original_amount, other.amount), (50_000, 50_000));
+        assert_eq!(tx.output[payer.output_index].value.to_sat(), payer.amount);
+        assert_eq!(payment.total_amount, 150_000);
+    }
+
+    #[test]
+    fn proportional_and_even_deduction() {
+        assert_eq!(split_fee(1_001, &[100_000, 300_000], FeeSplit::Proportional), [251, 750]);
+        assert_eq!(split_fee(1_001, &[100_000, 300_000], FeeSplit::Even), [501, 500]);
+
+        let (proportional, _) = pay(&[100_000, 300_000], 500_000, r#"{"subtractFeeFromOutputs": [0, 1]}"#);
+        let (even, _) = pay(&[100_000, 300_000], 500_000, r#"{"subtractFeeFromOutputs": [0, 1], "subtractFeeMode": "even"}"#);
+        for (payment, split) in [(&proportional, FeeSplit::Proportional), (&even, FeeSplit::Even)] {
+            let paid: Vec<u64> = payment.recipients.iter().map(|r| r.original_amount - r.amount).collect();
+            assert_eq!(paid, split_fee(payment.fee, &[100_000, 300_000], split));
+            assert_eq!(paid.iter().sum::<u64>(), payment.fee);
+        }
+    }
+
+    #[test]
+    fn share_below_dust_is_an_error() {
+        let payments = payments(&[100_000, 600]);
+        assert_eq!(deduct_fee(1_000, &payments, &[0, 1], FeeSplit::Even), Err(
+            "Recipient 1 (".to_string() + &payments[1].address + ") can't pay its 500 sat share of the fee: it would receive 100 sats, below the 294 sat dust limit"
+        ));
+        assert_eq!(deduct_fee(1_000, &payments, &[0, 1], FeeSplit::Proportional), Ok(vec![99_005, 595]));
+        assert!(deduct_fee(1_000, &payments, &[1], FeeSplit::Even).unwrap_err().contains("it would receive 0 sats"));
+    }
+}
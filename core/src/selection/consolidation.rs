@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::selection::balance::check_maturity;
+use crate::selection::{input_vbytes, output_vbytes, MIN_CHANGE_SAT, TX_OVERHEAD_VBYTES};
+use crate::transaction::{address_script, check_amount_total, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::network_or_default;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidationTransaction {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(alias = "input_count")]
+    pub input_count: usize,
+    #[serde(with = "crate::utils::amount", alias = "input_total")]
+    pub input_total: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    #[serde(with = "crate::utils::amount", alias = "output_amount")]
+    pub output_amount: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UneconomicalUtxo {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(with = "crate::utils::amount", alias = "input_cost")]
+    pub input_cost: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidationPlan {
+    pub transactions: Vec<ConsolidationTransaction>,
+    #[serde(with = "crate::utils::amount", alias = "total_reclaimed")]
+    pub total_reclaimed: u64,
+    #[serde(with = "crate::utils::amount", alias = "total_fees")]
+    pub total_fees: u64,
+    pub uneconomical: Vec<UneconomicalUtxo>,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn plan_consolidation(
+    utxos_json: &str,
+    fee_rate_sat_vb: f64,
+    max_inputs_per_tx: usize,
+    destination: &str,
+    network: Option<String>,
+    tip_height: Option<u32>,
+    allow_immature_coinbase: Option<bool>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("plan_consolidation", utxos_json.len() + destination.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        check_amount_total(utxos.iter().map(|utxo| utxo.amount), "utxos").map_err(|e| JsValue::from_str(&e))?;
+        if max_inputs_per_tx == 0 {
+            return Err(JsValue::from_str("max_inputs_per_tx must be at least 1"));
+        }
+
+        let destination_vbytes = output_vbytes(&address_script(destination, Some(&network))?);
+        let mut economical = Vec::new();
+        let mut uneconomical = Vec::new();
+        for utxo in utxos.into_iter().filter(|utxo| !utxo.frozen) {
+            if !allow_immature_coinbase.unwrap_or(false) {
+                check_maturity(&utxo, tip_height).map_err(|e| JsValue::from_str(&e))?;
+            }
+            let input_cost = fee_rate.fee_for_vbytes(input_vbytes(&utxo)?).to_sat();
+            if utxo.amount > input_cost {
+                economical.push(utxo);
+            } else {
+                uneconomical.push(UneconomicalUtxo {
+                    txid: utxo.txid,
+                    vout: utxo.vout,
+                    amount: utxo.amount,
+                    input_cost,
+                });
+            }
+        }
+        economical.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
+
+        let mut plan = ConsolidationPlan {
+            transactions: Vec::new(),
+            total_reclaimed: 0,
+            total_fees: 0,
+            uneconomical,
+        };
+
+        for chunk in economical.chunks(max_inputs_per_tx) {
+            let input_total: u64 = chunk.iter().map(|utxo| utxo.amount).sum();
+            let mut inputs_vbytes = 0.0;
+            for utxo in chunk {
+                inputs_vbytes += input_vbytes(utxo)?;
+            }
+            let vbytes = TX_OVERHEAD_VBYTES + inputs_vbytes + destination_vbytes;
+            let fee = fee_rate.fee_for_vbytes(vbytes).to_sat();
+
+            if input_total < fee + MIN_CHANGE_SAT {
+                for utxo in chunk {
+                    plan.uneconomical.push(UneconomicalUtxo {
+                        txid: utxo.txid.clone(),
+                        vout: utxo.vout,
+                        amount: utxo.amount,
+                        input_cost: fee_rate.fee_for_vbytes(input_vbytes(utxo)?).to_sat(),
+                    });
+                }
+                continue;
+            }
+
+            let output_amount = input_total - fee;
+            let output = TransactionOutput {
+                address: Some(destination.to_string()),
+                amount: output_amount,
+                is_change: false,
+                script_pubkey: None,
+            };
+            let tx = unsigned_transaction(chunk, &[output], Some(&network))?;
+            let tx_bytes = finalize(&tx, Funding::Unsigned(chunk), &checks)
+                .map_err(|e| JsValue::from_str(&format!("Consolidation transaction {}: {}", plan.transactions.len(), e)))?;
+
+            plan.total_reclaimed += output_amount;
+            plan.total_fees += fee;
+            plan.transactions.push(ConsolidationTransaction {
+                tx_hex: bytes_to_hex(&tx_bytes),
+                input_count: chunk.len(),
+                input_total,
+                fee,
+                output_amount,
+            });
+        }
+
+        to_json_with_amounts(&plan, amount_format, compact)
+    })
+}
@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::selection::{MIN_CHANGE_SAT, OUTPUT_VBYTES, TX_OVERHEAD_VBYTES};
+use crate::transaction::weight::{utxo_input_type, InputType};
+use crate::transaction::{check_amount_total, TransactionInput};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::units::FeeRate;
+
+/// A coin whose input costs at least this share of its value is marginal.
+const MARGINAL_COST_SHARE: f64 = 0.1;
+const P2TR_OUTPUT_VBYTES: f64 = 43.0;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DustUtxo {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "input_type")]
+    pub input_type: String,
+    #[serde(alias = "input_vbytes")]
+    pub input_vbytes: f64,
+    #[serde(with = "crate::utils::amount", alias = "cost_now")]
+    pub cost_now: u64,
+    #[serde(with = "crate::utils::amount", alias = "cost_later")]
+    pub cost_later: u64,
+    /// healthy, marginal or unspendable at the current feerate.
+    #[serde(alias = "status_now")]
+    pub status_now: String,
+    #[serde(alias = "status_later")]
+    pub status_later: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeRateHealth {
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: FeeRate,
+    pub healthy: usize,
+    pub marginal: usize,
+    pub unspendable: usize,
+    /// Value held in marginal and unspendable coins.
+    #[serde(with = "crate::utils::amount", alias = "value_at_risk")]
+    pub value_at_risk: u64,
+    #[serde(with = "crate::utils::amount", alias = "unspendable_value")]
+    pub unspendable_value: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidationAdvice {
+    /// consolidate_now, wait, or nothing_to_consolidate.
+    pub action: String,
+    #[serde(alias = "consolidate_now")]
+    pub consolidate_now: bool,
+    /// `txid:vout` of the coins the comparison covers.
+    pub candidates: Vec<String>,
+    #[serde(with = "crate::utils::amount", alias = "candidate_total")]
+    pub candidate_total: u64,
+    /// p2tr or p2wpkh: the output the candidates would be merged into.
+    #[serde(alias = "consolidated_output_type")]
+    pub consolidated_output_type: String,
+    #[serde(alias = "consolidation_vbytes")]
+    pub consolidation_vbytes: f64,
+    #[serde(with = "crate::utils::amount", alias = "consolidation_fee")]
+    pub consolidation_fee: u64,
+    #[serde(with = "crate::utils::amount", alias = "consolidated_amount")]
+    pub consolidated_amount: u64,
+    #[serde(with = "crate::utils::amount", alias = "consolidated_spend_cost")]
+    pub consolidated_spend_cost: u64,
+    /// What spending each candidate as its own input later costs in total, counting
+    /// only the candidates worth spending then.
+    #[serde(with = "crate::utils::amount", alias = "individual_spend_cost")]
+    pub individual_spend_cost: u64,
+    /// Value left after fees if the candidates are spent one by one later.
+    #[serde(with = "crate::utils::amount", alias = "individual_recovered")]
+    pub individual_recovered: u64,
+    /// Value left after consolidating now and spending the merged coin later.
+    #[serde(with = "crate::utils::amount", alias = "consolidated_recovered")]
+    pub consolidated_recovered: u64,
+    /// `consolidated_recovered - individual_recovered`.
+    #[serde(with = "crate::utils::amount::signed")]
+    pub savings: i64,
+    pub math: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DustReport {
+    pub utxos: Vec<DustUtxo>,
+    pub now: FeeRateHealth,
+    pub later: FeeRateHealth,
+    pub recommendation: ConsolidationAdvice,
+}
+
+/// How much of the wallet's value is tied up in coins that cost too much to spend, now
+/// and at an assumed future feerate, and whether merging the small ones now beats
+/// spending them one by one later. Each input is priced by its own type, so a taproot
+/// coin stays healthy at feerates where a legacy one of the same value doesn't.
+///
+/// A coin is unspendable when its input costs at least its value, and marginal when the
+/// input costs at least a tenth of it. The candidates for consolidation are the coins
+/// not healthy at the future rate that are still worth spending now.
+#[wasm_bindgen]
+pub fn dust_report(utxos_json: &str, current_fee_rate_sat_vb: f64, future_fee_rate_sat_vb: f64, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+    check_amount_total(utxos.iter().map(|utxo| utxo.amount), "utxos").map_err(|e| JsValue::from_str(&e))?;
+    let current_fee_rate = FeeRate::from_sat_per_vb(current_fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+    let future_fee_rate = FeeRate::from_sat_per_vb(future_fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+
+    let mut coins = Vec::new();
+    for utxo in utxos.iter().filter(|utxo| !utxo.frozen) {
+        let input_type = utxo_input_type(utxo).map_err(|e| JsValue::from_str(&e))?;
+        let cost_now = current_fee_rate.fee_for_vbytes(input_type.vbytes()).to_sat();
+        let cost_later = future_fee_rate.fee_for_vbytes(input_type.vbytes()).to_sat();
+        coins.push((input_type, DustUtxo {
+            txid: utxo.txid.clone(),
+            vout: utxo.vout,
+            amount: utxo.amount,
+            input_type: input_type.name(),
+            input_vbytes: input_type.vbytes(),
+            cost_now,
+            cost_later,
+            status_now: status(utxo.amount, cost_now).to_string(),
+            status_later: status(utxo.amount, cost_later).to_string(),
+        }));
+    }
+
+    let now = health(coins.iter().map(|(_, coin)| (coin.amount, coin.status_now.as_str())), current_fee_rate);
+    let later = health(coins.iter().map(|(_, coin)| (coin.amount, coin.status_later.as_str())), future_fee_rate);
+    let candidates: Vec<&(InputType, DustUtxo)> = coins
+        .iter()
+        .filter(|(_, coin)| coin.status_later != "healthy" && coin.amount > coin.cost_now)
+        .collect();
+    let recommendation = advise(&candidates, current_fee_rate, future_fee_rate);
+
+    let report = DustReport {
+        utxos: coins.into_iter().map(|(_, coin)| coin).collect(),
+        now,
+        later,
+        recommendation,
+    };
+    to_json_with_amounts(&report, amount_format, compact)
+}
+
+fn status(amount: u64, input_cost: u64) -> &'static str {
+    if input_cost >= amount {
+        "unspendable"
+    } else if input_cost as f64 >= amount as f64 * MARGINAL_COST_SHARE {
+        "marginal"
+    } else {
+        "healthy"
+    }
+}
+
+fn health<'a>(coins: impl Iterator<Item = (u64, &'a str)>, fee_rate: FeeRate) -> FeeRateHealth {
+    let mut health = FeeRateHealth { fee_rate, healthy: 0, marginal: 0, unspendable: 0, value_at_risk: 0, unspendable_value: 0 };
+    for (amount, status) in coins {
+        match status {
+            "healthy" => health.healthy += 1,
+            "marginal" => {
+                health.marginal += 1;
+                health.value_at_risk += amount;
+            }
+            _ => {
+                health.unspendable += 1;
+                health.value_at_risk += amount;
+                health.unspendable_value += amount;
+            }
+        }
+    }
+    health
+}
+
+/// The merged coin goes to taproot if any candidate is already taproot, since that's
+/// the cheapest to spend later, and to P2WPKH otherwise.
+fn advise(candidates: &[&(InputType, DustUtxo)], current_fee_rate: FeeRate, future_fee_rate: FeeRate) -> ConsolidationAdvice {
+    let (output_type, output_vbytes, merged_input) = if candidates.iter().any(|(input_type, _)| *input_type == InputType::P2trKeyPath) {
+        ("p2tr", P2TR_OUTPUT_VBYTES, InputType::P2trKeyPath)
+    } else {
+        ("p2wpkh", OUTPUT_VBYTES, InputType::P2wpkh)
+    };
+    let candidate_total: u64 = candidates.iter().map(|(_, coin)| coin.amount).sum();
+    let inputs_vbytes: f64 = candidates.iter().map(|(_, coin)| coin.input_vbytes).sum();
+    let consolidation_vbytes = TX_OVERHEAD_VBYTES + inputs_vbytes + output_vbytes;
+    let consolidation_fee = current_fee_rate.fee_for_vbytes(consolidation_vbytes).to_sat();
+    let consolidated_amount = candidate_total.saturating_sub(consolidation_fee);
+    let consolidated_spend_cost = future_fee_rate.fee_for_vbytes(merged_input.vbytes()).to_sat();
+    let consolidated_recovered = if consolidated_amount >= MIN_CHANGE_SAT {
+        consolidated_amount.saturating_sub(consolidated_spend_cost)
+    } else {
+        0
+    };
+
+    // Coins unspendable later are simply never spent, so they recover nothing and cost nothing.
+    let worth_spending_later = || candidates.iter().filter(|(_, coin)| coin.amount > coin.cost_later);
+    // Each is an extra input in some later transaction, so only its own input is charged.
+    let worth_later_total: u64 = worth_spending_later().map(|(_, coin)| coin.amount).sum();
+    let individual_spend_cost: u64 = worth_spending_later().map(|(_, coin)| coin.cost_later).sum();
+    let individual_recovered = worth_later_total - individual_spend_cost;
+    let savings = consolidated_recovered as i64 - individual_recovered as i64;
+
+    let mut math = Vec::new();
+    let action = if candidates.is_empty() {
+        math.push("every coin is healthy at the future rate or already costs more than it's worth to spend".to_string());
+        "nothing_to_consolidate"
+    } else {
+        math.push(format!(
+            "consolidate now: {} inputs ({:.2} vB) + {} output ({} vB) + {} vB overhead = {:.2} vB × {} sat/vB = {} sats fee",
+            candidates.len(), inputs_vbytes, output_type, output_vbytes, TX_OVERHEAD_VBYTES, consolidation_vbytes, current_fee_rate.sat_per_vb(), consolidation_fee
+        ));
+        math.push(format!(
+            "then spend the merged coin: {} sats − {} sats ({} input, {:.2} vB × {} sat/vB) = {} sats recovered",
+            consolidated_amount, consolidated_spend_cost, merged_input.name(), merged_input.vbytes(), future_fee_rate.sat_per_vb(), consolidated_recovered
+        ));
+        math.push(format!(
+            "spend individually later: {} sats in coins still worth spending − {} sats in input fees at {} sat/vB = {} sats recovered",
+            worth_later_total, individual_spend_cost, future_fee_rate.sat_per_vb(), individual_recovered
+        ));
+        math.push(format!("consolidating now {} {} sats", if savings > 0 { "saves" } else { "loses" }, savings.unsigned_abs()));
+        if savings > 0 { "consolidate_now" } else { "wait" }
+    };
+
+    ConsolidationAdvice {
+        action: action.to_string(),
+        consolidate_now: action == "consolidate_now",
+        candidates: candidates.iter().map(|(_, coin)| format!("{}:{}", coin.txid, coin.vout)).collect(),
+        candidate_total,
+        consolidated_output_type: output_type.to_string(),
+        consolidation_vbytes,
+        consolidation_fee,
+        consolidated_amount,
+        consolidated_spend_cost,
+        individual_spend_cost,
+        individual_recovered,
+        consolidated_recovered,
+        savings,
+        math,
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::secp256k1::{All, Secp256k1};
+use bitcoin::sighash::SighashCache;
+use bitcoin::{Amount, CompressedPublicKey, PrivateKey, PublicKey, ScriptBuf, Transaction, TxOut};
+use crate::selection::balance::check_maturity;
+use crate::selection::input_vbytes;
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::sign::{apply_signature, sign_input};
+use crate::transaction::{address_script, check_amount_total, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::network::network_or_default;
+use crate::utils::units::FeeRate;
+use crate::wallet::parse_private_key;
+
+/// A 65-byte public key in the scriptSig instead of a 33-byte one.
+const UNCOMPRESSED_PUBKEY_EXTRA_VBYTES: f64 = 32.0;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignKeyScript {
+    /// `p2pkh`, `p2sh-p2wpkh`, `p2wpkh` or `p2tr`.
+    pub kind: String,
+    pub compressed: bool,
+    pub address: Option<String>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    #[serde(alias = "utxo_count")]
+    pub utxo_count: usize,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignKeySweep {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    pub txid: String,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    pub vsize: usize,
+    #[serde(alias = "input_count")]
+    pub input_count: usize,
+    /// Every script the key can spend, with what was swept from each.
+    pub found: Vec<ForeignKeyScript>,
+    /// UTXOs that cost more to spend than they hold at this feerate, left behind.
+    #[serde(alias = "inputs_skipped")]
+    pub inputs_skipped: Vec<TransactionInput>,
+    /// UTXOs whose scriptPubkey isn't one of the key's scripts, left behind.
+    pub unmatched: Vec<TransactionInput>,
+}
+
+/// Sweeps everything a loose private key holds, as found on a paper wallet or in a WIF
+/// backup, to `destination_address` in one signed transaction. The key is tried as
+/// compressed and uncompressed P2PKH, P2SH-P2WPKH, P2WPKH and key-path P2TR whatever its
+/// WIF flag says, since old backups don't always record which form was funded; the UTXOs
+/// are the caller's lookups for the addresses in `found`, and each is signed the way its
+/// scriptPubkey asks. The fee comes from the signed size, so uncompressed inputs pay for
+/// their larger key.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn sweep_foreign_key(
+    wif_or_hex: &str,
+    utxos_json: &str,
+    destination_address: &str,
+    fee_rate_sat_vb: f64,
+    network: Option<String>,
+    tip_height: Option<u32>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let private_key = parse_private_key(wif_or_hex, None, network.network)?;
+    let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+    check_amount_total(utxos.iter().map(|utxo| utxo.amount), "utxos").map_err(|e| JsValue::from_str(&e))?;
+    let destination_script = address_script(destination_address, Some(&network))?;
+
+    let secp = Secp256k1::new();
+    let pubkey = private_key.inner.public_key(&secp);
+    let compressed = CompressedPublicKey(pubkey);
+    let p2wpkh = ScriptBuf::new_p2wpkh(&compressed.wpubkey_hash());
+    let scripts = [
+        ("p2pkh", true, ScriptBuf::new_p2pkh(&PublicKey::new(pubkey).pubkey_hash())),
+        ("p2pkh", false, ScriptBuf::new_p2pkh(&PublicKey::new_uncompressed(pubkey).pubkey_hash())),
+        ("p2sh-p2wpkh", true, ScriptBuf::new_p2sh(&p2wpkh.script_hash())),
+        ("p2wpkh", true, p2wpkh),
+        ("p2tr", true, ScriptBuf::new_p2tr(&secp, pubkey.x_only_public_key().0, None)),
+    ];
+    let mut found: Vec<ForeignKeyScript> = scripts
+        .iter()
+        .map(|(kind, compressed, script)| ForeignKeyScript {
+            kind: kind.to_string(),
+            compressed: *compressed,
+            address: network.address(script),
+            script_pubkey: bytes_to_hex(script.as_bytes()),
+            utxo_count: 0,
+            amount: 0,
+        })
+        .collect();
+
+    let mut inputs_used = Vec::new();
+    let mut inputs_skipped = Vec::new();
+    let mut unmatched = Vec::new();
+    // Frozen coins are the user saying "not this one", even for a sweep.
+    for mut utxo in utxos.into_iter().filter(|utxo| !utxo.frozen) {
+        let script = hex_to_bytes(&utxo.script_pubkey, HexMode::Lenient)
+            .map_err(|e| JsValue::from_str(&format!("{}:{}: Invalid scriptPubkey: {}", utxo.txid, utxo.vout, e)))?;
+        let Some(position) = scripts.iter().position(|(_, _, candidate)| candidate.as_bytes() == script.as_slice()) else {
+            unmatched.push(utxo);
+            continue;
+        };
+        check_maturity(&utxo, tip_height).map_err(|e| JsValue::from_str(&e))?;
+        // The match says how the input is spent, which the script alone doesn't for P2SH.
+        // The weight table only knows compressed keys.
+        utxo.input_type = Some(scripts[position].0.to_string());
+        let mut vbytes = input_vbytes(&utxo)?;
+        if !scripts[position].1 {
+            vbytes += UNCOMPRESSED_PUBKEY_EXTRA_VBYTES;
+        }
+        if utxo.amount > fee_rate.fee_for_vbytes(vbytes).to_sat() {
+            found[position].utxo_count += 1;
+            found[position].amount += utxo.amount;
+            inputs_used.push(utxo);
+        } else {
+            inputs_skipped.push(utxo);
+        }
+    }
+    if inputs_used.is_empty() {
+        return Err(JsValue::from_str(&format!(
+            "Nothing to sweep: {}",
+            if !inputs_skipped.is_empty() {
+                format!("all {} of the key's UTXOs cost more to spend than they are worth at this feerate", inputs_skipped.len())
+            } else if !unmatched.is_empty() {
+                format!("none of the {} UTXOs pay to a script this key spends", unmatched.len())
+            } else {
+                "no UTXOs provided".to_string()
+            }
+        )));
+    }
+
+    // Sign once at the full amount to learn the exact size, then again at the real one.
+    // Low-R ECDSA and BIP340 signatures have a fixed length, so the size doesn't move.
+    let total: u64 = inputs_used.iter().map(|utxo| utxo.amount).sum();
+    let output = TransactionOutput {
+        address: Some(destination_address.to_string()),
+        amount: total,
+        is_change: false,
+        script_pubkey: None,
+    };
+    let unsigned = unsigned_transaction(&inputs_used, &[output], Some(&network))?;
+    let prevouts: Vec<TxOut> = inputs_used
+        .iter()
+        .map(|utxo| TxOut {
+            value: Amount::from_sat(utxo.amount),
+            script_pubkey: ScriptBuf::from_bytes(hex_to_bytes(&utxo.script_pubkey, HexMode::Lenient).unwrap_or_default()),
+        })
+        .collect();
+    let vsize = sign_all(&secp, unsigned.clone(), &prevouts, &private_key)?.vsize();
+    let fee = fee_rate.fee_for_vbytes(vsize as f64).to_sat();
+    let dust = destination_script.minimal_non_dust().to_sat();
+    if total < fee + dust {
+        return Err(JsValue::from_str(&format!(
+            "Nothing to sweep: spendable total of {} sats does not cover the {} sat fee plus a non-dust output",
+            total, fee
+        )));
+    }
+
+    let mut unsigned = unsigned;
+    unsigned.output[0].value = Amount::from_sat(total - fee);
+    let tx = sign_all(&secp, unsigned, &prevouts, &private_key)?;
+    let tx_bytes = finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?;
+
+    let result = ForeignKeySweep {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        txid: tx.compute_txid().to_string(),
+        amount: total - fee,
+        fee,
+        vsize: tx.vsize(),
+        input_count: inputs_used.len(),
+        found,
+        inputs_skipped,
+        unmatched,
+    };
+    to_json_with_amounts(&result, amount_format, compact)
+}
+
+/// Signs every input with `private_key`. `sign_input` picks the compressed or
+/// uncompressed form for P2PKH from the script, so one key covers all five scripts.
+fn sign_all(secp: &Secp256k1<All>, tx: Transaction, prevouts: &[TxOut], private_key: &PrivateKey) -> Result<Transaction, JsValue> {
+    let key = PrivateKey::new(private_key.inner, private_key.network);
+    let mut cache = SighashCache::new(tx);
+    let mut signatures = Vec::with_capacity(prevouts.len());
+    for (index, prevout) in prevouts.iter().enumerate() {
+        let signature = sign_input(secp, &mut cache, index, prevout, prevouts, &key)
+            .map_err(|e| JsValue::from_str(&format!("Input {}: {}", index, e)))?;
+        signatures.push(signature);
+    }
+    let mut tx = cache.into_transaction();
+    for (index, signature) in signatures.into_iter().enumerate() {
+        apply_signature(&mut tx, index, signature);
+    }
+    Ok(tx)
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::OutPoint;
+use crate::selection::{select, SelectionOptions};
+use crate::transaction::{decode_tx_hex, parse_outpoint, TransactionInput};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::units::{Amount, FeeRate};
+
+const STATE_VERSION: u32 = 1;
+
+/// An outpoint to reserve: `"txid:vout"`, or an object with `txid` and `vout` such as an
+/// entry of `select_coins`' `selected` list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OutpointEntry {
+    Text(String),
+    Object { txid: String, vout: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Reservation {
+    pub outpoint: String,
+    pub tag: String,
+    /// Unix time in seconds after which the reservation no longer holds the coin.
+    #[serde(default, alias = "expires_at")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InFlightState {
+    version: u32,
+    reservations: Vec<Reservation>,
+}
+
+/// Coins already committed to a transaction that hasn't confirmed yet, so two sends
+/// started close together don't pick the same inputs. Reservations are grouped by a
+/// caller-chosen tag, typically a payment id or the spending txid, and last until they're
+/// released, their transaction is seen confirmed or evicted, or their TTL runs out.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct InFlightTracker {
+    reservations: BTreeMap<OutPoint, Reservation>,
+}
+
+#[wasm_bindgen]
+impl InFlightTracker {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> InFlightTracker {
+        InFlightTracker::default()
+    }
+
+    /// Reserves every outpoint in `outpoints_json` under `tag`, for `ttl_seconds` if given
+    /// and otherwise until released. Nothing is reserved if any outpoint is already held
+    /// by a live reservation under another tag; reserving again under the same tag renews
+    /// it. `now` is Unix time in seconds and defaults to the clock. Returns how many
+    /// outpoints were reserved.
+    pub fn reserve_utxos(&mut self, outpoints_json: &str, tag: &str, ttl_seconds: Option<u32>, now: Option<f64>) -> Result<usize, JsValue> {
+        if tag.is_empty() {
+            return Err(JsValue::from_str("The reservation tag can't be empty"));
+        }
+        let now = clock(now);
+        let entries: Vec<OutpointEntry> = parse_json_list(outpoints_json, "outpoints").map_err(|e| JsValue::from_str(&e))?;
+        let mut outpoints = Vec::new();
+        for (index, entry) in entries.into_iter().enumerate() {
+            let outpoint = match entry {
+                OutpointEntry::Text(text) => OutPoint::from_str(text.trim()).map_err(|e| format!("Invalid outpoint {:?}: {}", text, e)),
+                OutpointEntry::Object { txid, vout } => parse_outpoint(txid.trim(), vout).map_err(|e| e.as_string().unwrap_or_default()),
+            }
+            .map_err(|e| JsValue::from_str(&format!("outpoints[{}]: {}", index, e)))?;
+            if let Some(existing) = self.reservations.get(&outpoint).filter(|r| r.tag != tag && is_live(r, now)) {
+                return Err(JsValue::from_str(&format!(
+                    "{} is already reserved by {:?}; release it or wait for that transaction first",
+                    outpoint, existing.tag
+                )));
+            }
+            outpoints.push(outpoint);
+        }
+
+        let expires_at = ttl_seconds.map(|ttl| now + u64::from(ttl));
+        for outpoint in &outpoints {
+            self.reservations.insert(*outpoint, Reservation { outpoint: outpoint.to_string(), tag: tag.to_string(), expires_at });
+        }
+        Ok(outpoints.len())
+    }
+
+    /// Drops every reservation under `tag`, for a send that was abandoned or failed to
+    /// broadcast. Returns how many outpoints were freed.
+    pub fn release(&mut self, tag: &str) -> usize {
+        let before = self.reservations.len();
+        self.reservations.retain(|_, reservation| reservation.tag != tag);
+        before - self.reservations.len()
+    }
+
+    /// Drops the reservations on every coin `tx_hex` spends. Call it once the reserving
+    /// transaction confirms, when the coins are gone from the UTXO set anyway, or when it
+    /// is evicted from the mempool and its coins are spendable again. Returns how many
+    /// outpoints were freed.
+    pub fn release_transaction(&mut self, tx_hex: &str) -> Result<usize, JsValue> {
+        let tx = decode_tx_hex(tx_hex)?;
+        Ok(tx.input.iter().filter(|input| self.reservations.remove(&input.previous_output).is_some()).count())
+    }
+
+    /// Drops reservations whose TTL has run out at `now`. Returns how many were dropped.
+    pub fn prune_expired(&mut self, now: Option<f64>) -> usize {
+        let now = clock(now);
+        let before = self.reservations.len();
+        self.reservations.retain(|_, reservation| is_live(reservation, now));
+        before - self.reservations.len()
+    }
+
+    pub fn is_reserved(&self, txid: &str, vout: u32, now: Option<f64>) -> Result<bool, JsValue> {
+        let outpoint = parse_outpoint(txid.trim(), vout)?;
+        Ok(self.reservations.get(&outpoint).is_some_and(|reservation| is_live(reservation, clock(now))))
+    }
+
+    /// The outpoints held at `now` as `txid:vout` strings, the form `select_coins` takes as
+    /// its `reserved` option.
+    pub fn reserved_outpoints(&self, now: Option<f64>, compact: Option<bool>) -> Result<String, JsValue> {
+        to_json(&self.live(clock(now)), compact)
+    }
+
+    /// Every reservation, expired ones included, with its tag and expiry.
+    pub fn reservations(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let reservations: Vec<&Reservation> = self.reservations.values().collect();
+        to_json(&reservations, compact)
+    }
+
+    /// `select_coins` with the coins reserved at `now` held back, on top of any `reserved`
+    /// outpoints already in `options_json`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn select_coins(
+        &self,
+        utxos_json: &str,
+        target_sat: u64,
+        fee_rate_sat_vb: f64,
+        long_term_fee_rate_sat_vb: f64,
+        options_json: Option<String>,
+        now: Option<f64>,
+        compact: Option<bool>,
+    ) -> Result<String, JsValue> {
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let long_term_fee_rate = FeeRate::from_sat_per_vb(long_term_fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let mut options: SelectionOptions = match options_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+            None => SelectionOptions::default(),
+        };
+        options.reserved.extend(self.live(clock(now)));
+
+        let result = select(&utxos, Amount::from_sat(target_sat), fee_rate, long_term_fee_rate, &options)
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        to_json(&result, compact)
+    }
+
+    pub fn export_state(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let state = InFlightState {
+            version: STATE_VERSION,
+            reservations: self.reservations.values().cloned().collect(),
+        };
+
+        to_json(&state, compact)
+    }
+
+    pub fn import_state(state_json: &str) -> Result<InFlightTracker, JsValue> {
+        let state: InFlightState = serde_json::from_str(state_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid in-flight state JSON: {}", e)))?;
+        if state.version != STATE_VERSION {
+            return Err(JsValue::from_str(&format!("Unsupported in-flight state version {}", state.version)));
+        }
+
+        let mut tracker = InFlightTracker::new();
+        for reservation in state.reservations {
+            let outpoint = OutPoint::from_str(&reservation.outpoint)
+                .map_err(|e| JsValue::from_str(&format!("Invalid reserved outpoint {:?}: {}", reservation.outpoint, e)))?;
+            tracker.reservations.insert(outpoint, reservation);
+        }
+        Ok(tracker)
+    }
+}
+
+impl InFlightTracker {
+    fn live(&self, now: u64) -> Vec<String> {
+        self.reservations.values().filter(|reservation| is_live(reservation, now)).map(|reservation| reservation.outpoint.clone()).collect()
+    }
+}
+
+fn is_live(reservation: &Reservation, now: u64) -> bool {
+    reservation.expires_at.is_none_or(|expires_at| now < expires_at)
+}
+
+fn clock(now: Option<f64>) -> u64 {
+    now.unwrap_or_else(|| js_sys::Date::now() / 1000.0) as u64
+}
@@ -0,0 +1,608 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{RngCore, SeedableRng};
+use bitcoin::{OutPoint, Script};
+use crate::transaction::{check_amount_total, TransactionInput};
+use crate::transaction::weight::{utxo_input_type, InputType};
+use crate::utils::rng::with_rng;
+use crate::utils::varint::encode_compact_size;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::units::{Amount, FeeRate};
+use balance::{check_maturity, is_immature};
+use crate::utils::metrics::timed;
+
+pub mod balance;
+pub mod batch;
+pub mod consolidation;
+pub mod dust;
+#[cfg(feature = "signing")]
+pub mod foreign_key;
+pub mod inflight;
+pub mod split;
+pub mod sweep;
+
+pub use balance::compute_balance;
+pub use batch::build_batch_payment;
+pub use consolidation::plan_consolidation;
+pub use dust::dust_report;
+#[cfg(feature = "signing")]
+pub use foreign_key::sweep_foreign_key;
+pub use inflight::InFlightTracker;
+pub use split::build_split_payment;
+pub use sweep::{max_send_amount, build_sweep_transaction};
+
+pub const TX_OVERHEAD_VBYTES: f64 = 11.0;
+pub const OUTPUT_VBYTES: f64 = 31.0;
+pub const MIN_CHANGE_SAT: u64 = 546;
+
+const BNB_TOTAL_TRIES: usize = 100_000;
+
+// Soft constraints a coin can break; each is given up only if the target can't be met
+// while honoring it, in RELAXATION_ORDER.
+const AVOID_LARGE: u8 = 1;
+const AVOID_REUSED: u8 = 2;
+const AVOID_UNCONFIRMED: u8 = 4;
+/// Cheapest to give up first: coins on a reused address are already linked to each
+/// other, a large coin costs privacy and leaves big change, and an unconfirmed parent
+/// can still be replaced or dropped, taking the payment with it.
+const RELAXATION_ORDER: [(u8, &str, &str); 3] = [
+    (AVOID_REUSED, "avoidReusedAddresses", "Spent coins on reused addresses: other coins can't cover the target"),
+    (AVOID_LARGE, "maxInputValue", "Spent coins above maxInputValue: smaller coins can't cover the target"),
+    (
+        AVOID_UNCONFIRMED,
+        "preferConfirmedOverUnconfirmed",
+        "Spent unconfirmed coins: confirmed coins can't cover the target",
+    ),
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionOptions {
+    #[serde(default, alias = "include_negative_effective_value")]
+    pub include_negative_effective_value: bool,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default = "default_recipient_count", alias = "recipient_count")]
+    pub recipient_count: usize,
+    #[serde(default, alias = "min_confirmations")]
+    pub min_confirmations: Option<u32>,
+    #[serde(default, alias = "tip_height")]
+    pub tip_height: Option<u32>,
+    /// Coins worth more than this are only spent if smaller ones can't cover the target.
+    #[serde(default, alias = "max_input_value")]
+    pub max_input_value: Option<u64>,
+    #[serde(default, alias = "prefer_confirmed_over_unconfirmed")]
+    pub prefer_confirmed_over_unconfirmed: bool,
+    /// Holds back coins whose scriptPubKey appears more than once in the UTXO set.
+    #[serde(default, alias = "avoid_reused_addresses")]
+    pub avoid_reused_addresses: bool,
+    /// Lets coinbase outputs be spent before they mature, for regtest experiments.
+    #[serde(default, alias = "allow_immature_coinbase")]
+    pub allow_immature_coinbase: bool,
+    /// `txid:vout` outpoints already being spent by a transaction in flight, as listed by
+    /// `InFlightTracker.reserved_outpoints`. They're held back like frozen coins.
+    #[serde(default)]
+    pub reserved: Vec<String>,
+}
+
+impl Default for SelectionOptions {
+    fn default() -> Self {
+        SelectionOptions {
+            include_negative_effective_value: false,
+            seed: None,
+            recipient_count: default_recipient_count(),
+            min_confirmations: None,
+            tip_height: None,
+            max_input_value: None,
+            prefer_confirmed_over_unconfirmed: false,
+            avoid_reused_addresses: false,
+            allow_immature_coinbase: false,
+            reserved: Vec::new(),
+        }
+    }
+}
+
+fn default_recipient_count() -> usize {
+    1
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectedCoin {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(with = "crate::utils::amount::signed", alias = "effective_value")]
+    pub effective_value: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionResult {
+    pub selected: Vec<SelectedCoin>,
+    #[serde(alias = "excluded_negative_value")]
+    pub excluded_negative_value: Vec<SelectedCoin>,
+    #[serde(with = "crate::utils::amount", alias = "total_selected")]
+    pub total_selected: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub change: u64,
+    pub changeless: bool,
+    #[serde(with = "crate::utils::amount::signed")]
+    pub waste: i64,
+    pub algorithm: String,
+    /// Soft constraints that had to be given up to reach the target, by option name.
+    #[serde(default)]
+    pub relaxed: Vec<String>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+struct Candidate<'a> {
+    utxo: &'a TransactionInput,
+    effective_value: i64,
+    fee: i64,
+    long_term_fee: i64,
+    /// The AVOID_ constraints this coin breaks.
+    avoid: u8,
+}
+
+/// What every attempt at a selection shares, however many constraints are relaxed.
+struct Costs {
+    target_sat: u64,
+    target: i64,
+    fixed_fee: u64,
+    change_output_fee: u64,
+    cost_of_change: i64,
+    is_feerate_high: bool,
+    seed: Option<u64>,
+    held_back_note: String,
+}
+
+#[wasm_bindgen]
+pub fn select_coins(
+    utxos_json: &str,
+    target_sat: u64,
+    fee_rate_sat_vb: f64,
+    long_term_fee_rate_sat_vb: f64,
+    options_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("select_coins", utxos_json.len(), || {
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let long_term_fee_rate = FeeRate::from_sat_per_vb(long_term_fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+
+        let options: SelectionOptions = match options_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+            None => SelectionOptions::default(),
+        };
+
+        let result = select(&utxos, Amount::from_sat(target_sat), fee_rate, long_term_fee_rate, &options)
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        to_json_with_amounts(&result, amount_format, compact)
+    })
+}
+
+pub fn select(
+    utxos: &[TransactionInput],
+    target: Amount,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    options: &SelectionOptions,
+) -> Result<SelectionResult, String> {
+    let target_sat = target.to_sat();
+    check_amount_total(utxos.iter().map(|utxo| utxo.amount), "utxos")?;
+    check_amount_total([target_sat], "target")?;
+    let min_confirmations = match (options.min_confirmations, options.tip_height) {
+        (Some(_), None) => return Err("min_confirmations needs tip_height to count confirmations".to_string()),
+        (Some(min), Some(tip)) => Some((min, tip)),
+        (None, _) => None,
+    };
+    let reserved = options
+        .reserved
+        .iter()
+        .enumerate()
+        .map(|(index, outpoint)| {
+            OutPoint::from_str(outpoint.trim()).map_err(|e| format!("reserved[{}]: invalid outpoint {:?}: {}", index, outpoint, e))
+        })
+        .collect::<Result<HashSet<_>, _>>()?;
+
+    let mut pinned = Vec::new();
+    let mut candidates = Vec::new();
+    let mut excluded = Vec::new();
+    let mut held_back = 0u64;
+    let mut immature = 0u64;
+    let mut in_flight = 0u64;
+    let mut script_counts: HashMap<String, usize> = HashMap::new();
+    if options.avoid_reused_addresses {
+        for utxo in utxos {
+            *script_counts.entry(utxo.script_pubkey.trim().to_ascii_lowercase()).or_default() += 1;
+        }
+    }
+    for utxo in utxos {
+        let unconfirmed = match options.tip_height {
+            Some(tip) => confirmations(utxo, tip) == 0,
+            None => utxo.height.is_none(),
+        };
+        let confirmations = min_confirmations.map(|(_, tip)| confirmations(utxo, tip));
+        let too_young = matches!((confirmations, min_confirmations), (Some(have), Some((min, _))) if have < min);
+        if utxo.must_spend && utxo.frozen {
+            return Err(format!("{}:{} is marked both frozen and must_spend", utxo.txid, utxo.vout));
+        }
+        if utxo.must_spend && too_young {
+            return Err(format!(
+                "must_spend coin {}:{} has {} confirmations, below min_confirmations",
+                utxo.txid,
+                utxo.vout,
+                confirmations.unwrap_or(0)
+            ));
+        }
+        if !reserved.is_empty() && OutPoint::from_str(&format!("{}:{}", utxo.txid.trim(), utxo.vout)).is_ok_and(|o| reserved.contains(&o)) {
+            if utxo.must_spend {
+                return Err(format!("must_spend coin {}:{} is reserved by a transaction in flight", utxo.txid, utxo.vout));
+            }
+            in_flight += utxo.amount;
+            continue;
+        }
+        if utxo.frozen || too_young {
+            held_back += utxo.amount;
+            continue;
+        }
+        // An immature coinbase coin is held back like a frozen one, unless it has to be spent.
+        if utxo.is_coinbase && !options.allow_immature_coinbase {
+            if options.tip_height.is_none() || utxo.must_spend {
+                check_maturity(utxo, options.tip_height)?;
+            }
+            if options.tip_height.is_some_and(|tip| is_immature(utxo, tip)) {
+                immature += utxo.amount;
+                continue;
+            }
+        }
+
+        let vbytes = utxo_input_type(utxo)?.vbytes();
+        let fee = fee_rate.fee_for_vbytes(vbytes).to_sat() as i64;
+        let mut avoid = 0;
+        if options.max_input_value.is_some_and(|max| utxo.amount > max) {
+            avoid |= AVOID_LARGE;
+        }
+        if script_counts.get(&utxo.script_pubkey.trim().to_ascii_lowercase()).is_some_and(|&count| count > 1) {
+            avoid |= AVOID_REUSED;
+        }
+        if options.prefer_confirmed_over_unconfirmed && unconfirmed {
+            avoid |= AVOID_UNCONFIRMED;
+        }
+        let candidate = Candidate {
+            utxo,
+            effective_value: utxo.amount as i64 - fee,
+            fee,
+            long_term_fee: long_term_fee_rate.fee_for_vbytes(vbytes).to_sat() as i64,
+            avoid,
+        };
+        if utxo.must_spend {
+            pinned.push(candidate);
+        } else if candidate.effective_value <= 0 && !options.include_negative_effective_value {
+            excluded.push(coin_summary(&candidate));
+        } else {
+            candidates.push(candidate);
+        }
+    }
+
+    let fixed_vbytes = TX_OVERHEAD_VBYTES + OUTPUT_VBYTES * options.recipient_count as f64;
+    let fixed_fee = fee_rate.fee_for_vbytes(fixed_vbytes).to_sat();
+    let change_output_fee = fee_rate.fee_for_vbytes(OUTPUT_VBYTES).to_sat();
+    // Change is assumed to be P2WPKH, both as an output now and as an input later.
+    let cost_of_change = (change_output_fee + long_term_fee_rate.fee_for_vbytes(InputType::P2wpkh.vbytes()).to_sat()) as i64;
+    let costs = Costs {
+        target_sat,
+        target: (target_sat + fixed_fee) as i64,
+        fixed_fee,
+        change_output_fee,
+        cost_of_change,
+        is_feerate_high: fee_rate > long_term_fee_rate,
+        seed: options.seed,
+        held_back_note: held_back_note(held_back, immature, in_flight),
+    };
+
+    // Must-spend coins ignore the soft constraints. The others are relaxed one at a time
+    // until the target is met; the error from the last attempt, with every constraint
+    // relaxed, is the one reported.
+    let broken = candidates.iter().fold(0, |flags, c| flags | c.avoid);
+    let attempt = |relaxed: u8| {
+        let pool = candidates.iter().filter(|c| c.avoid & !relaxed == 0).copied().collect();
+        choose(pinned.clone(), pool, &costs)
+    };
+    let mut steps = RELAXATION_ORDER.iter().filter(|(flag, _, _)| broken & flag != 0);
+    let mut relaxed = 0;
+    let mut best = loop {
+        match attempt(relaxed) {
+            Ok(result) => break result,
+            Err(e) => match steps.next() {
+                Some((flag, _, _)) => relaxed |= flag,
+                None => return Err(e),
+            },
+        }
+    };
+    // A later relaxation can make an earlier one unnecessary; restore what it can.
+    let last_relaxed = RELAXATION_ORDER.iter().rev().find(|(flag, _, _)| relaxed & flag != 0).map(|(flag, _, _)| *flag);
+    for (flag, _, _) in RELAXATION_ORDER.iter().rev() {
+        if relaxed & flag == 0 || Some(*flag) == last_relaxed {
+            continue;
+        }
+        if let Ok(result) = attempt(relaxed & !flag) {
+            relaxed &= !flag;
+            best = result;
+        }
+    }
+
+    for (flag, name, warning) in RELAXATION_ORDER {
+        if relaxed & flag != 0 {
+            best.relaxed.push(name.to_string());
+            best.warnings.push(warning.to_string());
+        }
+    }
+    best.excluded_negative_value = excluded;
+    Ok(best)
+}
+
+fn held_back_note(held_back: u64, immature: u64, in_flight: u64) -> String {
+    let parts: Vec<String> = [
+        (held_back, "frozen or below min_confirmations"),
+        (immature, "in immature coinbase outputs"),
+        (in_flight, "reserved by transactions in flight"),
+    ]
+    .iter()
+    .filter(|(amount, _)| *amount > 0)
+    .map(|(amount, reason)| format!("{} sats {}", amount, reason))
+    .collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+/// Runs the selection algorithms over must-spend coins and a pool of candidates.
+fn choose<'a>(mut pinned: Vec<Candidate<'a>>, mut candidates: Vec<Candidate<'a>>, costs: &Costs) -> Result<SelectionResult, String> {
+    let Costs { target_sat, target, fixed_fee, change_output_fee, cost_of_change, .. } = *costs;
+    let held_back_note = &costs.held_back_note;
+
+    let pinned_value: i64 = pinned.iter().map(|c| c.effective_value).sum();
+    let available: i64 = pinned_value + candidates.iter().map(|c| c.effective_value).sum::<i64>();
+    if available < target {
+        return Err(format!(
+            "Insufficient funds: need {} sats including fees, spendable effective value is {} sats{}",
+            target, available, held_back_note
+        ));
+    }
+
+    // Must-spend coins sit at the front of the pool; the algorithms only search the rest.
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.effective_value));
+    let pinned_count = pinned.len();
+    pinned.append(&mut candidates);
+    let candidates = pinned;
+    let (pinned_pool, free_pool) = candidates.split_at(pinned_count);
+    let pinned_indexes: Vec<usize> = (0..pinned_count).collect();
+    let with_pinned = |indexes: Vec<usize>| -> Vec<usize> {
+        pinned_indexes.iter().copied().chain(indexes.into_iter().map(|i| i + pinned_count)).collect()
+    };
+
+    if pinned_count > 0 && pinned_value >= target {
+        let excess = pinned_value - target;
+        let change = (excess >= (change_output_fee + MIN_CHANGE_SAT) as i64).then_some((change_output_fee, cost_of_change));
+        let mut result = build_result(&candidates, &pinned_indexes, target, fixed_fee, change, "must_spend");
+        if result.change > target_sat {
+            result.warnings.push(format!(
+                "must_spend coins alone exceed the target and leave a {} sat change output, more than the {} sats being sent",
+                result.change, target_sat
+            ));
+        }
+        return Ok(result);
+    }
+    let free_target = target - pinned_value;
+    let pinned_waste: i64 = pinned_pool.iter().map(|c| c.fee - c.long_term_fee).sum();
+
+    let bnb = branch_and_bound(free_pool, free_target, cost_of_change, costs.is_feerate_high, pinned_waste)
+        .map(|indexes| build_result(&candidates, &with_pinned(indexes), target, fixed_fee, None, "branch_and_bound"));
+
+    let srd_indexes = match costs.seed {
+        Some(seed) => single_random_draw(free_pool, free_target, change_output_fee, &mut StdRng::seed_from_u64(seed)),
+        None => with_rng(|rng| single_random_draw(free_pool, free_target, change_output_fee, rng)),
+    };
+    let srd = srd_indexes.map(|indexes| {
+        build_result(
+            &candidates,
+            &with_pinned(indexes),
+            target,
+            fixed_fee,
+            Some((change_output_fee, cost_of_change)),
+            "single_random_draw",
+        )
+    });
+
+    let best = match (bnb, srd) {
+        (Some(a), Some(b)) => if a.waste <= b.waste { a } else { b },
+        (Some(a), None) => a,
+        (None, Some(b)) => b,
+        (None, None) => {
+            return Err(format!(
+                "Insufficient funds: need {} sats plus change, spendable effective value is {} sats{}",
+                target, available, held_back_note
+            ))
+        }
+    };
+    Ok(best)
+}
+
+pub fn output_vbytes(script_pubkey: &Script) -> f64 {
+    (8 + encode_compact_size(script_pubkey.len() as u64).len() + script_pubkey.len()) as f64
+}
+
+pub fn input_vbytes(utxo: &TransactionInput) -> Result<f64, JsValue> {
+    utxo_input_type(utxo)
+        .map(|input_type| input_type.vbytes())
+        .map_err(|e| JsValue::from_str(&e))
+}
+
+/// Unconfirmed coins (no height, or a height past the tip) have zero confirmations.
+pub fn confirmations(utxo: &TransactionInput, tip_height: u32) -> u32 {
+    match utxo.height {
+        Some(height) if height <= tip_height => tip_height - height + 1,
+        _ => 0,
+    }
+}
+
+fn coin_summary(candidate: &Candidate) -> SelectedCoin {
+    SelectedCoin {
+        txid: candidate.utxo.txid.clone(),
+        vout: candidate.utxo.vout,
+        amount: candidate.utxo.amount,
+        effective_value: candidate.effective_value,
+    }
+}
+
+fn build_result(
+    candidates: &[Candidate],
+    indexes: &[usize],
+    target: i64,
+    fixed_fee: u64,
+    change: Option<(u64, i64)>,
+    algorithm: &str,
+) -> SelectionResult {
+    let selected: Vec<&Candidate> = indexes.iter().map(|&i| &candidates[i]).collect();
+    let total_selected: u64 = selected.iter().map(|c| c.utxo.amount).sum();
+    let selected_effective: i64 = selected.iter().map(|c| c.effective_value).sum();
+    let input_fees: i64 = selected.iter().map(|c| c.fee).sum();
+    let timing_waste: i64 = selected.iter().map(|c| c.fee - c.long_term_fee).sum();
+
+    let (fee, change_value, waste) = match change {
+        Some((change_output_fee, cost_of_change)) => {
+            let change_value = (selected_effective - target - change_output_fee as i64) as u64;
+            (
+                input_fees as u64 + fixed_fee + change_output_fee,
+                change_value,
+                timing_waste + cost_of_change,
+            )
+        }
+        None => {
+            let excess = selected_effective - target;
+            (input_fees as u64 + fixed_fee + excess as u64, 0, timing_waste + excess)
+        }
+    };
+
+    SelectionResult {
+        selected: selected.iter().map(|c| coin_summary(c)).collect(),
+        excluded_negative_value: Vec::new(),
+        total_selected,
+        fee,
+        change: change_value,
+        changeless: change.is_none(),
+        waste,
+        algorithm: algorithm.to_string(),
+        relaxed: Vec::new(),
+        warnings: Vec::new(),
+    }
+}
+
+fn branch_and_bound(
+    pool: &[Candidate],
+    target: i64,
+    cost_of_change: i64,
+    is_feerate_high: bool,
+    base_waste: i64,
+) -> Option<Vec<usize>> {
+    let mut curr_value: i64 = 0;
+    let mut curr_waste: i64 = base_waste;
+    let mut curr_available_value: i64 = pool.iter().map(|c| c.effective_value.max(0)).sum();
+    let mut curr_selection: Vec<usize> = Vec::new();
+    let mut best_selection: Option<Vec<usize>> = None;
+    let mut best_waste = i64::MAX;
+
+    let mut utxo_pool_index = 0usize;
+    for _ in 0..BNB_TOTAL_TRIES {
+        let mut backtrack = false;
+        if curr_value + curr_available_value < target
+            || curr_value > target + cost_of_change
+            || (curr_waste > best_waste && is_feerate_high)
+        {
+            backtrack = true;
+        } else if curr_value >= target {
+            let waste = curr_waste + (curr_value - target);
+            if waste <= best_waste {
+                best_selection = Some(curr_selection.clone());
+                best_waste = waste;
+            }
+            backtrack = true;
+        } else if utxo_pool_index >= pool.len() {
+            backtrack = true;
+        }
+
+        if backtrack {
+            let Some(&last) = curr_selection.last() else {
+                break;
+            };
+            // Restore the lookahead for coins skipped after the last inclusion, then try omitting it.
+            utxo_pool_index -= 1;
+            while utxo_pool_index > last {
+                curr_available_value += pool[utxo_pool_index].effective_value.max(0);
+                utxo_pool_index -= 1;
+            }
+            let utxo = &pool[utxo_pool_index];
+            curr_value -= utxo.effective_value;
+            curr_waste -= utxo.fee - utxo.long_term_fee;
+            curr_selection.pop();
+        } else {
+            let utxo = &pool[utxo_pool_index];
+            curr_available_value -= utxo.effective_value.max(0);
+            let skip_equivalent = !curr_selection.is_empty()
+                && utxo_pool_index - 1 != *curr_selection.last().unwrap_or(&0)
+                && utxo.effective_value == pool[utxo_pool_index - 1].effective_value
+                && utxo.fee == pool[utxo_pool_index - 1].fee;
+            if !skip_equivalent {
+                curr_selection.push(utxo_pool_index);
+                curr_value += utxo.effective_value;
+                curr_waste += utxo.fee - utxo.long_term_fee;
+            }
+        }
+        utxo_pool_index += 1;
+    }
+
+    best_selection
+}
+
+fn single_random_draw(
+    pool: &[Candidate],
+    target: i64,
+    change_output_fee: u64,
+    rng: &mut dyn RngCore,
+) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..pool.len()).collect();
+    order.shuffle(rng);
+
+    let required = target + change_output_fee as i64 + MIN_CHANGE_SAT as i64;
+    let mut selected = Vec::new();
+    let mut value = 0i64;
+    for index in order {
+        if pool[index].effective_value <= 0 {
+            continue;
+        }
+        selected.push(index);
+        value += pool[index].effective_value;
+        if value >= required {
+            return Some(selected);
+        }
+    }
+    None
+}
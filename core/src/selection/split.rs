@@ -0,0 +1,420 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::selection::balance::check_maturity;
+use crate::selection::{input_vbytes, output_vbytes, TX_OVERHEAD_VBYTES};
+use crate::transaction::{check_amount_total, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::network::{network_or_default, require_address_network};
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+
+#[derive(Deserialize)]
+pub struct SplitRecipient {
+    pub address: String,
+    /// The recipient's relative share in "proportional" mode.
+    #[serde(default)]
+    pub weight: Option<u64>,
+    /// The fixed amount in "remainder_to_last" mode, for every recipient but the last.
+    #[serde(default, rename = "amountSat", alias = "amount_sat", alias = "amount")]
+    pub amount: Option<u64>,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitShare {
+    #[serde(alias = "output_index")]
+    pub output_index: usize,
+    pub address: String,
+    pub label: Option<String>,
+    /// What the recipient would get before the fee: `amountSat + feeShare`.
+    #[serde(with = "crate::utils::amount")]
+    pub share: u64,
+    #[serde(with = "crate::utils::amount", alias = "fee_share")]
+    pub fee_share: u64,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    /// Sats this recipient got from the amount's rounding remainder (0 or 1).
+    #[serde(alias = "remainder_sats")]
+    pub remainder_sats: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitPayment {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(alias = "split_mode")]
+    pub split_mode: String,
+    #[serde(with = "crate::utils::amount", alias = "total_input")]
+    pub total_input: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    /// Sats left over after rounding every amount down, handed out one each from the
+    /// first recipient on.
+    #[serde(alias = "rounding_remainder")]
+    pub rounding_remainder: u64,
+    /// The same for the fee shares: the first recipients pay one sat more.
+    #[serde(alias = "fee_rounding_remainder")]
+    pub fee_rounding_remainder: u64,
+    pub recipients: Vec<SplitShare>,
+    #[serde(alias = "inputs_used")]
+    pub inputs_used: Vec<TransactionInput>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SplitMode {
+    Equal,
+    Proportional,
+    RemainderToLast,
+}
+
+/// Spends every UTXO to the recipients with no change, so the fee comes out of what
+/// they receive. `split_mode` divides both the coins and the fee: "equal" shares,
+/// "proportional" to each recipient's `weight`, or "remainder_to_last", where every
+/// recipient but the last gets its `amountSat` and the last gets the rest, paying the fee.
+/// Outputs plus fee always equal the inputs to the sat.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn build_split_payment(
+    total_utxos_json: &str,
+    recipients_json: &str,
+    fee_rate_sat_vb: f64,
+    split_mode: &str,
+    network: Option<String>,
+    tip_height: Option<u32>,
+    allow_immature_coinbase: Option<bool>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("build_split_payment", total_utxos_json.len() + recipients_json.len() + split_mode.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+        let mode = parse_split_mode(split_mode).map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let recipients: Vec<SplitRecipient> =
+            parse_json_list(recipients_json, "recipients").map_err(|e| JsValue::from_str(&e))?;
+        if recipients.is_empty() {
+            return Err(JsValue::from_str("No recipients given"));
+        }
+        let utxos: Vec<TransactionInput> =
+            parse_json_list(total_utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+
+        // As in a sweep, frozen coins stay where they are.
+        let inputs: Vec<TransactionInput> = utxos.into_iter().filter(|utxo| !utxo.frozen).collect();
+        if inputs.is_empty() {
+            return Err(JsValue::from_str("No spendable UTXOs provided"));
+        }
+        let total_input = check_amount_total(inputs.iter().map(|utxo| utxo.amount), "utxos").map_err(|e| JsValue::from_str(&e))?;
+        let mut vbytes = TX_OVERHEAD_VBYTES;
+        for utxo in &inputs {
+            if !allow_immature_coinbase.unwrap_or(false) {
+                check_maturity(utxo, tip_height).map_err(|e| JsValue::from_str(&e))?;
+            }
+            vbytes += input_vbytes(utxo)?;
+        }
+
+        let mut seen = HashSet::new();
+        let mut scripts = Vec::with_capacity(recipients.len());
+        for (index, recipient) in recipients.iter().enumerate() {
+            let script = require_address_network(&recipient.address, Some(&network))
+                .map_err(|e| JsValue::from_str(&format!("Recipient {}: {}", index, e)))?;
+            if !seen.insert(script.clone()) {
+                return Err(JsValue::from_str(&format!("Recipient {} ({}) is listed twice", index, recipient.address.trim())));
+            }
+            vbytes += output_vbytes(&script);
+            scripts.push(script);
+        }
+
+        let fee = fee_rate.fee_for_vbytes(vbytes).to_sat();
+        let available = total_input.checked_sub(fee).ok_or_else(|| {
+            JsValue::from_str(&format!("The {} sat fee is more than the {} sats the UTXOs hold", fee, total_input))
+        })?;
+        let split = split_amounts(mode, &recipients, available, fee).map_err(|e| JsValue::from_str(&e))?;
+
+        let mut problems = Vec::new();
+        for (index, (recipient, script)) in recipients.iter().zip(&scripts).enumerate() {
+            let dust = script.minimal_non_dust().to_sat();
+            if split.amounts[index] < dust {
+                problems.push(format!(
+                    "recipient {} ({}){} would receive {} sats after its {} sat fee share, below the {} sat dust limit",
+                    index,
+                    recipient.address.trim(),
+                    recipient.label.as_ref().map(|label| format!(" \"{}\"", label)).unwrap_or_default(),
+                    split.amounts[index],
+                    split.fee_shares[index],
+                    dust
+                ));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(JsValue::from_str(&problems.join("; ")));
+        }
+
+        let outputs: Vec<TransactionOutput> = recipients
+            .iter()
+            .zip(&split.amounts)
+            .map(|(recipient, &amount)| TransactionOutput {
+                address: Some(recipient.address.trim().to_string()),
+                amount,
+                is_change: false,
+                script_pubkey: None,
+            })
+            .collect();
+        let tx = unsigned_transaction(&inputs, &outputs, Some(&network))?;
+        let tx_bytes = finalize(&tx, Funding::Unsigned(&inputs), &checks).map_err(|e| JsValue::from_str(&e))?;
+
+        let result = SplitPayment {
+            tx_hex: bytes_to_hex(&tx_bytes),
+            split_mode: split_mode.trim().to_ascii_lowercase(),
+            total_input,
+            fee,
+            rounding_remainder: split.remainder,
+            fee_rounding_remainder: split.fee_remainder,
+            recipients: recipients
+                .into_iter()
+                .enumerate()
+                .map(|(index, recipient)| SplitShare {
+                    output_index: index,
+                    address: recipient.address.trim().to_string(),
+                    label: recipient.label,
+                    share: split.amounts[index] + split.fee_shares[index],
+                    fee_share: split.fee_shares[index],
+                    amount: split.amounts[index],
+                    remainder_sats: u64::from((index as u64) < split.remainder),
+                })
+                .collect(),
+            inputs_used: inputs,
+        };
+
+        to_json_with_amounts(&result, amount_format, compact)
+    })
+}
+
+struct Split {
+    amounts: Vec<u64>,
+    fee_shares: Vec<u64>,
+    remainder: u64,
+    fee_remainder: u64,
+}
+
+fn parse_split_mode(mode: &str) -> Result<SplitMode, String> {
+    match mode.trim().to_ascii_lowercase().as_str() {
+        "equal" => Ok(SplitMode::Equal),
+        "proportional" => Ok(SplitMode::Proportional),
+        "remainder_to_last" => Ok(SplitMode::RemainderToLast),
+        other => Err(format!(
+            "Unknown split_mode \"{}\" (expected equal, proportional or remainder_to_last)",
+            other
+        )),
+    }
+}
+
+/// Divides `available` (the inputs less the fee) and the fee itself by the same rule.
+fn split_amounts(mode: SplitMode, recipients: &[SplitRecipient], available: u64, fee: u64) -> Result<Split, String> {
+    let weights: Vec<u64> = match mode {
+        SplitMode::Equal => vec![1; recipients.len()],
+        SplitMode::Proportional => {
+            let weights = recipients
+                .iter()
+                .enumerate()
+                .map(|(index, recipient)| match recipient.weight {
+                    Some(weight) if weight > 0 => Ok(weight),
+                    Some(_) => Err(format!("recipient {} has a weight of 0", index)),
+                    None => Err(format!("recipient {} needs a weight in proportional mode", index)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            check_amount_total(weights.iter().copied(), "weights")?;
+            weights
+        }
+        SplitMode::RemainderToLast => return remainder_to_last(recipients, available, fee),
+    };
+
+    let (amounts, remainder) = split_exact(available, &weights);
+    let (fee_shares, fee_remainder) = split_exact(fee, &weights);
+    Ok(Split { amounts, fee_shares, remainder, fee_remainder })
+}
+
+/// Fixed amounts first; the last recipient takes what's left and so pays all the fee.
+fn remainder_to_last(recipients: &[SplitRecipient], available: u64, fee: u64) -> Result<Split, String> {
+    let (last, fixed) = recipients.split_last().expect("recipients is not empty");
+    if last.amount.is_some() {
+        return Err("the last recipient takes the remainder in remainder_to_last mode, so it can't have an amount".to_string());
+    }
+    let mut amounts = fixed
+        .iter()
+        .enumerate()
+        .map(|(index, recipient)| {
+            recipient
+                .amount
+                .ok_or_else(|| format!("recipient {} needs an amount in remainder_to_last mode", index))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let fixed_total = check_amount_total(amounts.iter().copied(), "amounts")?;
+    let rest = available.checked_sub(fixed_total).ok_or_else(|| {
+        format!(
+            "the fixed amounts total {} sats, more than the {} sats left after the {} sat fee",
+            fixed_total, available, fee
+        )
+    })?;
+    amounts.push(rest);
+
+    let mut fee_shares = vec![0; fixed.len()];
+    fee_shares.push(fee);
+    Ok(Split { amounts, fee_shares, remainder: 0, fee_remainder: 0 })
+}
+
+/// Divides `total` by `weights`, rounding each share down, then gives the sats left
+/// over one each to the first shares. The shares sum to exactly `total`; the second
+/// value is how many sats rounding left over. All-zero weights split evenly.
+pub fn split_exact(total: u64, weights: &[u64]) -> (Vec<u64>, u64) {
+    let weight_total: u128 = weights.iter().map(|&weight| weight as u128).sum();
+    if weight_total == 0 && !weights.is_empty() {
+        return split_exact(total, &vec![1; weights.len()]);
+    }
+    let mut shares: Vec<u64> = weights
+        .iter()
+        .map(|&weight| (total as u128 * weight as u128 / weight_total) as u64)
+        .collect();
+    let remainder = total - shares.iter().sum::<u64>();
+    for share in shares.iter_mut().take(remainder as usize) {
+        *share += 1;
+    }
+    (shares, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::deserialize_hex;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::{Address, CompressedPublicKey, Network, Transaction};
+
+    fn address(byte: u8) -> Address {
+        let secret = SecretKey::from_slice(&[byte; 32]).unwrap();
+        Address::p2wpkh(&CompressedPublicKey(secret.public_key(&Secp256k1::new())), Network::Testnet)
+    }
+
+    fn recipient(byte: u8, weight: Option<u64>, amount: Option<u64>) -> SplitRecipient {
+        SplitRecipient { address: address(byte).to_string(), weight, amount, label: None }
+    }
+
+    fn weighted(weights: &[u64]) -> Vec<SplitRecipient> {
+        weights.iter().enumerate().map(|(index, &weight)| recipient(index as u8 + 1, Some(weight), None)).collect()
+    }
+
+    fn error(result: Result<Split, String>) -> String {
+        result.err().unwrap()
+    }
+
+    #[test]
+    fn split_exact_gives_the_remainder_to_the_first_shares() {
+        assert_eq!(split_exact(10, &[1, 1, 1]), (vec![4, 3, 3], 1));
+        assert_eq!(split_exact(101, &[1, 2, 3]), (vec![17, 34, 50], 2));
+        assert_eq!(split_exact(7, &[0, 0]), (vec![4, 3], 1));
+        assert_eq!(split_exact(0, &[5, 9]), (vec![0, 0], 0));
+        let (shares, _) = split_exact(999_999_999, &[3, 7, 11, 13]);
+        assert_eq!(shares.iter().sum::<u64>(), 999_999_999);
+    }
+
+    #[test]
+    fn equal_mode() {
+        let split = split_amounts(SplitMode::Equal, &weighted(&[0, 0, 0]), 1_000_001, 1_001).unwrap();
+        assert_eq!(split.amounts, [333_334, 333_334, 333_333]);
+        assert_eq!(split.fee_shares, [334, 334, 333]);
+        assert_eq!((split.remainder, split.fee_remainder), (2, 2));
+    }
+
+    #[test]
+    fn proportional_mode() {
+        let split = split_amounts(SplitMode::Proportional, &weighted(&[1, 2, 4]), 100_000, 500).unwrap();
+        assert_eq!(split.amounts, [14_286, 28_572, 57_142]);
+        assert_eq!(split.fee_shares, [72, 143, 285]);
+        assert_eq!((split.remainder, split.fee_remainder), (2, 2));
+        assert_eq!(split.amounts.iter().sum::<u64>() + split.fee_shares.iter().sum::<u64>(), 100_500);
+    }
+
+    #[test]
+    fn remainder_to_last_mode() {
+        let recipients = [recipient(1, None, Some(30_000)), recipient(2, None, Some(20_001)), recipient(3, None, None)];
+        let split = split_amounts(SplitMode::RemainderToLast, &recipients, 99_999, 777).unwrap();
+        assert_eq!(split.amounts, [30_000, 20_001, 49_998]);
+        assert_eq!(split.fee_shares, [0, 0, 777]);
+        assert_eq!((split.remainder, split.fee_remainder), (0, 0));
+    }
+
+    #[test]
+    fn rejects_zero_and_missing_weights() {
+        let mut recipients = weighted(&[3, 0]);
+        assert_eq!(error(split_amounts(SplitMode::Proportional, &recipients, 1_000, 10)), "recipient 1 has a weight of 0");
+        recipients[1].weight = None;
+        assert_eq!(
+            error(split_amounts(SplitMode::Proportional, &recipients, 1_000, 10)),
+            "recipient 1 needs a weight in proportional mode"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_amounts() {
+        let missing = [recipient(1, None, Some(500)), recipient(2, None, None), recipient(3, None, None)];
+        assert_eq!(
+            error(split_amounts(SplitMode::RemainderToLast, &missing, 1_000, 10)),
+            "recipient 1 needs an amount in remainder_to_last mode"
+        );
+        let last = [recipient(1, None, Some(500)), recipient(2, None, Some(500))];
+        assert!(error(split_amounts(SplitMode::RemainderToLast, &last, 1_000, 10)).contains("can't have an amount"));
+        let over = [recipient(1, None, Some(1_001)), recipient(2, None, None)];
+        assert_eq!(
+            error(split_amounts(SplitMode::RemainderToLast, &over, 1_000, 10)),
+            "the fixed amounts total 1001 sats, more than the 1000 sats left after the 10 sat fee"
+        );
+    }
+
+    #[test]
+    fn outputs_and_fee_add_up_to_the_inputs() {
+        let utxos = format!(
+            r#"[{{"txid": "{}", "vout": 0, "amountSat": 100000, "scriptPubkey": "{}"}},
+                {{"txid": "{}", "vout": 1, "amountSat": 23457, "scriptPubkey": "{}"}}]"#,
+            "ab".repeat(32),
+            address(9).script_pubkey().to_hex_string(),
+            "cd".repeat(32),
+            address(9).script_pubkey().to_hex_string()
+        );
+        let recipients: Vec<String> = (1..=3).map(|byte| format!(r#"{{"address": "{}"}}"#, address(byte))).collect();
+        for mode in ["equal", "proportional", "remainder_to_last"] {
+            let recipients = match mode {
+                "proportional" => (1..=3).map(|byte| format!(r#"{{"address": "{}", "weight": {}}}"#, address(byte), byte)).collect(),
+                "remainder_to_last" => (1..=3)
+                    .map(|byte| match byte {
+                        3 => format!(r#"{{"address": "{}"}}"#, address(byte)),
+                        _ => format!(r#"{{"address": "{}", "amountSat": 40000}}"#, address(byte)),
+                    })
+                    .collect(),
+                _ => recipients.clone(),
+            };
+            let json = build_split_payment(&utxos, &format!("[{}]", recipients.join(",")), 3.0, mode, None, None, None, None, None, None)
+                .unwrap();
+            let payment: SplitPayment = serde_json::from_str(&json).unwrap();
+            let tx: Transaction = deserialize_hex(&payment.tx_hex).unwrap();
+
+            let outputs: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+            assert_eq!(payment.total_input, 123_457);
+            assert_eq!(outputs + payment.fee, payment.total_input, "{}", mode);
+            for share in &payment.recipients {
+                assert_eq!(tx.output[share.output_index].value.to_sat(), share.amount);
+                assert_eq!(share.share, share.amount + share.fee_share);
+            }
+            // The remainder's sats go one each to the first recipients.
+            let remainder_sats: Vec<u64> = payment.recipients.iter().map(|share| share.remainder_sats).collect();
+            let expected: Vec<u64> = (0..3).map(|index| u64::from(index < payment.rounding_remainder)).collect();
+            assert_eq!(remainder_sats, expected, "{}", mode);
+        }
+    }
+}
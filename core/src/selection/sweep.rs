@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::selection::balance::check_maturity;
+use crate::selection::{input_vbytes, output_vbytes, TX_OVERHEAD_VBYTES};
+use crate::transaction::{address_script, check_amount_total, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepPlan {
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    #[serde(alias = "inputs_used")]
+    pub inputs_used: Vec<TransactionInput>,
+    #[serde(alias = "inputs_skipped")]
+    pub inputs_skipped: Vec<TransactionInput>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepTransaction {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub fee: u64,
+    #[serde(alias = "input_count")]
+    pub input_count: usize,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn max_send_amount(
+    utxos_json: &str,
+    destination_address: &str,
+    fee_rate_sat_vb: f64,
+    network: Option<String>,
+    tip_height: Option<u32>,
+    allow_immature_coinbase: Option<bool>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("max_send_amount", utxos_json.len() + destination_address.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let plan = plan_sweep(utxos_json, destination_address, fee_rate, &network, tip_height, allow_immature_coinbase.unwrap_or(false))?;
+
+        to_json_with_amounts(&plan, amount_format, compact)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn build_sweep_transaction(
+    utxos_json: &str,
+    destination_address: &str,
+    fee_rate_sat_vb: f64,
+    network: Option<String>,
+    tip_height: Option<u32>,
+    allow_immature_coinbase: Option<bool>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("build_sweep_transaction", utxos_json.len() + destination_address.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let plan = plan_sweep(utxos_json, destination_address, fee_rate, &network, tip_height, allow_immature_coinbase.unwrap_or(false))?;
+
+        if let Some(reason) = plan.reason {
+            return Err(JsValue::from_str(&format!("Nothing to sweep: {}", reason)));
+        }
+
+        let output = TransactionOutput {
+            address: Some(destination_address.to_string()),
+            amount: plan.amount,
+            is_change: false,
+            script_pubkey: None,
+        };
+        let tx = unsigned_transaction(&plan.inputs_used, &[output], Some(&network))?;
+        let tx_bytes = finalize(&tx, Funding::Unsigned(&plan.inputs_used), &checks).map_err(|e| JsValue::from_str(&e))?;
+
+        let result = SweepTransaction {
+            tx_hex: bytes_to_hex(&tx_bytes),
+            amount: plan.amount,
+            fee: plan.fee,
+            input_count: plan.inputs_used.len(),
+        };
+
+        to_json_with_amounts(&result, amount_format, compact)
+    })
+}
+
+pub fn plan_sweep(
+    utxos_json: &str,
+    destination_address: &str,
+    fee_rate: FeeRate,
+    network: &NetworkParams,
+    tip_height: Option<u32>,
+    allow_immature_coinbase: bool,
+) -> Result<SweepPlan, JsValue> {
+    let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+
+    check_amount_total(utxos.iter().map(|utxo| utxo.amount), "utxos").map_err(|e| JsValue::from_str(&e))?;
+
+    let destination_script = address_script(destination_address, Some(network))?;
+    let destination_vbytes = output_vbytes(&destination_script);
+    let mut inputs_used = Vec::new();
+    let mut inputs_skipped = Vec::new();
+    let mut inputs_vbytes = 0.0;
+    // Frozen coins are the user saying "not this one", even for a sweep.
+    for utxo in utxos.into_iter().filter(|utxo| !utxo.frozen) {
+        if !allow_immature_coinbase {
+            check_maturity(&utxo, tip_height).map_err(|e| JsValue::from_str(&e))?;
+        }
+        let vbytes = input_vbytes(&utxo)?;
+        if utxo.amount > fee_rate.fee_for_vbytes(vbytes).to_sat() {
+            inputs_vbytes += vbytes;
+            inputs_used.push(utxo);
+        } else {
+            inputs_skipped.push(utxo);
+        }
+    }
+
+    let mut plan = SweepPlan {
+        amount: 0,
+        fee: 0,
+        inputs_used,
+        inputs_skipped,
+        reason: None,
+    };
+
+    if plan.inputs_used.is_empty() {
+        plan.reason = Some(if plan.inputs_skipped.is_empty() {
+            "no UTXOs provided".to_string()
+        } else {
+            format!("all {} UTXOs cost more to spend than they are worth at this feerate", plan.inputs_skipped.len())
+        });
+        return Ok(plan);
+    }
+
+    let vbytes = TX_OVERHEAD_VBYTES + inputs_vbytes + destination_vbytes;
+    let fee = fee_rate.fee_for_vbytes(vbytes).to_sat();
+    let total: u64 = plan.inputs_used.iter().map(|utxo| utxo.amount).sum();
+    let dust = destination_script.minimal_non_dust().to_sat();
+
+    if total < fee + dust {
+        plan.reason = Some(format!(
+            "spendable total of {} sats does not cover the {} sat fee plus a non-dust output",
+            total, fee
+        ));
+        plan.inputs_skipped.append(&mut plan.inputs_used);
+        return Ok(plan);
+    }
+
+    plan.fee = fee;
+    plan.amount = total - fee;
+    Ok(plan)
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{Amount, Transaction, TxOut};
+use crate::script::interpreter::verify_inputs;
+use crate::transaction::standardness::{standardness, StandardnessOptions, StandardnessViolation, INVALID_SCRIPT_CODE, NONSTANDARD_SCRIPT_CODE};
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+
+const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+/// Core's DEFAULT_MAX_RAW_TX_FEE_RATE, 0.10 BTC/kvB, in sat/kWU.
+const DEFAULT_MAX_FEE_RATE: u64 = 2_500_000;
+
+/// Policy options, plus the fee-rate cap `sendrawtransaction` and `testmempoolaccept` apply.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptOptions {
+    #[serde(flatten)]
+    pub policy: StandardnessOptions,
+    /// `maxfeerate`; 0 disables the check.
+    #[serde(default, rename = "maxFeeRateSatVb", alias = "max_fee_rate_sat_vb", alias = "max_fee_rate")]
+    pub max_fee_rate: Option<FeeRate>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptFees {
+    /// Sats.
+    pub base: u64,
+    /// Over the sigop-adjusted vsize.
+    #[serde(rename = "effectiveFeeRateSatVb", alias = "effective_fee_rate_sat_vb", alias = "effective_feerate")]
+    pub effective_feerate: FeeRate,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptResult {
+    pub txid: String,
+    pub wtxid: String,
+    pub allowed: bool,
+    /// Bitcoin Core's reject reason for the first failed check.
+    #[serde(alias = "reject_reason")]
+    pub reject_reason: Option<String>,
+    #[serde(alias = "reject_details")]
+    pub reject_details: Option<String>,
+    pub vsize: u64,
+    pub fees: Option<AcceptFees>,
+    pub warnings: Vec<String>,
+    /// What a node would also check, but needs the chain or mempool for.
+    #[serde(alias = "checks_skipped")]
+    pub checks_skipped: Vec<String>,
+}
+
+/// A local stand-in for `testmempoolaccept`: consensus checks, relay policy, fees and
+/// every input's scripts (signatures included) against the given prevouts, in the order
+/// Bitcoin Core runs them. Checks that need the UTXO set, chain tip or mempool are listed
+/// in `checks_skipped` rather than run.
+#[wasm_bindgen]
+pub fn local_accept_check(tx_hex: &str, prevouts_json: &str, options_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("local_accept_check", tx_hex.len() + prevouts_json.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+        let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+        let options: AcceptOptions = match options_json {
+            Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+            _ => AcceptOptions::default(),
+        };
+        let max_fee_rate = options.max_fee_rate.unwrap_or(FeeRate::from_sat_per_kwu(DEFAULT_MAX_FEE_RATE));
+
+        let report = standardness(&tx, Some(&prevouts), &options.policy);
+        let fees = report.fee.map(|fee| AcceptFees { base: fee, effective_feerate: FeeRate::from_fee(Amount::from_sat(fee), report.vsize as f64) });
+        // Like Core, a script that fails only the policy flags is reported after the
+        // consensus script checks, which also give lax DER its precise failure.
+        let reason = |violation: &StandardnessViolation| (violation.code.clone(), violation.message.clone());
+        let script_code = |violation: &&StandardnessViolation| [NONSTANDARD_SCRIPT_CODE, INVALID_SCRIPT_CODE].contains(&violation.code.as_str());
+        let rejection = consensus_problem(&tx, &prevouts)
+            .or_else(|| report.violations.iter().find(|violation| !script_code(violation)).map(reason))
+            .or_else(|| script_problem(&tx, &prevouts))
+            .or_else(|| report.violations.iter().find(script_code).map(reason))
+            .or_else(|| {
+                let fees = fees.as_ref()?;
+                (max_fee_rate > FeeRate::ZERO && fees.effective_feerate > max_fee_rate).then(|| {
+                    (
+                        "max-fee-exceeded".to_string(),
+                        format!("Fee rate {:.2} sat/vB is above max_fee_rate_sat_vb {}", fees.effective_feerate.sat_per_vb(), max_fee_rate),
+                    )
+                })
+            });
+
+        let (reject_reason, reject_details) = rejection.unzip();
+        let result = AcceptResult {
+            txid: tx.compute_txid().to_string(),
+            wtxid: tx.compute_wtxid().to_string(),
+            allowed: reject_reason.is_none(),
+            reject_reason,
+            reject_details,
+            vsize: report.vsize,
+            fees,
+            warnings: report.warnings,
+            checks_skipped: [
+                "inputs exist and are unspent (UTXO set)",
+                "finality: nLockTime and BIP68 sequence locks against the chain tip",
+                "coinbase maturity of spent outputs",
+                "conflicts with mempool transactions and BIP125 replacement",
+                "mempool minimum fee and package, ancestor and descendant limits",
+                "policy-only script flags beyond low-S, minimal scriptSig pushes and STRICTENC (e.g. CLEANSTACK, NULLFAIL)",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+        };
+
+        to_json(&result, compact)
+    })
+}
+
+/// Core's CheckTransaction and CheckTxInputs.
+fn consensus_problem(tx: &Transaction, prevouts: &[TxOut]) -> Option<(String, String)> {
+    let reject = |code: &str, details: String| Some((code.to_string(), details));
+    if tx.input.is_empty() {
+        return reject("bad-txns-vin-empty", "Transaction has no inputs".to_string());
+    }
+    if tx.output.is_empty() {
+        return reject("bad-txns-vout-empty", "Transaction has no outputs".to_string());
+    }
+    if tx.base_size() * 4 > MAX_BLOCK_WEIGHT {
+        return reject("bad-txns-oversize", format!("Non-witness size {} bytes can't fit in a block", tx.base_size()));
+    }
+    let mut total_out = Amount::ZERO;
+    for (index, output) in tx.output.iter().enumerate() {
+        if output.value > Amount::MAX_MONEY {
+            return reject("bad-txns-vout-toolarge", format!("Output {} is worth more than 21 million BTC", index));
+        }
+        total_out = match total_out.checked_add(output.value).filter(|total| *total <= Amount::MAX_MONEY) {
+            Some(total) => total,
+            None => return reject("bad-txns-txouttotal-toolarge", "Outputs total more than 21 million BTC".to_string()),
+        };
+    }
+    let mut seen = HashSet::new();
+    for (index, input) in tx.input.iter().enumerate() {
+        if !seen.insert(input.previous_output) {
+            return reject("bad-txns-inputs-duplicate", format!("Input {} spends {} again", index, input.previous_output));
+        }
+    }
+    if tx.is_coinbase() {
+        return reject("coinbase", "Coinbase transactions can't be relayed".to_string());
+    }
+    if let Some(index) = tx.input.iter().position(|input| input.previous_output.is_null()) {
+        return reject("bad-txns-prevout-null", format!("Input {} spends the null outpoint", index));
+    }
+
+    let mut total_in = Amount::ZERO;
+    for prevout in prevouts {
+        total_in = match total_in.checked_add(prevout.value).filter(|total| *total <= Amount::MAX_MONEY) {
+            Some(total) => total,
+            None => return reject("bad-txns-inputvalues-outofrange", "Inputs total more than 21 million BTC".to_string()),
+        };
+    }
+    if total_in < total_out {
+        return reject(
+            "bad-txns-in-belowout",
+            format!("Inputs total {} sats, less than the outputs' {} sats", total_in.to_sat(), total_out.to_sat()),
+        );
+    }
+    None
+}
+
+fn script_problem(tx: &Transaction, prevouts: &[TxOut]) -> Option<(String, String)> {
+    verify_inputs(tx, prevouts).err().map(|(index, failure)| {
+        let at = match (&failure.opcode, failure.position) {
+            (Some(opcode), Some(position)) => format!(" at {} (instruction {} of {})", opcode, position, failure.phase),
+            _ => format!(" in {}", failure.phase),
+        };
+        (INVALID_SCRIPT_CODE.to_string(), format!("Input {}{}: {}", index, at, failure.reason))
+    })
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::{Address, ScriptBuf};
+use crate::script::output_script_type;
+use crate::utils::network::{address_network_label, parse_address_for_network, parse_network_params, AddressError, NetworkParams};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::sanitize::{sanitize, sanitize_with_warnings, InputKind};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressCheck {
+    /// The address as given, trimmed, with any invisible characters removed.
+    pub address: String,
+    pub valid: bool,
+    /// p2pkh, p2sh, p2wpkh, p2wsh, p2tr, or witness_unknown for future segwit versions.
+    #[serde(rename = "type")]
+    pub address_type: Option<String>,
+    /// The network it was checked against, or the networks its encoding belongs to.
+    pub network: Option<String>,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
+    /// The form to put in a QR code: see `address_for_qr`.
+    pub qr: Option<String>,
+    pub error: Option<String>,
+    /// Characters removed or replaced before checking, such as zero-width spaces.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressEntry {
+    pub index: usize,
+    #[serde(flatten)]
+    pub check: AddressCheck,
+    /// The first entry with the same scriptPubKey, or the same text if neither is valid.
+    #[serde(alias = "duplicate_of")]
+    pub duplicate_of: Option<usize>,
+}
+
+/// Checks an address, and with `network` set that it belongs to that network. Never
+/// throws for a bad address: the result says what's wrong with it.
+#[wasm_bindgen]
+pub fn validate_address(address: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("validate_address", address.len(), || {
+        let params = optional_network(network)?;
+        to_json(&check_address(address, params.as_ref()), compact)
+    })
+}
+
+/// `validate_address` for a JSON array of addresses in one call, one entry per element
+/// in input order. Entries that aren't strings are reported rather than failing the batch.
+#[wasm_bindgen]
+pub fn validate_addresses(addresses_json: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("validate_addresses", addresses_json.len(), || {
+        let params = optional_network(network)?;
+        let addresses: Vec<Value> = serde_json::from_str(addresses_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid addresses JSON: must be an array ({})", e)))?;
+
+        let mut first_seen: HashMap<String, usize> = HashMap::with_capacity(addresses.len());
+        let mut entries = Vec::with_capacity(addresses.len());
+        for (index, address) in addresses.iter().enumerate() {
+            let check = match address {
+                Value::String(address) => check_address(address, params.as_ref()),
+                other => AddressCheck {
+                    address: other.to_string(),
+                    valid: false,
+                    address_type: None,
+                    network: None,
+                    script_pubkey: None,
+                    qr: None,
+                    error: Some("Address must be a string".to_string()),
+                    warnings: Vec::new(),
+                },
+            };
+            let key = check.script_pubkey.clone().unwrap_or_else(|| check.address.clone());
+            let duplicate_of = match first_seen.get(&key) {
+                Some(&first) => Some(first),
+                None => {
+                    first_seen.insert(key, index);
+                    None
+                }
+            };
+            entries.push(AddressEntry { index, check, duplicate_of });
+        }
+
+        to_json(&entries, compact)
+    })
+}
+
+/// The address as it should go in a QR code. Bech32 addresses are uppercased, which
+/// BIP173 allows so that QR codes can use the denser alphanumeric mode; base58 is
+/// case-sensitive and comes back unchanged. `network` is only needed for custom
+/// address encodings.
+#[wasm_bindgen]
+pub fn address_for_qr(address: &str, network: Option<String>) -> Result<String, JsValue> {
+    let params = optional_network(network)?;
+    qr_address(address.trim(), params.as_ref()).map_err(|e| JsValue::from_str(&e))
+}
+
+/// A BIP21 `bitcoin:` URI for a QR code: the scheme and a bech32 address uppercased.
+/// Query parameters are left alone, since labels, messages and `pj` endpoints are
+/// case-sensitive.
+#[wasm_bindgen]
+pub fn bitcoin_uri_for_qr(uri: &str, network: Option<String>) -> Result<String, JsValue> {
+    let params = optional_network(network)?;
+    let uri = uri.trim();
+    let rest = match uri.get(..8) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("bitcoin:") => &uri[8..],
+        _ => return Err(JsValue::from_str("Not a BIP21 URI (expected bitcoin:...)")),
+    };
+    let (address, query) = match rest.split_once('?') {
+        Some((address, query)) => (address, Some(query)),
+        None => (rest, None),
+    };
+    let address = match address {
+        "" => String::new(),
+        address => qr_address(address, params.as_ref()).map_err(|e| JsValue::from_str(&e))?,
+    };
+    Ok(match query {
+        Some(query) => format!("BITCOIN:{}?{}", address, query),
+        None => format!("BITCOIN:{}", address),
+    })
+}
+
+/// The one validator behind both functions, using the same parsing as transaction outputs.
+pub fn check_address(address: &str, network: Option<&NetworkParams>) -> AddressCheck {
+    let mut check = AddressCheck {
+        address: address.trim().to_string(),
+        valid: false,
+        address_type: None,
+        network: None,
+        script_pubkey: None,
+        qr: None,
+        error: None,
+        warnings: Vec::new(),
+    };
+    let address = match sanitize_with_warnings(address, InputKind::Address) {
+        Ok((address, warnings)) => {
+            check.warnings = warnings;
+            address
+        }
+        Err(error) => {
+            check.error = Some(error);
+            return check;
+        }
+    };
+    let address = address.trim();
+    check.address = address.to_string();
+    if address.is_empty() {
+        check.error = Some("Empty address".to_string());
+        return check;
+    }
+
+    match parse_address_for_network(address, network) {
+        Ok(script) => {
+            let address_type = match output_script_type(&script) {
+                "nonstandard" if script.witness_version().is_some() => "witness_unknown",
+                address_type => address_type,
+            };
+            check.valid = true;
+            check.address_type = Some(address_type.to_string());
+            check.network = Some(match network {
+                Some(params) => params.target_name().to_string(),
+                None => address_label(address).to_string(),
+            });
+            check.script_pubkey = Some(script.to_hex_string());
+            check.qr = Some(qr_form(address, &script));
+        }
+        Err(AddressError::Invalid(message)) => check.error = Some(message),
+        Err(AddressError::WrongNetwork(label)) => {
+            let target = network.map_or("the requested network", NetworkParams::target_name);
+            check.network = Some(label.to_string());
+            check.error = Some(format!("Address is for {}, not {}", label, target));
+        }
+    }
+    check
+}
+
+fn qr_address(address: &str, network: Option<&NetworkParams>) -> Result<String, String> {
+    let address = sanitize(address, InputKind::Address)?;
+    let address = address.trim();
+    let script = parse_address_for_network(address, network).map_err(|e| match e {
+        AddressError::Invalid(message) => message,
+        AddressError::WrongNetwork(label) => {
+            format!("Address is for {}, not {}", label, network.map_or("the requested network", NetworkParams::target_name))
+        }
+    })?;
+    Ok(qr_form(address, &script))
+}
+
+fn qr_form(address: &str, script: &ScriptBuf) -> String {
+    if script.witness_version().is_some() {
+        address.to_ascii_uppercase()
+    } else {
+        address.to_string()
+    }
+}
+
+fn address_label(address: &str) -> &'static str {
+    match address.parse::<Address<NetworkUnchecked>>() {
+        Ok(unchecked) => address_network_label(&unchecked),
+        Err(_) => "an unknown network",
+    }
+}
+
+pub fn optional_network(network: Option<String>) -> Result<Option<NetworkParams>, JsValue> {
+    network
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| parse_network_params(&name))
+        .transpose()
+        .map_err(|e| JsValue::from_str(&e))
+}
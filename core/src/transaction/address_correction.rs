@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::base58;
+use bitcoin::bech32::primitives::decode::CheckedHrpstring;
+use bitcoin::bech32::{Bech32, Bech32m};
+use crate::transaction::address::{check_address, optional_network};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::network::NetworkParams;
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const STANDARD_HRPS: [&str; 3] = ["bc", "tb", "bcrt"];
+const MAX_SUGGESTIONS: usize = 3;
+/// Characters commonly misread for one another, in either encoding.
+const CONFUSABLE: [&str; 9] = ["0OoQD", "1lIi", "5Ss", "2Zz", "8B", "9gq", "uv", "vw", "nm"];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressSuggestion {
+    /// A valid address one edit away from the input. A guess, never to be used unconfirmed.
+    pub address: String,
+    /// `substitution`, `transposition` or `case`.
+    pub kind: String,
+    /// Character positions (0-based) that differ from the input.
+    pub positions: Vec<usize>,
+    /// The suggestion with the changed characters in brackets.
+    pub highlighted: String,
+    #[serde(rename = "type")]
+    pub address_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AddressCorrection {
+    pub address: String,
+    pub valid: bool,
+    /// `bech32` or `base58`, as far as the input's shape tells.
+    pub encoding: String,
+    /// The validation error for the input as given.
+    pub error: Option<String>,
+    /// Most likely first.
+    pub suggestions: Vec<AddressSuggestion>,
+    pub note: Option<String>,
+}
+
+/// Suggests what a mistyped address was probably meant to be: every address a single
+/// changed character or swapped pair of neighbours away that passes its checksum and
+/// `validate_address` for `network`. A bech32 checksum can only be repaired one way
+/// by a single substitution, so it points at the position of the error. A Base58Check
+/// checksum lets a random edit through once in 2^32. Characters outside the alphabet and
+/// look-alike swaps rank first. These are guesses to show the user, never to apply: one
+/// edit away from a typo can also be someone else's valid address.
+#[wasm_bindgen]
+pub fn suggest_address_correction(address: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("suggest_address_correction", address.len(), || {
+        let params = optional_network(network)?;
+        to_json(&correct(address, params.as_ref()), compact)
+    })
+}
+
+fn correct(address: &str, network: Option<&NetworkParams>) -> AddressCorrection {
+    let check = check_address(address, network);
+    let address = check.address.clone();
+    let bech32_data = bech32_data_start(&address, network);
+    let mut correction = AddressCorrection {
+        address: address.clone(),
+        valid: check.valid,
+        encoding: if bech32_data.is_some() { "bech32" } else { "base58" }.to_string(),
+        error: check.error,
+        suggestions: Vec::new(),
+        note: None,
+    };
+    if check.valid || address.is_empty() || !address.is_ascii() {
+        return correction;
+    }
+    if checksum_valid(&address, bech32_data.is_some()) {
+        correction.note = Some("The checksum holds, so this isn't a typo; see the error".to_string());
+        return correction;
+    }
+
+    let mut candidates: Vec<(u8, AddressSuggestion)> = Vec::new();
+    match bech32_data {
+        Some(data_start) => {
+            let lower = address.to_ascii_lowercase();
+            if lower != address && address.to_ascii_uppercase() != address && checksum_valid(&lower, true) {
+                candidates.extend(candidate(&lower, network, "case", Vec::new(), 0));
+            } else {
+                candidates.extend(edits(&lower, data_start, BECH32_CHARSET, network, true));
+            }
+        }
+        None => candidates.extend(edits(&address, 0, BASE58_ALPHABET, network, false)),
+    }
+    candidates.sort_by(|(a_rank, a), (b_rank, b)| a_rank.cmp(b_rank).then_with(|| a.positions.cmp(&b.positions)));
+    correction.suggestions = candidates.into_iter().take(MAX_SUGGESTIONS).map(|(_, suggestion)| suggestion).collect();
+    correction.note = Some(if correction.suggestions.is_empty() {
+        "No valid address is a single typo away; ask for the address again".to_string()
+    } else {
+        "Guesses only: confirm the address with the recipient before sending to any of these".to_string()
+    });
+    correction
+}
+
+/// Where a bech32 address's data part starts, when it has a known human-readable part.
+fn bech32_data_start(address: &str, network: Option<&NetworkParams>) -> Option<usize> {
+    let lower = address.to_ascii_lowercase();
+    let separator = lower.find('1')?;
+    let hrp = &lower[..separator];
+    let custom = network.map(|params| params.bech32_hrp.to_lowercase());
+    (STANDARD_HRPS.contains(&hrp) || custom.as_deref() == Some(hrp)).then_some(separator + 1)
+}
+
+/// Every single substitution and adjacent transposition from `start` on whose checksum
+/// holds and which is a valid address, ranked.
+fn edits(address: &str, start: usize, alphabet: &str, network: Option<&NetworkParams>, bech32: bool) -> Vec<(u8, AddressSuggestion)> {
+    let chars: Vec<char> = address.chars().collect();
+    let mut found = Vec::new();
+    for position in start..chars.len() {
+        let original = chars[position];
+        for replacement in alphabet.chars().filter(|&c| c != original) {
+            let mut edited = chars.clone();
+            edited[position] = replacement;
+            let edited: String = edited.into_iter().collect();
+            if !checksum_valid(&edited, bech32) {
+                continue;
+            }
+            let rank = if !alphabet.contains(original) {
+                0
+            } else if confusable(original, replacement) {
+                1
+            } else {
+                3
+            };
+            found.extend(candidate(&edited, network, "substitution", vec![position], rank));
+        }
+        if position + 1 < chars.len() && chars[position] != chars[position + 1] {
+            let mut edited = chars.clone();
+            edited.swap(position, position + 1);
+            let edited: String = edited.into_iter().collect();
+            if checksum_valid(&edited, bech32) {
+                found.extend(candidate(&edited, network, "transposition", vec![position, position + 1], 2));
+            }
+        }
+    }
+    found
+}
+
+fn checksum_valid(address: &str, bech32: bool) -> bool {
+    if bech32 {
+        CheckedHrpstring::new::<Bech32>(address).is_ok() || CheckedHrpstring::new::<Bech32m>(address).is_ok()
+    } else {
+        base58::decode_check(address).is_ok()
+    }
+}
+
+fn confusable(a: char, b: char) -> bool {
+    CONFUSABLE.iter().any(|group| group.contains(a) && group.contains(b))
+}
+
+/// The suggestion for `address` if it's valid for `network`.
+fn candidate(address: &str, network: Option<&NetworkParams>, kind: &str, positions: Vec<usize>, rank: u8) -> Option<(u8, AddressSuggestion)> {
+    let check = check_address(address, network);
+    if !check.valid {
+        return None;
+    }
+    let highlighted = address
+        .chars()
+        .enumerate()
+        .map(|(index, c)| if positions.contains(&index) { format!("[{}]", c) } else { c.to_string() })
+        .collect();
+    Some((rank, AddressSuggestion { address: address.to_string(), kind: kind.to_string(), positions, highlighted, address_type: check.address_type }))
+}
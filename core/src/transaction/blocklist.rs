@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{ScriptBuf, Transaction};
+use crate::transaction::parse_tx_hex;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::network::{network_or_default, require_address_network, NetworkParams};
+
+/// An output to screen: an entry of a builder's `outputs_json`, or anything else with an
+/// `address` or a `script_pubkey`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScreenedOutput {
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default, alias = "scriptPubkey", alias = "script_pubkey")]
+    script_pubkey: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreeningMatch {
+    /// The output's index in the transaction, or in the outputs array.
+    pub index: usize,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    /// The blocklist entry it matched, as given.
+    pub entry: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreeningResult {
+    /// True when no output is on the blocklist.
+    pub clear: bool,
+    #[serde(alias = "outputs_screened")]
+    pub outputs_screened: usize,
+    pub matches: Vec<ScreeningMatch>,
+}
+
+/// Checks a transaction's outputs against a blocklist before it is signed. The outputs
+/// are a transaction's hex or a JSON array of `{address}` or `{script_pubkey}` objects,
+/// such as a builder's `outputs_json`; the blocklist is a JSON array of addresses and
+/// scriptPubKey hex. Both sides are compared as scriptPubKey bytes, so an address in
+/// another case, bech32 variant or network prefix still matches. Nothing leaves wasm.
+#[wasm_bindgen]
+pub fn screen_outputs(tx_hex_or_outputs_json: &str, blocklist_json: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let blocklist = parse_blocklist(blocklist_json, &network).map_err(|e| JsValue::from_str(&e))?;
+    let scripts = output_scripts(tx_hex_or_outputs_json, &network)?;
+
+    let matches: Vec<ScreeningMatch> = scripts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, script)| {
+            blocklist.iter().find(|(listed, _)| listed == script).map(|(_, entry)| ScreeningMatch {
+                index,
+                script_pubkey: bytes_to_hex(script.as_bytes()),
+                entry: entry.clone(),
+            })
+        })
+        .collect();
+    let result = ScreeningResult { clear: matches.is_empty(), outputs_screened: scripts.len(), matches };
+    to_json(&result, compact)
+}
+
+/// A build's `blocklist` and `allowBlocklisted` from its `checks_json`: scripts its
+/// transaction mustn't pay, and the listed ones it may pay anyway because someone
+/// reviewed and approved the payment.
+#[derive(Default)]
+pub struct Blocklist {
+    /// Each listed scriptPubKey with the entry it came from, for the error message.
+    listed: Vec<(ScriptBuf, String)>,
+    allowed: Vec<ScriptBuf>,
+}
+
+impl Blocklist {
+    pub fn new(entries: &[String], allowed: &[String], network: &NetworkParams) -> Result<Blocklist, String> {
+        let listed = parse_entries(entries, "blocklist", network)?;
+        let allowed = parse_entries(allowed, "allowBlocklisted", network)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, (script, entry))| {
+                if listed.iter().any(|(listed, _)| *listed == script) {
+                    Ok(script)
+                } else {
+                    Err(format!("allowBlocklisted[{}]: {} isn't on the blocklist", index, entry))
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Blocklist { listed, allowed })
+    }
+
+    /// Checks a finished transaction's outputs.
+    pub fn check(&self, tx: &Transaction) -> Result<(), String> {
+        for (vout, output) in tx.output.iter().enumerate() {
+            let Some((script, entry)) = self.listed.iter().find(|(listed, _)| *listed == output.script_pubkey) else {
+                continue;
+            };
+            if !self.allowed.contains(script) {
+                return Err(format!(
+                    "Output {} of the transaction pays {}, which is on the blocklist; allowBlocklisted allows it for this build",
+                    vout, entry
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_blocklist(json: &str, network: &NetworkParams) -> Result<Vec<(ScriptBuf, String)>, String> {
+    let entries: Vec<String> = parse_json_list(json, "blocklist")?;
+    parse_entries(&entries, "blocklist", network)
+}
+
+fn parse_entries(entries: &[String], what: &str, network: &NetworkParams) -> Result<Vec<(ScriptBuf, String)>, String> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let script = entry_script(entry, network).map_err(|e| format!("{}[{}]: {}", what, index, e))?;
+            Ok((script, entry.trim().to_string()))
+        })
+        .collect()
+}
+
+/// An address or scriptPubKey hex. Addresses are tried first, since some are valid hex.
+fn entry_script(entry: &str, network: &NetworkParams) -> Result<ScriptBuf, String> {
+    let entry = entry.trim();
+    any_network_address(entry, network).or_else(|address_error| match hex_to_bytes(entry, HexMode::Lenient) {
+        Ok(bytes) if !bytes.is_empty() => Ok(ScriptBuf::from_bytes(bytes)),
+        _ => Err(address_error),
+    })
+}
+
+/// An address on any network, so a listed mainnet address also blocks its script on the
+/// test networks, plus `network`'s own encoding if it has one.
+fn any_network_address(address: &str, network: &NetworkParams) -> Result<ScriptBuf, String> {
+    require_address_network(address, None).or_else(|e| {
+        if network.has_custom_encoding() {
+            require_address_network(address, Some(network))
+        } else {
+            Err(e)
+        }
+    })
+}
+
+fn output_scripts(tx_hex_or_outputs_json: &str, network: &NetworkParams) -> Result<Vec<ScriptBuf>, JsValue> {
+    let input = tx_hex_or_outputs_json.trim();
+    if !input.starts_with('[') {
+        let tx = parse_tx_hex(input).map_err(|e| JsValue::from_str(&e))?;
+        return Ok(tx.output.into_iter().map(|output| output.script_pubkey).collect());
+    }
+    let outputs: Vec<ScreenedOutput> = parse_json_list(input, "outputs").map_err(|e| JsValue::from_str(&e))?;
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| match (&output.script_pubkey, &output.address) {
+            (Some(script), _) => hex_to_bytes(script, HexMode::Lenient)
+                .map(ScriptBuf::from_bytes)
+                .map_err(|e| JsValue::from_str(&format!("outputs[{}]: Invalid scriptPubkey hex: {}", index, e))),
+            (None, Some(address)) => any_network_address(address.trim(), network)
+                .map_err(|e| JsValue::from_str(&format!("outputs[{}]: {}", index, e))),
+            (None, None) => Err(JsValue::from_str(&format!("outputs[{}]: needs an address or a scriptPubkey", index))),
+        })
+        .collect()
+}
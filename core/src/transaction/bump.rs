@@ -0,0 +1,462 @@
+use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, WPubkeyHash, Witness};
+use crate::transaction::rbf::{RbfPrevout, DEFAULT_INCREMENTAL_RELAY_FEE};
+use crate::transaction::weight::{estimated_signed_vsize, utxo_input_type};
+use crate::transaction::{parse_tx_hex, TransactionInput};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::metrics::timed;
+use crate::utils::units::FeeRate;
+use crate::utils::varint::encode_compact_size;
+
+/// Bitcoin Core's default ancestor count limit, which a CPFP child counts towards.
+const CHAIN_COUNT_LIMIT: usize = 25;
+const UNKNOWN_FEE: &str = "its fee is unknown: give the prevouts of its inputs and of its unconfirmed ancestors";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RbfOption {
+    pub possible: bool,
+    #[serde(alias = "signals_rbf")]
+    pub signals_rbf: bool,
+    /// `reduce_change`, `drop_change` or `add_input`.
+    pub funding: Option<String>,
+    #[serde(alias = "change_vout")]
+    pub change_vout: Option<u32>,
+    /// The confirmed coin added to pay for it, as `txid:vout`.
+    #[serde(alias = "added_input")]
+    pub added_input: Option<String>,
+    #[serde(alias = "new_fee")]
+    pub new_fee: Option<u64>,
+    #[serde(alias = "new_vsize")]
+    pub new_vsize: Option<u64>,
+    /// Sats on top of the original's fee.
+    pub cost: Option<u64>,
+    /// Descendants the replacement invalidates, which would have to be sent again.
+    pub evicts: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpfpOption {
+    pub possible: bool,
+    /// The owned output the child spends.
+    #[serde(alias = "spend_vout")]
+    pub spend_vout: Option<u32>,
+    #[serde(alias = "child_vsize")]
+    pub child_vsize: Option<u64>,
+    /// Everything the child pays in fees, which is all of its cost.
+    #[serde(alias = "child_fee")]
+    pub child_fee: Option<u64>,
+    pub cost: Option<u64>,
+    pub problems: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BumpOptions {
+    pub txid: String,
+    pub vsize: u64,
+    /// None when an input's amount is unknown.
+    pub fee: Option<u64>,
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: Option<FeeRate>,
+    /// The rate miners see: the transaction with its unconfirmed ancestors, or a
+    /// descendant's package that already pays more.
+    #[serde(rename = "effectiveFeeRateSatVb", alias = "effective_fee_rate_sat_vb", alias = "effective_fee_rate")]
+    pub effective_fee_rate: Option<FeeRate>,
+    #[serde(alias = "unconfirmed_ancestors")]
+    pub unconfirmed_ancestors: Vec<String>,
+    pub descendants: Vec<String>,
+    pub rbf: RbfOption,
+    pub cpfp: CpfpOption,
+    /// `none`, `rbf`, `cpfp` or `wait`.
+    pub recommendation: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BumpReport {
+    #[serde(rename = "targetFeeRateSatVb", alias = "target_fee_rate_sat_vb", alias = "target_fee_rate")]
+    pub target_fee_rate: FeeRate,
+    pub transactions: Vec<BumpOptions>,
+}
+
+/// The wallet's unconfirmed transactions and what they spend and create.
+struct Pool<'a> {
+    txs: Vec<(Txid, Transaction)>,
+    index: HashMap<Txid, usize>,
+    /// Which unconfirmed transaction spends each outpoint.
+    spent_by: HashMap<OutPoint, Txid>,
+    prevouts: HashMap<OutPoint, u64>,
+    utxos: HashMap<OutPoint, &'a TransactionInput>,
+}
+
+/// What a wallet can do about each of its unconfirmed transactions to get it confirmed at
+/// `target_fee_rate_sat_vb`: replace it (BIP125 RBF, paid from its change or an added
+/// confirmed coin), or spend one of its outputs with a child paying for the package
+/// (CPFP), with the cost of each in sats and the cheaper one recommended. The wallet's
+/// coins come from `utxos_json` in `select_coins` format, unconfirmed ones (no `height`)
+/// included; an output is the wallet's if it's listed there or another of the
+/// transactions spends it. `prevouts_json` gives `{txid, vout, amountSat}` for inputs
+/// that don't spend one of the transactions. Replacements keep their outputs' order.
+/// Package rates only approximate how miners pick transactions, and the mempool's own
+/// view of other people's descendants isn't available.
+#[wasm_bindgen]
+pub fn bump_options(unconfirmed_txs_json: &str, prevouts_json: &str, utxos_json: &str, target_fee_rate_sat_vb: f64, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("bump_options", unconfirmed_txs_json.len() + prevouts_json.len() + utxos_json.len(), || {
+        let target = FeeRate::from_sat_per_vb(target_fee_rate_sat_vb).map_err(|e| JsValue::from_str(&e))?;
+        let tx_hexes: Vec<String> = parse_json_list(unconfirmed_txs_json, "unconfirmed transactions").map_err(|e| JsValue::from_str(&e))?;
+        let prevouts: Vec<RbfPrevout> = if prevouts_json.trim().is_empty() {
+            Vec::new()
+        } else {
+            parse_json_list(prevouts_json, "prevouts").map_err(|e| JsValue::from_str(&e))?
+        };
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "utxos").map_err(|e| JsValue::from_str(&e))?;
+        let pool = Pool::new(&tx_hexes, &prevouts, &utxos).map_err(|e| JsValue::from_str(&e))?;
+
+        let transactions = pool.txs.iter().map(|(txid, tx)| pool.options(*txid, tx, target)).collect();
+        to_json(&BumpReport { target_fee_rate: target, transactions }, compact)
+    })
+}
+
+impl<'a> Pool<'a> {
+    fn new(tx_hexes: &[String], prevouts: &[RbfPrevout], utxos: &'a [TransactionInput]) -> Result<Pool<'a>, String> {
+        let mut pool = Pool { txs: Vec::new(), index: HashMap::new(), spent_by: HashMap::new(), prevouts: HashMap::new(), utxos: HashMap::new() };
+        for (position, hex) in tx_hexes.iter().enumerate() {
+            let tx = parse_tx_hex(hex).map_err(|e| format!("unconfirmed transactions[{}]: {}", position, e))?;
+            let txid = tx.compute_txid();
+            if pool.index.contains_key(&txid) {
+                continue;
+            }
+            for input in &tx.input {
+                if let Some(other) = pool.spent_by.insert(input.previous_output, txid) {
+                    return Err(format!("{} and {} both spend {}; only one of them can confirm", other, txid, input.previous_output));
+                }
+            }
+            pool.index.insert(txid, pool.txs.len());
+            pool.txs.push((txid, tx));
+        }
+        for (position, prevout) in prevouts.iter().enumerate() {
+            let txid = prevout.txid.parse().map_err(|e| format!("prevouts[{}]: invalid txid: {}", position, e))?;
+            pool.prevouts.insert(OutPoint { txid, vout: prevout.vout }, prevout.amount);
+        }
+        for (position, utxo) in utxos.iter().enumerate() {
+            let txid = utxo.txid.parse().map_err(|e| format!("utxos[{}]: invalid txid: {}", position, e))?;
+            pool.utxos.insert(OutPoint { txid, vout: utxo.vout }, utxo);
+        }
+        Ok(pool)
+    }
+
+    fn tx(&self, txid: &Txid) -> Option<&Transaction> {
+        self.index.get(txid).map(|&index| &self.txs[index].1)
+    }
+
+    fn fee(&self, tx: &Transaction) -> Option<u64> {
+        let spent: Option<u64> = tx
+            .input
+            .iter()
+            .map(|input| {
+                let outpoint = input.previous_output;
+                match self.tx(&outpoint.txid) {
+                    Some(parent) => parent.output.get(outpoint.vout as usize).map(|output| output.value.to_sat()),
+                    None => self.prevouts.get(&outpoint).copied(),
+                }
+            })
+            .sum();
+        spent?.checked_sub(tx.output.iter().map(|output| output.value.to_sat()).sum())
+    }
+
+    fn ancestors(&self, tx: &Transaction) -> BTreeSet<Txid> {
+        let mut found = BTreeSet::new();
+        let mut pending: Vec<&Transaction> = vec![tx];
+        while let Some(next) = pending.pop() {
+            for input in &next.input {
+                if let Some(parent) = self.tx(&input.previous_output.txid) {
+                    if found.insert(input.previous_output.txid) {
+                        pending.push(parent);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn descendants(&self, txid: Txid) -> BTreeSet<Txid> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![txid];
+        while let Some(next) = pending.pop() {
+            let Some(tx) = self.tx(&next) else { continue };
+            for vout in 0..tx.output.len() as u32 {
+                if let Some(&child) = self.spent_by.get(&OutPoint { txid: next, vout }) {
+                    if found.insert(child) {
+                        pending.push(child);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Vsize and fees of `tx` with its unconfirmed ancestors. Fees are None when any is
+    /// unknown.
+    fn package(&self, tx: &Transaction) -> (u64, Option<u64>) {
+        let mut vsize = tx.vsize() as u64;
+        let mut fees = self.fee(tx);
+        for ancestor in self.ancestors(tx).iter().filter_map(|txid| self.tx(txid)) {
+            vsize += ancestor.vsize() as u64;
+            fees = fees.zip(self.fee(ancestor)).map(|(a, b)| a + b);
+        }
+        (vsize, fees)
+    }
+
+    fn is_owned(&self, outpoint: &OutPoint) -> bool {
+        self.utxos.contains_key(outpoint) || self.spent_by.contains_key(outpoint)
+    }
+
+    fn options(&self, txid: Txid, tx: &Transaction, target: FeeRate) -> BumpOptions {
+        let vsize = tx.vsize() as u64;
+        let fee = self.fee(tx);
+        let ancestors = self.ancestors(tx);
+        let descendants = self.descendants(txid);
+        let (package_vsize, package_fees) = self.package(tx);
+        let package_rate = package_fees.map(|fees| FeeRate::from_fee(Amount::from_sat(fees), package_vsize as f64));
+        // A descendant's package includes this transaction, so one that pays more pulls it in.
+        let lifted = descendants
+            .iter()
+            .filter_map(|child| self.tx(child))
+            .filter_map(|child| {
+                let (vsize, fees) = self.package(child);
+                Some(FeeRate::from_fee(Amount::from_sat(fees?), vsize as f64))
+            })
+            .reduce(|best, rate| if rate > best { rate } else { best });
+        let effective = match (package_rate, lifted) {
+            (Some(own), Some(lifted)) if lifted > own => Some(lifted),
+            (own, _) => own,
+        };
+
+        let rbf = self.rbf_option(tx, fee, &descendants, package_vsize, package_fees, target);
+        let cpfp = self.cpfp_option(txid, tx, &ancestors, package_vsize, package_fees, target);
+        let (recommendation, reason) = match effective {
+            Some(rate) if rate >= target => {
+                let reason = if lifted == effective && package_rate < effective {
+                    format!("A descendant already brings it to {:.2} sat/vB, at or above the target", rate.sat_per_vb())
+                } else {
+                    format!("It already pays {:.2} sat/vB, at or above the target", rate.sat_per_vb())
+                };
+                ("none", reason)
+            }
+            _ => match (rbf.cost.filter(|_| rbf.possible), cpfp.cost.filter(|_| cpfp.possible)) {
+                (Some(rbf_cost), Some(cpfp_cost)) if cpfp_cost < rbf_cost => {
+                    ("cpfp", format!("A child costs {} sats, less than the {} a replacement adds", cpfp_cost, rbf_cost))
+                }
+                (Some(rbf_cost), Some(cpfp_cost)) => {
+                    ("rbf", format!("A replacement adds {} sats, no more than the {} a child costs", rbf_cost, cpfp_cost))
+                }
+                (Some(rbf_cost), None) => ("rbf", format!("A replacement adds {} sats; a child isn't possible", rbf_cost)),
+                (None, Some(cpfp_cost)) => ("cpfp", format!("A child costs {} sats; a replacement isn't possible", cpfp_cost)),
+                (None, None) => {
+                    let problems: Vec<&str> = rbf.problems.iter().chain(&cpfp.problems).map(String::as_str).collect();
+                    ("wait", format!("Neither a replacement nor a child is possible: {}", problems.join("; ")))
+                }
+            },
+        };
+
+        BumpOptions {
+            txid: txid.to_string(),
+            vsize,
+            fee,
+            fee_rate: fee.map(|fee| FeeRate::from_fee(Amount::from_sat(fee), vsize as f64)),
+            effective_fee_rate: effective,
+            unconfirmed_ancestors: ancestors.iter().map(Txid::to_string).collect(),
+            descendants: descendants.iter().map(Txid::to_string).collect(),
+            rbf,
+            cpfp,
+            recommendation: recommendation.to_string(),
+            reason,
+        }
+    }
+
+    fn rbf_option(
+        &self,
+        tx: &Transaction,
+        fee: Option<u64>,
+        descendants: &BTreeSet<Txid>,
+        package_vsize: u64,
+        package_fees: Option<u64>,
+        target: FeeRate,
+    ) -> RbfOption {
+        let signals_rbf = tx.input.iter().any(|input| input.sequence.is_rbf());
+        let mut option = RbfOption {
+            possible: false,
+            signals_rbf,
+            funding: None,
+            change_vout: None,
+            added_input: None,
+            new_fee: None,
+            new_vsize: None,
+            cost: None,
+            evicts: descendants.iter().map(Txid::to_string).collect(),
+            problems: Vec::new(),
+        };
+        if !signals_rbf {
+            option.problems.push(
+                "it doesn't signal replaceability (BIP125); nodes running Bitcoin Core 28 or later replace it anyway by default, \
+                 but older or differently configured nodes refuse"
+                    .to_string(),
+            );
+        }
+        let (Some(fee), Some(package_fees)) = (fee, package_fees) else {
+            option.problems.push(UNKNOWN_FEE.to_string());
+            return option;
+        };
+        let evicted_fees: Option<u64> = descendants.iter().map(|child| self.tx(child).and_then(|child| self.fee(child))).sum();
+        let Some(evicted_fees) = evicted_fees else {
+            option.problems.push("a descendant's fee is unknown, and the replacement has to pay for evicting it".to_string());
+            return option;
+        };
+
+        let vsize = tx.vsize() as f64;
+        let ancestor_fees = package_fees - fee;
+        let ancestor_vsize = (package_vsize - tx.vsize() as u64) as f64;
+        let incremental = FeeRate::from_sat_per_vb(DEFAULT_INCREMENTAL_RELAY_FEE).unwrap_or(FeeRate::ZERO);
+        // What a replacement of `new_vsize` vB has to pay: enough for its package to reach
+        // the target, and the original's fees plus its own relay (BIP125 rules 3 and 4).
+        let required = |new_vsize: f64| {
+            let for_target = target.fee_for_vbytes(ancestor_vsize + new_vsize).to_sat().saturating_sub(ancestor_fees);
+            for_target.max(fee + evicted_fees + incremental.fee_for_vbytes(new_vsize).to_sat())
+        };
+
+        // Paid from the largest change output, which stays above dust or goes to fees.
+        let change = (0..tx.output.len() as u32)
+            .filter(|&vout| self.is_owned(&OutPoint { txid: tx.compute_txid(), vout }))
+            .max_by_key(|&vout| tx.output[vout as usize].value);
+        if let Some(vout) = change {
+            let output = &tx.output[vout as usize];
+            let extra = required(vsize) - fee;
+            let dust = output.script_pubkey.minimal_non_dust().to_sat();
+            if output.value.to_sat() >= extra + dust {
+                return funded(option, "reduce_change", Some(vout), None, fee + extra, vsize, fee);
+            }
+            let without_change = vsize - output_vbytes(&output.script_pubkey);
+            if tx.output.len() > 1 && output.value.to_sat() + fee >= required(without_change) {
+                return funded(option, "drop_change", Some(vout), None, fee + output.value.to_sat(), without_change, fee);
+            }
+        }
+
+        // Otherwise the smallest confirmed coin that covers it, with any excess going to
+        // the change output, or a new one.
+        let mut coins: Vec<(&OutPoint, &&TransactionInput)> = self
+            .utxos
+            .iter()
+            .filter(|(outpoint, utxo)| utxo.height.is_some() && !utxo.frozen && !self.spent_by.contains_key(outpoint))
+            .collect();
+        coins.sort_by_key(|(outpoint, utxo)| (utxo.amount, outpoint.to_string()));
+        for (outpoint, utxo) in coins {
+            let Ok(input_type) = utxo_input_type(utxo) else { continue };
+            let with_input = vsize + input_type.vbytes();
+            if change.is_some() {
+                let new_fee = required(with_input);
+                if utxo.amount + fee >= new_fee {
+                    return funded(option, "add_input", change, Some(outpoint), new_fee, with_input, fee);
+                }
+                continue;
+            }
+            // Change goes back to the coin's own script type, P2WPKH when it isn't given.
+            let change_script = match hex_to_bytes(&utxo.script_pubkey, HexMode::Lenient) {
+                Ok(bytes) if !bytes.is_empty() => ScriptBuf::from_bytes(bytes),
+                _ => ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+            };
+            let with_change = with_input + output_vbytes(&change_script);
+            let new_fee = required(with_change);
+            let dust = change_script.minimal_non_dust().to_sat();
+            if utxo.amount + fee >= new_fee + dust {
+                return funded(option, "add_input", None, Some(outpoint), new_fee, with_change, fee);
+            }
+            if utxo.amount + fee >= required(with_input) {
+                return funded(option, "add_input", None, Some(outpoint), utxo.amount + fee, with_input, fee);
+            }
+        }
+        option.problems.push(match change {
+            Some(_) => "the change output is too small and no confirmed coin covers the rest".to_string(),
+            None => "it has no change output and no confirmed coin covers the higher fee".to_string(),
+        });
+        option
+    }
+
+    fn cpfp_option(&self, txid: Txid, tx: &Transaction, ancestors: &BTreeSet<Txid>, package_vsize: u64, package_fees: Option<u64>, target: FeeRate) -> CpfpOption {
+        let mut option = CpfpOption { possible: false, spend_vout: None, child_vsize: None, child_fee: None, cost: None, problems: Vec::new() };
+        let spendable: Vec<(u32, &TransactionInput)> = (0..tx.output.len() as u32)
+            .filter_map(|vout| {
+                let outpoint = OutPoint { txid, vout };
+                let utxo = self.utxos.get(&outpoint).filter(|utxo| !utxo.frozen && !self.spent_by.contains_key(&outpoint))?;
+                Some((vout, *utxo))
+            })
+            .collect();
+        if spendable.is_empty() {
+            option.problems.push("it has no unspent output of ours for a child to spend".to_string());
+            return option;
+        }
+        // The child counts towards the ancestor limit along with this transaction.
+        if ancestors.len() + 2 > CHAIN_COUNT_LIMIT {
+            option.problems.push(format!("a child would have {} unconfirmed ancestors, over the limit of {}", ancestors.len() + 1, CHAIN_COUNT_LIMIT - 1));
+            return option;
+        }
+        let Some(package_fees) = package_fees else {
+            option.problems.push(UNKNOWN_FEE.to_string());
+            return option;
+        };
+
+        // Of the outputs that can pay for a child, the largest, which leaves the most behind.
+        let mut best = None;
+        for (vout, utxo) in spendable {
+            let output = &tx.output[vout as usize];
+            let child = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn { previous_output: OutPoint { txid, vout }, script_sig: ScriptBuf::new(), sequence: Sequence::ENABLE_RBF_NO_LOCKTIME, witness: Witness::new() }],
+                output: vec![TxOut { value: output.value, script_pubkey: output.script_pubkey.clone() }],
+            };
+            let child_vsize = estimated_signed_vsize(&child, std::slice::from_ref(utxo));
+            let for_target = target.fee_for_vbytes((package_vsize + child_vsize) as f64).to_sat().saturating_sub(package_fees);
+            let child_fee = for_target.max(child_vsize);
+            let remaining = output.value.to_sat().saturating_sub(child_fee);
+            if remaining >= output.script_pubkey.minimal_non_dust().to_sat() && best.is_none_or(|(_, _, _, value): (u32, u64, u64, u64)| output.value.to_sat() > value) {
+                best = Some((vout, child_vsize, child_fee, output.value.to_sat()));
+            }
+        }
+        match best {
+            Some((vout, child_vsize, child_fee, _)) => {
+                option.possible = true;
+                option.spend_vout = Some(vout);
+                option.child_vsize = Some(child_vsize);
+                option.child_fee = Some(child_fee);
+                option.cost = Some(child_fee);
+            }
+            None => option.problems.push("none of our outputs is large enough to pay for a child and stay above dust".to_string()),
+        }
+        option
+    }
+}
+
+fn funded(mut option: RbfOption, funding: &str, change_vout: Option<u32>, added: Option<&OutPoint>, new_fee: u64, new_vsize: f64, fee: u64) -> RbfOption {
+    option.possible = option.signals_rbf;
+    option.funding = Some(funding.to_string());
+    option.change_vout = change_vout;
+    option.added_input = added.map(OutPoint::to_string);
+    option.new_fee = Some(new_fee);
+    option.new_vsize = Some(new_vsize.ceil() as u64);
+    option.cost = Some(new_fee - fee);
+    option
+}
+
+fn output_vbytes(script_pubkey: &ScriptBuf) -> f64 {
+    (8 + encode_compact_size(script_pubkey.len() as u64).len() + script_pubkey.len()) as f64
+}
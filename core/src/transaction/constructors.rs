@@ -0,0 +1,308 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use bitcoin::{Amount, ScriptBuf, Txid};
+use crate::transaction::weight::parse_input_type;
+use crate::transaction::{TransactionInput, TransactionOutput};
+use crate::utils::json::{parse_json_value, to_json, value_kind};
+use crate::utils::sanitize::{sanitize, InputKind};
+use crate::utils::network::{network_or_default, parse_address_for_network, require_address_network, AddressError};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Outpoint {
+    pub txid: String,
+    pub vout: u32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct InputOptions {
+    #[serde(default, alias = "input_type")]
+    input_type: Option<String>,
+    #[serde(default, alias = "is_coinbase")]
+    is_coinbase: bool,
+    #[serde(default)]
+    height: Option<u32>,
+    #[serde(default)]
+    frozen: bool,
+    #[serde(default, alias = "must_spend")]
+    must_spend: bool,
+}
+
+/// Validates an outpoint and returns it as `{txid, vout}` JSON. `vout` has to be a whole
+/// JS number; a numeric string is refused rather than converted.
+#[wasm_bindgen]
+pub fn make_outpoint(txid: &str, vout: JsValue, compact: Option<bool>) -> Result<String, JsValue> {
+    let vout = match vout.as_f64() {
+        Some(number) => Ok(number),
+        None => Err(match vout.as_string() {
+            Some(text) => format!("vout must be a number, got the string {:?}", text),
+            None => format!("vout must be a number, got {}", vout.js_typeof().as_string().unwrap_or_default()),
+        }),
+    };
+    let outpoint = checked_outpoint(txid, vout).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&outpoint, compact)
+}
+
+/// `make_outpoint` once `vout` is out of its `JsValue`. A bad txid is reported first.
+fn checked_outpoint(txid: &str, vout: Result<f64, String>) -> Result<Outpoint, String> {
+    let txid = checked_txid(txid)?;
+    Ok(Outpoint { txid: txid.to_string(), vout: checked_vout(vout?)? })
+}
+
+/// Validates a coin to spend and returns it as the input JSON `build_transaction` takes.
+/// `outpoint_json` is `make_outpoint`'s result, any object with `txid` and `vout`, or a
+/// `txid:vout` string. `options_json` sets the remaining `TransactionInput` fields;
+/// unknown ones are refused.
+#[wasm_bindgen]
+pub fn make_input(outpoint_json: &str, amount: u64, script_pubkey: &str, options_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let outpoint = parse_outpoint_json(outpoint_json).map_err(|e| JsValue::from_str(&e))?;
+    check_amount(amount).map_err(|e| JsValue::from_str(&e))?;
+    if script_pubkey.is_empty() {
+        return Err(JsValue::from_str("script_pubkey is empty"));
+    }
+    let script_pubkey =
+        ScriptBuf::from_hex(script_pubkey).map_err(|e| JsValue::from_str(&format!("Invalid script_pubkey: {}", e)))?;
+    let options: InputOptions = match options_json {
+        Some(json) if !json.trim().is_empty() => parse_json_value(&json, "options").map_err(|e| JsValue::from_str(&e))?,
+        _ => InputOptions::default(),
+    };
+    if let Some(input_type) = &options.input_type {
+        parse_input_type(input_type).map_err(|e| JsValue::from_str(&format!("options.inputType: {}", e)))?;
+    }
+    if options.frozen && options.must_spend {
+        return Err(JsValue::from_str("A coin can't be both frozen and mustSpend"));
+    }
+
+    let input = TransactionInput {
+        txid: outpoint.txid,
+        vout: outpoint.vout,
+        amount,
+        script_pubkey: script_pubkey.to_hex_string(),
+        input_type: options.input_type,
+        is_coinbase: options.is_coinbase,
+        height: options.height,
+        frozen: options.frozen,
+        must_spend: options.must_spend,
+    };
+    to_json(&input, compact)
+}
+
+/// Validates a payment and returns it as the output JSON `build_transaction` takes.
+/// `address_or_script` is an address for `network`, or script hex for outputs with no
+/// address such as OP_RETURN.
+#[wasm_bindgen]
+pub fn make_output(address_or_script: &str, amount: u64, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    check_amount(amount).map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let target = address_or_script.trim();
+    if target.is_empty() {
+        return Err(JsValue::from_str("An output needs an address or a script"));
+    }
+
+    let (address, script_pubkey) = match parse_address_for_network(target, Some(&network)) {
+        Ok(_) => (Some(target.to_string()), None),
+        Err(AddressError::Invalid(_)) if target.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            let script = ScriptBuf::from_hex(target).map_err(|e| JsValue::from_str(&format!("Invalid script: {}", e)))?;
+            (None, Some(script.to_hex_string()))
+        }
+        Err(_) => return Err(JsValue::from_str(&require_address_network(target, Some(&network)).unwrap_err())),
+    };
+    let output = TransactionOutput { address, amount, is_change: false, script_pubkey };
+    to_json(&output, compact)
+}
+
+fn parse_outpoint_json(outpoint_json: &str) -> Result<Outpoint, String> {
+    let value: Value = serde_json::from_str(outpoint_json).map_err(|e| format!("Invalid outpoint JSON: {}", e))?;
+    let (txid, vout) = match &value {
+        Value::String(outpoint) => {
+            let (txid, vout) =
+                outpoint.rsplit_once(':').ok_or_else(|| format!("Outpoint {:?} isn't of the form txid:vout", outpoint))?;
+            if vout.is_empty() || !vout.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(format!("Outpoint {:?}: vout must be a decimal number", outpoint));
+            }
+            (txid, checked_vout(vout.parse::<f64>().unwrap_or(f64::NAN))?)
+        }
+        Value::Object(fields) => {
+            let txid = match fields.get("txid") {
+                Some(Value::String(txid)) => txid.as_str(),
+                Some(other) => return Err(format!("outpoint.txid must be a string, got {}", value_kind(other))),
+                None => return Err("outpoint.txid is missing".to_string()),
+            };
+            let vout = match fields.get("vout") {
+                Some(Value::Number(number)) => {
+                    checked_vout(number.as_f64().unwrap_or(f64::NAN)).map_err(|e| format!("outpoint.{}", e))?
+                }
+                Some(Value::String(text)) => return Err(format!("outpoint.vout must be a number, got the string {:?}", text)),
+                Some(other) => return Err(format!("outpoint.vout must be a number, got {}", value_kind(other))),
+                None => return Err("outpoint.vout is missing".to_string()),
+            };
+            (txid, vout)
+        }
+        other => return Err(format!("An outpoint is a {{txid, vout}} object or a txid:vout string, got {}", value_kind(other))),
+    };
+    Ok(Outpoint { txid: checked_txid(txid)?.to_string(), vout })
+}
+
+/// A txid in display (explorer) byte order: exactly 64 hex characters, nothing around them.
+fn checked_txid(txid: &str) -> Result<Txid, String> {
+    let txid = sanitize(txid, InputKind::Hex)?;
+    let txid = txid.as_ref();
+    if txid.len() != 64 {
+        return Err(format!("txid must be 64 hex characters, got {}", txid.len()));
+    }
+    if let Some(position) = txid.bytes().position(|b| !b.is_ascii_hexdigit()) {
+        return Err(format!("txid has a non-hex character {:?} at position {}", txid[position..].chars().next().unwrap_or('?'), position));
+    }
+    Txid::from_str(txid).map_err(|e| format!("Invalid txid: {}", e))
+}
+
+fn checked_vout(vout: f64) -> Result<u32, String> {
+    if vout.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&vout) {
+        return Err(format!("vout must be a whole number from 0 to {}, got {}", u32::MAX, vout));
+    }
+    Ok(vout as u32)
+}
+
+fn check_amount(amount: u64) -> Result<(), String> {
+    let max_money = Amount::MAX_MONEY.to_sat();
+    if amount > max_money {
+        return Err(format!("amount {} exceeds the maximum of {} sats", amount, max_money));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::deserialize;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{Address, Network, PubkeyHash, ScriptHash, Transaction, WPubkeyHash, WScriptHash};
+    use crate::transaction::build_transaction_detailed;
+    use crate::utils::encoding::{hex_to_bytes, HexMode};
+
+    /// splitmix64, so every run checks the same cases and a failure can be replayed.
+    struct Cases(u64);
+
+    impl Cases {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        fn bytes<const N: usize>(&mut self) -> [u8; N] {
+            std::array::from_fn(|_| self.next() as u8)
+        }
+
+        /// A standard script of a random kind.
+        fn script(&mut self) -> ScriptBuf {
+            match self.below(5) {
+                0 => ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(self.bytes())),
+                1 => ScriptBuf::new_p2sh(&ScriptHash::from_byte_array(self.bytes())),
+                2 => ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(self.bytes())),
+                3 => ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array(self.bytes())),
+                _ => ScriptBuf::new_witness_program(&bitcoin::WitnessProgram::new(bitcoin::WitnessVersion::V1, &self.bytes::<32>()).unwrap()),
+            }
+        }
+    }
+
+    const CASES: usize = 200;
+
+    #[test]
+    fn constructed_parts_are_accepted_by_the_builder() {
+        let mut cases = Cases(0x5eed);
+        for case in 0..CASES {
+            let mut inputs = Vec::new();
+            let mut outpoints = Vec::new();
+            let mut total_in = 0u64;
+            for _ in 0..1 + cases.below(3) {
+                let txid = Txid::from_byte_array(cases.bytes()).to_string();
+                let vout = cases.below(u32::MAX as u64 + 1) as u32;
+                let outpoint = to_json(&checked_outpoint(&txid, Ok(vout as f64)).unwrap(), None).unwrap();
+                let amount = 10_000 + cases.below(Amount::MAX_MONEY.to_sat() / 4);
+                let options = match cases.below(3) {
+                    0 => None,
+                    1 => Some(r#"{"inputType": "p2wpkh", "height": 800000}"#.to_string()),
+                    _ => Some(r#"{"input_type": "p2tr", "must_spend": true}"#.to_string()),
+                };
+                let input = make_input(&outpoint, amount, &cases.script().to_hex_string(), options, None).unwrap();
+                inputs.push(serde_json::from_str::<Value>(&input).unwrap());
+                outpoints.push((txid, vout));
+                total_in += amount;
+            }
+
+            let mut outputs = Vec::new();
+            let mut expected = Vec::new();
+            let mut left = total_in - 1_000;
+            for _ in 0..1 + cases.below(3) {
+                let amount = 1_000 + cases.below(left / 4);
+                left -= amount;
+                let script = cases.script();
+                // Addresses and raw script hex both have to go through.
+                let target = match cases.below(2) {
+                    0 => Address::from_script(&script, Network::Bitcoin).unwrap().to_string(),
+                    _ => script.to_hex_string(),
+                };
+                let output = make_output(&target, amount, Some("mainnet".to_string()), None).unwrap();
+                outputs.push(serde_json::from_str::<Value>(&output).unwrap());
+                expected.push((script, amount));
+            }
+            if cases.below(4) == 0 {
+                let script = ScriptBuf::new_op_return([0x42u8; 8]);
+                outputs.push(serde_json::from_str(&make_output(&script.to_hex_string(), 0, None, None).unwrap()).unwrap());
+                expected.push((script, 0));
+            }
+
+            let inputs_json = Value::Array(inputs).to_string();
+            let outputs_json = Value::Array(outputs).to_string();
+            let built = build_transaction_detailed(&inputs_json, &outputs_json, Some("mainnet".to_string()), None, None, None, None, None, None)
+                .unwrap();
+            let built: Value = serde_json::from_str(&built).unwrap();
+            let tx: Transaction = deserialize(&hex_to_bytes(built["txHex"].as_str().unwrap(), HexMode::Strict).unwrap()).unwrap();
+
+            let mut spent: Vec<_> = tx.input.iter().map(|input| (input.previous_output.txid.to_string(), input.previous_output.vout)).collect();
+            spent.sort();
+            outpoints.sort();
+            assert_eq!(spent, outpoints, "case {}", case);
+            let mut paid: Vec<_> = tx.output.iter().map(|output| (output.script_pubkey.clone(), output.value.to_sat())).collect();
+            paid.sort();
+            expected.sort();
+            assert_eq!(paid, expected, "case {}", case);
+        }
+    }
+
+    #[test]
+    fn outpoint_forms_agree() {
+        let mut cases = Cases(7);
+        for _ in 0..CASES {
+            let txid = Txid::from_byte_array(cases.bytes()).to_string();
+            let vout = cases.below(u32::MAX as u64 + 1) as u32;
+            let made = to_json(&checked_outpoint(&txid, Ok(vout as f64)).unwrap(), None).unwrap();
+            for form in [made.clone(), format!("\"{}:{}\"", txid, vout), format!("\"{}:{}\"", txid.to_uppercase(), vout)] {
+                let parsed = parse_outpoint_json(&form).unwrap();
+                assert_eq!((parsed.txid, parsed.vout), (txid.clone(), vout), "{}", form);
+            }
+        }
+    }
+
+    #[test]
+    fn outpoint_errors() {
+        let txid = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+        assert_eq!(checked_outpoint("abc", Err("vout".to_string())).unwrap_err(), "txid must be 64 hex characters, got 3");
+        assert!(checked_outpoint(txid, Ok(1.5)).unwrap_err().starts_with("vout must be a whole number"));
+        assert!(checked_outpoint(txid, Ok(-1.0)).is_err());
+        assert!(checked_outpoint(txid, Ok(u32::MAX as f64 + 1.0)).is_err());
+        assert_eq!(checked_outpoint(txid, Ok(u32::MAX as f64)).unwrap().vout, u32::MAX);
+        assert!(parse_outpoint_json(&format!("\"{}:1x\"", txid)).unwrap_err().contains("vout must be a decimal number"));
+        assert!(parse_outpoint_json(&format!("{{\"txid\":\"{}\",\"vout\":\"1\"}}", txid)).unwrap_err().contains("got the string"));
+        assert_eq!(check_amount(Amount::MAX_MONEY.to_sat() + 1).unwrap_err(), "amount 2100000000000001 exceeds the maximum of 2100000000000000 sats");
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::sighash::{EcdsaSighashType, SighashCache, TapSighashType};
+use bitcoin::{Amount, ScriptBuf, Transaction, TxOut};
+use bitcoin::key::{Keypair, TapTweak};
+use crate::script::interpreter::verify_inputs;
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::sign::{apply_signature, key_spend, key_spend_sighash_as, key_spend_signature, KeySignature, KeySpend};
+use crate::transaction::{decode_tx_hex, output_script, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, parse_json_value, to_json_with_amounts};
+use crate::utils::network::network_or_default;
+use crate::utils::units::FeeRate;
+use crate::wallet::parse_private_key;
+
+/// Above this Core's `sendrawtransaction` refuses it (0.10 BTC/kvB).
+const MAX_FEE_RATE: f64 = 10_000.0;
+const SIGHASH_ALL_ANYONECANPAY: u8 = 0x81;
+
+/// One pledger's signed input: a transaction spending only their coin to the full
+/// output set, which isn't valid on its own.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pledge {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    /// The pledged coin, needed to check the signature once combined.
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssembledCrowdfund {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    pub txid: String,
+    #[serde(alias = "pledge_count")]
+    pub pledge_count: usize,
+    #[serde(alias = "total_pledged")]
+    pub total_pledged: u64,
+    pub fee: u64,
+    pub vsize: u64,
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: FeeRate,
+    pub warnings: Vec<String>,
+}
+
+/// Signs `utxo_json` (an input as `build_transaction` takes it) with
+/// SIGHASH_ALL|ANYONECANPAY towards `output_set_json`: the signature commits to the
+/// outputs and this input only, so other pledges can be added without invalidating it.
+#[wasm_bindgen]
+pub fn create_pledge(
+    output_set_json: &str,
+    utxo_json: &str,
+    private_key: &str,
+    network: Option<String>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let outputs: Vec<TransactionOutput> =
+        parse_json_list(output_set_json, "output set").map_err(|e| JsValue::from_str(&e))?;
+    if outputs.is_empty() {
+        return Err(JsValue::from_str("Output set must not be empty"));
+    }
+    let utxo: TransactionInput = parse_json_value(utxo_json, "UTXO").map_err(|e| JsValue::from_str(&e))?;
+    let private_key = parse_private_key(private_key, None, network.network)?;
+
+    let mut tx = unsigned_transaction(std::slice::from_ref(&utxo), &outputs, Some(&network))?;
+    let prevout = TxOut {
+        value: Amount::from_sat(utxo.amount),
+        script_pubkey: std::mem::take(&mut tx.input[0].script_sig),
+    };
+
+    let secp = Secp256k1::new();
+    let secret_key = private_key.inner;
+    let spend = key_spend(&secp, &prevout.script_pubkey, &secret_key.public_key(&secp), private_key.compressed, "private key")
+        .map_err(|e| JsValue::from_str(&e))?;
+    let sighash = key_spend_sighash_as(&mut SighashCache::new(&tx), 0, &prevout, std::slice::from_ref(&prevout), &spend, true)
+        .map_err(|e| JsValue::from_str(&format!("Failed to sign pledge: {}", e)))?;
+    let signature = match spend {
+        KeySpend::P2tr(_) => {
+            let tweaked = Keypair::from_secret_key(&secp, &secret_key).tap_tweak(&secp, None);
+            KeySignature::Schnorr(bitcoin::taproot::Signature {
+                signature: secp.sign_schnorr_no_aux_rand(&sighash, &tweaked.to_keypair()),
+                sighash_type: TapSighashType::AllPlusAnyoneCanPay,
+            })
+        }
+        _ => KeySignature::Ecdsa(bitcoin::ecdsa::Signature {
+            signature: secp.sign_ecdsa_low_r(&sighash, &secret_key),
+            sighash_type: EcdsaSighashType::AllPlusAnyoneCanPay,
+        }),
+    };
+    let signature = key_spend_signature(&spend, signature).map_err(|e| JsValue::from_str(&format!("Failed to sign pledge: {}", e)))?;
+    apply_signature(&mut tx, 0, signature);
+    // One pledge rarely covers the outputs, so its fee means nothing until assembly.
+    let tx_bytes = finalize(&tx, Funding::Unknown, &checks).map_err(|e| JsValue::from_str(&e))?;
+
+    let pledge = Pledge {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        amount: utxo.amount,
+        script_pubkey: prevout.script_pubkey.to_hex_string(),
+    };
+    to_json_with_amounts(&pledge, amount_format, compact)
+}
+
+/// Merges pledges (from `create_pledge`) into one transaction paying `output_set_json`.
+/// Each pledge must be signed ALL|ANYONECANPAY for exactly this output set, and its
+/// signature is re-verified in the combined transaction. The pledges must cover the
+/// outputs plus at least 1 sat/vB, or the checks' `minFeeRateSatVb`; anything beyond the
+/// outputs goes to the fee.
+#[wasm_bindgen]
+pub fn assemble_crowdfund(output_set_json: &str, pledges_json: &str, checks_json: Option<String>, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let outputs: Vec<TransactionOutput> =
+        parse_json_list(output_set_json, "output set").map_err(|e| JsValue::from_str(&e))?;
+    let outputs = outputs
+        .iter()
+        .map(|output| Ok(TxOut { value: Amount::from_sat(output.amount), script_pubkey: output_script(output, None)? }))
+        .collect::<Result<Vec<_>, JsValue>>()?;
+    let pledges: Vec<Pledge> = parse_json_list(pledges_json, "pledges").map_err(|e| JsValue::from_str(&e))?;
+    if pledges.is_empty() {
+        return Err(JsValue::from_str("No pledges to assemble"));
+    }
+
+    let mut tx = Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: Vec::with_capacity(pledges.len()),
+        output: outputs,
+    };
+    let mut prevouts = Vec::with_capacity(pledges.len());
+    let mut seen = HashSet::new();
+    for (index, pledge) in pledges.iter().enumerate() {
+        let pledge_error = |reason: String| JsValue::from_str(&format!("Pledge {}: {}", index, reason));
+        let pledge_tx = decode_tx_hex(&pledge.tx_hex).map_err(|_| pledge_error("invalid transaction hex".to_string()))?;
+        let script_pubkey = ScriptBuf::from_hex(&pledge.script_pubkey).map_err(|e| pledge_error(format!("invalid script pubkey: {}", e)))?;
+        let [input] = pledge_tx.input.as_slice() else {
+            return Err(pledge_error(format!("has {} inputs; a pledge spends exactly one", pledge_tx.input.len())));
+        };
+        if pledge_tx.output != tx.output {
+            return Err(pledge_error("was signed for a different output set".to_string()));
+        }
+        if pledge_tx.version != tx.version || pledge_tx.lock_time != tx.lock_time {
+            return Err(pledge_error("version or lock time differs from the crowdfund's (2 and 0)".to_string()));
+        }
+        if !seen.insert(input.previous_output) {
+            return Err(pledge_error(format!("spends {}, which another pledge already spends", input.previous_output)));
+        }
+        if let Some(found) = sighash_flag(&input.script_sig, &input.witness, &script_pubkey) {
+            if found != SIGHASH_ALL_ANYONECANPAY {
+                return Err(pledge_error(format!(
+                    "signed with sighash type 0x{:02x}; pledges need SIGHASH_ALL|ANYONECANPAY (0x81), otherwise adding other inputs invalidates the signature",
+                    found
+                )));
+            }
+        }
+        tx.input.push(input.clone());
+        prevouts.push(TxOut { value: Amount::from_sat(pledge.amount), script_pubkey });
+    }
+
+    if let Err((index, failure)) = verify_inputs(&tx, &prevouts) {
+        return Err(JsValue::from_str(&format!(
+            "Pledge {}'s signature doesn't validate in the combined transaction: {}",
+            index, failure.reason
+        )));
+    }
+
+    let total_pledged: u64 = prevouts.iter().map(|prevout| prevout.value.to_sat()).sum();
+    let total_out: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    let vsize = tx.vsize() as u64;
+    let min_rate = checks.fee_limits.min_fee_rate.sat_per_vb();
+    let min_fee = (vsize as f64 * min_rate).ceil() as u64;
+    if total_pledged < total_out + min_fee {
+        return Err(JsValue::from_str(&format!(
+            "Pledges total {} sats; the outputs need {} plus at least {} sats of fee ({} sat/vB for {} vB)",
+            total_pledged, total_out, min_fee, min_rate, vsize
+        )));
+    }
+    let fee = total_pledged - total_out;
+    let fee_rate = FeeRate::from_fee(Amount::from_sat(fee), vsize as f64);
+    let mut warnings = Vec::new();
+    if fee_rate.sat_per_vb() > MAX_FEE_RATE {
+        warnings.push(format!(
+            "Fee rate {:.0} sat/vB is above what nodes accept from sendrawtransaction by default; the pledges overshoot the outputs by {} sats",
+            fee_rate.sat_per_vb(), fee
+        ));
+    }
+
+    let tx_bytes = finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?;
+
+    let assembled = AssembledCrowdfund {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        txid: tx.compute_txid().to_string(),
+        pledge_count: pledges.len(),
+        total_pledged,
+        fee,
+        vsize,
+        fee_rate,
+        warnings,
+    };
+    to_json_with_amounts(&assembled, amount_format, compact)
+}
+
+/// The sighash byte of a single-key spend's signature, if it can be found.
+pub fn sighash_flag(script_sig: &bitcoin::Script, witness: &bitcoin::Witness, spent: &bitcoin::Script) -> Option<u8> {
+    if spent.is_p2tr() {
+        // A 64-byte signature is SIGHASH_DEFAULT.
+        return witness.nth(0).map(|signature| if signature.len() == 64 { 0 } else { signature.last().copied().unwrap_or(0) });
+    }
+    if spent.is_p2wpkh() || spent.is_p2sh() {
+        return witness.nth(0).and_then(|signature| signature.last().copied());
+    }
+    match script_sig.instructions().next()? {
+        Ok(bitcoin::script::Instruction::PushBytes(signature)) => signature.as_bytes().last().copied(),
+        _ => None,
+    }
+}
@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{OutPoint, Transaction, TxIn, TxOut};
+use crate::transaction::decode_tx_hex;
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::json::to_json;
+
+#[derive(Serialize, Deserialize)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDiff {
+    /// added, removed, changed or unchanged. A move alone leaves an input unchanged.
+    pub status: String,
+    pub outpoint: String,
+    #[serde(alias = "index_before")]
+    pub index_before: Option<usize>,
+    #[serde(alias = "index_after")]
+    pub index_after: Option<usize>,
+    pub moved: bool,
+    pub sequence: Option<Change<u32>>,
+    /// "unsigned → signed", "signed → unsigned" or "signature replaced".
+    #[serde(alias = "signature_change")]
+    pub signature_change: Option<String>,
+    #[serde(alias = "script_sig_changed")]
+    pub script_sig_changed: bool,
+    #[serde(alias = "witness_changed")]
+    pub witness_changed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputDiff {
+    /// added, removed, amount_changed or unchanged.
+    pub status: String,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    pub address: Option<String>,
+    #[serde(alias = "index_before")]
+    pub index_before: Option<usize>,
+    #[serde(alias = "index_after")]
+    pub index_after: Option<usize>,
+    pub moved: bool,
+    #[serde(alias = "amount_before")]
+    pub amount_before: Option<u64>,
+    #[serde(alias = "amount_after")]
+    pub amount_after: Option<u64>,
+    /// `amount_after - amount_before`, counting a missing side as 0.
+    #[serde(alias = "amount_delta")]
+    pub amount_delta: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionDiff {
+    pub identical: bool,
+    /// Whether the transactions differ in more than scriptSigs and witnesses.
+    #[serde(alias = "unsigned_changed")]
+    pub unsigned_changed: bool,
+    pub txid: Change<String>,
+    pub version: Option<Change<i32>>,
+    pub locktime: Option<Change<u32>>,
+    pub inputs: Vec<InputDiff>,
+    pub outputs: Vec<OutputDiff>,
+    #[serde(alias = "output_total")]
+    pub output_total: Change<u64>,
+    /// One line per difference, in the order of the fields above.
+    pub summary: Vec<String>,
+}
+
+/// What changed between two versions of a transaction, such as an original and its
+/// RBF replacement or a PSBT's transaction before and after a round trip. Inputs are
+/// matched by outpoint and outputs by scriptPubKey, so reordering shows as a move
+/// rather than a removal. Entries follow `tx_b`'s order, with removed ones last in
+/// `tx_a`'s order. `network` only picks how output addresses are shown.
+#[wasm_bindgen]
+pub fn diff_transactions(tx_a_hex: &str, tx_b_hex: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let a = decode_tx_hex(tx_a_hex)?;
+    let b = decode_tx_hex(tx_b_hex)?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+
+    to_json(&diff(&a, &b, &network), compact)
+}
+
+pub fn diff(a: &Transaction, b: &Transaction, network: &NetworkParams) -> TransactionDiff {
+    let mut summary = Vec::new();
+    let version = (a.version != b.version).then_some(Change { before: a.version.0, after: b.version.0 });
+    if let Some(change) = &version {
+        summary.push(format!("version {} → {}", change.before, change.after));
+    }
+    let locktime = (a.lock_time != b.lock_time).then(|| Change {
+        before: a.lock_time.to_consensus_u32(),
+        after: b.lock_time.to_consensus_u32(),
+    });
+    if let Some(change) = &locktime {
+        summary.push(format!("locktime {} → {}", change.before, change.after));
+    }
+
+    let inputs = diff_inputs(&a.input, &b.input);
+    for input in &inputs {
+        if let Some(line) = input_line(input) {
+            summary.push(line);
+        }
+    }
+    let outputs = diff_outputs(&a.output, &b.output, network);
+    for output in &outputs {
+        if let Some(line) = output_line(output) {
+            summary.push(line);
+        }
+    }
+
+    let total = |outputs: &[TxOut]| outputs.iter().map(|output| output.value.to_sat()).sum::<u64>();
+    let txid = Change { before: a.compute_txid().to_string(), after: b.compute_txid().to_string() };
+    TransactionDiff {
+        identical: a == b,
+        unsigned_changed: txid.before != txid.after,
+        txid,
+        version,
+        locktime,
+        inputs,
+        outputs,
+        output_total: Change { before: total(&a.output), after: total(&b.output) },
+        summary,
+    }
+}
+
+fn diff_inputs(before: &[TxIn], after: &[TxIn]) -> Vec<InputDiff> {
+    let position_in = |inputs: &[TxIn], outpoint: OutPoint| inputs.iter().position(|input| input.previous_output == outpoint);
+
+    let mut diffs: Vec<InputDiff> = after
+        .iter()
+        .enumerate()
+        .map(|(index_after, input)| match position_in(before, input.previous_output) {
+            Some(index_before) => compare_input(&before[index_before], input, index_before, index_after),
+            None => one_sided_input("added", input, None, Some(index_after)),
+        })
+        .collect();
+    diffs.extend(
+        before
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| position_in(after, input.previous_output).is_none())
+            .map(|(index_before, input)| one_sided_input("removed", input, Some(index_before), None)),
+    );
+    diffs
+}
+
+fn compare_input(before: &TxIn, after: &TxIn, index_before: usize, index_after: usize) -> InputDiff {
+    let sequence = (before.sequence != after.sequence).then(|| Change {
+        before: before.sequence.to_consensus_u32(),
+        after: after.sequence.to_consensus_u32(),
+    });
+    let script_sig_changed = before.script_sig != after.script_sig;
+    let witness_changed = before.witness != after.witness;
+    let signature_change = match (is_signed(before), is_signed(after)) {
+        (false, true) => Some("unsigned → signed"),
+        (true, false) => Some("signed → unsigned"),
+        (true, true) if script_sig_changed || witness_changed => Some("signature replaced"),
+        _ => None,
+    };
+    let changed = sequence.is_some() || script_sig_changed || witness_changed;
+
+    InputDiff {
+        status: if changed { "changed" } else { "unchanged" }.to_string(),
+        outpoint: before.previous_output.to_string(),
+        index_before: Some(index_before),
+        index_after: Some(index_after),
+        moved: index_before != index_after,
+        sequence,
+        signature_change: signature_change.map(str::to_string),
+        script_sig_changed,
+        witness_changed,
+    }
+}
+
+fn one_sided_input(status: &str, input: &TxIn, index_before: Option<usize>, index_after: Option<usize>) -> InputDiff {
+    InputDiff {
+        status: status.to_string(),
+        outpoint: input.previous_output.to_string(),
+        index_before,
+        index_after,
+        moved: false,
+        sequence: None,
+        signature_change: None,
+        script_sig_changed: false,
+        witness_changed: false,
+    }
+}
+
+/// Signature data rather than the empty scriptSig, or the prevout-script placeholder
+/// that unsigned transactions sometimes carry. A segwit program is push-only too, so
+/// it's recognised by shape.
+fn is_signed(input: &TxIn) -> bool {
+    let script_sig = &input.script_sig;
+    !input.witness.is_empty() || (!script_sig.is_empty() && script_sig.is_push_only() && !script_sig.is_witness_program())
+}
+
+/// Outputs paying the same script twice are paired in order.
+fn diff_outputs(before: &[TxOut], after: &[TxOut], network: &NetworkParams) -> Vec<OutputDiff> {
+    let mut matched = vec![false; before.len()];
+    let mut diffs = Vec::with_capacity(after.len());
+    for (index_after, output) in after.iter().enumerate() {
+        let index_before = (0..before.len()).find(|&i| !matched[i] && before[i].script_pubkey == output.script_pubkey);
+        if let Some(i) = index_before {
+            matched[i] = true;
+        }
+        let amount_before = index_before.map(|i| before[i].value.to_sat());
+        let status = match amount_before {
+            None => "added",
+            Some(amount) if amount != output.value.to_sat() => "amount_changed",
+            Some(_) => "unchanged",
+        };
+        diffs.push(output_diff(status, output, network, index_before, Some(index_after), amount_before, Some(output.value.to_sat())));
+    }
+    for (index_before, output) in before.iter().enumerate().filter(|&(i, _)| !matched[i]) {
+        diffs.push(output_diff("removed", output, network, Some(index_before), None, Some(output.value.to_sat()), None));
+    }
+    diffs
+}
+
+fn output_diff(
+    status: &str,
+    output: &TxOut,
+    network: &NetworkParams,
+    index_before: Option<usize>,
+    index_after: Option<usize>,
+    amount_before: Option<u64>,
+    amount_after: Option<u64>,
+) -> OutputDiff {
+    OutputDiff {
+        status: status.to_string(),
+        script_pubkey: bytes_to_hex(output.script_pubkey.as_bytes()),
+        address: network.address(&output.script_pubkey),
+        index_before,
+        index_after,
+        moved: matches!((index_before, index_after), (Some(x), Some(y)) if x != y),
+        amount_before,
+        amount_after,
+        amount_delta: amount_after.unwrap_or(0) as i64 - amount_before.unwrap_or(0) as i64,
+    }
+}
+
+fn input_line(input: &InputDiff) -> Option<String> {
+    let name = |index: Option<usize>| format!("input {} ({})", index.expect("set for this status"), input.outpoint);
+    match input.status.as_str() {
+        "added" => return Some(format!("{} added", name(input.index_after))),
+        "removed" => return Some(format!("{} removed", name(input.index_before))),
+        _ => {}
+    }
+
+    let mut changes = Vec::new();
+    if input.moved {
+        changes.push(format!("moved from {}", input.index_before.expect("matched inputs have both")));
+    }
+    if let Some(sequence) = &input.sequence {
+        changes.push(format!("sequence {:#010x} → {:#010x}", sequence.before, sequence.after));
+    }
+    match &input.signature_change {
+        Some(change) => changes.push(change.clone()),
+        None if input.script_sig_changed || input.witness_changed => changes.push("scriptSig/witness changed".to_string()),
+        None => {}
+    }
+    (!changes.is_empty()).then(|| format!("{}: {}", name(input.index_after), changes.join(", ")))
+}
+
+fn output_line(output: &OutputDiff) -> Option<String> {
+    let payee = output.address.clone().unwrap_or_else(|| format!("script {}", output.script_pubkey));
+    let index = output.index_after.or(output.index_before).expect("one side is set");
+    match output.status.as_str() {
+        "added" => Some(format!("output {} to {} added: {} sats", index, payee, output.amount_delta)),
+        "removed" => Some(format!("output {} to {} removed: {} sats", index, payee, -output.amount_delta)),
+        _ => {
+            let mut changes = Vec::new();
+            if output.moved {
+                changes.push(format!("moved from {}", output.index_before.expect("matched outputs have both")));
+            }
+            if output.status == "amount_changed" {
+                changes.push(format!(
+                    "{} → {} sats ({:+})",
+                    output.amount_before.unwrap_or(0),
+                    output.amount_after.unwrap_or(0),
+                    output.amount_delta
+                ));
+            }
+            (!changes.is_empty()).then(|| format!("output {} to {}: {}", index, payee, changes.join(", ")))
+        }
+    }
+}
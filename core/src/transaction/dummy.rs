@@ -0,0 +1,250 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::all::OP_CHECKSIG;
+use bitcoin::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::{Script, ScriptBuf, TxIn, Witness};
+use crate::script::parse_multisig;
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::{decode_tx_hex, parse_sighash_type};
+use crate::utils::bytes_to_hex;
+use crate::utils::json::parse_json_list;
+
+// A low-R DER signature (SEQUENCE of two 32-byte INTEGERs) is 70 bytes; the sighash byte makes 71.
+// r and s are 0x0101...01, so the placeholder parses as a signature without signing anything.
+const DUMMY_DER_SIGNATURE: [u8; 70] = {
+    let mut der = [0x01u8; 70];
+    der[0] = 0x30;
+    der[1] = 0x44;
+    der[2] = 0x02;
+    der[3] = 0x20;
+    der[36] = 0x02;
+    der[37] = 0x20;
+    der
+};
+const DUMMY_PUBKEY: [u8; 33] = {
+    let mut pubkey = [0u8; 33];
+    pubkey[0] = 0x02;
+    pubkey
+};
+pub const DUMMY_SCHNORR_SIGNATURE: [u8; 64] = [0u8; 64];
+
+/// A prevout plus what's needed to know how it's spent. `redeem_script` and `witness_script`
+/// are only read for P2SH and P2WSH outputs, and `pubkey` only for P2PKH, where an
+/// uncompressed key is 32 bytes longer than the compressed one assumed by default.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DummyPrevout {
+    #[serde(default, rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    #[serde(default, alias = "redeem_script")]
+    pub redeem_script: Option<String>,
+    #[serde(default, alias = "witness_script")]
+    pub witness_script: Option<String>,
+    #[serde(default)]
+    pub pubkey: Option<String>,
+    #[serde(default)]
+    pub sighash: Option<String>,
+}
+
+/// Fills every unsigned input with placeholders the size of its final signatures, keys and
+/// scripts, so the result's vsize is what the signed transaction's will be. The placeholders
+/// are not valid signatures; signing overwrites them. Inputs that already have a scriptSig or
+/// witness are left as they are.
+#[wasm_bindgen]
+pub fn populate_dummy_signatures(tx_hex: &str, prevouts_json: &str, checks_json: Option<String>) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let mut tx = decode_tx_hex(tx_hex)?;
+    let prevouts: Vec<DummyPrevout> = parse_json_list(prevouts_json, "prevouts").map_err(|e| JsValue::from_str(&e))?;
+    if prevouts.len() != tx.input.len() {
+        return Err(JsValue::from_str(&format!(
+            "Expected {} prevouts (one per input), got {}",
+            tx.input.len(),
+            prevouts.len()
+        )));
+    }
+
+    for (index, (input, prevout)) in tx.input.iter_mut().zip(&prevouts).enumerate() {
+        if !input.script_sig.is_empty() || !input.witness.is_empty() {
+            continue;
+        }
+        populate_input(input, prevout).map_err(|e| JsValue::from_str(&format!("Input {}: {}", index, e)))?;
+    }
+
+    // Amounts are optional here, so the fee isn't known.
+    Ok(bytes_to_hex(&finalize(&tx, Funding::Unknown, &checks).map_err(|e| JsValue::from_str(&e))?))
+}
+
+/// Serialized vsize (weight / 4, rounded up) of a transaction as given.
+#[wasm_bindgen]
+pub fn transaction_vsize(tx_hex: &str) -> Result<u32, JsValue> {
+    Ok(decode_tx_hex(tx_hex)?.vsize() as u32)
+}
+
+fn populate_input(input: &mut TxIn, prevout: &DummyPrevout) -> Result<(), String> {
+    let script_pubkey = parse_script(&prevout.script_pubkey, "script_pubkey")?;
+    let ecdsa = || dummy_ecdsa_signature(prevout.sighash.as_deref());
+
+    if script_pubkey.is_p2wpkh() {
+        input.witness = Witness::from_slice(&[ecdsa()?, DUMMY_PUBKEY.to_vec()]);
+    } else if script_pubkey.is_p2pkh() {
+        input.script_sig = push_items(&[ecdsa()?, dummy_p2pkh_pubkey(prevout.pubkey.as_deref())?])?;
+    } else if script_pubkey.is_p2pk() {
+        input.script_sig = push_items(&[ecdsa()?])?;
+    } else if script_pubkey.is_p2tr() {
+        let mut signature = DUMMY_SCHNORR_SIGNATURE.to_vec();
+        match prevout.sighash.as_deref().map(str::trim) {
+            None => {}
+            Some(value) if value.eq_ignore_ascii_case("DEFAULT") || value.eq_ignore_ascii_case("SIGHASH_DEFAULT") => {}
+            Some(value) => signature.push(parse_sighash_type(value)?.to_u32() as u8),
+        }
+        input.witness = Witness::from_slice(&[signature]);
+    } else if script_pubkey.is_p2wsh() {
+        let witness_script = required_script(prevout.witness_script.as_deref(), "witnessScript", "P2WSH")?;
+        input.witness = script_witness(&witness_script, ecdsa()?)?;
+    } else if script_pubkey.is_p2sh() {
+        // Without a redeem script, assume P2SH-P2WPKH: the only P2SH spend the signers derive themselves.
+        let redeem_script = match prevout.redeem_script.as_deref() {
+            Some(hex) => parse_script(hex, "redeemScript")?,
+            None => ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([0; 20])),
+        };
+        let redeem_push = PushBytesBuf::try_from(redeem_script.to_bytes()).map_err(|e| e.to_string())?;
+        if redeem_script.is_p2wpkh() {
+            input.script_sig = Builder::new().push_slice(&redeem_push).into_script();
+            input.witness = Witness::from_slice(&[ecdsa()?, DUMMY_PUBKEY.to_vec()]);
+        } else if redeem_script.is_p2wsh() {
+            let witness_script = required_script(prevout.witness_script.as_deref(), "witnessScript", "P2SH-P2WSH")?;
+            input.script_sig = Builder::new().push_slice(&redeem_push).into_script();
+            input.witness = script_witness(&witness_script, ecdsa()?)?;
+        } else {
+            let mut items = script_satisfaction(&redeem_script, ecdsa()?)?;
+            items.push(redeem_script.to_bytes());
+            input.script_sig = push_items(&items)?;
+        }
+    } else {
+        return Err(format!("can't size a spend of a {} output", crate::script::output_script_type(&script_pubkey)));
+    }
+    Ok(())
+}
+
+pub fn dummy_ecdsa_signature(sighash: Option<&str>) -> Result<Vec<u8>, String> {
+    let sighash_type = parse_sighash_type(sighash.unwrap_or("ALL"))?;
+    let mut signature = DUMMY_DER_SIGNATURE.to_vec();
+    signature.push(sighash_type.to_u32() as u8);
+    Ok(signature)
+}
+
+fn dummy_p2pkh_pubkey(pubkey: Option<&str>) -> Result<Vec<u8>, String> {
+    let Some(hex) = pubkey else {
+        return Ok(DUMMY_PUBKEY.to_vec());
+    };
+    let key = bitcoin::PublicKey::from_str(hex.trim()).map_err(|e| format!("Invalid pubkey: {}", e))?;
+    Ok(key.to_bytes())
+}
+
+fn script_witness(witness_script: &Script, signature: Vec<u8>) -> Result<Witness, String> {
+    let mut items = script_satisfaction(witness_script, signature)?;
+    items.push(witness_script.to_bytes());
+    Ok(Witness::from_slice(&items))
+}
+
+/// Stack items that satisfy `m`-of-`n` CHECKMULTISIG (with the empty dummy element first)
+/// or a single `<pubkey> OP_CHECKSIG`. Other scripts have no fixed-size satisfaction to
+/// predict, so they're rejected.
+fn script_satisfaction(script: &Script, signature: Vec<u8>) -> Result<Vec<Vec<u8>>, String> {
+    if let Some(multisig) = parse_multisig(script) {
+        let mut items = vec![Vec::new()];
+        items.extend(vec![signature; multisig.threshold]);
+        return Ok(items);
+    }
+    let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+    match instructions.as_slice() {
+        [Instruction::PushBytes(key), Instruction::Op(op)] if *op == OP_CHECKSIG && matches!(key.len(), 33 | 65) => {
+            Ok(vec![signature])
+        }
+        _ => Err("only multisig and single-key CHECKSIG scripts can be sized; sign this input before measuring".to_string()),
+    }
+}
+
+fn push_items(items: &[Vec<u8>]) -> Result<ScriptBuf, String> {
+    let mut builder = Builder::new();
+    for item in items {
+        builder = builder.push_slice(PushBytesBuf::try_from(item.clone()).map_err(|e| e.to_string())?);
+    }
+    Ok(builder.into_script())
+}
+
+fn required_script(hex: Option<&str>, field: &str, what: &str) -> Result<ScriptBuf, String> {
+    let hex = hex.ok_or_else(|| format!("{} prevouts need a {} to be sized", what, field))?;
+    parse_script(hex, field)
+}
+
+fn parse_script(hex: &str, field: &str) -> Result<ScriptBuf, String> {
+    ScriptBuf::from_hex(hex.trim()).map_err(|e| format!("Invalid {}: {}", field, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholder_is_a_low_r_signature() {
+        let signature = dummy_ecdsa_signature(None).unwrap();
+        assert_eq!(signature.len(), 71);
+        let parsed = bitcoin::ecdsa::Signature::from_slice(&signature).unwrap();
+        assert_eq!(parsed.sighash_type, bitcoin::sighash::EcdsaSighashType::All);
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn dummy_vsize_matches_the_signed_transaction() {
+        use bitcoin::absolute::LockTime;
+        use bitcoin::key::{CompressedPublicKey, PrivateKey};
+        use bitcoin::secp256k1::{Secp256k1, SecretKey};
+        use bitcoin::sighash::SighashCache;
+        use bitcoin::transaction::Version;
+        use bitcoin::{Amount, Network, OutPoint, Transaction, TxOut, Txid};
+        use crate::transaction::sign::{apply_signature, sign_input};
+
+        let secp = Secp256k1::new();
+        let key = PrivateKey::new(SecretKey::from_slice(&[0x11; 32]).unwrap(), Network::Testnet);
+        let pubkey = CompressedPublicKey::from_private_key(&secp, &key).unwrap();
+        let p2wpkh = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash());
+        let scripts = [p2wpkh.clone(), ScriptBuf::new_p2pkh(&pubkey.pubkey_hash()), ScriptBuf::new_p2sh(&p2wpkh.script_hash())];
+        let prevouts: Vec<TxOut> = scripts
+            .iter()
+            .map(|script_pubkey| TxOut { value: Amount::from_sat(50_000), script_pubkey: script_pubkey.clone() })
+            .collect();
+        let unsigned = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: (0..3).map(|vout| TxIn { previous_output: OutPoint { txid: Txid::all_zeros(), vout }, ..Default::default() }).collect(),
+            output: vec![TxOut { value: Amount::from_sat(149_000), script_pubkey: p2wpkh }],
+        };
+
+        let mut dummy = unsigned.clone();
+        for (input, prevout) in dummy.input.iter_mut().zip(&prevouts) {
+            let prevout = DummyPrevout {
+                amount: prevout.value.to_sat(),
+                script_pubkey: prevout.script_pubkey.to_hex_string(),
+                redeem_script: None,
+                witness_script: None,
+                pubkey: None,
+                sighash: None,
+            };
+            populate_input(input, &prevout).unwrap();
+        }
+        let mut signed = unsigned.clone();
+        let mut cache = SighashCache::new(&unsigned);
+        for (index, prevout) in prevouts.iter().enumerate() {
+            let signature = sign_input(&secp, &mut cache, index, prevout, &prevouts, &key).unwrap();
+            apply_signature(&mut signed, index, signature);
+        }
+
+        assert_eq!(dummy.weight(), signed.weight());
+        assert_eq!(dummy.vsize(), signed.vsize());
+    }
+}
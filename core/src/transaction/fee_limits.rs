@@ -0,0 +1,80 @@
+use bitcoin::Transaction;
+use crate::transaction::check_amount_total;
+use crate::utils::units::{Amount, FeeRate};
+
+/// Bitcoin Core's default `-minrelaytxfee`, 1 sat/vB.
+const DEFAULT_MIN_FEE_RATE: f64 = 1.0;
+
+/// The fee bounds of one build, from the `maxFeeSat`, `maxFeeRateSatVb` and
+/// `minFeeRateSatVb` fields of its `checks_json`. Nothing is shared between calls, so
+/// one caller's limits never apply to another's transaction.
+#[derive(Clone, Copy)]
+pub struct FeeLimits {
+    pub max_fee: Option<Amount>,
+    pub max_fee_rate: Option<FeeRate>,
+    /// 1 sat/vB unless set, so nothing that can't relay is built by accident. Zero turns
+    /// the check off.
+    pub min_fee_rate: FeeRate,
+}
+
+impl Default for FeeLimits {
+    fn default() -> FeeLimits {
+        FeeLimits {
+            max_fee: None,
+            max_fee_rate: None,
+            min_fee_rate: FeeRate::from_sat_per_vb(DEFAULT_MIN_FEE_RATE).unwrap_or_default(),
+        }
+    }
+}
+
+impl FeeLimits {
+    pub fn new(max_fee: Option<u64>, max_fee_rate: Option<FeeRate>, min_fee_rate: Option<FeeRate>) -> Result<FeeLimits, String> {
+        if max_fee_rate.is_some_and(|rate| rate.sat_per_vb() <= 0.0) {
+            return Err("The maximum fee rate must be positive".to_string());
+        }
+        let defaults = FeeLimits::default();
+        Ok(FeeLimits {
+            max_fee: max_fee.map(Amount::from_sat),
+            max_fee_rate,
+            min_fee_rate: min_fee_rate.unwrap_or(defaults.min_fee_rate),
+        })
+    }
+
+    /// Checks a finished transaction whose inputs bring `input_total` sats, over `vsize`.
+    /// The fee is whatever the inputs hold beyond the outputs, so change and dust
+    /// decisions made while building are already in it.
+    pub fn check(&self, tx: &Transaction, input_total: u64, vsize: u64) -> Result<(), String> {
+        let output_total = check_amount_total(tx.output.iter().map(|output| output.value.to_sat()), "outputs")?;
+        let min_rate = self.min_fee_rate.sat_per_vb();
+        let Some(fee) = input_total.checked_sub(output_total) else {
+            if min_rate == 0.0 {
+                return Ok(());
+            }
+            return Err(format!(
+                "Outputs total {} sats, more than the {} sats the inputs hold, so there is no fee",
+                output_total, input_total
+            ));
+        };
+
+        if let Some(max_fee) = self.max_fee.filter(|max_fee| fee > max_fee.to_sat()) {
+            return Err(format!("Fee is {} sats, above the {} sat limit set by maxFeeSat", fee, max_fee.to_sat()));
+        }
+        let rate = FeeRate::from_fee(Amount::from_sat(fee), vsize as f64).sat_per_vb();
+        if let Some(max_rate) = self.max_fee_rate.filter(|max_rate| rate > max_rate.sat_per_vb()) {
+            return Err(format!(
+                "Fee rate is {:.2} sat/vB ({} sats for {} vB), above the {} sat/vB limit set by maxFeeRateSatVb",
+                rate,
+                fee,
+                vsize,
+                max_rate.sat_per_vb()
+            ));
+        }
+        if rate < min_rate {
+            return Err(format!(
+                "Fee rate is {:.2} sat/vB ({} sats for {} vB), below the {} sat/vB minimum; minFeeRateSatVb: 0 allows it",
+                rate, fee, vsize, min_rate
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use serde::Deserialize;
+use bitcoin::Transaction;
+#[cfg(feature = "signing")]
+use bitcoin::TxOut;
+use crate::transaction::blocklist::Blocklist;
+use crate::transaction::fee_limits::FeeLimits;
+use crate::transaction::serialization::checked_serialize;
+use crate::transaction::weight::estimated_signed_vsize;
+use crate::transaction::{check_amount_total, TransactionInput};
+use crate::utils::json::parse_json_value;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::units::FeeRate;
+
+/// A builder's `checks_json`: the limits its finished transaction has to meet. Each call
+/// takes its own, so two callers on one page can't change each other's.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ChecksJson {
+    #[serde(default, alias = "max_fee_sat", alias = "maxFee", alias = "max_fee", deserialize_with = "crate::utils::amount::deserialize_option")]
+    max_fee_sat: Option<u64>,
+    #[serde(default, alias = "max_fee_rate_sat_vb", alias = "maxFeeRate", alias = "max_fee_rate")]
+    max_fee_rate_sat_vb: Option<FeeRate>,
+    #[serde(default, alias = "min_fee_rate_sat_vb", alias = "minFeeRate", alias = "min_fee_rate")]
+    min_fee_rate_sat_vb: Option<FeeRate>,
+    /// Addresses, on any network, and scriptPubKey hex the transaction mustn't pay.
+    #[serde(default)]
+    blocklist: Vec<String>,
+    /// Blocklist entries this build may pay anyway.
+    #[serde(default, alias = "allow_blocklisted")]
+    allow_blocklisted: Vec<String>,
+}
+
+/// The checks one build applies in `finalize`.
+#[derive(Default)]
+pub struct BuildChecks {
+    pub fee_limits: FeeLimits,
+    pub blocklist: Blocklist,
+}
+
+impl BuildChecks {
+    /// Parses a builder's `checks_json`. Leaving it out, or passing an empty string,
+    /// gives the defaults. `network` is only needed for blocklisted addresses in a
+    /// network's own encoding; the standard networks' addresses are read on any.
+    pub fn parse(checks_json: Option<&str>, network: Option<&NetworkParams>) -> Result<BuildChecks, String> {
+        let json: ChecksJson = match checks_json {
+            Some(json) if !json.trim().is_empty() => parse_json_value(json, "checks")?,
+            _ => ChecksJson::default(),
+        };
+        let fee_limits = FeeLimits::new(json.max_fee_sat, json.max_fee_rate_sat_vb, json.min_fee_rate_sat_vb)?;
+        let blocklist = if json.blocklist.is_empty() && json.allow_blocklisted.is_empty() {
+            Blocklist::default()
+        } else {
+            let default_network;
+            let network = match network {
+                Some(network) => network,
+                None => {
+                    default_network = network_or_default(None)?;
+                    &default_network
+                }
+            };
+            Blocklist::new(&json.blocklist, &json.allow_blocklisted, network)?
+        };
+        Ok(BuildChecks { fee_limits, blocklist })
+    }
+}
+
+/// What a finished transaction spends, for the fee limits.
+pub enum Funding<'a> {
+    /// Not signed yet: the rate is over the estimated signed vsize.
+    Unsigned(&'a [TransactionInput]),
+    /// Every input's prevout is known: the rate is over the transaction's own vsize, so
+    /// inputs still to be signed make it look higher than it will be.
+    #[cfg(feature = "signing")]
+    Signed(&'a [TxOut]),
+    /// The spent amounts aren't known, as when re-ordering a transaction or signing one
+    /// input of it, or the transaction is a fragment such as a pledge that others
+    /// complete. Fee limits don't apply; everything else does.
+    Unknown,
+}
+
+/// The one exit every builder's transaction takes: the fee limits, the blocklist and
+/// the serialization check, in that order. Returns the serialized transaction.
+pub fn finalize(tx: &Transaction, funding: Funding, checks: &BuildChecks) -> Result<Vec<u8>, String> {
+    match funding {
+        Funding::Unsigned(inputs) => {
+            let input_total = check_amount_total(inputs.iter().map(|input| input.amount), "inputs")?;
+            checks.fee_limits.check(tx, input_total, estimated_signed_vsize(tx, inputs))?;
+        }
+        #[cfg(feature = "signing")]
+        Funding::Signed(prevouts) => {
+            let input_total = check_amount_total(prevouts.iter().map(|prevout| prevout.value.to_sat()), "prevouts")?;
+            checks.fee_limits.check(tx, input_total, tx.vsize() as u64)?;
+        }
+        Funding::Unknown => {}
+    }
+    checks.blocklist.check(tx)?;
+    checked_serialize(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{absolute, transaction, Amount, ScriptBuf, TxOut};
+
+    fn paying(sats: u64) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: Vec::new(),
+            output: vec![TxOut { value: Amount::from_sat(sats), script_pubkey: ScriptBuf::new() }],
+        }
+    }
+
+    #[test]
+    fn limits_belong_to_one_call() {
+        let strict = BuildChecks::parse(Some(r#"{"maxFeeSat": 500}"#), None).unwrap();
+        let loose = BuildChecks::parse(None, None).unwrap();
+        let tx = paying(9_000);
+        assert!(strict.fee_limits.check(&tx, 10_000, 200).unwrap_err().contains("maxFeeSat"));
+        assert!(loose.fee_limits.check(&tx, 10_000, 200).is_ok());
+    }
+
+    #[test]
+    fn minimum_defaults_to_one_sat_per_vbyte() {
+        let tx = paying(9_900);
+        let defaults = BuildChecks::parse(Some(""), None).unwrap();
+        assert!(defaults.fee_limits.check(&tx, 10_000, 200).unwrap_err().contains("minFeeRateSatVb: 0"));
+        let zero = BuildChecks::parse(Some(r#"{"min_fee_rate_sat_vb": 0}"#), None).unwrap();
+        assert!(zero.fee_limits.check(&tx, 10_000, 200).is_ok());
+    }
+
+    #[test]
+    fn blocklist_and_allowance_belong_to_one_build() {
+        // scriptPubKey of the BIP173 P2WPKH test vector.
+        let listed = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+        let mut tx = paying(9_000);
+        tx.output[0].script_pubkey = ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+
+        let blocked = BuildChecks::parse(Some(&format!(r#"{{"blocklist": ["{}"]}}"#, listed)), None).unwrap();
+        assert!(blocked.blocklist.check(&tx).unwrap_err().contains("allowBlocklisted"));
+        let allowed =
+            BuildChecks::parse(Some(&format!(r#"{{"blocklist": ["{0}"], "allowBlocklisted": ["{0}"]}}"#, listed)), None).unwrap();
+        assert!(allowed.blocklist.check(&tx).is_ok());
+        // An allowance isn't used up: the same checks pass the same payment again.
+        assert!(allowed.blocklist.check(&tx).is_ok());
+        assert!(blocked.blocklist.check(&tx).is_err());
+        assert!(BuildChecks::parse(None, None).unwrap().blocklist.check(&tx).is_ok());
+    }
+
+    #[test]
+    fn allowance_must_be_on_the_blocklist() {
+        let error = BuildChecks::parse(Some(r#"{"allowBlocklisted": ["0014751e76e8199196d454941c45d1b3a323f1433bd6"]}"#), None)
+            .err()
+            .unwrap();
+        assert!(error.contains("isn't on the blocklist"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_unknown_and_bad_fields() {
+        assert!(BuildChecks::parse(Some(r#"{"maxFeeSats": 500}"#), None).is_err());
+        assert!(BuildChecks::parse(Some(r#"{"maxFeeRateSatVb": 0}"#), None).is_err());
+    }
+
+    #[test]
+    fn totals_above_max_money_are_refused() {
+        let checks = BuildChecks::parse(None, None).unwrap();
+        let mut tx = paying(u64::MAX / 2);
+        tx.output.push(tx.output[0].clone());
+        tx.output.push(tx.output[0].clone());
+        let error = checks.fee_limits.check(&tx, 10_000, 200).unwrap_err();
+        assert!(error.starts_with("outputs[0] amount"), "{}", error);
+
+        let input: TransactionInput = serde_json::from_value(serde_json::json!({
+            "txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            "vout": 0,
+            "amountSat": 2_000_000_000_000_000u64,
+            "scriptPubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+        }))
+        .unwrap();
+        let inputs = vec![input.clone(), input];
+        let error = finalize(&paying(9_000), Funding::Unsigned(&inputs), &checks).unwrap_err();
+        assert_eq!(error, "inputs amounts sum to more than 2100000000000000 sats");
+    }
+}
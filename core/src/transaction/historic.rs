@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::ecdsa;
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::{Script, TxIn};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::{output_warnings, unsigned_transaction, BuiltTransaction, TransactionInput, TransactionOutput};
+use crate::utils::bytes_to_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::network::network_or_default;
+#[cfg(feature = "signing")]
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+#[cfg(feature = "signing")]
+use bitcoin::sighash::SighashCache;
+#[cfg(feature = "signing")]
+use bitcoin::{Amount, ScriptBuf, TxOut, Witness};
+#[cfg(feature = "signing")]
+use crate::script::interpreter::verify_input;
+#[cfg(feature = "signing")]
+use crate::transaction::sign::{key_spend, KeySpend};
+#[cfg(feature = "signing")]
+use crate::transaction::{check_input_index, decode_tx_hex};
+#[cfg(feature = "signing")]
+use crate::utils::{hex_to_bytes, HexMode};
+#[cfg(feature = "signing")]
+use crate::wallet::parse_private_key;
+
+/// Block 363,725, where BIP66 made strict DER signatures consensus.
+const BIP66_HEIGHT: u32 = 363_725;
+
+/// Something about an input that modern wallets never produce. `severity` is `label`
+/// (standard, just old-fashioned), `nonstandard` (valid, but default nodes won't relay
+/// it) or `invalid` (accepted before a soft fork, rejected since).
+#[derive(Serialize, Deserialize)]
+pub struct InputQuirk {
+    /// `non_minimal_push`, `lax_der`, `high_s`, `undefined_sighash`, `uncompressed_key`
+    /// or `hybrid_key`.
+    pub kind: String,
+    /// `script_sig` or `witness`.
+    pub location: String,
+    /// The push or witness item it's about, counting from 0.
+    pub item: usize,
+    pub severity: String,
+    pub message: String,
+}
+
+#[cfg(feature = "signing")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoricSignature {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    /// DER with the sighash byte, as pushed.
+    pub signature: String,
+    pub pubkey: String,
+    #[serde(alias = "compressed_key")]
+    pub compressed_key: bool,
+    #[serde(alias = "high_s")]
+    pub high_s: bool,
+    /// Whether the input verifies under the consensus rules.
+    pub valid: bool,
+    pub error: Option<String>,
+    /// False when a quirk keeps default nodes from relaying the transaction.
+    pub standard: bool,
+    pub quirks: Vec<InputQuirk>,
+}
+
+/// Builds a transaction the way pre-2015 wallets did: version 1 unless `version` says
+/// otherwise (any value is taken, standard or not), inputs and outputs in the given
+/// order, final sequences and no locktime. Use `build_transaction` for anything meant
+/// to be broadcast today; this exists for rebuilding old transactions byte for byte.
+/// Early transactions often paid no fee, which takes `minFeeRateSatVb: 0` in `checks_json`.
+#[wasm_bindgen]
+pub fn build_historic_transaction(
+    inputs_json: &str,
+    outputs_json: &str,
+    network: Option<String>,
+    version: Option<u32>,
+    checks_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let inputs: Vec<TransactionInput> = parse_json_list(inputs_json, "inputs").map_err(|e| JsValue::from_str(&e))?;
+    let outputs: Vec<TransactionOutput> = parse_json_list(outputs_json, "outputs").map_err(|e| JsValue::from_str(&e))?;
+    if outputs.iter().filter(|output| output.is_change).count() > 1 {
+        return Err(JsValue::from_str("At most one output can be marked as change"));
+    }
+
+    let mut tx = unsigned_transaction(&inputs, &outputs, Some(&network))?;
+    // Consensus reads the version as a signed 32-bit number; keep the bits as given.
+    tx.version = bitcoin::transaction::Version(version.unwrap_or(1) as i32);
+    let mut warnings = output_warnings(&tx);
+    if !(1..=3).contains(&tx.version.0) {
+        warnings.push(format!("Version {} is outside the standard range 1 to 3, so it won't relay", tx.version.0));
+    }
+
+    let tx_bytes = finalize(&tx, Funding::Unsigned(&inputs), &checks).map_err(|e| JsValue::from_str(&e))?;
+    let change_vout = outputs.iter().position(|output| output.is_change);
+    let built = BuiltTransaction {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        input_order: (0..tx.input.len()).collect(),
+        output_order: (0..tx.output.len()).collect(),
+        change_vout,
+        change_index: change_vout,
+        warnings,
+    };
+    to_json(&built, compact)
+}
+
+/// Signs a P2PKH or P2PK input with SIGHASH_ALL the way early wallets did. The key is
+/// pushed in whichever form `script_pubkey_hex` commits to, uncompressed included. The
+/// nonce is ground for a low R like every other signer here, so fee estimates hold. With
+/// `high_s` the S value is flipped to its high form: still valid, but not standard.
+/// The result is verified and its quirks listed, so nothing non-standard goes unnoticed.
+#[cfg(feature = "signing")]
+#[wasm_bindgen]
+pub fn sign_historic_input(
+    tx_hex: &str,
+    private_key: &str,
+    input_index: usize,
+    script_pubkey_hex: &str,
+    high_s: Option<bool>,
+    checks_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let private_key = parse_private_key(private_key, None, bitcoin::Network::Testnet)?;
+    let mut tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let script = ScriptBuf::from_bytes(
+        hex_to_bytes(script_pubkey_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e)))?,
+    );
+
+    let secret_key = private_key.inner;
+    let own_key = secret_key.public_key(&secp);
+    let pubkey = if let Some(key) = script.p2pk_public_key() {
+        if key.inner != own_key {
+            return Err(JsValue::from_str("private key does not match the P2PK script"));
+        }
+        key
+    } else if script.is_p2pkh() {
+        match key_spend(&secp, &script, &own_key, private_key.compressed, "private key").map_err(|e| JsValue::from_str(&e))? {
+            KeySpend::P2pkh(key) => key,
+            _ => unreachable!("a P2PKH script gives a P2PKH spend"),
+        }
+    } else {
+        return Err(JsValue::from_str("Historic signing covers P2PKH and P2PK outputs"));
+    };
+
+    let sighash = SighashCache::new(&tx)
+        .legacy_signature_hash(input_index, &script, EcdsaSighashType::All.to_u32())
+        .map(Message::from)
+        .map_err(|e| JsValue::from_str(&format!("sighash error: {}", e)))?;
+    let mut signature = secp.sign_ecdsa_low_r(&sighash, &secret_key);
+    if high_s.unwrap_or(false) {
+        signature = flip_s(&signature);
+    }
+    let signature = bitcoin::ecdsa::Signature::sighash_all(signature);
+    let builder = bitcoin::script::Builder::new().push_slice(signature.serialize());
+    tx.input[input_index].script_sig = if script.is_p2pkh() { builder.push_key(&pubkey) } else { builder }.into_script();
+    tx.input[input_index].witness = Witness::default();
+
+    // A legacy sighash doesn't commit to the other inputs' prevouts.
+    let mut prevouts = vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new() }; tx.input.len()];
+    prevouts[input_index] = TxOut { value: Amount::ZERO, script_pubkey: script };
+    let outcome = verify_input(&tx, input_index, &prevouts);
+    let quirks = input_quirks(&tx.input[input_index]);
+    let result = HistoricSignature {
+        tx_hex: bytes_to_hex(&finalize(&tx, Funding::Unknown, &checks).map_err(|e| JsValue::from_str(&e))?),
+        signature: bytes_to_hex(&signature.to_vec()),
+        pubkey: bytes_to_hex(&pubkey.to_bytes()),
+        compressed_key: pubkey.compressed,
+        high_s: high_s.unwrap_or(false),
+        valid: outcome.is_ok(),
+        error: outcome.err().map(|failure| format!("{}: {}", failure.phase, failure.reason)),
+        standard: quirks.iter().all(|quirk| quirk.severity == "label"),
+        quirks,
+    };
+    to_json(&result, compact)
+}
+
+/// s → n − s, which verifies just the same.
+#[cfg(feature = "signing")]
+fn flip_s(signature: &ecdsa::Signature) -> ecdsa::Signature {
+    let mut compact = signature.serialize_compact();
+    let s = SecretKey::from_slice(&compact[32..]).expect("s is a valid scalar").negate();
+    compact[32..].copy_from_slice(&s.secret_bytes());
+    ecdsa::Signature::from_compact(&compact).expect("r ‖ n − s is a valid signature")
+}
+
+/// Pre-BIP66 and otherwise old-fashioned encodings in an input, found from the scriptSig
+/// and witness alone: non-minimal pushes, signatures that are only lax DER, high S or an
+/// undefined sighash byte, and uncompressed or hybrid keys. Witness items are only
+/// checked for signatures, since a script-path witness can hold arbitrary data.
+pub fn input_quirks(input: &TxIn) -> Vec<InputQuirk> {
+    let mut quirks = Vec::new();
+    let script_sig = &input.script_sig;
+    let mut push = 0;
+    for instruction in script_sig.instruction_indices() {
+        let Ok((offset, instruction)) = instruction else {
+            break;
+        };
+        let Instruction::PushBytes(bytes) = instruction else {
+            continue;
+        };
+        // `instructions_minimal` stops at the first offender, so each push is checked on its own.
+        let rest = Script::from_bytes(&script_sig.as_bytes()[offset..]);
+        if let Some(Err(bitcoin::script::Error::NonMinimalPush)) = rest.instructions_minimal().next() {
+            quirks.push(quirk(
+                "non_minimal_push",
+                "script_sig",
+                push,
+                "nonstandard",
+                "Data push larger than necessary (MINIMALDATA policy)".to_string(),
+            ));
+        }
+        element_quirks(bytes.as_bytes(), "script_sig", push, true, &mut quirks);
+        push += 1;
+    }
+    for (index, item) in input.witness.iter().enumerate() {
+        element_quirks(item, "witness", index, false, &mut quirks);
+    }
+    quirks
+}
+
+fn element_quirks(bytes: &[u8], location: &str, item: usize, check_keys: bool, quirks: &mut Vec<InputQuirk>) {
+    if let Some((signature, strict, hash_type)) = ecdsa_signature(bytes) {
+        if !strict {
+            quirks.push(quirk(
+                "lax_der",
+                location,
+                item,
+                "invalid",
+                format!("Non-canonical DER signature, accepted only before BIP66 (block {})", BIP66_HEIGHT),
+            ));
+        }
+        if EcdsaSighashType::from_standard(hash_type as u32).is_err() {
+            quirks.push(quirk(
+                "undefined_sighash",
+                location,
+                item,
+                "nonstandard",
+                format!("Sighash byte {:#04x} isn't a defined type (STRICTENC policy)", hash_type),
+            ));
+        }
+        let mut normalized = signature;
+        normalized.normalize_s();
+        if normalized != signature {
+            quirks.push(quirk(
+                "high_s",
+                location,
+                item,
+                "nonstandard",
+                "Non-canonical signature: S value is unnecessarily high (LOW_S policy)".to_string(),
+            ));
+        }
+        return;
+    }
+    if !check_keys || bytes.len() != 65 {
+        return;
+    }
+    match bytes[0] {
+        0x04 => quirks.push(quirk("uncompressed_key", location, item, "label", "Uncompressed public key".to_string())),
+        0x06 | 0x07 => quirks.push(quirk(
+            "hybrid_key",
+            location,
+            item,
+            "nonstandard",
+            "Hybrid public key, which is neither compressed nor uncompressed (STRICTENC policy)".to_string(),
+        )),
+        _ => {}
+    }
+}
+
+/// A push that reads as a DER signature with a sighash byte, and whether it's strict DER.
+pub fn ecdsa_signature(bytes: &[u8]) -> Option<(ecdsa::Signature, bool, u8)> {
+    if !(9..=73).contains(&bytes.len()) || bytes[0] != 0x30 {
+        return None;
+    }
+    let (&hash_type, der) = bytes.split_last()?;
+    match ecdsa::Signature::from_der(der) {
+        Ok(signature) => Some((signature, true, hash_type)),
+        Err(_) => ecdsa::Signature::from_der_lax(der).ok().map(|signature| (signature, false, hash_type)),
+    }
+}
+
+fn quirk(kind: &str, location: &str, item: usize, severity: &str, message: String) -> InputQuirk {
+    InputQuirk {
+        kind: kind.to_string(),
+        location: location.to_string(),
+        item,
+        severity: severity.to_string(),
+        message,
+    }
+}
@@ -0,0 +1,475 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::absolute::LOCK_TIME_THRESHOLD;
+use bitcoin::block::Header;
+use bitcoin::relative;
+use bitcoin::{Sequence, Transaction};
+#[cfg(feature = "wallet")]
+use rand::Rng;
+use crate::transaction::decode_tx_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::{hex_to_bytes, HexMode};
+#[cfg(feature = "wallet")]
+use crate::utils::rng::with_rng;
+
+/// Bitcoin Core backdates one anti-fee-sniping locktime in ten, by up to 99 blocks, so
+/// transactions that were delayed (e.g. by a high-latency mix network) don't stand out.
+#[cfg(feature = "wallet")]
+const BACKDATE_ONE_IN: u32 = 10;
+#[cfg(feature = "wallet")]
+const MAX_BACKDATE_BLOCKS: u32 = 99;
+/// Consensus takes the median of the last 11 block timestamps.
+const MEDIAN_TIME_SPAN: usize = 11;
+/// The target block interval, for turning blocks into time and back.
+pub const BLOCK_INTERVAL_SECS: u64 = 600;
+
+#[derive(Serialize, Deserialize)]
+pub struct LocktimeDescription {
+    pub value: u32,
+    pub kind: String,
+    pub height: Option<u32>,
+    pub timestamp: Option<u32>,
+    pub description: String,
+}
+
+/// A block for `median_time_past`: its timestamp, or its 80-byte header in hex.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum BlockTime {
+    Timestamp(u32),
+    Header(String),
+}
+
+/// Where a spent coin confirmed: its height, or the height with the median time past of
+/// the block before it, which time-based relative locks count from.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum PrevoutHeight {
+    Height(u32),
+    Detailed { height: u32, mtp: Option<u32> },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStatus {
+    /// "absolute" for nLockTime, "relative" for an input's BIP68 sequence lock.
+    pub scope: String,
+    #[serde(alias = "input_index")]
+    pub input_index: Option<usize>,
+    /// "height" or "time".
+    pub unit: String,
+    /// The locktime, or the relative lock in blocks or 512-second units.
+    pub value: u32,
+    /// The height the next block must reach, or the median time past the tip must reach.
+    pub required: Option<u64>,
+    pub satisfied: bool,
+    /// Blocks still to be mined before the transaction can go in the next block.
+    #[serde(alias = "remaining_blocks")]
+    pub remaining_blocks: Option<u64>,
+    #[serde(alias = "remaining_seconds")]
+    pub remaining_seconds: Option<u64>,
+    /// Whether the remaining figures rest on the 10-minute block interval rather than
+    /// known heights or times.
+    pub estimated: bool,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockEvaluation {
+    #[serde(alias = "tip_height")]
+    pub tip_height: u32,
+    pub mtp: u32,
+    /// Whether every lock allows the transaction into the next block.
+    pub satisfied: bool,
+    pub locks: Vec<LockStatus>,
+}
+
+#[wasm_bindgen]
+pub fn locktime_from_height(height: u32) -> Result<u32, JsValue> {
+    if height >= LOCK_TIME_THRESHOLD {
+        return Err(JsValue::from_str(&format!(
+            "Block height {} is not below {}, so it would be read as a timestamp",
+            height, LOCK_TIME_THRESHOLD
+        )));
+    }
+    Ok(height)
+}
+
+#[wasm_bindgen]
+pub fn locktime_from_timestamp(unix_time: u32) -> Result<u32, JsValue> {
+    if unix_time < LOCK_TIME_THRESHOLD {
+        return Err(JsValue::from_str(&format!(
+            "Timestamp {} is below {}, so it would be read as a block height",
+            unix_time, LOCK_TIME_THRESHOLD
+        )));
+    }
+    Ok(unix_time)
+}
+
+#[wasm_bindgen]
+pub fn describe_locktime(locktime_value: u32, compact: Option<bool>) -> Result<String, JsValue> {
+    to_json(&locktime_description(locktime_value), compact)
+}
+
+/// Consensus finality (`IsFinalTx`) for inclusion in the block after `current_height`,
+/// the same check nodes apply before accepting a transaction into the mempool.
+#[wasm_bindgen]
+pub fn is_final(tx_hex: &str, current_height: u32, current_mtp: u32) -> Result<bool, JsValue> {
+    let tx = decode_tx_hex(tx_hex)?;
+    Ok(is_final_tx(&tx, current_height.saturating_add(1), current_mtp))
+}
+
+/// The median of up to 11 block timestamps, given as numbers or header hex, as
+/// consensus computes it for the newest of them. Fewer than 11 only happens near genesis.
+#[wasm_bindgen]
+pub fn median_time_past(last_11_headers_json_or_timestamps: &str) -> Result<u32, JsValue> {
+    let blocks: Vec<BlockTime> = parse_json_list(last_11_headers_json_or_timestamps, "headers").map_err(|e| JsValue::from_str(&e))?;
+    let times = blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| block_timestamp(block).map_err(|e| format!("headers[{}]: {}", index, e)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| JsValue::from_str(&e))?;
+    median_of(times).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Whether each lock on a transaction lets it into the block after `tip_height`, and if
+/// not roughly how long is left. `prevout_heights_json` has one entry per input: the
+/// height its coin confirmed at, `{height, mtp}` to time relative locks exactly, or
+/// null for an unconfirmed coin. `mtp` is the tip's median time past.
+#[wasm_bindgen]
+pub fn evaluate_locks(tx_hex: &str, prevout_heights_json: &str, tip_height: u32, mtp: u32, compact: Option<bool>) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(tx_hex)?;
+    let heights: Vec<Option<PrevoutHeight>> =
+        parse_json_list(prevout_heights_json, "prevout_heights").map_err(|e| JsValue::from_str(&e))?;
+    if heights.len() != tx.input.len() {
+        return Err(JsValue::from_str(&format!(
+            "prevout_heights has {} entries but the transaction has {} inputs",
+            heights.len(),
+            tx.input.len()
+        )));
+    }
+
+    let evaluation = lock_evaluation(&tx, &heights, tip_height, mtp).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&evaluation, compact)
+}
+
+pub fn is_final_tx(tx: &Transaction, block_height: u32, block_time: u32) -> bool {
+    let lock_time = tx.lock_time.to_consensus_u32();
+    if lock_time == 0 {
+        return true;
+    }
+
+    let threshold = if lock_time < LOCK_TIME_THRESHOLD { block_height } else { block_time };
+    if lock_time < threshold {
+        return true;
+    }
+
+    // A locktime is only enforced if at least one input opts in with a non-final sequence.
+    tx.input.iter().all(|input| input.sequence == Sequence::MAX)
+}
+
+fn block_timestamp(block: &BlockTime) -> Result<u32, String> {
+    match block {
+        BlockTime::Timestamp(time) => Ok(*time),
+        BlockTime::Header(hex) => {
+            let bytes = hex_to_bytes(hex, HexMode::Lenient).map_err(|e| format!("Invalid header hex: {}", e))?;
+            let header: Header = bitcoin::consensus::deserialize(&bytes)
+                .map_err(|e| format!("Invalid block header (expected 80 bytes): {}", e))?;
+            Ok(header.time)
+        }
+    }
+}
+
+/// Bitcoin Core's `GetMedianTimePast`: the middle of the sorted times, the upper of the
+/// two middles for an even count.
+pub fn median_of(mut times: Vec<u32>) -> Result<u32, String> {
+    if times.is_empty() || times.len() > MEDIAN_TIME_SPAN {
+        return Err(format!("Expected 1 to {} block timestamps, got {}", MEDIAN_TIME_SPAN, times.len()));
+    }
+    times.sort_unstable();
+    Ok(times[times.len() / 2])
+}
+
+pub fn lock_evaluation(tx: &Transaction, heights: &[Option<PrevoutHeight>], tip_height: u32, mtp: u32) -> Result<LockEvaluation, String> {
+    let mut locks = Vec::new();
+    let lock_time = tx.lock_time.to_consensus_u32();
+    if lock_time != 0 {
+        locks.push(absolute_lock(tx, lock_time, tip_height, mtp));
+    }
+
+    // BIP68 only applies from version 2.
+    if tx.version.0 >= 2 {
+        for (index, (input, height)) in tx.input.iter().zip(heights).enumerate() {
+            let Some(lock) = input.sequence.to_relative_lock_time() else {
+                continue;
+            };
+            locks.push(relative_lock(index, lock, height.as_ref(), tip_height, mtp)?);
+        }
+    }
+
+    Ok(LockEvaluation {
+        tip_height,
+        mtp,
+        satisfied: locks.iter().all(|lock| lock.satisfied),
+        locks,
+    })
+}
+
+pub fn absolute_lock(tx: &Transaction, lock_time: u32, tip_height: u32, mtp: u32) -> LockStatus {
+    let is_height = lock_time < LOCK_TIME_THRESHOLD;
+    let mut status = LockStatus {
+        scope: "absolute".to_string(),
+        input_index: None,
+        unit: if is_height { "height" } else { "time" }.to_string(),
+        value: lock_time,
+        required: None,
+        satisfied: true,
+        remaining_blocks: None,
+        remaining_seconds: None,
+        estimated: false,
+        note: None,
+    };
+    if !tx.is_lock_time_enabled() {
+        status.note = Some("every input has sequence 0xffffffff, which disables the locktime".to_string());
+        return status;
+    }
+
+    if is_height {
+        // Final once the block's height passes the locktime.
+        status.required = Some(lock_time as u64 + 1);
+        let remaining = (lock_time as u64).saturating_sub(tip_height as u64);
+        status.satisfied = remaining == 0;
+        if !status.satisfied {
+            status.remaining_blocks = Some(remaining);
+            status.remaining_seconds = Some(remaining * BLOCK_INTERVAL_SECS);
+            status.estimated = true;
+        }
+    } else {
+        // BIP113: final once the median time past of the block before passes the locktime.
+        status.required = Some(lock_time as u64 + 1);
+        let remaining = (lock_time as u64 + 1).saturating_sub(mtp as u64);
+        status.satisfied = remaining == 0;
+        if !status.satisfied {
+            status.remaining_seconds = Some(remaining);
+            status.remaining_blocks = Some(remaining.div_ceil(BLOCK_INTERVAL_SECS));
+            status.estimated = true;
+            status.note = Some("median time past runs about an hour behind the clock".to_string());
+        }
+    }
+    status
+}
+
+fn relative_lock(index: usize, lock: relative::LockTime, height: Option<&PrevoutHeight>, tip_height: u32, mtp: u32) -> Result<LockStatus, String> {
+    let (confirmed_at, prevout_mtp) = match height {
+        Some(PrevoutHeight::Height(height)) => (Some(*height), None),
+        Some(PrevoutHeight::Detailed { height, mtp }) => (Some(*height), *mtp),
+        None => (None, None),
+    };
+    if confirmed_at.is_some_and(|height| height > tip_height) {
+        return Err(format!("prevout_heights[{}] is above the tip height {}", index, tip_height));
+    }
+
+    let mut status = LockStatus {
+        scope: "relative".to_string(),
+        input_index: Some(index),
+        unit: String::new(),
+        value: 0,
+        required: None,
+        satisfied: false,
+        remaining_blocks: None,
+        remaining_seconds: None,
+        estimated: false,
+        note: None,
+    };
+    match lock {
+        relative::LockTime::Blocks(blocks) => {
+            let blocks = blocks.value() as u64;
+            status.unit = "height".to_string();
+            status.value = blocks as u32;
+            // The spending block's height must be at least the coin's height plus the lock.
+            let remaining = match confirmed_at {
+                Some(height) => {
+                    status.required = Some(height as u64 + blocks);
+                    (height as u64 + blocks).saturating_sub(tip_height as u64 + 1)
+                }
+                None => {
+                    status.note = Some("the coin is unconfirmed; the lock counts from the block that confirms it".to_string());
+                    blocks
+                }
+            };
+            status.satisfied = confirmed_at.is_some() && remaining == 0;
+            if !status.satisfied {
+                status.remaining_blocks = Some(remaining);
+                status.remaining_seconds = Some(remaining * BLOCK_INTERVAL_SECS);
+                status.estimated = true;
+            }
+        }
+        relative::LockTime::Time(interval) => {
+            let seconds = interval.value() as u64 * 512;
+            status.unit = "time".to_string();
+            status.value = interval.value() as u32;
+            let start = match (confirmed_at, prevout_mtp) {
+                (Some(_), Some(prevout_mtp)) => Some(prevout_mtp as u64),
+                (Some(height), None) => {
+                    // Without the coin's own median time past, assume 10-minute blocks since it.
+                    status.estimated = true;
+                    status.note = Some("pass {height, mtp} for this coin to time the lock exactly".to_string());
+                    Some((mtp as u64).saturating_sub((tip_height - height + 1) as u64 * BLOCK_INTERVAL_SECS))
+                }
+                (None, _) => {
+                    status.note = Some("the coin is unconfirmed; the lock counts from the block that confirms it".to_string());
+                    None
+                }
+            };
+            // BIP68: the tip's median time past must reach the start plus the lock.
+            let remaining = match start {
+                Some(start) => {
+                    status.required = Some(start + seconds);
+                    (start + seconds).saturating_sub(mtp as u64)
+                }
+                None => seconds,
+            };
+            status.satisfied = start.is_some() && remaining == 0;
+            if !status.satisfied {
+                status.remaining_seconds = Some(remaining);
+                status.remaining_blocks = Some(remaining.div_ceil(BLOCK_INTERVAL_SECS));
+                status.estimated = true;
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// The locktime Bitcoin Core's `DiscourageFeeSniping` picks for a transaction built at
+/// `tip_height`: the tip itself, or occasionally a few blocks earlier. The random draws
+/// come from `with_rng`, so `set_test_seed` makes them reproducible.
+#[cfg(feature = "wallet")]
+pub fn anti_fee_sniping_locktime(tip_height: u32) -> Result<u32, String> {
+    if tip_height >= LOCK_TIME_THRESHOLD {
+        return Err(format!(
+            "Tip height {} is not below {}, so it would be read as a timestamp",
+            tip_height, LOCK_TIME_THRESHOLD
+        ));
+    }
+    Ok(with_rng(|rng| {
+        if rng.gen_range(0..BACKDATE_ONE_IN) == 0 {
+            tip_height.saturating_sub(rng.gen_range(0..=MAX_BACKDATE_BLOCKS))
+        } else {
+            tip_height
+        }
+    }))
+}
+
+#[cfg(not(feature = "wallet"))]
+pub fn anti_fee_sniping_locktime(_tip_height: u32) -> Result<u32, String> {
+    Err("anti-fee-sniping needs a build with the wallet feature for its random backdating".to_string())
+}
+
+/// Whether a transaction has the shape anti-fee-sniping leaves: a block-height locktime
+/// that binds, and no input using its sequence for a relative lock. It can't be told
+/// apart from a deliberate lock to a height near the tip at signing time.
+#[cfg(feature = "decode")]
+pub fn looks_like_anti_fee_sniping(tx: &Transaction) -> bool {
+    let lock_time = tx.lock_time.to_consensus_u32();
+    lock_time != 0
+        && lock_time < LOCK_TIME_THRESHOLD
+        && tx.is_lock_time_enabled()
+        && tx.input.iter().all(|input| input.sequence >= Sequence::ENABLE_RBF_NO_LOCKTIME)
+}
+
+pub fn locktime_description(value: u32) -> LocktimeDescription {
+    if value == 0 {
+        return LocktimeDescription {
+            value,
+            kind: "none".to_string(),
+            height: None,
+            timestamp: None,
+            description: "no locktime".to_string(),
+        };
+    }
+
+    if value < LOCK_TIME_THRESHOLD {
+        LocktimeDescription {
+            value,
+            kind: "height".to_string(),
+            height: Some(value),
+            timestamp: None,
+            description: format!("final once the chain has passed block {}", value),
+        }
+    } else {
+        LocktimeDescription {
+            value,
+            kind: "time".to_string(),
+            height: None,
+            timestamp: Some(value),
+            description: format!("final once the median time past exceeds {} (unix seconds)", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::TxIn;
+
+    fn locked(lock_time: u32, sequence: Sequence) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_consensus(lock_time),
+            input: vec![TxIn { sequence, ..Default::default() }, TxIn { sequence: Sequence::MAX, ..Default::default() }],
+            output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn threshold_splits_heights_from_times() {
+        assert_eq!(locktime_from_height(LOCK_TIME_THRESHOLD - 1).unwrap(), 499_999_999);
+        assert_eq!(locktime_from_timestamp(LOCK_TIME_THRESHOLD).unwrap(), 500_000_000);
+
+        let below = locktime_description(LOCK_TIME_THRESHOLD - 1);
+        assert_eq!((below.kind.as_str(), below.height, below.timestamp), ("height", Some(499_999_999), None));
+        let at = locktime_description(LOCK_TIME_THRESHOLD);
+        assert_eq!((at.kind.as_str(), at.height, at.timestamp), ("time", None, Some(500_000_000)));
+        assert_eq!(locktime_description(0).kind, "none");
+    }
+
+    #[test]
+    fn height_locks_need_a_later_block() {
+        let tx = locked(800_000, Sequence::ENABLE_LOCKTIME_NO_RBF);
+        assert!(!is_final_tx(&tx, 800_000, 0));
+        assert!(is_final_tx(&tx, 800_001, 0));
+        // A height lock ignores the clock, however late it is.
+        assert!(!is_final_tx(&tx, 799_999, u32::MAX));
+    }
+
+    #[test]
+    fn time_locks_compare_against_median_time_past() {
+        let tx = locked(LOCK_TIME_THRESHOLD, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        assert!(!is_final_tx(&tx, u32::MAX, LOCK_TIME_THRESHOLD));
+        assert!(is_final_tx(&tx, 0, LOCK_TIME_THRESHOLD + 1));
+        // 499,999,999 is a height, so a block time that large doesn't satisfy it.
+        let height_lock = locked(LOCK_TIME_THRESHOLD - 1, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        assert!(!is_final_tx(&height_lock, 1, u32::MAX));
+    }
+
+    #[test]
+    fn final_sequences_disable_the_locktime() {
+        assert!(is_final_tx(&locked(800_000, Sequence::MAX), 1, 0));
+        assert!(is_final_tx(&locked(u32::MAX, Sequence::MAX), 1, 0));
+        assert!(!is_final_tx(&locked(800_000, Sequence::from_consensus(0xffff_fffe)), 1, 0));
+        assert!(is_final_tx(&locked(0, Sequence::ZERO), 0, 0));
+    }
+
+    #[test]
+    fn median_of_up_to_eleven_times() {
+        assert_eq!(median_of(vec![5, 1, 3]).unwrap(), 3);
+        assert_eq!(median_of((1..=11).rev().collect()).unwrap(), 6);
+        assert_eq!(median_of(vec![7, 9]).unwrap(), 9);
+        assert!(median_of(Vec::new()).is_err());
+        assert!(median_of(vec![0; 12]).is_err());
+    }
+}
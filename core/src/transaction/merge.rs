@@ -0,0 +1,135 @@
+use wasm_bindgen::prelude::*;
+use bitcoin::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::{Script, ScriptBuf, Transaction, Witness};
+use crate::transaction::decode_tx_hex;
+use crate::transaction::serialization::checked_serialize;
+use crate::utils::bytes_to_hex;
+use crate::utils::metrics::timed;
+
+/// Combines two copies of the same transaction signed by different parties, in the
+/// spirit of Core's `combinerawtransaction`. Each input takes whichever copy has data;
+/// when both do, their elements are merged position by position, so each cosigner
+/// can fill its own slot of a shared stack template and leave the others empty.
+#[wasm_bindgen]
+pub fn merge_transactions(tx_a_hex: &str, tx_b_hex: &str) -> Result<String, JsValue> {
+    timed("merge_transactions", tx_a_hex.len() + tx_b_hex.len(), || {
+        let a = decode_tx_hex(tx_a_hex)?;
+        let b = decode_tx_hex(tx_b_hex)?;
+        let merged = merge(&a, &b).map_err(|e| JsValue::from_str(&e))?;
+        Ok(bytes_to_hex(&checked_serialize(&merged).map_err(|e| JsValue::from_str(&e))?))
+    })
+}
+
+pub fn merge(a: &Transaction, b: &Transaction) -> Result<Transaction, String> {
+    check_same_unsigned(a, b)?;
+
+    let mut merged = a.clone();
+    for (index, (input, other)) in merged.input.iter_mut().zip(&b.input).enumerate() {
+        input.script_sig = merge_script_sig(&input.script_sig, &other.script_sig)
+            .map_err(|e| format!("Input {} scriptSig: {}", index, e))?;
+        input.witness = merge_witness(&input.witness, &other.witness).map_err(|e| format!("Input {} witness: {}", index, e))?;
+    }
+    Ok(merged)
+}
+
+fn check_same_unsigned(a: &Transaction, b: &Transaction) -> Result<(), String> {
+    if a.version != b.version {
+        return Err(format!("Transactions differ: version {} vs {}", a.version.0, b.version.0));
+    }
+    if a.lock_time != b.lock_time {
+        return Err(format!(
+            "Transactions differ: locktime {} vs {}",
+            a.lock_time.to_consensus_u32(),
+            b.lock_time.to_consensus_u32()
+        ));
+    }
+    if a.input.len() != b.input.len() || a.output.len() != b.output.len() {
+        return Err(format!(
+            "Transactions differ: {} inputs/{} outputs vs {} inputs/{} outputs",
+            a.input.len(),
+            a.output.len(),
+            b.input.len(),
+            b.output.len()
+        ));
+    }
+    for (index, (x, y)) in a.input.iter().zip(&b.input).enumerate() {
+        if x.previous_output != y.previous_output || x.sequence != y.sequence {
+            return Err(format!("Transactions differ at input {}", index));
+        }
+    }
+    if let Some(index) = a.output.iter().zip(&b.output).position(|(x, y)| x != y) {
+        return Err(format!("Transactions differ at output {}", index));
+    }
+    Ok(())
+}
+
+fn merge_script_sig(a: &ScriptBuf, b: &ScriptBuf) -> Result<ScriptBuf, String> {
+    // An unsigned input may carry its prevout script as a placeholder; real scriptSigs are push-only.
+    let (a_signed, b_signed) = (is_signature_data(a), is_signature_data(b));
+    if a == b || !b_signed {
+        return Ok(a.clone());
+    }
+    if !a_signed {
+        return Ok(b.clone());
+    }
+
+    let (Some(a_pushes), Some(b_pushes)) = (pushes(a), pushes(b)) else {
+        return Err("both copies have different data".to_string());
+    };
+    let merged = merge_items(&a_pushes, &b_pushes)?;
+    let mut builder = Builder::new();
+    for item in merged {
+        let push = PushBytesBuf::try_from(item).map_err(|e| format!("Push too large: {}", e))?;
+        builder = builder.push_slice(push);
+    }
+    Ok(builder.into_script())
+}
+
+fn merge_witness(a: &Witness, b: &Witness) -> Result<Witness, String> {
+    if a == b || b.is_empty() {
+        return Ok(a.clone());
+    }
+    if a.is_empty() {
+        return Ok(b.clone());
+    }
+    let a_items: Vec<Vec<u8>> = a.iter().map(|item| item.to_vec()).collect();
+    let b_items: Vec<Vec<u8>> = b.iter().map(|item| item.to_vec()).collect();
+    Ok(Witness::from_slice(&merge_items(&a_items, &b_items)?))
+}
+
+fn merge_items(a: &[Vec<u8>], b: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, String> {
+    if a.len() != b.len() {
+        return Err(format!(
+            "both copies have data but with different element counts ({} vs {})",
+            a.len(),
+            b.len()
+        ));
+    }
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .map(|(position, (x, y))| {
+            if x == y || y.is_empty() {
+                Ok(x.clone())
+            } else if x.is_empty() {
+                Ok(y.clone())
+            } else {
+                Err(format!("both copies have different non-empty data at element {}", position))
+            }
+        })
+        .collect()
+}
+
+fn is_signature_data(script: &Script) -> bool {
+    !script.is_empty() && script.is_push_only()
+}
+
+fn pushes(script: &Script) -> Option<Vec<Vec<u8>>> {
+    script
+        .instructions()
+        .map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes().to_vec()),
+            _ => None,
+        })
+        .collect()
+}
@@ -1,35 +1,267 @@
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
-use bitcoin::{Transaction, TxIn, TxOut, OutPoint, ScriptBuf, Witness, Amount, Address, EcdsaSighashType, PrivateKey, Network};
-use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::{Transaction, TxIn, TxOut, OutPoint, ScriptBuf, Witness, Amount};
+use bitcoin::sighash::EcdsaSighashType;
+use std::collections::HashMap;
 use std::str::FromStr;
-use crate::utils::{bytes_to_hex, hex_to_bytes};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::network::{network_or_default, require_address_network, NetworkParams};
+use ordering::{apply_order, parse_ordering, transaction_order};
+use version::{parse_version, truc_violations};
+use finalize::{finalize, BuildChecks, Funding};
+use weight::estimated_signed_vsize;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::step::{check_work_units, step_json, to_result, Progress, RawValue};
+use crate::utils::metrics::timed;
+use crate::utils::sanitize::{sanitize, InputKind};
 
-#[derive(Serialize, Deserialize)]
+pub mod accept;
+pub mod address;
+pub mod address_correction;
+pub mod blocklist;
+pub mod bump;
+pub mod constructors;
+#[cfg(feature = "signing")]
+pub mod crowdfund;
+pub mod diff;
+pub mod dummy;
+pub mod fee_limits;
+pub mod finalize;
+pub mod historic;
+pub mod locktime;
+#[cfg(feature = "signing")]
+pub mod merge;
+#[cfg(feature = "signing")]
+pub mod multisig;
+pub mod multisig_witness;
+#[cfg(feature = "signing")]
+pub mod offer;
+pub mod ordering;
+#[cfg(feature = "signing")]
+pub mod p2wsh;
+pub mod prevouts;
+#[cfg(any(feature = "signing", feature = "decode"))]
+pub mod provider;
+pub mod rbf;
+pub mod serialization;
+#[cfg(feature = "signing")]
+pub mod sign;
+#[cfg(feature = "signing")]
+pub mod signer;
+pub mod signing_record;
+pub mod standardness;
+pub mod unconfirmed;
+pub mod version;
+pub mod weight;
+pub mod whitelist;
+pub mod witness;
+
+pub use accept::local_accept_check;
+pub use diff::diff_transactions;
+pub use address::{validate_address, validate_addresses, address_for_qr, bitcoin_uri_for_qr};
+pub use address_correction::suggest_address_correction;
+pub use blocklist::screen_outputs;
+pub use bump::bump_options;
+pub use constructors::{make_outpoint, make_input, make_output};
+#[cfg(feature = "signing")]
+pub use crowdfund::{assemble_crowdfund, create_pledge};
+pub use dummy::{populate_dummy_signatures, transaction_vsize};
+pub use historic::build_historic_transaction;
+#[cfg(feature = "signing")]
+pub use historic::sign_historic_input;
+pub use locktime::{locktime_from_height, locktime_from_timestamp, describe_locktime, is_final, median_time_past, evaluate_locks};
+#[cfg(feature = "signing")]
+pub use merge::merge_transactions;
+pub use multisig_witness::diagnose_multisig_witness;
+#[cfg(feature = "signing")]
+pub use offer::{create_offer, fill_offer};
+pub use ordering::sort_transaction_bip69;
+#[cfg(feature = "signing")]
+pub use p2wsh::sign_p2wsh_input;
+pub use prevouts::parse_prevouts;
+pub use rbf::check_rbf;
+pub use serialization::{set_serialization_checks, validate_serialization};
+pub use witness::{get_witness, push_witness_item, remove_witness_item, set_witness};
+#[cfg(feature = "signing")]
+pub use sign::{sign_transaction, sign_all_inputs, sign_all_inputs_with_provider, BatchSigner};
+#[cfg(feature = "signing")]
+pub use signer::sign_all_inputs_with_signer;
+pub use signing_record::{export_signing_record, verify_signing_record};
+pub use standardness::check_standardness;
+pub use unconfirmed::UnconfirmedGraph;
+pub use version::check_truc;
+pub use weight::{estimate_input_weight, input_weight_table};
+pub use whitelist::{restrict_outputs, RestrictedBuilder};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct TransactionInput {
     pub txid: String,
     pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
     pub amount: u64,
+    #[serde(alias = "script_pubkey")]
     pub script_pubkey: String,
+    #[serde(default, alias = "input_type")]
+    pub input_type: Option<String>,
+    #[serde(default, alias = "is_coinbase")]
+    pub is_coinbase: bool,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub frozen: bool,
+    #[serde(default, alias = "must_spend")]
+    pub must_spend: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct TransactionOutput {
-    pub address: String,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
     pub amount: u64,
+    #[serde(default, alias = "is_change")]
+    pub is_change: bool,
+    /// Raw script hex, for outputs with no address such as OP_RETURN. Exactly one of
+    /// `address` and `script_pubkey` is given.
+    #[serde(default, alias = "script_pubkey")]
+    pub script_pubkey: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuiltTransaction {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(alias = "input_order")]
+    pub input_order: Vec<usize>,
+    #[serde(alias = "output_order")]
+    pub output_order: Vec<usize>,
+    #[serde(alias = "change_vout")]
+    pub change_vout: Option<usize>,
+    /// Older name for `change_vout`.
+    #[serde(alias = "change_index")]
+    pub change_index: Option<usize>,
+    pub warnings: Vec<String>,
+}
+
+// The unused fee_sat keeps the original JS signature; every later option trails it.
+#[allow(clippy::too_many_arguments)]
 #[wasm_bindgen]
 pub fn build_transaction(
     inputs_json: &str,
     outputs_json: &str,
     _fee_sat: u64,
+    network: Option<String>,
+    allow_any_network: Option<bool>,
+    version: Option<u32>,
+    ordering: Option<String>,
+    anti_fee_sniping_tip: Option<u32>,
+    checks_json: Option<String>,
 ) -> Result<String, JsValue> {
-    let inputs: Vec<TransactionInput> = serde_json::from_str(inputs_json)
-        .map_err(|e| JsValue::from_str(&format!("Invalid inputs JSON: {}", e)))?;
+    timed("build_transaction", inputs_json.len() + outputs_json.len(), || {
+        let built = build(inputs_json, outputs_json, network, allow_any_network, version, ordering, anti_fee_sniping_tip, checks_json)?;
+        Ok(built.tx_hex)
+    })
+}
+
+/// Same as `build_transaction`, but also reports where each input and output ended up
+/// after ordering, so the change output can be found again (e.g. for a fee bump).
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn build_transaction_detailed(
+    inputs_json: &str,
+    outputs_json: &str,
+    network: Option<String>,
+    allow_any_network: Option<bool>,
+    version: Option<u32>,
+    ordering: Option<String>,
+    anti_fee_sniping_tip: Option<u32>,
+    checks_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    timed("build_transaction_detailed", inputs_json.len() + outputs_json.len(), || {
+        let built = build(inputs_json, outputs_json, network, allow_any_network, version, ordering, anti_fee_sniping_tip, checks_json)?;
+        to_json(&built, compact)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build(
+    inputs_json: &str,
+    outputs_json: &str,
+    network: Option<String>,
+    allow_any_network: Option<bool>,
+    version: Option<u32>,
+    ordering: Option<String>,
+    anti_fee_sniping_tip: Option<u32>,
+    checks_json: Option<String>,
+) -> Result<BuiltTransaction, JsValue> {
+    let version = parse_version(version).map_err(|e| JsValue::from_str(&e))?;
+    let ordering = parse_ordering(ordering.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let required_network = if allow_any_network.unwrap_or(false) { None } else { Some(&network) };
+
+    let inputs: Vec<TransactionInput> = parse_json_list(inputs_json, "inputs").map_err(|e| JsValue::from_str(&e))?;
+
+    let outputs: Vec<TransactionOutput> = parse_json_list(outputs_json, "outputs").map_err(|e| JsValue::from_str(&e))?;
+
+    if outputs.iter().filter(|output| output.is_change).count() > 1 {
+        return Err(JsValue::from_str("At most one output can be marked as change"));
+    }
 
-    let outputs: Vec<TransactionOutput> = serde_json::from_str(outputs_json)
-        .map_err(|e| JsValue::from_str(&format!("Invalid outputs JSON: {}", e)))?;
+    let mut tx = unsigned_transaction(&inputs, &outputs, required_network)?;
+    tx.version = version;
+    if let Some(tip_height) = anti_fee_sniping_tip {
+        set_anti_fee_sniping(&mut tx, tip_height).map_err(|e| JsValue::from_str(&e))?;
+    }
+
+    let change = outputs.iter().position(|output| output.is_change);
+    let (input_order, output_order) = transaction_order(&tx, ordering, change);
+    apply_order(&mut tx, &input_order, &output_order);
+    let inputs: Vec<TransactionInput> = input_order.iter().map(|&i| inputs[i].clone()).collect();
+
+    let (errors, _) = truc_violations(&tx, estimated_signed_vsize(&tx, &inputs), None);
+    if let Some(error) = errors.first() {
+        return Err(JsValue::from_str(error));
+    }
+
+    let change_vout = output_order.iter().position(|&i| outputs[i].is_change);
+    let tx_bytes = finalize(&tx, Funding::Unsigned(&inputs), &checks).map_err(|e| JsValue::from_str(&e))?;
+    Ok(BuiltTransaction {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        input_order,
+        output_order,
+        change_vout,
+        change_index: change_vout,
+        warnings: output_warnings(&tx),
+    })
+}
+
+/// Locks `tx` to `tip_height` (see `anti_fee_sniping_locktime`). A locktime only binds
+/// when some input's sequence isn't final, so final sequences drop to 0xfffffffe, which
+/// leaves RBF signalling as it was.
+pub fn set_anti_fee_sniping(tx: &mut Transaction, tip_height: u32) -> Result<(), String> {
+    let height = locktime::anti_fee_sniping_locktime(tip_height)?;
+    tx.lock_time = bitcoin::absolute::LockTime::from_height(height).map_err(|e| e.to_string())?;
+    for input in &mut tx.input {
+        if input.sequence == bitcoin::Sequence::MAX {
+            input.sequence = bitcoin::Sequence::ENABLE_LOCKTIME_NO_RBF;
+        }
+    }
+    Ok(())
+}
+
+pub fn unsigned_transaction(
+    inputs: &[TransactionInput],
+    outputs: &[TransactionOutput],
+    network: Option<&NetworkParams>,
+) -> Result<Transaction, JsValue> {
+    check_unique_outpoints(inputs)?;
+    check_amount_total(inputs.iter().map(|input| input.amount), "inputs").map_err(|e| JsValue::from_str(&e))?;
+    check_amount_total(outputs.iter().map(|output| output.amount), "outputs").map_err(|e| JsValue::from_str(&e))?;
 
     let mut tx = Transaction {
         version: bitcoin::transaction::Version::TWO,
@@ -39,8 +271,7 @@ pub fn build_transaction(
     };
 
     for input in inputs {
-        let outpoint = OutPoint::from_str(&format!("{}:{}", input.txid, input.vout))
-            .map_err(|e| JsValue::from_str(&format!("Invalid outpoint: {}", e)))?;
+        let outpoint = parse_outpoint(&input.txid, input.vout)?;
 
         let script_pubkey: ScriptBuf = ScriptBuf::from_hex(&input.script_pubkey)
             .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e)))?;
@@ -54,71 +285,241 @@ pub fn build_transaction(
     }
 
     for output in outputs {
-        let amount = Amount::from_sat(output.amount);
-        let address = Address::from_str(&output.address)
-            .map_err(|e| JsValue::from_str(&format!("Invalid address: {}", e)))?
-            .assume_checked();
-
         tx.output.push(TxOut {
-            value: amount,
-            script_pubkey: address.script_pubkey(),
+            value: Amount::from_sat(output.amount),
+            script_pubkey: output_script(output, network)?,
         });
     }
 
-    let tx_bytes = bitcoin::consensus::serialize(&tx);
-    Ok(bytes_to_hex(&tx_bytes))
+    Ok(tx)
 }
 
-#[wasm_bindgen]
-pub fn sign_transaction(
-    tx_hex: &str,
-    private_key_hex: &str,
-    input_index: usize,
-    _script_pubkey_hex: &str,
-    _satoshi_value: u64,
-) -> Result<String, JsValue> {
-    let secp = Secp256k1::new();
+/// Spending an outpoint twice makes the transaction consensus-invalid.
+pub fn check_unique_outpoints(inputs: &[TransactionInput]) -> Result<(), JsValue> {
+    let mut seen: HashMap<(String, u32), usize> = HashMap::new();
+    for (index, input) in inputs.iter().enumerate() {
+        let key = (input.txid.trim().to_ascii_lowercase(), input.vout);
+        if let Some(first) = seen.insert(key, index) {
+            return Err(JsValue::from_str(&format!(
+                "inputs[{}] and inputs[{}] both spend {}:{}",
+                first, index, input.txid, input.vout
+            )));
+        }
+    }
+    Ok(())
+}
 
-    let private_key_bytes: Vec<u8> = hex_to_bytes(private_key_hex)
-        .map_err(|e| JsValue::from_str(&format!("Invalid private key hex: {}", e)))?;
+/// Problems that don't make the transaction invalid but are almost certainly mistakes.
+pub fn output_warnings(tx: &Transaction) -> Vec<String> {
+    tx.output
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| output.value == Amount::ZERO && !output.script_pubkey.is_op_return())
+        .map(|(index, _)| format!("outputs[{}] has a zero amount", index))
+        .collect()
+}
 
-    if private_key_bytes.len() != 32 {
-        return Err(JsValue::from_str("Private key must be 32 bytes"));
+pub fn parse_outpoint(txid: &str, vout: u32) -> Result<OutPoint, JsValue> {
+    let txid = sanitize(txid, InputKind::Hex).map_err(|e| JsValue::from_str(&e))?;
+    let txid = txid.as_ref();
+    if txid.len() != 64 {
+        return Err(JsValue::from_str(&format!(
+            "Invalid outpoint: txid must be 64 hex characters in display (explorer) byte order, got {} characters",
+            txid.len()
+        )));
     }
 
-    let mut key_array = [0u8; 32];
-    key_array.copy_from_slice(&private_key_bytes);
+    OutPoint::from_str(&format!("{}:{}", txid, vout)).map_err(|e| {
+        JsValue::from_str(&format!(
+            "Invalid outpoint: {} (txid is expected in display byte order, as shown by block explorers)",
+            e
+        ))
+    })
+}
 
-    let secret_key = SecretKey::from_slice(&key_array)
-        .map_err(|e| JsValue::from_str(&format!("Invalid secret key: {}", e)))?;
+pub fn address_script(address: &str, network: Option<&NetworkParams>) -> Result<ScriptBuf, JsValue> {
+    require_address_network(address, network).map_err(|e| JsValue::from_str(&e))
+}
 
-    let private_key = PrivateKey::new(secret_key, Network::Testnet);
+pub fn output_script(output: &TransactionOutput, network: Option<&NetworkParams>) -> Result<ScriptBuf, JsValue> {
+    match (&output.address, &output.script_pubkey) {
+        (Some(address), None) => address_script(address, network),
+        (None, Some(script)) => ScriptBuf::from_hex(script.trim())
+            .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e))),
+        _ => Err(JsValue::from_str("An output needs exactly one of address or scriptPubkey")),
+    }
+}
 
-    let tx_bytes: Vec<u8> = hex_to_bytes(tx_hex)
-        .map_err(|e| JsValue::from_str(&format!("Invalid tx hex: {}", e)))?;
+#[wasm_bindgen]
+pub fn calculate_txid(tx_hex: &str) -> Result<String, JsValue> {
+    timed("calculate_txid", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
 
-    let mut tx: Transaction = bitcoin::consensus::deserialize(&tx_bytes)
-        .map_err(|e| JsValue::from_str(&format!("Failed to deserialize tx: {}", e)))?;
+        Ok(tx.compute_txid().to_string())
+    })
+}
 
-    let pub_key_bytes = private_key.public_key(&secp).to_bytes();
-    let sig_bytes = vec![0u8; 64];
-    let mut sig_with_sighash = sig_bytes;
-    sig_with_sighash.push(EcdsaSighashType::All as u8);
+#[derive(Serialize, Deserialize)]
+pub struct TxidEntry {
+    pub index: usize,
+    pub txid: Option<String>,
+    pub wtxid: Option<String>,
+    pub error: Option<String>,
+}
 
-    let witness_items: Vec<Vec<u8>> = vec![sig_with_sighash, pub_key_bytes];
-    tx.input[input_index].witness = Witness::from_slice(&witness_items);
+#[wasm_bindgen]
+pub fn calculate_txids(txs_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("calculate_txids", txs_json.len(), || {
+        let txs = parse_tx_list(txs_json)?;
+        let entries: Vec<TxidEntry> = txs.iter().enumerate().map(|(index, tx_hex)| txid_entry(index, tx_hex)).collect();
 
-    let signed_tx_bytes = bitcoin::consensus::serialize(&tx);
-    Ok(bytes_to_hex(&signed_tx_bytes))
+        to_json(&entries, compact)
+    })
 }
 
+/// `calculate_txids` in steps of up to `max_work_units` transactions; the final step's
+/// `result` is the same list of entries.
 #[wasm_bindgen]
-pub fn calculate_txid(tx_hex: &str) -> Result<String, JsValue> {
-    let tx_bytes: Vec<u8> = hex_to_bytes(tx_hex)
-        .map_err(|e| JsValue::from_str(&format!("Invalid tx hex: {}", e)))?;
+pub struct TxidBatch {
+    txs: Vec<String>,
+    entries: Vec<TxidEntry>,
+    result: Option<Box<RawValue>>,
+}
 
-    let tx: Transaction = bitcoin::consensus::deserialize(&tx_bytes)
-        .map_err(|e| JsValue::from_str(&format!("Failed to deserialize tx: {}", e)))?;
+#[wasm_bindgen]
+impl TxidBatch {
+    #[wasm_bindgen(constructor)]
+    pub fn new(txs_json: &str) -> Result<TxidBatch, JsValue> {
+        let txs = parse_tx_list(txs_json)?;
+        Ok(TxidBatch { entries: Vec::with_capacity(txs.len()), txs, result: None })
+    }
 
-    Ok(tx.compute_txid().to_string())
+    pub fn step(&mut self, max_work_units: u32, compact: Option<bool>) -> Result<String, JsValue> {
+        let units = check_work_units(max_work_units)?;
+        if self.result.is_none() {
+            let end = self.txs.len().min(self.entries.len() + units);
+            for index in self.entries.len()..end {
+                let entry = txid_entry(index, &self.txs[index]);
+                self.entries.push(entry);
+            }
+            if self.entries.len() == self.txs.len() {
+                self.result = Some(to_result(&self.entries)?);
+            }
+        }
+        step_json(Progress::of(self.entries.len(), self.txs.len()), self.result.as_deref(), compact)
+    }
+}
+
+fn parse_tx_list(txs_json: &str) -> Result<Vec<String>, JsValue> {
+    serde_json::from_str(txs_json).map_err(|e| JsValue::from_str(&format!("Invalid transactions JSON: {}", e)))
+}
+
+fn txid_entry(index: usize, tx_hex: &str) -> TxidEntry {
+    match parse_tx_hex(tx_hex) {
+        Ok(tx) => TxidEntry {
+            index,
+            txid: Some(tx.compute_txid().to_string()),
+            wtxid: Some(tx.compute_wtxid().to_string()),
+            error: None,
+        },
+        Err(e) => TxidEntry {
+            index,
+            txid: None,
+            wtxid: None,
+            error: Some(e),
+        },
+    }
+}
+
+pub fn check_input_index(tx: &Transaction, input_index: usize) -> Result<(), JsValue> {
+    require_input_index(tx, input_index).map_err(|e| JsValue::from_str(&e))
+}
+
+pub fn require_input_index(tx: &Transaction, input_index: usize) -> Result<(), String> {
+    if input_index >= tx.input.len() {
+        return Err(format!("input index {} out of range (transaction has {} inputs)", input_index, tx.input.len()));
+    }
+    Ok(())
+}
+
+pub fn check_amount_total<I: IntoIterator<Item = u64>>(amounts: I, what: &str) -> Result<u64, String> {
+    let max_money = Amount::MAX_MONEY.to_sat();
+    let mut total: u64 = 0;
+    for (i, amount) in amounts.into_iter().enumerate() {
+        if amount > max_money {
+            return Err(format!(
+                "{}[{}] amount {} exceeds the maximum of {} sats",
+                what, i, amount, max_money
+            ));
+        }
+        total = total
+            .checked_add(amount)
+            .filter(|total| *total <= max_money)
+            .ok_or_else(|| format!("{} amounts sum to more than {} sats", what, max_money))?;
+    }
+    Ok(total)
+}
+
+/// Parses `ALL`, `NONE` or `SINGLE`, optionally suffixed with `|ANYONECANPAY`.
+pub fn parse_sighash_type(value: &str) -> Result<EcdsaSighashType, String> {
+    match value.trim().to_ascii_uppercase().replace("SIGHASH_", "").as_str() {
+        "ALL" => Ok(EcdsaSighashType::All),
+        "NONE" => Ok(EcdsaSighashType::None),
+        "SINGLE" => Ok(EcdsaSighashType::Single),
+        "ALL|ANYONECANPAY" => Ok(EcdsaSighashType::AllPlusAnyoneCanPay),
+        "NONE|ANYONECANPAY" => Ok(EcdsaSighashType::NonePlusAnyoneCanPay),
+        "SINGLE|ANYONECANPAY" => Ok(EcdsaSighashType::SinglePlusAnyoneCanPay),
+        _ => Err(format!("Unknown sighash type \"{}\" (expected ALL, NONE or SINGLE, optionally with |ANYONECANPAY)", value)),
+    }
+}
+
+pub fn parse_tx_hex(tx_hex: &str) -> Result<Transaction, String> {
+    let tx_bytes: Vec<u8> = hex_to_bytes(tx_hex, HexMode::Lenient)
+        .map_err(|e| format!("Invalid tx hex: {}", e))?;
+
+    bitcoin::consensus::deserialize(&tx_bytes)
+        .map_err(|e| format!("Failed to deserialize tx: {}", e))
+}
+
+pub fn decode_tx_hex(tx_hex: &str) -> Result<Transaction, JsValue> {
+    parse_tx_hex(tx_hex).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_inputs() -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn input_index_is_bounds_checked() {
+        let tx = two_inputs();
+        assert!(require_input_index(&tx, 1).is_ok());
+        assert_eq!(require_input_index(&tx, 2).unwrap_err(), "input index 2 out of range (transaction has 2 inputs)");
+        assert_eq!(require_input_index(&tx, 5).unwrap_err(), "input index 5 out of range (transaction has 2 inputs)");
+    }
+
+    #[test]
+    fn amount_totals_cannot_overflow() {
+        let max_money = Amount::MAX_MONEY.to_sat();
+        assert_eq!(check_amount_total([1, 2, 3], "inputs").unwrap(), 6);
+        assert_eq!(check_amount_total([max_money], "inputs").unwrap(), max_money);
+        assert!(check_amount_total([max_money + 1], "inputs").unwrap_err().starts_with("inputs[0] amount"));
+        assert!(check_amount_total([max_money, 1], "outputs").unwrap_err().contains("sum to more than"));
+        assert!(check_amount_total([u64::MAX, u64::MAX], "utxos").unwrap_err().starts_with("utxos[0]"));
+    }
+
+    #[test]
+    fn truncated_transactions_are_errors() {
+        assert!(parse_tx_hex("").is_err());
+        assert!(parse_tx_hex("02000000").is_err());
+        assert!(parse_tx_hex("0200000001").is_err());
+    }
 }
@@ -0,0 +1,297 @@
+use std::borrow::Borrow;
+use bitcoin::script::{Builder, Instruction, PushBytes};
+use bitcoin::secp256k1::{self, All, Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{PrivateKey, Script, ScriptBuf, Transaction, TxOut, Witness};
+use crate::script::parse_multisig;
+use crate::transaction::sign::InputSignature;
+
+/// Consensus limit on a single push, which caps a legacy P2SH redeem script.
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+/// Where a multisig script sits in the output it spends.
+#[derive(Clone, Copy, PartialEq)]
+enum Wrapping {
+    /// Legacy P2SH: `OP_0 <sigs> <redeemScript>` in the scriptSig.
+    P2sh,
+    /// The P2WSH program in the scriptSig, `<> <sigs> <witnessScript>` in the witness.
+    P2shP2wsh,
+    P2wsh,
+}
+
+/// Adds `private_key`'s signature to a P2SH, P2SH-P2WSH or P2WSH multisig input.
+/// Signatures already on the input are kept and matched to their keys, so cosigners can
+/// sign one after another; the result lists them in script key order, as
+/// OP_CHECKMULTISIG requires, behind the dummy element it pops.
+pub fn sign_multisig_input<T: Borrow<Transaction>>(
+    secp: &Secp256k1<All>,
+    cache: &mut SighashCache<T>,
+    index: usize,
+    prevout: &TxOut,
+    redeem_script: Option<&Script>,
+    witness_script: Option<&Script>,
+    private_key: &PrivateKey,
+) -> Result<InputSignature, String> {
+    let (wrapping, script) = locate_script(&prevout.script_pubkey, redeem_script, witness_script)?;
+    let multisig = parse_multisig(script).ok_or(
+        "only multisig scripts (<m> <keys> <n> OP_CHECKMULTISIG) can be signed this way; use sign_p2wsh_input for other witness scripts",
+    )?;
+    let keys = multisig
+        .pubkeys
+        .iter()
+        .map(|key| secp256k1::PublicKey::from_slice(key))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("multisig script has an invalid public key: {}", e))?;
+
+    let own_key = private_key.inner.public_key(secp);
+    let own_slot = keys.iter().position(|key| *key == own_key).ok_or("private key is not one of the multisig keys")?;
+
+    let mut slots: Vec<Option<Vec<u8>>> = vec![None; keys.len()];
+    let sighash_of = |cache: &mut SighashCache<T>, sighash_type: EcdsaSighashType| -> Result<Message, String> {
+        let sighash = match wrapping {
+            Wrapping::P2sh => cache.legacy_signature_hash(index, script, sighash_type.to_u32()).map(Message::from).map_err(|e| e.to_string()),
+            _ => cache.p2wsh_signature_hash(index, script, prevout.value, sighash_type).map(Message::from).map_err(|e| e.to_string()),
+        };
+        sighash.map_err(|e| format!("sighash error: {}", e))
+    };
+
+    let tx = cache.transaction();
+    let script_sig = &tx.input[index].script_sig;
+    // build_transaction leaves the prevout's scriptPubKey in the scriptSig as a placeholder.
+    let script_sig = if *script_sig == prevout.script_pubkey { Script::new() } else { script_sig.as_script() };
+    let existing = existing_signatures(wrapping, script_sig, &tx.input[index].witness, script)?;
+    for (position, bytes) in existing.into_iter().enumerate() {
+        let signature = bitcoin::ecdsa::Signature::from_slice(&bytes)
+            .map_err(|e| format!("existing signature {} doesn't parse: {}", position, e))?;
+        let message = sighash_of(cache, signature.sighash_type)?;
+        // Older signers didn't always produce low-S signatures; they're still valid in P2SH.
+        let mut normalized = signature.signature;
+        normalized.normalize_s();
+        let slot = keys
+            .iter()
+            .position(|key| secp.verify_ecdsa(&message, &normalized, key).is_ok())
+            .ok_or_else(|| format!("existing signature {} doesn't verify against any key in the script", position))?;
+        slots[slot] = Some(bytes);
+    }
+
+    let message = sighash_of(cache, EcdsaSighashType::All)?;
+    let signature = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa_low_r(&message, &private_key.inner));
+    slots[own_slot] = Some(signature.to_vec());
+
+    // CHECKMULTISIG takes exactly m signatures; more would be left on the stack.
+    let signatures: Vec<Vec<u8>> = slots.into_iter().flatten().take(multisig.threshold).collect();
+    assemble(wrapping, script, signatures)
+}
+
+/// The multisig script the output commits to, checked against its hash.
+fn locate_script<'a>(
+    script_pubkey: &Script,
+    redeem_script: Option<&'a Script>,
+    witness_script: Option<&'a Script>,
+) -> Result<(Wrapping, &'a Script), String> {
+    if script_pubkey.is_p2wsh() {
+        if redeem_script.is_some() {
+            return Err("a native P2WSH output takes witness_script_hex, not redeem_script_hex".to_string());
+        }
+        let witness_script = witness_script.ok_or("P2WSH inputs need witness_script_hex")?;
+        if *script_pubkey != ScriptBuf::new_p2wsh(&witness_script.wscript_hash()) {
+            return Err("witness script does not hash to the P2WSH output".to_string());
+        }
+        return Ok((Wrapping::P2wsh, witness_script));
+    }
+    if !script_pubkey.is_p2sh() {
+        return Err("redeem_script_hex and witness_script_hex only apply to P2SH and P2WSH outputs".to_string());
+    }
+
+    match (redeem_script, witness_script) {
+        (_, Some(witness_script)) => {
+            let program = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+            if redeem_script.is_some_and(|redeem_script| *redeem_script != program) {
+                return Err("redeem script is not the P2WSH program of the witness script".to_string());
+            }
+            if *script_pubkey != ScriptBuf::new_p2sh(&program.script_hash()) {
+                return Err("witness script does not hash to the P2SH-P2WSH output".to_string());
+            }
+            Ok((Wrapping::P2shP2wsh, witness_script))
+        }
+        (Some(redeem_script), None) => {
+            if *script_pubkey != ScriptBuf::new_p2sh(&redeem_script.script_hash()) {
+                return Err("redeem script does not hash to the P2SH output".to_string());
+            }
+            if redeem_script.is_p2wsh() {
+                return Err("redeem script is a P2WSH program; pass the witness script as witness_script_hex".to_string());
+            }
+            if redeem_script.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(format!(
+                    "redeem script is {} bytes; a P2SH scriptSig can only push {}",
+                    redeem_script.len(),
+                    MAX_SCRIPT_ELEMENT_SIZE
+                ));
+            }
+            Ok((Wrapping::P2sh, redeem_script))
+        }
+        (None, None) => Err("P2SH multisig inputs need redeem_script_hex".to_string()),
+    }
+}
+
+/// Signatures from an earlier partial signing of the same script. Empty placeholders,
+/// which some wallets leave for missing signatures, are skipped.
+fn existing_signatures(wrapping: Wrapping, script_sig: &Script, witness: &Witness, script: &Script) -> Result<Vec<Vec<u8>>, String> {
+    let items: Vec<Vec<u8>> = match wrapping {
+        Wrapping::P2sh => {
+            if script_sig.is_empty() {
+                return Ok(Vec::new());
+            }
+            script_sig
+                .instructions()
+                .map(|instruction| match instruction {
+                    Ok(Instruction::PushBytes(push)) => Ok(push.as_bytes().to_vec()),
+                    _ => Err("input's scriptSig isn't a partial multisig spend; clear it to sign afresh".to_string()),
+                })
+                .collect::<Result<_, _>>()?
+        }
+        _ => {
+            if witness.is_empty() {
+                return Ok(Vec::new());
+            }
+            witness.iter().map(<[u8]>::to_vec).collect()
+        }
+    };
+
+    match items.split_last() {
+        Some((last, rest)) if last.as_slice() == script.as_bytes() && rest.first().is_some_and(Vec::is_empty) => {
+            Ok(rest[1..].iter().filter(|item| !item.is_empty()).cloned().collect())
+        }
+        _ => Err("input already carries a spend of a different script; clear it to sign afresh".to_string()),
+    }
+}
+
+fn assemble(wrapping: Wrapping, script: &Script, signatures: Vec<Vec<u8>>) -> Result<InputSignature, String> {
+    let push = |bytes: &[u8]| <&PushBytes>::try_from(bytes).map(|push| push.to_owned()).map_err(|e| e.to_string());
+    if wrapping == Wrapping::P2sh {
+        let mut builder = Builder::new().push_int(0);
+        for signature in &signatures {
+            builder = builder.push_slice(push(signature)?);
+        }
+        let script_sig = builder.push_slice(push(script.as_bytes())?).into_script();
+        return Ok(InputSignature { script_sig, witness: Witness::default() });
+    }
+
+    let mut items = Vec::with_capacity(signatures.len() + 2);
+    items.push(Vec::new());
+    items.extend(signatures);
+    items.push(script.to_bytes());
+    let script_sig = match wrapping {
+        Wrapping::P2shP2wsh => Builder::new().push_slice(push(ScriptBuf::new_p2wsh(&script.wscript_hash()).as_bytes())?).into_script(),
+        _ => ScriptBuf::new(),
+    };
+    Ok(InputSignature { script_sig, witness: Witness::from_slice(&items) })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::opcodes::all::OP_CHECKMULTISIG;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, TxIn, Txid};
+    use crate::script::interpreter::verify_input;
+    use crate::transaction::parse_tx_hex;
+    use crate::transaction::sign::sign_transaction;
+    use crate::utils::bytes_to_hex;
+
+    const AMOUNT: u64 = 100_000;
+
+    fn private_key(byte: u8) -> String {
+        bytes_to_hex(&[byte; 32])
+    }
+
+    fn two_of_three() -> ScriptBuf {
+        let secp = Secp256k1::new();
+        let mut builder = Builder::new().push_int(2);
+        for byte in 1..=3 {
+            let public = SecretKey::from_slice(&[byte; 32]).unwrap().public_key(&secp);
+            builder = builder.push_slice(public.serialize());
+        }
+        builder.push_int(3).push_opcode(OP_CHECKMULTISIG).into_script()
+    }
+
+    fn unsigned() -> String {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::from_byte_array([5; 32]), 0), ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(99_000), script_pubkey: ScriptBuf::new_op_return([2u8; 4]) }],
+        };
+        bitcoin::consensus::encode::serialize_hex(&tx)
+    }
+
+    /// Signs with keys 3 and then 1, so the second signer has to put its signature first.
+    fn cosign(script_pubkey: &Script, redeem_script: Option<&Script>, witness_script: Option<&Script>) -> (Transaction, Transaction) {
+        let sign = |tx_hex: &str, key: u8| {
+            sign_transaction(
+                tx_hex,
+                &private_key(key),
+                0,
+                &script_pubkey.to_hex_string(),
+                AMOUNT,
+                redeem_script.map(Script::to_hex_string),
+                witness_script.map(Script::to_hex_string),
+            )
+            .unwrap()
+        };
+        let partial = sign(&unsigned(), 3);
+        let complete = sign(&partial, 1);
+        (parse_tx_hex(&partial).unwrap(), parse_tx_hex(&complete).unwrap())
+    }
+
+    fn assert_verifies(tx: &Transaction, script_pubkey: &Script) {
+        let prevouts = [TxOut { value: Amount::from_sat(AMOUNT), script_pubkey: script_pubkey.to_owned() }];
+        if let Err(failure) = verify_input(tx, 0, &prevouts) {
+            panic!("fails in {} at {:?}: {}", failure.phase, failure.opcode, failure.reason);
+        }
+    }
+
+    fn assert_rejected(tx: &Transaction, script_pubkey: &Script) {
+        let prevouts = [TxOut { value: Amount::from_sat(AMOUNT), script_pubkey: script_pubkey.to_owned() }];
+        assert!(verify_input(tx, 0, &prevouts).is_err());
+    }
+
+    #[test]
+    fn p2sh_multisig_verifies() {
+        let redeem_script = two_of_three();
+        let script_pubkey = ScriptBuf::new_p2sh(&redeem_script.script_hash());
+        let (partial, complete) = cosign(&script_pubkey, Some(&redeem_script), None);
+        assert_rejected(&partial, &script_pubkey);
+        assert!(complete.input[0].witness.is_empty());
+        assert_verifies(&complete, &script_pubkey);
+    }
+
+    #[test]
+    fn p2sh_p2wsh_multisig_verifies() {
+        let witness_script = two_of_three();
+        let program = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let script_pubkey = ScriptBuf::new_p2sh(&program.script_hash());
+        let (partial, complete) = cosign(&script_pubkey, None, Some(&witness_script));
+        assert_rejected(&partial, &script_pubkey);
+        assert_eq!(complete.input[0].witness.len(), 4);
+        assert_verifies(&complete, &script_pubkey);
+
+        // The redeem script may be given as well, as long as it's the program.
+        let (_, with_redeem) = cosign(&script_pubkey, Some(&program), Some(&witness_script));
+        assert_verifies(&with_redeem, &script_pubkey);
+    }
+
+    #[test]
+    fn p2wsh_multisig_verifies() {
+        let witness_script = two_of_three();
+        let script_pubkey = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let (partial, complete) = cosign(&script_pubkey, None, Some(&witness_script));
+        assert_rejected(&partial, &script_pubkey);
+        assert!(complete.input[0].script_sig.is_empty());
+        assert_verifies(&complete, &script_pubkey);
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::script::PushBytes;
+use bitcoin::secp256k1::{self, Message, Secp256k1};
+use bitcoin::sighash::SighashCache;
+use bitcoin::{Amount, ScriptBuf, TxOut, Witness};
+use crate::script::interpreter::signature_checks;
+use crate::script::parse_multisig;
+use crate::transaction::serialization::checked_serialize;
+use crate::transaction::{check_input_index, decode_tx_hex};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WitnessSignature {
+    /// Position in the witness stack.
+    pub position: usize,
+    pub signature: String,
+    #[serde(alias = "sighash_type")]
+    pub sighash_type: Option<String>,
+    /// Index of the script key it verifies against.
+    #[serde(alias = "key_index")]
+    pub key_index: Option<usize>,
+    pub pubkey: Option<String>,
+    /// Valid, but non-standard: relay policy requires low S.
+    #[serde(alias = "high_s")]
+    pub high_s: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultisigWitnessDiagnosis {
+    pub threshold: usize,
+    pub pubkeys: Vec<String>,
+    /// Whether the input verifies as it is, and the interpreter's reason if it doesn't.
+    pub valid: bool,
+    pub error: Option<String>,
+    /// The empty element OP_CHECKMULTISIG pops beyond its arguments.
+    #[serde(alias = "dummy_present")]
+    pub dummy_present: bool,
+    #[serde(alias = "witness_script_present")]
+    pub witness_script_present: bool,
+    pub signatures: Vec<WitnessSignature>,
+    /// Whether the matched signatures appear in script key order.
+    #[serde(alias = "order_consistent")]
+    pub order_consistent: bool,
+    /// Distinct keys with a valid signature.
+    #[serde(alias = "keys_signed")]
+    pub keys_signed: Vec<usize>,
+    #[serde(alias = "enough_signatures")]
+    pub enough_signatures: bool,
+    pub problems: Vec<String>,
+    /// `<> <sigs in key order> <witnessScript>`, consensus-serialized. None without
+    /// enough valid signatures.
+    #[serde(alias = "corrected_witness")]
+    pub corrected_witness: Option<String>,
+    #[serde(alias = "corrected_tx_hex")]
+    pub corrected_tx_hex: Option<String>,
+    #[serde(alias = "corrected_valid")]
+    pub corrected_valid: Option<bool>,
+}
+
+/// Works out why a P2WSH or P2SH-P2WSH CHECKMULTISIG witness fails: every signature is
+/// checked against every key in `witness_script_hex`, which shows signatures out of key
+/// order, a missing dummy element, duplicates and signatures for another sighash. When
+/// enough signatures verify, the witness is rebuilt in the order the script expects and
+/// re-verified.
+#[wasm_bindgen]
+pub fn diagnose_multisig_witness(tx_hex: &str, input_index: usize, prevout_amount: u64, witness_script_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::verification_only();
+    let tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let script_bytes = hex_to_bytes(witness_script_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid witness script: {}", e)))?;
+    let script = ScriptBuf::from_bytes(script_bytes);
+    let multisig = parse_multisig(&script)
+        .ok_or_else(|| JsValue::from_str("The witness script isn't <m> <keys> <n> OP_CHECKMULTISIG"))?;
+    let keys: Vec<Option<secp256k1::PublicKey>> = multisig.pubkeys.iter().map(|key| secp256k1::PublicKey::from_slice(key).ok()).collect();
+
+    let input = &tx.input[input_index];
+    let program = ScriptBuf::new_p2wsh(&script.wscript_hash());
+    // A P2SH-P2WSH scriptSig is a single push of the P2WSH program.
+    let nested = !input.script_sig.is_empty();
+    let script_pubkey = if nested { ScriptBuf::new_p2sh(&program.script_hash()) } else { program.clone() };
+    let mut problems = Vec::new();
+    if nested && input.script_sig.as_bytes().get(1..) != Some(program.as_bytes()) {
+        problems.push("the scriptSig isn't a push of this witness script's P2WSH program".to_string());
+    }
+    for (index, key) in keys.iter().enumerate() {
+        if key.is_none() {
+            problems.push(format!("script key {} isn't a valid public key", index));
+        }
+    }
+
+    let items: Vec<Vec<u8>> = input.witness.iter().map(<[u8]>::to_vec).collect();
+    let witness_script_present = items.last().is_some_and(|last| *last == script.to_bytes());
+    let mut elements = &items[..];
+    if witness_script_present {
+        elements = &elements[..elements.len() - 1];
+    } else {
+        problems.push("the last witness element isn't the witness script".to_string());
+    }
+    let dummy_present = elements.first().is_some_and(Vec::is_empty);
+    if dummy_present {
+        elements = &elements[1..];
+    } else {
+        problems.push("the dummy element is missing: the witness must start with an empty item".to_string());
+    }
+
+    let mut cache = SighashCache::new(&tx);
+    let mut signatures = Vec::new();
+    for (offset, bytes) in elements.iter().enumerate() {
+        let mut entry = WitnessSignature {
+            position: usize::from(dummy_present) + offset,
+            signature: bytes_to_hex(bytes),
+            sighash_type: None,
+            key_index: None,
+            pubkey: None,
+            high_s: false,
+            error: None,
+        };
+        if bytes.is_empty() {
+            entry.error = Some("empty placeholder; OP_CHECKMULTISIG takes exactly m signatures".to_string());
+            signatures.push(entry);
+            continue;
+        }
+        let signature = match bitcoin::ecdsa::Signature::from_slice(bytes) {
+            Ok(signature) => signature,
+            Err(e) => {
+                entry.error = Some(format!("not a DER signature with a sighash byte: {}", e));
+                signatures.push(entry);
+                continue;
+            }
+        };
+        entry.sighash_type = Some(signature.sighash_type.to_string());
+        let message = cache
+            .p2wsh_signature_hash(input_index, &script, Amount::from_sat(prevout_amount), signature.sighash_type)
+            .map(Message::from)
+            .map_err(|e| JsValue::from_str(&format!("sighash error: {}", e)))?;
+        let mut normalized = signature.signature;
+        normalized.normalize_s();
+        entry.high_s = normalized != signature.signature;
+        entry.key_index = keys.iter().position(|key| key.is_some_and(|key| secp.verify_ecdsa(&message, &normalized, &key).is_ok()));
+        match entry.key_index {
+            Some(index) => entry.pubkey = Some(bytes_to_hex(&multisig.pubkeys[index])),
+            None => entry.error = Some("doesn't verify against any script key (wrong sighash, amount or transaction?)".to_string()),
+        }
+        signatures.push(entry);
+    }
+
+    let matched: Vec<usize> = signatures.iter().filter_map(|signature| signature.key_index).collect();
+    let order_consistent = matched.windows(2).all(|pair| pair[0] < pair[1]);
+    if !order_consistent {
+        problems.push(format!("signatures are in key order {:?}; OP_CHECKMULTISIG needs them ascending", matched));
+    }
+    let mut keys_signed = matched.clone();
+    keys_signed.sort_unstable();
+    keys_signed.dedup();
+    if keys_signed.len() < matched.len() {
+        problems.push("more than one signature from the same key".to_string());
+    }
+    let enough_signatures = keys_signed.len() >= multisig.threshold;
+    if !enough_signatures {
+        problems.push(format!("{} of {} required signatures verify", keys_signed.len(), multisig.threshold));
+    }
+    if elements.len() != multisig.threshold {
+        problems.push(format!("the witness has {} signature elements; the script takes exactly {}", elements.len(), multisig.threshold));
+    }
+    if signatures.iter().any(|signature| signature.high_s) {
+        problems.push("a high-S signature is valid but won't relay".to_string());
+    }
+
+    let mut prevouts = vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new() }; tx.input.len()];
+    prevouts[input_index] = TxOut { value: Amount::from_sat(prevout_amount), script_pubkey };
+    let (_, outcome) = signature_checks(&tx, input_index, &prevouts);
+
+    let mut corrected_witness = None;
+    let mut corrected_tx_hex = None;
+    let mut corrected_valid = None;
+    if enough_signatures {
+        let mut stack: Vec<Vec<u8>> = vec![Vec::new()];
+        for key in keys_signed.iter().take(multisig.threshold) {
+            let position = signatures.iter().position(|signature| signature.key_index == Some(*key)).expect("matched key");
+            stack.push(elements[position].clone());
+        }
+        stack.push(script.to_bytes());
+        let witness = Witness::from_slice(&stack);
+        let mut corrected = tx.clone();
+        corrected.input[input_index].witness = witness.clone();
+        if nested {
+            let push = <&PushBytes>::try_from(program.as_bytes()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+            corrected.input[input_index].script_sig = bitcoin::script::Builder::new().push_slice(push).into_script();
+        }
+        corrected_valid = Some(signature_checks(&corrected, input_index, &prevouts).1.is_ok());
+        corrected_witness = Some(bytes_to_hex(&bitcoin::consensus::serialize(&witness)));
+        corrected_tx_hex = Some(bytes_to_hex(&checked_serialize(&corrected).map_err(|e| JsValue::from_str(&e))?));
+    }
+
+    let diagnosis = MultisigWitnessDiagnosis {
+        threshold: multisig.threshold,
+        pubkeys: multisig.pubkeys.iter().map(|key| bytes_to_hex(key)).collect(),
+        valid: outcome.is_ok(),
+        error: outcome.err().map(|failure| format!("{}: {}", failure.phase, failure.reason)),
+        dummy_present,
+        witness_script_present,
+        signatures,
+        order_consistent,
+        keys_signed,
+        enough_signatures,
+        problems,
+        corrected_witness,
+        corrected_tx_hex,
+        corrected_valid,
+    };
+    to_json(&diagnosis, compact)
+}
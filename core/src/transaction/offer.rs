@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::key::{Keypair, TapTweak};
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::{Amount, PrivateKey, ScriptBuf, Transaction, TxOut};
+use crate::script::interpreter::verify_inputs;
+use crate::transaction::crowdfund::sighash_flag;
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::sign::{apply_signature, key_spend, key_spend_signature, sign_input, KeySignature, KeySpend};
+use crate::transaction::{check_amount_total, decode_tx_hex, unsigned_transaction, TransactionInput, TransactionOutput};
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, parse_json_value, to_json_with_amounts};
+use crate::utils::network::network_or_default;
+use crate::utils::units::FeeRate;
+use crate::wallet::parse_private_key;
+
+const SIGHASH_SINGLE_ANYONECANPAY: u8 = 0x83;
+/// Below this the filled transaction wouldn't relay (Core's default `-minrelaytxfee`).
+const MIN_FEE_RATE: f64 = 1.0;
+
+/// The maker's half of a trade: a transaction spending only their coin to the one
+/// output they ask for, which isn't valid until a taker pays for that output.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Offer {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    /// The offered coin, needed to check the signature once filled.
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    /// What the maker asks to be paid.
+    #[serde(with = "crate::utils::amount", alias = "ask_amount")]
+    pub ask_amount: u64,
+    #[serde(alias = "ask_script_pubkey")]
+    pub ask_script_pubkey: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilledOffer {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    pub txid: String,
+    /// Where the maker's input and the ask output sit. They always share an index, as
+    /// SIGHASH_SINGLE pairs input i with output i.
+    #[serde(alias = "maker_index")]
+    pub maker_index: usize,
+    #[serde(alias = "taker_inputs")]
+    pub taker_inputs: usize,
+    #[serde(alias = "taker_outputs")]
+    pub taker_outputs: usize,
+    pub fee: u64,
+    pub vsize: u64,
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: FeeRate,
+    pub warnings: Vec<String>,
+}
+
+/// Signs `utxo_json` (an input as `build_transaction` takes it) with
+/// SIGHASH_SINGLE|ANYONECANPAY towards `ask_output_json`: the signature commits to this
+/// input and the output at its own index only, so a taker can add their inputs and
+/// outputs around it without invalidating it.
+#[wasm_bindgen]
+pub fn create_offer(
+    utxo_json: &str,
+    ask_output_json: &str,
+    private_key: &str,
+    network: Option<String>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let utxo: TransactionInput = parse_json_value(utxo_json, "UTXO").map_err(|e| JsValue::from_str(&e))?;
+    let ask: TransactionOutput = parse_json_value(ask_output_json, "ask output").map_err(|e| JsValue::from_str(&e))?;
+    let private_key = parse_private_key(private_key, None, network.network)?;
+
+    let mut tx = unsigned_transaction(std::slice::from_ref(&utxo), std::slice::from_ref(&ask), Some(&network))?;
+    let prevout = TxOut {
+        value: Amount::from_sat(utxo.amount),
+        script_pubkey: std::mem::take(&mut tx.input[0].script_sig),
+    };
+
+    let secp = Secp256k1::new();
+    let secret_key = private_key.inner;
+    let spend = key_spend(&secp, &prevout.script_pubkey, &secret_key.public_key(&secp), private_key.compressed, "private key")
+        .map_err(|e| JsValue::from_str(&e))?;
+    let sighash = single_anyonecanpay_sighash(&tx, &prevout, &spend).map_err(|e| JsValue::from_str(&format!("Failed to sign offer: {}", e)))?;
+    let signature = match spend {
+        KeySpend::P2tr(_) => {
+            let tweaked = Keypair::from_secret_key(&secp, &secret_key).tap_tweak(&secp, None);
+            KeySignature::Schnorr(bitcoin::taproot::Signature {
+                signature: secp.sign_schnorr_no_aux_rand(&sighash, &tweaked.to_keypair()),
+                sighash_type: TapSighashType::SinglePlusAnyoneCanPay,
+            })
+        }
+        _ => KeySignature::Ecdsa(bitcoin::ecdsa::Signature {
+            signature: secp.sign_ecdsa_low_r(&sighash, &secret_key),
+            sighash_type: EcdsaSighashType::SinglePlusAnyoneCanPay,
+        }),
+    };
+    let signature = key_spend_signature(&spend, signature).map_err(|e| JsValue::from_str(&format!("Failed to sign offer: {}", e)))?;
+    apply_signature(&mut tx, 0, signature);
+    // Only the taker's inputs give the offer a fee, so there is none to limit yet.
+    let tx_bytes = finalize(&tx, Funding::Unknown, &checks).map_err(|e| JsValue::from_str(&e))?;
+
+    let offer = Offer {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        amount: utxo.amount,
+        script_pubkey: prevout.script_pubkey.to_hex_string(),
+        ask_amount: ask.amount,
+        ask_script_pubkey: tx.output[0].script_pubkey.to_hex_string(),
+    };
+    to_json_with_amounts(&offer, amount_format, compact)
+}
+
+/// Completes an offer from `create_offer`. The maker's input and the ask output stay at
+/// index 0, where SIGHASH_SINGLE needs them paired; the taker's inputs and outputs go
+/// after them. Each taker input is signed SIGHASH_ALL with whichever of `taker_keys_json`
+/// matches it, and every signature, the maker's included, is verified in the combined
+/// transaction before it is returned. Whatever the outputs don't take goes to the fee.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn fill_offer(
+    offer_json: &str,
+    taker_inputs_json: &str,
+    taker_outputs_json: &str,
+    taker_keys_json: &str,
+    network: Option<String>,
+    checks_json: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let checks = BuildChecks::parse(checks_json.as_deref(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    let offer: Offer = parse_json_value(offer_json, "offer").map_err(|e| {
+        JsValue::from_str(&format!("{} (fill_offer takes create_offer's result, not just its txHex)", e))
+    })?;
+    let taker_inputs: Vec<TransactionInput> = parse_json_list(taker_inputs_json, "taker inputs").map_err(|e| JsValue::from_str(&e))?;
+    let taker_outputs: Vec<TransactionOutput> =
+        parse_json_list(taker_outputs_json, "taker outputs").map_err(|e| JsValue::from_str(&e))?;
+    let key_strings: Vec<String> = parse_json_list(taker_keys_json, "taker keys").map_err(|e| JsValue::from_str(&e))?;
+    let taker_keys = key_strings
+        .iter()
+        .map(|key| parse_private_key(key, None, network.network))
+        .collect::<Result<Vec<PrivateKey>, JsValue>>()?;
+    if taker_inputs.is_empty() {
+        return Err(JsValue::from_str("The taker needs at least one input to pay for the ask"));
+    }
+
+    let offer_tx = decode_tx_hex(&offer.tx_hex).map_err(|_| JsValue::from_str("Offer: invalid transaction hex"))?;
+    let maker_script = ScriptBuf::from_hex(&offer.script_pubkey).map_err(|e| JsValue::from_str(&format!("Offer: invalid script pubkey: {}", e)))?;
+    let maker_prevout = TxOut { value: Amount::from_sat(offer.amount), script_pubkey: maker_script };
+    check_offer(&offer_tx, &maker_prevout)?;
+
+    let taker_tx = unsigned_transaction(&taker_inputs, &taker_outputs, Some(&network))?;
+    if let Some(index) = taker_tx.input.iter().position(|input| input.previous_output == offer_tx.input[0].previous_output) {
+        return Err(JsValue::from_str(&format!(
+            "Taker input {} spends {}, the coin the maker is offering",
+            index, offer_tx.input[0].previous_output
+        )));
+    }
+
+    // Version and lock time are under the maker's signature, so the offer's are kept.
+    let mut tx = Transaction {
+        version: offer_tx.version,
+        lock_time: offer_tx.lock_time,
+        input: offer_tx.input.clone(),
+        output: offer_tx.output.clone(),
+    };
+    let mut prevouts = vec![maker_prevout];
+    for (input, mut txin) in taker_inputs.iter().zip(taker_tx.input) {
+        prevouts.push(TxOut { value: Amount::from_sat(input.amount), script_pubkey: std::mem::take(&mut txin.script_sig) });
+        tx.input.push(txin);
+    }
+    tx.output.extend(taker_tx.output);
+    check_alignment(&tx, &offer_tx)?;
+
+    let total_in = check_amount_total(prevouts.iter().map(|prevout| prevout.value.to_sat()), "inputs").map_err(|e| JsValue::from_str(&e))?;
+    let total_out = check_amount_total(tx.output.iter().map(|output| output.value.to_sat()), "outputs").map_err(|e| JsValue::from_str(&e))?;
+    if total_out > total_in {
+        return Err(JsValue::from_str(&format!(
+            "The outputs total {} sats but the maker's coin and the taker's inputs only bring {}",
+            total_out, total_in
+        )));
+    }
+
+    let secp = Secp256k1::new();
+    let mut signatures = Vec::with_capacity(taker_inputs.len());
+    {
+        let mut cache = SighashCache::new(&tx);
+        for index in 1..tx.input.len() {
+            let signature = taker_keys
+                .iter()
+                .find_map(|key| sign_input(&secp, &mut cache, index, &prevouts[index], &prevouts, key).ok())
+                .ok_or_else(|| JsValue::from_str(&format!("None of the taker keys can sign taker input {}", index - 1)))?;
+            signatures.push((index, signature));
+        }
+    }
+    for (index, signature) in signatures {
+        apply_signature(&mut tx, index, signature);
+    }
+
+    if let Err((index, failure)) = verify_inputs(&tx, &prevouts) {
+        return Err(JsValue::from_str(&if index == 0 {
+            format!("The maker's signature doesn't validate in the filled transaction: {}", failure.reason)
+        } else {
+            format!("Taker input {}'s signature doesn't validate: {}", index - 1, failure.reason)
+        }));
+    }
+    let tx_bytes = finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?;
+
+    let fee = total_in - total_out;
+    let vsize = tx.vsize() as u64;
+    let fee_rate = FeeRate::from_fee(Amount::from_sat(fee), vsize as f64);
+    let mut warnings = Vec::new();
+    // Only reachable when the checks lowered minFeeRateSatVb.
+    if fee_rate.sat_per_vb() < MIN_FEE_RATE {
+        warnings.push(format!("Fee rate {:.2} sat/vB is below the default minimum relay fee of {} sat/vB", fee_rate.sat_per_vb(), MIN_FEE_RATE));
+    }
+
+    let filled = FilledOffer {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        txid: tx.compute_txid().to_string(),
+        maker_index: 0,
+        taker_inputs: taker_inputs.len(),
+        taker_outputs: taker_outputs.len(),
+        fee,
+        vsize,
+        fee_rate,
+        warnings,
+    };
+    to_json_with_amounts(&filled, amount_format, compact)
+}
+
+/// The SIGHASH_SINGLE|ANYONECANPAY message for input 0 of `tx`.
+fn single_anyonecanpay_sighash(tx: &Transaction, prevout: &TxOut, spend: &KeySpend) -> Result<Message, String> {
+    let mut cache = SighashCache::new(tx);
+    let sighash_type = EcdsaSighashType::SinglePlusAnyoneCanPay;
+    let sighash_error = |e: &dyn std::fmt::Display| format!("sighash error: {}", e);
+    match spend {
+        KeySpend::P2wpkh(_) => cache
+            .p2wpkh_signature_hash(0, &prevout.script_pubkey, prevout.value, sighash_type)
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+        KeySpend::P2shP2wpkh(pubkey) => cache
+            .p2wpkh_signature_hash(0, &ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()), prevout.value, sighash_type)
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+        KeySpend::P2pkh(_) => cache
+            .legacy_signature_hash(0, &prevout.script_pubkey, sighash_type.to_u32())
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+        KeySpend::P2tr(_) => cache
+            .taproot_key_spend_signature_hash(0, &Prevouts::One(0, prevout.clone()), TapSighashType::SinglePlusAnyoneCanPay)
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+    }
+}
+
+/// An offer is one input paired with one output, signed SINGLE|ANYONECANPAY.
+fn check_offer(offer_tx: &Transaction, maker_prevout: &TxOut) -> Result<(), JsValue> {
+    if offer_tx.input.len() != 1 || offer_tx.output.len() != 1 {
+        return Err(JsValue::from_str(&format!(
+            "Offer has {} inputs and {} outputs; an offer pairs exactly one of each",
+            offer_tx.input.len(),
+            offer_tx.output.len()
+        )));
+    }
+    let input = &offer_tx.input[0];
+    match sighash_flag(&input.script_sig, &input.witness, &maker_prevout.script_pubkey) {
+        Some(SIGHASH_SINGLE_ANYONECANPAY) => Ok(()),
+        Some(found) => Err(JsValue::from_str(&format!(
+            "Offer is signed with sighash type 0x{:02x}; offers need SIGHASH_SINGLE|ANYONECANPAY (0x83), otherwise the taker's inputs or outputs invalidate it",
+            found
+        ))),
+        None => Err(JsValue::from_str("Offer input isn't signed")),
+    }
+}
+
+/// SIGHASH_SINGLE signs the output at the input's own index, so the ask must still sit
+/// at the maker input's index. Anything else leaves a signature over the wrong output,
+/// or, for a legacy input past the last output, over the constant 1.
+fn check_alignment(tx: &Transaction, offer_tx: &Transaction) -> Result<(), JsValue> {
+    let maker_outpoint = offer_tx.input[0].previous_output;
+    let maker_index = tx
+        .input
+        .iter()
+        .position(|input| input.previous_output == maker_outpoint)
+        .ok_or_else(|| JsValue::from_str("The maker's input is missing from the filled transaction"))?;
+    match tx.output.get(maker_index) {
+        Some(output) if *output == offer_tx.output[0] => Ok(()),
+        Some(_) => Err(JsValue::from_str(&format!(
+            "Output {} isn't the maker's ask, but SIGHASH_SINGLE pairs the maker's input {} with it",
+            maker_index, maker_index
+        ))),
+        None => Err(JsValue::from_str(&format!(
+            "The maker's input is at index {} but there is no output {} for SIGHASH_SINGLE to pair it with",
+            maker_index, maker_index
+        ))),
+    }
+}
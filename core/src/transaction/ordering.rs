@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+#[cfg(feature = "wallet")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "wallet")]
+use rand::Rng;
+use bitcoin::Transaction;
+use crate::transaction::decode_tx_hex;
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::utils::bytes_to_hex;
+#[cfg(feature = "wallet")]
+use crate::utils::rng::with_rng;
+use crate::utils::json::to_json;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TxOrdering {
+    AsGiven,
+    Bip69,
+    #[cfg(feature = "wallet")]
+    Shuffle,
+    /// Inputs and recipients as given, with the change output moved to a random position.
+    #[cfg(feature = "wallet")]
+    RandomChange,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedTransaction {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(alias = "input_order")]
+    pub input_order: Vec<usize>,
+    #[serde(alias = "output_order")]
+    pub output_order: Vec<usize>,
+}
+
+/// Reorders an unsigned transaction per BIP69. Signing has to happen afterwards,
+/// since moving inputs or outputs invalidates existing signatures.
+#[wasm_bindgen]
+pub fn sort_transaction_bip69(tx_hex: &str, checks_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let mut tx = decode_tx_hex(tx_hex)?;
+    if tx.input.iter().any(|input| !input.witness.is_empty()) {
+        return Err(JsValue::from_str("Transaction is already signed; sort it before signing"));
+    }
+
+    let (input_order, output_order) = bip69_order(&tx);
+    apply_order(&mut tx, &input_order, &output_order);
+
+    let result = SortedTransaction {
+        tx_hex: bytes_to_hex(&finalize(&tx, Funding::Unknown, &checks).map_err(|e| JsValue::from_str(&e))?),
+        input_order,
+        output_order,
+    };
+
+    to_json(&result, compact)
+}
+
+pub fn parse_ordering(ordering: Option<&str>) -> Result<TxOrdering, String> {
+    match ordering.map(|o| o.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("none") => Ok(TxOrdering::AsGiven),
+        Some("bip69") => Ok(TxOrdering::Bip69),
+        #[cfg(feature = "wallet")]
+        Some("shuffle") => Ok(TxOrdering::Shuffle),
+        #[cfg(feature = "wallet")]
+        Some("random_change") => Ok(TxOrdering::RandomChange),
+        #[cfg(not(feature = "wallet"))]
+        Some(other @ ("shuffle" | "random_change")) => Err(format!("{} ordering needs a build with the wallet feature", other)),
+        Some(other) => Err(format!("Unknown ordering \"{}\" (expected none, bip69, shuffle or random_change)", other)),
+    }
+}
+
+/// New positions as indices into the original inputs and outputs: `input_order[i]`
+/// is the original index of the input that ends up at position `i`. `change` is the
+/// original index of the change output, if there is one.
+#[cfg_attr(not(feature = "wallet"), allow(unused_variables))]
+pub fn transaction_order(tx: &Transaction, ordering: TxOrdering, change: Option<usize>) -> (Vec<usize>, Vec<usize>) {
+    match ordering {
+        TxOrdering::AsGiven => ((0..tx.input.len()).collect(), (0..tx.output.len()).collect()),
+        TxOrdering::Bip69 => bip69_order(tx),
+        #[cfg(feature = "wallet")]
+        TxOrdering::Shuffle => {
+            let mut input_order: Vec<usize> = (0..tx.input.len()).collect();
+            let mut output_order: Vec<usize> = (0..tx.output.len()).collect();
+            with_rng(|rng| {
+                input_order.shuffle(rng);
+                output_order.shuffle(rng);
+            });
+            (input_order, output_order)
+        }
+        #[cfg(feature = "wallet")]
+        TxOrdering::RandomChange => {
+            let mut output_order: Vec<usize> = (0..tx.output.len()).filter(|&i| Some(i) != change).collect();
+            if let Some(change) = change {
+                let position = with_rng(|rng| rng.gen_range(0..=output_order.len()));
+                output_order.insert(position, change);
+            }
+            ((0..tx.input.len()).collect(), output_order)
+        }
+    }
+}
+
+/// BIP69: inputs by previous txid (display byte order) then vout, outputs by amount
+/// then scriptPubKey bytes.
+pub fn bip69_order(tx: &Transaction) -> (Vec<usize>, Vec<usize>) {
+    let mut input_order: Vec<usize> = (0..tx.input.len()).collect();
+    input_order.sort_by_key(|&i| {
+        let outpoint = tx.input[i].previous_output;
+        (outpoint.txid.to_string(), outpoint.vout)
+    });
+
+    let mut output_order: Vec<usize> = (0..tx.output.len()).collect();
+    output_order.sort_by(|&a, &b| {
+        let (a, b) = (&tx.output[a], &tx.output[b]);
+        a.value.cmp(&b.value).then_with(|| a.script_pubkey.as_bytes().cmp(b.script_pubkey.as_bytes()))
+    });
+
+    (input_order, output_order)
+}
+
+pub fn apply_order(tx: &mut Transaction, input_order: &[usize], output_order: &[usize]) {
+    tx.input = input_order.iter().map(|&i| tx.input[i].clone()).collect();
+    tx.output = output_order.iter().map(|&i| tx.output[i].clone()).collect();
+}
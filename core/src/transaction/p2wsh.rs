@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::secp256k1::{All, Message, Secp256k1};
+use bitcoin::sighash::SighashCache;
+use bitcoin::{Amount, PrivateKey, Script, ScriptBuf, Transaction, Witness};
+use crate::transaction::parse_sighash_type;
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::{check_input_index, decode_tx_hex};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::parse_private_key;
+use crate::utils::metrics::timed;
+
+/// One witness element: a literal hex push, or a signature computed with `private_keys[sign_with]`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum StackItem {
+    Literal(String),
+    Signature {
+        sign_with: usize,
+        #[serde(default)]
+        sighash: Option<String>,
+    },
+}
+
+#[wasm_bindgen]
+pub fn sign_p2wsh_input(
+    tx_hex: &str,
+    input_index: usize,
+    witness_script_hex: &str,
+    amount: u64,
+    stack_template_json: &str,
+    private_keys_json: &str,
+    checks_json: Option<String>,
+) -> Result<String, JsValue> {
+    timed("sign_p2wsh_input", tx_hex.len() + witness_script_hex.len() + stack_template_json.len() + private_keys_json.len(), || {
+        let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+        let secp = Secp256k1::new();
+
+        let mut tx = decode_tx_hex(tx_hex)?;
+        check_input_index(&tx, input_index)?;
+
+        let witness_script = ScriptBuf::from_bytes(
+            hex_to_bytes(witness_script_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid witness script: {}", e)))?,
+        );
+        let template: Vec<StackItem> = serde_json::from_str(stack_template_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid stack template JSON: {}", e)))?;
+        let key_strings: Vec<String> = serde_json::from_str(private_keys_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid private keys JSON: {}", e)))?;
+        let private_keys = key_strings
+            .iter()
+            .map(|key| parse_private_key(key, None, bitcoin::Network::Testnet))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let witness = p2wsh_witness(&secp, &tx, input_index, &witness_script, amount, &template, &private_keys)?;
+        tx.input[input_index].script_sig = ScriptBuf::new();
+        tx.input[input_index].witness = witness;
+
+        // Only this input's amount is known, so the fee can't be.
+        Ok(bytes_to_hex(&finalize(&tx, Funding::Unknown, &checks).map_err(|e| JsValue::from_str(&e))?))
+    })
+}
+
+/// The witness for spending `witness_script`: `template`'s items followed by the script.
+pub fn p2wsh_witness(
+    secp: &Secp256k1<All>,
+    tx: &Transaction,
+    input_index: usize,
+    witness_script: &Script,
+    amount: u64,
+    template: &[StackItem],
+    private_keys: &[PrivateKey],
+) -> Result<Witness, JsValue> {
+    let mut cache = SighashCache::new(tx);
+    let mut items: Vec<Vec<u8>> = Vec::with_capacity(template.len() + 1);
+    for (position, item) in template.iter().enumerate() {
+        match item {
+            StackItem::Literal(hex) => items.push(
+                hex_to_bytes(hex, HexMode::Lenient)
+                    .map_err(|e| JsValue::from_str(&format!("Invalid stack item {}: {}", position, e)))?,
+            ),
+            StackItem::Signature { sign_with, sighash } => {
+                let private_key = private_keys.get(*sign_with).ok_or_else(|| {
+                    JsValue::from_str(&format!(
+                        "Stack item {} signs with key {} but only {} keys were provided",
+                        position,
+                        sign_with,
+                        private_keys.len()
+                    ))
+                })?;
+                let sighash_type = parse_sighash_type(sighash.as_deref().unwrap_or("ALL"))
+                    .map_err(|e| JsValue::from_str(&format!("Stack item {}: {}", position, e)))?;
+                let sighash = cache
+                    .p2wsh_signature_hash(input_index, witness_script, Amount::from_sat(amount), sighash_type)
+                    .map_err(|e| JsValue::from_str(&format!("Failed to compute sighash: {}", e)))?;
+                let signature = bitcoin::ecdsa::Signature {
+                    signature: secp.sign_ecdsa_low_r(&Message::from(sighash), &private_key.inner),
+                    sighash_type,
+                };
+                items.push(signature.to_vec());
+            }
+        }
+    }
+    items.push(witness_script.to_bytes());
+
+    Ok(Witness::from_slice(&items))
+}
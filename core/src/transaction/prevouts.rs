@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{Amount, ScriptBuf, TxOut};
+use crate::utils::json::parse_json_list;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Prevout {
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+}
+
+pub fn parse_prevouts(prevouts_json: &str, input_count: usize) -> Result<Vec<TxOut>, JsValue> {
+    let prevouts: Vec<Prevout> = parse_json_list(prevouts_json, "prevouts").map_err(|e| JsValue::from_str(&e))?;
+
+    if prevouts.len() != input_count {
+        return Err(JsValue::from_str(&format!(
+            "Expected {} prevouts (one per input), got {}",
+            input_count,
+            prevouts.len()
+        )));
+    }
+
+    prevouts
+        .into_iter()
+        .enumerate()
+        .map(|(i, prevout)| {
+            let script_pubkey = ScriptBuf::from_hex(&prevout.script_pubkey)
+                .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey for prevout {}: {}", i, e)))?;
+            Ok(TxOut {
+                value: Amount::from_sat(prevout.amount),
+                script_pubkey,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use js_sys::{Function, Promise, Reflect};
+use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, TxOut};
+use crate::transaction::prevouts::Prevout;
+use crate::utils::json::{parse_json_list, parse_json_value};
+
+/// Looks up prevouts through a JS callback `(txid, vout) => Promise<{amount, script_pubkey}>`.
+/// Each outpoint is requested at most once per call.
+pub struct PrevoutProvider {
+    callback: Function,
+    cache: HashMap<OutPoint, TxOut>,
+}
+
+impl PrevoutProvider {
+    pub fn new(callback: Function) -> PrevoutProvider {
+        PrevoutProvider { callback, cache: HashMap::new() }
+    }
+
+    pub async fn fetch(&mut self, input: usize, outpoint: OutPoint) -> Result<TxOut, JsValue> {
+        if let Some(prevout) = self.cache.get(&outpoint) {
+            return Ok(prevout.clone());
+        }
+
+        let fail = |reason: String| prevout_error(input, &outpoint, &reason);
+        let returned = self
+            .callback
+            .call2(&JsValue::NULL, &JsValue::from_str(&outpoint.txid.to_string()), &JsValue::from(outpoint.vout))
+            .map_err(|e| fail(format!("provider threw: {}", js_error_message(&e))))?;
+        // A plain value is accepted too, so a synchronous lookup works as well.
+        let value = JsFuture::from(Promise::resolve(&returned))
+            .await
+            .map_err(|e| fail(format!("provider rejected: {}", js_error_message(&e))))?;
+
+        let prevout = parse_provided(&value).map_err(fail)?;
+        self.cache.insert(outpoint, prevout.clone());
+        Ok(prevout)
+    }
+}
+
+/// One prevout per input, from `prevouts_json` where given and from `provider` for the
+/// rest. `prevouts_json` may be omitted or hold `null` for the inputs to look up.
+pub async fn resolve_prevouts(
+    tx: &Transaction,
+    prevouts_json: Option<&str>,
+    provider: Option<Function>,
+) -> Result<Vec<TxOut>, JsValue> {
+    let given: Vec<Option<Prevout>> = match prevouts_json {
+        Some(json) if !json.trim().is_empty() => parse_json_list(json, "prevouts").map_err(|e| JsValue::from_str(&e))?,
+        _ => Vec::new(),
+    };
+    if !given.is_empty() && given.len() != tx.input.len() {
+        return Err(JsValue::from_str(&format!(
+            "Expected {} prevouts (one per input, null to look one up), got {}",
+            tx.input.len(),
+            given.len()
+        )));
+    }
+
+    let mut provider = provider.map(PrevoutProvider::new);
+    let mut prevouts = Vec::with_capacity(tx.input.len());
+    for (index, input) in tx.input.iter().enumerate() {
+        let outpoint = input.previous_output;
+        let prevout = match (given.get(index).and_then(Option::as_ref), provider.as_mut()) {
+            (Some(prevout), _) => to_txout(prevout)
+                .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey for prevout {}: {}", index, e)))?,
+            (None, Some(provider)) => provider.fetch(index, outpoint).await?,
+            (None, None) => return Err(prevout_error(index, &outpoint, "no prevout given and no provider to look it up")),
+        };
+        prevouts.push(prevout);
+    }
+    Ok(prevouts)
+}
+
+fn parse_provided(value: &JsValue) -> Result<TxOut, String> {
+    if value.is_null() || value.is_undefined() {
+        return Err("provider returned nothing".to_string());
+    }
+    let json = match value.as_string() {
+        Some(json) => json,
+        None => js_sys::JSON::stringify(value)
+            .ok()
+            .and_then(|json| json.as_string())
+            .ok_or("provider returned a value that isn't JSON")?,
+    };
+    let prevout: Prevout = parse_json_value(&json, "prevout").map_err(|e| format!("provider returned {}", e))?;
+    to_txout(&prevout).map_err(|e| format!("provider returned an invalid script pubkey: {}", e))
+}
+
+fn to_txout(prevout: &Prevout) -> Result<TxOut, String> {
+    let script_pubkey = ScriptBuf::from_hex(&prevout.script_pubkey).map_err(|e| e.to_string())?;
+    Ok(TxOut { value: Amount::from_sat(prevout.amount), script_pubkey })
+}
+
+/// A JS `Error` that also carries `input`, `txid` and `vout`, so callers can tell which
+/// lookup failed without parsing the message.
+pub fn prevout_error(input: usize, outpoint: &OutPoint, reason: &str) -> JsValue {
+    let error = js_sys::Error::new(&format!("Prevout for input {} ({}) unavailable: {}", input, outpoint, reason));
+    let _ = Reflect::set(&error, &JsValue::from_str("input"), &JsValue::from(input as u32));
+    let _ = Reflect::set(&error, &JsValue::from_str("txid"), &JsValue::from_str(&outpoint.txid.to_string()));
+    let _ = Reflect::set(&error, &JsValue::from_str("vout"), &JsValue::from(outpoint.vout));
+    error.into()
+}
+
+pub fn js_error_message(value: &JsValue) -> String {
+    if let Some(error) = value.dyn_ref::<js_sys::Error>() {
+        return String::from(error.message());
+    }
+    value.as_string().unwrap_or_else(|| format!("{:?}", value))
+}
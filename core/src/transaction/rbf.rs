@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{OutPoint, Transaction, Txid};
+#[cfg(feature = "http")]
+use crate::esplora::EsploraTx;
+use crate::transaction::parse_tx_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::units::FeeRate;
+
+/// Bitcoin Core's default `-incrementalrelayfee`, 1 sat/vB.
+pub const DEFAULT_INCREMENTAL_RELAY_FEE: f64 = 1.0;
+
+/// The transaction being replaced: raw hex, or Esplora's JSON for it, which carries its
+/// prevouts, fee and weight so they don't have to be supplied separately.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum OriginalTx {
+    Raw(String),
+    #[cfg(feature = "http")]
+    Decoded(Box<EsploraTx>),
+}
+
+/// A coin spent by either transaction. `confirmed` only matters for coins the replacement
+/// adds; leaving it out makes the unconfirmed-inputs rule undecidable for that coin.
+#[derive(Serialize, Deserialize)]
+pub struct RbfPrevout {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(default)]
+    pub confirmed: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RbfRule {
+    pub rule: String,
+    /// `None` when the inputs given aren't enough to decide.
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RbfCheck {
+    /// True only when every rule passed.
+    pub valid: bool,
+    pub rules: Vec<RbfRule>,
+    #[serde(alias = "original_fee")]
+    pub original_fee: Option<u64>,
+    #[serde(alias = "original_vsize")]
+    pub original_vsize: u64,
+    #[serde(alias = "replacement_fee")]
+    pub replacement_fee: Option<u64>,
+    #[serde(alias = "replacement_vsize")]
+    pub replacement_vsize: u64,
+    /// Smallest fee that satisfies both the absolute-fee and incremental-relay rules.
+    #[serde(alias = "min_replacement_fee")]
+    pub min_replacement_fee: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+/// The essentials of the original, whichever form it came in.
+struct Original {
+    txid: Txid,
+    inputs: Vec<(OutPoint, u32)>,
+    output_total: u64,
+    fee: Option<u64>,
+    vsize: u64,
+}
+
+/// Checks the BIP125 rules a wallet can evaluate without a mempool. The original must signal.
+/// The replacement must not add unconfirmed inputs, must pay a higher absolute fee and a
+/// higher feerate, and must add enough fee to relay itself at `incremental_relay_fee_sat_vb`
+/// (default 1). Descendants of the original, and signalling inherited from unconfirmed
+/// ancestors, need a node's mempool and aren't considered.
+#[wasm_bindgen]
+pub fn check_rbf(
+    original_tx_json_or_hex: &str,
+    replacement_tx_hex: &str,
+    prevouts_json: &str,
+    incremental_relay_fee_sat_vb: Option<f64>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let original = parse_original(original_tx_json_or_hex).map_err(|e| JsValue::from_str(&e))?;
+    let replacement = parse_tx_hex(replacement_tx_hex).map_err(|e| JsValue::from_str(&format!("Replacement: {}", e)))?;
+    let incremental = incremental_relay_fee_sat_vb.unwrap_or(DEFAULT_INCREMENTAL_RELAY_FEE);
+    let incremental = FeeRate::from_sat_per_vb(incremental).map_err(|e| JsValue::from_str(&e))?;
+
+    let entries: Vec<RbfPrevout> = if prevouts_json.trim().is_empty() {
+        Vec::new()
+    } else {
+        parse_json_list(prevouts_json, "prevouts").map_err(|e| JsValue::from_str(&e))?
+    };
+    let mut prevouts: HashMap<OutPoint, &RbfPrevout> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let txid: Txid = entry
+            .txid
+            .parse()
+            .map_err(|e| JsValue::from_str(&format!("prevouts[{}]: invalid txid: {}", index, e)))?;
+        prevouts.insert(OutPoint { txid, vout: entry.vout }, entry);
+    }
+
+    to_json(&evaluate(&original, &replacement, &prevouts, incremental), compact)
+}
+
+fn evaluate(original: &Original, replacement: &Transaction, prevouts: &HashMap<OutPoint, &RbfPrevout>, incremental: FeeRate) -> RbfCheck {
+    let mut rules = Vec::new();
+    let mut warnings = Vec::new();
+    let replacement_vsize = replacement.vsize() as u64;
+    if replacement.input.iter().any(|input| input.script_sig.is_empty() && input.witness.is_empty()) {
+        warnings.push(
+            "The replacement has unsigned inputs, so its vsize (and the fee it needs) will grow once signed; \
+             check the signed transaction, or one from populate_dummy_signatures"
+                .to_string(),
+        );
+    }
+    if replacement.compute_txid() == original.txid {
+        warnings.push("The replacement is the original transaction itself".to_string());
+    }
+
+    let original_outpoints: Vec<OutPoint> = original.inputs.iter().map(|(outpoint, _)| *outpoint).collect();
+    let conflicts = replacement.input.iter().filter(|input| original_outpoints.contains(&input.previous_output)).count();
+    rules.push(RbfRule {
+        rule: "conflicts_with_original".to_string(),
+        passed: Some(conflicts > 0),
+        detail: if conflicts > 0 {
+            format!("Spends {} of the original's {} inputs", conflicts, original.inputs.len())
+        } else {
+            "Spends none of the original's inputs, so it doesn't replace it: both could confirm".to_string()
+        },
+    });
+
+    let signalling = original.inputs.iter().position(|(_, sequence)| *sequence < 0xffff_fffe);
+    rules.push(RbfRule {
+        rule: "original_signals_rbf".to_string(),
+        passed: Some(signalling.is_some()),
+        detail: match signalling {
+            Some(index) => format!("Input {} of the original has a sequence below 0xfffffffe (BIP125 rule 1)", index),
+            None => "No input of the original has a sequence below 0xfffffffe (BIP125 rule 1). Nodes running Bitcoin Core 28 or \
+                     later replace it anyway by default (full RBF), but older or differently configured nodes refuse"
+                .to_string(),
+        },
+    });
+
+    let added: Vec<&OutPoint> = replacement
+        .input
+        .iter()
+        .map(|input| &input.previous_output)
+        .filter(|outpoint| !original_outpoints.contains(outpoint))
+        .collect();
+    let unconfirmed: Vec<String> = added
+        .iter()
+        .filter(|outpoint| prevouts.get(outpoint).and_then(|prevout| prevout.confirmed) == Some(false))
+        .map(|outpoint| outpoint.to_string())
+        .collect();
+    let unknown: Vec<String> = added
+        .iter()
+        .filter(|outpoint| prevouts.get(outpoint).and_then(|prevout| prevout.confirmed).is_none())
+        .map(|outpoint| outpoint.to_string())
+        .collect();
+    rules.push(RbfRule {
+        rule: "no_new_unconfirmed_inputs".to_string(),
+        passed: if !unconfirmed.is_empty() { Some(false) } else if !unknown.is_empty() { None } else { Some(true) },
+        detail: if !unconfirmed.is_empty() {
+            format!("Adds unconfirmed inputs {} (BIP125 rule 2)", unconfirmed.join(", "))
+        } else if !unknown.is_empty() {
+            format!("Adds inputs whose confirmation status wasn't given: {} (BIP125 rule 2)", unknown.join(", "))
+        } else if added.is_empty() {
+            "Adds no inputs beyond the original's (BIP125 rule 2)".to_string()
+        } else {
+            format!("The {} added inputs are all confirmed (BIP125 rule 2)", added.len())
+        },
+    });
+
+    let replacement_fee = transaction_fee(replacement, prevouts);
+    let original_fee = original.fee.or_else(|| {
+        let spent: Option<u64> = original.inputs.iter().map(|(outpoint, _)| prevouts.get(outpoint).map(|prevout| prevout.amount)).sum();
+        spent.and_then(|spent| spent.checked_sub(original.output_total))
+    });
+    // Bitcoin Core's CFeeRate::GetFee rounds up, working in sat/kvB.
+    let relay_fee = (replacement_vsize * (incremental.sat_per_vb() * 1000.0).round() as u64).div_ceil(1000);
+    let missing = match (original_fee, replacement_fee.as_ref()) {
+        (None, _) => "the original's fee is unknown: give its prevouts or its Esplora JSON".to_string(),
+        (_, Err(e)) => e.clone(),
+        _ => String::new(),
+    };
+
+    match (original_fee, replacement_fee.as_ref().ok()) {
+        (Some(old), Some(&new)) => {
+            rules.push(RbfRule {
+                rule: "higher_absolute_fee".to_string(),
+                passed: Some(new >= old),
+                detail: format!("Pays {} sats against the original's {} (BIP125 rule 3)", new, old),
+            });
+            let delta = new as i64 - old as i64;
+            rules.push(RbfRule {
+                rule: "pays_for_own_relay".to_string(),
+                passed: Some(delta >= relay_fee as i64),
+                detail: format!(
+                    "Adds {} sats; relaying {} vB at the incremental relay fee of {} needs at least {} (BIP125 rule 4)",
+                    delta, replacement_vsize, incremental, relay_fee
+                ),
+            });
+            // Cross-multiplied so the comparison is exact.
+            let higher = new as u128 * original.vsize as u128 > old as u128 * replacement_vsize as u128;
+            rules.push(RbfRule {
+                rule: "higher_feerate".to_string(),
+                passed: Some(higher),
+                detail: format!(
+                    "Pays {:.2} sat/vB against the original's {:.2}",
+                    new as f64 / replacement_vsize as f64,
+                    old as f64 / original.vsize as f64
+                ),
+            });
+        }
+        _ => {
+            for rule in ["higher_absolute_fee", "pays_for_own_relay", "higher_feerate"] {
+                rules.push(RbfRule { rule: rule.to_string(), passed: None, detail: format!("Can't check: {}", missing) });
+            }
+        }
+    }
+
+    RbfCheck {
+        valid: rules.iter().all(|rule| rule.passed == Some(true)),
+        rules,
+        original_fee,
+        original_vsize: original.vsize,
+        replacement_fee: replacement_fee.ok(),
+        replacement_vsize,
+        min_replacement_fee: original_fee.map(|old| old + relay_fee),
+        warnings,
+    }
+}
+
+fn transaction_fee(tx: &Transaction, prevouts: &HashMap<OutPoint, &RbfPrevout>) -> Result<u64, String> {
+    let mut spent = 0u64;
+    for input in &tx.input {
+        let prevout = prevouts
+            .get(&input.previous_output)
+            .ok_or_else(|| format!("no prevout was given for {}", input.previous_output))?;
+        spent += prevout.amount;
+    }
+    let paid: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    spent.checked_sub(paid).ok_or_else(|| format!("transaction {} pays out more than it spends", tx.compute_txid()))
+}
+
+fn parse_original(input: &str) -> Result<Original, String> {
+    let trimmed = input.trim();
+    let parsed: OriginalTx = if trimmed.starts_with('{') {
+        serde_json::from_str(trimmed).map_err(|e| format!("Invalid original transaction JSON: {}", e))?
+    } else {
+        OriginalTx::Raw(trimmed.to_string())
+    };
+
+    match parsed {
+        OriginalTx::Raw(hex) => {
+            let tx = parse_tx_hex(&hex).map_err(|e| format!("Original: {}", e))?;
+            Ok(Original {
+                txid: tx.compute_txid(),
+                inputs: tx.input.iter().map(|input| (input.previous_output, input.sequence.0)).collect(),
+                output_total: tx.output.iter().map(|output| output.value.to_sat()).sum(),
+                fee: None,
+                vsize: tx.vsize() as u64,
+            })
+        }
+        #[cfg(feature = "http")]
+        OriginalTx::Decoded(tx) => {
+            let txid = tx.txid.parse().map_err(|e| format!("Original: invalid txid: {}", e))?;
+            let inputs = tx
+                .vin
+                .iter()
+                .enumerate()
+                .map(|(index, vin)| match (&vin.txid, vin.vout, vin.sequence) {
+                    (Some(txid), Some(vout), Some(sequence)) => {
+                        let txid = txid.parse().map_err(|e| format!("Original vin[{}]: invalid txid: {}", index, e))?;
+                        Ok((OutPoint { txid, vout }, sequence))
+                    }
+                    _ => Err(format!("Original vin[{}] needs txid, vout and sequence", index)),
+                })
+                .collect::<Result<_, String>>()?;
+            let weight = tx.weight.ok_or("Original transaction JSON needs its weight")?;
+            let output_total = tx.vout.iter().map(|vout| vout.value).sum();
+            let fee = tx.fee.or_else(|| {
+                let spent: Option<u64> = tx.vin.iter().map(|vin| vin.prevout.as_ref().map(|prevout| prevout.value)).sum();
+                spent.and_then(|spent| spent.checked_sub(output_total))
+            });
+            Ok(Original { txid, inputs, output_total, fee, vsize: weight.div_ceil(4) })
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::Hash;
+use bitcoin::{absolute, transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use crate::utils::{bytes_to_hex, hex_to_bytes, wasm_log, HexMode};
+use crate::utils::json::to_json;
+use crate::utils::varint::decode_compact_size;
+use crate::utils::metrics::timed;
+
+/// On in debug builds, so a builder or signer bug shows up before a release does.
+static SERIALIZATION_CHECKS: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+#[derive(Serialize, Deserialize)]
+pub struct SerializationIssue {
+    /// `error` for a serialization nodes reject, `warning` for one they accept but that
+    /// is easy to misread.
+    pub severity: String,
+    pub code: String,
+    pub message: String,
+    /// Byte offset into the transaction, where there is one.
+    pub offset: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializationReport {
+    /// True when there are no errors.
+    pub valid: bool,
+    /// The marker and flag bytes are present.
+    #[serde(alias = "segwit_marker")]
+    pub segwit_marker: bool,
+    /// Some input has a non-empty witness.
+    #[serde(alias = "has_witness")]
+    pub has_witness: bool,
+    pub txid: Option<String>,
+    pub wtxid: Option<String>,
+    pub size: Option<usize>,
+    #[serde(alias = "base_size")]
+    pub base_size: Option<usize>,
+    pub weight: Option<u64>,
+    pub vsize: Option<u64>,
+    /// How the transaction serializes canonically, when that differs from the input.
+    #[serde(alias = "canonical_hex")]
+    pub canonical_hex: Option<String>,
+    pub issues: Vec<SerializationIssue>,
+}
+
+/// Checks a transaction's bytes rather than what they decode to: that the BIP144 marker
+/// and flag agree with the witnesses, that every CompactSize is minimal, that nothing
+/// trails the locktime, and that the transaction re-serializes to the same bytes. A
+/// segwit serialization whose witnesses are all empty must be written the legacy way, and
+/// `canonical_hex` then gives that form.
+#[wasm_bindgen]
+pub fn validate_serialization(tx_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("validate_serialization", tx_hex.len(), || {
+        let bytes = hex_to_bytes(tx_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid tx hex: {}", e)))?;
+        to_json(&serialization_report(&bytes), compact)
+    })
+}
+
+/// Turns the check the builders and signers run on their own output on or off. It is on
+/// in debug builds and off in release builds. While on, a transaction that fails it is
+/// logged to the console and an error is returned instead of the hex.
+#[wasm_bindgen]
+pub fn set_serialization_checks(enabled: bool) {
+    SERIALIZATION_CHECKS.store(enabled, Ordering::Relaxed);
+}
+
+/// `consensus::serialize`, checked by `validate_serialization` while serialization
+/// checks are on.
+pub fn checked_serialize(tx: &Transaction) -> Result<Vec<u8>, String> {
+    let bytes = bitcoin::consensus::serialize(tx);
+    if !SERIALIZATION_CHECKS.load(Ordering::Relaxed) {
+        return Ok(bytes);
+    }
+    let errors: Vec<String> = serialization_report(&bytes)
+        .issues
+        .into_iter()
+        .filter(|issue| issue.severity == "error")
+        .map(|issue| issue.message)
+        .collect();
+    if errors.is_empty() {
+        return Ok(bytes);
+    }
+    let message = format!("Produced a malformed transaction serialization: {}", errors.join("; "));
+    wasm_log(&format!("{} ({})", message, bytes_to_hex(&bytes)));
+    Err(message)
+}
+
+pub fn serialization_report(bytes: &[u8]) -> SerializationReport {
+    let mut report = SerializationReport {
+        valid: false,
+        segwit_marker: false,
+        has_witness: false,
+        txid: None,
+        wtxid: None,
+        size: None,
+        base_size: None,
+        weight: None,
+        vsize: None,
+        canonical_hex: None,
+        issues: Vec::new(),
+    };
+    let segwit = match bytes.get(4..6) {
+        Some([0x00, 0x01]) => true,
+        Some([0x00, flag]) => {
+            report.issues.push(error(
+                "unknown_flag",
+                format!(
+                    "The segwit marker is followed by flag 0x{:02x}; only 0x01 is defined (a legacy transaction with no inputs also reads this way)",
+                    flag
+                ),
+                Some(5),
+            ));
+            return report;
+        }
+        _ => false,
+    };
+    report.segwit_marker = segwit;
+
+    let parsed = match parse(bytes, segwit) {
+        Ok(parsed) => parsed,
+        Err((message, offset)) => {
+            report.issues.push(error("malformed", message, Some(offset)));
+            if !segwit {
+                add_missing_marker_hint(&mut report, bytes);
+            }
+            return report;
+        }
+    };
+    let tx = &parsed.tx;
+    report.has_witness = tx.input.iter().any(|input| !input.witness.is_empty());
+    for offset in &parsed.non_minimal {
+        report.issues.push(error("non_minimal_compact_size", "A CompactSize isn't minimally encoded".to_string(), Some(*offset)));
+    }
+    if segwit && !tx.input.is_empty() && !report.has_witness {
+        report.issues.push(error(
+            "empty_witnesses",
+            "The marker and flag say segwit, but every witness is empty; such a transaction must be serialized without them (nodes reject it as a superfluous witness record)".to_string(),
+            Some(4),
+        ));
+    }
+    if segwit && tx.input.is_empty() {
+        report.issues.push(warning(
+            "no_inputs",
+            "No inputs, serialized with the segwit marker so it can't be misread as a legacy transaction; fine for a template, not for relay".to_string(),
+            Some(4),
+        ));
+    }
+    if parsed.end < bytes.len() {
+        report.issues.push(error(
+            "trailing_data",
+            format!("{} byte(s) follow the locktime", bytes.len() - parsed.end),
+            Some(parsed.end),
+        ));
+    }
+
+    let canonical = bitcoin::consensus::serialize(tx);
+    if canonical != bytes {
+        if report.issues.iter().all(|issue| issue.severity != "error") {
+            report.issues.push(error("non_canonical", "The transaction doesn't re-serialize to the same bytes".to_string(), None));
+        }
+        report.canonical_hex = Some(bytes_to_hex(&canonical));
+    }
+    if parsed.end < bytes.len() && !segwit {
+        add_missing_marker_hint(&mut report, bytes);
+    }
+
+    report.valid = report.issues.iter().all(|issue| issue.severity != "error");
+    report.txid = Some(tx.compute_txid().to_string());
+    report.wtxid = Some(tx.compute_wtxid().to_string());
+    report.size = Some(tx.total_size());
+    report.base_size = Some(tx.base_size());
+    report.weight = Some(tx.weight().to_wu());
+    report.vsize = Some(tx.vsize() as u64);
+    report
+}
+
+/// A legacy parse that fails or leaves bytes over may be a segwit transaction whose
+/// marker and flag were cut out with the witnesses left in. If so, `canonical_hex`
+/// becomes the transaction with them put back.
+fn add_missing_marker_hint(report: &mut SerializationReport, bytes: &[u8]) {
+    let Some(version) = bytes.get(..4) else {
+        return;
+    };
+    let mut patched = version.to_vec();
+    patched.extend_from_slice(&[0x00, 0x01]);
+    patched.extend_from_slice(&bytes[4..]);
+    let Ok(parsed) = parse(&patched, true) else {
+        return;
+    };
+    let has_witness = parsed.tx.input.iter().any(|input| !input.witness.is_empty());
+    if parsed.end != patched.len() || !has_witness {
+        return;
+    }
+    report.issues.push(error(
+        "missing_marker",
+        "Parses as a segwit transaction once a marker and flag (0001) are put back after the version: the witnesses are still there".to_string(),
+        Some(4),
+    ));
+    report.canonical_hex = Some(bytes_to_hex(&bitcoin::consensus::serialize(&parsed.tx)));
+}
+
+struct Parsed {
+    tx: Transaction,
+    /// Offsets of CompactSizes longer than they need to be.
+    non_minimal: Vec<usize>,
+    end: usize,
+}
+
+/// Walks the serialization by hand, so non-minimal lengths and stray bytes are found
+/// where the consensus decoder would only fail.
+fn parse(bytes: &[u8], segwit: bool) -> Result<Parsed, (String, usize)> {
+    let mut reader = Reader { bytes, pos: 0, non_minimal: Vec::new() };
+    let version = i32::from_le_bytes(reader.array("version")?);
+    if segwit {
+        reader.take(2, "marker and flag")?;
+    }
+    let input_count = reader.compact("input count")?;
+    let mut input = Vec::new();
+    for _ in 0..input_count {
+        let txid = Txid::from_byte_array(reader.array("previous txid")?);
+        let vout = u32::from_le_bytes(reader.array("previous output index")?);
+        let script_len = reader.compact("scriptSig length")?;
+        let script_sig = ScriptBuf::from_bytes(reader.take(script_len, "scriptSig")?.to_vec());
+        let sequence = Sequence(u32::from_le_bytes(reader.array("sequence")?));
+        input.push(TxIn { previous_output: OutPoint { txid, vout }, script_sig, sequence, witness: Witness::default() });
+    }
+    let output_count = reader.compact("output count")?;
+    let mut output = Vec::new();
+    for _ in 0..output_count {
+        let value = Amount::from_sat(u64::from_le_bytes(reader.array("output amount")?));
+        let script_len = reader.compact("scriptPubKey length")?;
+        let script_pubkey = ScriptBuf::from_bytes(reader.take(script_len, "scriptPubKey")?.to_vec());
+        output.push(TxOut { value, script_pubkey });
+    }
+    if segwit {
+        for txin in &mut input {
+            let item_count = reader.compact("witness item count")?;
+            let mut items = Vec::new();
+            for _ in 0..item_count {
+                let len = reader.compact("witness item length")?;
+                items.push(reader.take(len, "witness item")?.to_vec());
+            }
+            txin.witness = Witness::from_slice(&items);
+        }
+    }
+    let lock_time = absolute::LockTime::from_consensus(u32::from_le_bytes(reader.array("locktime")?));
+
+    Ok(Parsed {
+        tx: Transaction { version: transaction::Version(version), lock_time, input, output },
+        non_minimal: reader.non_minimal,
+        end: reader.pos,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    non_minimal: Vec<usize>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], (String, usize)> {
+        let slice = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or_else(|| (format!("The transaction ends inside the {}", what), self.pos))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self, what: &str) -> Result<[u8; N], (String, usize)> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N, what)?);
+        Ok(array)
+    }
+
+    fn compact(&mut self, what: &str) -> Result<usize, (String, usize)> {
+        let start = self.pos;
+        let decoded = decode_compact_size(self.bytes.get(start..).unwrap_or_default())
+            .map_err(|_| (format!("The transaction ends inside the {}", what), start))?;
+        if !decoded.canonical {
+            self.non_minimal.push(start);
+        }
+        self.pos += decoded.bytes_consumed;
+        // Anything longer than what is left can't be read, whatever it claims.
+        let remaining = self.bytes.len() - self.pos;
+        if decoded.value > remaining as u64 {
+            return Err((format!("The {} is {}, more than the {} bytes left", what, decoded.value, remaining), start));
+        }
+        Ok(decoded.value as usize)
+    }
+}
+
+fn error(code: &str, message: String, offset: Option<usize>) -> SerializationIssue {
+    SerializationIssue { severity: "error".to_string(), code: code.to_string(), message, offset }
+}
+
+fn warning(code: &str, message: String, offset: Option<usize>) -> SerializationIssue {
+    SerializationIssue { severity: "warning".to_string(), code: code.to_string(), message, offset }
+}
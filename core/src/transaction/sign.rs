@@ -0,0 +1,552 @@
+use std::borrow::Borrow;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::key::{Keypair, TapTweak};
+use bitcoin::script::PushBytes;
+use bitcoin::secp256k1::{self, All, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::{Amount, CompressedPublicKey, Network, PrivateKey, PublicKey, Script, ScriptBuf, Transaction, TxOut, Witness};
+use js_sys::Function;
+use crate::transaction::{check_input_index, decode_tx_hex, parse_prevouts};
+use crate::transaction::multisig::sign_multisig_input;
+use crate::transaction::provider::resolve_prevouts;
+use crate::transaction::serialization::checked_serialize;
+use crate::utils::step::{check_work_units, step_json, to_result, Progress, RawValue};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::parse_private_key;
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+
+#[derive(Serialize, Deserialize)]
+pub struct SkippedInput {
+    pub index: usize,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignAllResult {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    #[serde(alias = "signed_inputs")]
+    pub signed_inputs: Vec<usize>,
+    #[serde(alias = "skipped_inputs")]
+    pub skipped_inputs: Vec<SkippedInput>,
+}
+
+/// Signatures for one input, applied once every sighash has been computed.
+pub struct InputSignature {
+    pub script_sig: ScriptBuf,
+    pub witness: Witness,
+}
+
+/// `redeem_script_hex` and `witness_script_hex` sign a P2SH, P2SH-P2WSH or P2WSH
+/// multisig input instead of a single-key spend, keeping signatures already on it.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn sign_transaction(
+    tx_hex: &str,
+    private_key_hex: &str,
+    input_index: usize,
+    script_pubkey_hex: &str,
+    satoshi_value: u64,
+    redeem_script_hex: Option<String>,
+    witness_script_hex: Option<String>,
+) -> Result<String, JsValue> {
+    timed("sign_transaction", tx_hex.len() + private_key_hex.len() + script_pubkey_hex.len(), || {
+        let secp = Secp256k1::new();
+        let private_key = parse_private_key(private_key_hex, None, Network::Testnet)?;
+
+        let mut tx = decode_tx_hex(tx_hex)?;
+        check_input_index(&tx, input_index)?;
+
+        let script_bytes = hex_to_bytes(script_pubkey_hex, HexMode::Lenient)
+            .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e)))?;
+        let prevout = TxOut {
+            value: Amount::from_sat(satoshi_value),
+            script_pubkey: ScriptBuf::from_bytes(script_bytes),
+        };
+        if prevout.script_pubkey.is_p2tr() && tx.input.len() > 1 {
+            return Err(JsValue::from_str(
+                "Taproot signatures commit to every prevout; use sign_all_inputs with the full prevouts list",
+            ));
+        }
+
+        let redeem_script = optional_script(redeem_script_hex, "redeem script")?;
+        let witness_script = optional_script(witness_script_hex, "witness script")?;
+        // A P2WPKH redeem script is the nested single-key spend sign_input already handles.
+        let multisig = witness_script.is_some() || redeem_script.as_ref().is_some_and(|script| !script.is_p2wpkh());
+
+        let mut cache = SighashCache::new(&tx);
+        let all_prevouts = [prevout.clone()];
+        let signature = if multisig {
+            sign_multisig_input(&secp, &mut cache, input_index, &prevout, redeem_script.as_deref(), witness_script.as_deref(), &private_key)
+        } else {
+            sign_input(&secp, &mut cache, input_index, &prevout, &all_prevouts, &private_key)
+        }
+        .map_err(|e| JsValue::from_str(&format!("Failed to sign input {}: {}", input_index, e)))?;
+        apply_signature(&mut tx, input_index, signature);
+
+        let tx_bytes = checked_serialize(&tx).map_err(|e| JsValue::from_str(&e))?;
+        Ok(bytes_to_hex(&tx_bytes))
+    })
+}
+
+fn optional_script(hex: Option<String>, what: &str) -> Result<Option<ScriptBuf>, JsValue> {
+    match hex.filter(|hex| !hex.trim().is_empty()) {
+        Some(hex) => hex_to_bytes(&hex, HexMode::Lenient)
+            .map(|bytes| Some(ScriptBuf::from_bytes(bytes)))
+            .map_err(|e| JsValue::from_str(&format!("Invalid {}: {}", what, e))),
+        None => Ok(None),
+    }
+}
+
+#[wasm_bindgen]
+pub fn sign_all_inputs(tx_hex: &str, private_key_hex: &str, prevouts_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("sign_all_inputs", tx_hex.len() + private_key_hex.len() + prevouts_json.len(), || {
+        let private_key = parse_private_key(private_key_hex, None, Network::Testnet)?;
+
+        let tx = decode_tx_hex(tx_hex)?;
+        let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+        sign_all(tx, &private_key, &prevouts, compact)
+    })
+}
+
+/// Same as `sign_all_inputs`, with prevouts missing from `prevouts_json` (omitted, or
+/// `null` entries) looked up through `provider`, a `(txid, vout) => Promise` callback.
+#[wasm_bindgen]
+pub async fn sign_all_inputs_with_provider(
+    tx_hex: String,
+    private_key_hex: String,
+    prevouts_json: Option<String>,
+    provider: Function,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let private_key = parse_private_key(&private_key_hex, None, Network::Testnet)?;
+    let tx = decode_tx_hex(&tx_hex)?;
+    let prevouts = resolve_prevouts(&tx, prevouts_json.as_deref(), Some(provider)).await?;
+    sign_all(tx, &private_key, &prevouts, compact)
+}
+
+fn sign_all(mut tx: Transaction, private_key: &PrivateKey, prevouts: &[TxOut], compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+
+    // One cache for the whole transaction, so hashPrevouts/hashSequence/hashOutputs
+    // (and their taproot equivalents) are computed once rather than per input.
+    let mut cache = SighashCache::new(&tx);
+    let mut signatures = Vec::new();
+    let mut skipped_inputs = Vec::new();
+    for (index, prevout) in prevouts.iter().enumerate() {
+        match sign_input(&secp, &mut cache, index, prevout, prevouts, private_key) {
+            Ok(signature) => signatures.push((index, signature)),
+            Err(reason) => skipped_inputs.push(SkippedInput { index, reason }),
+        }
+    }
+
+    let signed_inputs = signatures.iter().map(|(index, _)| *index).collect();
+    for (index, signature) in signatures {
+        apply_signature(&mut tx, index, signature);
+    }
+
+    let tx_bytes = checked_serialize(&tx).map_err(|e| JsValue::from_str(&e))?;
+    let result = SignAllResult {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        signed_inputs,
+        skipped_inputs,
+    };
+
+    to_json(&result, compact)
+}
+
+/// `sign_all_inputs` in steps, for transactions with enough inputs to stall the page.
+/// Each `step(max_work_units)` signs up to that many inputs; the final step's `result`
+/// is what `sign_all_inputs` returns. The sighash cache is kept between steps.
+#[wasm_bindgen]
+pub struct BatchSigner {
+    cache: SighashCache<Transaction>,
+    private_key: PrivateKey,
+    prevouts: Vec<TxOut>,
+    signatures: Vec<(usize, InputSignature)>,
+    skipped_inputs: Vec<SkippedInput>,
+    next: usize,
+    result: Option<Box<RawValue>>,
+}
+
+#[wasm_bindgen]
+impl BatchSigner {
+    #[wasm_bindgen(constructor)]
+    pub fn new(tx_hex: &str, private_key_hex: &str, prevouts_json: &str) -> Result<BatchSigner, JsValue> {
+        let private_key = parse_private_key(private_key_hex, None, Network::Testnet)?;
+        let tx = decode_tx_hex(tx_hex)?;
+        let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+        Ok(BatchSigner {
+            cache: SighashCache::new(tx),
+            private_key,
+            prevouts,
+            signatures: Vec::new(),
+            skipped_inputs: Vec::new(),
+            next: 0,
+            result: None,
+        })
+    }
+
+    pub fn step(&mut self, max_work_units: u32, compact: Option<bool>) -> Result<String, JsValue> {
+        let units = check_work_units(max_work_units)?;
+        if self.result.is_none() {
+            let secp = Secp256k1::new();
+            let end = self.prevouts.len().min(self.next + units);
+            for index in self.next..end {
+                let prevout = &self.prevouts[index];
+                match sign_input(&secp, &mut self.cache, index, prevout, &self.prevouts, &self.private_key) {
+                    Ok(signature) => self.signatures.push((index, signature)),
+                    Err(reason) => self.skipped_inputs.push(SkippedInput { index, reason }),
+                }
+            }
+            self.next = end;
+            if self.next == self.prevouts.len() {
+                let result = self.finish().map_err(|e| JsValue::from_str(&e))?;
+                self.result = Some(to_result(&result)?);
+            }
+        }
+        step_json(Progress::of(self.next, self.prevouts.len()), self.result.as_deref(), compact)
+    }
+}
+
+impl BatchSigner {
+    fn finish(&mut self) -> Result<SignAllResult, String> {
+        let mut tx = self.cache.transaction().clone();
+        let signed_inputs = self.signatures.iter().map(|(index, _)| *index).collect();
+        for (index, signature) in std::mem::take(&mut self.signatures) {
+            apply_signature(&mut tx, index, signature);
+        }
+        Ok(SignAllResult {
+            tx_hex: bytes_to_hex(&checked_serialize(&tx)?),
+            signed_inputs,
+            skipped_inputs: std::mem::take(&mut self.skipped_inputs),
+        })
+    }
+}
+
+/// A single-key output a key can spend, with the key in the form its script commits to.
+pub enum KeySpend {
+    P2wpkh(CompressedPublicKey),
+    P2shP2wpkh(CompressedPublicKey),
+    P2pkh(PublicKey),
+    /// Key-path spend without a script tree; holds the internal key.
+    P2tr(XOnlyPublicKey),
+}
+
+/// Signs `index` if `prevout` is a P2PKH, P2SH-P2WPKH, P2WPKH or P2TR key-path output of `private_key`.
+pub fn sign_input<T: Borrow<Transaction>>(
+    secp: &Secp256k1<All>,
+    cache: &mut SighashCache<T>,
+    index: usize,
+    prevout: &TxOut,
+    all_prevouts: &[TxOut],
+    private_key: &PrivateKey,
+) -> Result<InputSignature, String> {
+    let secret_key = private_key.inner;
+    let spend = key_spend(secp, &prevout.script_pubkey, &secret_key.public_key(secp), private_key.compressed, "private key")?;
+    let sighash = key_spend_sighash(cache, index, prevout, all_prevouts, &spend)?;
+    key_spend_signature(&spend, key_signature(secp, &spend, &sighash, &secret_key))
+}
+
+/// Signs a `key_spend_sighash` message: low-R ECDSA, or BIP340 with the key tweaked for
+/// a taproot output without a script tree.
+pub fn key_signature(secp: &Secp256k1<All>, spend: &KeySpend, sighash: &Message, secret_key: &secp256k1::SecretKey) -> KeySignature {
+    match spend {
+        KeySpend::P2tr(_) => {
+            let tweaked = Keypair::from_secret_key(secp, secret_key).tap_tweak(secp, None);
+            KeySignature::Schnorr(bitcoin::taproot::Signature {
+                signature: secp.sign_schnorr_no_aux_rand(sighash, &tweaked.to_keypair()),
+                sighash_type: TapSighashType::Default,
+            })
+        }
+        _ => KeySignature::Ecdsa(bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa_low_r(sighash, secret_key))),
+    }
+}
+
+/// Matches `pubkey` against `script`. `key_name` says whose key it is in the errors.
+pub fn key_spend(
+    secp: &Secp256k1<All>,
+    script: &Script,
+    pubkey: &secp256k1::PublicKey,
+    compressed: bool,
+    key_name: &str,
+) -> Result<KeySpend, String> {
+    if script.is_p2wpkh() {
+        if !compressed {
+            return Err("P2WPKH requires a compressed public key".to_string());
+        }
+        let pubkey = CompressedPublicKey(*pubkey);
+        if *script != ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) {
+            return Err(format!("{} does not match the P2WPKH script", key_name));
+        }
+        return Ok(KeySpend::P2wpkh(pubkey));
+    }
+
+    if script.is_p2sh() {
+        if !compressed {
+            return Err("P2SH-P2WPKH requires a compressed public key".to_string());
+        }
+        let pubkey = CompressedPublicKey(*pubkey);
+        let redeem_script = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash());
+        if *script != ScriptBuf::new_p2sh(&redeem_script.script_hash()) {
+            return Err(format!("{} does not match the P2SH script (only P2SH-P2WPKH is supported)", key_name));
+        }
+        return Ok(KeySpend::P2shP2wpkh(pubkey));
+    }
+
+    if script.is_p2pkh() {
+        let compressed = PublicKey::new(*pubkey);
+        let uncompressed = PublicKey::new_uncompressed(*pubkey);
+        return [compressed, uncompressed]
+            .into_iter()
+            .find(|pubkey| *script == ScriptBuf::new_p2pkh(&pubkey.pubkey_hash()))
+            .map(KeySpend::P2pkh)
+            .ok_or_else(|| format!("{} does not match the P2PKH script", key_name));
+    }
+
+    if script.is_p2tr() {
+        let (internal_key, _) = pubkey.x_only_public_key();
+        if *script != ScriptBuf::new_p2tr(secp, internal_key, None) {
+            return Err(format!(
+                "{} does not match the P2TR output key (only key-path spends without a script tree are supported)",
+                key_name
+            ));
+        }
+        return Ok(KeySpend::P2tr(internal_key));
+    }
+
+    Err("unsupported script type (expected P2PKH, P2SH-P2WPKH, P2WPKH or P2TR)".to_string())
+}
+
+/// The SIGHASH_ALL (SIGHASH_DEFAULT for taproot) message for a key spend.
+pub fn key_spend_sighash<T: Borrow<Transaction>>(
+    cache: &mut SighashCache<T>,
+    index: usize,
+    prevout: &TxOut,
+    all_prevouts: &[TxOut],
+    spend: &KeySpend,
+) -> Result<Message, String> {
+    key_spend_sighash_as(cache, index, prevout, all_prevouts, spend, false)
+}
+
+/// As `key_spend_sighash`; with `anyone_can_pay` it's SIGHASH_ALL|ANYONECANPAY, which
+/// commits to this input only, so others can be added after signing.
+pub fn key_spend_sighash_as<T: Borrow<Transaction>>(
+    cache: &mut SighashCache<T>,
+    index: usize,
+    prevout: &TxOut,
+    all_prevouts: &[TxOut],
+    spend: &KeySpend,
+    anyone_can_pay: bool,
+) -> Result<Message, String> {
+    let sighash_error = |e: &dyn std::fmt::Display| format!("sighash error: {}", e);
+    let (ecdsa_type, tap_type) = if anyone_can_pay {
+        (EcdsaSighashType::AllPlusAnyoneCanPay, TapSighashType::AllPlusAnyoneCanPay)
+    } else {
+        (EcdsaSighashType::All, TapSighashType::Default)
+    };
+    match spend {
+        KeySpend::P2wpkh(_) => cache
+            .p2wpkh_signature_hash(index, &prevout.script_pubkey, prevout.value, ecdsa_type)
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+        KeySpend::P2shP2wpkh(pubkey) => cache
+            .p2wpkh_signature_hash(index, &ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()), prevout.value, ecdsa_type)
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+        KeySpend::P2pkh(_) => cache
+            .legacy_signature_hash(index, &prevout.script_pubkey, ecdsa_type.to_u32())
+            .map(Message::from)
+            .map_err(|e| sighash_error(&e)),
+        KeySpend::P2tr(_) => {
+            let prevouts = if anyone_can_pay { Prevouts::One(index, prevout.clone()) } else { Prevouts::All(all_prevouts) };
+            cache
+                .taproot_key_spend_signature_hash(index, &prevouts, tap_type)
+                .map(Message::from)
+                .map_err(|e| sighash_error(&e))
+        }
+    }
+}
+
+pub enum KeySignature {
+    Ecdsa(bitcoin::ecdsa::Signature),
+    Schnorr(bitcoin::taproot::Signature),
+}
+
+/// Places a signature the way the spend expects it.
+pub fn key_spend_signature(spend: &KeySpend, signature: KeySignature) -> Result<InputSignature, String> {
+    match (spend, signature) {
+        (KeySpend::P2wpkh(pubkey), KeySignature::Ecdsa(signature)) => Ok(InputSignature {
+            script_sig: ScriptBuf::new(),
+            witness: Witness::p2wpkh(&signature, &pubkey.0),
+        }),
+        (KeySpend::P2shP2wpkh(pubkey), KeySignature::Ecdsa(signature)) => {
+            // Both halves are required: the scriptSig reveals the P2WPKH program, the witness satisfies it.
+            let redeem_script = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash());
+            let script_sig = bitcoin::script::Builder::new()
+                .push_slice(<&PushBytes>::try_from(redeem_script.as_bytes()).map_err(|e| e.to_string())?)
+                .into_script();
+            Ok(InputSignature { script_sig, witness: Witness::p2wpkh(&signature, &pubkey.0) })
+        }
+        (KeySpend::P2pkh(pubkey), KeySignature::Ecdsa(signature)) => Ok(InputSignature {
+            script_sig: bitcoin::script::Builder::new().push_slice(signature.serialize()).push_key(pubkey).into_script(),
+            witness: Witness::default(),
+        }),
+        (KeySpend::P2tr(_), KeySignature::Schnorr(signature)) => Ok(InputSignature {
+            script_sig: ScriptBuf::new(),
+            witness: Witness::p2tr_key_spend(&signature),
+        }),
+        _ => Err("signature algorithm doesn't match the script type".to_string()),
+    }
+}
+
+pub fn apply_signature(tx: &mut Transaction, index: usize, signature: InputSignature) {
+    tx.input[index].script_sig = signature.script_sig;
+    tx.input[index].witness = signature.witness;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, Sequence, TxIn, Txid};
+    use bitcoin::hashes::Hash;
+    use crate::script::interpreter::{verify_input, verify_inputs};
+    use crate::transaction::parse_tx_hex;
+    use crate::wallet::{derive_addresses_from_key, KeyPair};
+
+    const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn unsigned(input_count: usize) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: (0..input_count)
+                .map(|vout| TxIn {
+                    previous_output: OutPoint::new(Txid::from_byte_array([7; 32]), vout as u32),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut { value: Amount::from_sat(90_000), script_pubkey: ScriptBuf::new_op_return([1u8; 8]) }],
+        }
+    }
+
+    fn prevouts_json(prevouts: &[TxOut]) -> String {
+        let prevouts: Vec<_> = prevouts
+            .iter()
+            .map(|prevout| serde_json::json!({"amountSat": prevout.value.to_sat(), "scriptPubkey": prevout.script_pubkey.to_hex_string()}))
+            .collect();
+        serde_json::Value::from(prevouts).to_string()
+    }
+
+    /// Runs every input through the interpreter, one at a time and as a batch.
+    fn assert_verifies(tx: &Transaction, prevouts: &[TxOut]) {
+        for index in 0..tx.input.len() {
+            if let Err(failure) = verify_input(tx, index, prevouts) {
+                panic!("input {} fails in {}: {}", index, failure.phase, failure.reason);
+            }
+        }
+        if let Err((index, failure)) = verify_inputs(tx, prevouts) {
+            panic!("input {} fails in the batch: {}", index, failure.reason);
+        }
+    }
+
+    #[test]
+    fn nested_segwit_spend_verifies() {
+        let keypair: KeyPair = serde_json::from_str(&derive_addresses_from_key(KEY, None, None).unwrap()).unwrap();
+        let nested = keypair.addresses.nested_segwit.unwrap();
+        assert_eq!(nested.kind, "p2sh-p2wpkh");
+        assert!(nested.address.starts_with('2'));
+        let prevout = TxOut { value: Amount::from_sat(100_000), script_pubkey: ScriptBuf::from_hex(&nested.script_pubkey).unwrap() };
+
+        let tx_hex = bitcoin::consensus::encode::serialize_hex(&unsigned(1));
+        let signed = sign_transaction(&tx_hex, KEY, 0, &nested.script_pubkey, 100_000, None, None).unwrap();
+        let tx = parse_tx_hex(&signed).unwrap();
+        assert_eq!(tx.input[0].script_sig.len(), 23, "a push of the 22-byte P2WPKH program");
+        assert_eq!(tx.input[0].witness.len(), 2);
+        assert_verifies(&tx, std::slice::from_ref(&prevout));
+
+        // Either half alone is what the mempool rejects.
+        let mut without_witness = tx.clone();
+        without_witness.input[0].witness = Witness::default();
+        assert!(verify_input(&without_witness, 0, std::slice::from_ref(&prevout)).is_err());
+        let mut without_script_sig = tx;
+        without_script_sig.input[0].script_sig = ScriptBuf::new();
+        assert!(verify_input(&without_script_sig, 0, &[prevout]).is_err());
+    }
+
+    #[test]
+    fn every_key_spend_type_verifies_after_sign_all() {
+        let keypair: KeyPair = serde_json::from_str(&derive_addresses_from_key(KEY, None, None).unwrap()).unwrap();
+        let addresses = keypair.addresses;
+        let prevouts: Vec<TxOut> = [&addresses.legacy, addresses.segwit.as_ref().unwrap(), addresses.nested_segwit.as_ref().unwrap(), &addresses.taproot]
+            .iter()
+            .map(|entry| TxOut { value: Amount::from_sat(25_000), script_pubkey: ScriptBuf::from_hex(&entry.script_pubkey).unwrap() })
+            .collect();
+
+        let tx_hex = bitcoin::consensus::encode::serialize_hex(&unsigned(prevouts.len()));
+        let result: SignAllResult = serde_json::from_str(&sign_all_inputs(&tx_hex, KEY, &prevouts_json(&prevouts), None).unwrap()).unwrap();
+        assert_eq!(result.signed_inputs, [0, 1, 2, 3]);
+        assert!(result.skipped_inputs.is_empty());
+
+        let tx = parse_tx_hex(&result.tx_hex).unwrap();
+        assert_verifies(&tx, &prevouts);
+
+        // A signature over the wrong amount fails, for segwit and taproot alike.
+        let mut wrong_amounts = prevouts.clone();
+        for prevout in &mut wrong_amounts {
+            prevout.value = Amount::from_sat(25_001);
+        }
+        for index in 1..tx.input.len() {
+            assert!(verify_input(&tx, index, &wrong_amounts).is_err(), "input {}", index);
+        }
+    }
+    /// 500 P2WPKH inputs signed with a fresh sighash cache per input, which recomputes
+    /// hashPrevouts, hashSequence and hashOutputs each time, against the one cache
+    /// `sign_all_inputs` and `BatchSigner` keep.
+    #[test]
+    #[ignore = "benchmark; run with --release -- --ignored --nocapture"]
+    fn benchmark_signing_500_inputs() {
+        const INPUTS: usize = 500;
+        let secp = Secp256k1::new();
+        let private_key = parse_private_key(KEY, None, Network::Testnet).unwrap();
+        let public = CompressedPublicKey::from_private_key(&secp, &private_key).unwrap();
+        let prevouts = vec![TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new_p2wpkh(&public.wpubkey_hash()) }; INPUTS];
+        let tx = unsigned(INPUTS);
+        let tx_hex = bitcoin::consensus::encode::serialize_hex(&tx);
+        let prevouts_json = prevouts_json(&prevouts);
+
+        let start = std::time::Instant::now();
+        let mut per_input = tx.clone();
+        for index in 0..INPUTS {
+            let signature = sign_input(&secp, &mut SighashCache::new(&tx), index, &prevouts[index], &prevouts, &private_key).unwrap();
+            apply_signature(&mut per_input, index, signature);
+        }
+        let fresh_caches = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let result: SignAllResult = serde_json::from_str(&sign_all_inputs(&tx_hex, KEY, &prevouts_json, None).unwrap()).unwrap();
+        let one_cache = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let mut signer = BatchSigner::new(&tx_hex, KEY, &prevouts_json).unwrap();
+        let batched = loop {
+            let step: serde_json::Value = serde_json::from_str(&signer.step(50, None).unwrap()).unwrap();
+            if let Some(result) = step.get("result").filter(|result| !result.is_null()) {
+                break serde_json::from_value::<SignAllResult>(result.clone()).unwrap();
+            }
+        };
+        let stepped = start.elapsed();
+
+        println!("{} inputs: fresh cache per input {:?}, sign_all_inputs {:?}, BatchSigner in steps of 50 {:?}", INPUTS, fresh_caches, one_cache, stepped);
+        // Low-R signing is deterministic, so all three must produce the same transaction.
+        assert_eq!(result.tx_hex, bitcoin::consensus::encode::serialize_hex(&per_input));
+        assert_eq!(batched.tx_hex, result.tx_hex);
+        assert_verifies(&parse_tx_hex(&result.tx_hex).unwrap(), &prevouts);
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use js_sys::{Function, Promise, Reflect};
+use bitcoin::key::TapTweak;
+use bitcoin::secp256k1::{self, ecdsa, schnorr, All, Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache, TapSighashType};
+use bitcoin::{Amount, ScriptBuf, TxOut};
+use crate::transaction::decode_tx_hex;
+use crate::transaction::provider::js_error_message;
+use crate::transaction::serialization::checked_serialize;
+use crate::transaction::sign::{
+    apply_signature, key_spend, key_spend_sighash, key_spend_signature, KeySignature, KeySpend, SignAllResult, SkippedInput,
+};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::path::{format_path, parse_path};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignerPrevout {
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    /// Where the signer's key for this input lives; inputs without one are skipped.
+    pub path: Option<String>,
+}
+
+/// A JS object with `getPublicKey(path)` and `signHash(path, sighash_hex, algorithm)`,
+/// both returning Promises of hex strings.
+struct ExternalSigner {
+    object: JsValue,
+    get_public_key: Function,
+    sign_hash: Function,
+    public_keys: HashMap<String, secp256k1::PublicKey>,
+}
+
+/// Signs every input the external signer (e.g. a hardware wallet over WebUSB) holds the
+/// key for. bitlab computes the sighashes, and checks each returned signature against
+/// the key before placing it, so a faulty device fails here rather than at broadcast.
+#[wasm_bindgen]
+pub async fn sign_all_inputs_with_signer(tx_hex: String, prevouts_json: String, signer: JsValue, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut tx = decode_tx_hex(&tx_hex)?;
+    let entries: Vec<SignerPrevout> = parse_json_list(&prevouts_json, "prevouts").map_err(|e| JsValue::from_str(&e))?;
+    if entries.len() != tx.input.len() {
+        return Err(JsValue::from_str(&format!(
+            "Expected {} prevouts (one per input), got {}",
+            tx.input.len(),
+            entries.len()
+        )));
+    }
+    let mut prevouts = Vec::with_capacity(entries.len());
+    let mut paths = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let script_pubkey = ScriptBuf::from_hex(&entry.script_pubkey)
+            .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey for prevout {}: {}", index, e)))?;
+        prevouts.push(TxOut { value: Amount::from_sat(entry.amount), script_pubkey });
+        let path = entry
+            .path
+            .as_deref()
+            .map(|path| parse_path(path, false).map(|parsed| format_path(&parsed.path, None)))
+            .transpose()
+            .map_err(|e| JsValue::from_str(&format!("Prevout {}: {}", index, e)))?;
+        paths.push(path);
+    }
+
+    let mut signer = ExternalSigner::new(signer)?;
+    let secp = Secp256k1::new();
+    let unsigned = tx.clone();
+    let mut cache = SighashCache::new(&unsigned);
+    let mut signatures = Vec::new();
+    let mut skipped_inputs = Vec::new();
+    for (index, (prevout, path)) in prevouts.iter().zip(&paths).enumerate() {
+        let Some(path) = path else {
+            skipped_inputs.push(SkippedInput { index, reason: "no derivation path given for the signer".to_string() });
+            continue;
+        };
+        let pubkey = signer.public_key(path).await?;
+        let spend = match key_spend(&secp, &prevout.script_pubkey, &pubkey, true, "signer's key") {
+            Ok(spend) => spend,
+            Err(reason) => {
+                skipped_inputs.push(SkippedInput { index, reason });
+                continue;
+            }
+        };
+        let sighash = key_spend_sighash(&mut cache, index, prevout, &prevouts, &spend)
+            .map_err(|e| JsValue::from_str(&format!("Failed to sign input {}: {}", index, e)))?;
+        let signature = signer.sign(&secp, index, path, &spend, &pubkey, &sighash).await?;
+        let signature = key_spend_signature(&spend, signature)
+            .map_err(|e| JsValue::from_str(&format!("Failed to sign input {}: {}", index, e)))?;
+        signatures.push((index, signature));
+    }
+
+    let signed_inputs = signatures.iter().map(|(index, _)| *index).collect();
+    for (index, signature) in signatures {
+        apply_signature(&mut tx, index, signature);
+    }
+    let tx_bytes = checked_serialize(&tx).map_err(|e| JsValue::from_str(&e))?;
+    let result = SignAllResult {
+        tx_hex: bytes_to_hex(&tx_bytes),
+        signed_inputs,
+        skipped_inputs,
+    };
+
+    to_json(&result, compact)
+}
+
+impl ExternalSigner {
+    fn new(object: JsValue) -> Result<ExternalSigner, JsValue> {
+        let method = |name: &str| {
+            Reflect::get(&object, &JsValue::from_str(name))
+                .ok()
+                .and_then(|value| value.dyn_into::<Function>().ok())
+                .ok_or_else(|| JsValue::from_str(&format!("Signer has no {} method", name)))
+        };
+        Ok(ExternalSigner {
+            get_public_key: method("getPublicKey")?,
+            sign_hash: method("signHash")?,
+            object,
+            public_keys: HashMap::new(),
+        })
+    }
+
+    /// The key at `path`, asked for once per path. Compressed (33 bytes) or uncompressed
+    /// (65 bytes) SEC encodings are accepted. For taproot it is the internal key: the
+    /// signer is expected to apply the BIP86 tweak when signing.
+    async fn public_key(&mut self, path: &str) -> Result<secp256k1::PublicKey, JsValue> {
+        if let Some(pubkey) = self.public_keys.get(path) {
+            return Ok(*pubkey);
+        }
+        let returned = call(&self.get_public_key, &self.object, &[JsValue::from_str(path)])
+            .await
+            .map_err(|e| JsValue::from_str(&format!("Signer getPublicKey({}) failed: {}", path, e)))?;
+        let pubkey = returned
+            .as_string()
+            .and_then(|hex| hex_to_bytes(&hex, HexMode::Lenient).ok())
+            .and_then(|bytes| secp256k1::PublicKey::from_slice(&bytes).ok())
+            .ok_or_else(|| JsValue::from_str(&format!("Signer getPublicKey({}) didn't return a public key as hex", path)))?;
+        self.public_keys.insert(path.to_string(), pubkey);
+        Ok(pubkey)
+    }
+
+    async fn sign(
+        &self,
+        secp: &Secp256k1<All>,
+        index: usize,
+        path: &str,
+        spend: &KeySpend,
+        pubkey: &secp256k1::PublicKey,
+        sighash: &Message,
+    ) -> Result<KeySignature, JsValue> {
+        let algorithm = match spend {
+            KeySpend::P2tr(_) => "schnorr",
+            _ => "ecdsa",
+        };
+        let args = [
+            JsValue::from_str(path),
+            JsValue::from_str(&bytes_to_hex(sighash.as_ref())),
+            JsValue::from_str(algorithm),
+        ];
+        let returned = call(&self.sign_hash, &self.object, &args)
+            .await
+            .map_err(|e| JsValue::from_str(&format!("Signer signHash failed for input {}: {}", index, e)))?;
+        let invalid = || JsValue::from_str(&format!("signer returned invalid signature for input {}", index));
+        let bytes = returned.as_string().and_then(|hex| hex_to_bytes(&hex, HexMode::Lenient).ok()).ok_or_else(invalid)?;
+
+        match spend {
+            KeySpend::P2tr(internal_key) => {
+                let signature = schnorr::Signature::from_slice(&bytes).map_err(|_| invalid())?;
+                let (output_key, _) = internal_key.tap_tweak(secp, None);
+                secp.verify_schnorr(&signature, sighash, &output_key.to_x_only_public_key()).map_err(|_| invalid())?;
+                Ok(KeySignature::Schnorr(bitcoin::taproot::Signature { signature, sighash_type: TapSighashType::Default }))
+            }
+            _ => {
+                let mut signature = parse_ecdsa(&bytes).ok_or_else(invalid)?;
+                // Devices may return high-S signatures, which are valid but don't relay.
+                signature.normalize_s();
+                secp.verify_ecdsa(sighash, &signature, pubkey).map_err(|_| invalid())?;
+                Ok(KeySignature::Ecdsa(bitcoin::ecdsa::Signature { signature, sighash_type: EcdsaSighashType::All }))
+            }
+        }
+    }
+}
+
+/// DER, DER with a trailing SIGHASH_ALL byte, or 64-byte compact.
+fn parse_ecdsa(bytes: &[u8]) -> Option<ecdsa::Signature> {
+    if bytes.len() == 64 {
+        return ecdsa::Signature::from_compact(bytes).ok();
+    }
+    let der = match bytes {
+        [0x30, len, ..] if bytes.len() == *len as usize + 3 && bytes.last() == Some(&0x01) => &bytes[..bytes.len() - 1],
+        _ => bytes,
+    };
+    ecdsa::Signature::from_der(der).ok()
+}
+
+async fn call(function: &Function, this: &JsValue, args: &[JsValue]) -> Result<JsValue, String> {
+    let returned = function
+        .apply(this, &args.iter().collect::<js_sys::Array>())
+        .map_err(|e| js_error_message(&e))?;
+    JsFuture::from(Promise::resolve(&returned)).await.map_err(|e| js_error_message(&e))
+}
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::secp256k1::{ecdsa, Message, Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::sighash::{EcdsaSighashType, TapSighashType};
+use bitcoin::{Amount, PublicKey, ScriptBuf, Transaction, TxOut};
+use crate::script::interpreter::{signature_checks, SignatureCheck};
+use crate::script::output_script_type;
+use crate::transaction::prevouts::Prevout;
+use crate::transaction::{decode_tx_hex, parse_prevouts, parse_tx_hex};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+/// Bumped if the record layout changes, so old archives stay readable.
+const RECORD_FORMAT: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureRecord {
+    /// `ecdsa_legacy`, `ecdsa_segwit_v0`, `schnorr_key_path` or `schnorr_tapscript`.
+    pub algorithm: String,
+    #[serde(alias = "sighash_type")]
+    pub sighash_type: String,
+    #[serde(alias = "sighash_flag")]
+    pub sighash_flag: u8,
+    /// The 32-byte digest the signature commits to, in signing byte order.
+    pub sighash: String,
+    pub signature: String,
+    /// For a key-path spend, the tweaked output key.
+    pub pubkey: String,
+    pub valid: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputSigningRecord {
+    #[serde(alias = "input_index")]
+    pub input_index: usize,
+    pub outpoint: String,
+    pub prevout: Prevout,
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    /// Whether the whole spend verifies, not just its signatures.
+    pub valid: bool,
+    pub error: Option<String>,
+    pub signatures: Vec<SignatureRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningRecord {
+    pub format: u32,
+    pub txid: String,
+    pub wtxid: String,
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    pub valid: bool,
+    pub inputs: Vec<InputSigningRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureRecheck {
+    pub valid: bool,
+    #[serde(alias = "recorded_valid")]
+    pub recorded_valid: bool,
+    /// Whether recomputing from `tx_hex` gives the recorded digest. None without a
+    /// transaction to recompute from.
+    #[serde(alias = "sighash_matches")]
+    pub sighash_matches: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputRecheck {
+    #[serde(alias = "input_index")]
+    pub input_index: usize,
+    pub signatures: Vec<SignatureRecheck>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningRecordCheck {
+    /// Every input lists at least one signature and they all verify against their
+    /// digests and keys.
+    pub valid: bool,
+    /// Nothing differs from what the record claims.
+    #[serde(alias = "matches_record")]
+    pub matches_record: bool,
+    /// Whether the digests were recomputed from the record's transaction and prevouts.
+    #[serde(alias = "transaction_checked")]
+    pub transaction_checked: bool,
+    pub inputs: Vec<InputRecheck>,
+    pub problems: Vec<String>,
+}
+
+/// A self-contained archive of what a signed transaction's inputs signed: for every
+/// signature checked during script verification, the sighash algorithm and digest, the
+/// signature and the key. Transactions that fail verification still get a record, with
+/// the failing inputs marked invalid. `prevouts_json` is `[{amount, script_pubkey}]`,
+/// one per input, and is kept in the record.
+#[wasm_bindgen]
+pub fn export_signing_record(tx_hex: &str, prevouts_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(tx_hex)?;
+    let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+
+    to_json(&signing_record(&tx, &prevouts), compact)
+}
+
+pub fn signing_record(tx: &Transaction, prevouts: &[TxOut]) -> SigningRecord {
+    let inputs: Vec<InputSigningRecord> = (0..tx.input.len())
+        .map(|index| {
+            let (checks, outcome) = signature_checks(tx, index, prevouts);
+            let prevout = &prevouts[index];
+            let error = outcome.err().map(|failure| format!("{}: {}", failure.phase, failure.reason));
+            InputSigningRecord {
+                input_index: index,
+                outpoint: tx.input[index].previous_output.to_string(),
+                prevout: Prevout {
+                    amount: prevout.value.to_sat(),
+                    script_pubkey: bytes_to_hex(prevout.script_pubkey.as_bytes()),
+                },
+                script_type: output_script_type(&prevout.script_pubkey).to_string(),
+                valid: error.is_none(),
+                error,
+                signatures: checks.iter().map(signature_record).collect(),
+            }
+        })
+        .collect();
+
+    SigningRecord {
+        format: RECORD_FORMAT,
+        txid: tx.compute_txid().to_string(),
+        wtxid: tx.compute_wtxid().to_string(),
+        tx_hex: bytes_to_hex(&bitcoin::consensus::serialize(tx)),
+        valid: inputs.iter().all(|input| input.valid),
+        inputs,
+    }
+}
+
+fn signature_record(check: &SignatureCheck) -> SignatureRecord {
+    SignatureRecord {
+        algorithm: check.algorithm.to_string(),
+        sighash_type: sighash_name(check.algorithm, check.sighash_flag),
+        sighash_flag: check.sighash_flag,
+        sighash: bytes_to_hex(&check.digest),
+        signature: bytes_to_hex(&check.signature),
+        pubkey: bytes_to_hex(&check.pubkey),
+        valid: check.valid,
+    }
+}
+
+fn sighash_name(algorithm: &str, flag: u8) -> String {
+    if algorithm.starts_with("schnorr") {
+        TapSighashType::from_consensus_u8(flag).map(|kind| kind.to_string()).unwrap_or_else(|_| format!("0x{:02x}", flag))
+    } else {
+        EcdsaSighashType::from_consensus(flag as u32).to_string()
+    }
+}
+
+/// Re-verifies a record from `export_signing_record` on its own: each signature against
+/// its recorded digest and key. The digests are also recomputed from the record's
+/// transaction and prevouts, so a record edited after export shows up as a mismatch.
+#[wasm_bindgen]
+pub fn verify_signing_record(record_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let record: SigningRecord = serde_json::from_str(record_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid signing record JSON: {}", e)))?;
+    if record.format != RECORD_FORMAT {
+        return Err(JsValue::from_str(&format!(
+            "Unsupported signing record format {} (expected {})",
+            record.format, RECORD_FORMAT
+        )));
+    }
+
+    to_json(&recheck(&record), compact)
+}
+
+pub fn recheck(record: &SigningRecord) -> SigningRecordCheck {
+    let secp = Secp256k1::verification_only();
+    let mut problems = Vec::new();
+    let recomputed = recompute(record, &mut problems);
+
+    let inputs: Vec<InputRecheck> = record
+        .inputs
+        .iter()
+        .map(|input| {
+            let checks = recomputed.as_ref().and_then(|inputs| inputs.get(input.input_index));
+            if let Some(checks) = checks {
+                if checks.len() != input.signatures.len() {
+                    problems.push(format!(
+                        "input {}: the transaction checks {} signatures, the record lists {}",
+                        input.input_index,
+                        checks.len(),
+                        input.signatures.len()
+                    ));
+                }
+            }
+            let signatures = input
+                .signatures
+                .iter()
+                .enumerate()
+                .map(|(index, signature)| {
+                    let name = format!("input {} signature {}", input.input_index, index);
+                    let valid = verify_signature(&secp, signature).unwrap_or_else(|e| {
+                        problems.push(format!("{}: {}", name, e));
+                        false
+                    });
+                    if valid != signature.valid {
+                        problems.push(format!(
+                            "{}: recorded as {} but {}",
+                            name,
+                            if signature.valid { "valid" } else { "invalid" },
+                            if valid { "verifies" } else { "doesn't verify" }
+                        ));
+                    }
+                    let sighash_matches = checks.map(|checks| {
+                        checks.get(index).is_some_and(|check| bytes_to_hex(&check.digest) == signature.sighash.trim().to_ascii_lowercase())
+                    });
+                    if sighash_matches == Some(false) {
+                        problems.push(format!("{}: sighash differs from the transaction's", name));
+                    }
+                    SignatureRecheck { valid, recorded_valid: signature.valid, sighash_matches }
+                })
+                .collect();
+            InputRecheck { input_index: input.input_index, signatures }
+        })
+        .collect();
+
+    SigningRecordCheck {
+        valid: inputs
+            .iter()
+            .all(|input| !input.signatures.is_empty() && input.signatures.iter().all(|signature| signature.valid)),
+        matches_record: problems.is_empty(),
+        transaction_checked: recomputed.is_some(),
+        inputs,
+        problems,
+    }
+}
+
+/// The transaction's own signature checks, per input. None, with a problem noted, when
+/// the record's transaction or prevouts can't be read.
+fn recompute(record: &SigningRecord, problems: &mut Vec<String>) -> Option<Vec<Vec<SignatureCheck>>> {
+    let tx = match parse_tx_hex(&record.tx_hex) {
+        Ok(tx) => tx,
+        Err(e) => {
+            problems.push(format!("tx_hex: {}", e));
+            return None;
+        }
+    };
+    if tx.compute_txid().to_string() != record.txid || tx.compute_wtxid().to_string() != record.wtxid {
+        problems.push("txid or wtxid doesn't match tx_hex".to_string());
+    }
+    if record.inputs.len() != tx.input.len() {
+        problems.push(format!("the record lists {} inputs, the transaction has {}", record.inputs.len(), tx.input.len()));
+        return None;
+    }
+    let mut prevouts = Vec::with_capacity(record.inputs.len());
+    for input in &record.inputs {
+        match hex_to_bytes(&input.prevout.script_pubkey, HexMode::Lenient) {
+            Ok(script) => prevouts.push(TxOut { value: Amount::from_sat(input.prevout.amount), script_pubkey: ScriptBuf::from_bytes(script) }),
+            Err(e) => {
+                problems.push(format!("input {}: invalid prevout script_pubkey hex: {}", input.input_index, e));
+                return None;
+            }
+        }
+    }
+    Some((0..tx.input.len()).map(|index| signature_checks(&tx, index, &prevouts).0).collect())
+}
+
+fn verify_signature<C: Verification>(secp: &Secp256k1<C>, record: &SignatureRecord) -> Result<bool, String> {
+    let decode = |hex: &str, what: &str| hex_to_bytes(hex, HexMode::Lenient).map_err(|e| format!("invalid {} hex: {}", what, e));
+    let digest: [u8; 32] = decode(&record.sighash, "sighash")?
+        .try_into()
+        .map_err(|_| "sighash must be 32 bytes".to_string())?;
+    let digest = Message::from_digest(digest);
+    let signature = decode(&record.signature, "signature")?;
+    let pubkey = decode(&record.pubkey, "pubkey")?;
+
+    match record.algorithm.as_str() {
+        "ecdsa_legacy" | "ecdsa_segwit_v0" => {
+            let (_, der) = signature.split_last().ok_or("empty signature")?;
+            let Ok(mut parsed) = ecdsa::Signature::from_der(der) else {
+                return Ok(false);
+            };
+            let Ok(key) = PublicKey::from_slice(&pubkey) else {
+                return Ok(false);
+            };
+            parsed.normalize_s();
+            Ok(secp.verify_ecdsa(&digest, &parsed, &key.inner).is_ok())
+        }
+        "schnorr_key_path" | "schnorr_tapscript" => {
+            let (Ok(parsed), Ok(key)) = (bitcoin::taproot::Signature::from_slice(&signature), XOnlyPublicKey::from_slice(&pubkey)) else {
+                return Ok(false);
+            };
+            Ok(secp.verify_schnorr(&parsed.signature, &digest, &key).is_ok())
+        }
+        other => Err(format!("unknown algorithm \"{}\"", other)),
+    }
+}
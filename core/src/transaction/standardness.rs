@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut};
+use crate::script::parse_multisig;
+use crate::transaction::historic::input_quirks;
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::units::{Amount, FeeRate};
+
+// Bitcoin Core's policy constants (policy/policy.h).
+const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+const MAX_STANDARD_TX_SIGOPS_COST: usize = 16_000;
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+const MAX_P2SH_SIGOPS: usize = 15;
+const MAX_BARE_MULTISIG_KEYS: usize = 3;
+const TX_MAX_STANDARD_VERSION: i32 = 3;
+const DEFAULT_BYTES_PER_SIGOP: u64 = 20;
+const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+/// Core's reject codes for a script that fails only the policy flags, or the consensus ones.
+pub const NONSTANDARD_SCRIPT_CODE: &str = "non-mandatory-script-verify-flag";
+pub const INVALID_SCRIPT_CODE: &str = "mandatory-script-verify-flag-failed";
+
+/// A node's relay policy. The defaults are Bitcoin Core's (before v30, which lifts the
+/// OP_RETURN limits).
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardnessOptions {
+    /// `-minrelaytxfee`.
+    #[serde(default = "default_min_relay_fee", rename = "minRelayFeeSatVb", alias = "min_relay_fee_sat_vb", alias = "min_relay_fee")]
+    pub min_relay_fee: FeeRate,
+    /// `-dustrelayfee`.
+    #[serde(default = "default_dust_relay_fee", rename = "dustRelayFeeSatVb", alias = "dust_relay_fee_sat_vb", alias = "dust_relay_fee")]
+    pub dust_relay_fee: FeeRate,
+    /// `-datacarriersize`: the largest OP_RETURN scriptPubKey, opcode and pushes included.
+    #[serde(default = "default_max_datacarrier_bytes", alias = "max_datacarrier_bytes")]
+    pub max_datacarrier_bytes: usize,
+    #[serde(default, alias = "allow_multiple_op_returns")]
+    pub allow_multiple_op_returns: bool,
+    /// `-permitbaremultisig`.
+    #[serde(default = "default_permit_bare_multisig", alias = "permit_bare_multisig")]
+    pub permit_bare_multisig: bool,
+}
+
+impl Default for StandardnessOptions {
+    fn default() -> Self {
+        StandardnessOptions {
+            min_relay_fee: default_min_relay_fee(),
+            dust_relay_fee: default_dust_relay_fee(),
+            max_datacarrier_bytes: default_max_datacarrier_bytes(),
+            allow_multiple_op_returns: false,
+            permit_bare_multisig: default_permit_bare_multisig(),
+        }
+    }
+}
+
+fn default_min_relay_fee() -> FeeRate {
+    FeeRate::from_sat_per_kwu(250)
+}
+
+fn default_dust_relay_fee() -> FeeRate {
+    FeeRate::from_sat_per_kwu(750)
+}
+
+fn default_max_datacarrier_bytes() -> usize {
+    83
+}
+
+fn default_permit_bare_multisig() -> bool {
+    true
+}
+
+/// One policy failure. `code` is Bitcoin Core's reject reason where it has one.
+#[derive(Serialize, Deserialize)]
+pub struct StandardnessViolation {
+    pub code: String,
+    pub message: String,
+    pub input: Option<usize>,
+    pub output: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardnessReport {
+    pub standard: bool,
+    pub weight: u64,
+    /// Sigop-adjusted, as the mempool measures it.
+    pub vsize: u64,
+    pub fee: Option<u64>,
+    #[serde(rename = "feeRateSatVb", alias = "fee_rate_sat_vb", alias = "fee_rate")]
+    pub fee_rate: Option<FeeRate>,
+    pub violations: Vec<StandardnessViolation>,
+    /// Checks that need prevouts and were skipped because none were given.
+    pub skipped: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Runs the stateless relay-policy checks a default Bitcoin Core node applies before
+/// accepting a transaction to its mempool. Input-side checks (fees, spent script types,
+/// witness limits, P2SH sigops) need `prevouts_json`. Chain- and mempool-dependent checks
+/// (finality, missing inputs, conflicts, package limits) aren't covered.
+#[wasm_bindgen]
+pub fn check_standardness(tx_hex: &str, prevouts_json: Option<String>, options_json: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("check_standardness", tx_hex.len(), || {
+        let tx = decode_tx_hex(tx_hex)?;
+        let prevouts: Option<Vec<TxOut>> = match prevouts_json {
+            Some(json) if !json.trim().is_empty() => Some(parse_prevouts(&json, tx.input.len())?),
+            _ => None,
+        };
+        let options: StandardnessOptions = match options_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+            None => StandardnessOptions::default(),
+        };
+        to_json(&standardness(&tx, prevouts.as_deref(), &options), compact)
+    })
+}
+
+pub fn standardness(tx: &Transaction, prevouts: Option<&[TxOut]>, options: &StandardnessOptions) -> StandardnessReport {
+    let mut violations = Vec::new();
+    let mut warnings = Vec::new();
+    let mut violation = |code: &str, message: String, input: Option<usize>, output: Option<usize>| {
+        violations.push(StandardnessViolation { code: code.to_string(), message, input, output });
+    };
+
+    if tx.is_coinbase() {
+        violation("coinbase", "Coinbase transactions can't be relayed".to_string(), None, None);
+    }
+    if tx.version.0 < 1 || tx.version.0 > TX_MAX_STANDARD_VERSION {
+        violation("version", format!("Version {} is outside the standard range 1 to {}", tx.version.0, TX_MAX_STANDARD_VERSION), None, None);
+    }
+
+    let weight = tx.weight().to_wu();
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        violation(
+            "tx-size",
+            format!("Weight {} exceeds the standard maximum of {} ({} vB)", weight, MAX_STANDARD_TX_WEIGHT, MAX_STANDARD_TX_WEIGHT / 4),
+            None,
+            None,
+        );
+    }
+    if tx.base_size() < MIN_STANDARD_TX_NONWITNESS_SIZE {
+        violation(
+            "tx-size-small",
+            format!("Non-witness size {} bytes is below the minimum of {}", tx.base_size(), MIN_STANDARD_TX_NONWITNESS_SIZE),
+            None,
+            None,
+        );
+    }
+
+    for (index, input) in tx.input.iter().enumerate() {
+        let size = input.script_sig.len();
+        if size > MAX_STANDARD_SCRIPTSIG_SIZE {
+            violation("scriptsig-size", format!("scriptSig is {} bytes, over the {}-byte limit", size, MAX_STANDARD_SCRIPTSIG_SIZE), Some(index), None);
+        }
+        if !input.script_sig.is_push_only() {
+            violation("scriptsig-not-pushonly", "scriptSig contains opcodes other than pushes".to_string(), Some(index), None);
+        }
+        // Signature and push encodings Core's policy script flags reject; lax DER has been
+        // consensus-invalid since BIP66.
+        for quirk in input_quirks(input) {
+            let code = match quirk.severity.as_str() {
+                "nonstandard" => NONSTANDARD_SCRIPT_CODE,
+                "invalid" => INVALID_SCRIPT_CODE,
+                _ => continue,
+            };
+            let place = if quirk.location == "witness" { "witness item" } else { "scriptSig push" };
+            violation(code, format!("{} {}: {}", place, quirk.item, quirk.message), Some(index), None);
+        }
+    }
+
+    let dust_relay_fee = options.dust_relay_fee.to_bitcoin();
+    let mut op_returns = 0;
+    let mut dust = Vec::new();
+    for (index, output) in tx.output.iter().enumerate() {
+        let script = &output.script_pubkey;
+        if let Some((code, reason)) = output_script_problem(script, options) {
+            violation(code, reason, None, Some(index));
+            continue;
+        }
+        if script.is_op_return() {
+            op_returns += 1;
+            continue;
+        }
+        let threshold = script.minimal_non_dust_custom(dust_relay_fee).to_sat();
+        if output.value.to_sat() < threshold {
+            dust.push((index, output.value.to_sat(), threshold));
+        }
+    }
+    if op_returns > 1 && !options.allow_multiple_op_returns {
+        violation("multi-op-return", format!("{} OP_RETURN outputs; only one is standard", op_returns), None, None);
+    }
+
+    let fee = prevouts.and_then(|prevouts| {
+        let spent: u64 = prevouts.iter().map(|prevout| prevout.value.to_sat()).sum();
+        let paid: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+        spent.checked_sub(paid)
+    });
+    // Bitcoin Core allows a single dust output in a zero-fee transaction (ephemeral dust), to be spent by a child.
+    if dust.len() == 1 && fee == Some(0) {
+        warnings.push(format!(
+            "Output {} is dust; a zero-fee transaction may carry one dust output, but it only relays in a package with a child that spends it",
+            dust[0].0
+        ));
+    } else {
+        for (index, value, threshold) in dust {
+            violation("dust", format!("{} sats is below the {}-sat dust threshold for this script", value, threshold), None, Some(index));
+        }
+    }
+
+    let mut skipped = Vec::new();
+    let sigop_cost = match prevouts {
+        Some(prevouts) => {
+            for (index, (input, prevout)) in tx.input.iter().zip(prevouts).enumerate() {
+                if let Some(reason) = input_problem(input, &prevout.script_pubkey) {
+                    violation("bad-txns-nonstandard-inputs", reason, Some(index), None);
+                }
+                if let Some(reason) = witness_problem(input, &prevout.script_pubkey) {
+                    violation("bad-witness-nonstandard", reason, Some(index), None);
+                }
+            }
+            let spent: HashMap<OutPoint, &TxOut> =
+                tx.input.iter().map(|input| input.previous_output).zip(prevouts).collect();
+            tx.total_sigop_cost(|outpoint| spent.get(outpoint).map(|prevout| (*prevout).clone()))
+        }
+        None => {
+            skipped.extend(
+                ["min relay fee", "spent script types and P2SH sigops", "witness size limits", "sigops in redeem and witness scripts"]
+                    .map(str::to_string),
+            );
+            tx.total_sigop_cost(|_| None)
+        }
+    };
+    if sigop_cost > MAX_STANDARD_TX_SIGOPS_COST {
+        violation(
+            "bad-txns-too-many-sigops",
+            format!("Sigop cost {} exceeds the standard maximum of {}", sigop_cost, MAX_STANDARD_TX_SIGOPS_COST),
+            None,
+            None,
+        );
+    }
+
+    let vsize = weight.max(sigop_cost as u64 * DEFAULT_BYTES_PER_SIGOP).div_ceil(4);
+    if let Some(fee) = fee {
+        // CFeeRate::GetFee rounds up, working in sat/kvB.
+        let required = (vsize * (options.min_relay_fee.sat_per_vb() * 1000.0).round() as u64).div_ceil(1000);
+        if fee < required {
+            violation(
+                "min relay fee not met",
+                format!("min relay fee not met, {} < {}: {} vB needs {}", fee, required, vsize, options.min_relay_fee),
+                None,
+                None,
+            );
+        }
+    } else if prevouts.is_some() {
+        violation("bad-txns-in-belowout", "Outputs total more than the inputs spend".to_string(), None, None);
+    }
+    if tx.input.iter().any(|input| input.script_sig.is_empty() && input.witness.is_empty()) {
+        warnings.push("Some inputs are unsigned, so size and fee rate are measured before signatures are added".to_string());
+    }
+
+    StandardnessReport {
+        standard: violations.is_empty(),
+        weight,
+        vsize,
+        fee,
+        fee_rate: fee.map(|fee| FeeRate::from_fee(Amount::from_sat(fee), vsize as f64)),
+        violations,
+        skipped,
+        warnings,
+    }
+}
+
+/// Reject code and reason when an output script isn't standard (Core's `IsStandard`).
+fn output_script_problem(script: &Script, options: &StandardnessOptions) -> Option<(&'static str, String)> {
+    if script.is_op_return() {
+        if !Script::from_bytes(&script.as_bytes()[1..]).is_push_only() {
+            return Some(("scriptpubkey", "OP_RETURN output contains opcodes other than pushes".to_string()));
+        }
+        if script.len() > options.max_datacarrier_bytes {
+            return Some(("scriptpubkey", format!(
+                "OP_RETURN script is {} bytes, over the {}-byte data carrier limit",
+                script.len(),
+                options.max_datacarrier_bytes
+            )));
+        }
+        return None;
+    }
+    if let Some(multisig) = parse_multisig(script) {
+        if multisig.pubkeys.len() > MAX_BARE_MULTISIG_KEYS {
+            return Some(("scriptpubkey", format!(
+                "Bare multisig with {} keys; at most {} is standard",
+                multisig.pubkeys.len(),
+                MAX_BARE_MULTISIG_KEYS
+            )));
+        }
+        if !options.permit_bare_multisig {
+            return Some(("bare-multisig", "Bare multisig is disabled (permit_bare_multisig is off)".to_string()));
+        }
+        return None;
+    }
+    if script.is_p2pkh() || script.is_p2sh() || script.is_p2pk() || script.is_p2wpkh() || script.is_p2wsh() {
+        return None;
+    }
+    match script.witness_version() {
+        // Version 0 programs must be exactly 20 or 32 bytes; those returned above.
+        Some(version) if version.to_num() == 0 => Some(("scriptpubkey", "Witness v0 program of invalid length".to_string())),
+        Some(_) => None,
+        None => Some(("scriptpubkey", "Nonstandard output script".to_string())),
+    }
+}
+
+/// Core's `AreInputsStandard` for one input.
+fn input_problem(input: &TxIn, spent: &Script) -> Option<String> {
+    if spent.is_p2sh() {
+        let redeem = input.script_sig.redeem_script()?;
+        let sigops = redeem.count_sigops();
+        if sigops > MAX_P2SH_SIGOPS {
+            return Some(format!("Redeem script has {} sigops, over the limit of {}", sigops, MAX_P2SH_SIGOPS));
+        }
+        return None;
+    }
+    let standard = spent.is_p2pkh()
+        || spent.is_p2pk()
+        || spent.is_p2wpkh()
+        || spent.is_p2wsh()
+        || spent.is_p2tr()
+        || is_anchor(spent)
+        || parse_multisig(spent).is_some();
+    if standard {
+        None
+    } else if spent.is_witness_program() {
+        Some("Spends a witness program of an undefined version, which is reserved for future soft forks".to_string())
+    } else {
+        Some("Spends a nonstandard output script".to_string())
+    }
+}
+
+/// Core's `IsWitnessStandard` for one input.
+fn witness_problem(input: &TxIn, spent: &Script) -> Option<String> {
+    let witness = &input.witness;
+    if witness.is_empty() {
+        return None;
+    }
+    let (program, nested) = if spent.is_p2sh() {
+        match input.script_sig.redeem_script() {
+            Some(redeem) => (redeem, true),
+            None => return Some("Witness data on a P2SH input whose scriptSig has no redeem script".to_string()),
+        }
+    } else {
+        (spent, false)
+    };
+    if !program.is_witness_program() {
+        return Some("Witness data on an input that spends a non-witness script".to_string());
+    }
+    if is_anchor(program) && !nested {
+        return Some("Pay-to-anchor inputs must have an empty witness".to_string());
+    }
+
+    if program.is_p2wsh() {
+        let items: Vec<&[u8]> = witness.iter().collect();
+        let (script, stack) = items.split_last().expect("witness is not empty");
+        if script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+            return Some(format!("Witness script is {} bytes, over the {}-byte limit", script.len(), MAX_STANDARD_P2WSH_SCRIPT_SIZE));
+        }
+        if stack.len() > MAX_STANDARD_P2WSH_STACK_ITEMS {
+            return Some(format!("{} witness stack items, over the limit of {}", stack.len(), MAX_STANDARD_P2WSH_STACK_ITEMS));
+        }
+        if let Some(item) = stack.iter().find(|item| item.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE) {
+            return Some(format!("Witness stack item of {} bytes, over the {}-byte limit", item.len(), MAX_STANDARD_P2WSH_STACK_ITEM_SIZE));
+        }
+    }
+
+    if program.is_p2tr() && !nested {
+        if witness.taproot_annex().is_some() {
+            return Some("Taproot annex is reserved and nonstandard".to_string());
+        }
+        let items: Vec<&[u8]> = witness.iter().collect();
+        if items.len() >= 2 {
+            let control = items[items.len() - 1];
+            if control.first().map(|byte| byte & 0xfe) == Some(TAPSCRIPT_LEAF_VERSION) {
+                let stack = &items[..items.len() - 2];
+                if let Some(item) = stack.iter().find(|item| item.len() > MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE) {
+                    return Some(format!(
+                        "Tapscript stack item of {} bytes, over the {}-byte limit",
+                        item.len(),
+                        MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE
+                    ));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Pay-to-anchor: `OP_1 <0x4e73>`.
+fn is_anchor(script: &Script) -> bool {
+    script.as_bytes() == [0x51, 0x02, 0x4e, 0x73]
+}
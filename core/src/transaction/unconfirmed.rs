@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{OutPoint, Transaction, Txid};
+use crate::transaction::{decode_tx_hex, parse_prevouts};
+use crate::utils::bytes_to_hex;
+use crate::utils::json::to_json;
+
+const STATE_VERSION: u32 = 1;
+/// Bitcoin Core's default `-limitancestorcount` and `-limitdescendantcount`; counts
+/// include the transaction itself.
+const CHAIN_COUNT_LIMIT: usize = 25;
+/// Core's default `-limitancestorsize` and `-limitdescendantsize`, 101 kvB.
+const CHAIN_VSIZE_LIMIT: u64 = 101_000;
+/// BIP125 rule 5: a replacement may evict at most this many transactions.
+const MAX_EVICTIONS: usize = 100;
+
+struct Tracked {
+    tx: Transaction,
+    /// Each input's amount when it came with the transaction's prevouts. Inputs spending
+    /// another tracked transaction are looked up there instead.
+    input_values: Vec<Option<u64>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackedState {
+    #[serde(alias = "tx_hex")]
+    tx_hex: String,
+    #[serde(alias = "input_values")]
+    input_values: Vec<Option<u64>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GraphState {
+    version: u32,
+    transactions: Vec<TrackedState>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageStats {
+    pub txid: String,
+    pub vsize: u64,
+    /// None when an input's amount is unknown.
+    pub fee: Option<u64>,
+    /// Counts and sizes include the transaction itself, as Core's limits do.
+    #[serde(alias = "ancestor_count")]
+    pub ancestor_count: usize,
+    #[serde(alias = "ancestor_vsize")]
+    pub ancestor_vsize: u64,
+    #[serde(alias = "ancestor_fees")]
+    pub ancestor_fees: Option<u64>,
+    #[serde(alias = "descendant_count")]
+    pub descendant_count: usize,
+    #[serde(alias = "descendant_vsize")]
+    pub descendant_vsize: u64,
+    #[serde(alias = "descendant_fees")]
+    pub descendant_fees: Option<u64>,
+    /// Whether a new transaction spending this one would stay within the default
+    /// ancestor and descendant limits.
+    #[serde(alias = "can_spend")]
+    pub can_spend: bool,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Conflict {
+    pub txid: String,
+    /// The outpoints both it and the replacement spend.
+    pub outpoints: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacementImpact {
+    #[serde(alias = "replacement_txid")]
+    pub replacement_txid: String,
+    /// Tracked transactions spending an input of the replacement.
+    pub conflicts: Vec<Conflict>,
+    /// The conflicts and all their descendants: everything the replacement evicts.
+    pub evicted: Vec<String>,
+    #[serde(alias = "evicted_vsize")]
+    pub evicted_vsize: u64,
+    /// What the replacement has to pay at least (BIP125 rule 3). None when an evicted
+    /// transaction's fee is unknown.
+    #[serde(alias = "evicted_fees")]
+    pub evicted_fees: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+/// The user's unconfirmed transactions and how they chain, so a balance built on them can
+/// drop what a fee bump invalidates. A transaction is a child of another when it spends one
+/// of its outputs. Removing a transaction removes its descendants too, since they can't
+/// confirm without it.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct UnconfirmedGraph {
+    transactions: BTreeMap<Txid, Tracked>,
+    /// Which tracked transaction spends each outpoint.
+    spends: BTreeMap<OutPoint, Txid>,
+}
+
+#[wasm_bindgen]
+impl UnconfirmedGraph {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> UnconfirmedGraph {
+        UnconfirmedGraph::default()
+    }
+
+    /// Tracks a transaction and returns its txid. `prevouts_json` (one `{amount,
+    /// script_pubkey}` per input) is only needed for fees, and only for inputs that don't
+    /// spend another tracked transaction. A transaction spending an outpoint a tracked one
+    /// already spends is refused: check it with `affected_by_replacement` and remove what
+    /// it replaces first. Adding a tracked transaction again only updates its prevouts.
+    pub fn add_tx(&mut self, tx_hex: &str, prevouts_json: Option<String>) -> Result<String, JsValue> {
+        let tx = decode_tx_hex(tx_hex)?;
+        if tx.is_coinbase() {
+            return Err(JsValue::from_str("A coinbase transaction is never unconfirmed"));
+        }
+        let input_values = match prevouts_json {
+            Some(json) => parse_prevouts(&json, tx.input.len())?.iter().map(|prevout| Some(prevout.value.to_sat())).collect(),
+            None => vec![None; tx.input.len()],
+        };
+        let txid = tx.compute_txid();
+        if let Some(conflict) = self.conflicts_of(&tx).into_iter().find(|(conflict, _)| *conflict != txid) {
+            return Err(JsValue::from_str(&format!(
+                "{} spends {}, which tracked transaction {} already spends; see affected_by_replacement and remove_tx the replaced one first",
+                txid, conflict.1[0], conflict.0
+            )));
+        }
+
+        if let Some(tracked) = self.transactions.get_mut(&txid) {
+            if input_values.iter().any(Option::is_some) {
+                tracked.input_values = input_values;
+            }
+            return Ok(txid.to_string());
+        }
+        for input in &tx.input {
+            self.spends.insert(input.previous_output, txid);
+        }
+        self.transactions.insert(txid, Tracked { tx, input_values });
+        Ok(txid.to_string())
+    }
+
+    /// Stops tracking `txid` and everything descending from it, for a transaction that
+    /// was replaced, evicted or abandoned. Returns the removed txids, `txid` first.
+    /// Use `confirm_tx` instead for one that confirmed, as its descendants stay valid.
+    pub fn remove_tx(&mut self, txid: &str, compact: Option<bool>) -> Result<String, JsValue> {
+        let txid = self.tracked_txid(txid)?;
+        let mut removed = vec![txid];
+        removed.extend(self.descendants_of(txid));
+        for removed_txid in &removed {
+            self.forget(removed_txid);
+        }
+        let removed: Vec<String> = removed.iter().map(Txid::to_string).collect();
+        to_json(&removed, compact)
+    }
+
+    /// Stops tracking a transaction that confirmed. Its descendants stay: they are still
+    /// unconfirmed, but no longer have it as an ancestor. Its children keep the amounts
+    /// they spend from it, so their fees stay known.
+    pub fn confirm_tx(&mut self, txid: &str) -> Result<(), JsValue> {
+        let txid = self.tracked_txid(txid)?;
+        let outputs = self.transactions[&txid].tx.output.clone();
+        for child in self.children(&txid) {
+            if let Some(tracked) = self.transactions.get_mut(&child) {
+                for (input, value) in tracked.tx.input.iter().zip(tracked.input_values.iter_mut()) {
+                    if input.previous_output.txid == txid && value.is_none() {
+                        *value = outputs.get(input.previous_output.vout as usize).map(|output| output.value.to_sat());
+                    }
+                }
+            }
+        }
+        self.forget(&txid);
+        Ok(())
+    }
+
+    /// Every tracked transaction that spends an output of `txid`, directly or through
+    /// others, nearest first.
+    pub fn descendants(&self, txid: &str, compact: Option<bool>) -> Result<String, JsValue> {
+        let descendants: Vec<String> = self.descendants_of(self.tracked_txid(txid)?).iter().map(Txid::to_string).collect();
+        to_json(&descendants, compact)
+    }
+
+    /// Every tracked transaction `txid` spends from, directly or through others, nearest
+    /// first.
+    pub fn ancestors(&self, txid: &str, compact: Option<bool>) -> Result<String, JsValue> {
+        let ancestors: Vec<String> = self.ancestors_of(self.tracked_txid(txid)?).iter().map(Txid::to_string).collect();
+        to_json(&ancestors, compact)
+    }
+
+    /// What tracking `replacement_tx_hex` would cost: the tracked transactions spending
+    /// any of its inputs, and their descendants, which all leave the mempool if it is
+    /// accepted. Nothing is changed; `remove_tx` on each conflict evicts them.
+    pub fn affected_by_replacement(&self, replacement_tx_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+        let replacement = decode_tx_hex(replacement_tx_hex)?;
+        let replacement_txid = replacement.compute_txid();
+        let conflicts: Vec<(Txid, Vec<OutPoint>)> =
+            self.conflicts_of(&replacement).into_iter().filter(|(txid, _)| *txid != replacement_txid).collect();
+
+        let mut evicted: Vec<Txid> = Vec::new();
+        for (txid, _) in &conflicts {
+            for evicted_txid in std::iter::once(*txid).chain(self.descendants_of(*txid)) {
+                if !evicted.contains(&evicted_txid) {
+                    evicted.push(evicted_txid);
+                }
+            }
+        }
+        let evicted_vsize = evicted.iter().map(|txid| self.vsize(txid)).sum();
+        let evicted_fees = evicted.iter().map(|txid| self.fee(txid)).sum();
+        let mut warnings = Vec::new();
+        if evicted.len() > MAX_EVICTIONS {
+            warnings.push(format!(
+                "The replacement would evict {} transactions; BIP125 allows at most {}, so nodes will refuse it",
+                evicted.len(),
+                MAX_EVICTIONS
+            ));
+        }
+        if self.transactions.contains_key(&replacement_txid) {
+            warnings.push(format!("{} is already tracked, so it replaces nothing", replacement_txid));
+        }
+
+        let impact = ReplacementImpact {
+            replacement_txid: replacement_txid.to_string(),
+            conflicts: conflicts
+                .into_iter()
+                .map(|(txid, outpoints)| Conflict { txid: txid.to_string(), outpoints: outpoints.iter().map(OutPoint::to_string).collect() })
+                .collect(),
+            evicted: evicted.iter().map(Txid::to_string).collect(),
+            evicted_vsize,
+            evicted_fees,
+            warnings,
+        };
+        to_json(&impact, compact)
+    }
+
+    /// `txid`'s ancestor and descendant package against Core's default chain limits (25
+    /// transactions and 101 kvB either way), and whether one more spend of it still fits.
+    pub fn package_stats(&self, txid: &str, compact: Option<bool>) -> Result<String, JsValue> {
+        let txid = self.tracked_txid(txid)?;
+        let ancestors: Vec<Txid> = std::iter::once(txid).chain(self.ancestors_of(txid)).collect();
+        let descendants: Vec<Txid> = std::iter::once(txid).chain(self.descendants_of(txid)).collect();
+        let vsize = self.vsize(&txid);
+        let ancestor_vsize: u64 = ancestors.iter().map(|txid| self.vsize(txid)).sum();
+        let descendant_vsize: u64 = descendants.iter().map(|txid| self.vsize(txid)).sum();
+
+        let mut warnings = Vec::new();
+        if ancestors.len() >= CHAIN_COUNT_LIMIT {
+            warnings.push(format!(
+                "{} unconfirmed ancestors including itself: a child would have more than the {} nodes allow",
+                ancestors.len(),
+                CHAIN_COUNT_LIMIT
+            ));
+        }
+        if ancestor_vsize >= CHAIN_VSIZE_LIMIT {
+            warnings.push(format!("Ancestors total {} vB, at Core's {} vB limit", ancestor_vsize, CHAIN_VSIZE_LIMIT));
+        }
+        // A new child counts as a descendant of every ancestor, so each one needs room.
+        for ancestor in &ancestors {
+            let count = 1 + self.descendants_of(*ancestor).len();
+            if count >= CHAIN_COUNT_LIMIT {
+                warnings.push(format!(
+                    "{} already has {} descendants including itself: another spend would exceed the {} nodes allow",
+                    ancestor, count, CHAIN_COUNT_LIMIT
+                ));
+            }
+        }
+
+        let stats = PackageStats {
+            txid: txid.to_string(),
+            vsize,
+            fee: self.fee(&txid),
+            ancestor_count: ancestors.len(),
+            ancestor_vsize,
+            ancestor_fees: ancestors.iter().map(|txid| self.fee(txid)).sum(),
+            descendant_count: descendants.len(),
+            descendant_vsize,
+            descendant_fees: descendants.iter().map(|txid| self.fee(txid)).sum(),
+            can_spend: warnings.is_empty(),
+            warnings,
+        };
+        to_json(&stats, compact)
+    }
+
+    /// The tracked txids, in txid order.
+    pub fn txids(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let txids: Vec<String> = self.transactions.keys().map(Txid::to_string).collect();
+        to_json(&txids, compact)
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    pub fn export_state(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let state = GraphState {
+            version: STATE_VERSION,
+            transactions: self
+                .transactions
+                .values()
+                .map(|tracked| TrackedState {
+                    tx_hex: bytes_to_hex(&bitcoin::consensus::serialize(&tracked.tx)),
+                    input_values: tracked.input_values.clone(),
+                })
+                .collect(),
+        };
+
+        to_json(&state, compact)
+    }
+
+    pub fn import_state(state_json: &str) -> Result<UnconfirmedGraph, JsValue> {
+        let state: GraphState = serde_json::from_str(state_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid unconfirmed graph state JSON: {}", e)))?;
+        if state.version != STATE_VERSION {
+            return Err(JsValue::from_str(&format!("Unsupported unconfirmed graph state version {}", state.version)));
+        }
+
+        let mut graph = UnconfirmedGraph::new();
+        for (index, tracked) in state.transactions.into_iter().enumerate() {
+            let tx = decode_tx_hex(&tracked.tx_hex)?;
+            if tracked.input_values.len() != tx.input.len() {
+                return Err(JsValue::from_str(&format!(
+                    "transactions[{}]: {} input values for {} inputs",
+                    index,
+                    tracked.input_values.len(),
+                    tx.input.len()
+                )));
+            }
+            let txid = tx.compute_txid();
+            for input in &tx.input {
+                graph.spends.insert(input.previous_output, txid);
+            }
+            graph.transactions.insert(txid, Tracked { tx, input_values: tracked.input_values });
+        }
+        Ok(graph)
+    }
+}
+
+impl UnconfirmedGraph {
+    fn tracked_txid(&self, txid: &str) -> Result<Txid, JsValue> {
+        let parsed = Txid::from_str(txid.trim()).map_err(|e| JsValue::from_str(&format!("Invalid txid {:?}: {}", txid, e)))?;
+        if !self.transactions.contains_key(&parsed) {
+            return Err(JsValue::from_str(&format!("{} isn't tracked", parsed)));
+        }
+        Ok(parsed)
+    }
+
+    fn forget(&mut self, txid: &Txid) {
+        if let Some(tracked) = self.transactions.remove(txid) {
+            for input in &tracked.tx.input {
+                if self.spends.get(&input.previous_output) == Some(txid) {
+                    self.spends.remove(&input.previous_output);
+                }
+            }
+        }
+    }
+
+    /// Tracked transactions spending any of `tx`'s inputs, with the shared outpoints.
+    fn conflicts_of(&self, tx: &Transaction) -> Vec<(Txid, Vec<OutPoint>)> {
+        let mut conflicts: BTreeMap<Txid, Vec<OutPoint>> = BTreeMap::new();
+        for input in &tx.input {
+            if let Some(spender) = self.spends.get(&input.previous_output) {
+                conflicts.entry(*spender).or_default().push(input.previous_output);
+            }
+        }
+        conflicts.into_iter().collect()
+    }
+
+    fn children(&self, txid: &Txid) -> BTreeSet<Txid> {
+        let outputs = self.transactions.get(txid).map_or(0, |tracked| tracked.tx.output.len());
+        (0..outputs as u32).filter_map(|vout| self.spends.get(&OutPoint { txid: *txid, vout }).copied()).collect()
+    }
+
+    fn parents(&self, txid: &Txid) -> BTreeSet<Txid> {
+        self.transactions.get(txid).map_or_else(BTreeSet::new, |tracked| {
+            tracked
+                .tx
+                .input
+                .iter()
+                .map(|input| input.previous_output.txid)
+                .filter(|parent| self.transactions.contains_key(parent))
+                .collect()
+        })
+    }
+
+    fn descendants_of(&self, txid: Txid) -> Vec<Txid> {
+        self.walk(txid, |graph, txid| graph.children(txid))
+    }
+
+    fn ancestors_of(&self, txid: Txid) -> Vec<Txid> {
+        self.walk(txid, |graph, txid| graph.parents(txid))
+    }
+
+    /// Breadth-first from `start`, which isn't included.
+    fn walk(&self, start: Txid, next: impl Fn(&UnconfirmedGraph, &Txid) -> BTreeSet<Txid>) -> Vec<Txid> {
+        let mut seen = BTreeSet::from([start]);
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(txid) = queue.pop_front() {
+            for found in next(self, &txid) {
+                if seen.insert(found) {
+                    order.push(found);
+                    queue.push_back(found);
+                }
+            }
+        }
+        order
+    }
+
+    fn vsize(&self, txid: &Txid) -> u64 {
+        self.transactions.get(txid).map_or(0, |tracked| tracked.tx.vsize() as u64)
+    }
+
+    /// Inputs minus outputs, with inputs from tracked parents read off the parent.
+    fn fee(&self, txid: &Txid) -> Option<u64> {
+        let tracked = self.transactions.get(txid)?;
+        let mut total_in = 0u64;
+        for (input, value) in tracked.tx.input.iter().zip(&tracked.input_values) {
+            let outpoint = input.previous_output;
+            let value = value.or_else(|| {
+                let parent = self.transactions.get(&outpoint.txid)?;
+                parent.tx.output.get(outpoint.vout as usize).map(|output| output.value.to_sat())
+            })?;
+            total_in = total_in.checked_add(value)?;
+        }
+        let total_out = tracked.tx.output.iter().try_fold(0u64, |total, output| total.checked_add(output.value.to_sat()))?;
+        total_in.checked_sub(total_out)
+    }
+}
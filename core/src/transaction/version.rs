@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::transaction::Version;
+use bitcoin::Transaction;
+use crate::transaction::decode_tx_hex;
+use crate::utils::json::to_json;
+
+/// BIP431: a TRUC transaction may be at most 10,000 vB.
+pub const TRUC_MAX_VSIZE: u64 = 10_000;
+/// BIP431: a TRUC transaction spending an unconfirmed TRUC parent may be at most 1,000 vB.
+pub const TRUC_CHILD_MAX_VSIZE: u64 = 1_000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrucCheck {
+    pub version: i32,
+    #[serde(alias = "is_truc")]
+    pub is_truc: bool,
+    pub vsize: u64,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[wasm_bindgen]
+pub fn check_truc(tx_hex: &str, parent_tx_hex: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(tx_hex)?;
+    let parent = match parent_tx_hex {
+        Some(hex) => Some(decode_tx_hex(&hex)?),
+        None => None,
+    };
+
+    let vsize = tx.vsize() as u64;
+    let (errors, mut warnings) = truc_violations(&tx, vsize, parent.as_ref());
+    if tx.version == Version(3) && tx.input.iter().all(|input| input.witness.is_empty()) {
+        warnings.push("transaction looks unsigned; its size will grow once signed".to_string());
+    }
+
+    let result = TrucCheck {
+        version: tx.version.0,
+        is_truc: tx.version == Version(3),
+        vsize,
+        errors,
+        warnings,
+    };
+
+    to_json(&result, compact)
+}
+
+pub fn parse_version(version: Option<u32>) -> Result<Version, String> {
+    match version.unwrap_or(2) {
+        1 => Ok(Version::ONE),
+        2 => Ok(Version::TWO),
+        3 => Ok(Version(3)),
+        other => Err(format!("Unsupported transaction version {} (expected 1, 2 or 3)", other)),
+    }
+}
+
+#[cfg(feature = "decode")]
+pub fn describe_version(version: Version) -> &'static str {
+    match version.0 {
+        1 => "v1 (original; no BIP68 relative locktimes)",
+        2 => "v2 (BIP68 relative locktimes)",
+        3 => "v3 (TRUC, BIP431 topologically restricted)",
+        _ => "nonstandard version",
+    }
+}
+
+/// TRUC rules that can be checked without mempool access. `vsize` is passed in so
+/// callers can supply an estimate of the signed size for unsigned transactions.
+pub fn truc_violations(tx: &Transaction, vsize: u64, parent: Option<&Transaction>) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let is_truc = tx.version == Version(3);
+
+    if is_truc && vsize > TRUC_MAX_VSIZE {
+        errors.push(format!("TRUC transaction is {} vB, above the {} vB limit", vsize, TRUC_MAX_VSIZE));
+    }
+
+    if let Some(parent) = parent {
+        let parent_txid = parent.compute_txid();
+        let spends_parent = tx.input.iter().any(|input| input.previous_output.txid == parent_txid);
+        let parent_is_truc = parent.version == Version(3);
+
+        if !spends_parent {
+            warnings.push("transaction does not spend any output of the provided parent".to_string());
+        } else if is_truc && !parent_is_truc {
+            errors.push("TRUC transaction cannot spend an unconfirmed non-TRUC parent".to_string());
+        } else if !is_truc && parent_is_truc {
+            errors.push("non-TRUC transaction cannot spend an unconfirmed TRUC parent".to_string());
+        } else if is_truc && vsize > TRUC_CHILD_MAX_VSIZE {
+            errors.push(format!(
+                "TRUC child is {} vB, above the {} vB limit for spending an unconfirmed TRUC parent",
+                vsize, TRUC_CHILD_MAX_VSIZE
+            ));
+        }
+    }
+
+    if is_truc {
+        warnings.push("TRUC allows at most one unconfirmed ancestor and one descendant; this cannot be checked offline".to_string());
+    }
+
+    (errors, warnings)
+}
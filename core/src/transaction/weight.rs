@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{ScriptBuf, Transaction};
+use crate::transaction::TransactionInput;
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::varint::encode_compact_size;
+use crate::utils::json::to_json;
+
+// Signature sizes including the sighash byte. ECDSA signing grinds for low R, so a DER
+// signature is at most 71 bytes rather than 72 (Bitcoin Core assumes the same for its own keys).
+const ECDSA_SIGNATURE_BYTES: u64 = 71;
+const SCHNORR_SIGNATURE_BYTES: u64 = 64;
+const COMPRESSED_PUBKEY_BYTES: u64 = 33;
+// Outpoint (36) + sequence (4).
+const TXIN_BASE_BYTES: u64 = 40;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputType {
+    P2pkh,
+    P2pk,
+    P2shP2wpkh,
+    P2wpkh,
+    P2trKeyPath,
+    P2shMultisig { m: u8, n: u8 },
+    P2shP2wshMultisig { m: u8, n: u8 },
+    P2wshMultisig { m: u8, n: u8 },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputWeight {
+    #[serde(alias = "input_type")]
+    pub input_type: String,
+    #[serde(alias = "non_witness_bytes")]
+    pub non_witness_bytes: u64,
+    #[serde(alias = "witness_bytes")]
+    pub witness_bytes: u64,
+    pub weight: u64,
+    pub vbytes: f64,
+    #[serde(alias = "relative_to_p2tr")]
+    pub relative_to_p2tr: f64,
+}
+
+#[wasm_bindgen]
+pub fn estimate_input_weight(script_pubkey_hex_or_type: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let input_type = parse_input_type(script_pubkey_hex_or_type).map_err(|e| JsValue::from_str(&e))?;
+
+    to_json(&input_type.describe(), compact)
+}
+
+#[wasm_bindgen]
+pub fn input_weight_table(compact: Option<bool>) -> Result<String, JsValue> {
+    let table: Vec<InputWeight> = [
+        InputType::P2pkh,
+        InputType::P2pk,
+        InputType::P2shP2wpkh,
+        InputType::P2wpkh,
+        InputType::P2trKeyPath,
+        InputType::P2shMultisig { m: 2, n: 3 },
+        InputType::P2shP2wshMultisig { m: 2, n: 3 },
+        InputType::P2wshMultisig { m: 2, n: 3 },
+    ]
+    .iter()
+    .map(InputType::describe)
+    .collect();
+
+    to_json(&table, compact)
+}
+
+impl InputType {
+    pub fn name(&self) -> String {
+        match self {
+            InputType::P2pkh => "p2pkh".to_string(),
+            InputType::P2pk => "p2pk".to_string(),
+            InputType::P2shP2wpkh => "p2sh-p2wpkh".to_string(),
+            InputType::P2wpkh => "p2wpkh".to_string(),
+            InputType::P2trKeyPath => "p2tr-keypath".to_string(),
+            InputType::P2shMultisig { m, n } => format!("p2sh-multisig-{}-of-{}", m, n),
+            InputType::P2shP2wshMultisig { m, n } => format!("p2sh-p2wsh-multisig-{}-of-{}", m, n),
+            InputType::P2wshMultisig { m, n } => format!("p2wsh-multisig-{}-of-{}", m, n),
+        }
+    }
+
+    /// Serialized size of the scriptSig (without its length prefix) and of the witness.
+    fn sizes(&self) -> (u64, u64) {
+        let ecdsa_push = 1 + ECDSA_SIGNATURE_BYTES;
+        let pubkey_push = 1 + COMPRESSED_PUBKEY_BYTES;
+        let p2wpkh_witness = 1 + ecdsa_push + pubkey_push;
+        match *self {
+            InputType::P2pkh => (ecdsa_push + pubkey_push, 0),
+            InputType::P2pk => (ecdsa_push, 0),
+            InputType::P2shP2wpkh => (1 + 22, p2wpkh_witness),
+            InputType::P2wpkh => (0, p2wpkh_witness),
+            InputType::P2trKeyPath => (0, 1 + 1 + SCHNORR_SIGNATURE_BYTES),
+            InputType::P2shMultisig { m, n } => {
+                let script = multisig_script_bytes(n);
+                (1 + m as u64 * ecdsa_push + push_opcode_bytes(script) + script, 0)
+            }
+            InputType::P2shP2wshMultisig { m, n } => (1 + 34, multisig_witness_bytes(m, n)),
+            InputType::P2wshMultisig { m, n } => (0, multisig_witness_bytes(m, n)),
+        }
+    }
+
+    pub fn weight(&self) -> u64 {
+        let (script_sig, witness) = self.sizes();
+        4 * (TXIN_BASE_BYTES + compact_size_len(script_sig) + script_sig) + witness
+    }
+
+    pub fn vbytes(&self) -> f64 {
+        self.weight() as f64 / 4.0
+    }
+
+    fn describe(&self) -> InputWeight {
+        let (script_sig, witness) = self.sizes();
+        let relative = self.weight() as f64 / InputType::P2trKeyPath.weight() as f64;
+        InputWeight {
+            input_type: self.name(),
+            non_witness_bytes: TXIN_BASE_BYTES + compact_size_len(script_sig) + script_sig,
+            witness_bytes: witness,
+            weight: self.weight(),
+            vbytes: self.vbytes(),
+            relative_to_p2tr: (relative * 100.0).round() / 100.0,
+        }
+    }
+}
+
+/// Accepts a type name (`p2wpkh`, `p2wsh-multisig-2-of-3`, ...) or a scriptPubKey in hex.
+pub fn parse_input_type(value: &str) -> Result<InputType, String> {
+    let value = value.trim();
+    let lower = value.to_ascii_lowercase();
+    let simple = match lower.as_str() {
+        "p2pkh" => Some(InputType::P2pkh),
+        "p2pk" => Some(InputType::P2pk),
+        "p2sh-p2wpkh" | "nested-segwit" => Some(InputType::P2shP2wpkh),
+        "p2wpkh" => Some(InputType::P2wpkh),
+        "p2tr" | "p2tr-keypath" => Some(InputType::P2trKeyPath),
+        _ => None,
+    };
+    if let Some(input_type) = simple {
+        return Ok(input_type);
+    }
+
+    for prefix in ["p2sh-p2wsh-multisig-", "p2wsh-multisig-", "p2sh-multisig-"] {
+        let Some(rest) = lower.strip_prefix(prefix) else {
+            continue;
+        };
+        let (m, n) = parse_m_of_n(rest)?;
+        // A P2SH redeem script is capped at 520 bytes, which fits 15 compressed keys.
+        let max_keys = if prefix == "p2sh-multisig-" { 15 } else { 16 };
+        if n > max_keys {
+            return Err(format!("{} supports at most {} keys, got {}", prefix.trim_end_matches('-'), max_keys, n));
+        }
+        return Ok(match prefix {
+            "p2sh-p2wsh-multisig-" => InputType::P2shP2wshMultisig { m, n },
+            "p2wsh-multisig-" => InputType::P2wshMultisig { m, n },
+            _ => InputType::P2shMultisig { m, n },
+        });
+    }
+
+    let bytes = hex_to_bytes(value, HexMode::Lenient)
+        .map_err(|_| format!("Unknown input type \"{}\": expected a type name or scriptPubKey hex", value))?;
+    let script = ScriptBuf::from_bytes(bytes);
+    if script.is_p2pkh() {
+        Ok(InputType::P2pkh)
+    } else if script.is_p2wpkh() {
+        Ok(InputType::P2wpkh)
+    } else if script.is_p2tr() {
+        Ok(InputType::P2trKeyPath)
+    } else if script.is_p2pk() {
+        Ok(InputType::P2pk)
+    } else if script.is_p2sh() {
+        Err("P2SH scripts do not reveal how they are spent; pass an input type such as p2sh-p2wpkh or p2sh-multisig-2-of-3".to_string())
+    } else if script.is_p2wsh() {
+        Err("P2WSH scripts do not reveal how they are spent; pass an input type such as p2wsh-multisig-2-of-3".to_string())
+    } else {
+        Err("Cannot estimate the input weight of a nonstandard scriptPubKey".to_string())
+    }
+}
+
+/// How a UTXO will be spent, using its explicit `input_type` when given.
+/// UTXOs without a script are priced as P2WPKH.
+pub fn utxo_input_type(utxo: &TransactionInput) -> Result<InputType, String> {
+    match &utxo.input_type {
+        Some(input_type) => parse_input_type(input_type),
+        None if utxo.script_pubkey.trim().is_empty() => Ok(InputType::P2wpkh),
+        None => parse_input_type(&utxo.script_pubkey)
+            .map_err(|e| format!("UTXO {}:{}: {}", utxo.txid, utxo.vout, e)),
+    }
+}
+
+/// Estimated vsize once every input is signed, from each input's spend type.
+/// Inputs whose spend type cannot be determined are counted without signature data.
+pub fn estimated_signed_vsize(tx: &Transaction, inputs: &[TransactionInput]) -> u64 {
+    let mut stripped = tx.clone();
+    for input in &mut stripped.input {
+        input.script_sig = ScriptBuf::new();
+        input.witness.clear();
+    }
+
+    let mut weight = stripped.weight().to_wu();
+    let mut witness_inputs = 0u64;
+    for input_type in inputs.iter().filter_map(|utxo| utxo_input_type(utxo).ok()) {
+        let (_, witness) = input_type.sizes();
+        if witness > 0 {
+            witness_inputs += 1;
+        }
+        weight += input_type.weight() - 4 * (TXIN_BASE_BYTES + 1);
+    }
+    if witness_inputs > 0 {
+        // Segwit marker and flag, plus an empty witness for every non-witness input.
+        weight += 2 + (tx.input.len() as u64).saturating_sub(witness_inputs);
+    }
+    weight.div_ceil(4)
+}
+
+fn parse_m_of_n(value: &str) -> Result<(u8, u8), String> {
+    let parsed = value
+        .split_once("-of-")
+        .and_then(|(m, n)| Some((m.parse::<u8>().ok()?, n.parse::<u8>().ok()?)));
+    match parsed {
+        Some((m, n)) if m >= 1 && m <= n => Ok((m, n)),
+        _ => Err(format!("Invalid multisig threshold \"{}\": expected m-of-n with 1 <= m <= n", value)),
+    }
+}
+
+fn multisig_script_bytes(n: u8) -> u64 {
+    // OP_m <n pubkey pushes> OP_n OP_CHECKMULTISIG
+    3 + n as u64 * (1 + COMPRESSED_PUBKEY_BYTES)
+}
+
+fn multisig_witness_bytes(m: u8, n: u8) -> u64 {
+    let script = multisig_script_bytes(n);
+    // Item count, the empty CHECKMULTISIG dummy, m signatures and the witness script.
+    let items = 2 + m as u64;
+    compact_size_len(items) + 1 + m as u64 * (1 + ECDSA_SIGNATURE_BYTES) + compact_size_len(script) + script
+}
+
+fn push_opcode_bytes(len: u64) -> u64 {
+    match len {
+        0..=75 => 1,
+        76..=255 => 2,
+        _ => 3,
+    }
+}
+
+fn compact_size_len(value: u64) -> u64 {
+    encode_compact_size(value).len() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+    #[test]
+    fn estimate_matches_a_low_r_signature() {
+        // RFC6979 without grinding: SHA256("Satoshi Nakamoto") signed by the scalar 1 has
+        // r = 0x934b..., so its DER form needs the 33-byte INTEGER. Grinding brings it to 71.
+        let secp = Secp256k1::signing_only();
+        let mut key = [0u8; 32];
+        key[31] = 1;
+        let key = SecretKey::from_slice(&key).unwrap();
+        let message = Message::from_digest(sha256::Hash::hash(b"Satoshi Nakamoto").to_byte_array());
+        let high_r = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa(&message, &key));
+        assert_eq!(high_r.to_vec().len() as u64, ECDSA_SIGNATURE_BYTES + 1);
+        let low_r = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa_low_r(&message, &key));
+        assert_eq!(low_r.to_vec().len() as u64, ECDSA_SIGNATURE_BYTES);
+    }
+
+    #[test]
+    fn p2wpkh_input_counts_a_71_byte_signature() {
+        // 41 non-witness bytes, and a witness of two items: 72 and 34 bytes with their pushes.
+        assert_eq!(InputType::P2wpkh.weight(), 4 * 41 + 1 + 72 + 34);
+        assert_eq!(InputType::P2pkh.sizes(), (72 + 34, 0));
+    }
+}
@@ -0,0 +1,109 @@
+use wasm_bindgen::prelude::*;
+use bitcoin::ScriptBuf;
+use crate::transaction::{build, parse_tx_hex, BuiltTransaction};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::utils::network::{network_or_default, require_address_network, NetworkParams};
+
+/// A transaction builder that only pays scripts fixed when it was created. The allowed
+/// list lives in wasm memory and is checked against the finished transaction, so JS
+/// that edits the outputs between confirmation and `build` can't redirect funds.
+#[wasm_bindgen]
+pub struct RestrictedBuilder {
+    allowed: Vec<ScriptBuf>,
+    change: Option<ScriptBuf>,
+    network_name: Option<String>,
+    network: NetworkParams,
+}
+
+/// Creates a `RestrictedBuilder`. `allowed_addresses_or_scripts_json` is an array of
+/// addresses on `network` or scriptPubKey hex. The output marked `is_change` may also
+/// pay `allow_change_to`; without it, change needs to be on the list like any output.
+#[wasm_bindgen]
+pub fn restrict_outputs(
+    allowed_addresses_or_scripts_json: &str,
+    allow_change_to: Option<String>,
+    network: Option<String>,
+) -> Result<RestrictedBuilder, JsValue> {
+    let network_params = network_or_default(network.clone()).map_err(|e| JsValue::from_str(&e))?;
+    let entries: Vec<String> = parse_json_list(allowed_addresses_or_scripts_json, "allowed outputs")
+        .map_err(|e| JsValue::from_str(&e))?;
+    let allowed = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| allowed_script(entry, &network_params).map_err(|e| format!("allowed outputs[{}]: {}", index, e)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| JsValue::from_str(&e))?;
+    if allowed.is_empty() && allow_change_to.is_none() {
+        return Err(JsValue::from_str("The allowed outputs list is empty, so nothing could be paid"));
+    }
+    let change = allow_change_to
+        .map(|address| {
+            require_address_network(&address, Some(&network_params)).map_err(|e| JsValue::from_str(&format!("allow_change_to: {}", e)))
+        })
+        .transpose()?;
+
+    Ok(RestrictedBuilder { allowed, change, network_name: network, network: network_params })
+}
+
+#[wasm_bindgen]
+impl RestrictedBuilder {
+    /// `build_transaction_detailed` on this builder's network, refusing the result if
+    /// any output pays outside the allowed list. The error names the offending entry of
+    /// `outputs_json` and its address.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build(
+        &self,
+        inputs_json: &str,
+        outputs_json: &str,
+        version: Option<u32>,
+        ordering: Option<String>,
+        anti_fee_sniping_tip: Option<u32>,
+        checks_json: Option<String>,
+        compact: Option<bool>,
+    ) -> Result<String, JsValue> {
+        let built = build(inputs_json, outputs_json, self.network_name.clone(), Some(false), version, ordering, anti_fee_sniping_tip, checks_json)?;
+        self.check(&built).map_err(|e| JsValue::from_str(&e))?;
+
+        to_json(&built, compact)
+    }
+
+    /// The allowed scriptPubKeys as hex, with the change script last if there is one.
+    pub fn allowed_scripts(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let scripts: Vec<String> = self.allowed.iter().chain(&self.change).map(|script| bytes_to_hex(script.as_bytes())).collect();
+        to_json(&scripts, compact)
+    }
+}
+
+impl RestrictedBuilder {
+    /// Checks the serialized transaction rather than the parsed outputs, so nothing
+    /// between parsing and serializing can slip an output past the list.
+    fn check(&self, built: &BuiltTransaction) -> Result<(), String> {
+        let tx = parse_tx_hex(&built.tx_hex)?;
+        for (vout, output) in tx.output.iter().enumerate() {
+            let script = &output.script_pubkey;
+            let is_change = built.change_vout == Some(vout);
+            if self.allowed.contains(script) || (is_change && self.change.as_ref() == Some(script)) {
+                continue;
+            }
+            let index = built.output_order[vout];
+            let payee = self.network.address(script).unwrap_or_else(|| format!("script {}", bytes_to_hex(script.as_bytes())));
+            return Err(if self.change.as_ref() == Some(script) {
+                format!("Output {} pays the change address {} but isn't marked as change", index, payee)
+            } else {
+                format!("Output {} pays {}, which isn't an allowed output", index, payee)
+            });
+        }
+        Ok(())
+    }
+}
+
+/// An address on `network`, or scriptPubKey hex. Addresses are tried first, since some
+/// are valid hex.
+fn allowed_script(entry: &str, network: &NetworkParams) -> Result<ScriptBuf, String> {
+    let entry = entry.trim();
+    require_address_network(entry, Some(network)).or_else(|address_error| match hex_to_bytes(entry, HexMode::Lenient) {
+        Ok(bytes) if !bytes.is_empty() => Ok(ScriptBuf::from_bytes(bytes)),
+        _ => Err(address_error),
+    })
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{Transaction, Witness};
+use crate::transaction::serialization::checked_serialize;
+use crate::transaction::{check_input_index, decode_tx_hex};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, to_json};
+
+// Consensus limits (consensus/consensus.h, script/script.h). A witness past these can
+// never verify, whatever script it spends.
+const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+const MAX_STACK_SIZE: usize = 1000;
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+// Bitcoin Core's relay policy for P2WSH and tapscript stacks (policy/policy.h).
+const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+const MAX_STANDARD_STACK_ITEM_SIZE: usize = 80;
+
+/// A transaction after a witness edit.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WitnessEdit {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    pub txid: String,
+    pub wtxid: String,
+    pub vsize: usize,
+    pub weight: u64,
+    /// The edited input's witness items, hex.
+    pub witness: Vec<String>,
+}
+
+/// Replaces input `input_index`'s witness with `items_json`, a JSON array of hex stack
+/// items in witness order (an empty array clears it). Limits that would make the spend
+/// invalid always apply; relay policy limits apply too unless `allow_nonstandard` is set.
+#[wasm_bindgen]
+pub fn set_witness(tx_hex: &str, input_index: usize, items_json: &str, allow_nonstandard: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let items: Vec<String> = parse_json_list(items_json, "witness items").map_err(|e| JsValue::from_str(&e))?;
+    let items = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| parse_item(item).map_err(|e| format!("witness items[{}]: {}", index, e)))
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| JsValue::from_str(&e))?;
+    tx.input[input_index].witness = Witness::from_slice(&items);
+    finish_edit(tx, input_index, allow_nonstandard, compact)
+}
+
+/// Input `input_index`'s witness items as a JSON array of hex strings, bottom of the
+/// stack first.
+#[wasm_bindgen]
+pub fn get_witness(tx_hex: &str, input_index: usize, compact: Option<bool>) -> Result<String, JsValue> {
+    let tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    to_json(&witness_hex(&tx.input[input_index].witness), compact)
+}
+
+/// Appends one hex item to the end of input `input_index`'s witness.
+#[wasm_bindgen]
+pub fn push_witness_item(tx_hex: &str, input_index: usize, item_hex: &str, allow_nonstandard: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let item = parse_item(item_hex).map_err(|e| JsValue::from_str(&e))?;
+    tx.input[input_index].witness.push(item);
+    finish_edit(tx, input_index, allow_nonstandard, compact)
+}
+
+/// Removes the item at `position` (0 is the first) from input `input_index`'s witness.
+#[wasm_bindgen]
+pub fn remove_witness_item(tx_hex: &str, input_index: usize, position: usize, allow_nonstandard: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let mut items = tx.input[input_index].witness.to_vec();
+    if position >= items.len() {
+        return Err(JsValue::from_str(&format!(
+            "witness position {} out of range (input {} has {} witness items)",
+            position,
+            input_index,
+            items.len()
+        )));
+    }
+    items.remove(position);
+    tx.input[input_index].witness = Witness::from_slice(&items);
+    finish_edit(tx, input_index, allow_nonstandard, compact)
+}
+
+fn parse_item(item_hex: &str) -> Result<Vec<u8>, String> {
+    hex_to_bytes(item_hex, HexMode::Lenient).map_err(|e| format!("Invalid witness item: {}", e))
+}
+
+fn witness_hex(witness: &Witness) -> Vec<String> {
+    witness.iter().map(bytes_to_hex).collect()
+}
+
+fn finish_edit(tx: Transaction, input_index: usize, allow_nonstandard: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let items = tx.input[input_index].witness.to_vec();
+    let weight = tx.weight().to_wu();
+    structural_problem(&items, weight)
+        .or_else(|| policy_problem(&items, weight).filter(|_| !allow_nonstandard.unwrap_or(false)))
+        .map_or(Ok(()), |problem| Err(JsValue::from_str(&format!("Input {}: {}", input_index, problem))))?;
+
+    let bytes = checked_serialize(&tx).map_err(|e| JsValue::from_str(&e))?;
+    to_json(&WitnessEdit {
+        tx_hex: bytes_to_hex(&bytes),
+        txid: tx.compute_txid().to_string(),
+        wtxid: tx.compute_wtxid().to_string(),
+        vsize: tx.vsize(),
+        weight,
+        witness: witness_hex(&tx.input[input_index].witness),
+    }, compact)
+}
+
+/// Limits no spend can get past. The last two items are exempt from the element size
+/// limit, since a witness or leaf script and a taproot control block can be longer.
+fn structural_problem(items: &[Vec<u8>], weight: u64) -> Option<String> {
+    if weight > MAX_BLOCK_WEIGHT {
+        return Some(format!("Weight {} exceeds the block weight limit of {}", weight, MAX_BLOCK_WEIGHT));
+    }
+    let stack = &items[..items.len().saturating_sub(2)];
+    if stack.len() > MAX_STACK_SIZE {
+        return Some(format!("{} witness stack items, over the consensus limit of {}", stack.len(), MAX_STACK_SIZE));
+    }
+    stack
+        .iter()
+        .find(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE)
+        .map(|item| format!("Witness stack item of {} bytes, over the consensus limit of {}", item.len(), MAX_SCRIPT_ELEMENT_SIZE))
+}
+
+/// Relay policy as far as it can be checked without the spent output: the standard
+/// weight, and Core's P2WSH and tapscript stack limits with the script (and any control
+/// block) left out. `check_standardness` applies the exact rules given the prevouts.
+fn policy_problem(items: &[Vec<u8>], weight: u64) -> Option<String> {
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        return Some(format!("Weight {} exceeds the standard maximum of {}", weight, MAX_STANDARD_TX_WEIGHT));
+    }
+    let stack_items = items.len().saturating_sub(1);
+    if stack_items > MAX_STANDARD_P2WSH_STACK_ITEMS {
+        return Some(format!("{} witness stack items, over the standard limit of {}", stack_items, MAX_STANDARD_P2WSH_STACK_ITEMS));
+    }
+    items[..items.len().saturating_sub(2)]
+        .iter()
+        .find(|item| item.len() > MAX_STANDARD_STACK_ITEM_SIZE)
+        .map(|item| format!("Witness stack item of {} bytes, over the standard limit of {}", item.len(), MAX_STANDARD_STACK_ITEM_SIZE))
+}
@@ -0,0 +1,180 @@
+use std::cell::Cell;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serializer};
+
+thread_local! {
+    /// Set only while `with_amount_format` serializes one result.
+    static AMOUNTS_AS_STRINGS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// How a result writes its amounts: JSON numbers, or decimal strings for callers that
+/// parse them into BigInt. Functions whose results carry amounts take it as their
+/// trailing `amount_format` argument, so one caller's choice never changes another's
+/// output.
+#[cfg(any(feature = "wallet", feature = "signing", feature = "psbt", feature = "decode", feature = "http"))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum AmountFormat {
+    #[default]
+    Number,
+    String,
+}
+
+#[cfg(any(feature = "wallet", feature = "signing", feature = "psbt", feature = "decode", feature = "http"))]
+impl AmountFormat {
+    /// Reads an `amount_format` argument: `"number"`, `"string"`, or nothing for numbers.
+    pub fn parse(format: Option<&str>) -> Result<AmountFormat, String> {
+        match format {
+            None | Some("number") => Ok(AmountFormat::Number),
+            Some("string") => Ok(AmountFormat::String),
+            Some(other) => Err(format!("Unknown amount format '{}' (expected \"string\" or \"number\")", other)),
+        }
+    }
+}
+
+/// Runs `serialize` with amounts written in `format`, and puts the previous format back
+/// afterwards.
+#[cfg(any(feature = "wallet", feature = "signing", feature = "psbt", feature = "decode", feature = "http"))]
+pub fn with_amount_format<T>(format: AmountFormat, serialize: impl FnOnce() -> T) -> T {
+    let previous = AMOUNTS_AS_STRINGS.with(|flag| flag.replace(format == AmountFormat::String));
+    let result = serialize();
+    AMOUNTS_AS_STRINGS.with(|flag| flag.set(previous));
+    result
+}
+
+fn amounts_as_strings() -> bool {
+    AMOUNTS_AS_STRINGS.with(Cell::get)
+}
+
+pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    if amounts_as_strings() {
+        serializer.serialize_str(&value.to_string())
+    } else {
+        serializer.serialize_u64(*value)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    deserializer.deserialize_any(AmountVisitor)
+}
+
+/// `deserialize` for an `Option<u64>` field: `null` is None. Pair it with
+/// `#[serde(default)]` so a missing field is None too.
+pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    struct Sats(u64);
+
+    impl<'de> Deserialize<'de> for Sats {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Sats, D::Error> {
+            deserialize(deserializer).map(Sats)
+        }
+    }
+
+    Ok(Option::<Sats>::deserialize(deserializer)?.map(|Sats(value)| value))
+}
+
+struct AmountVisitor;
+
+impl Visitor<'_> for AmountVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an integer number of satoshis, as a JSON number or decimal string")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u64, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u64, E> {
+        u64::try_from(value).map_err(|_| E::custom(format!("amount cannot be negative (got {})", value)))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<u64, E> {
+        Err(E::custom(format!(
+            "amount must be a whole number of satoshis (got {}); pass large values as strings",
+            value
+        )))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<u64, E> {
+        let trimmed = value.trim();
+        if trimmed.is_empty() || !trimmed.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(E::custom(format!(
+                "amount string must contain only decimal digits (satoshis), got \"{}\"",
+                value
+            )));
+        }
+        trimmed
+            .parse::<u64>()
+            .map_err(|_| E::custom(format!("amount \"{}\" is too large", value)))
+    }
+}
+
+#[cfg(any(feature = "wallet", feature = "http"))]
+pub mod signed {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        if super::amounts_as_strings() {
+            serializer.serialize_str(&value.to_string())
+        } else {
+            serializer.serialize_i64(*value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Signed {
+            Number(i64),
+            Text(String),
+        }
+
+        match Signed::deserialize(deserializer)? {
+            Signed::Number(n) => Ok(n),
+            Signed::Text(s) => s
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| serde::de::Error::custom(format!("invalid signed amount \"{}\"", s))),
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "wallet", feature = "signing", feature = "psbt", feature = "decode", feature = "http")))]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Output {
+        #[serde(with = "super")]
+        amount: u64,
+    }
+
+    /// 20,999,999.99999999 BTC, more than will ever exist, through both formats and
+    /// back with no rounding.
+    #[test]
+    fn largest_supply_round_trips() {
+        let output = Output { amount: 2_099_999_999_999_999 };
+        let number = serde_json::to_string(&output).unwrap();
+        assert_eq!(number, r#"{"amount":2099999999999999}"#);
+        let string = with_amount_format(AmountFormat::String, || serde_json::to_string(&output).unwrap());
+        assert_eq!(string, r#"{"amount":"2099999999999999"}"#);
+        assert_eq!(serde_json::from_str::<Output>(&number).unwrap(), output);
+        assert_eq!(serde_json::from_str::<Output>(&string).unwrap(), output);
+    }
+
+    #[test]
+    fn format_lasts_for_one_serialization() {
+        let output = Output { amount: 5 };
+        with_amount_format(AmountFormat::String, || ());
+        assert_eq!(serde_json::to_string(&output).unwrap(), r#"{"amount":5}"#);
+    }
+
+    #[test]
+    fn rejects_fractional_negative_and_unknown() {
+        assert!(serde_json::from_str::<Output>(r#"{"amount":1.5}"#).is_err());
+        assert!(serde_json::from_str::<Output>(r#"{"amount":-1}"#).is_err());
+        assert!(serde_json::from_str::<Output>(r#"{"amount":"1e8"}"#).is_err());
+        assert!(AmountFormat::parse(Some("bigint")).is_err());
+    }
+}
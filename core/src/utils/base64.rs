@@ -0,0 +1,74 @@
+use wasm_bindgen::prelude::*;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[wasm_bindgen]
+pub fn base64_encode(hex: &str, url_safe: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid hex: {}", e)))?;
+
+    Ok(encode_base64(&bytes, url_safe.unwrap_or(false)))
+}
+
+#[wasm_bindgen]
+pub fn base64_decode(b64: &str) -> Result<String, JsValue> {
+    let bytes = decode_base64(b64).map_err(|e| JsValue::from_str(&format!("Invalid base64: {}", e)))?;
+    Ok(bytes_to_hex(&bytes))
+}
+
+pub fn encode_base64(bytes: &[u8], url_safe: bool) -> String {
+    let alphabet = if url_safe { URL_SAFE } else { STANDARD };
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        out.push(alphabet[(n >> 18) as usize & 63] as char);
+        out.push(alphabet[(n >> 12) as usize & 63] as char);
+        out.push(if chunk.len() > 1 { alphabet[(n >> 6) as usize & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { alphabet[n as usize & 63] as char } else { '=' });
+    }
+
+    out
+}
+
+pub fn decode_base64(b64: &str) -> Result<Vec<u8>, String> {
+    let chars: Vec<u8> = b64.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+
+    let data_len = chars.iter().rposition(|&c| c != b'=').map_or(0, |i| i + 1);
+    let padding = chars.len() - data_len;
+    if padding > 2 {
+        return Err("too much padding".to_string());
+    }
+    if padding > 0 && !chars.len().is_multiple_of(4) {
+        return Err("padded input length must be a multiple of 4".to_string());
+    }
+    if data_len % 4 == 1 {
+        return Err("truncated input".to_string());
+    }
+
+    let mut out = Vec::with_capacity(data_len * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for (i, &c) in chars[..data_len].iter().enumerate() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(format!("invalid character {:?} at position {}", c as char, i)),
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,73 @@
+use wasm_bindgen::prelude::*;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+
+#[wasm_bindgen]
+pub fn reverse_hash_hex(hash_hex: &str) -> Result<String, JsValue> {
+    let mut bytes = hash_bytes(hash_hex)?;
+    bytes.reverse();
+    Ok(bytes_to_hex(&bytes))
+}
+
+#[wasm_bindgen]
+pub fn txid_to_internal(txid: &str) -> Result<String, JsValue> {
+    reverse_hash_hex(txid)
+}
+
+#[wasm_bindgen]
+pub fn internal_to_txid(bytes_hex: &str) -> Result<String, JsValue> {
+    reverse_hash_hex(bytes_hex)
+}
+
+fn hash_bytes(hash_hex: &str) -> Result<Vec<u8>, JsValue> {
+    let bytes = hex_to_bytes(hash_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid hash hex: {}", e)))?;
+
+    if bytes.len() != 32 {
+        return Err(JsValue::from_str(&format!(
+            "Hash must be 32 bytes (64 hex characters), got {} bytes",
+            bytes.len()
+        )));
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::{deserialize, serialize_hex};
+    use bitcoin::hashes::{sha256d, Hash};
+    use bitcoin::Transaction;
+    use crate::transaction::build_transaction;
+
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+    /// As block explorers show it.
+    const GENESIS_TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    #[test]
+    fn internal_order_is_the_raw_hash() {
+        let raw_hash = sha256d::Hash::hash(&hex_to_bytes(GENESIS_COINBASE, HexMode::Strict).unwrap());
+        assert_eq!(txid_to_internal(GENESIS_TXID).unwrap(), bytes_to_hex(raw_hash.as_byte_array()));
+        assert_eq!(internal_to_txid(&bytes_to_hex(raw_hash.as_byte_array())).unwrap(), GENESIS_TXID);
+        assert_eq!(reverse_hash_hex(&reverse_hash_hex(GENESIS_TXID).unwrap()).unwrap(), GENESIS_TXID);
+    }
+
+    #[test]
+    fn explorer_txid_spends_the_right_output() {
+        let funding: Transaction = deserialize(&hex_to_bytes(GENESIS_COINBASE, HexMode::Strict).unwrap()).unwrap();
+        let inputs = serde_json::json!([{
+            "txid": GENESIS_TXID,
+            "vout": 0,
+            "amountSat": funding.output[0].value.to_sat(),
+            "scriptPubkey": funding.output[0].script_pubkey.to_hex_string(),
+        }]);
+        let outputs = serde_json::json!([{ "scriptPubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6", "amountSat": 4_999_990_000u64 }]);
+        let tx_hex = build_transaction(&inputs.to_string(), &outputs.to_string(), 0, None, None, None, None, None, None).unwrap();
+
+        let spending: Transaction = deserialize(&hex_to_bytes(&tx_hex, HexMode::Strict).unwrap()).unwrap();
+        assert_eq!(spending.input[0].previous_output.txid, funding.compute_txid());
+        // The outpoint is serialized in internal order, right after the version.
+        let serialized_txid = &serialize_hex(&spending)[10..74];
+        assert_eq!(serialized_txid, txid_to_internal(GENESIS_TXID).unwrap());
+    }
+}
@@ -0,0 +1,156 @@
+use wasm_bindgen::prelude::*;
+
+const SATS_PER_BTC: i128 = 100_000_000;
+const MAX_MONEY: i128 = 21_000_000 * SATS_PER_BTC;
+/// `Number.MAX_SAFE_INTEGER`: larger JS numbers may already have lost digits.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// An exact number of satoshis for JS code that would otherwise add BTC amounts as
+/// floats. Values are immutable; arithmetic returns a new `Amount`. Plain arithmetic
+/// only refuses results outside the 64-bit range and may go negative, as a balance
+/// delta can. The `checked_` variants also insist on a valid output amount, 0 to 21
+/// million BTC.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount {
+    sats: i64,
+}
+
+#[wasm_bindgen]
+impl Amount {
+    /// From a satoshi count given as a decimal string, a BigInt or a safe integer.
+    pub fn from_sat(sats: JsValue) -> Result<Amount, JsValue> {
+        let sats = js_integer(&sats, "satoshi amount").map_err(|e| JsValue::from_str(&e))?;
+        Amount::from_i128(sats)
+    }
+
+    /// From a BTC amount written in decimal, like "0.001" or "-21000000". Exponents and
+    /// more than eight decimals are refused rather than rounded.
+    pub fn from_btc_string(btc: &str) -> Result<Amount, JsValue> {
+        let sats = parse_btc(btc).map_err(|e| JsValue::from_str(&e))?;
+        Amount::from_i128(sats)
+    }
+
+    pub fn add(&self, other: &Amount) -> Result<Amount, JsValue> {
+        Amount::from_i128(self.sats as i128 + other.sats as i128)
+    }
+
+    pub fn sub(&self, other: &Amount) -> Result<Amount, JsValue> {
+        Amount::from_i128(self.sats as i128 - other.sats as i128)
+    }
+
+    /// Multiplies by an integer given as a string, a BigInt or a safe integer.
+    pub fn mul_by_int(&self, factor: JsValue) -> Result<Amount, JsValue> {
+        let factor = js_integer(&factor, "factor").map_err(|e| JsValue::from_str(&e))?;
+        let product = (self.sats as i128).checked_mul(factor).ok_or_else(overflow)?;
+        Amount::from_i128(product)
+    }
+
+    pub fn checked_add(&self, other: &Amount) -> Result<Amount, JsValue> {
+        self.add(other)?.money_range()
+    }
+
+    pub fn checked_sub(&self, other: &Amount) -> Result<Amount, JsValue> {
+        self.sub(other)?.money_range()
+    }
+
+    pub fn checked_mul_by_int(&self, factor: JsValue) -> Result<Amount, JsValue> {
+        self.mul_by_int(factor)?.money_range()
+    }
+
+    /// -1, 0 or 1, for sorting.
+    pub fn compare(&self, other: &Amount) -> i32 {
+        self.cmp(other) as i32
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.sats < 0
+    }
+
+    /// Always eight decimals, as Bitcoin Core prints amounts: "0.00001000".
+    pub fn to_btc_string(&self) -> String {
+        format_btc(self.sats)
+    }
+
+    pub fn to_sat_string(&self) -> String {
+        self.sats.to_string()
+    }
+}
+
+impl Amount {
+    fn from_i128(sats: i128) -> Result<Amount, JsValue> {
+        i64::try_from(sats).map(|sats| Amount { sats }).map_err(|_| overflow())
+    }
+
+    fn money_range(self) -> Result<Amount, JsValue> {
+        if (0..=MAX_MONEY).contains(&(self.sats as i128)) {
+            Ok(self)
+        } else {
+            Err(JsValue::from_str(&format!(
+                "Amount {} BTC is outside the valid range of 0 to 21000000 BTC",
+                format_btc(self.sats)
+            )))
+        }
+    }
+}
+
+fn overflow() -> JsValue {
+    JsValue::from_str("Amount overflow: the result doesn't fit in 64 bits of satoshis")
+}
+
+fn js_integer(value: &JsValue, what: &str) -> Result<i128, String> {
+    if let Some(text) = value.as_string() {
+        return parse_integer(&text, what);
+    }
+    if value.is_bigint() {
+        return i128::try_from(value.clone()).map_err(|_| format!("{} is too large", what));
+    }
+    match value.as_f64() {
+        Some(n) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER => Ok(n as i128),
+        Some(n) => Err(format!("{} must be a whole number no larger than 2^53 - 1 (got {}); pass a string or BigInt", what, n)),
+        None => Err(format!("{} must be a string, BigInt or number", what)),
+    }
+}
+
+pub fn parse_integer(text: &str, what: &str) -> Result<i128, String> {
+    let trimmed = text.trim();
+    let digits = trimmed.strip_prefix('-').unwrap_or(trimmed);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("{} must be a whole number in decimal digits, got \"{}\"", what, text));
+    }
+    trimmed.parse::<i128>().map_err(|_| format!("{} \"{}\" is too large", what, text))
+}
+
+/// Parses a decimal BTC amount to satoshis without going through a float. More than
+/// eight decimals is an error rather than a rounding.
+pub fn parse_btc(text: &str) -> Result<i128, String> {
+    let trimmed = text.trim();
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let well_formed = !(whole.is_empty() && fraction.is_empty())
+        && whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit());
+    if !well_formed {
+        return Err(format!("BTC amount must be a decimal number like \"0.0015\", got \"{}\"", text));
+    }
+    if fraction.len() > 8 {
+        return Err(format!("BTC amount \"{}\" has more than 8 decimal places", text));
+    }
+    // Long enough to pass MAX_MONEY without overflowing i128; the caller checks the range.
+    if whole.trim_start_matches('0').len() > 20 {
+        return Err(format!("BTC amount \"{}\" is too large", text));
+    }
+
+    let whole: i128 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| format!("BTC amount \"{}\" is too large", text))? };
+    let fraction: i128 = format!("{:0<8}", fraction).parse().expect("eight digits");
+    let sats = whole * SATS_PER_BTC + fraction;
+    Ok(if negative { -sats } else { sats })
+}
+
+pub fn format_btc(sats: i64) -> String {
+    let sign = if sats < 0 { "-" } else { "" };
+    let magnitude = sats.unsigned_abs();
+    format!("{}{}.{:08}", sign, magnitude / SATS_PER_BTC as u64, magnitude % SATS_PER_BTC as u64)
+}
@@ -1,18 +1,286 @@
+use std::borrow::Cow;
+use wasm_bindgen::prelude::*;
+use crate::utils::metrics::timed;
+use crate::utils::sanitize::{non_ascii_error, sanitize, InputKind};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const HEX_DIGITS_UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+/// How `hex_to_bytes` treats input that isn't bare hex digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexMode {
+    /// Only an even number of hex digits: surrounding whitespace or a `0x` prefix is an
+    /// error, for callers that compare input byte for byte.
+    Strict,
+    /// Ignores surrounding whitespace and a leading `0x`/`0X`, as pasted hex often has.
+    Lenient,
+}
+
 pub fn bytes_to_hex(bytes: &[u8]) -> String {
-    bytes.iter()
-        .map(|b| format!("{:02x}", b))
-        .collect()
+    bytes_to_hex_cased(bytes, false)
 }
 
-pub fn hex_to_bytes(hex_str: &str) -> Result<Vec<u8>, String> {
-    let hex_str = hex_str.trim();
-    if hex_str.len() % 2 != 0 {
+/// `bytes_to_hex` with a choice of digit case, for systems that insist on uppercase.
+pub fn bytes_to_hex_cased(bytes: &[u8], uppercase: bool) -> String {
+    let digits = if uppercase { HEX_DIGITS_UPPER } else { HEX_DIGITS };
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        hex.push(digits[(b >> 4) as usize] as char);
+        hex.push(digits[(b & 0x0f) as usize] as char);
+    }
+    hex
+}
+
+pub fn hex_to_bytes(hex_str: &str, mode: HexMode) -> Result<Vec<u8>, String> {
+    let cleaned = checked_chars(hex_str, mode)?;
+    let hex_str = match mode {
+        HexMode::Strict => &cleaned,
+        HexMode::Lenient => {
+            let trimmed = cleaned.trim();
+            trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X")).unwrap_or(trimmed)
+        }
+    };
+    if !hex_str.len().is_multiple_of(2) {
         return Err("Hex string must have even length".to_string());
     }
 
-    (0..hex_str.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&hex_str[i..i+2], 16))
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Invalid hex character: {}", e))
+    hex_str
+        .as_bytes()
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, pair)| match (hex_value(pair[0]), hex_value(pair[1])) {
+            (Some(hi), Some(lo)) => Ok((hi << 4) | lo),
+            _ => Err(format!(
+                "Invalid hex character: {:?} at position {}",
+                String::from_utf8_lossy(pair),
+                i * 2
+            )),
+        })
+        .collect()
+}
+
+/// Lenient hex goes through the input sanitizer, so zero-width characters and fullwidth
+/// digits pasted along with it are dealt with there. Strict hex is compared byte for byte
+/// and isn't cleaned, but a non-ASCII character is still named rather than shown as bytes.
+fn checked_chars(hex_str: &str, mode: HexMode) -> Result<Cow<'_, str>, String> {
+    match mode {
+        HexMode::Strict => match non_ascii_error(hex_str, InputKind::Hex) {
+            Some(error) => Err(error),
+            None => Ok(Cow::Borrowed(hex_str)),
+        },
+        HexMode::Lenient => {
+            let cleaned = sanitize(hex_str, InputKind::Hex)?;
+            match non_ascii_error(&cleaned, InputKind::Hex) {
+                Some(error) => Err(error),
+                None => Ok(cleaned),
+            }
+        }
+    }
+}
+
+/// Reads the bytes of checked hex without decoding it into a buffer first, for
+/// consensus-decoding large payloads straight from their hex.
+#[cfg(feature = "decode")]
+pub struct HexReader<'a> {
+    digits: Cow<'a, str>,
+    /// Digits already read from the front of `digits`.
+    offset: usize,
+}
+
+#[cfg(feature = "decode")]
+impl<'a> HexReader<'a> {
+    /// Fails as `hex_to_bytes` would, before anything is read.
+    pub fn new(hex_str: &'a str, mode: HexMode) -> Result<HexReader<'a>, String> {
+        let cleaned = checked_chars(hex_str, mode)?;
+        let (start, end) = match mode {
+            HexMode::Strict => (0, cleaned.len()),
+            HexMode::Lenient => {
+                let trimmed = cleaned.trim();
+                let start = cleaned.len() - cleaned.trim_start().len();
+                let prefix = if trimmed.starts_with("0x") || trimmed.starts_with("0X") { 2 } else { 0 };
+                (start + prefix, start + trimmed.len())
+            }
+        };
+        let hex_str = &cleaned[start..end];
+        if !hex_str.len().is_multiple_of(2) {
+            return Err("Hex string must have even length".to_string());
+        }
+        if let Some(position) = hex_str.bytes().position(|c| hex_value(c).is_none()) {
+            let start = position & !1;
+            return Err(format!(
+                "Invalid hex character: {:?} at position {}",
+                String::from_utf8_lossy(&hex_str.as_bytes()[start..start + 2]),
+                start
+            ));
+        }
+        let digits = match cleaned {
+            Cow::Borrowed(text) => Cow::Borrowed(&text[start..end]),
+            Cow::Owned(text) => Cow::Owned(text[start..end].to_string()),
+        };
+        Ok(HexReader { digits, offset: 0 })
+    }
+
+    /// Bytes not yet read.
+    pub fn remaining(&self) -> usize {
+        (self.digits.len() - self.offset) / 2
+    }
+}
+
+#[cfg(feature = "decode")]
+impl bitcoin::io::Read for HexReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> bitcoin::io::Result<usize> {
+        let count = buf.len().min(self.remaining());
+        let digits = &self.digits.as_bytes()[self.offset..self.offset + count * 2];
+        for (byte, pair) in buf.iter_mut().zip(digits.chunks_exact(2)) {
+            *byte = (hex_value(pair[0]).expect("checked in new") << 4) | hex_value(pair[1]).expect("checked in new");
+        }
+        self.offset += count * 2;
+        Ok(count)
+    }
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Re-encodes hex in one case after checking it. `strict` refuses the whitespace and
+/// `0x` prefix that are otherwise stripped.
+#[wasm_bindgen]
+pub fn normalize_hex(hex: &str, uppercase: Option<bool>, strict: Option<bool>) -> Result<String, JsValue> {
+    timed("normalize_hex", hex.len(), || {
+        let mode = if strict.unwrap_or(false) { HexMode::Strict } else { HexMode::Lenient };
+        let bytes = hex_to_bytes(hex, mode).map_err(|e| JsValue::from_str(&format!("Invalid hex: {}", e)))?;
+        Ok(bytes_to_hex_cased(&bytes, uppercase.unwrap_or(false)))
+    })
+}
+
+/// Hex decoding in chunks, for payloads too large to hand over as one string. Digits
+/// may be split anywhere between `push` calls, whitespace between them is skipped, and
+/// a `0x` prefix is allowed at the very start. Decoded bytes collect in a buffer that
+/// `read_into` drains without giving up its capacity, or `finish` hands over whole.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct HexDecoder {
+    buffer: Vec<u8>,
+    /// The high nibble of a byte whose second digit hasn't arrived yet.
+    pending: Option<u8>,
+    /// Characters seen so far, for error positions across chunks.
+    position: usize,
+}
+
+#[wasm_bindgen]
+impl HexDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> HexDecoder {
+        HexDecoder::default()
+    }
+
+    /// Decodes `chunk` onto the buffer and returns the number of bytes buffered. On an
+    /// invalid character nothing from the chunk is kept.
+    pub fn push(&mut self, chunk: &str) -> Result<usize, JsValue> {
+        let chunk = sanitize(chunk, InputKind::Hex).map_err(|e| JsValue::from_str(&e))?;
+        let chunk = chunk.as_ref();
+        let mut digits = chunk.as_bytes();
+        if self.position == 0 && self.buffer.is_empty() && self.pending.is_none() {
+            let trimmed = chunk.trim_start();
+            if let Some(rest) = trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X")) {
+                self.position += chunk.len() - rest.len();
+                digits = rest.as_bytes();
+            }
+        }
+
+        let (kept, pending) = (self.buffer.len(), self.pending);
+        self.buffer.reserve(digits.len() / 2);
+        for (offset, &c) in digits.iter().enumerate() {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            let Some(value) = hex_value(c) else {
+                self.buffer.truncate(kept);
+                self.pending = pending;
+                return Err(JsValue::from_str(&format!(
+                    "Invalid hex character: {:?} at position {}",
+                    c as char,
+                    self.position + offset
+                )));
+            };
+            match self.pending.take() {
+                Some(high) => self.buffer.push((high << 4) | value),
+                None => self.pending = Some(value),
+            }
+        }
+        self.position += digits.len();
+        Ok(self.buffer.len())
+    }
+
+    /// Bytes decoded and not yet read.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Moves up to `out.len()` bytes from the front of the buffer into `out` and returns
+    /// how many were written, so one `Uint8Array` can be reused for every read.
+    pub fn read_into(&mut self, out: &mut [u8]) -> usize {
+        let count = out.len().min(self.buffer.len());
+        out[..count].copy_from_slice(&self.buffer[..count]);
+        self.buffer.drain(..count);
+        count
+    }
+
+    /// Returns everything still buffered and resets the decoder for the next payload.
+    pub fn finish(&mut self) -> Result<Vec<u8>, JsValue> {
+        if self.pending.is_some() {
+            return Err(JsValue::from_str("Hex string must have even length"));
+        }
+        self.position = 0;
+        Ok(std::mem::take(&mut self.buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn hex_reader_borrows_clean_input() {
+        let hex = "00ff10ab".repeat(64);
+        let mut reader = HexReader::new(&hex, HexMode::Lenient).unwrap();
+        assert!(matches!(reader.digits, Cow::Borrowed(_)), "clean hex shouldn't be copied");
+
+        let mut bytes = vec![0u8; reader.remaining()];
+        bitcoin::io::Read::read_exact(&mut reader, &mut bytes).unwrap();
+        assert_eq!(bytes, hex_to_bytes(&hex, HexMode::Strict).unwrap());
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn hex_decoder_reuses_its_buffer() {
+        let chunk = "0123456789abcdef".repeat(256);
+        let mut decoder = HexDecoder::new();
+        let mut out = vec![0u8; chunk.len() / 2];
+        let mut total = 0;
+        for _ in 0..1000 {
+            decoder.push(&chunk).unwrap();
+            total += decoder.read_into(&mut out);
+        }
+        assert_eq!(total, 1000 * chunk.len() / 2);
+        // Drained between pushes, the buffer never grows past about one chunk's bytes.
+        assert!(decoder.buffer.capacity() <= chunk.len(), "capacity {}", decoder.buffer.capacity());
+        assert_eq!(decoder.finish().unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn hex_decoder_joins_split_digits() {
+        let mut decoder = HexDecoder::new();
+        for chunk in ["0xde", "a", "d be", "ef"] {
+            decoder.push(chunk).unwrap();
+        }
+        assert_eq!(decoder.finish().unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+    }
 }
@@ -0,0 +1,320 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+#[cfg(any(feature = "wallet", feature = "signing", feature = "psbt", feature = "decode", feature = "http"))]
+use crate::utils::amount::{with_amount_format, AmountFormat};
+
+/// Serializes a result for JS. Fields come out in declaration order, which is why new
+/// fields are only ever added at the end of a struct, and optional ones are `null`
+/// rather than missing, so a result's shape doesn't depend on its values. `compact` is
+/// the caller's per-call choice of whitespace: none (the default), or `false` for two-space
+/// indentation. Only whitespace changes; the fields and their order are the same either way.
+pub fn to_json<T: Serialize>(value: &T, compact: Option<bool>) -> Result<String, JsValue> {
+    let json = serde_json::to_string(value).map_err(|e| JsValue::from_str(&format!("Failed to serialize: {}", e)))?;
+    if compact.unwrap_or(true) {
+        Ok(json)
+    } else {
+        Ok(indent(&json))
+    }
+}
+
+/// `to_json` with amounts written in `amounts`, for results that carry them.
+#[cfg(any(feature = "wallet", feature = "signing", feature = "psbt", feature = "decode", feature = "http"))]
+pub fn to_json_with_amounts<T: Serialize>(value: &T, amounts: AmountFormat, compact: Option<bool>) -> Result<String, JsValue> {
+    with_amount_format(amounts, || to_json(value, compact))
+}
+
+/// Rewrites `json` in a canonical form for hashing or comparing API responses: object
+/// keys sorted by UTF-16 code units, no whitespace and numbers written the way JS
+/// prints them, as RFC 8785 (JCS) does. Integers are kept exact even past 2^53, since
+/// amounts are integers and a hash over a rounded amount would be worse than useless.
+#[wasm_bindgen]
+pub fn canonicalize_json(json: &str) -> Result<String, JsValue> {
+    let value: Value = serde_json::from_str(json).map_err(|e| JsValue::from_str(&format!("Invalid JSON: {}", e)))?;
+    let mut out = String::with_capacity(json.len());
+    write_canonical(&value, &mut out);
+    Ok(out)
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Null | Value::Bool(_) | Value::String(_) => out.push_str(&value.to_string()),
+        Value::Number(number) => match number.as_f64().filter(|_| number.is_f64()) {
+            Some(float) => out.push_str(&js_number(float)),
+            None => out.push_str(&number.to_string()),
+        },
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+            out.push('{');
+            for (index, (key, item)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// ECMAScript's Number::toString: plain decimals from 1e-6 up to 1e21, exponents
+/// outside that, and no trailing `.0`.
+fn js_number(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let magnitude = value.abs();
+    if (1e-6..1e21).contains(&magnitude) {
+        return value.to_string();
+    }
+    let text = format!("{:e}", value);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) if !exponent.starts_with('-') => format!("{}e+{}", mantissa, exponent),
+        _ => text,
+    }
+}
+
+/// Indents compact JSON the way `serde_json::to_string_pretty` would. Working on the
+/// text rather than a `Value` keeps the field order, including inside job results
+/// stored as raw JSON.
+fn indent(json: &str) -> String {
+    let mut out = String::with_capacity(json.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = json.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                if matches!(chars.peek(), Some('}' | ']')) {
+                    out.push(chars.next().expect("peeked"));
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn newline(out: &mut String, depth: usize) {
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
+
+/// Parses a JSON array element by element, reporting every bad element with its
+/// index and field path (e.g. `inputs[2].amount must be a number (got string "1e5")`).
+pub fn parse_json_list<T: DeserializeOwned>(json: &str, what: &str) -> Result<Vec<T>, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid {} JSON: {}", what, e))?;
+    let Value::Array(items) = value else {
+        return Err(format!("{} must be a JSON array (got {})", what, value_kind(&value)));
+    };
+
+    let mut parsed = Vec::with_capacity(items.len());
+    let mut problems = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        match deserialize_at(item, &format!("{}[{}]", what, index)) {
+            Ok(value) => parsed.push(value),
+            Err(problem) => problems.push(problem),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+/// Same as `parse_json_list` for a single JSON value such as an options object.
+pub fn parse_json_value<T: DeserializeOwned>(json: &str, what: &str) -> Result<T, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid {} JSON: {}", what, e))?;
+    deserialize_at(value, what)
+}
+
+fn deserialize_at<T: DeserializeOwned>(value: Value, location: &str) -> Result<T, String> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        let location = match path.as_str() {
+            "" | "." => location.to_string(),
+            p if p.starts_with('[') => format!("{}{}", location, p),
+            p => format!("{}.{}", location, p),
+        };
+        describe_error(&location, &e.into_inner().to_string())
+    })
+}
+
+fn describe_error(location: &str, message: &str) -> String {
+    if let Some(field) = message.strip_prefix("missing field `").and_then(|rest| rest.strip_suffix('`')) {
+        return format!("{}.{} is missing", location, field);
+    }
+    if let Some((got, expected)) = message.strip_prefix("invalid type: ").and_then(|rest| rest.split_once(", expected ")) {
+        return format!("{} must be {} (got {})", location, expected_kind(expected), got);
+    }
+    format!("{}: {}", location, message)
+}
+
+fn expected_kind(expected: &str) -> &str {
+    match expected {
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i32" | "i64" | "f64" => "a number",
+        "a string" | "a borrowed string" => "a string",
+        "a boolean" => "a boolean",
+        other => other,
+    }
+}
+
+pub fn value_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::{Deserializer, MapAccess, Visitor};
+    use serde_json::value::RawValue;
+
+    /// An object's fields in the order they were written, which `Value` doesn't keep.
+    struct Fields(Vec<(String, Box<RawValue>)>);
+
+    impl<'de> serde::Deserialize<'de> for Fields {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct FieldsVisitor;
+            impl<'de> Visitor<'de> for FieldsVisitor {
+                type Value = Fields;
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("an object")
+                }
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Fields, A::Error> {
+                    let mut fields = Vec::new();
+                    while let Some(entry) = map.next_entry()? {
+                        fields.push(entry);
+                    }
+                    Ok(Fields(fields))
+                }
+            }
+            deserializer.deserialize_map(FieldsVisitor)
+        }
+    }
+
+    fn fields(json: &str) -> Vec<(String, Box<RawValue>)> {
+        serde_json::from_str::<Fields>(json).unwrap().0
+    }
+
+    /// Fields may be added after the recorded ones, but none may move or go missing.
+    fn assert_appended(json: &str, recorded: &[&str]) {
+        let names: Vec<String> = fields(json).into_iter().map(|(name, _)| name).collect();
+        assert!(
+            names.len() >= recorded.len() && names.iter().zip(recorded).all(|(name, expected)| name == expected),
+            "fields {:?} don't start with the recorded {:?}; new fields go at the end",
+            names,
+            recorded
+        );
+    }
+
+    fn field(json: &str, name: &str) -> String {
+        fields(json).into_iter().find(|(key, _)| key == name).unwrap().1.get().to_string()
+    }
+
+    fn inputs() -> String {
+        serde_json::json!([{
+            "txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            "vout": 0,
+            "amountSat": 5_000_000_000u64,
+            "scriptPubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+        }])
+        .to_string()
+    }
+
+    fn outputs() -> String {
+        serde_json::json!([{ "scriptPubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6", "amountSat": 4_999_990_000u64 }])
+            .to_string()
+    }
+
+    #[test]
+    fn build_result_fields_are_append_only() {
+        let built = crate::transaction::build_transaction_detailed(&inputs(), &outputs(), None, None, None, None, None, None, None).unwrap();
+        assert_appended(&built, &["txHex", "inputOrder", "outputOrder", "changeVout", "changeIndex", "warnings"]);
+        // Absent values are null rather than missing.
+        assert_eq!(field(&built, "changeVout"), "null");
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn decode_result_fields_are_append_only() {
+        let tx_hex = crate::transaction::build_transaction(&inputs(), &outputs(), 0, None, None, None, None, None, None).unwrap();
+        let decoded = crate::decode::decode_transaction(&tx_hex, None).unwrap();
+        assert_appended(&decoded, &[
+            "txid", "wtxid", "version", "versionDescription", "lockTime", "antiFeeSniping",
+            "size", "vsize", "weight", "segwit", "inputCount", "outputCount",
+        ]);
+    }
+
+    #[cfg(feature = "wallet")]
+    #[test]
+    fn selection_result_fields_are_append_only() {
+        let selection = crate::selection::select_coins(&inputs(), 1_000_000, 2.0, 10.0, None, None, None).unwrap();
+        assert_appended(&selection, &[
+            "selected", "excludedNegativeValue", "totalSelected", "fee", "change",
+            "changeless", "waste", "algorithm", "relaxed", "warnings",
+        ]);
+        let selected = field(&selection, "selected");
+        let first: Vec<Box<RawValue>> = serde_json::from_str(&selected).unwrap();
+        assert_appended(first[0].get(), &["txid", "vout", "amountSat", "effectiveValue"]);
+    }
+
+    #[test]
+    fn pretty_output_keeps_field_order() {
+        let built = crate::transaction::build_transaction_detailed(&inputs(), &outputs(), None, None, None, None, None, None, Some(false)).unwrap();
+        assert!(built.contains("\n  \"txHex\""), "{}", built);
+        assert_appended(&built, &["txHex", "inputOrder", "outputOrder", "changeVout", "changeIndex", "warnings"]);
+    }
+}
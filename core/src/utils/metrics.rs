@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::utils::json::to_json;
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Checked once per instrumented call, so a disabled build pays one relaxed load.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static METRICS: RefCell<Metrics> = RefCell::new(Metrics::new(DEFAULT_CAPACITY));
+}
+
+struct Record {
+    operation: &'static str,
+    started_ms: f64,
+    duration_ms: f64,
+    input_bytes: usize,
+    ok: bool,
+}
+
+struct Metrics {
+    capacity: usize,
+    records: VecDeque<Record>,
+    /// Calls recorded since the last reset, including ones the ring has since dropped.
+    total: u64,
+}
+
+impl Metrics {
+    fn new(capacity: usize) -> Metrics {
+        Metrics { capacity, records: VecDeque::with_capacity(capacity), total: 0 }
+    }
+
+    fn push(&mut self, record: Record) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+        self.total += 1;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricRecord {
+    pub operation: String,
+    /// `performance.now()` when the call started.
+    #[serde(alias = "started_ms")]
+    pub started_ms: f64,
+    #[serde(alias = "duration_ms")]
+    pub duration_ms: f64,
+    /// Total length of the call's string and byte arguments.
+    #[serde(alias = "input_bytes")]
+    pub input_bytes: usize,
+    /// False when the call returned an error.
+    pub ok: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationSummary {
+    pub operation: String,
+    pub calls: usize,
+    pub errors: usize,
+    #[serde(alias = "total_ms")]
+    pub total_ms: f64,
+    #[serde(alias = "max_ms")]
+    pub max_ms: f64,
+    #[serde(alias = "input_bytes")]
+    pub input_bytes: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MetricsReport {
+    pub enabled: bool,
+    pub capacity: usize,
+    /// Calls recorded since the last reset.
+    pub recorded: u64,
+    /// How many of those the ring buffer no longer holds.
+    pub dropped: u64,
+    /// Oldest first.
+    pub records: Vec<MetricRecord>,
+    /// The records grouped by operation, slowest total first.
+    pub operations: Vec<OperationSummary>,
+}
+
+/// Starts recording each instrumented call's duration, input size and outcome into a ring
+/// buffer of `capacity` records (1024 by default). Changing the capacity clears what was
+/// recorded. Nothing leaves wasm; `get_metrics` hands the records to the caller.
+#[wasm_bindgen]
+pub fn enable_metrics(capacity: Option<usize>) -> Result<(), JsValue> {
+    let capacity = capacity.unwrap_or(DEFAULT_CAPACITY);
+    if capacity == 0 {
+        return Err(JsValue::from_str("Metrics capacity must be at least 1"));
+    }
+    METRICS.with(|metrics| {
+        let mut metrics = metrics.borrow_mut();
+        if metrics.capacity != capacity {
+            *metrics = Metrics::new(capacity);
+        }
+    });
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Stops recording. What was recorded stays until `reset_metrics`.
+#[wasm_bindgen]
+pub fn disable_metrics() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+#[wasm_bindgen]
+pub fn reset_metrics() {
+    METRICS.with(|metrics| {
+        let mut metrics = metrics.borrow_mut();
+        metrics.records.clear();
+        metrics.total = 0;
+    });
+}
+
+#[wasm_bindgen]
+pub fn get_metrics(compact: Option<bool>) -> Result<String, JsValue> {
+    let report = METRICS.with(|metrics| {
+        let metrics = metrics.borrow();
+        let mut operations: Vec<OperationSummary> = Vec::new();
+        for record in &metrics.records {
+            let summary = match operations.iter_mut().position(|summary| summary.operation == record.operation) {
+                Some(position) => &mut operations[position],
+                None => {
+                    operations.push(OperationSummary {
+                        operation: record.operation.to_string(),
+                        calls: 0,
+                        errors: 0,
+                        total_ms: 0.0,
+                        max_ms: 0.0,
+                        input_bytes: 0,
+                    });
+                    operations.last_mut().expect("just pushed")
+                }
+            };
+            summary.calls += 1;
+            summary.errors += usize::from(!record.ok);
+            summary.total_ms += record.duration_ms;
+            summary.max_ms = summary.max_ms.max(record.duration_ms);
+            summary.input_bytes += record.input_bytes;
+        }
+        operations.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+        MetricsReport {
+            enabled: ENABLED.load(Ordering::Relaxed),
+            capacity: metrics.capacity,
+            recorded: metrics.total,
+            dropped: metrics.total - metrics.records.len() as u64,
+            records: metrics
+                .records
+                .iter()
+                .map(|record| MetricRecord {
+                    operation: record.operation.to_string(),
+                    started_ms: record.started_ms,
+                    duration_ms: record.duration_ms,
+                    input_bytes: record.input_bytes,
+                    ok: record.ok,
+                })
+                .collect(),
+            operations,
+        }
+    });
+    to_json(&report, compact)
+}
+
+/// Runs `call`, recording it as `operation` when metrics are enabled. This wraps the body
+/// of a public operation; `input_bytes` is the length of its string and byte arguments.
+pub fn timed<T, E>(operation: &'static str, input_bytes: usize, call: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return call();
+    }
+    let started_ms = now_ms();
+    let result = call();
+    let duration_ms = now_ms() - started_ms;
+    let record = Record { operation, started_ms, duration_ms, input_bytes, ok: result.is_ok() };
+    METRICS.with(|metrics| metrics.borrow_mut().push(record));
+    result
+}
+
+/// `performance.now()`, looked up on the global object so it works in workers too.
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    use wasm_bindgen::JsCast;
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
+        .ok()
+        .and_then(|performance| performance.dyn_into::<web_sys::Performance>().ok())
+        .map(|performance| performance.now())
+        .unwrap_or_else(js_sys::Date::now)
+}
+
+/// Milliseconds since the first call, for native builds where there is no `performance`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
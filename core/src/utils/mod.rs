@@ -1,5 +1,28 @@
 pub mod encoding;
 pub mod logging;
+pub mod byte_order;
+pub mod varint;
+pub mod base64;
+pub mod amount;
+pub mod units;
+pub mod decimal;
+pub mod network;
+#[cfg(feature = "wallet")]
+pub mod rng;
+pub mod json;
+pub mod metrics;
+pub mod step;
+pub mod sanitize;
 
-pub use encoding::{bytes_to_hex, hex_to_bytes};
+pub use encoding::{bytes_to_hex, hex_to_bytes, normalize_hex, HexDecoder, HexMode};
 pub use logging::wasm_log;
+pub use byte_order::{reverse_hash_hex, txid_to_internal, internal_to_txid};
+pub use varint::{varint_encode, varint_decode};
+pub use base64::{base64_encode, base64_decode};
+pub use json::canonicalize_json;
+pub use metrics::{enable_metrics, disable_metrics, reset_metrics, get_metrics};
+pub use decimal::Amount;
+pub use network::network_info;
+pub use sanitize::sanitize_input;
+#[cfg(feature = "wallet")]
+pub use rng::{set_test_seed, clear_test_seed, test_seed_active};
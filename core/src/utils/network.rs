@@ -0,0 +1,489 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::bech32::{segwit, Fe32, Hrp};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{base58, Address, Network, PrivateKey, Script, ScriptBuf, WitnessVersion};
+use crate::utils::json::{parse_json_value, to_json};
+use crate::utils::sanitize::{sanitize, InputKind};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+
+pub const DEFAULT_NETWORK: Network = Network::Testnet;
+
+/// Address and key encoding for a chain. Named networks use rust-bitcoin's parameters.
+/// A JSON descriptor can override them for custom signets, or for networks this crate
+/// doesn't know yet. `network` is the chain the overrides build on; it decides anything
+/// that isn't an encoding (e.g. how WIF keys are written).
+#[derive(Clone, PartialEq, Eq)]
+pub struct NetworkParams {
+    pub network: Network,
+    pub name: String,
+    pub bech32_hrp: Hrp,
+    pub pubkey_hash: u8,
+    pub script_hash: u8,
+    pub xpub_version: [u8; 4],
+    pub xprv_version: [u8; 4],
+    pub signet_challenge: Option<ScriptBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NetworkDescriptor {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default, alias = "bech32_hrp")]
+    pub bech32_hrp: Option<String>,
+    #[serde(default, alias = "pubkey_hash")]
+    pub pubkey_hash: Option<u8>,
+    #[serde(default, alias = "script_hash")]
+    pub script_hash: Option<u8>,
+    #[serde(default, alias = "xpub_version")]
+    pub xpub_version: Option<String>,
+    #[serde(default, alias = "xprv_version")]
+    pub xprv_version: Option<String>,
+    #[serde(default, alias = "signet_challenge")]
+    pub signet_challenge: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInfo {
+    pub name: String,
+    pub base: String,
+    #[serde(alias = "bech32_hrp")]
+    pub bech32_hrp: String,
+    #[serde(alias = "pubkey_hash")]
+    pub pubkey_hash: u8,
+    #[serde(alias = "script_hash")]
+    pub script_hash: u8,
+    #[serde(alias = "xpub_version")]
+    pub xpub_version: String,
+    #[serde(alias = "xprv_version")]
+    pub xprv_version: String,
+    #[serde(alias = "wif_version")]
+    pub wif_version: u8,
+    #[serde(default, alias = "signet_challenge")]
+    pub signet_challenge: Option<String>,
+    pub magic: String,
+    #[serde(alias = "genesis_hash")]
+    pub genesis_hash: String,
+    #[serde(alias = "default_port")]
+    pub default_port: u16,
+    /// The BIP44 coin type, used as the second step of `m/purpose'/coin_type'/account'`.
+    #[serde(alias = "coin_type")]
+    pub coin_type: u32,
+    pub prefixes: EncodingPrefixes,
+}
+
+/// The leading characters of each encoding, worked out from the version bytes above
+/// rather than written down, so they hold for custom networks as well.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodingPrefixes {
+    /// Every first character a P2PKH address can have, e.g. `["m", "n"]` on testnet.
+    pub p2pkh: Vec<String>,
+    pub p2sh: Vec<String>,
+    /// The HRP and separator, e.g. `bc1`.
+    pub segwit: String,
+    #[serde(alias = "wif_compressed")]
+    pub wif_compressed: Vec<String>,
+    #[serde(alias = "wif_uncompressed")]
+    pub wif_uncompressed: Vec<String>,
+    /// The prefix every extended key has, e.g. `tpub`.
+    pub xpub: String,
+    pub xprv: String,
+}
+
+/// Resolves a network name or JSON descriptor to the parameters every other function uses.
+/// This is handy to check a custom signet definition before using it, and gives a UI the
+/// prefixes, ports and hashes to label things with, taken from the same parameters that
+/// encode and validate them.
+#[wasm_bindgen]
+pub fn network_info(network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let wif_version = params.wif_version();
+    let prefixes = EncodingPrefixes {
+        p2pkh: base58_first_chars(&[params.pubkey_hash], 20, &[]),
+        p2sh: base58_first_chars(&[params.script_hash], 20, &[]),
+        segwit: format!("{}1", params.bech32_hrp.to_lowercase()),
+        wif_compressed: base58_first_chars(&[wif_version], 32, &[0x01]),
+        wif_uncompressed: base58_first_chars(&[wif_version], 32, &[]),
+        xpub: base58_common_prefix(&params.xpub_version, 74),
+        xprv: base58_common_prefix(&params.xprv_version, 74),
+    };
+    let info = NetworkInfo {
+        name: params.name.clone(),
+        base: network_name(params.network).to_string(),
+        bech32_hrp: params.bech32_hrp.to_lowercase(),
+        pubkey_hash: params.pubkey_hash,
+        script_hash: params.script_hash,
+        xpub_version: bytes_to_hex(&params.xpub_version),
+        xprv_version: bytes_to_hex(&params.xprv_version),
+        wif_version,
+        signet_challenge: params.signet_challenge.as_ref().map(|script| bytes_to_hex(script.as_bytes())),
+        magic: bytes_to_hex(&params.magic()),
+        genesis_hash: genesis_block(params.network).block_hash().to_string(),
+        default_port: params.default_port(),
+        coin_type: params.coin_type(),
+        prefixes,
+    };
+
+    to_json(&info, compact)
+}
+
+pub fn parse_network(name: &str) -> Result<Network, String> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "mainnet" | "bitcoin" | "main" => Ok(Network::Bitcoin),
+        "testnet" | "testnet3" | "test" => Ok(Network::Testnet),
+        "testnet4" => Ok(Network::Testnet4),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        other => Err(format!(
+            "Unknown network '{}' (expected mainnet, testnet, testnet4, signet or regtest, or a JSON network descriptor)",
+            other
+        )),
+    }
+}
+
+/// Accepts a network name, or a JSON descriptor such as
+/// `{"name":"teamnet","signetChallenge":"5121…51ae"}` or `{"base":"regtest","bech32Hrp":"xrt"}`.
+pub fn parse_network_params(input: &str) -> Result<NetworkParams, String> {
+    if !input.trim_start().starts_with('{') {
+        return Ok(NetworkParams::from_network(parse_network(input)?));
+    }
+
+    let descriptor: NetworkDescriptor = parse_json_value(input, "network")?;
+    let base = match &descriptor.base {
+        Some(base) => parse_network(base)?,
+        None => Network::Signet,
+    };
+    let mut params = NetworkParams::from_network(base);
+
+    if let Some(challenge) = &descriptor.signet_challenge {
+        if base != Network::Signet {
+            return Err(format!("signetChallenge only applies to signet, not {}", network_name(base)));
+        }
+        let bytes = hex_to_bytes(challenge, HexMode::Lenient).map_err(|e| format!("Invalid signetChallenge hex: {}", e))?;
+        if bytes.is_empty() {
+            return Err("signetChallenge cannot be empty".to_string());
+        }
+        params.signet_challenge = Some(ScriptBuf::from_bytes(bytes));
+        params.name = "custom signet".to_string();
+    }
+    if let Some(hrp) = &descriptor.bech32_hrp {
+        params.bech32_hrp = Hrp::parse(hrp.trim()).map_err(|e| format!("Invalid bech32Hrp \"{}\": {}", hrp, e))?;
+    }
+    if let Some(version) = descriptor.pubkey_hash {
+        params.pubkey_hash = version;
+    }
+    if let Some(version) = descriptor.script_hash {
+        params.script_hash = version;
+    }
+    if params.pubkey_hash == params.script_hash {
+        return Err("pubkeyHash and scriptHash versions must differ".to_string());
+    }
+    if let Some(version) = &descriptor.xpub_version {
+        params.xpub_version = parse_key_version(version, "xpubVersion")?;
+    }
+    if let Some(version) = &descriptor.xprv_version {
+        params.xprv_version = parse_key_version(version, "xprvVersion")?;
+    }
+    if params.xpub_version == params.xprv_version {
+        return Err("xpubVersion and xprvVersion must differ".to_string());
+    }
+    if let Some(name) = descriptor.name.filter(|name| !name.trim().is_empty()) {
+        params.name = name.trim().to_string();
+    } else if params != NetworkParams::from_network(base) && params.signet_challenge.is_none() {
+        params.name = format!("custom {}", network_name(base));
+    }
+    Ok(params)
+}
+
+pub fn network_or_default(name: Option<String>) -> Result<NetworkParams, String> {
+    match name {
+        Some(name) => parse_network_params(&name),
+        None => Ok(NetworkParams::from_network(DEFAULT_NETWORK)),
+    }
+}
+
+pub fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "mainnet",
+        Network::Testnet => "testnet",
+        Network::Testnet4 => "testnet4",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+pub fn address_network_label(address: &Address<NetworkUnchecked>) -> &'static str {
+    if address.is_valid_for_network(Network::Bitcoin) {
+        "mainnet"
+    } else if address.is_valid_for_network(Network::Testnet) {
+        if address.is_valid_for_network(Network::Regtest) {
+            "testnet/signet/regtest"
+        } else {
+            "testnet/signet"
+        }
+    } else if address.is_valid_for_network(Network::Regtest) {
+        "regtest"
+    } else {
+        "an unknown network"
+    }
+}
+
+/// Why an address was refused for a network.
+pub enum AddressError {
+    Invalid(String),
+    /// Well-formed, but for another network; carries that network's label.
+    WrongNetwork(&'static str),
+}
+
+/// Parses an address to its scriptPubKey. With `network` set, the address must use that
+/// network's encoding.
+pub fn require_address_network(address: &str, network: Option<&NetworkParams>) -> Result<ScriptBuf, String> {
+    let address = address.trim();
+    parse_address_for_network(address, network).map_err(|e| match e {
+        AddressError::Invalid(message) => message,
+        AddressError::WrongNetwork(label) => format!(
+            "address {} is for {} but transaction targets {}",
+            address,
+            label,
+            network.map_or("any network", NetworkParams::target_name)
+        ),
+    })
+}
+
+/// `require_address_network` without the wording, for callers that phrase their own.
+pub fn parse_address_for_network(address: &str, network: Option<&NetworkParams>) -> Result<ScriptBuf, AddressError> {
+    let address = sanitize(address, InputKind::Address).map_err(AddressError::Invalid)?;
+    let address = address.trim();
+    let params = match network {
+        Some(params) if params.has_custom_encoding() => params,
+        _ => return parse_standard_address(address, network.map(|params| params.network)),
+    };
+
+    params.parse_address(address).ok_or_else(|| match address.parse::<Address<NetworkUnchecked>>() {
+        Ok(unchecked) => AddressError::WrongNetwork(address_network_label(&unchecked)),
+        Err(_) => AddressError::WrongNetwork("an unknown network"),
+    })
+}
+
+fn parse_standard_address(address: &str, network: Option<Network>) -> Result<ScriptBuf, AddressError> {
+    let unchecked: Address<NetworkUnchecked> =
+        address.parse().map_err(|e| AddressError::Invalid(format!("Invalid address: {}", e)))?;
+
+    match network {
+        Some(network) if !unchecked.is_valid_for_network(network) => Err(AddressError::WrongNetwork(address_network_label(&unchecked))),
+        _ => Ok(unchecked.assume_checked().script_pubkey()),
+    }
+}
+
+impl NetworkParams {
+    pub fn from_network(network: Network) -> NetworkParams {
+        let (pubkey_hash, script_hash, xpub_version, xprv_version) = match network {
+            Network::Bitcoin => (0x00, 0x05, [0x04, 0x88, 0xb2, 0x1e], [0x04, 0x88, 0xad, 0xe4]),
+            _ => (0x6f, 0xc4, [0x04, 0x35, 0x87, 0xcf], [0x04, 0x35, 0x83, 0x94]),
+        };
+        let hrp = match network {
+            Network::Bitcoin => "bc",
+            Network::Regtest => "bcrt",
+            _ => "tb",
+        };
+        NetworkParams {
+            network,
+            name: network_name(network).to_string(),
+            bech32_hrp: Hrp::parse_unchecked(hrp),
+            pubkey_hash,
+            script_hash,
+            xpub_version,
+            xprv_version,
+            signet_challenge: None,
+        }
+    }
+
+    /// How errors name the network: custom encodings by their own name, everything else
+    /// by the base network.
+    pub fn target_name(&self) -> &str {
+        if self.has_custom_encoding() {
+            &self.name
+        } else {
+            network_name(self.network)
+        }
+    }
+
+    /// True when addresses differ from the base network's, so rust-bitcoin's `Address`
+    /// can't encode them. A custom signet challenge alone doesn't count.
+    pub fn has_custom_encoding(&self) -> bool {
+        let standard = NetworkParams::from_network(self.network);
+        self.bech32_hrp != standard.bech32_hrp
+            || self.pubkey_hash != standard.pubkey_hash
+            || self.script_hash != standard.script_hash
+    }
+
+    /// The address for `script`, or `None` for scripts without an address form (e.g. bare multisig).
+    pub fn address(&self, script: &Script) -> Option<String> {
+        if !self.has_custom_encoding() {
+            return Address::from_script(script, self.network).ok().map(|address| address.to_string());
+        }
+
+        let bytes = script.as_bytes();
+        if script.is_p2pkh() {
+            Some(base58_address(self.pubkey_hash, &bytes[3..23]))
+        } else if script.is_p2sh() {
+            Some(base58_address(self.script_hash, &bytes[2..22]))
+        } else {
+            let version = script.witness_version()?;
+            let version = Fe32::try_from(version.to_num()).ok()?;
+            segwit::encode(self.bech32_hrp, version, &bytes[2..]).ok()
+        }
+    }
+
+    fn parse_address(&self, address: &str) -> Option<ScriptBuf> {
+        if let Ok((hrp, version, program)) = segwit::decode(address) {
+            if hrp != self.bech32_hrp {
+                return None;
+            }
+            let version = WitnessVersion::try_from(version.to_u8()).ok()?;
+            let program = bitcoin::WitnessProgram::new(version, &program).ok()?;
+            return Some(ScriptBuf::new_witness_program(&program));
+        }
+
+        let payload = base58::decode_check(address).ok()?;
+        let (&version, hash) = payload.split_first()?;
+        let hash: [u8; 20] = hash.try_into().ok()?;
+        if version == self.pubkey_hash {
+            Some(ScriptBuf::new_p2pkh(&bitcoin::PubkeyHash::from_byte_array(hash)))
+        } else if version == self.script_hash {
+            Some(ScriptBuf::new_p2sh(&bitcoin::ScriptHash::from_byte_array(hash)))
+        } else {
+            None
+        }
+    }
+
+    /// Rewrites an extended key that uses this network's version bytes to the base network's,
+    /// so rust-bitcoin can parse it. The key may carry an origin (`[f00dbabe/48h/1h]`) and
+    /// derivation steps. Anything else passes through unchanged.
+    #[cfg(feature = "wallet")]
+    pub fn standard_extended_key(&self, key: &str) -> String {
+        let key = key.trim();
+        let start = key.find(']').map_or(0, |i| i + 1);
+        let end = key[start..].find('/').map_or(key.len(), |i| start + i);
+        let mut payload = match base58::decode_check(&key[start..end]) {
+            Ok(payload) if payload.len() == 78 => payload,
+            _ => return key.to_string(),
+        };
+
+        let standard = NetworkParams::from_network(self.network);
+        let replacement = if payload[..4] == self.xpub_version {
+            standard.xpub_version
+        } else if payload[..4] == self.xprv_version {
+            standard.xprv_version
+        } else {
+            return key.to_string();
+        };
+        payload[..4].copy_from_slice(&replacement);
+        format!("{}{}{}", &key[..start], base58::encode_check(&payload), &key[end..])
+    }
+
+    /// The WIF version byte, read back from rust-bitcoin's own encoding of a key.
+    pub fn wif_version(&self) -> u8 {
+        let key = SecretKey::from_slice(&[1; 32]).expect("valid secret key");
+        let wif = PrivateKey::new(key, self.network).to_wif();
+        base58::decode_check(&wif).expect("rust-bitcoin WIF")[0]
+    }
+
+    /// Bitcoin Core's default P2P port. Every signet, custom or not, uses the signet port.
+    pub fn default_port(&self) -> u16 {
+        match self.network {
+            Network::Bitcoin => 8333,
+            Network::Testnet => 18333,
+            Network::Testnet4 => 48333,
+            Network::Signet => 38333,
+            _ => 18444,
+        }
+    }
+
+    /// SLIP-44 registers 0 for mainnet and 1 for every test network.
+    pub fn coin_type(&self) -> u32 {
+        match self.network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        }
+    }
+
+    /// The P2P message start. On signet it's derived from the challenge script, so every
+    /// custom signet gets its own.
+    pub fn magic(&self) -> [u8; 4] {
+        match &self.signet_challenge {
+            Some(challenge) => {
+                let mut data = Vec::with_capacity(challenge.len() + 9);
+                bitcoin::consensus::Encodable::consensus_encode(challenge, &mut data).expect("vec write");
+                let hash = sha256d::Hash::hash(&data).to_byte_array();
+                [hash[0], hash[1], hash[2], hash[3]]
+            }
+            None => self.network.magic().to_bytes(),
+        }
+    }
+}
+
+fn base58_address(version: u8, hash: &[u8]) -> String {
+    let mut payload = Vec::with_capacity(21);
+    payload.push(version);
+    payload.extend_from_slice(hash);
+    base58::encode_check(&payload)
+}
+
+/// The first characters of every base58check string of `version ‖ payload_len bytes ‖
+/// suffix`, found from the smallest and largest such value. Both can be one digit apart in
+/// length, in which case the shorter run ends at `z` and the longer one starts at `2`.
+fn base58_first_chars(version: &[u8], payload_len: usize, suffix: &[u8]) -> Vec<String> {
+    const ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    if version.first() == Some(&0) {
+        return vec!["1".to_string()];
+    }
+    let (low, high) = base58_bounds(version, payload_len, suffix);
+    let position = |encoded: &str| ALPHABET.find(&encoded[..1]).expect("base58 character");
+    let (low_first, high_first) = (position(&low), position(&high));
+    let ranges = if low.len() == high.len() {
+        vec![low_first..=high_first]
+    } else {
+        vec![low_first..=ALPHABET.len() - 1, 1..=high_first]
+    };
+    let mut chars: Vec<String> = Vec::new();
+    for index in ranges.into_iter().flatten() {
+        let char = ALPHABET[index..=index].to_string();
+        if !chars.contains(&char) {
+            chars.push(char);
+        }
+    }
+    chars.sort_by_key(|char| ALPHABET.find(char.as_str()));
+    chars
+}
+
+/// The prefix every base58check string of `version ‖ payload_len bytes` starts with.
+fn base58_common_prefix(version: &[u8], payload_len: usize) -> String {
+    let (low, high) = base58_bounds(version, payload_len, &[]);
+    low.chars().zip(high.chars()).take_while(|(a, b)| a == b).map(|(char, _)| char).collect()
+}
+
+/// The base58 of the smallest and largest `version ‖ payload ‖ suffix ‖ checksum`.
+fn base58_bounds(version: &[u8], payload_len: usize, suffix: &[u8]) -> (String, String) {
+    let bound = |fill: u8| {
+        let mut data = version.to_vec();
+        data.resize(version.len() + payload_len, fill);
+        data.extend_from_slice(suffix);
+        data.extend([fill; 4]);
+        base58::encode(&data)
+    };
+    (bound(0x00), bound(0xff))
+}
+
+fn parse_key_version(hex: &str, what: &str) -> Result<[u8; 4], String> {
+    let bytes = hex_to_bytes(hex, HexMode::Lenient).map_err(|e| format!("Invalid {} hex: {}", what, e))?;
+    bytes.try_into().map_err(|_| format!("{} must be 4 bytes", what))
+}
@@ -0,0 +1,51 @@
+use std::cell::RefCell;
+use bitcoin::hashes::{sha256, Hash};
+use rand::RngCore;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use wasm_bindgen::prelude::*;
+use crate::utils::{hex_to_bytes, HexMode};
+
+pub const TEST_SEED_ACKNOWLEDGEMENT: &str = "I know this is for tests";
+
+thread_local! {
+    static TEST_RNG: RefCell<Option<ChaCha20Rng>> = const { RefCell::new(None) };
+}
+
+#[wasm_bindgen]
+pub fn set_test_seed(seed_hex: &str, acknowledgement: Option<String>) -> Result<(), JsValue> {
+    let acknowledged = acknowledgement.as_deref() == Some(TEST_SEED_ACKNOWLEDGEMENT);
+    if !cfg!(feature = "unsafe_deterministic") && !acknowledged {
+        return Err(JsValue::from_str(&format!(
+            "Deterministic randomness is for tests only: build with the unsafe_deterministic feature or pass \"{}\"",
+            TEST_SEED_ACKNOWLEDGEMENT
+        )));
+    }
+
+    let seed_bytes = hex_to_bytes(seed_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid seed hex: {}", e)))?;
+    if seed_bytes.is_empty() {
+        return Err(JsValue::from_str("Seed must not be empty"));
+    }
+
+    let seed = sha256::Hash::hash(&seed_bytes).to_byte_array();
+    TEST_RNG.with(|rng| *rng.borrow_mut() = Some(ChaCha20Rng::from_seed(seed)));
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn clear_test_seed() {
+    TEST_RNG.with(|rng| *rng.borrow_mut() = None);
+}
+
+#[wasm_bindgen]
+pub fn test_seed_active() -> bool {
+    TEST_RNG.with(|rng| rng.borrow().is_some())
+}
+
+pub fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    TEST_RNG.with(|cell| match cell.borrow_mut().as_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    })
+}
@@ -0,0 +1,346 @@
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::utils::json::to_json;
+
+/// What a string is about to be parsed as, which decides the lookalikes worth mapping:
+/// fullwidth letters and digits in anything ASCII-only, and curly quotes only where `'`
+/// marks a hardened step. Japanese mnemonics keep their ideographic spaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputKind {
+    Hex,
+    Address,
+    Key,
+    Descriptor,
+    Path,
+    Mnemonic,
+    Text,
+}
+
+impl InputKind {
+    pub fn parse(name: &str) -> Result<InputKind, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "hex" => Ok(InputKind::Hex),
+            "address" => Ok(InputKind::Address),
+            "key" => Ok(InputKind::Key),
+            "descriptor" => Ok(InputKind::Descriptor),
+            "path" => Ok(InputKind::Path),
+            "mnemonic" => Ok(InputKind::Mnemonic),
+            "text" => Ok(InputKind::Text),
+            other => Err(format!(
+                "Unknown input kind '{}' (expected hex, address, key, descriptor, path, mnemonic or text)",
+                other
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InputKind::Hex => "hex",
+            InputKind::Address => "address",
+            InputKind::Key => "key",
+            InputKind::Descriptor => "descriptor",
+            InputKind::Path => "path",
+            InputKind::Mnemonic => "mnemonic",
+            InputKind::Text => "text",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            InputKind::Hex => "Hex",
+            InputKind::Address => "Address",
+            InputKind::Key => "Key",
+            InputKind::Descriptor => "Descriptor",
+            InputKind::Path => "Derivation path",
+            InputKind::Mnemonic => "Mnemonic",
+            InputKind::Text => "Text",
+        }
+    }
+
+    fn maps_fullwidth(self) -> bool {
+        !matches!(self, InputKind::Mnemonic | InputKind::Text)
+    }
+
+    fn maps_quotes(self) -> bool {
+        matches!(self, InputKind::Descriptor | InputKind::Path)
+    }
+}
+
+/// A character the sanitizer acted on. `position` counts UTF-16 code units, as JS string
+/// indexes do, in the text as given.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub position: usize,
+    #[serde(alias = "code_point")]
+    pub code_point: String,
+    pub name: String,
+    /// invisible, space, lookalike or invalid.
+    pub category: String,
+    /// removed, replaced, kept or rejected.
+    pub action: String,
+    pub replacement: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SanitizedInput {
+    pub kind: String,
+    /// The text as the parsers see it: formatting characters removed, Unicode spaces and
+    /// lookalikes replaced. When `accepted` is false, what stripping would have left.
+    pub text: String,
+    pub changed: bool,
+    /// Whether the text passes under the requested mode.
+    pub accepted: bool,
+    pub error: Option<String>,
+    pub findings: Vec<Finding>,
+}
+
+enum Action {
+    Remove,
+    Replace(char),
+    Keep,
+    Reject,
+}
+
+/// What the library's own parsers would make of `text` as `kind` (hex, address, key,
+/// descriptor, path, mnemonic or text), so a form can clean or flag a field with the
+/// same rules before submitting it. `mode` is `"strip"` (the default), what the parsers
+/// do, or `"reject"`, which refuses zero-width characters and lookalikes instead of
+/// cleaning them. Only an unknown `kind` or `mode` throws.
+#[wasm_bindgen]
+pub fn sanitize_input(text: &str, kind: &str, mode: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let kind = InputKind::parse(kind).map_err(|e| JsValue::from_str(&e))?;
+    let reject = match mode.as_deref() {
+        None | Some("strip") => false,
+        Some("reject") => true,
+        Some(other) => {
+            return Err(JsValue::from_str(&format!(
+                "Unknown input sanitization '{}' (expected \"strip\" or \"reject\")",
+                other
+            )))
+        }
+    };
+    let (cleaned, findings) = scan(text, kind, reject);
+    let error = rejection(&findings, kind);
+    to_json(&SanitizedInput {
+        kind: kind.name().to_string(),
+        changed: cleaned != text,
+        text: cleaned.into_owned(),
+        accepted: error.is_none(),
+        error,
+        findings,
+    }, compact)
+}
+
+/// The text to parse, with zero-width characters removed and lookalikes mapped. ASCII
+/// comes back borrowed without a second look, which is nearly every call.
+pub fn sanitize(text: &str, kind: InputKind) -> Result<Cow<'_, str>, String> {
+    let (cleaned, findings) = scan(text, kind, false);
+    match rejection(&findings, kind) {
+        Some(error) => Err(error),
+        None => Ok(cleaned),
+    }
+}
+
+/// `sanitize` plus a warning for each character it changed, for results that have a
+/// `warnings` field to put them in.
+pub fn sanitize_with_warnings(text: &str, kind: InputKind) -> Result<(Cow<'_, str>, Vec<String>), String> {
+    let (cleaned, findings) = scan(text, kind, false);
+    if let Some(error) = rejection(&findings, kind) {
+        return Err(error);
+    }
+    let warnings = findings
+        .iter()
+        .filter(|finding| finding.action != "kept")
+        .map(|finding| format!("{} at position {} was {}", describe(finding), finding.position, finding.action))
+        .collect();
+    Ok((cleaned, warnings))
+}
+
+/// Names the first character outside ASCII and where it is, for parsers that only take
+/// ASCII and would otherwise report a byte offset into UTF-8.
+pub fn non_ascii_error(text: &str, kind: InputKind) -> Option<String> {
+    let mut position = 0;
+    for c in text.chars() {
+        if !c.is_ascii() {
+            return Some(format!(
+                "{} contains {} at position {}",
+                kind.label(),
+                character_name(c).map_or_else(|| format!("{} ({:?})", code_point(c), c), |name| format!("{} {}", code_point(c), name)),
+                position
+            ));
+        }
+        position += c.len_utf16();
+    }
+    None
+}
+
+fn scan(text: &str, kind: InputKind, reject: bool) -> (Cow<'_, str>, Vec<Finding>) {
+    if text.is_ascii() {
+        return (Cow::Borrowed(text), Vec::new());
+    }
+    let mut cleaned = String::with_capacity(text.len());
+    let mut findings = Vec::new();
+    let mut position = 0;
+    for c in text.chars() {
+        let Some((category, name, action)) = classify(c, kind) else {
+            cleaned.push(c);
+            position += c.len_utf16();
+            continue;
+        };
+        // A rejected character still gets its usual treatment in `cleaned`, so that
+        // `sanitize_input` can show what stripping would have given.
+        let (mut label, replacement) = match action {
+            Action::Remove => ("removed", None),
+            Action::Replace(ascii) => {
+                cleaned.push(ascii);
+                ("replaced", Some(ascii.to_string()))
+            }
+            Action::Keep => {
+                cleaned.push(c);
+                ("kept", None)
+            }
+            Action::Reject => ("rejected", None),
+        };
+        if reject && matches!(category, "invisible" | "lookalike") {
+            label = "rejected";
+        }
+        findings.push(Finding {
+            position,
+            code_point: code_point(c),
+            name,
+            category: category.to_string(),
+            action: label.to_string(),
+            replacement,
+        });
+        position += c.len_utf16();
+    }
+    (Cow::Owned(cleaned), findings)
+}
+
+fn classify(c: char, kind: InputKind) -> Option<(&'static str, String, Action)> {
+    if let Some(name) = invisible_name(c) {
+        return Some(("invisible", name.to_string(), Action::Remove));
+    }
+    if let Some(name) = space_name(c) {
+        let action = match (c, kind) {
+            ('\u{3000}', InputKind::Mnemonic) => Action::Keep,
+            _ => Action::Replace(' '),
+        };
+        return Some(("space", name.to_string(), action));
+    }
+    if c == '\u{FFFD}' {
+        let action = if kind == InputKind::Text { Action::Keep } else { Action::Reject };
+        return Some(("invalid", "REPLACEMENT CHARACTER".to_string(), action));
+    }
+    if kind.maps_fullwidth() && ('\u{FF01}'..='\u{FF5E}').contains(&c) {
+        let ascii = char::from_u32(c as u32 - 0xFEE0).expect("fullwidth forms map onto printable ASCII");
+        return Some(("lookalike", format!("FULLWIDTH {:?}", ascii), Action::Replace(ascii)));
+    }
+    if kind.maps_quotes() {
+        let (name, ascii) = match c {
+            '\u{2018}' => ("LEFT SINGLE QUOTATION MARK", '\''),
+            '\u{2019}' => ("RIGHT SINGLE QUOTATION MARK", '\''),
+            '\u{2032}' => ("PRIME", '\''),
+            '\u{02B9}' => ("MODIFIER LETTER PRIME", '\''),
+            '\u{201C}' => ("LEFT DOUBLE QUOTATION MARK", '"'),
+            '\u{201D}' => ("RIGHT DOUBLE QUOTATION MARK", '"'),
+            _ => return None,
+        };
+        return Some(("lookalike", name.to_string(), Action::Replace(ascii)));
+    }
+    None
+}
+
+fn invisible_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{00AD}' => "SOFT HYPHEN",
+        '\u{180E}' => "MONGOLIAN VOWEL SEPARATOR",
+        '\u{200B}' => "ZERO WIDTH SPACE",
+        '\u{200C}' => "ZERO WIDTH NON-JOINER",
+        '\u{200D}' => "ZERO WIDTH JOINER",
+        '\u{200E}' => "LEFT-TO-RIGHT MARK",
+        '\u{200F}' => "RIGHT-TO-LEFT MARK",
+        '\u{202A}' => "LEFT-TO-RIGHT EMBEDDING",
+        '\u{202B}' => "RIGHT-TO-LEFT EMBEDDING",
+        '\u{202C}' => "POP DIRECTIONAL FORMATTING",
+        '\u{202D}' => "LEFT-TO-RIGHT OVERRIDE",
+        '\u{202E}' => "RIGHT-TO-LEFT OVERRIDE",
+        '\u{2060}' => "WORD JOINER",
+        '\u{2061}' => "FUNCTION APPLICATION",
+        '\u{2062}' => "INVISIBLE TIMES",
+        '\u{2063}' => "INVISIBLE SEPARATOR",
+        '\u{2064}' => "INVISIBLE PLUS",
+        '\u{2066}' => "LEFT-TO-RIGHT ISOLATE",
+        '\u{2067}' => "RIGHT-TO-LEFT ISOLATE",
+        '\u{2068}' => "FIRST STRONG ISOLATE",
+        '\u{2069}' => "POP DIRECTIONAL ISOLATE",
+        '\u{FEFF}' => "ZERO WIDTH NO-BREAK SPACE",
+        _ => return None,
+    })
+}
+
+fn space_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{0085}' => "NEXT LINE",
+        '\u{00A0}' => "NO-BREAK SPACE",
+        '\u{1680}' => "OGHAM SPACE MARK",
+        '\u{2000}' => "EN QUAD",
+        '\u{2001}' => "EM QUAD",
+        '\u{2002}' => "EN SPACE",
+        '\u{2003}' => "EM SPACE",
+        '\u{2004}' => "THREE-PER-EM SPACE",
+        '\u{2005}' => "FOUR-PER-EM SPACE",
+        '\u{2006}' => "SIX-PER-EM SPACE",
+        '\u{2007}' => "FIGURE SPACE",
+        '\u{2008}' => "PUNCTUATION SPACE",
+        '\u{2009}' => "THIN SPACE",
+        '\u{200A}' => "HAIR SPACE",
+        '\u{2028}' => "LINE SEPARATOR",
+        '\u{2029}' => "PARAGRAPH SEPARATOR",
+        '\u{202F}' => "NARROW NO-BREAK SPACE",
+        '\u{205F}' => "MEDIUM MATHEMATICAL SPACE",
+        '\u{3000}' => "IDEOGRAPHIC SPACE",
+        _ => return None,
+    })
+}
+
+fn character_name(c: char) -> Option<String> {
+    classify(c, InputKind::Descriptor).map(|(_, name, _)| name)
+}
+
+fn code_point(c: char) -> String {
+    format!("U+{:04X}", c as u32)
+}
+
+fn describe(finding: &Finding) -> String {
+    format!("{} {}", finding.code_point, finding.name)
+}
+
+/// Why the findings stop the text from being parsed, naming up to three characters.
+fn rejection(findings: &[Finding], kind: InputKind) -> Option<String> {
+    let rejected: Vec<&Finding> = findings.iter().filter(|finding| finding.action == "rejected").collect();
+    if let Some(invalid) = rejected.iter().find(|finding| finding.category == "invalid") {
+        return Some(format!(
+            "{} has malformed UTF-16 at position {} (a lone surrogate, usually left by copying half of a character)",
+            kind.label(),
+            invalid.position
+        ));
+    }
+    if rejected.is_empty() {
+        return None;
+    }
+    let listed: Vec<String> =
+        rejected.iter().take(3).map(|finding| format!("{} at position {}", describe(finding), finding.position)).collect();
+    let more = match rejected.len() {
+        count if count > 3 => format!(" and {} more", count - 3),
+        _ => String::new(),
+    };
+    Some(format!(
+        "{} contains {}{} (retype the value or paste it as plain text)",
+        kind.label(),
+        listed.join(", "),
+        more
+    ))
+}
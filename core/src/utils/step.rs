@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+pub use serde_json::value::RawValue;
+use wasm_bindgen::prelude::*;
+use crate::utils::json::to_json;
+
+/// What every resumable job's `step(max_work_units)` returns. `result` is set once
+/// `done`, and holds what the one-shot function would have returned.
+#[derive(Serialize, Deserialize)]
+pub struct StepResult {
+    pub done: bool,
+    pub progress: Progress,
+    pub result: Option<Box<RawValue>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Progress {
+    pub completed: u64,
+    /// `None` when the amount of work isn't known up front (vanity search).
+    pub total: Option<u64>,
+    /// Work units the job is expected to take when `total` is unknown.
+    #[serde(default)]
+    pub expected: Option<f64>,
+}
+
+impl Progress {
+    pub fn of(completed: usize, total: usize) -> Progress {
+        Progress { completed: completed as u64, total: Some(total as u64), expected: None }
+    }
+}
+
+pub fn check_work_units(max_work_units: u32) -> Result<usize, JsValue> {
+    if max_work_units == 0 {
+        return Err(JsValue::from_str("max_work_units must be at least 1"));
+    }
+    Ok(max_work_units as usize)
+}
+
+/// Serializes a job's finished result once, so later `step` calls can repeat it. It's
+/// kept as raw JSON because a `Value` would sort its fields.
+pub fn to_result<T: Serialize>(result: &T) -> Result<Box<RawValue>, JsValue> {
+    serde_json::value::to_raw_value(result).map_err(|e| JsValue::from_str(&format!("Failed to serialize: {}", e)))
+}
+
+pub fn step_json(progress: Progress, result: Option<&RawValue>, compact: Option<bool>) -> Result<String, JsValue> {
+    let step = StepResult { done: result.is_some(), progress, result: result.map(RawValue::to_owned) };
+    to_json(&step, compact)
+}
@@ -0,0 +1,84 @@
+// Amounts and fee rates as distinct types, so sats and fee rates can't be swapped and a
+// sat/vB rate can't be read as sat/kWU. Amounts are rust-bitcoin's `Amount`; fee rates
+// wrap an `f64` sat/vB rather than rust-bitcoin's `FeeRate`, whose whole sat/kWU can't
+// hold rates like 1.01 sat/vB that wallets and Bitcoin Core's sat/kvB accept.
+
+use std::fmt;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+pub use bitcoin::Amount;
+
+/// Weight units per virtual byte.
+const WITNESS_SCALE_FACTOR: f64 = 4.0;
+
+/// A fee rate in sat/vB: finite and non-negative by construction. Serializes as that
+/// number, which is what every JSON field named `*fee_rate_sat_vb` holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct FeeRate(f64);
+
+impl FeeRate {
+    pub const ZERO: FeeRate = FeeRate(0.0);
+
+    pub fn from_sat_per_vb(sat_vb: f64) -> Result<FeeRate, String> {
+        if sat_vb.is_finite() && sat_vb >= 0.0 {
+            Ok(FeeRate(sat_vb))
+        } else {
+            Err(format!("Fee rate must be a finite, non-negative number of sat/vB, got {}", sat_vb))
+        }
+    }
+
+    pub fn from_sat_per_kwu(sat_kwu: u64) -> FeeRate {
+        FeeRate(sat_kwu as f64 * WITNESS_SCALE_FACTOR / 1000.0)
+    }
+
+    /// The rate a transaction paid: `fee` over `vbytes`, or zero for an empty one.
+    pub fn from_fee(fee: Amount, vbytes: f64) -> FeeRate {
+        if vbytes > 0.0 {
+            FeeRate(fee.to_sat() as f64 / vbytes)
+        } else {
+            FeeRate::ZERO
+        }
+    }
+
+    pub fn sat_per_vb(self) -> f64 {
+        self.0
+    }
+
+    pub fn sat_per_kwu(self) -> f64 {
+        self.0 * 1000.0 / WITNESS_SCALE_FACTOR
+    }
+
+    /// The fee for `vbytes` at this rate, rounded up and capped at the 21M coin supply.
+    pub fn fee_for_vbytes(self, vbytes: f64) -> Amount {
+        Amount::from_sat(((vbytes * self.0).ceil() as u64).min(Amount::MAX_MONEY.to_sat()))
+    }
+
+    /// rust-bitcoin's sat/kWU rate, rounded up so fees computed from it never fall short.
+    pub fn to_bitcoin(self) -> bitcoin::FeeRate {
+        bitcoin::FeeRate::from_sat_per_kwu(self.sat_per_kwu().ceil() as u64)
+    }
+}
+
+impl From<bitcoin::FeeRate> for FeeRate {
+    fn from(rate: bitcoin::FeeRate) -> FeeRate {
+        FeeRate::from_sat_per_kwu(rate.to_sat_per_kwu())
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sat/vB", self.0)
+    }
+}
+
+impl Serialize for FeeRate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FeeRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FeeRate, D::Error> {
+        FeeRate::from_sat_per_vb(f64::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedVarint {
+    pub value: u64,
+    #[serde(alias = "bytes_consumed")]
+    pub bytes_consumed: usize,
+    pub canonical: bool,
+}
+
+#[wasm_bindgen]
+pub fn varint_encode(value: u64) -> String {
+    bytes_to_hex(&encode_compact_size(value))
+}
+
+#[wasm_bindgen]
+pub fn varint_decode(hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let bytes = hex_to_bytes(hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid varint hex: {}", e)))?;
+
+    let decoded = decode_compact_size(&bytes).map_err(|e| JsValue::from_str(&e))?;
+
+    to_json(&decoded, compact)
+}
+
+pub fn encode_compact_size(value: u64) -> Vec<u8> {
+    match value {
+        0..=0xfc => vec![value as u8],
+        0xfd..=0xffff => {
+            let mut bytes = vec![0xfd];
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+            bytes
+        }
+        0x1_0000..=0xffff_ffff => {
+            let mut bytes = vec![0xfe];
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+            bytes
+        }
+        _ => {
+            let mut bytes = vec![0xff];
+            bytes.extend_from_slice(&value.to_le_bytes());
+            bytes
+        }
+    }
+}
+
+pub fn decode_compact_size(bytes: &[u8]) -> Result<DecodedVarint, String> {
+    let prefix = *bytes.first().ok_or("Varint is empty")?;
+
+    let width = match prefix {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        _ => {
+            return Ok(DecodedVarint {
+                value: prefix as u64,
+                bytes_consumed: 1,
+                canonical: true,
+            })
+        }
+    };
+
+    if bytes.len() < 1 + width {
+        return Err(format!(
+            "Varint with prefix 0x{:02x} needs {} bytes, only {} available",
+            prefix,
+            1 + width,
+            bytes.len()
+        ));
+    }
+
+    let mut buf = [0u8; 8];
+    buf[..width].copy_from_slice(&bytes[1..1 + width]);
+    let value = u64::from_le_bytes(buf);
+
+    let minimum = match width {
+        2 => 0xfd,
+        4 => 0x1_0000,
+        _ => 0x1_0000_0000,
+    };
+
+    Ok(DecodedVarint {
+        value,
+        bytes_consumed: 1 + width,
+        canonical: value >= minimum,
+    })
+}
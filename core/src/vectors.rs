@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use crate::utils::json::to_json;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkAddresses {
+    pub network: String,
+    pub wif: String,
+    pub p2pkh: String,
+    /// None for an uncompressed key, which segwit can't use.
+    #[serde(alias = "p2sh_p2wpkh")]
+    pub p2sh_p2wpkh: Option<String>,
+    pub p2wpkh: Option<String>,
+    pub p2tr: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyVector {
+    #[serde(alias = "private_key")]
+    pub private_key: String,
+    #[serde(alias = "public_key")]
+    pub public_key: String,
+    pub compressed: bool,
+    pub networks: Vec<NetworkAddresses>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SighashVector {
+    #[serde(alias = "input_index")]
+    pub input_index: usize,
+    /// `ALL`, or `DEFAULT` for taproot.
+    #[serde(alias = "sighash_type")]
+    pub sighash_type: String,
+    pub digest: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionVector {
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    #[serde(alias = "private_key")]
+    pub private_key: String,
+    /// The spent coin, as `build_transaction` takes an input.
+    pub input: Value,
+    pub outputs: Value,
+    pub prevouts: Value,
+    #[serde(alias = "unsigned_tx_hex")]
+    pub unsigned_tx_hex: String,
+    #[serde(alias = "signed_tx_hex")]
+    pub signed_tx_hex: String,
+    pub txid: String,
+    pub wtxid: String,
+    pub sighash: SighashVector,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PsbtVector {
+    #[serde(alias = "private_key")]
+    pub private_key: String,
+    pub inputs: Value,
+    pub outputs: Value,
+    pub locktime: u32,
+    /// `build_locktime_psbt`'s result.
+    #[serde(alias = "unsigned_psbt")]
+    pub unsigned_psbt: String,
+    /// `sign_locktime_psbt` with `finalize` false: the signature as a partial sig.
+    #[serde(alias = "signed_psbt")]
+    pub signed_psbt: String,
+    /// `sign_locktime_psbt` with `finalize` true, and the transaction it extracts.
+    #[serde(alias = "finalized_psbt")]
+    pub finalized_psbt: String,
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TestVectors {
+    pub keys: Vec<KeyVector>,
+    pub transactions: Vec<TransactionVector>,
+    pub psbt: PsbtVector,
+}
+
+/// The vectors, fixed when they were last reviewed.
+const VECTORS: &str = include_str!("vectors.json");
+
+/// The crate's canonical fixtures for integrators' own tests: known keys with their WIF
+/// and addresses on every network, an unsigned and signed transaction per single-key
+/// script type with its sighash digest, and a PSBT through creation, signing and
+/// finalizing. They are fixed data rather than the library's current output, and the
+/// tests check the library still produces every one, so a change in its output shows
+/// up as a failing test instead of as new vectors.
+#[wasm_bindgen]
+pub fn test_vectors(compact: Option<bool>) -> Result<String, JsValue> {
+    let vectors: TestVectors =
+        serde_json::from_str(VECTORS).map_err(|e| JsValue::from_str(&format!("Failed to read the vectors: {}", e)))?;
+    to_json(&vectors, compact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::sighash::SighashCache;
+    use bitcoin::{Amount, Network, TxOut};
+    use crate::psbt::locktime::{build_locktime_psbt, sign_locktime_psbt};
+    use crate::transaction::sign::{key_spend, key_spend_sighash};
+    use crate::transaction::{build_transaction, decode_tx_hex, sign_all_inputs};
+    use crate::wallet::keys::{address_from_pubkey, export_wif};
+    use crate::wallet::parse_private_key;
+
+    /// The keys every vector is built from: the scalar 1, and the BIP32 test vector 1
+    /// master key.
+    const KEY_ONE: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    const KEY_BIP32: &str = "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35";
+    const NETWORKS: [&str; 5] = ["mainnet", "testnet", "testnet4", "signet", "regtest"];
+    const SCRIPT_TYPES: [&str; 4] = ["p2pkh", "p2sh-p2wpkh", "p2wpkh", "p2tr"];
+    /// The coin every transaction vector spends, and what it pays.
+    const FUNDING_TXID: &str = "a0b1c2d3e4f5061728394a5b6c7d8e9fa0b1c2d3e4f5061728394a5b6c7d8e9f";
+    const FUNDING_AMOUNT: u64 = 100_000;
+    const PAYMENT_AMOUNT: u64 = 99_000;
+    const PSBT_LOCKTIME: u32 = 800_000;
+
+    fn fixed() -> TestVectors {
+        serde_json::from_str(VECTORS).unwrap()
+    }
+
+    fn json<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn keys_match_the_library() {
+        let computed = [key_vector(KEY_ONE, true), key_vector(KEY_ONE, false), key_vector(KEY_BIP32, true)].map(Result::unwrap);
+        assert_eq!(json(&fixed().keys), json(&computed));
+    }
+
+    #[test]
+    fn transactions_match_the_library() {
+        let computed: Vec<_> = SCRIPT_TYPES.iter().map(|script_type| transaction_vector(script_type).unwrap()).collect();
+        assert_eq!(json(&fixed().transactions), json(&computed));
+    }
+
+    #[test]
+    fn psbt_matches_the_library() {
+        assert_eq!(json(&fixed().psbt), json(&psbt_vector().unwrap()));
+    }
+
+    /// Values published elsewhere, so the fixed data doesn't only agree with itself: the
+    /// WIFs and P2PKH addresses of the key 1 (bitcoin wiki), its P2WPKH address from
+    /// BIP173, and the BIP32 test vector 1 master public key.
+    #[test]
+    fn keys_agree_with_published_values() {
+        let keys = fixed().keys;
+        let mainnet = |key: usize| keys[key].networks.iter().find(|network| network.network == "mainnet").unwrap();
+        let (compressed, uncompressed) = (mainnet(0), mainnet(1));
+        assert_eq!(compressed.wif, "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn");
+        assert_eq!(compressed.p2pkh, "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(compressed.p2wpkh.as_deref(), Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+        assert_eq!(uncompressed.wif, "5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf");
+        assert_eq!(uncompressed.p2pkh, "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm");
+        assert_eq!(keys[2].public_key, "0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2");
+    }
+
+    #[test]
+    fn returns_the_fixed_vectors() {
+        let returned: Value = serde_json::from_str(&test_vectors(None).unwrap()).unwrap();
+        assert_eq!(returned, json(&fixed()));
+    }
+
+    fn key_vector(private_key: &str, compressed: bool) -> Result<KeyVector, JsValue> {
+        let secp = Secp256k1::new();
+        let key = parse_private_key(private_key, Some(compressed), Network::Bitcoin)?;
+        let public_key = key.public_key(&secp).to_string();
+        let networks = NETWORKS
+            .iter()
+            .map(|network| {
+                let address = |address_type: &str| address_from_pubkey(&public_key, address_type, Some(network.to_string()));
+                Ok(NetworkAddresses {
+                    network: network.to_string(),
+                    wif: export_wif(private_key, Some(compressed), Some(network.to_string()))?,
+                    p2pkh: address("p2pkh")?,
+                    p2sh_p2wpkh: compressed.then(|| address("p2sh-p2wpkh")).transpose()?,
+                    p2wpkh: compressed.then(|| address("p2wpkh")).transpose()?,
+                    p2tr: address("p2tr")?,
+                })
+            })
+            .collect::<Result<Vec<_>, JsValue>>()?;
+        Ok(KeyVector { private_key: private_key.to_string(), public_key, compressed, networks })
+    }
+
+    /// `KEY_BIP32`'s coin of `script_type` paying `KEY_ONE`'s P2WPKH address on testnet.
+    fn transaction_vector(script_type: &str) -> Result<TransactionVector, JsValue> {
+        let secp = Secp256k1::new();
+        let key = parse_private_key(KEY_BIP32, None, Network::Testnet)?;
+        let spent_address = address_from_pubkey(&key.public_key(&secp).to_string(), script_type, None)?;
+        let script_pubkey = crate::transaction::address_script(&spent_address, None)?;
+        let payee = parse_private_key(KEY_ONE, None, Network::Testnet)?;
+        let payee_address = address_from_pubkey(&payee.public_key(&secp).to_string(), "p2wpkh", None)?;
+
+        let input = serde_json::json!({
+            "txid": FUNDING_TXID,
+            "vout": 0,
+            "amountSat": FUNDING_AMOUNT,
+            "scriptPubkey": script_pubkey.to_hex_string(),
+        });
+        let outputs = serde_json::json!([{ "address": payee_address, "amountSat": PAYMENT_AMOUNT }]);
+        let prevouts = serde_json::json!([{ "amountSat": FUNDING_AMOUNT, "scriptPubkey": script_pubkey.to_hex_string() }]);
+        let unsigned_tx_hex = build_transaction(&format!("[{}]", input), &outputs.to_string(), 0, None, None, None, None, None, None)?;
+        let signed: Value = parse_result(&sign_all_inputs(&unsigned_tx_hex, KEY_BIP32, &prevouts.to_string(), None)?)?;
+        let signed_tx_hex = signed["txHex"].as_str().unwrap_or_default().to_string();
+        let signed_tx = decode_tx_hex(&signed_tx_hex)?;
+
+        let unsigned_tx = decode_tx_hex(&unsigned_tx_hex)?;
+        let prevout = TxOut { value: Amount::from_sat(FUNDING_AMOUNT), script_pubkey };
+        let spend = key_spend(&secp, &prevout.script_pubkey, &key.inner.public_key(&secp), key.compressed, "private key")
+            .map_err(|e| JsValue::from_str(&e))?;
+        let digest = key_spend_sighash(&mut SighashCache::new(&unsigned_tx), 0, &prevout, std::slice::from_ref(&prevout), &spend)
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        Ok(TransactionVector {
+            script_type: script_type.to_string(),
+            private_key: KEY_BIP32.to_string(),
+            input,
+            outputs,
+            prevouts,
+            unsigned_tx_hex,
+            signed_tx_hex,
+            txid: signed_tx.compute_txid().to_string(),
+            wtxid: signed_tx.compute_wtxid().to_string(),
+            sighash: SighashVector {
+                input_index: 0,
+                sighash_type: if script_type == "p2tr" { "DEFAULT" } else { "ALL" }.to_string(),
+                digest: digest.to_string(),
+            },
+        })
+    }
+
+    fn psbt_vector() -> Result<PsbtVector, JsValue> {
+        let secp = Secp256k1::new();
+        let key = parse_private_key(KEY_BIP32, None, Network::Testnet)?;
+        let spent_address = address_from_pubkey(&key.public_key(&secp).to_string(), "p2wpkh", None)?;
+        let script_pubkey = crate::transaction::address_script(&spent_address, None)?;
+        let payee = parse_private_key(KEY_ONE, None, Network::Testnet)?;
+        let payee_address = address_from_pubkey(&payee.public_key(&secp).to_string(), "p2tr", None)?;
+
+        let inputs = serde_json::json!([{
+            "txid": FUNDING_TXID,
+            "vout": 1,
+            "amountSat": FUNDING_AMOUNT,
+            "scriptPubkey": script_pubkey.to_hex_string(),
+        }]);
+        let outputs = serde_json::json!([{ "address": payee_address, "amountSat": PAYMENT_AMOUNT }]);
+        let unsigned_psbt = build_locktime_psbt(&inputs.to_string(), &outputs.to_string(), PSBT_LOCKTIME, None)?;
+        let signed: Value = parse_result(&sign_locktime_psbt(&unsigned_psbt, KEY_BIP32, Some(false), None)?)?;
+        let finalized: Value = parse_result(&sign_locktime_psbt(&unsigned_psbt, KEY_BIP32, Some(true), None)?)?;
+
+        Ok(PsbtVector {
+            private_key: KEY_BIP32.to_string(),
+            inputs,
+            outputs,
+            locktime: PSBT_LOCKTIME,
+            unsigned_psbt,
+            signed_psbt: signed["psbt"].as_str().unwrap_or_default().to_string(),
+            finalized_psbt: finalized["psbt"].as_str().unwrap_or_default().to_string(),
+            tx_hex: finalized["txHex"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn parse_result(json: &str) -> Result<Value, JsValue> {
+        serde_json::from_str(json).map_err(|e| JsValue::from_str(&format!("Failed to read a result back: {}", e)))
+    }
+}
@@ -0,0 +1,182 @@
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::{Keypair, Parity, XOnlyPublicKey};
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use bitcoin::Network;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::keys::parse_public_key;
+use crate::wallet::{parse_private_key, reduce_mod_order, tagged_engine};
+
+/// Hex layout: the nonce point R' (33 bytes, compressed) followed by s' (32 bytes).
+const ADAPTOR_SIG_LEN: usize = 65;
+
+/// Schnorr adaptor signature: s' = k + e·x, where R' = k·G and the completed nonce
+/// R = R' + T has an even Y as BIP340 requires. The nonce is deterministic and retried
+/// until that holds, so the secret t is always added and never subtracted.
+#[wasm_bindgen]
+pub fn adaptor_sign(private_key: &str, message_hash: &str, adaptor_point: &str) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let keypair = Keypair::from_secret_key(&secp, &parse_private_key(private_key, None, Network::Testnet)?.inner);
+    let (xonly, parity) = keypair.x_only_public_key();
+    let secret = match parity {
+        Parity::Even => keypair.secret_key(),
+        Parity::Odd => keypair.secret_key().negate(),
+    };
+    let message = parse_message(message_hash)?;
+    let adaptor = parse_public_key(adaptor_point)?.inner;
+
+    for counter in 0u32..u32::MAX {
+        let mut engine = tagged_engine("BitLab/adaptor/nonce");
+        engine.input(&secret.secret_bytes());
+        engine.input(&xonly.serialize());
+        engine.input(&adaptor.serialize());
+        engine.input(&message);
+        engine.input(&counter.to_be_bytes());
+        let Ok(nonce) = SecretKey::from_slice(&sha256::Hash::from_engine(engine).to_byte_array()) else {
+            continue;
+        };
+
+        let nonce_point = nonce.public_key(&secp);
+        let Ok(combined) = nonce_point.combine(&adaptor) else {
+            continue;
+        };
+        let (combined_x, combined_parity) = combined.x_only_public_key();
+        if combined_parity == Parity::Odd {
+            continue;
+        }
+
+        let challenge = challenge(&combined_x, &xonly, &message);
+        let s = secret
+            .mul_tweak(&challenge)
+            .and_then(|ex| nonce.add_tweak(&Scalar::from(ex)))
+            .map_err(|e| JsValue::from_str(&format!("Failed to compute adaptor signature: {}", e)))?;
+
+        let mut signature = nonce_point.serialize().to_vec();
+        signature.extend_from_slice(&s.secret_bytes());
+        return Ok(bytes_to_hex(&signature));
+    }
+    Err(JsValue::from_str("No valid nonce found for this key, message and adaptor point"))
+}
+
+/// Checks s'·G = R' + e·P with e computed over R = R' + T, which guarantees the
+/// signature becomes a valid BIP340 signature once the discrete log of T is added.
+#[wasm_bindgen]
+pub fn adaptor_verify(pubkey: &str, message_hash: &str, adaptor_point: &str, adaptor_sig: &str) -> Result<bool, JsValue> {
+    let secp = Secp256k1::new();
+    let xonly = parse_signing_key(pubkey)?;
+    let message = parse_message(message_hash)?;
+    let adaptor = parse_public_key(adaptor_point)?.inner;
+    let (nonce_point, s) = parse_adaptor_sig(adaptor_sig)?;
+
+    let Ok(combined) = nonce_point.combine(&adaptor) else {
+        return Ok(false);
+    };
+    let (combined_x, combined_parity) = combined.x_only_public_key();
+    if combined_parity == Parity::Odd {
+        return Ok(false);
+    }
+
+    let challenge = challenge(&combined_x, &xonly, &message);
+    let expected = xonly
+        .public_key(Parity::Even)
+        .mul_tweak(&secp, &challenge)
+        .and_then(|ep| ep.combine(&nonce_point));
+    Ok(expected.is_ok_and(|point| point == s.public_key(&secp)))
+}
+
+/// Completes the adaptor signature with the secret t into a 64-byte BIP340 signature (R.x, s' + t).
+#[wasm_bindgen]
+pub fn adaptor_adapt(adaptor_sig: &str, secret: &str) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let (nonce_point, s) = parse_adaptor_sig(adaptor_sig)?;
+    let secret = parse_scalar(secret, "secret")?;
+
+    let combined = nonce_point
+        .combine(&secret.public_key(&secp))
+        .map_err(|_| JsValue::from_str("Secret does not match this adaptor signature"))?;
+    let (combined_x, combined_parity) = combined.x_only_public_key();
+    if combined_parity == Parity::Odd {
+        return Err(JsValue::from_str("Secret does not match this adaptor signature"));
+    }
+
+    let final_s = s
+        .add_tweak(&Scalar::from(secret))
+        .map_err(|e| JsValue::from_str(&format!("Failed to adapt signature: {}", e)))?;
+    let mut signature = combined_x.serialize().to_vec();
+    signature.extend_from_slice(&final_s.secret_bytes());
+    Ok(bytes_to_hex(&signature))
+}
+
+/// Recovers t = s - s' from the published signature, after checking that both signatures share the nonce.
+#[wasm_bindgen]
+pub fn adaptor_extract_secret(adaptor_sig: &str, final_sig: &str) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let (nonce_point, adaptor_s) = parse_adaptor_sig(adaptor_sig)?;
+    let final_bytes = hex_to_bytes(final_sig, HexMode::Strict)
+        .map_err(|e| JsValue::from_str(&format!("Invalid signature hex: {}", e)))?;
+    if final_bytes.len() != 64 {
+        return Err(JsValue::from_str(&format!("Final signature must be 64 bytes, got {}", final_bytes.len())));
+    }
+    let final_s = SecretKey::from_slice(&final_bytes[32..])
+        .map_err(|_| JsValue::from_str("Final signature has an invalid s value"))?;
+
+    let secret = final_s
+        .add_tweak(&Scalar::from(adaptor_s.negate()))
+        .map_err(|_| JsValue::from_str("Signatures are identical, there is no secret to extract"))?;
+
+    let combined = nonce_point
+        .combine(&secret.public_key(&secp))
+        .map_err(|_| JsValue::from_str("Final signature was not produced from this adaptor signature"))?;
+    if combined.x_only_public_key().0.serialize()[..] != final_bytes[..32] {
+        return Err(JsValue::from_str("Final signature was not produced from this adaptor signature"));
+    }
+    Ok(bytes_to_hex(&secret.secret_bytes()))
+}
+
+pub fn parse_message(message_hash: &str) -> Result<[u8; 32], JsValue> {
+    hex_to_bytes(message_hash, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid message hash hex: {}", e)))?
+        .try_into()
+        .map_err(|_| JsValue::from_str("Message hash must be 32 bytes"))
+}
+
+/// Accepts the BIP340 x-only key or a full public key.
+fn parse_signing_key(pubkey: &str) -> Result<XOnlyPublicKey, JsValue> {
+    let bytes = hex_to_bytes(pubkey, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid public key hex: {}", e)))?;
+    match bytes.len() {
+        32 => XOnlyPublicKey::from_slice(&bytes).map_err(|e| JsValue::from_str(&format!("Invalid x-only key: {}", e))),
+        _ => Ok(parse_public_key(pubkey)?.inner.x_only_public_key().0),
+    }
+}
+
+fn parse_adaptor_sig(adaptor_sig: &str) -> Result<(PublicKey, SecretKey), JsValue> {
+    let bytes = hex_to_bytes(adaptor_sig, HexMode::Strict)
+        .map_err(|e| JsValue::from_str(&format!("Invalid adaptor signature hex: {}", e)))?;
+    if bytes.len() != ADAPTOR_SIG_LEN {
+        return Err(JsValue::from_str(&format!(
+            "Adaptor signature must be {} bytes, got {}",
+            ADAPTOR_SIG_LEN,
+            bytes.len()
+        )));
+    }
+    let nonce_point = PublicKey::from_slice(&bytes[..33])
+        .map_err(|e| JsValue::from_str(&format!("Invalid adaptor nonce point: {}", e)))?;
+    let s = SecretKey::from_slice(&bytes[33..])
+        .map_err(|_| JsValue::from_str("Adaptor signature has an invalid s value"))?;
+    Ok((nonce_point, s))
+}
+
+fn parse_scalar(hex: &str, what: &str) -> Result<SecretKey, JsValue> {
+    let bytes = hex_to_bytes(hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid {} hex: {}", what, e)))?;
+    SecretKey::from_slice(&bytes)
+        .map_err(|_| JsValue::from_str(&format!("The {} must be a 32-byte scalar below the curve order", what)))
+}
+
+/// BIP340 challenge e = H_challenge(R.x || P.x || m) mod n.
+fn challenge(nonce_x: &XOnlyPublicKey, pubkey: &XOnlyPublicKey, message: &[u8; 32]) -> Scalar {
+    let mut engine = tagged_engine("BIP0340/challenge");
+    engine.input(&nonce_x.serialize());
+    engine.input(&pubkey.serialize());
+    engine.input(message);
+    reduce_mod_order(sha256::Hash::from_engine(engine).to_byte_array())
+}
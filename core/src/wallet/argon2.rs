@@ -0,0 +1,375 @@
+// Argon2id (RFC 9106, version 0x13) and the BLAKE2b it is built on (RFC 7693). Lanes
+// are filled one after another, since a wasm module runs on one thread anyway.
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 12] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+];
+
+const VERSION: u32 = 0x13;
+const ARGON2ID: u32 = 2;
+const SYNC_POINTS: usize = 4;
+const BLOCK_WORDS: usize = 128;
+const BLOCK_BYTES: usize = BLOCK_WORDS * 8;
+
+type Block = [u64; BLOCK_WORDS];
+
+/// Memory in KiB (one 1 KiB block each), passes over it, and lanes.
+#[derive(Clone, Copy)]
+pub struct Argon2Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Argon2Params {
+    /// The limits RFC 9106 puts on the parameters themselves, not on how strong they are.
+    pub fn check(&self) -> Result<(), String> {
+        if self.parallelism == 0 {
+            return Err("parallelism must be at least 1".to_string());
+        }
+        if self.parallelism > 0xff_ffff {
+            return Err(format!("parallelism is at most {}, got {}", 0xff_ffff, self.parallelism));
+        }
+        if self.iterations == 0 {
+            return Err("iterations must be at least 1".to_string());
+        }
+        if self.memory_kib < 8 * self.parallelism {
+            return Err(format!("memory_kib must be at least 8 per lane ({}), got {}", 8 * self.parallelism, self.memory_kib));
+        }
+        Ok(())
+    }
+}
+
+/// Argon2id of `password` and `salt`, `tag_len` bytes long. `params` must pass `check`.
+pub fn argon2id(password: &[u8], salt: &[u8], params: &Argon2Params, tag_len: usize) -> Vec<u8> {
+    argon2id_keyed(password, salt, &[], &[], params, tag_len)
+}
+
+/// Argon2id with the optional secret key `K` and associated data `X` as well.
+pub fn argon2id_keyed(password: &[u8], salt: &[u8], secret: &[u8], associated: &[u8], params: &Argon2Params, tag_len: usize) -> Vec<u8> {
+    let lanes = params.parallelism as usize;
+    let mut h0 = Blake2b::new(64);
+    for value in [params.parallelism, tag_len as u32, params.memory_kib, params.iterations, VERSION, ARGON2ID] {
+        h0.update(&value.to_le_bytes());
+    }
+    for data in [password, salt, secret, associated] {
+        h0.update(&(data.len() as u32).to_le_bytes());
+        h0.update(data);
+    }
+    let h0 = h0.finalize();
+
+    let segment_length = params.memory_kib as usize / (SYNC_POINTS * lanes);
+    let geometry = Geometry {
+        lanes,
+        segment_length,
+        lane_length: segment_length * SYNC_POINTS,
+        iterations: params.iterations as usize,
+    };
+    let mut memory: Vec<Block> = vec![[0; BLOCK_WORDS]; geometry.lane_length * lanes];
+    for lane in 0..lanes {
+        for column in 0..2u32 {
+            let mut input = h0.clone();
+            input.extend_from_slice(&column.to_le_bytes());
+            input.extend_from_slice(&(lane as u32).to_le_bytes());
+            memory[lane * geometry.lane_length + column as usize] = block_from_bytes(&h_prime(&input, BLOCK_BYTES));
+        }
+    }
+    for pass in 0..geometry.iterations {
+        for slice in 0..SYNC_POINTS {
+            for lane in 0..lanes {
+                fill_segment(&mut memory, &geometry, pass, lane, slice);
+            }
+        }
+    }
+
+    let mut last = memory[geometry.lane_length - 1];
+    for lane in 1..lanes {
+        xor_into(&mut last, &memory[lane * geometry.lane_length + geometry.lane_length - 1]);
+    }
+    h_prime(&block_to_bytes(&last), tag_len)
+}
+
+struct Geometry {
+    lanes: usize,
+    segment_length: usize,
+    lane_length: usize,
+    iterations: usize,
+}
+
+fn fill_segment(memory: &mut [Block], geometry: &Geometry, pass: usize, lane: usize, slice: usize) {
+    // Argon2id: the first half of the first pass is addressed independently of the data.
+    let data_independent = pass == 0 && slice < SYNC_POINTS / 2;
+    let mut input_block: Block = [0; BLOCK_WORDS];
+    let mut address_block: Block = [0; BLOCK_WORDS];
+    if data_independent {
+        let header = [pass, lane, slice, memory.len(), geometry.iterations, ARGON2ID as usize];
+        for (word, value) in input_block.iter_mut().zip(header) {
+            *word = value as u64;
+        }
+    }
+
+    let first = if pass == 0 && slice == 0 { 2 } else { 0 };
+    if data_independent && first != 0 {
+        next_addresses(&mut address_block, &mut input_block);
+    }
+    for index in first..geometry.segment_length {
+        let current = lane * geometry.lane_length + slice * geometry.segment_length + index;
+        let previous = if current.is_multiple_of(geometry.lane_length) { current + geometry.lane_length - 1 } else { current - 1 };
+        let pseudo_random = if data_independent {
+            if index.is_multiple_of(BLOCK_WORDS) {
+                next_addresses(&mut address_block, &mut input_block);
+            }
+            address_block[index % BLOCK_WORDS]
+        } else {
+            memory[previous][0]
+        };
+
+        let reference_lane = if pass == 0 && slice == 0 { lane } else { ((pseudo_random >> 32) as usize) % geometry.lanes };
+        let reference_index = reference_index(geometry, pass, slice, index, pseudo_random as u32, reference_lane == lane);
+        let block = compress(&memory[previous], &memory[reference_lane * geometry.lane_length + reference_index]);
+        if pass == 0 {
+            memory[current] = block;
+        } else {
+            xor_into(&mut memory[current], &block);
+        }
+    }
+}
+
+/// RFC 9106 section 3.4.1.2: where in the lane the reference block is, for `index`
+/// within the segment.
+fn reference_index(geometry: &Geometry, pass: usize, slice: usize, index: usize, pseudo_random: u32, same_lane: bool) -> usize {
+    let not_first = usize::from(index != 0);
+    let area = match (pass, same_lane) {
+        (0, _) if slice == 0 => index - 1,
+        (0, true) => slice * geometry.segment_length + index - 1,
+        (0, false) => slice * geometry.segment_length + not_first - 1,
+        (_, true) => geometry.lane_length - geometry.segment_length + index - 1,
+        (_, false) => geometry.lane_length - geometry.segment_length + not_first - 1,
+    } as u64;
+    let x = (pseudo_random as u64 * pseudo_random as u64) >> 32;
+    let relative = area - 1 - ((area * x) >> 32);
+    let start = if pass == 0 || slice == SYNC_POINTS - 1 { 0 } else { (slice + 1) * geometry.segment_length };
+    ((start as u64 + relative) % geometry.lane_length as u64) as usize
+}
+
+fn next_addresses(address_block: &mut Block, input_block: &mut Block) {
+    input_block[6] += 1;
+    let zero: Block = [0; BLOCK_WORDS];
+    *address_block = compress(&zero, &compress(&zero, input_block));
+}
+
+/// The compression function G: BLAKE2b's round, without the message, over the rows and
+/// then the columns of X xor Y, xored with X xor Y again.
+fn compress(x: &Block, y: &Block) -> Block {
+    let mut r = *x;
+    xor_into(&mut r, y);
+    let mut q = r;
+    for row in 0..8 {
+        let words: [usize; 16] = std::array::from_fn(|k| 16 * row + k);
+        permute(&mut q, &words);
+    }
+    for column in 0..8 {
+        let words: [usize; 16] = std::array::from_fn(|k| 2 * column + (k / 2) * 16 + k % 2);
+        permute(&mut q, &words);
+    }
+    xor_into(&mut q, &r);
+    q
+}
+
+fn permute(v: &mut Block, w: &[usize; 16]) {
+    mix(v, w[0], w[4], w[8], w[12]);
+    mix(v, w[1], w[5], w[9], w[13]);
+    mix(v, w[2], w[6], w[10], w[14]);
+    mix(v, w[3], w[7], w[11], w[15]);
+    mix(v, w[0], w[5], w[10], w[15]);
+    mix(v, w[1], w[6], w[11], w[12]);
+    mix(v, w[2], w[7], w[8], w[13]);
+    mix(v, w[3], w[4], w[9], w[14]);
+}
+
+/// BLAKE2b's G with Argon2's multiplication added to each addition.
+fn mix(v: &mut Block, a: usize, b: usize, c: usize, d: usize) {
+    let add = |x: u64, y: u64| x.wrapping_add(y).wrapping_add(2u64.wrapping_mul((x as u32 as u64) * (y as u32 as u64)));
+    v[a] = add(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = add(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = add(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = add(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+fn xor_into(target: &mut Block, other: &Block) {
+    target.iter_mut().zip(other).for_each(|(word, other)| *word ^= other);
+}
+
+fn block_from_bytes(bytes: &[u8]) -> Block {
+    std::array::from_fn(|i| u64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().expect("8 bytes")))
+}
+
+fn block_to_bytes(block: &Block) -> Vec<u8> {
+    block.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// H': BLAKE2b stretched to any length by chaining 64-byte hashes and keeping half of each.
+fn h_prime(input: &[u8], out_len: usize) -> Vec<u8> {
+    let prefixed = |len: usize| {
+        let mut hash = Blake2b::new(len);
+        hash.update(&(out_len as u32).to_le_bytes());
+        hash.update(input);
+        hash.finalize()
+    };
+    if out_len <= 64 {
+        return prefixed(out_len);
+    }
+    let rounds = out_len.div_ceil(32) - 2;
+    let mut out = Vec::with_capacity(out_len);
+    let mut v = prefixed(64);
+    out.extend_from_slice(&v[..32]);
+    for _ in 1..rounds {
+        v = blake2b(&v, 64);
+        out.extend_from_slice(&v[..32]);
+    }
+    out.extend_from_slice(&blake2b(&v, out_len - 32 * rounds));
+    out
+}
+
+pub fn blake2b(data: &[u8], out_len: usize) -> Vec<u8> {
+    let mut hash = Blake2b::new(out_len);
+    hash.update(data);
+    hash.finalize()
+}
+
+/// Unkeyed BLAKE2b with a 1 to 64 byte digest.
+pub struct Blake2b {
+    h: [u64; 8],
+    counter: u128,
+    buffer: [u8; 128],
+    buffered: usize,
+    out_len: usize,
+}
+
+impl Blake2b {
+    pub fn new(out_len: usize) -> Blake2b {
+        assert!((1..=64).contains(&out_len), "BLAKE2b digests are 1 to 64 bytes");
+        let mut h = BLAKE2B_IV;
+        h[0] ^= 0x0101_0000 ^ out_len as u64;
+        Blake2b { h, counter: 0, buffer: [0; 128], buffered: 0, out_len }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // The last block is compressed differently, so a full buffer waits for more data.
+            if self.buffered == 128 {
+                self.counter += 128;
+                let block = self.buffer;
+                self.compress(&block, false);
+                self.buffered = 0;
+            }
+            let take = data.len().min(128 - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+        }
+    }
+
+    pub fn finalize(mut self) -> Vec<u8> {
+        self.counter += self.buffered as u128;
+        self.buffer[self.buffered..].fill(0);
+        let block = self.buffer;
+        self.compress(&block, true);
+        self.h.iter().flat_map(|word| word.to_le_bytes()).take(self.out_len).collect()
+    }
+
+    fn compress(&mut self, block: &[u8; 128], last: bool) {
+        let m: [u64; 16] = std::array::from_fn(|i| u64::from_le_bytes(block[8 * i..8 * i + 8].try_into().expect("8 bytes")));
+        let mut v = [0u64; 16];
+        v[..8].copy_from_slice(&self.h);
+        v[8..].copy_from_slice(&BLAKE2B_IV);
+        v[12] ^= self.counter as u64;
+        v[13] ^= (self.counter >> 64) as u64;
+        if last {
+            v[14] = !v[14];
+        }
+        for s in &BLAKE2B_SIGMA {
+            blake2b_mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            blake2b_mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            blake2b_mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            blake2b_mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            blake2b_mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            blake2b_mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            blake2b_mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            blake2b_mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+        for i in 0..8 {
+            self.h[i] ^= v[i] ^ v[i + 8];
+        }
+    }
+}
+
+fn blake2b_mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::bytes_to_hex;
+
+    /// RFC 9106 section 5.3.
+    #[test]
+    fn rfc9106_argon2id_vector() {
+        let params = Argon2Params { memory_kib: 32, iterations: 3, parallelism: 4 };
+        let tag = argon2id_keyed(&[0x01; 32], &[0x02; 16], &[0x03; 8], &[0x04; 12], &params, 32);
+        assert_eq!(bytes_to_hex(&tag), "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659");
+    }
+
+    /// RFC 7693 appendix A.
+    #[test]
+    fn rfc7693_blake2b_vector() {
+        assert_eq!(
+            bytes_to_hex(&blake2b(b"abc", 64)),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+    }
+
+    #[test]
+    fn streamed_blake2b_matches_one_shot() {
+        let data: Vec<u8> = (0..=255).cycle().take(300).collect();
+        let mut hasher = Blake2b::new(64);
+        for chunk in data.chunks(37) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), blake2b(&data, 64));
+    }
+}
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv, Xpub};
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha512, Hash, HashEngine};
+use bitcoin::key::CompressedPublicKey;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{base58, Network, ScriptBuf};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::wallet::mnemonic::{mnemonic_to_entropy, pbkdf2_hmac};
+use crate::wallet::path::format_path;
+use crate::utils::json::to_json;
+use crate::utils::sanitize::{sanitize, InputKind};
+
+const MAX_ADDRESSES: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedType {
+    Standard,
+    Segwit,
+    TwoFactor,
+    TwoFactorSegwit,
+}
+
+/// Electrum's seed versions: the hex prefix of HMAC-SHA512("Seed version", phrase).
+const SEED_PREFIXES: [(&str, SeedType); 4] = [
+    ("01", SeedType::Standard),
+    ("100", SeedType::Segwit),
+    ("101", SeedType::TwoFactor),
+    ("102", SeedType::TwoFactorSegwit),
+];
+
+impl SeedType {
+    pub fn name(self) -> &'static str {
+        match self {
+            SeedType::Standard => "standard",
+            SeedType::Segwit => "segwit",
+            SeedType::TwoFactor => "2fa",
+            SeedType::TwoFactorSegwit => "2fa_segwit",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElectrumSeedInfo {
+    pub valid: bool,
+    #[serde(alias = "seed_type")]
+    pub seed_type: Option<String>,
+    #[serde(alias = "word_count")]
+    pub word_count: usize,
+    /// The words also form a valid BIP39 phrase, which derives unrelated keys.
+    #[serde(alias = "bip39_valid")]
+    pub bip39_valid: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElectrumAddress {
+    pub index: u32,
+    pub path: String,
+    pub address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElectrumWallet {
+    #[serde(alias = "seed_type")]
+    pub seed_type: String,
+    #[serde(alias = "account_path")]
+    pub account_path: String,
+    pub xpub: String,
+    pub addresses: Vec<ElectrumAddress>,
+}
+
+/// Checks a phrase against Electrum's seed versions (2.0 and later). A phrase that fails
+/// but is valid BIP39 says so, since the two are easy to mix up and derive different keys.
+#[wasm_bindgen]
+pub fn validate_electrum_mnemonic(phrase: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let normalized = normalize_phrase(phrase).map_err(|e| JsValue::from_str(&e))?;
+    let seed_type = seed_type_of(&normalized);
+    let bip39_valid = mnemonic_to_entropy(&normalized).is_ok();
+    let info = ElectrumSeedInfo {
+        valid: seed_type.is_some(),
+        seed_type: seed_type.map(|t| t.name().to_string()),
+        word_count: normalized.split(' ').filter(|word| !word.is_empty()).count(),
+        bip39_valid,
+        error: match seed_type {
+            Some(_) => None,
+            None => Some(not_electrum_message(bip39_valid)),
+        },
+    };
+
+    to_json(&info, compact)
+}
+
+/// The 64-byte seed Electrum stretches from a phrase: PBKDF2-HMAC-SHA512 over the
+/// normalized phrase, salted with `electrum` and the normalized passphrase, 2048 rounds.
+#[wasm_bindgen]
+pub fn electrum_mnemonic_to_seed(phrase: &str, passphrase: Option<String>) -> Result<String, JsValue> {
+    let (seed, _) = electrum_seed(phrase, passphrase.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    Ok(bytes_to_hex(&seed))
+}
+
+/// Addresses in the order Electrum shows them. Standard seeds use P2PKH at `m/0/i` and
+/// `m/1/i`; segwit seeds use P2WPKH at `m/0'/0/i` and `m/0'/1/i`. 2FA seeds need
+/// TrustedCoin's cosigner keys and aren't supported.
+#[wasm_bindgen]
+pub fn electrum_derive_addresses(
+    phrase: &str,
+    passphrase: Option<String>,
+    start: u32,
+    count: u32,
+    network: Option<String>,
+    chain: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    if count > MAX_ADDRESSES {
+        return Err(JsValue::from_str(&format!("At most {} addresses can be derived at once", MAX_ADDRESSES)));
+    }
+    let branch = match chain.map(|c| c.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("receive") => 0,
+        Some("change") => 1,
+        Some(other) => {
+            return Err(JsValue::from_str(&format!("Unknown chain \"{}\" (expected receive or change)", other)))
+        }
+    };
+    let (seed, seed_type) = electrum_seed(phrase, passphrase.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let account_path = match seed_type {
+        SeedType::Standard => DerivationPath::master(),
+        SeedType::Segwit => DerivationPath::from(vec![ChildNumber::Hardened { index: 0 }]),
+        SeedType::TwoFactor | SeedType::TwoFactorSegwit => {
+            return Err(JsValue::from_str(
+                "This is an Electrum 2FA (TrustedCoin) seed; its addresses need TrustedCoin's cosigner keys and can't be derived here",
+            ))
+        }
+    };
+
+    let secp = Secp256k1::new();
+    let account = Xpriv::new_master(params.network, &seed)
+        .and_then(|master| master.derive_priv(&secp, &account_path))
+        .map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+    let account = Xpub::from_priv(&secp, &account);
+    let branch_key = account
+        .derive_pub(&secp, &[ChildNumber::Normal { index: branch }])
+        .map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+
+    let mut addresses = Vec::with_capacity(count as usize);
+    for offset in 0..count {
+        let index = start
+            .checked_add(offset)
+            .filter(|&index| index < 1 << 31)
+            .ok_or_else(|| JsValue::from_str("Derivation indexes must stay below 2^31"))?;
+        let key = branch_key
+            .derive_pub(&secp, &[ChildNumber::Normal { index }])
+            .map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+        let key = CompressedPublicKey(key.public_key);
+        let script = match seed_type {
+            SeedType::Standard => ScriptBuf::new_p2pkh(&bitcoin::PublicKey::from(key).pubkey_hash()),
+            _ => ScriptBuf::new_p2wpkh(&key.wpubkey_hash()),
+        };
+        addresses.push(ElectrumAddress {
+            index,
+            path: format_path(&account_path.extend([ChildNumber::Normal { index: branch }, ChildNumber::Normal { index }]), None),
+            address: params.address(&script),
+        });
+    }
+
+    let wallet = ElectrumWallet {
+        seed_type: seed_type.name().to_string(),
+        account_path: format_path(&account_path, None),
+        xpub: account_xpub(&account, seed_type, &params),
+        addresses,
+    };
+
+    to_json(&wallet, compact)
+}
+
+/// The Electrum seed type of a phrase, if it has one, so BIP39 checks can point out
+/// Electrum seeds.
+pub fn electrum_seed_type(phrase: &str) -> Option<SeedType> {
+    normalize_phrase(phrase).ok().and_then(|normalized| seed_type_of(&normalized))
+}
+
+/// The stretched seed and the seed type, or why the phrase isn't an Electrum seed.
+pub fn electrum_seed(phrase: &str, passphrase: Option<&str>) -> Result<(Vec<u8>, SeedType), String> {
+    let normalized = normalize_phrase(phrase)?;
+    let seed_type = seed_type_of(&normalized)
+        .ok_or_else(|| not_electrum_message(mnemonic_to_entropy(&normalized).is_ok()))?;
+    let passphrase = normalize_text(passphrase.unwrap_or(""))?;
+    let salt = format!("electrum{}", passphrase);
+    Ok((pbkdf2_hmac::<sha512::Hash>(normalized.as_bytes(), salt.as_bytes(), 2048, 64), seed_type))
+}
+
+fn seed_type_of(normalized: &str) -> Option<SeedType> {
+    let mut engine = HmacEngine::<sha512::Hash>::new(b"Seed version");
+    engine.input(normalized.as_bytes());
+    let version = bytes_to_hex(&Hmac::from_engine(engine).to_byte_array()[..2]);
+    SEED_PREFIXES
+        .iter()
+        .find(|(prefix, _)| version.starts_with(prefix))
+        .map(|&(_, seed_type)| seed_type)
+}
+
+fn not_electrum_message(bip39_valid: bool) -> String {
+    if bip39_valid {
+        "This is a valid BIP39 mnemonic, not an Electrum seed. Restore it as BIP39 (in Electrum: Options > BIP39 seed); read as an Electrum seed it would give different keys".to_string()
+    } else {
+        "Not an Electrum seed: the phrase doesn't match any Electrum seed version. Check each word; a single wrong word changes the version. Pre-2.0 Electrum seeds aren't supported".to_string()
+    }
+}
+
+/// A seed phrase as Electrum hashes it, after the input sanitizer has dealt with any
+/// zero-width characters and Unicode spaces pasted along with it.
+fn normalize_phrase(phrase: &str) -> Result<String, String> {
+    normalize_text(&sanitize(phrase, InputKind::Mnemonic)?)
+}
+
+/// Electrum's text normalization (NFKD, lowercase, no accents, single spaces) for ASCII
+/// text. Without Unicode tables here, anything else is refused rather than hashed as is,
+/// which would quietly give the wrong seed.
+fn normalize_text(text: &str) -> Result<String, String> {
+    if !text.is_ascii() {
+        return Err("Only ASCII seeds and passphrases are supported: Electrum normalizes other text with Unicode NFKD, which this build can't reproduce".to_string());
+    }
+    Ok(text.split_ascii_whitespace().map(|word| word.to_ascii_lowercase()).collect::<Vec<_>>().join(" "))
+}
+
+/// The account xpub as Electrum writes it: `xpub`/`tpub` for standard seeds and
+/// SLIP-132 `zpub`/`vpub` for segwit seeds.
+fn account_xpub(account: &Xpub, seed_type: SeedType, params: &NetworkParams) -> String {
+    let mut payload = account.encode();
+    let version = match seed_type {
+        SeedType::Segwit if params.network == Network::Bitcoin => [0x04, 0xb2, 0x47, 0x46],
+        SeedType::Segwit => [0x04, 0x5f, 0x1c, 0xf6],
+        _ => params.xpub_version,
+    };
+    payload[..4].copy_from_slice(&version);
+    base58::encode_check(&payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::mnemonic::{check_phrase, Language};
+
+    /// From Electrum's test_wallet_vertical.py, with what Electrum 4.x shows for them.
+    const SEGWIT_SEED: &str = "bitter grass shiver impose acquire brush forget axis eager alone wine silver";
+    const STANDARD_SEED: &str = "cycle rocket west magnet parrot shuffle foot correct salt library feed song";
+    const BIP39_PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn first_receive(phrase: &str) -> ElectrumWallet {
+        let json = electrum_derive_addresses(phrase, None, 0, 1, Some("mainnet".to_string()), None, None).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn segwit_seed_matches_electrum() {
+        assert_eq!(electrum_seed_type(SEGWIT_SEED), Some(SeedType::Segwit));
+        let wallet = first_receive(SEGWIT_SEED);
+        assert_eq!(wallet.account_path, "m/0'");
+        assert!(wallet.xpub.starts_with("zpub"));
+        assert_eq!(wallet.addresses[0].path, "m/0'/0/0");
+        assert_eq!(wallet.addresses[0].address.as_deref(), Some("bc1q3g5tmkmlvxryhh843v4dz026avatc0zzr6h3af"));
+    }
+
+    #[test]
+    fn standard_seed_matches_electrum() {
+        assert_eq!(electrum_seed_type(STANDARD_SEED), Some(SeedType::Standard));
+        let wallet = first_receive(STANDARD_SEED);
+        assert_eq!(wallet.xpub, "xpub661MyMwAqRbcFWohJWt7PHsFEJfZAvw9ZxwQoDa4SoMgsDDM1T7WK3u9E4edkC4ugRnZ8E4xDZRpk8Rnts3Nbt97dPwT52CwBdDWroaZf8U");
+        assert_eq!(wallet.addresses[0].path, "m/0/0");
+        assert_eq!(wallet.addresses[0].address.as_deref(), Some("1NNkttn1YvVGdqBW4PR6zvc3Zx3H5owKRf"));
+    }
+
+    #[test]
+    fn bip39_and_electrum_are_told_apart() {
+        let error = electrum_seed(BIP39_PHRASE, None).unwrap_err();
+        assert!(error.starts_with("This is a valid BIP39 mnemonic, not an Electrum seed"), "{}", error);
+        let error = electrum_seed("abandon abandon abandon", None).unwrap_err();
+        assert!(error.starts_with("Not an Electrum seed"), "{}", error);
+
+        let (matches, error) = check_phrase(SEGWIT_SEED, &[Language::English]);
+        assert!(matches.is_empty());
+        assert!(error.unwrap().starts_with("This is an Electrum segwit seed, not a BIP39 mnemonic"));
+    }
+
+    #[test]
+    fn passphrase_changes_the_seed() {
+        let (plain, _) = electrum_seed(SEGWIT_SEED, None).unwrap();
+        let (salted, _) = electrum_seed(SEGWIT_SEED, Some("TREZOR")).unwrap();
+        assert_ne!(plain, salted);
+        assert_eq!(electrum_seed("  Bitter GRASS shiver impose acquire brush forget axis eager alone wine silver ", None).unwrap().0, plain);
+    }
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::SecretKey;
+use crate::utils::bytes_to_hex;
+use crate::wallet::mnemonic::{entropy_len_for_words, entropy_to_mnemonic_in, Language};
+use crate::utils::json::to_json;
+
+pub const MIN_COIN_FLIPS: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalEntropyKey {
+    #[serde(alias = "private_key")]
+    pub private_key: String,
+    #[serde(alias = "entropy_bits")]
+    pub entropy_bits: f64,
+    pub events: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalEntropyMnemonic {
+    pub mnemonic: String,
+    #[serde(alias = "word_count")]
+    pub word_count: usize,
+    #[serde(alias = "entropy_bits")]
+    pub entropy_bits: f64,
+    pub events: usize,
+}
+
+/// Key from at least 99 d6 rolls. Like Coldcard, the key is SHA256 of the rolls as
+/// ASCII digits, so the same rolls give the same key on both.
+#[wasm_bindgen]
+pub fn private_key_from_dice(rolls_string: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let rolls = parse_symbols(rolls_string, "123456", "dice roll").map_err(|e| JsValue::from_str(&e))?;
+    require_entropy(rolls.len(), dice_entropy_bits(rolls.len()), 256, min_dice_rolls(256), "dice rolls")
+        .map_err(|e| JsValue::from_str(&e))?;
+    key_result(&rolls, dice_entropy_bits(rolls.len()), compact)
+}
+
+/// BIP39 phrase from d6 rolls. The entropy is SHA256 of the rolls, truncated to the
+/// phrase length, which is how Coldcard builds 12- and 24-word seeds from dice. The
+/// phrase is English unless another loaded `language` is asked for.
+#[wasm_bindgen]
+pub fn mnemonic_from_dice(rolls_string: &str, word_count: Option<usize>, language: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let word_count = word_count.unwrap_or(24);
+    let language = match language.as_deref().map(str::trim) {
+        None | Some("") => Language::English,
+        Some(name) => Language::parse(name).map_err(|e| JsValue::from_str(&e))?,
+    };
+    let entropy_len = entropy_len_for_words(word_count).map_err(|e| JsValue::from_str(&e))?;
+    let rolls = parse_symbols(rolls_string, "123456", "dice roll").map_err(|e| JsValue::from_str(&e))?;
+    let bits = dice_entropy_bits(rolls.len());
+    require_entropy(rolls.len(), bits, entropy_len * 8, min_dice_rolls(entropy_len * 8), "dice rolls")
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    let digest = sha256::Hash::hash(rolls.as_bytes()).to_byte_array();
+    let result = PhysicalEntropyMnemonic {
+        mnemonic: entropy_to_mnemonic_in(&digest[..entropy_len], language).map_err(|e| JsValue::from_str(&e))?,
+        word_count,
+        entropy_bits: round_bits(bits),
+        events: rolls.len(),
+    };
+
+    to_json(&result, compact)
+}
+
+/// Key from at least 256 coin flips written as `0`/`1`, hashed the same way as dice.
+#[wasm_bindgen]
+pub fn from_coin_flips(bits_string: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let flips = parse_symbols(bits_string, "01", "coin flip").map_err(|e| JsValue::from_str(&e))?;
+    require_entropy(flips.len(), flips.len() as f64, 256, MIN_COIN_FLIPS, "coin flips")
+        .map_err(|e| JsValue::from_str(&e))?;
+    key_result(&flips, flips.len() as f64, compact)
+}
+
+/// Rolls needed for `bits` of entropy, rounded to the nearest roll as Coldcard does
+/// (99 rolls for 256 bits, 50 for 128).
+pub fn min_dice_rolls(bits: usize) -> usize {
+    (bits as f64 / 6f64.log2()).round() as usize
+}
+
+fn dice_entropy_bits(rolls: usize) -> f64 {
+    rolls as f64 * 6f64.log2()
+}
+
+fn key_result(events: &str, bits: f64, compact: Option<bool>) -> Result<String, JsValue> {
+    let digest = sha256::Hash::hash(events.as_bytes()).to_byte_array();
+    let secret_key = SecretKey::from_slice(&digest)
+        .map_err(|e| JsValue::from_str(&format!("Hashed entropy is not a valid key, add more input: {}", e)))?;
+
+    let result = PhysicalEntropyKey {
+        private_key: bytes_to_hex(&secret_key.secret_bytes()),
+        entropy_bits: round_bits(bits),
+        events: events.len(),
+    };
+
+    to_json(&result, compact)
+}
+
+/// Keeps only the allowed symbols; whitespace and commas separate groups and are ignored.
+fn parse_symbols(input: &str, allowed: &str, what: &str) -> Result<String, String> {
+    let mut symbols = String::new();
+    for (position, c) in input.chars().enumerate() {
+        if c.is_whitespace() || c == ',' {
+            continue;
+        }
+        if !allowed.contains(c) {
+            return Err(format!("Invalid {} '{}' at position {}", what, c, position + 1));
+        }
+        symbols.push(c);
+    }
+    Ok(symbols)
+}
+
+fn require_entropy(count: usize, bits: f64, needed_bits: usize, minimum: usize, what: &str) -> Result<(), String> {
+    if count < minimum {
+        return Err(format!(
+            "Only {} {} (about {:.1} bits of entropy); at least {} are needed for {} bits",
+            count, what, bits, minimum, needed_bits
+        ));
+    }
+    Ok(())
+}
+
+fn round_bits(bits: f64) -> f64 {
+    (bits * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 102 rolls, checked the way Coldcard's docs verify a dice seed: the SHA256 of the
+    /// roll digits as ASCII, e.g. `echo -n 1234…56 | sha256sum`, as the entropy.
+    const ROLLS: &str = "123456123456123456123456123456123456123456123456123456123456123456123456123456123456123456123456123456";
+    const ROLLS_SHA256: &str = "8fd128918b2e29d6dcbfa5b9a118e5c16d60498c7ba107922a8eb6eb1d36c112";
+    /// The BIP39 phrases of that digest, whole and truncated to 128 bits.
+    const WORDS_24: &str = "more matter caught bind tip twin indicate visa rifle angle defense lizard stock cave cradle injury always mule photo horse range opinion affair garlic";
+    const WORDS_12: &str = "more matter caught bind tip twin indicate visa rifle angle defense local";
+
+    fn field(json: &str, name: &str) -> serde_json::Value {
+        serde_json::from_str::<serde_json::Value>(json).unwrap()[name].clone()
+    }
+
+    #[test]
+    fn dice_match_coldcard() {
+        let key = private_key_from_dice(ROLLS, None).unwrap();
+        assert_eq!(field(&key, "privateKey"), ROLLS_SHA256);
+        assert_eq!(field(&key, "events"), 102);
+
+        let words = mnemonic_from_dice(ROLLS, Some(24), None, None).unwrap();
+        assert_eq!(field(&words, "mnemonic"), WORDS_24);
+        let words = mnemonic_from_dice(ROLLS, Some(12), None, None).unwrap();
+        assert_eq!(field(&words, "mnemonic"), WORDS_12);
+    }
+
+    #[test]
+    fn separators_are_ignored() {
+        let grouped: String = ROLLS.as_bytes().chunks(6).map(|group| std::str::from_utf8(group).unwrap()).collect::<Vec<_>>().join(", ");
+        assert_eq!(private_key_from_dice(&grouped, None).unwrap(), private_key_from_dice(ROLLS, None).unwrap());
+        assert_eq!(parse_symbols("1 2,3", "123456", "dice roll").unwrap(), "123");
+        assert_eq!(parse_symbols("1 7", "123456", "dice roll").unwrap_err(), "Invalid dice roll '7' at position 3");
+    }
+
+    #[test]
+    fn minimums_follow_coldcard() {
+        assert_eq!(min_dice_rolls(256), 99);
+        assert_eq!(min_dice_rolls(128), 50);
+        let error = require_entropy(98, dice_entropy_bits(98), 256, min_dice_rolls(256), "dice rolls").unwrap_err();
+        assert_eq!(error, "Only 98 dice rolls (about 253.3 bits of entropy); at least 99 are needed for 256 bits");
+    }
+
+    #[test]
+    fn coin_flips_hash_like_dice() {
+        let flips = "01".repeat(128);
+        let expected = bytes_to_hex(&sha256::Hash::hash(flips.as_bytes()).to_byte_array());
+        assert_eq!(field(&from_coin_flips(&flips, None).unwrap(), "privateKey"), expected.as_str());
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::{OutPoint, ScriptBuf, Txid};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use crate::descriptor::{derive_script, parse_descriptors};
+use crate::utils::network::{network_or_default, parse_address_for_network, AddressError, NetworkParams};
+use crate::utils::json::{parse_json_value, to_json};
+
+const DEFAULT_GAP_LIMIT: u32 = 20;
+const RECEIVE: usize = 0;
+const CHANGE: usize = 1;
+const CHAIN_NAMES: [&str; 2] = ["receive", "change"];
+/// Unhardened indexes stop at 2^31.
+const MAX_INDEX: u32 = 1 << 31;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletState {
+    #[serde(alias = "receive_index")]
+    pub receive_index: u32,
+    #[serde(alias = "change_index")]
+    pub change_index: u32,
+    #[serde(default, alias = "used_addresses")]
+    pub used_addresses: Vec<String>,
+    /// Outputs seen paying this wallet, from `register_incoming`.
+    #[serde(default)]
+    pub incoming: Vec<IncomingOutput>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IncomingOutput {
+    pub address: String,
+    pub txid: String,
+    pub vout: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReusedAddress {
+    pub address: String,
+    /// `receive` or `change`.
+    pub chain: String,
+    pub index: u32,
+    #[serde(alias = "times_received")]
+    pub times_received: usize,
+    pub txids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressReuseReport {
+    /// Owned addresses with at least one registered output.
+    #[serde(alias = "addresses_seen")]
+    pub addresses_seen: usize,
+    pub reused: Vec<ReusedAddress>,
+}
+
+/// Hands out receive and change addresses from one descriptor and remembers where it
+/// got to, so the same "next" address is never issued twice and an address that has
+/// received is never issued again unless asked for. The exported state holds indexes,
+/// addresses and outpoints only: the descriptor is passed to the constructor again.
+#[wasm_bindgen]
+pub struct Wallet {
+    branches: Vec<Descriptor<DescriptorPublicKey>>,
+    network: NetworkParams,
+    gap_limit: u32,
+    /// The index the next address on each chain is issued at.
+    next: [u32; 2],
+    /// Scripts are derived for indexes below this, to recognise used addresses.
+    derived: [u32; 2],
+    scripts: HashMap<ScriptBuf, (usize, u32)>,
+    used: BTreeMap<(usize, u32), String>,
+    /// Outputs registered to each address, to spot reuse.
+    incoming: BTreeMap<(usize, u32), BTreeSet<OutPoint>>,
+}
+
+#[wasm_bindgen]
+impl Wallet {
+    /// `descriptor` is a `<0;1>/*` multipath descriptor or a bare xpub/tpub.
+    #[wasm_bindgen(constructor)]
+    pub fn new(descriptor: &str, network: Option<String>, gap_limit: Option<u32>) -> Result<Wallet, JsValue> {
+        Wallet::build(descriptor, network, gap_limit.unwrap_or(DEFAULT_GAP_LIMIT)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// A fresh receive address. Marking an address used moves the next index past it,
+    /// so this never returns one that has received. With `allow_reuse` the newest
+    /// receive address is handed out again instead, even once it has received, for
+    /// callers that deliberately show one fixed address.
+    pub fn next_receive_address(&mut self, allow_reuse: Option<bool>) -> Result<String, JsValue> {
+        let index = self.next[RECEIVE];
+        if allow_reuse.unwrap_or(false) && index > 0 {
+            return self.address_at(RECEIVE, index - 1).map_err(|e| JsValue::from_str(&e));
+        }
+        self.issue(RECEIVE).map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn next_change_address(&mut self) -> Result<String, JsValue> {
+        self.issue(CHANGE).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Records usage seen elsewhere (e.g. an incoming payment). An address at index `i`
+    /// moves that chain's next index past `i`. Returns false for an address that isn't
+    /// among this wallet's addresses up to `gap_limit` past the next index.
+    pub fn mark_address_used(&mut self, address: &str) -> Result<bool, JsValue> {
+        let script = parse_address_for_network(address.trim(), Some(&self.network)).map_err(|e| match e {
+            AddressError::Invalid(message) => JsValue::from_str(&message),
+            AddressError::WrongNetwork(label) => {
+                JsValue::from_str(&format!("Address is for {}, not {}", label, self.network.target_name()))
+            }
+        })?;
+        let Some(&(chain, index)) = self.scripts.get(&script) else {
+            return Ok(false);
+        };
+        self.record_use(chain, index).map_err(|e| JsValue::from_str(&e))?;
+        Ok(true)
+    }
+
+    /// Records an output seen paying `script_pubkey` (hex), marking its address used as
+    /// `mark_address_used` does. Registering the same outpoint again changes nothing.
+    /// Returns false for a script that isn't this wallet's, up to `gap_limit` past the
+    /// next index.
+    pub fn register_incoming(&mut self, txid: &str, vout: u32, script_pubkey: &str) -> Result<bool, JsValue> {
+        let txid = Txid::from_str(txid.trim()).map_err(|e| JsValue::from_str(&format!("Invalid txid: {}", e)))?;
+        let script = ScriptBuf::from_hex(script_pubkey.trim())
+            .map_err(|e| JsValue::from_str(&format!("Invalid script_pubkey hex: {}", e)))?;
+        let Some(&(chain, index)) = self.scripts.get(&script) else {
+            return Ok(false);
+        };
+        self.record_use(chain, index).map_err(|e| JsValue::from_str(&e))?;
+        self.incoming.entry((chain, index)).or_default().insert(OutPoint { txid, vout });
+        Ok(true)
+    }
+
+    /// Every owned address that has received more than once among the registered
+    /// outputs, most reused first, with the transactions that paid it.
+    pub fn reuse_report(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let mut reused: Vec<ReusedAddress> = self
+            .incoming
+            .iter()
+            .filter(|(_, outpoints)| outpoints.len() > 1)
+            .map(|(&(chain, index), outpoints)| {
+                let txids: BTreeSet<String> = outpoints.iter().map(|outpoint| outpoint.txid.to_string()).collect();
+                Ok(ReusedAddress {
+                    address: self.address_at(chain, index)?,
+                    chain: CHAIN_NAMES[chain].to_string(),
+                    index,
+                    times_received: outpoints.len(),
+                    txids: txids.into_iter().collect(),
+                })
+            })
+            .collect::<Result<_, String>>()
+            .map_err(|e| JsValue::from_str(&e))?;
+        reused.sort_by_key(|reused| std::cmp::Reverse(reused.times_received));
+
+        to_json(&AddressReuseReport { addresses_seen: self.incoming.len(), reused }, compact)
+    }
+
+    pub fn receive_index(&self) -> u32 {
+        self.next[RECEIVE]
+    }
+
+    pub fn change_index(&self) -> u32 {
+        self.next[CHANGE]
+    }
+
+    pub fn export_state(&self, compact: Option<bool>) -> Result<String, JsValue> {
+        let state = WalletState {
+            receive_index: self.next[RECEIVE],
+            change_index: self.next[CHANGE],
+            used_addresses: self.used.values().cloned().collect(),
+            incoming: self
+                .incoming
+                .iter()
+                .flat_map(|(position, outpoints)| outpoints.iter().map(move |outpoint| (position, outpoint)))
+                .map(|(position, outpoint)| IncomingOutput {
+                    address: self.used[position].clone(),
+                    txid: outpoint.txid.to_string(),
+                    vout: outpoint.vout,
+                })
+                .collect(),
+        };
+
+        to_json(&state, compact)
+    }
+
+    /// Restores exported state. Indexes only move forward, so importing an older copy
+    /// never reissues addresses this wallet has already handed out.
+    pub fn import_state(&mut self, state_json: &str) -> Result<(), JsValue> {
+        let state: WalletState = parse_json_value(state_json, "wallet state").map_err(|e| JsValue::from_str(&e))?;
+        self.restore(state).map_err(|e| JsValue::from_str(&e))
+    }
+}
+
+impl Wallet {
+    fn build(descriptor: &str, network: Option<String>, gap_limit: u32) -> Result<Wallet, String> {
+        let network = network_or_default(network)?;
+        let branches = parse_descriptors(descriptor)?;
+        if branches.len() != 2 || !branches.iter().all(Descriptor::has_wildcard) {
+            return Err(
+                "Wallet needs a receive and a change branch: pass a <0;1>/* multipath descriptor or a bare xpub".to_string(),
+            );
+        }
+
+        let mut wallet = Wallet {
+            branches,
+            network,
+            gap_limit: gap_limit.max(1),
+            next: [0; 2],
+            derived: [0; 2],
+            scripts: HashMap::new(),
+            used: BTreeMap::new(),
+            incoming: BTreeMap::new(),
+        };
+        wallet.derive_lookahead(RECEIVE)?;
+        wallet.derive_lookahead(CHANGE)?;
+        Ok(wallet)
+    }
+
+    fn issue(&mut self, chain: usize) -> Result<String, String> {
+        let index = self.next[chain];
+        if index >= MAX_INDEX {
+            return Err(format!("The {} chain has used every unhardened index", CHAIN_NAMES[chain]));
+        }
+        let address = self.address_at(chain, index)?;
+        self.next[chain] = index + 1;
+        self.derive_lookahead(chain)?;
+        Ok(address)
+    }
+
+    fn record_use(&mut self, chain: usize, index: u32) -> Result<(), String> {
+        let address = self.address_at(chain, index)?;
+        self.used.insert((chain, index), address);
+        self.next[chain] = self.next[chain].max(index + 1);
+        self.derive_lookahead(chain)
+    }
+
+    fn restore(&mut self, state: WalletState) -> Result<(), String> {
+        for (chain, index) in [(RECEIVE, state.receive_index), (CHANGE, state.change_index)] {
+            if index > MAX_INDEX {
+                return Err(format!("{}_index {} is past the last unhardened index", CHAIN_NAMES[chain], index));
+            }
+            self.next[chain] = self.next[chain].max(index);
+            self.derive_lookahead(chain)?;
+        }
+
+        // Resolve every address before recording any, so a bad state changes nothing else.
+        let mut used = Vec::with_capacity(state.used_addresses.len());
+        for address in &state.used_addresses {
+            used.push(self.position_of(address)?);
+        }
+        let mut incoming = Vec::with_capacity(state.incoming.len());
+        for output in &state.incoming {
+            let txid = Txid::from_str(&output.txid).map_err(|e| format!("incoming txid {}: {}", output.txid, e))?;
+            incoming.push((self.position_of(&output.address)?, OutPoint { txid, vout: output.vout }));
+        }
+        for (chain, index) in used {
+            self.record_use(chain, index)?;
+        }
+        for ((chain, index), outpoint) in incoming {
+            self.record_use(chain, index)?;
+            self.incoming.entry((chain, index)).or_default().insert(outpoint);
+        }
+        Ok(())
+    }
+
+    fn position_of(&self, address: &str) -> Result<(usize, u32), String> {
+        let script = parse_address_for_network(address.trim(), Some(&self.network)).map_err(|e| match e {
+            AddressError::Invalid(message) => format!("used address {}: {}", address, message),
+            AddressError::WrongNetwork(label) => format!("used address {} is for {}", address, label),
+        })?;
+        self.scripts
+            .get(&script)
+            .copied()
+            .ok_or_else(|| format!("used address {} wasn't derived by this wallet; is the state from another one?", address))
+    }
+
+    fn address_at(&self, chain: usize, index: u32) -> Result<String, String> {
+        let script = derive_script(&self.branches[chain], index)?;
+        self.network
+            .address(&script)
+            .ok_or_else(|| format!("The {} descriptor's scripts have no address form", CHAIN_NAMES[chain]))
+    }
+
+    /// Keeps scripts derived `gap_limit` past the next index, as far as indexes go.
+    fn derive_lookahead(&mut self, chain: usize) -> Result<(), String> {
+        let target = self.next[chain].saturating_add(self.gap_limit).min(MAX_INDEX);
+        for index in self.derived[chain]..target {
+            self.scripts.insert(derive_script(&self.branches[chain], index)?, (chain, index));
+        }
+        self.derived[chain] = self.derived[chain].max(target);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_errors_name_the_field() {
+        let error = parse_json_value::<WalletState>(r#"{"receiveIndex": "3", "changeIndex": 0}"#, "wallet state").err().unwrap();
+        assert_eq!(error, r#"wallet state.receiveIndex must be a number (got string "3")"#);
+        let error = parse_json_value::<WalletState>(r#"{"receiveIndex": 3}"#, "wallet state").err().unwrap();
+        assert_eq!(error, "wallet state.changeIndex is missing");
+    }
+}
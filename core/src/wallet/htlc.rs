@@ -0,0 +1,464 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::key::Keypair;
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CSV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_IF, OP_SHA256};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{All, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{transaction, CompressedPublicKey, Network, ScriptBuf, Sequence, Transaction, TxOut, Witness};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::{check_input_index, decode_tx_hex, parse_prevouts};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::network::network_or_default;
+use crate::wallet::keys::{parse_public_key, require_compressed};
+use crate::wallet::parse_private_key;
+use crate::utils::json::to_json;
+
+/// BIP68 height-based relative locks are 16 bits.
+const MAX_TIMEOUT_BLOCKS: u32 = 0xffff;
+/// BIP341's point with no known discrete log, so the taproot output has no key path.
+const NUMS_POINT: &str = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HtlcContract {
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    #[serde(alias = "receiver_key")]
+    pub receiver_key: String,
+    #[serde(alias = "sender_key")]
+    pub sender_key: String,
+    #[serde(alias = "payment_hash")]
+    pub payment_hash: String,
+    #[serde(alias = "timeout_blocks")]
+    pub timeout_blocks: u32,
+    pub network: String,
+    pub address: String,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    #[serde(default, alias = "witness_script")]
+    pub witness_script: Option<String>,
+    #[serde(default, alias = "internal_key")]
+    pub internal_key: Option<String>,
+    #[serde(default, alias = "claim_leaf")]
+    pub claim_leaf: Option<String>,
+    #[serde(default, alias = "claim_control_block")]
+    pub claim_control_block: Option<String>,
+    #[serde(default, alias = "refund_leaf")]
+    pub refund_leaf: Option<String>,
+    #[serde(default, alias = "refund_control_block")]
+    pub refund_control_block: Option<String>,
+}
+
+/// The spending conditions, rebuilt from the keys, hash and timeout rather than trusted
+/// from the JSON.
+struct HtlcScripts {
+    receiver: CompressedPublicKey,
+    sender: CompressedPublicKey,
+    payment_hash: sha256::Hash,
+    timeout: u32,
+    kind: HtlcKind,
+}
+
+enum HtlcKind {
+    Taproot { claim: ScriptBuf, refund: ScriptBuf, spend_info: Box<TaprootSpendInfo> },
+    Wsh { witness_script: ScriptBuf },
+}
+
+#[derive(Clone, Copy)]
+enum HtlcPath {
+    Claim,
+    Refund,
+}
+
+/// A hash time-locked contract: the receiver can claim with the preimage of
+/// `payment_hash` (SHA256), or the sender can take the coins back once the output has
+/// `timeout_blocks` confirmations. With `wsh` (the default) both branches are one
+/// `OP_IF` script; with `tr` each is a leaf under an unspendable internal key.
+#[wasm_bindgen]
+pub fn build_htlc(
+    receiver_pubkey: &str,
+    sender_pubkey: &str,
+    payment_hash: &str,
+    timeout_blocks: u32,
+    script_type: Option<String>,
+    network: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let receiver = require_compressed(&parse_public_key(receiver_pubkey)?, "An HTLC")?;
+    let sender = require_compressed(&parse_public_key(sender_pubkey)?, "An HTLC")?;
+    let payment_hash = parse_payment_hash(payment_hash).map_err(|e| JsValue::from_str(&e))?;
+    let script_type = script_type.as_deref().unwrap_or("wsh").trim().to_ascii_lowercase();
+    let scripts = build_scripts(&secp, receiver, sender, payment_hash, timeout_blocks, &script_type)
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    let script_pubkey = scripts.script_pubkey(&secp);
+    let address = network.address(&script_pubkey).ok_or_else(|| JsValue::from_str("Cannot encode address"))?;
+    let mut contract = HtlcContract {
+        script_type: scripts.kind_name().to_string(),
+        receiver_key: receiver.to_string(),
+        sender_key: sender.to_string(),
+        payment_hash: payment_hash.to_string(),
+        timeout_blocks,
+        network: network.name.clone(),
+        address,
+        script_pubkey: bytes_to_hex(script_pubkey.as_bytes()),
+        witness_script: None,
+        internal_key: None,
+        claim_leaf: None,
+        claim_control_block: None,
+        refund_leaf: None,
+        refund_control_block: None,
+    };
+    match &scripts.kind {
+        HtlcKind::Taproot { claim, refund, spend_info } => {
+            let control_block = |leaf: &ScriptBuf| {
+                spend_info
+                    .control_block(&(leaf.clone(), LeafVersion::TapScript))
+                    .map(|control_block| bytes_to_hex(&control_block.serialize()))
+            };
+            contract.internal_key = Some(spend_info.internal_key().to_string());
+            contract.claim_leaf = Some(bytes_to_hex(claim.as_bytes()));
+            contract.claim_control_block = control_block(claim);
+            contract.refund_leaf = Some(bytes_to_hex(refund.as_bytes()));
+            contract.refund_control_block = control_block(refund);
+        }
+        HtlcKind::Wsh { witness_script } => contract.witness_script = Some(bytes_to_hex(witness_script.as_bytes())),
+    }
+
+    to_json(&contract, compact)
+}
+
+/// Signs input `input_index` through the claim branch with the receiver's key. The
+/// preimage is checked against the payment hash first, since a wrong one would only
+/// show up as a rejected transaction. No timelock applies.
+#[wasm_bindgen]
+pub fn claim_htlc(
+    htlc_json: &str,
+    tx_hex: &str,
+    input_index: usize,
+    prevouts_json: &str,
+    preimage_hex: &str,
+    private_key: &str,
+    checks_json: Option<String>,
+) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let (scripts, mut tx, prevouts) = load_spend(&secp, htlc_json, tx_hex, input_index, prevouts_json)?;
+    let preimage = hex_to_bytes(preimage_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid preimage hex: {}", e)))?;
+    let hash = sha256::Hash::hash(&preimage);
+    if hash != scripts.payment_hash {
+        return Err(JsValue::from_str(&format!(
+            "Preimage hashes to {}, not the payment hash {}",
+            hash, scripts.payment_hash
+        )));
+    }
+    let private_key = parse_private_key(private_key, None, Network::Testnet)?;
+    if CompressedPublicKey::from_private_key(&secp, &private_key).ok() != Some(scripts.receiver) {
+        return Err(JsValue::from_str("Private key does not match the HTLC's receiver key"));
+    }
+
+    let mut cache = SighashCache::new(&tx);
+    let signature = scripts.sign(&secp, &mut cache, HtlcPath::Claim, input_index, &prevouts, &private_key)?;
+    let witness = match &scripts.kind {
+        HtlcKind::Taproot { claim, spend_info, .. } => {
+            let control_block = spend_info
+                .control_block(&(claim.clone(), LeafVersion::TapScript))
+                .ok_or_else(|| JsValue::from_str("Claim leaf missing from the taproot tree"))?;
+            Witness::from_slice(&[signature, preimage, claim.to_bytes(), control_block.serialize()])
+        }
+        // 0x01 takes the OP_IF branch; MINIMALIF allows nothing else.
+        HtlcKind::Wsh { witness_script } => Witness::from_slice(&[signature, preimage, vec![1], witness_script.to_bytes()]),
+    };
+
+    tx.input[input_index].witness = witness;
+    Ok(bytes_to_hex(&finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?))
+}
+
+/// Signs input `input_index` through the refund branch with the sender's key. It sets the
+/// input's sequence to the timeout and the transaction version to 2 (BIP68 needs both)
+/// before signing. Nodes reject the refund until the HTLC output has `timeout_blocks`
+/// confirmations.
+#[wasm_bindgen]
+pub fn refund_htlc(
+    htlc_json: &str,
+    tx_hex: &str,
+    input_index: usize,
+    prevouts_json: &str,
+    private_key: &str,
+    checks_json: Option<String>,
+) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let (scripts, mut tx, prevouts) = load_spend(&secp, htlc_json, tx_hex, input_index, prevouts_json)?;
+    let private_key = parse_private_key(private_key, None, Network::Testnet)?;
+    if CompressedPublicKey::from_private_key(&secp, &private_key).ok() != Some(scripts.sender) {
+        return Err(JsValue::from_str("Private key does not match the HTLC's sender key"));
+    }
+
+    // Both fields are committed to by every input's signature, so they must be settled first.
+    let sequence = Sequence::from_height(scripts.timeout as u16);
+    let needs_change = tx.input[input_index].sequence != sequence || tx.version < transaction::Version::TWO;
+    let other_signed = tx
+        .input
+        .iter()
+        .enumerate()
+        .any(|(index, input)| index != input_index && (!input.witness.is_empty() || !input.script_sig.is_empty()));
+    if needs_change && other_signed {
+        return Err(JsValue::from_str(&format!(
+            "Other inputs are already signed; set input {} sequence to {} and version 2 before signing them",
+            input_index,
+            sequence.to_consensus_u32()
+        )));
+    }
+    tx.input[input_index].sequence = sequence;
+    if tx.version < transaction::Version::TWO {
+        tx.version = transaction::Version::TWO;
+    }
+
+    let mut cache = SighashCache::new(&tx);
+    let signature = scripts.sign(&secp, &mut cache, HtlcPath::Refund, input_index, &prevouts, &private_key)?;
+    let witness = match &scripts.kind {
+        HtlcKind::Taproot { refund, spend_info, .. } => {
+            let control_block = spend_info
+                .control_block(&(refund.clone(), LeafVersion::TapScript))
+                .ok_or_else(|| JsValue::from_str("Refund leaf missing from the taproot tree"))?;
+            Witness::from_slice(&[signature, refund.to_bytes(), control_block.serialize()])
+        }
+        // The empty element takes the OP_ELSE branch.
+        HtlcKind::Wsh { witness_script } => Witness::from_slice(&[signature, Vec::new(), witness_script.to_bytes()]),
+    };
+
+    tx.input[input_index].witness = witness;
+    Ok(bytes_to_hex(&finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?))
+}
+
+fn parse_payment_hash(payment_hash: &str) -> Result<sha256::Hash, String> {
+    let bytes = hex_to_bytes(payment_hash, HexMode::Lenient).map_err(|e| format!("Invalid payment hash hex: {}", e))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("Payment hash must be 32 bytes (a SHA256 digest), got {}", bytes.len()))?;
+    Ok(sha256::Hash::from_byte_array(bytes))
+}
+
+fn build_scripts(
+    secp: &Secp256k1<All>,
+    receiver: CompressedPublicKey,
+    sender: CompressedPublicKey,
+    payment_hash: sha256::Hash,
+    timeout: u32,
+    script_type: &str,
+) -> Result<HtlcScripts, String> {
+    if timeout == 0 || timeout > MAX_TIMEOUT_BLOCKS {
+        return Err(format!("timeout_blocks must be between 1 and {}, got {}", MAX_TIMEOUT_BLOCKS, timeout));
+    }
+    if receiver == sender {
+        return Err("Receiver and sender keys must differ".to_string());
+    }
+
+    let kind = match script_type {
+        "wsh" | "p2wsh" => {
+            let witness_script = Builder::new()
+                .push_opcode(OP_IF)
+                .push_opcode(OP_SHA256)
+                .push_slice(payment_hash.to_byte_array())
+                .push_opcode(OP_EQUALVERIFY)
+                .push_key(&receiver.into())
+                .push_opcode(OP_ELSE)
+                .push_int(timeout as i64)
+                .push_opcode(OP_CSV)
+                .push_opcode(OP_DROP)
+                .push_key(&sender.into())
+                .push_opcode(OP_ENDIF)
+                .push_opcode(OP_CHECKSIG)
+                .into_script();
+            HtlcKind::Wsh { witness_script }
+        }
+        "tr" | "taproot" | "p2tr" => {
+            let claim = Builder::new()
+                .push_opcode(OP_SHA256)
+                .push_slice(payment_hash.to_byte_array())
+                .push_opcode(OP_EQUALVERIFY)
+                .push_x_only_key(&receiver.0.x_only_public_key().0)
+                .push_opcode(OP_CHECKSIG)
+                .into_script();
+            let refund = Builder::new()
+                .push_int(timeout as i64)
+                .push_opcode(OP_CSV)
+                .push_opcode(OP_DROP)
+                .push_x_only_key(&sender.0.x_only_public_key().0)
+                .push_opcode(OP_CHECKSIG)
+                .into_script();
+            let internal_key = XOnlyPublicKey::from_str(NUMS_POINT).expect("valid NUMS point");
+            let spend_info = TaprootBuilder::new()
+                .add_leaf(1, claim.clone())
+                .and_then(|builder| builder.add_leaf(1, refund.clone()))
+                .map_err(|e| format!("Cannot build taproot tree: {}", e))?
+                .finalize(secp, internal_key)
+                .map_err(|_| "Cannot build taproot tree".to_string())?;
+            HtlcKind::Taproot { claim, refund, spend_info: Box::new(spend_info) }
+        }
+        other => return Err(format!("Unknown script type \"{}\" (expected wsh or tr)", other)),
+    };
+    Ok(HtlcScripts { receiver, sender, payment_hash, timeout, kind })
+}
+
+impl HtlcScripts {
+    fn kind_name(&self) -> &'static str {
+        match self.kind {
+            HtlcKind::Taproot { .. } => "tr",
+            HtlcKind::Wsh { .. } => "wsh",
+        }
+    }
+
+    fn script_pubkey(&self, secp: &Secp256k1<All>) -> ScriptBuf {
+        match &self.kind {
+            HtlcKind::Taproot { spend_info, .. } => {
+                ScriptBuf::new_p2tr(secp, spend_info.internal_key(), spend_info.merkle_root())
+            }
+            HtlcKind::Wsh { witness_script } => ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+        }
+    }
+
+    /// The serialized signature for one branch: ECDSA over the whole witness script, or
+    /// Schnorr over that branch's leaf.
+    fn sign(
+        &self,
+        secp: &Secp256k1<All>,
+        cache: &mut SighashCache<&Transaction>,
+        path: HtlcPath,
+        input_index: usize,
+        prevouts: &[TxOut],
+        private_key: &bitcoin::PrivateKey,
+    ) -> Result<Vec<u8>, JsValue> {
+        match &self.kind {
+            HtlcKind::Taproot { claim, refund, .. } => {
+                let leaf = match path {
+                    HtlcPath::Claim => claim,
+                    HtlcPath::Refund => refund,
+                };
+                let leaf_hash = TapLeafHash::from_script(leaf, LeafVersion::TapScript);
+                let sighash = cache
+                    .taproot_script_spend_signature_hash(input_index, &Prevouts::All(prevouts), leaf_hash, TapSighashType::Default)
+                    .map_err(|e| JsValue::from_str(&format!("Failed to compute sighash: {}", e)))?;
+                let keypair = Keypair::from_secret_key(secp, &private_key.inner);
+                let signature = bitcoin::taproot::Signature {
+                    signature: secp.sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair),
+                    sighash_type: TapSighashType::Default,
+                };
+                Ok(signature.to_vec())
+            }
+            HtlcKind::Wsh { witness_script } => {
+                let sighash = cache
+                    .p2wsh_signature_hash(input_index, witness_script, prevouts[input_index].value, EcdsaSighashType::All)
+                    .map_err(|e| JsValue::from_str(&format!("Failed to compute sighash: {}", e)))?;
+                let signature = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa_low_r(&Message::from(sighash), &private_key.inner));
+                Ok(signature.to_vec())
+            }
+        }
+    }
+}
+
+fn load_spend(
+    secp: &Secp256k1<All>,
+    htlc_json: &str,
+    tx_hex: &str,
+    input_index: usize,
+    prevouts_json: &str,
+) -> Result<(HtlcScripts, Transaction, Vec<TxOut>), JsValue> {
+    let contract: HtlcContract = serde_json::from_str(htlc_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid HTLC JSON: {}", e)))?;
+    let receiver = require_compressed(&parse_public_key(&contract.receiver_key)?, "An HTLC")?;
+    let sender = require_compressed(&parse_public_key(&contract.sender_key)?, "An HTLC")?;
+    let payment_hash = parse_payment_hash(&contract.payment_hash).map_err(|e| JsValue::from_str(&e))?;
+    let scripts = build_scripts(secp, receiver, sender, payment_hash, contract.timeout_blocks, &contract.script_type)
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    let tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+    if prevouts[input_index].script_pubkey != scripts.script_pubkey(secp) {
+        return Err(JsValue::from_str(&format!("Prevout {} is not paid to this HTLC", input_index)));
+    }
+    Ok((scripts, tx, prevouts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{Amount, OutPoint, TxIn, Txid};
+    use crate::script::interpreter::verify_input;
+    use crate::transaction::parse_tx_hex;
+
+    const RECEIVER: &str = "0303030303030303030303030303030303030303030303030303030303030303";
+    const SENDER: &str = "0404040404040404040404040404040404040404040404040404040404040404";
+    const PREIMAGE: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+    const TIMEOUT: u32 = 72;
+
+    fn public(private_key: &str) -> String {
+        SecretKey::from_str(private_key).unwrap().public_key(&Secp256k1::new()).to_string()
+    }
+
+    /// One HTLC output, its unsigned spend and the prevouts JSON for it.
+    fn funded(script_type: &str) -> (String, String, Vec<TxOut>, String) {
+        let payment_hash = sha256::Hash::hash(&hex_to_bytes(PREIMAGE, HexMode::Strict).unwrap()).to_string();
+        let htlc_json = build_htlc(&public(RECEIVER), &public(SENDER), &payment_hash, TIMEOUT, Some(script_type.to_string()), None, None).unwrap();
+        let htlc: HtlcContract = serde_json::from_str(&htlc_json).unwrap();
+        let prevout = TxOut { value: Amount::from_sat(50_000), script_pubkey: ScriptBuf::from_hex(&htlc.script_pubkey).unwrap() };
+        let tx = Transaction {
+            version: transaction::Version::ONE,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::from_byte_array([4; 32]), 1), ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(49_000), script_pubkey: ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros()) }],
+        };
+        let prevouts_json = serde_json::json!([{"amountSat": 50_000, "scriptPubkey": htlc.script_pubkey}]).to_string();
+        (htlc_json, bitcoin::consensus::encode::serialize_hex(&tx), vec![prevout], prevouts_json)
+    }
+
+    fn assert_verifies(tx: &Transaction, prevouts: &[TxOut]) {
+        if let Err(failure) = verify_input(tx, 0, prevouts) {
+            panic!("fails in {} at {:?}: {}", failure.phase, failure.opcode, failure.reason);
+        }
+        // The signature commits to the amount, so a different one must not verify.
+        let mut other_amount = prevouts.to_vec();
+        other_amount[0].value = Amount::from_sat(50_001);
+        assert!(verify_input(tx, 0, &other_amount).is_err());
+    }
+
+    #[test]
+    fn claims_verify() {
+        for script_type in ["wsh", "tr"] {
+            let (htlc, tx_hex, prevouts, prevouts_json) = funded(script_type);
+            let signed = parse_tx_hex(&claim_htlc(&htlc, &tx_hex, 0, &prevouts_json, PREIMAGE, RECEIVER, None).unwrap()).unwrap();
+            assert_eq!(signed.input[0].witness.nth(1).map(bytes_to_hex).as_deref(), Some(PREIMAGE), "{}", script_type);
+            assert_verifies(&signed, &prevouts);
+
+            // The same signature with another preimage fails at the hash check.
+            let mut wrong_preimage = signed.clone();
+            let mut items: Vec<Vec<u8>> = wrong_preimage.input[0].witness.iter().map(<[u8]>::to_vec).collect();
+            items[1][0] ^= 1;
+            wrong_preimage.input[0].witness = Witness::from_slice(&items);
+            let failure = verify_input(&wrong_preimage, 0, &prevouts).err().unwrap();
+            assert_eq!(failure.opcode.as_deref(), Some("OP_EQUALVERIFY"), "{}", script_type);
+        }
+    }
+
+    #[test]
+    fn refunds_verify_with_the_sequence_they_set() {
+        for script_type in ["wsh", "tr"] {
+            let (htlc, tx_hex, prevouts, prevouts_json) = funded(script_type);
+            let signed = parse_tx_hex(&refund_htlc(&htlc, &tx_hex, 0, &prevouts_json, SENDER, None).unwrap()).unwrap();
+            assert_eq!(signed.version, transaction::Version::TWO);
+            assert_eq!(signed.input[0].sequence, Sequence::from_height(TIMEOUT as u16), "{}", script_type);
+            assert_verifies(&signed, &prevouts);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use wasm_bindgen::prelude::*;
+use bitcoin::key::{CompressedPublicKey, UntweakedPublicKey};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Address, PrivateKey, PublicKey};
+use crate::utils::network::network_or_default;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::parse_secret_key;
+
+#[wasm_bindgen]
+pub fn export_wif(private_key_hex: &str, compressed: Option<bool>, network: Option<String>) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let secret_key = parse_secret_key(private_key_hex)?;
+
+    let private_key = if compressed.unwrap_or(true) {
+        PrivateKey::new(secret_key, network.network)
+    } else {
+        PrivateKey::new_uncompressed(secret_key, network.network)
+    };
+
+    Ok(private_key.to_wif())
+}
+
+#[wasm_bindgen]
+pub fn convert_pubkey_compression(pubkey_hex: &str) -> Result<String, JsValue> {
+    let mut pubkey = parse_public_key(pubkey_hex)?;
+    pubkey.compressed = !pubkey.compressed;
+    Ok(bytes_to_hex(&pubkey.to_bytes()))
+}
+
+#[wasm_bindgen]
+pub fn address_from_pubkey(pubkey_hex: &str, address_type: &str, network: Option<String>) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let pubkey = parse_public_key(pubkey_hex)?;
+
+    let address = match address_type {
+        "legacy" | "p2pkh" => Address::p2pkh(pubkey, network.network),
+        "segwit" | "p2wpkh" => Address::p2wpkh(&require_compressed(&pubkey, "P2WPKH")?, network.network),
+        "nested_segwit" | "p2sh-p2wpkh" => Address::p2shwpkh(&require_compressed(&pubkey, "P2SH-P2WPKH")?, network.network),
+        "taproot" | "p2tr" => {
+            let secp = Secp256k1::verification_only();
+            let internal_key: UntweakedPublicKey = pubkey.inner.x_only_public_key().0;
+            Address::p2tr(&secp, internal_key, None, network.network)
+        }
+        other => {
+            return Err(JsValue::from_str(&format!(
+                "Unknown address type '{}' (expected legacy, segwit, nested_segwit or taproot)",
+                other
+            )))
+        }
+    };
+
+    network
+        .address(&address.script_pubkey())
+        .ok_or_else(|| JsValue::from_str("Cannot encode address"))
+}
+
+pub fn parse_public_key(pubkey_hex: &str) -> Result<PublicKey, JsValue> {
+    let bytes = hex_to_bytes(pubkey_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid public key hex: {}", e)))?;
+
+    PublicKey::from_slice(&bytes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid public key: {}", e)))
+}
+
+pub fn require_compressed(pubkey: &PublicKey, script_type: &str) -> Result<CompressedPublicKey, JsValue> {
+    CompressedPublicKey::try_from(*pubkey).map_err(|_| {
+        JsValue::from_str(&format!(
+            "{} requires a compressed public key; uncompressed keys can only be used with legacy (P2PKH) addresses",
+            script_type
+        ))
+    })
+}
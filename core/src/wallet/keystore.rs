@@ -0,0 +1,273 @@
+use std::hint::black_box;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use crate::utils::base64::{decode_base64, encode_base64};
+use crate::utils::json::{parse_json_value, to_json};
+use crate::utils::metrics::{now_ms, timed};
+use crate::utils::rng::with_rng;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::argon2::{argon2id, Argon2Params};
+
+/// Blob layout, after base64: version, KDF id, memory (KiB, LE32), iterations (LE32),
+/// parallelism, salt, ciphertext, then an HMAC-SHA256 tag over everything before it.
+const FORMAT_VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const HEADER_LEN: usize = 1 + 1 + 4 + 4 + 1 + SALT_LEN;
+const SALT_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+
+/// OWASP's minimum recommendation for Argon2id, which takes 100 to 300 ms in wasm on
+/// mid-range hardware.
+const DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ITERATIONS: u32 = 2;
+const DEFAULT_PARALLELISM: u32 = 1;
+/// Below this a PIN falls to a GPU too quickly; new blobs may not go under it.
+const MIN_MEMORY_KIB: u32 = 8 * 1024;
+/// Above this a blob could exhaust a browser tab's memory, so it isn't even attempted.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 100;
+/// Lanes are filled one after another here, so more of them only cost time.
+const MAX_PARALLELISM: u32 = 16;
+/// How far `calibrate_kdf` raises memory before adding iterations instead.
+const CALIBRATION_MAX_MEMORY_KIB: u32 = 64 * 1024;
+const CALIBRATION_SAMPLE: Argon2Params = Argon2Params { memory_kib: 4096, iterations: 1, parallelism: 1 };
+
+const WRONG_PIN_OR_CORRUPTED: &str = "Wrong PIN or corrupted secret";
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct KdfParams {
+    #[serde(alias = "memory_kib")]
+    pub memory_kib: Option<u32>,
+    pub iterations: Option<u32>,
+    pub parallelism: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfCalibration {
+    /// Pass as `kdf_params_json` to `encrypt_secret`.
+    pub params: KdfParams,
+    /// What these parameters should take, scaled from `measured_ms`.
+    #[serde(alias = "estimated_ms")]
+    pub estimated_ms: f64,
+    /// One pass over 4 MiB on this device, the fastest of a few runs.
+    #[serde(alias = "measured_ms")]
+    pub measured_ms: f64,
+    pub warnings: Vec<String>,
+}
+
+/// Encrypts `secret_hex` under `pin` for storage in the browser: the PIN is stretched
+/// with Argon2id over a random salt, the secret is xored with an HMAC-SHA256 keystream
+/// and the result authenticated with HMAC-SHA256. `kdf_params_json` (`memory_kib`,
+/// `iterations`, `parallelism`, each optional) tunes the stretching; `calibrate_kdf`
+/// suggests values for the current device. Returns a base64 blob that records its
+/// parameters, so `decrypt_secret` needs only the PIN.
+#[wasm_bindgen]
+pub fn encrypt_secret(secret_hex: &str, pin: &str, kdf_params_json: Option<String>) -> Result<String, JsValue> {
+    timed("encrypt_secret", secret_hex.len(), || {
+        let secret = hex_to_bytes(secret_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid secret hex: {}", e)))?;
+        if secret.is_empty() {
+            return Err(JsValue::from_str("Secret must not be empty"));
+        }
+        if pin.is_empty() {
+            return Err(JsValue::from_str("PIN must not be empty"));
+        }
+        let params = match kdf_params_json {
+            Some(json) => parse_json_value::<KdfParams>(&json, "KDF params").map_err(|e| JsValue::from_str(&e))?,
+            None => KdfParams { memory_kib: None, iterations: None, parallelism: None },
+        };
+        let params = Argon2Params {
+            memory_kib: params.memory_kib.unwrap_or(DEFAULT_MEMORY_KIB),
+            iterations: params.iterations.unwrap_or(DEFAULT_ITERATIONS),
+            parallelism: params.parallelism.unwrap_or(DEFAULT_PARALLELISM),
+        };
+        if params.memory_kib < MIN_MEMORY_KIB {
+            return Err(JsValue::from_str(&format!(
+                "Invalid KDF params: memory_kib must be at least {} for new secrets, got {}",
+                MIN_MEMORY_KIB, params.memory_kib
+            )));
+        }
+        check_params(&params).map_err(|e| JsValue::from_str(&format!("Invalid KDF params: {}", e)))?;
+
+        let mut salt = [0u8; SALT_LEN];
+        with_rng(|rng| rng.fill_bytes(&mut salt));
+        let mut blob = vec![FORMAT_VERSION, KDF_ARGON2ID];
+        blob.extend_from_slice(&params.memory_kib.to_le_bytes());
+        blob.extend_from_slice(&params.iterations.to_le_bytes());
+        blob.push(params.parallelism as u8);
+        blob.extend_from_slice(&salt);
+
+        let (enc_key, mac_key) = derive_keys(pin, &salt, &params);
+        let mut ciphertext = secret;
+        xor_keystream(&enc_key, &mut ciphertext);
+        blob.extend_from_slice(&ciphertext);
+        let tag = mac(&mac_key, &blob);
+        blob.extend_from_slice(&tag);
+        Ok(encode_base64(&blob, false))
+    })
+}
+
+/// Decrypts a blob from `encrypt_secret`, returning the secret as hex. A blob that isn't
+/// in the format, or asks for more than 1 GiB or 100 iterations, is rejected before any
+/// work. Otherwise the KDF always runs in full and the tag is compared in constant time,
+/// so a wrong PIN and a damaged salt, ciphertext or tag fail alike, with the same error
+/// after the same time.
+#[wasm_bindgen]
+pub fn decrypt_secret(blob: &str, pin: &str) -> Result<String, JsValue> {
+    timed("decrypt_secret", blob.len(), || {
+        let bytes = decode_base64(blob.trim()).map_err(|e| JsValue::from_str(&format!("Invalid encrypted secret: {}", e)))?;
+        let (params, salt) = parse_header(&bytes).map_err(|e| JsValue::from_str(&format!("Invalid encrypted secret: {}", e)))?;
+        let (authenticated, tag) = bytes.split_at(bytes.len() - TAG_LEN);
+
+        let (enc_key, mac_key) = derive_keys(pin, salt, &params);
+        let matches = constant_time_eq(&mac(&mac_key, authenticated), tag);
+        // Decrypt either way, so the two outcomes differ only after all the work is done.
+        let mut secret = authenticated[HEADER_LEN..].to_vec();
+        xor_keystream(&enc_key, &mut secret);
+        if !matches {
+            return Err(JsValue::from_str(WRONG_PIN_OR_CORRUPTED));
+        }
+        Ok(bytes_to_hex(&secret))
+    })
+}
+
+/// Suggests `encrypt_secret` parameters that take about `target_ms` on this device, by
+/// timing a small Argon2id run and scaling it: memory first, up to 64 MiB, then
+/// iterations. Memory is what makes guessing expensive on GPUs, so it goes up before
+/// time does. Run this where the secret will be unlocked, as phones can be several times
+/// slower than desktops.
+#[wasm_bindgen]
+pub fn calibrate_kdf(target_ms: f64, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("calibrate_kdf", 0, || {
+        if !target_ms.is_finite() || target_ms <= 0.0 {
+            return Err(JsValue::from_str("target_ms must be a positive number"));
+        }
+        let mut measured_ms = f64::INFINITY;
+        for _ in 0..3 {
+            let started_ms = now_ms();
+            black_box(argon2id(b"calibration", &[0; SALT_LEN], &CALIBRATION_SAMPLE, 64));
+            measured_ms = measured_ms.min(now_ms() - started_ms);
+        }
+        // A timer coarsened against fingerprinting can read 0 for a fast run.
+        let ms_per_kib_pass = measured_ms.max(0.1) / CALIBRATION_SAMPLE.memory_kib as f64;
+
+        let mut warnings = Vec::new();
+        let budget_kib_passes = target_ms / ms_per_kib_pass;
+        let memory_kib = budget_kib_passes.clamp(MIN_MEMORY_KIB as f64, CALIBRATION_MAX_MEMORY_KIB as f64) as u32 / 1024 * 1024;
+        let iterations = ((budget_kib_passes / memory_kib as f64).floor() as u32).clamp(1, MAX_ITERATIONS);
+        let estimated_ms = ms_per_kib_pass * memory_kib as f64 * iterations as f64;
+        if estimated_ms > target_ms * 1.5 {
+            warnings.push(format!(
+                "This device is slow: even the minimum of {} KiB and 1 iteration takes about {:.0} ms",
+                MIN_MEMORY_KIB, estimated_ms
+            ));
+        }
+        if measured_ms < 1.0 {
+            warnings.push("The timer is too coarse for a precise measurement; the estimate may be off".to_string());
+        }
+
+        to_json(&KdfCalibration {
+            params: KdfParams { memory_kib: Some(memory_kib), iterations: Some(iterations), parallelism: Some(DEFAULT_PARALLELISM) },
+            estimated_ms,
+            measured_ms,
+            warnings,
+        }, compact)
+    })
+}
+
+/// The header's parameters and salt. Everything here is public, so failures are reported
+/// precisely; only the tag check has to stay silent about what went wrong.
+fn parse_header(bytes: &[u8]) -> Result<(Argon2Params, &[u8]), String> {
+    if bytes.len() < HEADER_LEN + 1 + TAG_LEN {
+        return Err(format!("{} bytes is too short", bytes.len()));
+    }
+    if bytes[0] != FORMAT_VERSION {
+        return Err(format!("format version {} isn't supported (this version reads {})", bytes[0], FORMAT_VERSION));
+    }
+    if bytes[1] != KDF_ARGON2ID {
+        return Err(format!("KDF {} isn't supported (only Argon2id, {})", bytes[1], KDF_ARGON2ID));
+    }
+    let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().expect("4 bytes"));
+    let params = Argon2Params { memory_kib: word(2), iterations: word(6), parallelism: bytes[10] as u32 };
+    check_params(&params)?;
+    Ok((params, &bytes[11..HEADER_LEN]))
+}
+
+fn check_params(params: &Argon2Params) -> Result<(), String> {
+    params.check()?;
+    if params.memory_kib > MAX_MEMORY_KIB {
+        return Err(format!("memory_kib is at most {}, got {}", MAX_MEMORY_KIB, params.memory_kib));
+    }
+    if params.iterations > MAX_ITERATIONS {
+        return Err(format!("iterations is at most {}, got {}", MAX_ITERATIONS, params.iterations));
+    }
+    if params.parallelism > MAX_PARALLELISM {
+        return Err(format!("parallelism is at most {}, got {}", MAX_PARALLELISM, params.parallelism));
+    }
+    Ok(())
+}
+
+/// Separate encryption and MAC keys from one 64-byte Argon2id output.
+fn derive_keys(pin: &str, salt: &[u8], params: &Argon2Params) -> ([u8; 32], [u8; 32]) {
+    let key = argon2id(pin.as_bytes(), salt, params, 64);
+    (key[..32].try_into().expect("32 bytes"), key[32..].try_into().expect("32 bytes"))
+}
+
+/// HMAC-SHA256 of a block counter, as a stream cipher. Each key encrypts one secret,
+/// since every blob has its own salt.
+fn xor_keystream(key: &[u8; 32], data: &mut [u8]) {
+    for (counter, chunk) in data.chunks_mut(32).enumerate() {
+        let block = mac(key, &(counter as u64).to_le_bytes());
+        chunk.iter_mut().zip(block).for_each(|(byte, key_byte)| *byte ^= key_byte);
+    }
+}
+
+fn mac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(key);
+    engine.input(data);
+    Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+}
+
+/// Compares every byte whatever the first difference, and keeps the optimizer from
+/// turning the fold back into an early exit.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b).fold(0u8, |difference, (x, y)| black_box(difference | (x ^ y)));
+    black_box(difference) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const FAST_PARAMS: &str = r#"{"memoryKib": 8192, "iterations": 1}"#;
+
+    #[test]
+    fn decrypts_what_it_encrypted() {
+        let blob = encrypt_secret(SECRET, "1234", Some(FAST_PARAMS.to_string())).unwrap();
+        assert_eq!(decrypt_secret(&blob, "1234").unwrap(), SECRET);
+
+        let bytes = decode_base64(&blob).unwrap();
+        let (params, _) = parse_header(&bytes).unwrap();
+        assert_eq!((params.memory_kib, params.iterations, params.parallelism), (8192, 1, DEFAULT_PARALLELISM));
+        assert_eq!(bytes.len(), HEADER_LEN + SECRET.len() / 2 + TAG_LEN);
+    }
+
+    #[test]
+    fn a_wrong_pin_fails_the_tag() {
+        let blob = encrypt_secret(SECRET, "1234", Some(FAST_PARAMS.to_string())).unwrap();
+        let bytes = decode_base64(&blob).unwrap();
+        let (params, salt) = parse_header(&bytes).unwrap();
+        let (authenticated, tag) = bytes.split_at(bytes.len() - TAG_LEN);
+        let (_, right_mac_key) = derive_keys("1234", salt, &params);
+        let (_, wrong_mac_key) = derive_keys("1235", salt, &params);
+        assert!(constant_time_eq(&mac(&right_mac_key, authenticated), tag));
+        assert!(!constant_time_eq(&mac(&wrong_mac_key, authenticated), tag));
+    }
+}
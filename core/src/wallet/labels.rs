@@ -0,0 +1,248 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::bip32::Xpub;
+use bitcoin::{Address, PublicKey, Txid};
+use crate::utils::json::{parse_json_list, to_json};
+
+/// One BIP-329 record. Fields we do not interpret (Sparrow's `height`, `fee`,
+/// `value`, `keypath`, ...) are carried in `extra`, in file order, so exports round-trip. Unlike API
+/// results, absent fields are left out, since that's what BIP-329 files do.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WalletLabel {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Uninterpreted fields in the order they were read. A plain JSON map would sort them,
+/// and a re-exported line would no longer match the wallet's byte for byte.
+#[derive(Clone, Default)]
+pub struct ExtraFields(pub Vec<(String, Value)>);
+
+impl Serialize for ExtraFields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ExtraFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ExtraFields, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = ExtraFields;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("label fields")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<ExtraFields, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(field) = access.next_entry::<String, Value>()? {
+                    fields.push(field);
+                }
+                Ok(ExtraFields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LabelLineError {
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LabelImport {
+    pub labels: Vec<WalletLabel>,
+    pub errors: Vec<LabelLineError>,
+}
+
+/// Each label becomes one JSONL line. `type` also accepts `address`, `transaction`
+/// and `utxo` as spellings of `addr`, `tx` and `output`.
+#[wasm_bindgen]
+pub fn export_labels_bip329(labels_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let labels = parse_labels(labels_json).map_err(|e| JsValue::from_str(&e))?;
+
+    let mut lines = Vec::with_capacity(labels.len());
+    let mut problems = Vec::new();
+    for (index, mut label) in labels.into_iter().enumerate() {
+        if let Err(e) = normalize_label(&mut label) {
+            problems.push(format!("labels[{}]: {}", index, e));
+            continue;
+        }
+        let line = to_json(&label, compact)?;
+        lines.push(line);
+    }
+
+    if !problems.is_empty() {
+        return Err(JsValue::from_str(&problems.join("; ")));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Parses a BIP-329 JSONL file. Bad lines are listed in `errors` rather than
+/// failing the whole import; blank lines are skipped.
+#[wasm_bindgen]
+pub fn import_labels_bip329(jsonl: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let mut result = LabelImport { labels: Vec::new(), errors: Vec::new() };
+
+    for (index, line) in jsonl.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = serde_json::from_str::<WalletLabel>(line)
+            .map_err(|e| e.to_string())
+            .and_then(|mut label| normalize_label(&mut label).map(|_| label));
+        match parsed {
+            Ok(label) => result.labels.push(label),
+            Err(error) => result.errors.push(LabelLineError { line: index + 1, error }),
+        }
+    }
+
+    to_json(&result, compact)
+}
+
+/// Reads each label from its own text, since going through a `Value` would sort the
+/// fields `extra` keeps in order. Bad input is reparsed by `parse_json_list` for its
+/// error messages.
+fn parse_labels(json: &str) -> Result<Vec<WalletLabel>, String> {
+    serde_json::from_str::<Vec<Box<RawValue>>>(json)
+        .ok()
+        .and_then(|items| items.iter().map(|item| serde_json::from_str(item.get()).ok()).collect())
+        .map_or_else(|| parse_json_list(json, "labels"), Ok)
+}
+
+fn normalize_label(label: &mut WalletLabel) -> Result<(), String> {
+    label.kind = match label.kind.trim() {
+        "tx" | "transaction" => "tx",
+        "addr" | "address" => "addr",
+        "pubkey" => "pubkey",
+        "input" => "input",
+        "output" | "utxo" => "output",
+        "xpub" => "xpub",
+        other => {
+            return Err(format!(
+                "unknown type \"{}\" (expected tx, addr, pubkey, input, output or xpub)",
+                other
+            ))
+        }
+    }
+    .to_string();
+
+    check_reference(&label.kind, &label.reference)?;
+    if label.spendable.is_some() && label.kind != "output" {
+        return Err(format!("spendable only applies to output records, not {}", label.kind));
+    }
+    Ok(())
+}
+
+fn check_reference(kind: &str, reference: &str) -> Result<(), String> {
+    match kind {
+        "tx" => check_txid(reference),
+        "addr" => Address::<NetworkUnchecked>::from_str(reference)
+            .map(|_| ())
+            .map_err(|e| format!("ref \"{}\" is not a valid address: {}", reference, e)),
+        "pubkey" => PublicKey::from_str(reference)
+            .map(|_| ())
+            .map_err(|e| format!("ref \"{}\" is not a valid public key: {}", reference, e)),
+        "xpub" => Xpub::from_str(reference)
+            .map(|_| ())
+            .map_err(|e| format!("ref \"{}\" is not a valid extended public key: {}", reference, e)),
+        // input and output refs are both txid:index
+        _ => {
+            let (txid, index) = reference
+                .rsplit_once(':')
+                .ok_or_else(|| format!("ref \"{}\" must be txid:index for {} records", reference, kind))?;
+            check_txid(txid)?;
+            index
+                .parse::<u32>()
+                .map(|_| ())
+                .map_err(|_| format!("ref \"{}\" has an invalid index \"{}\"", reference, index))
+        }
+    }
+}
+
+fn check_txid(txid: &str) -> Result<(), String> {
+    if txid.len() != 64 {
+        return Err(format!("txid \"{}\" must be 64 hex characters, got {}", txid, txid.len()));
+    }
+    Txid::from_str(txid)
+        .map(|_| ())
+        .map_err(|e| format!("txid \"{}\" is not valid hex: {}", txid, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Sparrow export: the BIP-329 example records with the fields Sparrow adds, plus
+    /// one no wallet defines.
+    const SPARROW: &str = r#"{"type":"tx","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd","label":"Transaction","origin":"wpkh([d34db33f/84'/0'/0'])","height":800000,"time":"2023-07-24T09:42:17Z","fee":1410,"value":-250000}
+{"type":"addr","ref":"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c","label":"Café ☕","keypath":"/0/12","heights":[800000,800123]}
+{"type":"output","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1","label":"Output","spendable":false,"value":50000,"height":800000,"keypath":"/1/3"}
+{"type":"input","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0","label":"Input"}
+{"type":"xpub","ref":"xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8","label":"Extended Public Key","zebra":true,"alpha":{"nested":1.5}}"#;
+
+    fn import(jsonl: &str) -> LabelImport {
+        serde_json::from_str(&import_labels_bip329(jsonl, None).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sparrow_export_round_trips_byte_for_byte() {
+        let imported = import(SPARROW);
+        assert!(imported.errors.is_empty());
+        assert_eq!(imported.labels.len(), 5);
+        let exported = export_labels_bip329(&serde_json::to_string(&imported.labels).unwrap(), None).unwrap();
+        assert_eq!(exported, SPARROW);
+    }
+
+    #[test]
+    fn known_and_unknown_fields() {
+        let labels = import(SPARROW).labels;
+        let kinds: Vec<&str> = labels.iter().map(|label| label.kind.as_str()).collect();
+        assert_eq!(kinds, ["tx", "addr", "output", "input", "xpub"]);
+        assert_eq!(labels[0].origin.as_deref(), Some("wpkh([d34db33f/84'/0'/0'])"));
+        assert_eq!(labels[2].spendable, Some(false));
+        assert_eq!(labels[3].spendable, None);
+        let keys = |label: &WalletLabel| label.extra.0.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+        assert_eq!(keys(&labels[0]), ["height", "time", "fee", "value"]);
+        assert_eq!(keys(&labels[4]), ["zebra", "alpha"]);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_not_fatal() {
+        let imported = import(&format!(
+            "{}\n\n{{\"type\":\"addr\",\"ref\":\"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c\",\"spendable\":true}}",
+            SPARROW.lines().next().unwrap()
+        ));
+        assert_eq!(imported.labels.len(), 1);
+        assert_eq!(imported.errors.len(), 1);
+        assert_eq!(imported.errors[0].line, 3);
+        assert_eq!(imported.errors[0].error, "spendable only applies to output records, not addr");
+    }
+}
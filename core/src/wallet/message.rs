@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::ecdsa;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::CompressedPublicKey;
+use bitcoin::opcodes::all::OP_RETURN;
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{All, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::sign_message::signed_msg_hash;
+use bitcoin::transaction::Version;
+use bitcoin::{absolute, Amount, OutPoint, PrivateKey, PublicKey, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use crate::script::parse_multisig;
+use crate::transaction::p2wsh::{p2wsh_witness, StackItem};
+use crate::transaction::sign::sign_input;
+use crate::utils::base64::{decode_base64, encode_base64};
+use crate::utils::network::{parse_network_params, require_address_network};
+use crate::wallet::parse_private_key;
+use crate::utils::json::to_json;
+
+const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// signmessage header bytes: 27 + recovery id, plus 4 for a compressed key (BIP137 adds
+/// 8 for P2SH-P2WPKH and 12 for P2WPKH).
+const HEADER_UNCOMPRESSED: u8 = 27;
+const HEADER_P2PKH: u8 = 31;
+const HEADER_P2SH_P2WPKH: u8 = 35;
+const HEADER_P2WPKH: u8 = 39;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressProof {
+    /// `legacy` (signmessage), `bip137` or `bip322-simple`.
+    pub scheme: String,
+    pub proof: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofVerification {
+    pub valid: bool,
+    pub scheme: String,
+    pub error: Option<String>,
+}
+
+/// The keys behind an address that one private key can't prove on its own.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProvingWallet {
+    #[serde(alias = "private_keys")]
+    private_keys: Vec<String>,
+    #[serde(alias = "witness_script")]
+    witness_script: Option<String>,
+    #[serde(alias = "stack_template")]
+    stack_template: Option<Vec<StackItem>>,
+}
+
+/// Proves control of `address` by signing `challenge_message` in the format the address
+/// type calls for: signmessage for P2PKH, BIP137 for P2SH-P2WPKH and P2WPKH, and BIP322
+/// simple signatures for P2TR and P2WSH. `private_key_or_wallet` is a hex or WIF key, or
+/// JSON `{privateKeys, witnessScript?, stackTemplate?}`.
+#[wasm_bindgen]
+pub fn prove_address(
+    private_key_or_wallet: &str,
+    address: &str,
+    challenge_message: &str,
+    network: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let script = address_script(address, network)?;
+    let wallet = parse_wallet(private_key_or_wallet)?;
+    let keys = wallet
+        .private_keys
+        .iter()
+        .map(|key| parse_private_key(key, None, bitcoin::Network::Testnet))
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(JsValue::from_str("No private keys given"));
+    }
+    let not_controlled = || JsValue::from_str(&format!("None of the given keys controls {}", address.trim()));
+
+    let secp = Secp256k1::new();
+    let proof = if script.is_p2pkh() || script.is_p2sh() || script.is_p2wpkh() {
+        let (key, header) = keys
+            .iter()
+            .find_map(|key| signmessage_header(&secp, key, &script).map(|header| (key, header)))
+            .ok_or_else(not_controlled)?;
+        let scheme = if script.is_p2pkh() { "legacy" } else { "bip137" };
+        AddressProof { scheme: scheme.to_string(), proof: sign_message(&secp, key, header, challenge_message) }
+    } else if script.is_p2tr() || script.is_p2wsh() {
+        let to_sign = bip322_to_sign(&bip322_to_spend(&script, challenge_message), Witness::new());
+        let witness = if script.is_p2tr() {
+            let prevout = TxOut { value: Amount::ZERO, script_pubkey: script.clone() };
+            let mut cache = SighashCache::new(&to_sign);
+            keys.iter()
+                .find_map(|key| sign_input(&secp, &mut cache, 0, &prevout, std::slice::from_ref(&prevout), key).ok())
+                .ok_or_else(not_controlled)?
+                .witness
+        } else {
+            p2wsh_proof_witness(&secp, &to_sign, &script, &wallet, &keys)?
+        };
+        AddressProof { scheme: "bip322-simple".to_string(), proof: encode_base64(&serialize(&witness), false) }
+    } else {
+        return Err(JsValue::from_str("Only P2PKH, P2SH-P2WPKH, P2WPKH, P2WSH and P2TR addresses can be proven"));
+    };
+
+    to_json(&proof, compact)
+}
+
+/// Checks a proof from `prove_address`, or from a wallet using the same formats. The
+/// scheme follows from the address and the proof's length; signmessage signatures made
+/// with the compressed header (as Electrum does for segwit addresses) are accepted too.
+#[wasm_bindgen]
+pub fn verify_address_proof(address: &str, challenge: &str, proof: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let script = address_script(address, network)?;
+    if !(script.is_p2pkh() || script.is_p2sh() || script.is_p2wpkh() || script.is_p2wsh() || script.is_p2tr()) {
+        return Err(JsValue::from_str("Only P2PKH, P2SH, P2WPKH, P2WSH and P2TR addresses can be proven"));
+    }
+    let bytes = decode_base64(proof.trim());
+
+    let signmessage = matches!(&bytes, Ok(bytes) if bytes.len() == 65) && !script.is_p2tr() && !script.is_p2wsh();
+    let (scheme, checked) = if signmessage || script.is_p2pkh() || script.is_p2sh() {
+        let scheme = if script.is_p2pkh() { "legacy" } else { "bip137" };
+        (scheme, bytes.and_then(|bytes| verify_message(&bytes, &script, challenge)))
+    } else {
+        ("bip322-simple", bytes.and_then(|bytes| verify_bip322(&bytes, &script, challenge)))
+    };
+
+    let verification = ProofVerification { valid: checked.is_ok(), scheme: scheme.to_string(), error: checked.err() };
+    to_json(&verification, compact)
+}
+
+fn address_script(address: &str, network: Option<String>) -> Result<ScriptBuf, JsValue> {
+    let network = network.map(|name| parse_network_params(&name)).transpose().map_err(|e| JsValue::from_str(&e))?;
+    require_address_network(address, network.as_ref()).map_err(|e| JsValue::from_str(&e))
+}
+
+fn parse_wallet(input: &str) -> Result<ProvingWallet, JsValue> {
+    let input = input.trim();
+    if !input.starts_with('{') {
+        return Ok(ProvingWallet { private_keys: vec![input.to_string()], witness_script: None, stack_template: None });
+    }
+    serde_json::from_str(input).map_err(|e| JsValue::from_str(&format!("Invalid wallet JSON: {}", e)))
+}
+
+/// The header base for signing `script` with `key`, if the key controls it.
+fn signmessage_header(secp: &Secp256k1<All>, key: &PrivateKey, script: &Script) -> Option<u8> {
+    let pubkey = key.public_key(secp);
+    if script.is_p2pkh() {
+        if *script != ScriptBuf::new_p2pkh(&pubkey.pubkey_hash()) {
+            return None;
+        }
+        return Some(if key.compressed { HEADER_P2PKH } else { HEADER_UNCOMPRESSED });
+    }
+    let compressed = CompressedPublicKey::try_from(pubkey).ok()?;
+    let p2wpkh = ScriptBuf::new_p2wpkh(&compressed.wpubkey_hash());
+    if script.is_p2sh() && *script == ScriptBuf::new_p2sh(&p2wpkh.script_hash()) {
+        Some(HEADER_P2SH_P2WPKH)
+    } else if *script == p2wpkh {
+        Some(HEADER_P2WPKH)
+    } else {
+        None
+    }
+}
+
+fn sign_message(secp: &Secp256k1<All>, key: &PrivateKey, header: u8, message: &str) -> String {
+    let digest = Message::from_digest(signed_msg_hash(message).to_byte_array());
+    let (recovery_id, compact) = secp.sign_ecdsa_recoverable(&digest, &key.inner).serialize_compact();
+    let mut bytes = Vec::with_capacity(65);
+    bytes.push(header + recovery_id.to_i32() as u8);
+    bytes.extend_from_slice(&compact);
+    encode_base64(&bytes, false)
+}
+
+fn verify_message(bytes: &[u8], script: &Script, message: &str) -> Result<(), String> {
+    if bytes.len() != 65 {
+        return Err(format!("a signmessage signature is 65 bytes, this proof is {}", bytes.len()));
+    }
+    let header = bytes[0];
+    if !(HEADER_UNCOMPRESSED..HEADER_P2WPKH + 4).contains(&header) {
+        return Err(format!("unknown signature header byte {}", header));
+    }
+    let recovery_id = RecoveryId::from_i32(((header - HEADER_UNCOMPRESSED) % 4) as i32).map_err(|e| e.to_string())?;
+    let signature = RecoverableSignature::from_compact(&bytes[1..], recovery_id).map_err(|e| format!("invalid signature: {}", e))?;
+    let digest = Message::from_digest(signed_msg_hash(message).to_byte_array());
+    let secp = Secp256k1::verification_only();
+    let recovered = secp.recover_ecdsa(&digest, &signature).map_err(|e| format!("signature doesn't match the message: {}", e))?;
+
+    let matches = if header < HEADER_P2PKH {
+        *script == ScriptBuf::new_p2pkh(&PublicKey::new_uncompressed(recovered).pubkey_hash())
+    } else {
+        let key = CompressedPublicKey(recovered);
+        let p2wpkh = ScriptBuf::new_p2wpkh(&key.wpubkey_hash());
+        [ScriptBuf::new_p2pkh(&PublicKey::from(key).pubkey_hash()), ScriptBuf::new_p2sh(&p2wpkh.script_hash()), p2wpkh]
+            .contains(&script.to_owned())
+    };
+    if !matches {
+        return Err("the message was signed by a different key".to_string());
+    }
+    Ok(())
+}
+
+/// BIP322's `message_hash`: SHA256 tagged with "BIP0322-signed-message".
+fn bip322_message_hash(message: &str) -> [u8; 32] {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// The virtual transaction whose only output, paying to `script_pubkey`, the proof spends.
+fn bip322_to_spend(script_pubkey: &Script, message: &str) -> Transaction {
+    let script_sig = Builder::new().push_int(0).push_slice(bip322_message_hash(message)).into_script();
+    Transaction {
+        version: Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: script_pubkey.to_owned() }],
+    }
+}
+
+fn bip322_to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: to_spend.compute_txid(), vout: 0 },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script() }],
+    }
+}
+
+/// Signs a P2WSH proof with the wallet's witness script. Multisig scripts get the
+/// usual `<dummy> <sig>...` stack from whichever keys match, in script order; other
+/// scripts need a `stackTemplate` as for `sign_p2wsh_input`.
+fn p2wsh_proof_witness(
+    secp: &Secp256k1<All>,
+    to_sign: &Transaction,
+    script: &Script,
+    wallet: &ProvingWallet,
+    keys: &[PrivateKey],
+) -> Result<Witness, JsValue> {
+    let witness_script = wallet
+        .witness_script
+        .as_deref()
+        .ok_or_else(|| JsValue::from_str("P2WSH addresses need the wallet's witnessScript"))?;
+    let witness_script = ScriptBuf::from_hex(witness_script.trim())
+        .map_err(|e| JsValue::from_str(&format!("Invalid witness script: {}", e)))?;
+    if ScriptBuf::new_p2wsh(&witness_script.wscript_hash()) != *script {
+        return Err(JsValue::from_str("The witness script doesn't hash to this address"));
+    }
+
+    let template = match &wallet.stack_template {
+        Some(template) => template.clone(),
+        None => {
+            let multisig = parse_multisig(&witness_script).ok_or_else(|| {
+                JsValue::from_str("The witness script isn't multisig; give a stackTemplate for it")
+            })?;
+            let pubkeys: Vec<Vec<u8>> = keys.iter().map(|key| key.public_key(secp).to_bytes()).collect();
+            let mut template = vec![StackItem::Literal(String::new())];
+            for script_key in &multisig.pubkeys {
+                if template.len() > multisig.threshold {
+                    break;
+                }
+                if let Some(index) = pubkeys.iter().position(|pubkey| pubkey == script_key) {
+                    template.push(StackItem::Signature { sign_with: index, sighash: None });
+                }
+            }
+            if template.len() <= multisig.threshold {
+                return Err(JsValue::from_str(&format!(
+                    "The script needs {} signatures but only {} of its keys were given",
+                    multisig.threshold,
+                    template.len() - 1
+                )));
+            }
+            template
+        }
+    };
+    p2wsh_witness(secp, to_sign, 0, &witness_script, 0, &template, keys)
+}
+
+fn verify_bip322(bytes: &[u8], script: &Script, message: &str) -> Result<(), String> {
+    let witness: Witness = deserialize(bytes).map_err(|_| "proof isn't a BIP322 simple signature (a serialized witness)".to_string())?;
+    let to_sign = bip322_to_sign(&bip322_to_spend(script, message), witness.clone());
+    let prevout = TxOut { value: Amount::ZERO, script_pubkey: script.to_owned() };
+    let mut cache = SighashCache::new(&to_sign);
+    let secp = Secp256k1::verification_only();
+    let items: Vec<&[u8]> = witness.iter().collect();
+    let mismatch = || "the signature doesn't match the message and address".to_string();
+
+    if script.is_p2wpkh() {
+        let [signature, pubkey] = items[..] else {
+            return Err(format!("a P2WPKH proof has 2 witness items, this one has {}", items.len()));
+        };
+        let pubkey = CompressedPublicKey::from_slice(pubkey).map_err(|e| format!("invalid public key: {}", e))?;
+        if ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) != *script {
+            return Err("the public key doesn't belong to the address".to_string());
+        }
+        let signature = ecdsa::Signature::from_slice(signature).map_err(|e| format!("invalid signature: {}", e))?;
+        let sighash = cache
+            .p2wpkh_signature_hash(0, script, Amount::ZERO, signature.sighash_type)
+            .map_err(|e| format!("sighash error: {}", e))?;
+        secp.verify_ecdsa(&Message::from(sighash), &signature.signature, &pubkey.0).map_err(|_| mismatch())
+    } else if script.is_p2tr() {
+        let [signature] = items[..] else {
+            return Err("only taproot key-path proofs (a single signature) can be checked".to_string());
+        };
+        let signature = bitcoin::taproot::Signature::from_slice(signature).map_err(|e| format!("invalid signature: {}", e))?;
+        let output_key = XOnlyPublicKey::from_slice(&script.as_bytes()[2..]).map_err(|e| format!("invalid output key: {}", e))?;
+        let sighash = cache
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&[prevout]), signature.sighash_type)
+            .map_err(|e| format!("sighash error: {}", e))?;
+        secp.verify_schnorr(&signature.signature, &Message::from(sighash), &output_key).map_err(|_| mismatch())
+    } else {
+        let Some((witness_script, stack)) = items.split_last() else {
+            return Err("the proof's witness is empty".to_string());
+        };
+        let witness_script = Script::from_bytes(witness_script);
+        if ScriptBuf::new_p2wsh(&witness_script.wscript_hash()) != *script {
+            return Err("the witness script doesn't hash to the address".to_string());
+        }
+        let multisig = parse_multisig(witness_script).ok_or("only multisig witness scripts can be checked")?;
+        let [dummy, signatures @ ..] = stack else {
+            return Err("the proof's witness has no signatures".to_string());
+        };
+        if !dummy.is_empty() || signatures.len() != multisig.threshold {
+            return Err(format!("a {}-of-{} proof needs an empty item and {} signatures", multisig.threshold, multisig.pubkeys.len(), multisig.threshold));
+        }
+        // OP_CHECKMULTISIG: each signature must match a later key than the one before it.
+        let mut pubkeys = multisig.pubkeys.iter();
+        for signature in signatures {
+            let signature = ecdsa::Signature::from_slice(signature).map_err(|e| format!("invalid signature: {}", e))?;
+            let sighash = cache
+                .p2wsh_signature_hash(0, witness_script, Amount::ZERO, signature.sighash_type)
+                .map_err(|e| format!("sighash error: {}", e))?;
+            let digest = Message::from(sighash);
+            pubkeys
+                .find(|pubkey| {
+                    bitcoin::secp256k1::PublicKey::from_slice(pubkey)
+                        .is_ok_and(|pubkey| secp.verify_ecdsa(&digest, &signature.signature, &pubkey).is_ok())
+                })
+                .ok_or_else(mismatch)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,460 @@
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, sha512, Hash, HashEngine};
+use unicode_normalization::UnicodeNormalization;
+use crate::utils::bytes_to_hex;
+use crate::wallet::electrum::electrum_seed_type;
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::sanitize::{sanitize, InputKind};
+
+const ENGLISH: &str = include_str!("wordlists/english.txt");
+
+pub const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// The languages with an official BIP39 wordlist. Only English is bundled unless the
+/// `bip39-all-languages` feature is on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    Italian,
+    Portuguese,
+    Czech,
+    Japanese,
+    Korean,
+    ChineseSimplified,
+    ChineseTraditional,
+}
+
+pub const LANGUAGES: [Language; 10] = [
+    Language::English,
+    Language::Spanish,
+    Language::French,
+    Language::Italian,
+    Language::Portuguese,
+    Language::Czech,
+    Language::Japanese,
+    Language::Korean,
+    Language::ChineseSimplified,
+    Language::ChineseTraditional,
+];
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "english",
+            Language::Spanish => "spanish",
+            Language::French => "french",
+            Language::Italian => "italian",
+            Language::Portuguese => "portuguese",
+            Language::Czech => "czech",
+            Language::Japanese => "japanese",
+            Language::Korean => "korean",
+            Language::ChineseSimplified => "chinese_simplified",
+            Language::ChineseTraditional => "chinese_traditional",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Spanish",
+            Language::French => "French",
+            Language::Italian => "Italian",
+            Language::Portuguese => "Portuguese",
+            Language::Czech => "Czech",
+            Language::Japanese => "Japanese",
+            Language::Korean => "Korean",
+            Language::ChineseSimplified => "Chinese (Simplified)",
+            Language::ChineseTraditional => "Chinese (Traditional)",
+        }
+    }
+
+    /// Names as in the BIP39 repository's file names, or ISO 639-1 codes (`zh-hans` and
+    /// `zh-hant` for the two Chinese lists).
+    pub fn parse(name: &str) -> Result<Language, String> {
+        let normalized = name.trim().to_ascii_lowercase().replace('-', "_");
+        let code = match normalized.as_str() {
+            "en" => "english",
+            "es" => "spanish",
+            "fr" => "french",
+            "it" => "italian",
+            "pt" => "portuguese",
+            "cs" => "czech",
+            "ja" => "japanese",
+            "ko" => "korean",
+            "zh_hans" | "zh_cn" => "chinese_simplified",
+            "zh_hant" | "zh_tw" => "chinese_traditional",
+            other => other,
+        };
+        LANGUAGES.into_iter().find(|language| language.name() == code).ok_or_else(|| {
+            format!(
+                "Unknown language \"{}\" (expected one of {})",
+                name,
+                LANGUAGES.map(|language| language.name()).join(", ")
+            )
+        })
+    }
+
+    /// BIP39 writes Japanese phrases with an ideographic space (U+3000) between words.
+    pub fn separator(self) -> &'static str {
+        match self {
+            Language::Japanese => "\u{3000}",
+            _ => " ",
+        }
+    }
+}
+
+/// The official list for `language`, one word per line, if this build bundles it. The
+/// lists are stored in NFKD, the form BIP39 hashes.
+fn bundled_wordlist(language: Language) -> Option<&'static str> {
+    match language {
+        Language::English => Some(ENGLISH),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::Spanish => Some(include_str!("wordlists/spanish.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::French => Some(include_str!("wordlists/french.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::Italian => Some(include_str!("wordlists/italian.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::Portuguese => Some(include_str!("wordlists/portuguese.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::Czech => Some(include_str!("wordlists/czech.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::Japanese => Some(include_str!("wordlists/japanese.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::Korean => Some(include_str!("wordlists/korean.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::ChineseSimplified => Some(include_str!("wordlists/chinese_simplified.txt")),
+        #[cfg(feature = "bip39-all-languages")]
+        Language::ChineseTraditional => Some(include_str!("wordlists/chinese_traditional.txt")),
+        #[cfg(not(feature = "bip39-all-languages"))]
+        _ => None,
+    }
+}
+
+pub fn english_wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| ENGLISH.lines().collect())
+}
+
+pub fn wordlist(language: Language) -> Result<&'static [&'static str], String> {
+    static WORDS: [OnceLock<Vec<&'static str>>; LANGUAGES.len()] = [const { OnceLock::new() }; LANGUAGES.len()];
+    let text = bundled_wordlist(language).ok_or_else(|| {
+        format!(
+            "The {} wordlist isn't in this build: enable the bip39-all-languages feature",
+            language.label()
+        )
+    })?;
+    let slot = LANGUAGES.iter().position(|&candidate| candidate == language).expect("every language is listed");
+    Ok(WORDS[slot].get_or_init(|| text.lines().collect()))
+}
+
+/// The languages whose wordlists this build bundles, in `LANGUAGES` order.
+pub fn available_languages() -> Vec<Language> {
+    LANGUAGES.into_iter().filter(|&language| wordlist(language).is_ok()).collect()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MnemonicCheck {
+    pub valid: bool,
+    /// The phrase's language, or null when it's invalid or valid in several lists.
+    pub language: Option<String>,
+    pub ambiguous: bool,
+    /// Every checked language the phrase is valid in.
+    pub languages: Vec<String>,
+    #[serde(alias = "word_count")]
+    pub word_count: usize,
+    #[serde(alias = "checked_languages")]
+    pub checked_languages: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Checks a BIP39 phrase. Without a language, every available list is tried; a phrase
+/// valid in more than one is reported as ambiguous instead of picking one.
+#[wasm_bindgen]
+pub fn validate_mnemonic(phrase: &str, language: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("validate_mnemonic", phrase.len(), || {
+        let languages = requested_languages(language.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let (matches, error) = check_phrase(phrase, &languages);
+        let result = MnemonicCheck {
+            valid: !matches.is_empty(),
+            language: match matches.as_slice() {
+                [(language, _)] => Some(language.name().to_string()),
+                _ => None,
+            },
+            ambiguous: matches.len() > 1,
+            languages: matches.iter().map(|(language, _)| language.name().to_string()).collect(),
+            word_count: sanitize(phrase, InputKind::Mnemonic)
+                .map_or_else(|_| phrase.split_whitespace().count(), |phrase| phrase.split_whitespace().count()),
+            checked_languages: languages.iter().map(|language| language.name().to_string()).collect(),
+            error,
+        };
+
+        to_json(&result, compact)
+    })
+}
+
+/// The 64-byte BIP39 seed: PBKDF2-HMAC-SHA512 over the phrase, salted with `mnemonic`
+/// and the passphrase, 2048 rounds. Both are NFKD-normalized first, so accented words and
+/// passphrases give the same seed however the keyboard composed them. The phrase is checked first, in `language` or in
+/// whichever available list it belongs to.
+#[wasm_bindgen]
+pub fn mnemonic_to_seed(phrase: &str, passphrase: Option<String>, language: Option<String>) -> Result<String, JsValue> {
+    timed("mnemonic_to_seed", phrase.len(), || {
+        let languages = requested_languages(language.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let seed = phrase_seed(phrase, passphrase.as_deref().unwrap_or_default(), &languages).map_err(|e| JsValue::from_str(&e))?;
+        Ok(bytes_to_hex(&seed))
+    })
+}
+
+/// The BIP39 seed of a phrase in any available language.
+pub fn bip39_seed(phrase: &str, passphrase: &str) -> Result<Vec<u8>, String> {
+    phrase_seed(phrase, passphrase, &available_languages())
+}
+
+fn phrase_seed(phrase: &str, passphrase: &str, languages: &[Language]) -> Result<Vec<u8>, String> {
+    let (matches, error) = check_phrase(phrase, languages);
+    // Every match spells the phrase with the same words, so any of them gives the seed.
+    let (_, words) = matches.first().ok_or_else(|| error.unwrap_or_default())?;
+    let salt = format!("mnemonic{}", passphrase.nfkd());
+    Ok(pbkdf2_hmac::<sha512::Hash>(words.join(" ").as_bytes(), salt.as_bytes(), 2048, 64))
+}
+
+fn requested_languages(language: Option<&str>) -> Result<Vec<Language>, String> {
+    match language.map(str::trim) {
+        None | Some("") | Some("auto") => Ok(available_languages()),
+        Some(name) => {
+            let language = Language::parse(name)?;
+            wordlist(language)?;
+            Ok(vec![language])
+        }
+    }
+}
+
+/// The languages a phrase is valid in, with its words as spelled in each list, and
+/// otherwise the most useful reason it's invalid.
+pub fn check_phrase(phrase: &str, languages: &[Language]) -> (Vec<(Language, Vec<&'static str>)>, Option<String>) {
+    let phrase = match normalize_phrase(phrase) {
+        Ok(phrase) => phrase,
+        Err(error) => return (Vec::new(), Some(error)),
+    };
+    let mut matches = Vec::new();
+    let mut best_error: Option<(usize, String)> = None;
+    for &language in languages {
+        let words = wordlist(language).expect("available language");
+        let known = phrase.split_whitespace().filter(|word| find_word(words, word).is_some()).count();
+        let result = word_indexes_in(&phrase, words, language).and_then(|indexes| {
+            indexes_to_entropy(&indexes)?;
+            Ok(indexes.iter().map(|&index| words[index]).collect::<Vec<_>>())
+        });
+        match result {
+            Ok(spelled) => matches.push((language, spelled)),
+            Err(error) if best_error.as_ref().is_none_or(|(best, _)| known > *best) => best_error = Some((known, error)),
+            Err(_) => {}
+        }
+    }
+    if !matches.is_empty() {
+        return (matches, None);
+    }
+
+    let all_known = best_error.as_ref().is_some_and(|(known, _)| *known == phrase.split_whitespace().count());
+    let mut error = best_error.map(|(_, error)| error).unwrap_or_else(|| "No wordlist to check against".to_string());
+    if let Some(seed_type) = electrum_seed_type(&phrase) {
+        error = format!(
+            "This is an Electrum {} seed, not a BIP39 mnemonic; use the Electrum functions (validate_electrum_mnemonic) instead",
+            seed_type.name()
+        );
+    } else if !all_known && available_languages() == [Language::English] {
+        error.push_str(". This build only has the English wordlist; the others need the bip39-all-languages feature");
+    }
+    (Vec::new(), Some(error))
+}
+
+/// The sanitized phrase in NFKD, the form the wordlists are stored in. Precomposed accents
+/// (as most keyboards type `é`) and Japanese full-width kana then match the list.
+fn normalize_phrase(phrase: &str) -> Result<String, String> {
+    Ok(sanitize(phrase, InputKind::Mnemonic)?.nfkd().collect())
+}
+
+fn find_word(words: &[&str], word: &str) -> Option<usize> {
+    let word = word.to_lowercase();
+    words.iter().position(|&candidate| candidate == word)
+}
+
+/// Entropy bytes behind a BIP39 phrase of `word_count` words (16 for 12 words, 32 for 24).
+pub fn entropy_len_for_words(word_count: usize) -> Result<usize, String> {
+    if !WORD_COUNTS.contains(&word_count) {
+        return Err(format!("word_count must be 12, 15, 18, 21 or 24, got {}", word_count));
+    }
+    Ok(word_count * 4 / 3)
+}
+
+/// BIP39 encoding in English.
+pub fn entropy_to_mnemonic(entropy: &[u8]) -> Result<String, String> {
+    entropy_to_mnemonic_in(entropy, Language::English)
+}
+
+/// BIP39 encoding: entropy followed by the first ENT/32 bits of its SHA256, read as 11-bit word indexes.
+pub fn entropy_to_mnemonic_in(entropy: &[u8], language: Language) -> Result<String, String> {
+    if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+        return Err(format!("BIP39 entropy must be 16 to 32 bytes in steps of 4, got {}", entropy.len()));
+    }
+    let checksum = sha256::Hash::hash(entropy).to_byte_array();
+    let mut bits: Vec<bool> = entropy.iter().flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1)).collect();
+    bits.extend((0..entropy.len() / 4).map(|i| checksum[i / 8] >> (7 - i % 8) & 1 == 1));
+
+    let words = wordlist(language)?;
+    let phrase: Vec<&str> = bits
+        .chunks(11)
+        .map(|chunk| words[chunk.iter().fold(0usize, |index, &bit| index << 1 | bit as usize)])
+        .collect();
+    Ok(phrase.join(language.separator()))
+}
+
+/// The entropy behind a BIP39 phrase, or why the phrase isn't one (an unknown word, a
+/// wrong length or a bad checksum). Words are matched case-insensitively.
+pub fn mnemonic_to_entropy(phrase: &str) -> Result<Vec<u8>, String> {
+    indexes_to_entropy(&word_indexes(phrase)?)
+}
+
+/// Each word's position in the English wordlist, matched case-insensitively.
+pub fn word_indexes(phrase: &str) -> Result<Vec<usize>, String> {
+    word_indexes_in(phrase, english_wordlist(), Language::English)
+}
+
+/// Words may be separated by any whitespace, including Japanese ideographic spaces.
+fn word_indexes_in(phrase: &str, words: &[&str], language: Language) -> Result<Vec<usize>, String> {
+    normalize_phrase(phrase)?
+        .split_whitespace()
+        .enumerate()
+        .map(|(position, word)| {
+            find_word(words, word).ok_or_else(|| {
+                format!("Word {} (\"{}\") is not in the {} BIP39 wordlist", position + 1, word, language.label())
+            })
+        })
+        .collect()
+}
+
+/// The inverse of `entropy_to_mnemonic` on word indexes, checking the checksum.
+pub fn indexes_to_entropy(indexes: &[usize]) -> Result<Vec<u8>, String> {
+    let entropy_len = entropy_len_for_words(indexes.len())?;
+    let bits: Vec<bool> = indexes.iter().flat_map(|&index| (0..11).rev().map(move |i| index >> i & 1 == 1)).collect();
+
+    let entropy: Vec<u8> = bits[..entropy_len * 8]
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |byte, &bit| byte << 1 | bit as u8))
+        .collect();
+    let checksum = sha256::Hash::hash(&entropy).to_byte_array();
+    let checksum_ok = bits[entropy_len * 8..]
+        .iter()
+        .enumerate()
+        .all(|(i, &bit)| (checksum[i / 8] >> (7 - i % 8) & 1 == 1) == bit);
+    if !checksum_ok {
+        return Err("BIP39 checksum mismatch: a word is wrong or the words are out of order".to_string());
+    }
+    Ok(entropy)
+}
+
+/// One PBKDF2-HMAC block (64 bytes with SHA512, 32 with SHA256) cut to `len` bytes. BIP39
+/// and Electrum stretch phrases into seeds with it, and SLIP-39 uses it as its cipher's
+/// round function.
+pub fn pbkdf2_hmac<T: Hash>(password: &[u8], salt: &[u8], rounds: u32, len: usize) -> Vec<u8> {
+    let mut engine = HmacEngine::<T>::new(password);
+    engine.input(salt);
+    engine.input(&1u32.to_be_bytes());
+    let mut block = Hmac::<T>::from_engine(engine);
+    let mut output = block[..].to_vec();
+    for _ in 1..rounds {
+        let mut engine = HmacEngine::<T>::new(password);
+        engine.input(&block[..]);
+        block = Hmac::from_engine(engine);
+        output.iter_mut().zip(&block[..]).for_each(|(out, byte)| *out ^= byte);
+    }
+    output.truncate(len);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABANDON_ABOUT: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn trezor_vector() {
+        let seed = bip39_seed(ABANDON_ABOUT, "TREZOR").unwrap();
+        assert_eq!(
+            bytes_to_hex(&seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn passphrase_is_nfkd_normalized() {
+        let composed = bip39_seed(ABANDON_ABOUT, "p\u{e4}ssphrase").unwrap();
+        let decomposed = bip39_seed(ABANDON_ABOUT, "pa\u{308}ssphrase").unwrap();
+        assert_eq!(composed, decomposed);
+        assert_eq!(
+            bytes_to_hex(&composed),
+            "c893132d99bba689c8a393aea70684aa5a0e3ab985f4f5b4123743f446093aa9a423447fde5a4be3cd003b0c21154221074d0979072eff80bb1e1a6ae33bf475"
+        );
+    }
+
+    #[cfg(feature = "bip39-all-languages")]
+    #[test]
+    fn bundled_lists_are_official() {
+        for language in LANGUAGES {
+            let words = wordlist(language).unwrap();
+            assert_eq!(words.len(), 2048, "{}", language.name());
+            assert!(words.iter().all(|word| unicode_normalization::is_nfkd(word)), "{}", language.name());
+        }
+        assert_eq!(sha256::Hash::hash(include_bytes!("wordlists/spanish.txt")).to_string(), "46846a5a0139d1e3cb77293e521c2865f7bcdb82c44e8d0a06a2cd0ecba48c0b");
+    }
+
+    /// The first vector of bip32JP's test_JP_BIP39.json: ideographic spaces between the
+    /// words, and a passphrase NFKD rewrites (㍍ becomes メートル, ガ splits its dakuten).
+    #[cfg(feature = "bip39-all-languages")]
+    #[test]
+    fn japanese_vector() {
+        let phrase = "あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あおぞら";
+        assert_eq!(entropy_to_mnemonic_in(&[0; 16], Language::Japanese).unwrap().nfc().collect::<String>(), phrase);
+        let seed = phrase_seed(phrase, "㍍ガバヴァぱばぐゞちぢ十人十色", &[Language::Japanese]).unwrap();
+        assert_eq!(
+            bytes_to_hex(&seed),
+            "a262d6fb6122ecf45be09c50492b31f92e9beb7d9a845987a02cefda57a15f9c467a17872029a9e92299b5cbdf306e3a0ee620245cbd508959b6cb7ca637bd55"
+        );
+    }
+
+    /// Phrases typed with precomposed accents, as keyboards produce them, against the
+    /// NFKD lists.
+    #[cfg(feature = "bip39-all-languages")]
+    #[test]
+    fn accented_phrases_match_as_typed() {
+        let french = "implorer visage sonnette voyage v\u{e9}loce pourpre volaille tribunal implorer visage sonnette voyelle";
+        let (matches, error) = check_phrase(french, &available_languages());
+        assert_eq!(matches.iter().map(|(language, _)| language.name()).collect::<Vec<_>>(), ["french"], "{:?}", error);
+        assert_eq!(
+            bytes_to_hex(&bip39_seed(french, "caf\u{e9}").unwrap()),
+            "c6aba437eb58002240ab6c2ff405ee2b79395481e8150838a5d10deeda85c023c97a6541c5463f3897f10a4f542c5f6ba80924cc24bfec1d341d1ed241c84194"
+        );
+
+        let spanish = "lino admitir bolero abrir \u{e1}lbum dejar acelga aprender lino admitir bolero abogado";
+        assert_eq!(
+            bytes_to_hex(&bip39_seed(spanish, "").unwrap()),
+            "b7e5e0244095d677e73e5280ade614c900e7073826d1fbe26eaf39e942a6d53143db0dc817acc9151b9dcd088052f14d1e585725574ced5f490b92a60abab55f"
+        );
+    }
+
+    #[cfg(not(feature = "bip39-all-languages"))]
+    #[test]
+    fn other_languages_need_the_feature() {
+        assert!(available_languages() == [Language::English]);
+        assert!(wordlist(Language::Spanish).unwrap_err().contains("bip39-all-languages"));
+    }
+}
@@ -1,68 +1,291 @@
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
-use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::key::{CompressedPublicKey, UntweakedPublicKey};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::constants::CURVE_ORDER;
+use bitcoin::secp256k1::{Scalar, Secp256k1, SecretKey};
 use bitcoin::{PrivateKey, PublicKey as BtcPublicKey, Address, Network};
-use rand::Rng;
-use crate::utils::bytes_to_hex;
+#[cfg(feature = "wallet")]
+use crate::utils::rng::with_rng;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::to_json;
+use crate::utils::metrics::timed;
+use crate::utils::sanitize::{sanitize, InputKind};
+
+#[cfg(feature = "signing")]
+pub mod adaptor;
+#[cfg(feature = "wallet")]
+pub mod argon2;
+#[cfg(feature = "wallet")]
+pub mod electrum;
+#[cfg(feature = "wallet")]
+pub mod entropy;
+#[cfg(feature = "wallet")]
+pub mod hd;
+#[cfg(feature = "signing")]
+pub mod htlc;
+pub mod keys;
+#[cfg(feature = "wallet")]
+pub mod keystore;
+pub mod labels;
+#[cfg(feature = "signing")]
+pub mod message;
+#[cfg(feature = "wallet")]
+pub mod mnemonic;
+pub mod musig;
+pub mod path;
+#[cfg(all(feature = "wallet", feature = "signing"))]
+pub mod recovery;
+#[cfg(feature = "signing")]
+pub mod reserves;
+#[cfg(feature = "wallet")]
+pub mod restore;
+#[cfg(feature = "signing")]
+pub mod s2c;
+#[cfg(feature = "wallet")]
+pub mod seedqr;
+#[cfg(feature = "wallet")]
+pub mod slip39;
+pub mod taproot;
+#[cfg(feature = "wallet")]
+pub mod vanity;
+#[cfg(feature = "wallet")]
+pub mod watch;
+pub mod xkey;
+
+pub use keys::{export_wif, convert_pubkey_compression, address_from_pubkey};
+pub use labels::{export_labels_bip329, import_labels_bip329};
+pub use musig::musig_aggregate_keys;
+pub use path::parse_derivation_path;
+pub use taproot::{taproot_tweak_pubkey, taproot_tweak_privkey, xonly_from_pubkey, commit_to_data, verify_commitment, commit_privkey_tweak, verify_taproot_address};
+pub use xkey::{inspect_xkey, xprv_to_xpub, derive_child, derive_children};
+#[cfg(feature = "signing")]
+pub use adaptor::{adaptor_sign, adaptor_verify, adaptor_adapt, adaptor_extract_secret};
+#[cfg(feature = "signing")]
+pub use htlc::{build_htlc, claim_htlc, refund_htlc};
+#[cfg(feature = "signing")]
+pub use message::{prove_address, verify_address_proof};
+#[cfg(all(feature = "wallet", feature = "signing"))]
+pub use recovery::{create_recovery_wallet, spend_recovery_primary, spend_recovery_delayed};
+#[cfg(feature = "signing")]
+pub use reserves::{create_proof_of_reserves, verify_proof_of_reserves};
+#[cfg(feature = "signing")]
+pub use s2c::{host_data_commitment, commit_signing_nonce, sign_with_commitment, verify_commitment_in_signature};
+#[cfg(feature = "wallet")]
+pub use keystore::{encrypt_secret, decrypt_secret, calibrate_kdf};
+#[cfg(feature = "wallet")]
+pub use entropy::{private_key_from_dice, mnemonic_from_dice, from_coin_flips};
+#[cfg(feature = "wallet")]
+pub use mnemonic::{validate_mnemonic, mnemonic_to_seed};
+#[cfg(feature = "wallet")]
+pub use electrum::{validate_electrum_mnemonic, electrum_mnemonic_to_seed, electrum_derive_addresses};
+#[cfg(feature = "wallet")]
+pub use restore::diagnose_restore;
+#[cfg(feature = "wallet")]
+pub use seedqr::{mnemonic_to_seedqr, seedqr_to_mnemonic};
+#[cfg(feature = "wallet")]
+pub use slip39::{split_seed_slip39, combine_slip39_shares};
+#[cfg(feature = "wallet")]
+pub use hd::Wallet;
+#[cfg(feature = "wallet")]
+pub use watch::WatchWallet;
+#[cfg(feature = "wallet")]
+pub use vanity::VanitySearch;
+
+/// One address with the script it locks to, so watch lists don't need a second call.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletAddress {
+    pub address: String,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    /// p2pkh, p2wpkh, p2sh-p2wpkh or p2tr.
+    pub kind: String,
+}
 
 #[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WalletAddresses {
+    pub legacy: WalletAddress,
+    pub segwit: Option<WalletAddress>,
+    #[serde(alias = "nested_segwit")]
+    pub nested_segwit: Option<WalletAddress>,
+    pub taproot: WalletAddress,
+}
+
+/// The address strings alone, as `derive_addresses_from_key` returned them before
+/// each entry carried its script.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatWalletAddresses {
     pub legacy: String,
-    pub segwit: String,
+    pub segwit: Option<String>,
+    #[serde(alias = "nested_segwit")]
+    pub nested_segwit: Option<String>,
     pub taproot: String,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct KeyPair {
+#[serde(rename_all = "camelCase")]
+pub struct KeyPair<A = WalletAddresses> {
+    #[serde(alias = "private_key")]
     pub private_key: String,
+    #[serde(alias = "public_key")]
     pub public_key: String,
-    pub addresses: WalletAddresses,
+    pub compressed: bool,
+    pub wif: String,
+    pub addresses: A,
 }
 
+#[cfg(feature = "wallet")]
 #[wasm_bindgen]
 pub fn generate_private_key() -> String {
-    let mut rng = rand::thread_rng();
     let mut bytes = [0u8; 32];
-    rng.fill(&mut bytes);
+    with_rng(|rng| rng.fill_bytes(&mut bytes));
     bytes_to_hex(&bytes)
 }
 
+/// The key's public key and its P2PKH, P2WPKH, P2SH-P2WPKH and P2TR addresses, each
+/// with its scriptPubKey and kind. The segwit entries are null for an uncompressed key.
 #[wasm_bindgen]
-pub fn derive_addresses_from_key(private_key_hex: &str) -> Result<String, JsValue> {
-    use crate::utils::hex_to_bytes;
-    
-    let secp = Secp256k1::new();
-    let private_key_bytes: Vec<u8> = hex_to_bytes(private_key_hex)
-        .map_err(|e| JsValue::from_str(&format!("Invalid private key hex: {}", e)))?;
-
-    if private_key_bytes.len() != 32 {
-        return Err(JsValue::from_str("Private key must be 32 bytes"));
-    }
+pub fn derive_addresses_from_key(private_key_hex: &str, compressed: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("derive_addresses_from_key", private_key_hex.len(), || {
+        to_json(&key_pair(private_key_hex, compressed)?, compact)
+    })
+}
 
-    let mut key_array = [0u8; 32];
-    key_array.copy_from_slice(&private_key_bytes);
+/// `derive_addresses_from_key` with bare address strings, for callers written against
+/// the older shape.
+#[wasm_bindgen]
+pub fn derive_addresses_from_key_compat(private_key_hex: &str, compressed: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let keypair = key_pair(private_key_hex, compressed)?;
+    let addresses = &keypair.addresses;
+    let compat = KeyPair {
+        addresses: CompatWalletAddresses {
+            legacy: addresses.legacy.address.clone(),
+            segwit: addresses.segwit.as_ref().map(|entry| entry.address.clone()),
+            nested_segwit: addresses.nested_segwit.as_ref().map(|entry| entry.address.clone()),
+            taproot: addresses.taproot.address.clone(),
+        },
+        private_key: keypair.private_key,
+        public_key: keypair.public_key,
+        compressed: keypair.compressed,
+        wif: keypair.wif,
+    };
 
-    let secret_key = SecretKey::from_slice(&key_array)
-        .map_err(|e| JsValue::from_str(&format!("Invalid secret key: {}", e)))?;
+    to_json(&compat, compact)
+}
 
-    let private_key = PrivateKey::new(secret_key, Network::Testnet);
+fn key_pair(private_key_hex: &str, compressed: Option<bool>) -> Result<KeyPair, JsValue> {
+    let secp = Secp256k1::new();
+    let private_key = parse_private_key(private_key_hex, compressed, Network::Testnet)?;
     let pubkey = BtcPublicKey::from_private_key(&secp, &private_key);
 
-    let legacy_address = Address::p2pkh(&pubkey, Network::Testnet);
-    let legacy_str = legacy_address.to_string();
+    let compressed_key = pubkey.compressed.then_some(CompressedPublicKey(pubkey.inner));
+    let segwit = compressed_key.map(|key| wallet_address(Address::p2wpkh(&key, Network::Testnet), "p2wpkh"));
+    let nested_segwit = compressed_key.map(|key| wallet_address(Address::p2shwpkh(&key, Network::Testnet), "p2sh-p2wpkh"));
+
+    let internal_key: UntweakedPublicKey = pubkey.inner.x_only_public_key().0;
 
     let addresses = WalletAddresses {
-        legacy: legacy_str.clone(),
-        segwit: legacy_str.clone(),
-        taproot: legacy_str,
+        legacy: wallet_address(Address::p2pkh(pubkey, Network::Testnet), "p2pkh"),
+        segwit,
+        nested_segwit,
+        taproot: wallet_address(Address::p2tr(&secp, internal_key, None, Network::Testnet), "p2tr"),
     };
 
-    let keypair = KeyPair {
-        private_key: private_key_hex.to_string(),
+    Ok(KeyPair {
+        private_key: bytes_to_hex(&private_key.inner.secret_bytes()),
         public_key: bytes_to_hex(&pubkey.to_bytes()),
+        compressed: pubkey.compressed,
+        wif: private_key.to_wif(),
         addresses,
-    };
+    })
+}
+
+fn wallet_address(address: Address, kind: &str) -> WalletAddress {
+    WalletAddress {
+        script_pubkey: bytes_to_hex(address.script_pubkey().as_bytes()),
+        address: address.to_string(),
+        kind: kind.to_string(),
+    }
+}
+
+pub fn parse_secret_key(private_key_hex: &str) -> Result<SecretKey, JsValue> {
+    secret_key_from_hex(private_key_hex).map_err(|e| JsValue::from_str(&e))
+}
+
+pub fn secret_key_from_hex(private_key_hex: &str) -> Result<SecretKey, String> {
+    let private_key_bytes: Vec<u8> =
+        hex_to_bytes(private_key_hex, HexMode::Lenient).map_err(|e| format!("Invalid private key hex: {}", e))?;
+
+    let key_array: [u8; 32] = private_key_bytes.as_slice().try_into().map_err(|_| "Private key must be 32 bytes".to_string())?;
+
+    SecretKey::from_slice(&key_array).map_err(|e| format!("Invalid secret key: {}", e))
+}
+
+pub fn parse_private_key(key: &str, compressed: Option<bool>, network: Network) -> Result<PrivateKey, JsValue> {
+    let key = sanitize(key, InputKind::Key).map_err(|e| JsValue::from_str(&e))?;
+    let key = key.trim();
+    let looks_like_hex = key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit());
+
+    if looks_like_hex {
+        let secret_key = parse_secret_key(key)?;
+        return Ok(match compressed.unwrap_or(true) {
+            true => PrivateKey::new(secret_key, network),
+            false => PrivateKey::new_uncompressed(secret_key, network),
+        });
+    }
+
+    let private_key = PrivateKey::from_wif(key)
+        .map_err(|e| JsValue::from_str(&format!("Invalid private key (expected 64 hex characters or WIF): {}", e)))?;
 
-    serde_json::to_string(&keypair)
-        .map_err(|e| JsValue::from_str(&format!("Failed to serialize: {}", e)))
+    if let Some(requested) = compressed {
+        if requested != private_key.compressed {
+            return Err(JsValue::from_str(&format!(
+                "WIF encodes a {} key but compressed={} was requested",
+                if private_key.compressed { "compressed" } else { "uncompressed" },
+                requested
+            )));
+        }
+    }
+
+    Ok(private_key)
+}
+
+/// A SHA256 engine primed with a BIP340 tag, for tagged hashes over several inputs.
+pub fn tagged_engine(tag: &str) -> sha256::HashEngine {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes()).to_byte_array();
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag_hash);
+    engine.input(&tag_hash);
+    engine
+}
+
+/// Any 32 bytes mod n. They are below 2n, so one subtraction reduces them.
+pub fn reduce_mod_order(bytes: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(bytes).unwrap_or_else(|_| {
+        let mut reduced = [0u8; 32];
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let diff = bytes[i] as i16 - CURVE_ORDER[i] as i16 - borrow;
+            reduced[i] = diff.rem_euclid(256) as u8;
+            borrow = (diff < 0) as i16;
+        }
+        Scalar::from_be_bytes(reduced).expect("reduced below the curve order")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_key_length_is_checked() {
+        assert!(secret_key_from_hex(&"11".repeat(32)).is_ok());
+        assert_eq!(secret_key_from_hex(&"11".repeat(31)).unwrap_err(), "Private key must be 32 bytes");
+        assert_eq!(secret_key_from_hex(&"11".repeat(33)).unwrap_err(), "Private key must be 32 bytes");
+        assert!(secret_key_from_hex(&"00".repeat(32)).unwrap_err().starts_with("Invalid secret key"));
+        assert!(secret_key_from_hex("zz").unwrap_err().starts_with("Invalid private key hex"));
+    }
 }
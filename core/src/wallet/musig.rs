@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, Verification};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::utils::json::{parse_json_list, to_json};
+use crate::wallet::{reduce_mod_order, tagged_engine};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusigAggregate {
+    /// Q as a compressed point.
+    #[serde(alias = "aggregate_key")]
+    pub aggregate_key: String,
+    /// Q's x coordinate, the internal key of a `tr(musig(...))` output.
+    #[serde(alias = "xonly_key")]
+    pub xonly_key: String,
+    /// The participant keys in the order they were aggregated.
+    pub keys: Vec<String>,
+    pub sorted: bool,
+}
+
+/// BIP327 KeyAgg over `pubkeys_json`, an array of compressed public keys. With `sort`
+/// (the default, as `musig()` descriptors do) the keys are put through KeySort first;
+/// without it their order is part of the result.
+#[wasm_bindgen]
+pub fn musig_aggregate_keys(pubkeys_json: &str, sort: Option<bool>, compact: Option<bool>) -> Result<String, JsValue> {
+    let entries: Vec<String> = parse_json_list(pubkeys_json, "pubkeys").map_err(|e| JsValue::from_str(&e))?;
+    let mut keys = parse_participant_keys(&entries)?;
+    let sorted = sort.unwrap_or(true);
+    if sorted {
+        key_sort(&mut keys);
+    }
+    let aggregate = key_agg(&Secp256k1::verification_only(), &keys).map_err(|e| JsValue::from_str(&e))?;
+
+    let result = MusigAggregate {
+        aggregate_key: bytes_to_hex(&aggregate.serialize()),
+        xonly_key: bytes_to_hex(&aggregate.x_only_public_key().0.serialize()),
+        keys: keys.iter().map(|key| bytes_to_hex(&key.serialize())).collect(),
+        sorted,
+    };
+    to_json(&result, compact)
+}
+
+/// Parses MuSig2 participant keys. BIP327 hashes the 33-byte encoding, so x-only and
+/// uncompressed keys are refused rather than guessed at.
+pub fn parse_participant_keys(entries: &[String]) -> Result<Vec<PublicKey>, JsValue> {
+    if entries.is_empty() {
+        return Err(JsValue::from_str("MuSig2 needs at least one participant key"));
+    }
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let bytes = hex_to_bytes(entry, HexMode::Lenient).map_err(|e| format!("pubkeys[{}]: Invalid public key hex: {}", index, e))?;
+            if bytes.len() != 33 {
+                return Err(format!("pubkeys[{}]: MuSig2 keys must be 33-byte compressed keys, got {} bytes", index, bytes.len()));
+            }
+            PublicKey::from_slice(&bytes).map_err(|e| format!("pubkeys[{}]: Invalid public key: {}", index, e))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(|e| JsValue::from_str(&e))
+}
+
+/// BIP327 KeySort: lexicographic order of the compressed encodings.
+pub fn key_sort(keys: &mut [PublicKey]) {
+    keys.sort_by_key(PublicKey::serialize);
+}
+
+/// BIP327 KeyAgg: Q = Σ aᵢ·Pᵢ, where aᵢ = H_agg(L ‖ Pᵢ) with L the hash of every key in
+/// order, except that the first key differing from the first one gets aᵢ = 1.
+pub fn key_agg<C: Verification>(secp: &Secp256k1<C>, keys: &[PublicKey]) -> Result<PublicKey, String> {
+    let mut list = tagged_engine("KeyAgg list");
+    for key in keys {
+        list.input(&key.serialize());
+    }
+    let list_hash = sha256::Hash::from_engine(list).to_byte_array();
+    let second = keys.iter().find(|key| **key != keys[0]);
+
+    let mut terms = Vec::with_capacity(keys.len());
+    for key in keys {
+        if Some(key) == second {
+            terms.push(*key);
+            continue;
+        }
+        let mut engine = tagged_engine("KeyAgg coefficient");
+        engine.input(&list_hash);
+        engine.input(&key.serialize());
+        let coefficient = reduce_mod_order(sha256::Hash::from_engine(engine).to_byte_array());
+        terms.push(key.mul_tweak(secp, &coefficient).map_err(|e| format!("KeyAgg failed: {}", e))?);
+    }
+    let refs: Vec<&PublicKey> = terms.iter().collect();
+    PublicKey::combine_keys(&refs).map_err(|_| "KeyAgg failed: the keys sum to the point at infinity".to_string())
+}
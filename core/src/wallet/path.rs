@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{ChildNumber, DerivationPath};
+use crate::utils::json::to_json;
+use crate::utils::sanitize::{sanitize, InputKind};
+
+const HARDENED_LIMIT: u32 = 1 << 31;
+
+#[derive(Serialize, Deserialize)]
+pub struct PathStep {
+    pub index: u32,
+    pub hardened: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ParsedPath {
+    pub path: String,
+    pub steps: Vec<PathStep>,
+    pub depth: usize,
+    /// `unhardened` for a trailing `/*`, `hardened` for `/*'` or `/*h`.
+    pub wildcard: Option<String>,
+}
+
+/// A path as written by hand or by another wallet, checked step by step.
+pub struct Path {
+    pub path: DerivationPath,
+    pub wildcard: Option<bool>,
+}
+
+/// Parses `m/84'/0'/0'`, `84h/0h/0h`, `m/0/*` and the like into the canonical
+/// `m/84'/0'/0'` form and its steps.
+#[wasm_bindgen]
+pub fn parse_derivation_path(path: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let parsed = parse_path(path, true).map_err(|e| JsValue::from_str(&e))?;
+    let steps: Vec<PathStep> = parsed
+        .path
+        .into_iter()
+        .map(|child| match *child {
+            ChildNumber::Normal { index } => PathStep { index, hardened: false },
+            ChildNumber::Hardened { index } => PathStep { index, hardened: true },
+        })
+        .collect();
+    let result = ParsedPath {
+        path: format_path(&parsed.path, parsed.wildcard),
+        depth: steps.len(),
+        steps,
+        wildcard: parsed.wildcard.map(|hardened| if hardened { "hardened" } else { "unhardened" }.to_string()),
+    };
+
+    to_json(&result, compact)
+}
+
+/// The one path parser every path-accepting function goes through. The leading `m/` is
+/// optional, hardened steps may end in `'`, `h` or `H`, and a final `*` is only accepted
+/// when `allow_wildcard` is set. Errors name the offending step by its 1-based position.
+pub fn parse_path(path: &str, allow_wildcard: bool) -> Result<Path, String> {
+    let path = sanitize(path, InputKind::Path)?;
+    let trimmed = path.trim();
+    let body = match trimmed {
+        "" | "m" | "M" => "",
+        _ => trimmed.strip_prefix("m/").or_else(|| trimmed.strip_prefix("M/")).unwrap_or(trimmed),
+    };
+
+    let mut children = Vec::new();
+    let mut wildcard = None;
+    let components: Vec<&str> = if body.is_empty() { Vec::new() } else { body.split('/').collect() };
+    for (position, component) in components.iter().enumerate() {
+        let step = position + 1;
+        let (digits, hardened) = match component.strip_suffix(['\'', 'h', 'H']) {
+            Some(digits) => (digits, true),
+            None => (*component, false),
+        };
+        if wildcard.is_some() {
+            return Err(format!("Invalid derivation path \"{}\": the wildcard must be the last step", trimmed));
+        }
+        if digits == "*" {
+            if !allow_wildcard {
+                return Err(format!("Invalid derivation path \"{}\": step {} is a wildcard, which isn't allowed here", trimmed, step));
+            }
+            wildcard = Some(hardened);
+            continue;
+        }
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!(
+                "Invalid derivation path \"{}\": step {} (\"{}\") is not an index",
+                trimmed, step, component
+            ));
+        }
+        let index: u32 = digits.parse().ok().filter(|&index| index < HARDENED_LIMIT).ok_or_else(|| {
+            let hint = if hardened { "" } else { "; mark hardened steps with ' or h instead of adding 2^31" };
+            format!(
+                "Invalid derivation path \"{}\": step {} index {} is out of range, indexes must be below 2^31 (2147483648){}",
+                trimmed, step, digits, hint
+            )
+        })?;
+        children.push(if hardened {
+            ChildNumber::Hardened { index }
+        } else {
+            ChildNumber::Normal { index }
+        });
+    }
+
+    Ok(Path { path: DerivationPath::from(children), wildcard })
+}
+
+/// Canonical form: `m`, then each step, with `'` for hardened ones.
+pub fn format_path(path: &DerivationPath, wildcard: Option<bool>) -> String {
+    let mut text = "m".to_string();
+    for child in path {
+        text.push('/');
+        text.push_str(&child.to_string());
+    }
+    match wildcard {
+        Some(true) => text.push_str("/*'"),
+        Some(false) => text.push_str("/*"),
+        None => {}
+    }
+    text
+}
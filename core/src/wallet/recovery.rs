@@ -0,0 +1,418 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::key::{Keypair, TapTweak};
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CSV, OP_ENDIF, OP_IFDUP, OP_NOTIF};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{All, Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{transaction, CompressedPublicKey, Network, ScriptBuf, Sequence, Transaction, TxOut, Witness};
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use crate::transaction::finalize::{finalize, BuildChecks, Funding};
+use crate::transaction::{check_input_index, decode_tx_hex, parse_prevouts};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::network_or_default;
+use crate::wallet::keys::{parse_public_key, require_compressed};
+use crate::wallet::parse_private_key;
+use crate::utils::json::to_json;
+
+/// BIP68 height-based relative locks are 16 bits.
+const MAX_DELAY_BLOCKS: u32 = 0xffff;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryWallet {
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    #[serde(alias = "primary_key")]
+    pub primary_key: String,
+    #[serde(alias = "recovery_key")]
+    pub recovery_key: String,
+    #[serde(alias = "delay_blocks")]
+    pub delay_blocks: u32,
+    pub network: String,
+    pub descriptor: String,
+    pub address: String,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    #[serde(default, alias = "witness_script")]
+    pub witness_script: Option<String>,
+    #[serde(default, alias = "recovery_leaf")]
+    pub recovery_leaf: Option<String>,
+    #[serde(default, alias = "control_block")]
+    pub control_block: Option<String>,
+}
+
+/// The spending conditions, rebuilt from the keys and delay rather than trusted from the JSON.
+struct RecoveryScripts {
+    primary: CompressedPublicKey,
+    recovery: CompressedPublicKey,
+    delay: u32,
+    kind: RecoveryKind,
+}
+
+enum RecoveryKind {
+    Taproot { leaf: ScriptBuf, spend_info: TaprootSpendInfo },
+    Wsh { witness_script: ScriptBuf },
+}
+
+/// "Primary now, or recovery after `delay_blocks`". With `tr` (the default), the primary key
+/// is the key path and the recovery key a single CSV leaf. With `wsh`, both paths are in
+/// one `or_d` script.
+#[wasm_bindgen]
+pub fn create_recovery_wallet(
+    primary_key: &str,
+    recovery_key: &str,
+    delay_blocks: u32,
+    network: Option<String>,
+    script_type: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let primary = require_compressed(&parse_public_key(primary_key)?, "A recovery wallet")?;
+    let recovery = require_compressed(&parse_public_key(recovery_key)?, "A recovery wallet")?;
+    let script_type = script_type.as_deref().unwrap_or("tr").trim().to_ascii_lowercase();
+    let scripts = build_scripts(&secp, primary, recovery, delay_blocks, &script_type).map_err(|e| JsValue::from_str(&e))?;
+
+    let script_pubkey = scripts.script_pubkey(&secp);
+    let descriptor = scripts.descriptor(&script_pubkey).map_err(|e| JsValue::from_str(&e))?;
+    let address = network.address(&script_pubkey).ok_or_else(|| JsValue::from_str("Cannot encode address"))?;
+
+    let mut wallet = RecoveryWallet {
+        script_type: scripts.kind_name().to_string(),
+        primary_key: primary.to_string(),
+        recovery_key: recovery.to_string(),
+        delay_blocks,
+        network: network.name.clone(),
+        descriptor,
+        address,
+        script_pubkey: bytes_to_hex(script_pubkey.as_bytes()),
+        witness_script: None,
+        recovery_leaf: None,
+        control_block: None,
+    };
+    match &scripts.kind {
+        RecoveryKind::Taproot { leaf, spend_info } => {
+            wallet.recovery_leaf = Some(bytes_to_hex(leaf.as_bytes()));
+            wallet.control_block = spend_info
+                .control_block(&(leaf.clone(), LeafVersion::TapScript))
+                .map(|control_block| bytes_to_hex(&control_block.serialize()));
+        }
+        RecoveryKind::Wsh { witness_script } => wallet.witness_script = Some(bytes_to_hex(witness_script.as_bytes())),
+    }
+
+    to_json(&wallet, compact)
+}
+
+/// Signs input `input_index` through the primary path: a taproot key-path signature, or
+/// the `pk(primary)` branch of the wsh script. No timelock applies.
+#[wasm_bindgen]
+pub fn spend_recovery_primary(
+    wallet_json: &str,
+    tx_hex: &str,
+    input_index: usize,
+    prevouts_json: &str,
+    private_key: &str,
+    checks_json: Option<String>,
+) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let (scripts, mut tx, prevouts) = load_spend(&secp, wallet_json, tx_hex, input_index, prevouts_json)?;
+    let private_key = parse_private_key(private_key, None, Network::Testnet)?;
+    if CompressedPublicKey::from_private_key(&secp, &private_key).ok() != Some(scripts.primary) {
+        return Err(JsValue::from_str("Private key does not match the wallet's primary key"));
+    }
+
+    let mut cache = SighashCache::new(&tx);
+    let witness = match &scripts.kind {
+        RecoveryKind::Taproot { spend_info, .. } => {
+            let sighash = cache
+                .taproot_key_spend_signature_hash(input_index, &Prevouts::All(&prevouts), TapSighashType::Default)
+                .map_err(|e| JsValue::from_str(&format!("Failed to compute sighash: {}", e)))?;
+            let tweaked = Keypair::from_secret_key(&secp, &private_key.inner).tap_tweak(&secp, spend_info.merkle_root());
+            let signature = bitcoin::taproot::Signature {
+                signature: secp.sign_schnorr_no_aux_rand(&Message::from(sighash), &tweaked.to_keypair()),
+                sighash_type: TapSighashType::Default,
+            };
+            Witness::p2tr_key_spend(&signature)
+        }
+        RecoveryKind::Wsh { witness_script } => {
+            let signature = wsh_signature(&secp, &mut cache, input_index, witness_script, &prevouts[input_index], &private_key)?;
+            Witness::from_slice(&[signature, witness_script.to_bytes()])
+        }
+    };
+
+    tx.input[input_index].witness = witness;
+    Ok(bytes_to_hex(&finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?))
+}
+
+/// Signs input `input_index` through the recovery path. It sets the input's sequence to the
+/// delay and the transaction version to 2 (BIP68 needs both) before signing. Nodes
+/// reject the spend until the coin has `delay_blocks` confirmations.
+#[wasm_bindgen]
+pub fn spend_recovery_delayed(
+    wallet_json: &str,
+    tx_hex: &str,
+    input_index: usize,
+    prevouts_json: &str,
+    private_key: &str,
+    checks_json: Option<String>,
+) -> Result<String, JsValue> {
+    let checks = BuildChecks::parse(checks_json.as_deref(), None).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::new();
+    let (scripts, mut tx, prevouts) = load_spend(&secp, wallet_json, tx_hex, input_index, prevouts_json)?;
+    let private_key = parse_private_key(private_key, None, Network::Testnet)?;
+    if CompressedPublicKey::from_private_key(&secp, &private_key).ok() != Some(scripts.recovery) {
+        return Err(JsValue::from_str("Private key does not match the wallet's recovery key"));
+    }
+
+    // Both fields are committed to by every input's signature, so they must be settled first.
+    let sequence = Sequence::from_height(scripts.delay as u16);
+    let needs_change = tx.input[input_index].sequence != sequence || tx.version < transaction::Version::TWO;
+    let other_signed = tx
+        .input
+        .iter()
+        .enumerate()
+        .any(|(index, input)| index != input_index && (!input.witness.is_empty() || !input.script_sig.is_empty()));
+    if needs_change && other_signed {
+        return Err(JsValue::from_str(&format!(
+            "Other inputs are already signed; set input {} sequence to {} and version 2 before signing them",
+            input_index,
+            sequence.to_consensus_u32()
+        )));
+    }
+    tx.input[input_index].sequence = sequence;
+    if tx.version < transaction::Version::TWO {
+        tx.version = transaction::Version::TWO;
+    }
+
+    let mut cache = SighashCache::new(&tx);
+    let witness = match &scripts.kind {
+        RecoveryKind::Taproot { leaf, spend_info } => {
+            let leaf_hash = TapLeafHash::from_script(leaf, LeafVersion::TapScript);
+            let sighash = cache
+                .taproot_script_spend_signature_hash(input_index, &Prevouts::All(&prevouts), leaf_hash, TapSighashType::Default)
+                .map_err(|e| JsValue::from_str(&format!("Failed to compute sighash: {}", e)))?;
+            let keypair = Keypair::from_secret_key(&secp, &private_key.inner);
+            let signature = bitcoin::taproot::Signature {
+                signature: secp.sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair),
+                sighash_type: TapSighashType::Default,
+            };
+            let control_block = spend_info
+                .control_block(&(leaf.clone(), LeafVersion::TapScript))
+                .ok_or_else(|| JsValue::from_str("Recovery leaf missing from the taproot tree"))?;
+            Witness::from_slice(&[signature.to_vec(), leaf.to_bytes(), control_block.serialize()])
+        }
+        RecoveryKind::Wsh { witness_script } => {
+            let signature = wsh_signature(&secp, &mut cache, input_index, witness_script, &prevouts[input_index], &private_key)?;
+            // The empty element fails pk(primary), which sends or_d into the recovery branch.
+            Witness::from_slice(&[signature, Vec::new(), witness_script.to_bytes()])
+        }
+    };
+
+    tx.input[input_index].witness = witness;
+    Ok(bytes_to_hex(&finalize(&tx, Funding::Signed(&prevouts), &checks).map_err(|e| JsValue::from_str(&e))?))
+}
+
+fn build_scripts(
+    secp: &Secp256k1<All>,
+    primary: CompressedPublicKey,
+    recovery: CompressedPublicKey,
+    delay: u32,
+    script_type: &str,
+) -> Result<RecoveryScripts, String> {
+    if delay == 0 || delay > MAX_DELAY_BLOCKS {
+        return Err(format!("delay_blocks must be between 1 and {}, got {}", MAX_DELAY_BLOCKS, delay));
+    }
+    if primary == recovery {
+        return Err("Primary and recovery keys must differ".to_string());
+    }
+
+    let kind = match script_type {
+        "tr" | "taproot" | "p2tr" => {
+            let leaf = Builder::new()
+                .push_x_only_key(&recovery.0.x_only_public_key().0)
+                .push_opcode(OP_CHECKSIGVERIFY)
+                .push_int(delay as i64)
+                .push_opcode(OP_CSV)
+                .into_script();
+            let spend_info = TaprootBuilder::new()
+                .add_leaf(0, leaf.clone())
+                .map_err(|e| format!("Cannot build taproot tree: {}", e))?
+                .finalize(secp, primary.0.x_only_public_key().0)
+                .map_err(|_| "Cannot build taproot tree".to_string())?;
+            RecoveryKind::Taproot { leaf, spend_info }
+        }
+        "wsh" | "p2wsh" => {
+            let witness_script = Builder::new()
+                .push_key(&primary.into())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_IFDUP)
+                .push_opcode(OP_NOTIF)
+                .push_key(&recovery.into())
+                .push_opcode(OP_CHECKSIGVERIFY)
+                .push_int(delay as i64)
+                .push_opcode(OP_CSV)
+                .push_opcode(OP_ENDIF)
+                .into_script();
+            RecoveryKind::Wsh { witness_script }
+        }
+        other => return Err(format!("Unknown script type \"{}\" (expected tr or wsh)", other)),
+    };
+    Ok(RecoveryScripts { primary, recovery, delay, kind })
+}
+
+impl RecoveryScripts {
+    fn kind_name(&self) -> &'static str {
+        match self.kind {
+            RecoveryKind::Taproot { .. } => "tr",
+            RecoveryKind::Wsh { .. } => "wsh",
+        }
+    }
+
+    fn script_pubkey(&self, secp: &Secp256k1<All>) -> ScriptBuf {
+        match &self.kind {
+            RecoveryKind::Taproot { spend_info, .. } => {
+                ScriptBuf::new_p2tr(secp, spend_info.internal_key(), spend_info.merkle_root())
+            }
+            RecoveryKind::Wsh { witness_script } => ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+        }
+    }
+
+    /// The miniscript descriptor for the same conditions, checked against our own script so an
+    /// imported watch-only wallet sees exactly this address.
+    fn descriptor(&self, script_pubkey: &ScriptBuf) -> Result<String, String> {
+        let text = match self.kind {
+            RecoveryKind::Taproot { .. } => format!(
+                "tr({},and_v(v:pk({}),older({})))",
+                self.primary.0.x_only_public_key().0,
+                self.recovery.0.x_only_public_key().0,
+                self.delay
+            ),
+            RecoveryKind::Wsh { .. } => format!(
+                "wsh(or_d(pk({}),and_v(v:pk({}),older({}))))",
+                self.primary, self.recovery, self.delay
+            ),
+        };
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&text).map_err(|e| format!("Invalid descriptor: {}", e))?;
+        let derived = descriptor
+            .at_derivation_index(0)
+            .map_err(|e| format!("Invalid descriptor: {}", e))?;
+        if derived.script_pubkey() != *script_pubkey {
+            return Err("Descriptor does not reproduce the wallet script".to_string());
+        }
+        Ok(descriptor.to_string())
+    }
+}
+
+fn load_spend(
+    secp: &Secp256k1<All>,
+    wallet_json: &str,
+    tx_hex: &str,
+    input_index: usize,
+    prevouts_json: &str,
+) -> Result<(RecoveryScripts, Transaction, Vec<TxOut>), JsValue> {
+    let wallet: RecoveryWallet = serde_json::from_str(wallet_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid recovery wallet JSON: {}", e)))?;
+    let primary = require_compressed(&parse_public_key(&wallet.primary_key)?, "A recovery wallet")?;
+    let recovery = require_compressed(&parse_public_key(&wallet.recovery_key)?, "A recovery wallet")?;
+    let scripts = build_scripts(secp, primary, recovery, wallet.delay_blocks, &wallet.script_type)
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    let tx = decode_tx_hex(tx_hex)?;
+    check_input_index(&tx, input_index)?;
+    let prevouts = parse_prevouts(prevouts_json, tx.input.len())?;
+    if prevouts[input_index].script_pubkey != scripts.script_pubkey(secp) {
+        return Err(JsValue::from_str(&format!(
+            "Prevout {} is not paid to this recovery wallet",
+            input_index
+        )));
+    }
+    Ok((scripts, tx, prevouts))
+}
+
+fn wsh_signature(
+    secp: &Secp256k1<All>,
+    cache: &mut SighashCache<&Transaction>,
+    input_index: usize,
+    witness_script: &ScriptBuf,
+    prevout: &TxOut,
+    private_key: &bitcoin::PrivateKey,
+) -> Result<Vec<u8>, JsValue> {
+    let sighash = cache
+        .p2wsh_signature_hash(input_index, witness_script, prevout.value, EcdsaSighashType::All)
+        .map_err(|e| JsValue::from_str(&format!("Failed to compute sighash: {}", e)))?;
+    let signature = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa_low_r(&Message::from(sighash), &private_key.inner));
+    Ok(signature.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{Amount, OutPoint, TxIn, Txid};
+    use crate::script::interpreter::verify_input;
+    use crate::transaction::parse_tx_hex;
+
+    const PRIMARY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+    const RECOVERY: &str = "0202020202020202020202020202020202020202020202020202020202020202";
+    const DELAY: u32 = 144;
+
+    fn public(private_key: &str) -> String {
+        let secret = SecretKey::from_str(private_key).unwrap();
+        secret.public_key(&Secp256k1::new()).to_string()
+    }
+
+    /// One coin of the wallet, its unsigned spend and the prevouts JSON for it.
+    fn funded(script_type: &str) -> (String, Transaction, Vec<TxOut>, String) {
+        let wallet_json = create_recovery_wallet(&public(PRIMARY), &public(RECOVERY), DELAY, None, Some(script_type.to_string()), None).unwrap();
+        let wallet: RecoveryWallet = serde_json::from_str(&wallet_json).unwrap();
+        let prevout = TxOut { value: Amount::from_sat(100_000), script_pubkey: ScriptBuf::from_hex(&wallet.script_pubkey).unwrap() };
+        let tx = Transaction {
+            version: transaction::Version::ONE,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::from_byte_array([9; 32]), 0), ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(99_000), script_pubkey: ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros()) }],
+        };
+        let prevouts_json = serde_json::json!([{"amountSat": 100_000, "scriptPubkey": wallet.script_pubkey}]).to_string();
+        (wallet_json, tx, vec![prevout], prevouts_json)
+    }
+
+    fn assert_verifies(tx: &Transaction, prevouts: &[TxOut]) {
+        if let Err(failure) = verify_input(tx, 0, prevouts) {
+            panic!("fails in {} at {:?}: {}", failure.phase, failure.opcode, failure.reason);
+        }
+        // The signature commits to the amount, so a different one must not verify.
+        let mut other_amount = prevouts.to_vec();
+        other_amount[0].value = Amount::from_sat(100_001);
+        assert!(verify_input(tx, 0, &other_amount).is_err());
+    }
+
+    #[test]
+    fn primary_spends_verify() {
+        for script_type in ["tr", "wsh"] {
+            let (wallet, tx, prevouts, prevouts_json) = funded(script_type);
+            let tx_hex = bitcoin::consensus::encode::serialize_hex(&tx);
+            let signed = parse_tx_hex(&spend_recovery_primary(&wallet, &tx_hex, 0, &prevouts_json, PRIMARY, None).unwrap()).unwrap();
+            assert_eq!(signed.input[0].witness.len(), if script_type == "tr" { 1 } else { 2 }, "{}", script_type);
+            assert_verifies(&signed, &prevouts);
+        }
+    }
+
+    #[test]
+    fn delayed_spends_verify_with_the_sequence_they_set() {
+        for script_type in ["tr", "wsh"] {
+            let (wallet, tx, prevouts, prevouts_json) = funded(script_type);
+            let tx_hex = bitcoin::consensus::encode::serialize_hex(&tx);
+            let signed = parse_tx_hex(&spend_recovery_delayed(&wallet, &tx_hex, 0, &prevouts_json, RECOVERY, None).unwrap()).unwrap();
+            assert_eq!(signed.version, transaction::Version::TWO);
+            assert_eq!(signed.input[0].sequence, Sequence::from_height(DELAY as u16));
+            assert_eq!(signed.input[0].witness.len(), 3, "{}", script_type);
+            assert_verifies(&signed, &prevouts);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::opcodes::OP_TRUE;
+use bitcoin::script::{Builder, Instruction};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::sighash::SighashCache;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use crate::script::interpreter::verify_input;
+use crate::transaction::crowdfund::sighash_flag;
+use crate::transaction::serialization::checked_serialize;
+use crate::transaction::sign::{apply_signature, sign_input};
+use crate::transaction::{decode_tx_hex, parse_prevouts, unsigned_transaction, TransactionInput};
+use crate::utils::bytes_to_hex;
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_list, to_json_with_amounts};
+use crate::utils::metrics::timed;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::wallet::parse_private_key;
+
+/// BIP127: the challenge input spends output 0 of a "transaction" whose txid is the
+/// SHA256 of this prefix and the message. No such transaction exists.
+const CHALLENGE_PREFIX: &str = "Proof-of-Reserves: ";
+const SIGHASH_DEFAULT: u8 = 0x00;
+const SIGHASH_ALL: u8 = 0x01;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenUtxo {
+    pub outpoint: String,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    pub address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveProof {
+    #[serde(alias = "tx_hex")]
+    pub tx_hex: String,
+    /// Output 0 of this txid is what the challenge input "spends".
+    #[serde(alias = "challenge_txid")]
+    pub challenge_txid: String,
+    pub utxos: Vec<ProvenUtxo>,
+    #[serde(with = "crate::utils::amount", alias = "total_proven")]
+    pub total_proven: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveVerification {
+    pub valid: bool,
+    /// Input 0 commits to `challenge_message`. A proof for another message fails here
+    /// even if every signature holds.
+    #[serde(alias = "challenge_matches")]
+    pub challenge_matches: bool,
+    /// The proven UTXOs' total, or 0 when the proof isn't valid.
+    #[serde(with = "crate::utils::amount", alias = "total_proven")]
+    pub total_proven: u64,
+    pub utxos: Vec<ProvenUtxo>,
+    pub error: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Proves control of `utxos_json` (inputs as `build_transaction` takes them) without
+/// moving them, in the BIP127 form: a transaction whose first input is a challenge
+/// committing to `challenge_message`, followed by the UTXOs, each signed SIGHASH_ALL so
+/// the signatures are only valid next to that challenge. The challenge spends an output
+/// that doesn't exist, so the transaction can never be mined. `keys_json` is a JSON list
+/// of hex or WIF keys; each UTXO is signed with whichever key controls it.
+#[wasm_bindgen]
+pub fn create_proof_of_reserves(
+    utxos_json: &str,
+    keys_json: &str,
+    challenge_message: &str,
+    network: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("create_proof_of_reserves", utxos_json.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let utxos: Vec<TransactionInput> = parse_json_list(utxos_json, "UTXOs").map_err(|e| JsValue::from_str(&e))?;
+        if utxos.is_empty() {
+            return Err(JsValue::from_str("At least one UTXO is needed to prove reserves"));
+        }
+        let keys: Vec<String> = parse_json_list(keys_json, "keys").map_err(|e| JsValue::from_str(&e))?;
+        let keys = keys
+            .iter()
+            .map(|key| parse_private_key(key, None, network.network))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tx = unsigned_transaction(&utxos, &[], Some(&network))?;
+        tx.version = Version::ONE;
+        let mut prevouts = vec![challenge_prevout()];
+        for (input, utxo) in tx.input.iter_mut().zip(&utxos) {
+            prevouts.push(TxOut { value: Amount::from_sat(utxo.amount), script_pubkey: std::mem::take(&mut input.script_sig) });
+        }
+        tx.input.insert(0, challenge_input(challenge_message));
+        let total: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+        // BIP127's single output: the whole amount, to a script that only shows the total.
+        tx.output.push(TxOut { value: Amount::from_sat(total), script_pubkey: Builder::new().push_opcode(OP_TRUE).into_script() });
+
+        let secp = Secp256k1::new();
+        let mut cache = SighashCache::new(&tx);
+        let mut signatures = Vec::new();
+        for index in 1..prevouts.len() {
+            let signature = keys
+                .iter()
+                .find_map(|key| sign_input(&secp, &mut cache, index, &prevouts[index], &prevouts, key).ok())
+                .ok_or_else(|| {
+                    let utxo = &utxos[index - 1];
+                    JsValue::from_str(&format!(
+                        "None of the given keys controls UTXO {} ({}:{}); only P2PKH, P2SH-P2WPKH, P2WPKH and P2TR key-path outputs can be signed here",
+                        index - 1,
+                        utxo.txid,
+                        utxo.vout
+                    ))
+                })?;
+            signatures.push((index, signature));
+        }
+        for (index, signature) in signatures {
+            apply_signature(&mut tx, index, signature);
+        }
+
+        let proof = ReserveProof {
+            tx_hex: bytes_to_hex(&checked_serialize(&tx).map_err(|e| JsValue::from_str(&e))?),
+            challenge_txid: challenge_txid(challenge_message).to_string(),
+            utxos: proven_utxos(&tx, &prevouts, &network),
+            total_proven: total,
+        };
+        to_json_with_amounts(&proof, amount_format, compact)
+    })
+}
+
+/// Checks a proof from `create_proof_of_reserves`, or another BIP127 proof: that input 0
+/// is the challenge for `challenge_message`, and that every other input's signatures
+/// hold against `prevouts_json` (one per input after the challenge) with SIGHASH_ALL.
+/// A proof only shows the keys control those outputs; whether they are still unspent
+/// has to be looked up separately. `network` is only used to show addresses.
+#[wasm_bindgen]
+pub fn verify_proof_of_reserves(
+    proof_tx_hex: &str,
+    prevouts_json: &str,
+    challenge_message: &str,
+    network: Option<String>,
+    amount_format: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    timed("verify_proof_of_reserves", proof_tx_hex.len(), || {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let tx = decode_tx_hex(proof_tx_hex)?;
+        if tx.input.len() < 2 {
+            return Err(JsValue::from_str("A proof of reserves has a challenge input and at least one UTXO"));
+        }
+        let mut prevouts = vec![challenge_prevout()];
+        prevouts.extend(parse_prevouts(prevouts_json, tx.input.len() - 1)?);
+
+        let challenge_matches = tx.input[0].previous_output == OutPoint { txid: challenge_txid(challenge_message), vout: 0 };
+        let outcome = if challenge_matches { check_proof(&tx, &prevouts) } else { Err("input 0 isn't the challenge for this message".to_string()) };
+        let mut warnings = Vec::new();
+        let total = prevouts.iter().fold(0u64, |total, prevout| total.saturating_add(prevout.value.to_sat()));
+        if tx.output.len() != 1 || tx.output[0].value.to_sat() != total {
+            warnings.push("BIP127 proofs have one output of the total amount; this one doesn't".to_string());
+        }
+
+        let verification = ReserveVerification {
+            valid: outcome.is_ok(),
+            challenge_matches,
+            total_proven: if outcome.is_ok() { total } else { 0 },
+            utxos: proven_utxos(&tx, &prevouts, &network),
+            error: outcome.err(),
+            warnings,
+        };
+        to_json_with_amounts(&verification, amount_format, compact)
+    })
+}
+
+fn check_proof(tx: &Transaction, prevouts: &[TxOut]) -> Result<(), String> {
+    let mut outpoints = HashSet::new();
+    for (index, input) in tx.input.iter().enumerate().skip(1) {
+        if input.previous_output == tx.input[0].previous_output || !outpoints.insert(input.previous_output) {
+            return Err(format!("input {} repeats an outpoint, which would count its amount twice", index));
+        }
+        let spent = &prevouts[index].script_pubkey;
+        if let Some(flag) = signature_flags(input, spent).into_iter().find(|&flag| flag != SIGHASH_ALL && flag != SIGHASH_DEFAULT) {
+            return Err(format!(
+                "input {} is signed with sighash 0x{:02x}; only SIGHASH_ALL ties a signature to the challenge",
+                index, flag
+            ));
+        }
+        verify_input(tx, index, prevouts).map_err(|failure| format!("input {}: {}", index, failure.reason))?;
+    }
+    Ok(())
+}
+
+/// The sighash bytes of the signatures in `input`. Key-path spends have their one
+/// signature read directly; for scripts, every DER-encoded push counts as a signature.
+fn signature_flags(input: &TxIn, spent: &Script) -> Vec<u8> {
+    let single_key = spent.is_p2pkh() || spent.is_p2wpkh() || (spent.is_p2tr() && input.witness.len() == 1)
+        || (spent.is_p2sh() && input.witness.len() == 2);
+    if single_key {
+        return sighash_flag(&input.script_sig, &input.witness, spent).into_iter().collect();
+    }
+    let pushes = input.script_sig.instructions().filter_map(|instruction| match instruction {
+        Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes().to_vec()),
+        _ => None,
+    });
+    pushes
+        .chain(input.witness.iter().map(<[u8]>::to_vec))
+        .filter(|item| bitcoin::ecdsa::Signature::from_slice(item).is_ok())
+        .filter_map(|signature| signature.last().copied())
+        .collect()
+}
+
+fn challenge_txid(message: &str) -> Txid {
+    let hash = sha256::Hash::hash(format!("{}{}", CHALLENGE_PREFIX, message).as_bytes());
+    Txid::from_byte_array(hash.to_byte_array())
+}
+
+fn challenge_input(message: &str) -> TxIn {
+    TxIn {
+        previous_output: OutPoint { txid: challenge_txid(message), vout: 0 },
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    }
+}
+
+/// What the challenge input is signed as spending: nothing, to an anyone-can-spend script.
+fn challenge_prevout() -> TxOut {
+    TxOut { value: Amount::ZERO, script_pubkey: Builder::new().push_opcode(OP_TRUE).into_script() }
+}
+
+fn proven_utxos(tx: &Transaction, prevouts: &[TxOut], network: &NetworkParams) -> Vec<ProvenUtxo> {
+    tx.input
+        .iter()
+        .zip(prevouts)
+        .skip(1)
+        .map(|(input, prevout)| ProvenUtxo {
+            outpoint: input.previous_output.to_string(),
+            amount: prevout.value.to_sat(),
+            script_pubkey: bytes_to_hex(prevout.script_pubkey.as_bytes()),
+            address: network.address(&prevout.script_pubkey),
+        })
+        .collect()
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv, Xpub};
+use bitcoin::key::CompressedPublicKey;
+use bitcoin::secp256k1::{All, PublicKey, Secp256k1};
+use bitcoin::{PublicKey as BtcPublicKey, Script, ScriptBuf};
+use crate::script::output_script_type;
+use crate::utils::json::to_json;
+use crate::utils::network::{network_or_default, require_address_network};
+use crate::wallet::electrum::{electrum_seed, electrum_seed_type, SeedType};
+use crate::wallet::mnemonic::bip39_seed;
+use crate::wallet::path::format_path;
+use crate::utils::metrics::timed;
+
+/// Receive and change addresses tried per account, the usual gap limit.
+const INDEXES_SEARCHED: u32 = 20;
+/// Purpose, scheme, label and the address type the purpose's wallets show.
+const BIP39_PURPOSES: [(u32, &str, &str, &str); 4] = [
+    (44, "bip44", "BIP44", "p2pkh"),
+    (49, "bip49", "BIP49", "p2sh-p2wpkh"),
+    (84, "bip84", "BIP84", "p2wpkh"),
+    (86, "bip86", "BIP86", "p2tr"),
+];
+const ACCOUNTS: [u32; 2] = [0, 1];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreMatch {
+    /// `bip44`, `bip49`, `bip84`, `bip86`, `electrum_standard` or `electrum_segwit`.
+    pub scheme: String,
+    pub path: String,
+    /// `receive` or `change`.
+    pub chain: String,
+    pub index: u32,
+    /// The kind of address that matched, which needn't be the one the scheme shows.
+    #[serde(alias = "script_type")]
+    pub script_type: String,
+    pub compressed: bool,
+    /// `none`, `given` or `trimmed`: which passphrase variant the wallet used.
+    pub passphrase: String,
+}
+
+/// One account searched: every receive and change index below 20 under `account_path`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreSearch {
+    pub scheme: String,
+    #[serde(alias = "account_path")]
+    pub account_path: String,
+    pub passphrase: String,
+    #[serde(alias = "addresses_checked")]
+    pub addresses_checked: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreDiagnosis {
+    pub found: bool,
+    /// One line for a support reply, e.g. "Found at BIP49 change index 3 (m/49'/0'/0'/1/3)".
+    pub summary: String,
+    #[serde(alias = "found_at")]
+    pub found_at: Option<RestoreMatch>,
+    /// The expected address's type, which decides the scripts each key is tried as.
+    #[serde(alias = "expected_type")]
+    pub expected_type: String,
+    #[serde(alias = "bip39_valid")]
+    pub bip39_valid: bool,
+    #[serde(alias = "electrum_seed_type")]
+    pub electrum_seed_type: Option<String>,
+    /// Every account searched, in order, up to the match if there is one.
+    pub searched: Vec<RestoreSearch>,
+}
+
+/// One account layout to search.
+struct Layout {
+    scheme: &'static str,
+    label: &'static str,
+    /// The address type a wallet using this layout shows.
+    script_type: &'static str,
+    account_path: DerivationPath,
+}
+
+/// Which derivation of `mnemonic` gives `expected_address`, for "I restored my words but
+/// the addresses are different". The phrase is read as BIP39 under BIP44, BIP49, BIP84
+/// and BIP86 accounts 0 and 1, and as an Electrum seed under both Electrum layouts,
+/// whichever it is valid as. Each account's first 20 receive and change keys are tried
+/// as every script of the expected address's type, uncompressed P2PKH included, so a
+/// wallet that shows an unexpected address type is found too. With a `passphrase`, the
+/// phrase is tried with it, without it, and with it trimmed; without one, only without.
+#[wasm_bindgen]
+pub fn diagnose_restore(
+    mnemonic: &str,
+    expected_address: &str,
+    network: Option<String>,
+    passphrase: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    timed("diagnose_restore", mnemonic.len() + expected_address.len(), || {
+        let params = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let expected = require_address_network(expected_address, Some(&params)).map_err(|e| JsValue::from_str(&e))?;
+        let expected_type = output_script_type(&expected);
+        if !matches!(expected_type, "p2pkh" | "p2sh" | "p2wpkh" | "p2tr") {
+            return Err(JsValue::from_str(&format!(
+                "{} is a {} address; only single-key addresses (P2PKH, P2SH-P2WPKH, P2WPKH, P2TR) come from a phrase alone",
+                expected_address.trim(),
+                expected_type
+            )));
+        }
+
+        let bip39_error = bip39_seed(mnemonic, "").err();
+        let electrum_type = electrum_seed_type(mnemonic);
+        let electrum_derivable = matches!(electrum_type, Some(SeedType::Standard | SeedType::Segwit));
+        if let (Some(error), false) = (&bip39_error, electrum_derivable) {
+            return Err(JsValue::from_str(&match electrum_type {
+                Some(_) => "This is an Electrum 2FA (TrustedCoin) seed; its addresses need TrustedCoin's cosigner keys and can't be searched here".to_string(),
+                None => format!("Neither a BIP39 phrase nor an Electrum seed: {}", error),
+            }));
+        }
+
+        let mut variants = vec![("none", String::new())];
+        if let Some(given) = passphrase.filter(|given| !given.is_empty()) {
+            let trimmed = given.trim().to_string();
+            variants.insert(0, ("given", given.clone()));
+            if trimmed != given && !trimmed.is_empty() {
+                variants.push(("trimmed", trimmed));
+            }
+        }
+
+        let secp = Secp256k1::new();
+        let mut searched = Vec::new();
+        let mut found_at = None;
+        'search: for (variant, passphrase) in &variants {
+            let mut seeds = Vec::new();
+            if bip39_error.is_none() {
+                let seed = bip39_seed(mnemonic, passphrase).map_err(|e| JsValue::from_str(&e))?;
+                seeds.push((seed, bip39_layouts(params.coin_type())));
+            }
+            if electrum_derivable {
+                let (seed, _) = electrum_seed(mnemonic, Some(passphrase)).map_err(|e| JsValue::from_str(&e))?;
+                seeds.push((seed, electrum_layouts()));
+            }
+            for (seed, layouts) in seeds {
+                let master = Xpriv::new_master(params.network, &seed).map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+                for layout in layouts {
+                    let account = master
+                        .derive_priv(&secp, &layout.account_path)
+                        .map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+                    searched.push(RestoreSearch {
+                        scheme: layout.scheme.to_string(),
+                        account_path: format_path(&layout.account_path, None),
+                        passphrase: variant.to_string(),
+                        addresses_checked: 2 * INDEXES_SEARCHED,
+                    });
+                    if let Some((chain, index, script_type, compressed)) = search_account(&secp, &Xpub::from_priv(&secp, &account), &expected)? {
+                        let path = layout.account_path.extend([ChildNumber::Normal { index: chain }, ChildNumber::Normal { index }]);
+                        let found = RestoreMatch {
+                            scheme: layout.scheme.to_string(),
+                            path: format_path(&path, None),
+                            chain: if chain == 0 { "receive" } else { "change" }.to_string(),
+                            index,
+                            script_type: script_type.to_string(),
+                            compressed,
+                            passphrase: variant.to_string(),
+                        };
+                        found_at = Some((layout, found));
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let summary = match &found_at {
+            Some((layout, found)) => found_summary(layout, found, passphrase_given(&variants)),
+            None => missing_summary(&searched, expected_type, passphrase_given(&variants)),
+        };
+        let diagnosis = RestoreDiagnosis {
+            found: found_at.is_some(),
+            summary,
+            found_at: found_at.map(|(_, found)| found),
+            expected_type: expected_type.to_string(),
+            bip39_valid: bip39_error.is_none(),
+            electrum_seed_type: electrum_type.map(|seed_type| seed_type.name().to_string()),
+            searched,
+        };
+        to_json(&diagnosis, compact)
+    })
+}
+
+fn bip39_layouts(coin_type: u32) -> Vec<Layout> {
+    let mut layouts = Vec::new();
+    for (purpose, scheme, label, script_type) in BIP39_PURPOSES {
+        for account in ACCOUNTS {
+            layouts.push(Layout {
+                scheme,
+                label,
+                script_type,
+                account_path: DerivationPath::from(vec![
+                    ChildNumber::Hardened { index: purpose },
+                    ChildNumber::Hardened { index: coin_type },
+                    ChildNumber::Hardened { index: account },
+                ]),
+            });
+        }
+    }
+    layouts
+}
+
+/// Electrum's two layouts, tried whichever the seed's version says, since the account
+/// path and address type are all that differ.
+fn electrum_layouts() -> Vec<Layout> {
+    vec![
+        Layout { scheme: "electrum_standard", label: "Electrum standard", script_type: "p2pkh", account_path: DerivationPath::master() },
+        Layout {
+            scheme: "electrum_segwit",
+            label: "Electrum segwit",
+            script_type: "p2wpkh",
+            account_path: DerivationPath::from(vec![ChildNumber::Hardened { index: 0 }]),
+        },
+    ]
+}
+
+/// The first receive or change key under `account` whose script is `expected`, as
+/// `(chain, index, script type, compressed)`.
+fn search_account(secp: &Secp256k1<All>, account: &Xpub, expected: &Script) -> Result<Option<(u32, u32, &'static str, bool)>, JsValue> {
+    for chain in [0, 1] {
+        let branch = account
+            .derive_pub(secp, &[ChildNumber::Normal { index: chain }])
+            .map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+        for index in 0..INDEXES_SEARCHED {
+            let key = branch
+                .derive_pub(secp, &[ChildNumber::Normal { index }])
+                .map_err(|e| JsValue::from_str(&format!("Key derivation failed: {}", e)))?;
+            if let Some((script_type, compressed)) = key_match(secp, &key.public_key, expected) {
+                return Ok(Some((chain, index, script_type, compressed)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Only the scripts of the expected address's type are built, so a P2WPKH search never
+/// pays for taproot tweaks.
+fn key_match(secp: &Secp256k1<All>, key: &PublicKey, expected: &Script) -> Option<(&'static str, bool)> {
+    let compressed = CompressedPublicKey(*key);
+    if expected.is_p2pkh() {
+        if *expected == ScriptBuf::new_p2pkh(&BtcPublicKey::new(*key).pubkey_hash()) {
+            return Some(("p2pkh", true));
+        }
+        if *expected == ScriptBuf::new_p2pkh(&BtcPublicKey::new_uncompressed(*key).pubkey_hash()) {
+            return Some(("p2pkh", false));
+        }
+        None
+    } else if expected.is_p2sh() {
+        (*expected == ScriptBuf::new_p2sh(&ScriptBuf::new_p2wpkh(&compressed.wpubkey_hash()).script_hash())).then_some(("p2sh-p2wpkh", true))
+    } else if expected.is_p2wpkh() {
+        (*expected == ScriptBuf::new_p2wpkh(&compressed.wpubkey_hash())).then_some(("p2wpkh", true))
+    } else {
+        (*expected == ScriptBuf::new_p2tr(secp, key.x_only_public_key().0, None)).then_some(("p2tr", true))
+    }
+}
+
+fn passphrase_given(variants: &[(&str, String)]) -> bool {
+    variants.len() > 1
+}
+
+fn found_summary(layout: &Layout, found: &RestoreMatch, passphrase_given: bool) -> String {
+    let mut summary = format!("Found at {} {} index {} ({})", layout.label, found.chain, found.index, found.path);
+    match (found.passphrase.as_str(), passphrase_given) {
+        ("given", _) => summary.push_str(", with the passphrase"),
+        ("trimmed", _) => summary.push_str(", with the spaces around the passphrase removed"),
+        (_, true) => summary.push_str(", without the passphrase"),
+        _ => {}
+    }
+    if !found.compressed {
+        summary.push_str(", from the uncompressed public key");
+    }
+    if found.script_type != layout.script_type {
+        summary.push_str(&format!(
+            ", but as a {} address rather than the {} addresses {} wallets show",
+            found.script_type, layout.script_type, layout.label
+        ));
+    }
+    summary
+}
+
+fn missing_summary(searched: &[RestoreSearch], expected_type: &str, passphrase_given: bool) -> String {
+    let mut schemes: Vec<&str> = Vec::new();
+    for search in searched {
+        if !schemes.contains(&search.scheme.as_str()) {
+            schemes.push(&search.scheme);
+        }
+    }
+    let total: u32 = searched.iter().map(|search| search.addresses_checked).sum();
+    let mut summary = format!(
+        "Not found: searched {} {} addresses, the first {} receive and change addresses of {} accounts ({})",
+        total,
+        expected_type,
+        INDEXES_SEARCHED,
+        searched.len(),
+        schemes.join(", ")
+    );
+    summary.push_str(if passphrase_given {
+        ", with and without the passphrase. Check the passphrase's exact spelling and case, or whether the address is past index 19"
+    } else {
+        ". If the wallet had a passphrase (\"25th word\"), pass it to search with it too"
+    });
+    summary
+}
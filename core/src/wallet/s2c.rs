@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::constants::CURVE_ORDER;
+use bitcoin::secp256k1::{ecdsa, Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use bitcoin::Network;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::adaptor::parse_message;
+use crate::wallet::keys::parse_public_key;
+use crate::wallet::{parse_private_key, reduce_mod_order, tagged_engine};
+use crate::utils::json::to_json;
+
+const COMMITMENT_TAG: &str = "BitLab/s2c/commitment";
+const HOST_COMMITMENT_TAG: &str = "BitLab/s2c/host";
+const NONCE_TAG: &str = "BitLab/s2c/nonce";
+
+/// The signer's first-round answer: the nonce point it will tweak, fixed before it sees
+/// the host's data.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonceCommitment {
+    #[serde(alias = "nonce_point")]
+    pub nonce_point: String,
+    #[serde(alias = "public_key")]
+    pub public_key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractSignature {
+    /// DER, low-S and without a sighash byte, as it goes into a scriptSig or witness once
+    /// the sighash byte is appended.
+    pub signature: String,
+    /// The same signature as 64 bytes, r ‖ s.
+    pub compact: String,
+    /// R, the nonce point before the commitment. Whoever keeps it can later show the
+    /// commitment is in the signature.
+    #[serde(alias = "nonce_point")]
+    pub nonce_point: String,
+    /// R + t·G, whose x coordinate is the signature's r.
+    #[serde(alias = "tweaked_nonce_point")]
+    pub tweaked_nonce_point: String,
+    #[serde(alias = "public_key")]
+    pub public_key: String,
+}
+
+/// Round one, on the host: tagged_hash("BitLab/s2c/host", data) for the fresh random
+/// `commitment_data_hex` it will later reveal. It binds the host to its data without
+/// telling the signer anything about it.
+#[wasm_bindgen]
+pub fn host_data_commitment(commitment_data_hex: &str) -> Result<String, JsValue> {
+    let data = parse_data(commitment_data_hex)?;
+    Ok(bytes_to_hex(&host_commitment(&data)))
+}
+
+/// Round one, on the signer: the nonce point R it will use for `sighash`, before it has
+/// seen the host's data. The nonce comes from the key, the sighash and
+/// `host_commitment_hex` only, so the later signature can't choose it to suit the data.
+#[wasm_bindgen]
+pub fn commit_signing_nonce(private_key: &str, sighash: &str, host_commitment_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let secret = parse_private_key(private_key, None, Network::Testnet)?.inner;
+    let message = parse_message(sighash)?;
+    let host_commitment: [u8; 32] = hex_to_bytes(host_commitment_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid host commitment hex: {}", e)))?
+        .try_into()
+        .map_err(|_| JsValue::from_str("Host commitment must be 32 bytes"))?;
+    let nonce = signing_nonce(&secret, &message, &host_commitment).map_err(|e| JsValue::from_str(&e))?;
+    let result = NonceCommitment {
+        nonce_point: bytes_to_hex(&nonce.public_key(&secp).serialize()),
+        public_key: bytes_to_hex(&secret.public_key(&secp).serialize()),
+    };
+    to_json(&result, compact)
+}
+
+/// ECDSA sign-to-contract: an ordinary signature over `sighash` whose nonce is tweaked
+/// by t = tagged_hash("BitLab/s2c/commitment", R ‖ data), so it commits to
+/// `commitment_data_hex` without any extra output.
+///
+/// Against nonce exfiltration this is round two. The host has sent
+/// `host_data_commitment(data)` and kept the R that `commit_signing_nonce` returned;
+/// only now does it reveal the data. The signer re-derives the same nonce from the
+/// commitment, so the result's `noncePoint` must equal the kept R. The host then checks
+/// `verify_commitment_in_signature` against that R. Signing is repeatable: the same
+/// key, sighash and data give the same signature, and different data a different nonce.
+#[wasm_bindgen]
+pub fn sign_with_commitment(private_key: &str, sighash: &str, commitment_data_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let secret = parse_private_key(private_key, None, Network::Testnet)?.inner;
+    let message = parse_message(sighash)?;
+    let data = parse_data(commitment_data_hex)?;
+    to_json(&contract_signature(&secret, message, &data).map_err(|e| JsValue::from_str(&e))?, compact)
+}
+
+/// k = tagged_hash("BitLab/s2c/nonce", d ‖ m ‖ host commitment ‖ counter), with the
+/// counter only moving past hashes that aren't valid scalars. The host commitment is in
+/// it so two signatures of one sighash under different data never share k: their
+/// tweaked nonces would differ by a known amount, which gives away the key.
+fn signing_nonce(secret: &SecretKey, message: &[u8; 32], host_commitment: &[u8; 32]) -> Result<SecretKey, String> {
+    (0u32..u32::MAX)
+        .find_map(|counter| {
+            let mut engine = tagged_engine(NONCE_TAG);
+            engine.input(&secret.secret_bytes());
+            engine.input(message);
+            engine.input(host_commitment);
+            engine.input(&counter.to_be_bytes());
+            SecretKey::from_slice(&sha256::Hash::from_engine(engine).to_byte_array()).ok()
+        })
+        .ok_or_else(|| "No valid nonce found for this key and sighash".to_string())
+}
+
+fn contract_signature(secret: &SecretKey, message: [u8; 32], data: &[u8]) -> Result<ContractSignature, String> {
+    let secp = Secp256k1::new();
+    let z = reduce_mod_order(message);
+    let nonce = signing_nonce(secret, &message, &host_commitment(data))?;
+    let nonce_point = nonce.public_key(&secp);
+    // The nonce was fixed in round one, so these can't be retried with another one; each
+    // fails with probability about 2⁻¹²⁸, and fresh data gets past it.
+    let degenerate = || "The commitment makes this nonce unusable; sign again with fresh commitment data".to_string();
+    let tweaked_nonce = nonce.add_tweak(&commitment_tweak(&nonce_point, data)).map_err(|_| degenerate())?;
+    let tweaked_point = tweaked_nonce.public_key(&secp);
+
+    // s = k'⁻¹·(z + r·d), with r the x coordinate of R' mod n.
+    let r = reduce_mod_order(x_coordinate(&tweaked_point));
+    let sum = secret.mul_tweak(&r).and_then(|rd| rd.add_tweak(&z)).map_err(|_| degenerate())?;
+    let s = invert(&tweaked_nonce).mul_tweak(&Scalar::from(sum)).map_err(|_| degenerate())?;
+    let mut compact = [0u8; 64];
+    compact[..32].copy_from_slice(&r.to_be_bytes());
+    compact[32..].copy_from_slice(&s.secret_bytes());
+    let mut signature = ecdsa::Signature::from_compact(&compact).map_err(|_| degenerate())?;
+    signature.normalize_s();
+
+    let public_key = secret.public_key(&secp);
+    secp.verify_ecdsa(&Message::from_digest(message), &signature, &public_key)
+        .map_err(|e| format!("Sign-to-contract produced an invalid signature: {}", e))?;
+    Ok(ContractSignature {
+        signature: bytes_to_hex(&signature.serialize_der()),
+        compact: bytes_to_hex(&signature.serialize_compact()),
+        nonce_point: bytes_to_hex(&nonce_point.serialize()),
+        tweaked_nonce_point: bytes_to_hex(&tweaked_point.serialize()),
+        public_key: bytes_to_hex(&public_key.serialize()),
+    })
+}
+
+/// Whether `signature` commits to `commitment_data` under the original nonce point R:
+/// its r must be the x coordinate of R + t·G. This only proves the commitment; check the
+/// signature itself against its key and sighash as usual. `signature` is DER, with or
+/// without a trailing sighash byte, or 64-byte compact.
+#[wasm_bindgen]
+pub fn verify_commitment_in_signature(signature: &str, nonce_point: &str, commitment_data: &str) -> Result<bool, JsValue> {
+    let signature = parse_signature(signature)?;
+    let nonce_point = parse_public_key(nonce_point)?.inner;
+    let data = parse_data(commitment_data)?;
+    Ok(commitment_verifies(&signature, &nonce_point, &data))
+}
+
+fn commitment_verifies(signature: &ecdsa::Signature, nonce_point: &PublicKey, data: &[u8]) -> bool {
+    let secp = Secp256k1::verification_only();
+    let Ok(tweaked_point) = nonce_point.add_exp_tweak(&secp, &commitment_tweak(nonce_point, data)) else {
+        return false;
+    };
+    let r = reduce_mod_order(x_coordinate(&tweaked_point));
+    signature.serialize_compact()[..32] == r.to_be_bytes()
+}
+
+fn host_commitment(data: &[u8]) -> [u8; 32] {
+    let mut engine = tagged_engine(HOST_COMMITMENT_TAG);
+    engine.input(data);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn parse_data(data_hex: &str) -> Result<Vec<u8>, JsValue> {
+    hex_to_bytes(data_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid commitment data hex: {}", e)))
+}
+
+/// t = tagged_hash(R ‖ data) mod n. Hashing R in stops a signer from picking a nonce
+/// after seeing the data.
+fn commitment_tweak(nonce_point: &PublicKey, data: &[u8]) -> Scalar {
+    let mut engine = tagged_engine(COMMITMENT_TAG);
+    engine.input(&nonce_point.serialize());
+    engine.input(data);
+    reduce_mod_order(sha256::Hash::from_engine(engine).to_byte_array())
+}
+
+fn x_coordinate(point: &PublicKey) -> [u8; 32] {
+    point.serialize()[1..].try_into().expect("33-byte compressed point")
+}
+
+/// k⁻¹ = k^(n-2) mod n. n is prime, so Fermat's little theorem gives the inverse.
+fn invert(scalar: &SecretKey) -> SecretKey {
+    let mut exponent = CURVE_ORDER;
+    exponent[31] -= 2;
+    let base = Scalar::from(*scalar);
+    let mut result: Option<SecretKey> = None;
+    for byte in exponent {
+        for bit in (0..8).rev() {
+            result = result.map(|value| value.mul_tweak(&Scalar::from(value)).expect("product of non-zero scalars mod a prime"));
+            if byte >> bit & 1 == 1 {
+                result = Some(match result {
+                    Some(value) => value.mul_tweak(&base).expect("product of non-zero scalars mod a prime"),
+                    None => *scalar,
+                });
+            }
+        }
+    }
+    result.expect("the exponent is non-zero")
+}
+
+fn parse_signature(signature: &str) -> Result<ecdsa::Signature, JsValue> {
+    let bytes = hex_to_bytes(signature, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid signature hex: {}", e)))?;
+    if bytes.len() == 64 {
+        return ecdsa::Signature::from_compact(&bytes)
+            .map_err(|e| JsValue::from_str(&format!("Invalid compact signature: {}", e)));
+    }
+    ecdsa::Signature::from_der(&bytes)
+        .or_else(|_| match bytes.split_last() {
+            Some((_, der)) => ecdsa::Signature::from_der(der),
+            None => ecdsa::Signature::from_der(&bytes),
+        })
+        .map_err(|e| JsValue::from_str(&format!("Invalid DER signature: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGHASH: [u8; 32] = [0x5a; 32];
+
+    fn key() -> SecretKey {
+        SecretKey::from_slice(&[0x11; 32]).unwrap()
+    }
+
+    #[test]
+    fn round_two_uses_the_round_one_nonce() {
+        let data = b"host randomness";
+        let committed = signing_nonce(&key(), &SIGHASH, &host_commitment(data)).unwrap();
+        let committed_point = committed.public_key(&Secp256k1::new());
+
+        let signed = contract_signature(&key(), SIGHASH, data).unwrap();
+        assert_eq!(signed.nonce_point, bytes_to_hex(&committed_point.serialize()));
+
+        let signature = ecdsa::Signature::from_compact(&hex_to_bytes(&signed.compact, HexMode::Strict).unwrap()).unwrap();
+        assert!(commitment_verifies(&signature, &committed_point, data));
+        assert!(!commitment_verifies(&signature, &committed_point, b"other data"));
+        let public_key = key().public_key(&Secp256k1::new());
+        assert!(Secp256k1::new().verify_ecdsa(&Message::from_digest(SIGHASH), &signature, &public_key).is_ok());
+    }
+
+    #[test]
+    fn different_data_never_shares_a_nonce() {
+        let first = contract_signature(&key(), SIGHASH, b"first").unwrap();
+        let second = contract_signature(&key(), SIGHASH, b"second").unwrap();
+        assert_ne!(first.nonce_point, second.nonce_point);
+        assert_eq!(contract_signature(&key(), SIGHASH, b"first").unwrap().signature, first.signature);
+    }
+}
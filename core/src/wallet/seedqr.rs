@@ -0,0 +1,146 @@
+use wasm_bindgen::prelude::*;
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::mnemonic::{english_wordlist, entropy_to_mnemonic, indexes_to_entropy, word_indexes};
+
+/// SeedQR only defines 12- and 24-word seeds.
+const SEEDQR_WORD_COUNTS: [usize; 2] = [12, 24];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SeedQrFormat {
+    /// Each word index as four decimal digits, for a numeric-mode QR code.
+    Standard,
+    /// The entropy bits alone, for a byte-mode QR code. The checksum bits of the last
+    /// word are left out and recomputed on decoding.
+    Compact,
+}
+
+/// Encodes a BIP39 phrase as a SeedQR payload: the digit string for `standard` (the
+/// default) or the entropy as hex for `compact`, for a QR library to render.
+#[wasm_bindgen]
+pub fn mnemonic_to_seedqr(phrase: &str, format: Option<String>) -> Result<String, JsValue> {
+    let format = parse_format(format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let indexes = word_indexes(phrase).map_err(|e| JsValue::from_str(&e))?;
+    check_word_count(indexes.len()).map_err(|e| JsValue::from_str(&e))?;
+    let entropy = indexes_to_entropy(&indexes).map_err(|e| JsValue::from_str(&e))?;
+
+    Ok(match format {
+        SeedQrFormat::Standard => indexes.iter().map(|index| format!("{:04}", index)).collect(),
+        SeedQrFormat::Compact => bytes_to_hex(&entropy),
+    })
+}
+
+/// Decodes a SeedQR payload back into its phrase. Compact payloads get their checksum
+/// recomputed; standard ones must carry a valid one.
+#[wasm_bindgen]
+pub fn seedqr_to_mnemonic(payload: &str, format: Option<String>) -> Result<String, JsValue> {
+    let format = parse_format(format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let phrase = match format {
+        SeedQrFormat::Standard => decode_standard(payload.trim()),
+        SeedQrFormat::Compact => decode_compact(payload.trim()),
+    };
+    phrase.map_err(|e| JsValue::from_str(&e))
+}
+
+fn decode_standard(digits: &str) -> Result<String, String> {
+    if let Some((position, c)) = digits.chars().enumerate().find(|(_, c)| !c.is_ascii_digit()) {
+        return Err(format!("Character {} ('{}') is not a digit; standard SeedQR is digits only", position + 1, c));
+    }
+    if !SEEDQR_WORD_COUNTS.iter().any(|words| words * 4 == digits.len()) {
+        return Err(format!(
+            "Standard SeedQR has 48 digits (12 words) or 96 digits (24 words), got {}",
+            digits.len()
+        ));
+    }
+
+    let mut indexes = Vec::with_capacity(digits.len() / 4);
+    for (position, chunk) in digits.as_bytes().chunks(4).enumerate() {
+        let text = std::str::from_utf8(chunk).expect("ASCII digits");
+        let index: usize = text.parse().expect("four digits");
+        if index >= english_wordlist().len() {
+            return Err(format!(
+                "Word {} (digits {}-{}, \"{}\") is index {}, but word indexes stop at 2047",
+                position + 1,
+                position * 4 + 1,
+                position * 4 + 4,
+                text,
+                index
+            ));
+        }
+        indexes.push(index);
+    }
+    indexes_to_entropy(&indexes)?;
+
+    let words = english_wordlist();
+    Ok(indexes.iter().map(|&index| words[index]).collect::<Vec<_>>().join(" "))
+}
+
+fn decode_compact(hex: &str) -> Result<String, String> {
+    let entropy = hex_to_bytes(hex, HexMode::Lenient).map_err(|e| format!("Invalid compact SeedQR hex: {}", e))?;
+    if entropy.len() != 16 && entropy.len() != 32 {
+        return Err(format!(
+            "Compact SeedQR is 16 bytes (12 words) or 32 bytes (24 words), got {}",
+            entropy.len()
+        ));
+    }
+    entropy_to_mnemonic(&entropy)
+}
+
+fn check_word_count(count: usize) -> Result<(), String> {
+    if !SEEDQR_WORD_COUNTS.contains(&count) {
+        return Err(format!("SeedQR encodes 12- or 24-word seeds, got {} words", count));
+    }
+    Ok(())
+}
+
+fn parse_format(format: Option<&str>) -> Result<SeedQrFormat, String> {
+    match format.map(|f| f.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("standard") => Ok(SeedQrFormat::Standard),
+        Some("compact") => Ok(SeedQrFormat::Compact),
+        Some(other) => Err(format!("Unknown SeedQR format \"{}\" (expected standard or compact)", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SeedSigner's SeedQR format docs, 24-word example.
+    const WORDS_24: &str = "attack pizza motion avocado network gather crop fresh patrol unusual wild holiday candy pony ranch winter theme error hybrid van cereal salon goddess expire";
+    const STANDARD_24: &str = "011513251154012711900771041507421289190620080870026613431420201617920614089619290300152408010643";
+    const COMPACT_24: &str = "0e74b64107f94cc0ccfae6a13dcbec3662154fec67e0e00999c07892597d190a";
+    /// SeedSigner's SeedQR format docs, 12-word example.
+    const WORDS_12: &str = "forum undo fragile fade shy sign arrest garment culture tube off merit";
+    const STANDARD_12: &str = "073318950739065415961602009907670428187212261116";
+    const COMPACT_12: &str = "5bbd9d71a8ec7990831aff359d426545";
+
+    #[test]
+    fn standard_matches_seedsigner() {
+        for (words, digits) in [(WORDS_12, STANDARD_12), (WORDS_24, STANDARD_24)] {
+            assert_eq!(mnemonic_to_seedqr(words, None).unwrap(), digits);
+            assert_eq!(mnemonic_to_seedqr(words, Some("standard".to_string())).unwrap(), digits);
+            assert_eq!(seedqr_to_mnemonic(digits, None).unwrap(), words);
+        }
+    }
+
+    #[test]
+    fn compact_matches_seedsigner() {
+        for (words, hex) in [(WORDS_12, COMPACT_12), (WORDS_24, COMPACT_24)] {
+            assert_eq!(mnemonic_to_seedqr(words, Some("compact".to_string())).unwrap(), hex);
+            assert_eq!(seedqr_to_mnemonic(hex, Some("Compact".to_string())).unwrap(), words);
+        }
+    }
+
+    #[test]
+    fn bad_payloads_are_explained() {
+        assert_eq!(decode_standard("0733a895").unwrap_err(), "Character 5 ('a') is not a digit; standard SeedQR is digits only");
+        assert!(decode_standard(&STANDARD_12[..44]).unwrap_err().contains("got 44"));
+        let out_of_range = format!("2048{}", &STANDARD_12[4..]);
+        assert!(decode_standard(&out_of_range).unwrap_err().contains("index 2048"));
+        // The last word carries the checksum, so changing it breaks the payload.
+        let bad_checksum = format!("{}1115", &STANDARD_12[..44]);
+        assert!(decode_standard(&bad_checksum).is_err());
+        assert!(decode_compact(&COMPACT_12[..30]).unwrap_err().contains("got 15"));
+        assert_eq!(check_word_count(18).unwrap_err(), "SeedQR encodes 12- or 24-word seeds, got 18 words");
+        assert!(parse_format(Some("tiny")).is_err());
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::rng::with_rng;
+use crate::utils::bytes_to_hex;
+use crate::wallet::mnemonic::pbkdf2_hmac;
+use crate::utils::json::to_json;
+
+const WORDLIST: &str = include_str!("wordlists/slip39.txt");
+
+const RADIX_BITS: usize = 10;
+const CHECKSUM_WORDS: usize = 3;
+/// Identifier and exponent (2 words), share parameters (2 words) and checksum (3 words).
+const METADATA_WORDS: usize = 7;
+/// 128 bits of share value need 13 words.
+const MIN_SHARE_WORDS: usize = METADATA_WORDS + 13;
+const MAX_SHARES: u8 = 16;
+const BASE_ITERATIONS: u32 = 10000;
+const ROUNDS: u8 = 4;
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const DIGEST_LEN: usize = 4;
+const CUSTOMIZATION: &[u8] = b"shamir";
+const CUSTOMIZATION_EXTENDABLE: &[u8] = b"shamir_extendable";
+
+fn slip39_wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| WORDLIST.lines().collect())
+}
+
+/// One SLIP-39 share, as carried by one mnemonic.
+#[derive(Clone, PartialEq, Eq)]
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GroupSpec {
+    Pair((u8, u8)),
+    Object { threshold: u8, count: u8 },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct SplitOptions {
+    #[serde(default, alias = "iteration_exponent")]
+    iteration_exponent: Option<u8>,
+    #[serde(default)]
+    extendable: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Slip39Group {
+    #[serde(alias = "member_threshold")]
+    pub member_threshold: u8,
+    pub shares: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Slip39Split {
+    pub identifier: u16,
+    pub extendable: bool,
+    #[serde(alias = "iteration_exponent")]
+    pub iteration_exponent: u8,
+    #[serde(alias = "group_threshold")]
+    pub group_threshold: u8,
+    pub groups: Vec<Slip39Group>,
+}
+
+/// Splits a master secret into SLIP-39 share mnemonics: `group_threshold` of the groups
+/// are needed, and within each group its own threshold of shares. `groups_json` lists
+/// each group as `[threshold, count]` or `{ threshold, count }`, e.g. `[[2, 3]]` for a
+/// single 2-of-3 group.
+#[wasm_bindgen]
+pub fn split_seed_slip39(
+    seed_or_entropy_hex: &str,
+    group_threshold: u8,
+    groups_json: &str,
+    passphrase: Option<String>,
+    options_json: Option<String>,
+    compact: Option<bool>,
+) -> Result<String, JsValue> {
+    let secret = hex_to_bytes(seed_or_entropy_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid secret hex: {}", e)))?;
+    let groups: Vec<GroupSpec> = serde_json::from_str(groups_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid groups JSON: {}", e)))?;
+    let options: SplitOptions = match options_json {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?,
+        _ => SplitOptions::default(),
+    };
+    let groups: Vec<(u8, u8)> = groups
+        .into_iter()
+        .map(|group| match group {
+            GroupSpec::Pair(pair) => pair,
+            GroupSpec::Object { threshold, count } => (threshold, count),
+        })
+        .collect();
+    let passphrase = check_passphrase(passphrase.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let iteration_exponent = options.iteration_exponent.unwrap_or(1);
+    let extendable = options.extendable.unwrap_or(true);
+
+    let split = split(&secret, group_threshold, &groups, passphrase, iteration_exponent, extendable)
+        .map_err(|e| JsValue::from_str(&e))?;
+    to_json(&split, compact)
+}
+
+/// Recovers the master secret from SLIP-39 share mnemonics, given as a JSON array. Any
+/// enough shares will do, in any order; a wrong passphrase gives a different secret
+/// rather than an error, as SLIP-39 intends.
+#[wasm_bindgen]
+pub fn combine_slip39_shares(shares_json: &str, passphrase: Option<String>) -> Result<String, JsValue> {
+    let mnemonics: Vec<String> = serde_json::from_str(shares_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid shares JSON: {}", e)))?;
+    let passphrase = check_passphrase(passphrase.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+    let secret = combine(&mnemonics, passphrase).map_err(|e| JsValue::from_str(&e))?;
+    Ok(bytes_to_hex(&secret))
+}
+
+fn split(
+    secret: &[u8],
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+    passphrase: &str,
+    iteration_exponent: u8,
+    extendable: bool,
+) -> Result<Slip39Split, String> {
+    if !(16..=32).contains(&secret.len()) || !secret.len().is_multiple_of(2) {
+        return Err(format!(
+            "The master secret must be 16 to 32 bytes, an even number, got {}",
+            secret.len()
+        ));
+    }
+    if iteration_exponent > 15 {
+        return Err(format!("iterationExponent is 4 bits, so at most 15, got {}", iteration_exponent));
+    }
+    if groups.is_empty() || groups.len() > MAX_SHARES as usize {
+        return Err(format!("Between 1 and {} groups are needed, got {}", MAX_SHARES, groups.len()));
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() {
+        return Err(format!(
+            "The group threshold must be between 1 and the number of groups ({}), got {}",
+            groups.len(),
+            group_threshold
+        ));
+    }
+    for (position, &(threshold, count)) in groups.iter().enumerate() {
+        if count == 0 || count > MAX_SHARES || threshold == 0 || threshold > count {
+            return Err(format!(
+                "Group {} must have 1 to {} shares and a threshold between 1 and its share count, got {} of {}",
+                position + 1,
+                MAX_SHARES,
+                threshold,
+                count
+            ));
+        }
+        if threshold == 1 && count > 1 {
+            return Err(format!(
+                "Group {} is 1-of-{}: use a single share instead, since every copy would be the whole group secret",
+                position + 1,
+                count
+            ));
+        }
+    }
+
+    let identifier = with_rng(|rng| rng.next_u32()) as u16 & 0x7fff;
+    let encrypted = feistel(secret, passphrase, iteration_exponent, identifier, extendable, false);
+    let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted);
+
+    let mut result = Vec::with_capacity(groups.len());
+    for (&(member_threshold, member_count), (group_index, group_secret)) in groups.iter().zip(group_secrets) {
+        let shares = split_secret(member_threshold, member_count, &group_secret)
+            .into_iter()
+            .map(|(member_index, value)| {
+                Share {
+                    identifier,
+                    extendable,
+                    iteration_exponent,
+                    group_index,
+                    group_threshold,
+                    group_count: groups.len() as u8,
+                    member_index,
+                    member_threshold,
+                    value,
+                }
+                .to_mnemonic()
+            })
+            .collect();
+        result.push(Slip39Group { member_threshold, shares });
+    }
+
+    Ok(Slip39Split { identifier, extendable, iteration_exponent, group_threshold, groups: result })
+}
+
+fn combine(mnemonics: &[String], passphrase: &str) -> Result<Vec<u8>, String> {
+    if mnemonics.is_empty() {
+        return Err("At least one share is required".to_string());
+    }
+    let shares = mnemonics
+        .iter()
+        .enumerate()
+        .map(|(position, mnemonic)| Share::from_mnemonic(mnemonic).map_err(|e| format!("Share {}: {}", position + 1, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let first = &shares[0];
+    let mut groups: BTreeMap<u8, Vec<(usize, &Share)>> = BTreeMap::new();
+    for (position, share) in shares.iter().enumerate() {
+        if (share.identifier, share.extendable, share.iteration_exponent)
+            != (first.identifier, first.extendable, first.iteration_exponent)
+        {
+            return Err(format!(
+                "Share {} belongs to a different split: it starts with \"{}\", share 1 with \"{}\"",
+                position + 1,
+                first_words(&mnemonics[position]),
+                first_words(&mnemonics[0])
+            ));
+        }
+        if share.value.len() != first.value.len() {
+            return Err(format!("Share {} has a different length than share 1, so it's from a different split", position + 1));
+        }
+        if (share.group_threshold, share.group_count) != (first.group_threshold, first.group_count) {
+            return Err(format!(
+                "Share {} says {} of {} groups are needed, share 1 says {} of {}, so they're from different splits",
+                position + 1,
+                share.group_threshold,
+                share.group_count,
+                first.group_threshold,
+                first.group_count
+            ));
+        }
+        let group = groups.entry(share.group_index).or_default();
+        if let Some(&(other, other_share)) = group.first() {
+            if other_share.member_threshold != share.member_threshold {
+                return Err(format!(
+                    "Shares {} and {} are both in group {} but disagree on its threshold ({} and {})",
+                    other + 1,
+                    position + 1,
+                    share.group_index + 1,
+                    other_share.member_threshold,
+                    share.member_threshold
+                ));
+            }
+        }
+        match group.iter().find(|(_, other)| other.member_index == share.member_index) {
+            Some((other, other_share)) if other_share.value != share.value => {
+                return Err(format!(
+                    "Shares {} and {} are both share {} of group {} but differ",
+                    other + 1,
+                    position + 1,
+                    share.member_index + 1,
+                    share.group_index + 1
+                ))
+            }
+            Some(_) => {}
+            None => group.push((position, share)),
+        }
+    }
+
+    let mut group_secrets = Vec::new();
+    let mut incomplete = Vec::new();
+    for (&group_index, members) in &groups {
+        let threshold = members[0].1.member_threshold as usize;
+        if members.len() < threshold {
+            incomplete.push(format!("group {} has {} of {} shares", group_index + 1, members.len(), threshold));
+            continue;
+        }
+        let points: Vec<(u8, Vec<u8>)> =
+            members[..threshold].iter().map(|(_, share)| (share.member_index, share.value.clone())).collect();
+        let secret = recover_secret(threshold as u8, &points)
+            .map_err(|e| format!("Group {}: {}", group_index + 1, e))?;
+        group_secrets.push((group_index, secret));
+    }
+    let group_threshold = first.group_threshold as usize;
+    if group_secrets.len() < group_threshold && first.group_count == 1 {
+        return Err(format!("Not enough shares: {}", incomplete.join(", ")));
+    }
+    if group_secrets.len() < group_threshold {
+        let mut message = format!(
+            "Not enough shares: {} of {} groups are needed and {} {} complete",
+            group_threshold,
+            first.group_count,
+            group_secrets.len(),
+            if group_secrets.len() == 1 { "is" } else { "are" }
+        );
+        if !incomplete.is_empty() {
+            message.push_str(&format!(" ({})", incomplete.join(", ")));
+        }
+        return Err(message);
+    }
+
+    group_secrets.truncate(group_threshold);
+    let encrypted = recover_secret(first.group_threshold, &group_secrets)?;
+    Ok(feistel(&encrypted, passphrase, first.iteration_exponent, first.identifier, first.extendable, true))
+}
+
+impl Share {
+    fn to_mnemonic(&self) -> String {
+        let mut bits = Vec::new();
+        push_bits(&mut bits, self.identifier as u32, 15);
+        push_bits(&mut bits, self.extendable as u32, 1);
+        push_bits(&mut bits, self.iteration_exponent as u32, 4);
+        push_bits(&mut bits, self.group_index as u32, 4);
+        push_bits(&mut bits, self.group_threshold as u32 - 1, 4);
+        push_bits(&mut bits, self.group_count as u32 - 1, 4);
+        push_bits(&mut bits, self.member_index as u32, 4);
+        push_bits(&mut bits, self.member_threshold as u32 - 1, 4);
+        let value_bits = self.value.len() * 8;
+        let padding = value_bits.div_ceil(RADIX_BITS) * RADIX_BITS - value_bits;
+        bits.extend(std::iter::repeat_n(false, padding));
+        for &byte in &self.value {
+            push_bits(&mut bits, byte as u32, 8);
+        }
+
+        let mut indexes: Vec<u32> = bits.chunks(RADIX_BITS).map(read_bits).collect();
+        let checksum = create_checksum(&indexes, self.extendable);
+        indexes.extend(checksum);
+        let words = slip39_wordlist();
+        indexes.iter().map(|&index| words[index as usize]).collect::<Vec<_>>().join(" ")
+    }
+
+    fn from_mnemonic(mnemonic: &str) -> Result<Share, String> {
+        let words = slip39_wordlist();
+        let indexes = mnemonic
+            .split_whitespace()
+            .enumerate()
+            .map(|(position, word)| {
+                let word = word.to_lowercase();
+                words
+                    .binary_search(&word.as_str())
+                    .map(|index| index as u32)
+                    .map_err(|_| format!("word {} (\"{}\") is not in the SLIP-39 wordlist", position + 1, word))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if indexes.len() < MIN_SHARE_WORDS {
+            return Err(format!("a share has at least {} words, got {}", MIN_SHARE_WORDS, indexes.len()));
+        }
+        let padding = RADIX_BITS * (indexes.len() - METADATA_WORDS) % 16;
+        if padding > 8 {
+            return Err(format!("{} words is not a valid share length", indexes.len()));
+        }
+
+        let extendable = indexes[1] >> 4 & 1 == 1;
+        if !verify_checksum(&indexes, extendable) {
+            return Err("invalid checksum: a word is mistyped, missing or out of order".to_string());
+        }
+
+        let mut bits = Vec::with_capacity(indexes.len() * RADIX_BITS);
+        for &index in &indexes[..indexes.len() - CHECKSUM_WORDS] {
+            push_bits(&mut bits, index, RADIX_BITS);
+        }
+        let field = |start: usize, len: usize| read_bits(&bits[start..start + len]);
+        let share = Share {
+            identifier: field(0, 15) as u16,
+            extendable,
+            iteration_exponent: field(16, 4) as u8,
+            group_index: field(20, 4) as u8,
+            group_threshold: field(24, 4) as u8 + 1,
+            group_count: field(28, 4) as u8 + 1,
+            member_index: field(32, 4) as u8,
+            member_threshold: field(36, 4) as u8 + 1,
+            value: Vec::new(),
+        };
+        if share.group_threshold > share.group_count {
+            return Err(format!(
+                "it needs {} groups but says there are only {}",
+                share.group_threshold, share.group_count
+            ));
+        }
+        let value_bits = &bits[40..];
+        if value_bits[..padding].iter().any(|&bit| bit) {
+            return Err("invalid padding: the first value word is too large".to_string());
+        }
+        let value = value_bits[padding..].chunks(8).map(|chunk| read_bits(chunk) as u8).collect();
+        Ok(Share { value, ..share })
+    }
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, len: usize) {
+    bits.extend((0..len).rev().map(|i| value >> i & 1 == 1));
+}
+
+fn read_bits(bits: &[bool]) -> u32 {
+    bits.iter().fold(0, |value, &bit| value << 1 | bit as u32)
+}
+
+fn first_words(mnemonic: &str) -> String {
+    mnemonic.split_whitespace().take(2).collect::<Vec<_>>().join(" ")
+}
+
+fn check_passphrase(passphrase: Option<&str>) -> Result<&str, String> {
+    let passphrase = passphrase.unwrap_or("");
+    if !passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+        return Err("SLIP-39 passphrases may only contain printable ASCII characters".to_string());
+    }
+    Ok(passphrase)
+}
+
+fn rs1024_polymod(values: impl Iterator<Item = u32>) -> u32 {
+    const GEN: [u32; 10] = [
+        0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009, 0x1C0C2412, 0x38086C24, 0x3090FC48, 0x21B1F890, 0x3F3F120,
+    ];
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 20;
+        checksum = (checksum & 0xFFFFF) << 10 ^ value;
+        for (i, generator) in GEN.iter().enumerate() {
+            if top >> i & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable {
+        CUSTOMIZATION_EXTENDABLE
+    } else {
+        CUSTOMIZATION
+    }
+}
+
+fn create_checksum(data: &[u32], extendable: bool) -> [u32; CHECKSUM_WORDS] {
+    let values = customization(extendable)
+        .iter()
+        .map(|&b| b as u32)
+        .chain(data.iter().copied())
+        .chain([0; CHECKSUM_WORDS]);
+    let polymod = rs1024_polymod(values) ^ 1;
+    [polymod >> 20 & 1023, polymod >> 10 & 1023, polymod & 1023]
+}
+
+fn verify_checksum(data: &[u32], extendable: bool) -> bool {
+    rs1024_polymod(customization(extendable).iter().map(|&b| b as u32).chain(data.iter().copied())) == 1
+}
+
+/// Exponent and logarithm tables for GF(256) with the AES polynomial, generator 3.
+fn gf_tables() -> &'static ([u8; 255], [u8; 256]) {
+    static TABLES: OnceLock<([u8; 255], [u8; 256])> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut exp = [0u8; 255];
+        let mut log = [0u8; 256];
+        let mut poly: u16 = 1;
+        for (i, slot) in exp.iter_mut().enumerate() {
+            *slot = poly as u8;
+            log[poly as usize] = i as u8;
+            poly = (poly << 1) ^ poly;
+            if poly & 0x100 != 0 {
+                poly ^= 0x11B;
+            }
+        }
+        (exp, log)
+    })
+}
+
+/// Lagrange interpolation at `x` of the polynomial through `points`, byte by byte.
+fn interpolate(points: &[(u8, Vec<u8>)], x: u8) -> Result<Vec<u8>, String> {
+    let mut xs: Vec<u8> = points.iter().map(|(px, _)| *px).collect();
+    xs.sort_unstable();
+    xs.dedup();
+    if xs.len() != points.len() {
+        return Err("two shares have the same index".to_string());
+    }
+    if let Some((_, value)) = points.iter().find(|(px, _)| *px == x) {
+        return Ok(value.clone());
+    }
+
+    let (exp, log) = gf_tables();
+    let log = |v: u8| log[v as usize] as u32;
+    let log_product: u32 = points.iter().map(|(px, _)| log(px ^ x)).sum();
+    let mut result = vec![0u8; points[0].1.len()];
+    for (px, value) in points {
+        let others: u32 = points.iter().filter(|(ox, _)| ox != px).map(|(ox, _)| log(px ^ ox)).sum();
+        let basis = (log_product + 255 * points.len() as u32 - log(px ^ x) - others) % 255;
+        for (out, &byte) in result.iter_mut().zip(value) {
+            if byte != 0 {
+                *out ^= exp[((log(byte) + basis) % 255) as usize];
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn share_digest(random: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut engine = HmacEngine::<sha256::Hash>::new(random);
+    engine.input(secret);
+    Hmac::<sha256::Hash>::from_engine(engine)[..DIGEST_LEN].to_vec()
+}
+
+/// Shamir's split with SLIP-39's digest: the secret sits at x = 255 and a keyed digest of
+/// it at x = 254, so a wrong combination is caught instead of giving a random secret.
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    if threshold == 1 {
+        return (0..count).map(|index| (index, secret.to_vec())).collect();
+    }
+    let random_bytes = |len: usize| {
+        let mut bytes = vec![0u8; len];
+        with_rng(|rng| rng.fill_bytes(&mut bytes));
+        bytes
+    };
+
+    let mut shares: Vec<(u8, Vec<u8>)> = (0..threshold - 2).map(|index| (index, random_bytes(secret.len()))).collect();
+    let random_part = random_bytes(secret.len() - DIGEST_LEN);
+    let mut digest = share_digest(&random_part, secret);
+    digest.extend(&random_part);
+    let mut base = shares.clone();
+    base.push((DIGEST_INDEX, digest));
+    base.push((SECRET_INDEX, secret.to_vec()));
+    for index in threshold - 2..count {
+        shares.push((index, interpolate(&base, index).expect("distinct indexes")));
+    }
+    shares
+}
+
+fn recover_secret(threshold: u8, points: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    if threshold == 1 {
+        return Ok(points[0].1.clone());
+    }
+    let secret = interpolate(points, SECRET_INDEX)?;
+    let digest = interpolate(points, DIGEST_INDEX)?;
+    if share_digest(&digest[DIGEST_LEN..], &secret) != digest[..DIGEST_LEN] {
+        return Err("the shares don't fit together: one of them is from a different split or was mistyped".to_string());
+    }
+    Ok(secret)
+}
+
+/// SLIP-39's four-round Feistel cipher over the master secret, keyed by the passphrase
+/// through PBKDF2-HMAC-SHA256. Decryption runs the rounds backwards.
+fn feistel(data: &[u8], passphrase: &str, iteration_exponent: u8, identifier: u16, extendable: bool, decrypt: bool) -> Vec<u8> {
+    let half = data.len() / 2;
+    let (mut left, mut right) = (data[..half].to_vec(), data[half..].to_vec());
+    let mut salt = if extendable { Vec::new() } else { [CUSTOMIZATION, &identifier.to_be_bytes()].concat() };
+    let salt_len = salt.len();
+    let iterations = (BASE_ITERATIONS << iteration_exponent) / ROUNDS as u32;
+
+    let rounds: Vec<u8> = if decrypt { (0..ROUNDS).rev().collect() } else { (0..ROUNDS).collect() };
+    for round in rounds {
+        salt.truncate(salt_len);
+        salt.extend(&right);
+        let password = [&[round], passphrase.as_bytes()].concat();
+        let key = pbkdf2_hmac::<sha256::Hash>(&password, &salt, iterations, right.len());
+        let mixed: Vec<u8> = left.iter().zip(&key).map(|(l, k)| l ^ k).collect();
+        left = std::mem::replace(&mut right, mixed);
+    }
+    [right, left].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The vectors are from the SLIP-39 reference implementation's vectors.json, which
+    /// all use the passphrase "TREZOR".
+    fn combine_vector(mnemonics: &[&str]) -> Result<String, String> {
+        let mnemonics: Vec<String> = mnemonics.iter().map(|mnemonic| mnemonic.to_string()).collect();
+        combine(&mnemonics, "TREZOR").map(|secret| bytes_to_hex(&secret))
+    }
+
+    #[test]
+    fn single_share_vector() {
+        let secret = combine_vector(&[
+            "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard",
+        ]);
+        assert_eq!(secret.unwrap(), "bb54aac4b89dc868ba37d9cc21b2cece");
+    }
+
+    #[test]
+    fn two_of_three_vector() {
+        let secret = combine_vector(&[
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+            "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+        ]);
+        assert_eq!(secret.unwrap(), "b43ceb7e57a0ea8766221624d01b0864");
+    }
+
+    #[test]
+    fn invalid_checksum_vector() {
+        let error = combine_vector(&[
+            "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney",
+        ]);
+        assert!(error.unwrap_err().contains("invalid checksum"));
+    }
+
+    #[test]
+    fn invalid_padding_vector() {
+        let error = combine_vector(&[
+            "duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness",
+        ]);
+        assert!(error.unwrap_err().contains("invalid padding"));
+    }
+}
@@ -0,0 +1,485 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::{Keypair, Parity, TapTweak, UntweakedPublicKey, XOnlyPublicKey};
+use bitcoin::secp256k1::{Scalar, Secp256k1, Verification};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TapNodeHash, TapTweakHash, TaprootBuilder};
+use bitcoin::{Network, ScriptBuf};
+use crate::transaction::address_script;
+use crate::utils::network::{network_or_default, require_address_network};
+use crate::utils::{bytes_to_hex, hex_to_bytes, HexMode};
+use crate::wallet::keys::parse_public_key;
+use crate::wallet::musig::{key_agg, key_sort, parse_participant_keys};
+use crate::wallet::parse_private_key;
+use crate::utils::json::to_json;
+
+/// Tag of the pay-to-contract tweak, kept apart from BIP341's TapTweak so a commitment
+/// can't be mistaken for a script tree.
+const P2C_TAG: &str = "PayToContract";
+/// BIP341's suggested internal key with no known discrete log, for script-path-only outputs.
+const NUMS_POINT: &str = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TweakedKey {
+    #[serde(alias = "output_key")]
+    pub output_key: String,
+    pub parity: u8,
+    pub tweak: String,
+    #[serde(alias = "tweaked_private_key")]
+    pub tweaked_private_key: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitmentVerification {
+    pub valid: bool,
+    /// The output key the internal key and data give.
+    #[serde(alias = "expected_output_key")]
+    pub expected_output_key: String,
+    #[serde(alias = "output_key")]
+    pub output_key: String,
+}
+
+/// How a counterparty says a taproot output key was made. Exactly one of `internalKey`
+/// (x-only or compressed hex, or `"nums"`) and `musig` (participant keys) gives the
+/// internal key; `leaves` or `merkleRoot` gives the script tree, if there is one.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaprootClaim {
+    #[serde(default, alias = "internal_key")]
+    pub internal_key: Option<String>,
+    #[serde(default)]
+    pub musig: Option<Vec<String>>,
+    /// Whether the MuSig2 keys go through KeySort first. Defaults to true, as in `musig()`
+    /// descriptors.
+    #[serde(default)]
+    pub sort: Option<bool>,
+    #[serde(default)]
+    pub leaves: Option<Vec<ClaimedLeaf>>,
+    #[serde(default, alias = "merkle_root")]
+    pub merkle_root: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimedLeaf {
+    pub script: String,
+    /// Depth in the tree. Optional for one leaf (0) or two (1 each).
+    #[serde(default)]
+    pub depth: Option<u8>,
+    /// Defaults to 0xc0, tapscript.
+    #[serde(default, alias = "leaf_version")]
+    pub leaf_version: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiedLeaf {
+    pub script: String,
+    pub depth: u8,
+    #[serde(alias = "leaf_version")]
+    pub leaf_version: u8,
+    #[serde(alias = "leaf_hash")]
+    pub leaf_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaprootAddressVerification {
+    pub matches: bool,
+    /// The output key in the address.
+    #[serde(alias = "output_key")]
+    pub output_key: String,
+    /// The output key the claim gives.
+    #[serde(alias = "expected_output_key")]
+    pub expected_output_key: String,
+    #[serde(alias = "internal_key")]
+    pub internal_key: String,
+    /// `single`, `musig` or `nums`.
+    #[serde(alias = "internal_key_kind")]
+    pub internal_key_kind: String,
+    /// The MuSig2 participant keys in the order they were aggregated.
+    #[serde(default, alias = "musig_keys")]
+    pub musig_keys: Option<Vec<String>>,
+    #[serde(alias = "merkle_root")]
+    pub merkle_root: Option<String>,
+    #[serde(default)]
+    pub leaves: Vec<VerifiedLeaf>,
+    pub tweak: String,
+    pub parity: u8,
+    /// Which part of the claim disagrees with the address, when it can be pinned down.
+    pub mismatch: Option<String>,
+}
+
+/// Recomputes a P2TR output key from `claim_json` (a `TaprootClaim`) and checks it
+/// against `address`, e.g. before paying an address a counterparty says is a 2-of-2
+/// MuSig2 between you. On a mismatch, the likely variations (an untweaked key, no script
+/// tree, the other key order, one participant's key alone) are tried to say which part
+/// of the claim is wrong.
+#[wasm_bindgen]
+pub fn verify_taproot_address(address: &str, claim_json: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::verification_only();
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let script_pubkey = require_address_network(address.trim(), Some(&network)).map_err(|e| JsValue::from_str(&e))?;
+    if !script_pubkey.is_p2tr() {
+        return Err(JsValue::from_str("Address is not P2TR"));
+    }
+    let output_key = parse_xonly(&bytes_to_hex(&script_pubkey.as_bytes()[2..]))?;
+    let claim: TaprootClaim = serde_json::from_str(claim_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid claim JSON: {}", e)))?;
+
+    let participants = claim.musig.as_deref().map(parse_participant_keys).transpose()?;
+    let sorted = claim.sort.unwrap_or(true);
+    let (internal_key, internal_key_kind, musig_keys) = match (&claim.internal_key, &participants) {
+        (Some(_), Some(_)) => return Err(JsValue::from_str("Give internalKey or musig, not both")),
+        (None, None) => return Err(JsValue::from_str("The claim needs internalKey or musig")),
+        (Some(key), None) if key.trim().eq_ignore_ascii_case("nums") => (parse_xonly(NUMS_POINT)?, "nums", None),
+        (Some(key), None) => (parse_internal_key(key)?, "single", None),
+        (None, Some(keys)) => {
+            let mut keys = keys.clone();
+            if sorted {
+                key_sort(&mut keys);
+            }
+            let aggregate = key_agg(&secp, &keys).map_err(|e| JsValue::from_str(&e))?;
+            (aggregate.x_only_public_key().0, "musig", Some(keys))
+        }
+    };
+    let (merkle_root, leaves) = match (claim.leaves, claim.merkle_root) {
+        (Some(_), Some(_)) => return Err(JsValue::from_str("Give leaves or merkleRoot, not both")),
+        (Some(leaves), None) => claimed_tree(&secp, internal_key, &leaves).map_err(|e| JsValue::from_str(&e))?,
+        (None, merkle_root) => (parse_merkle_root(merkle_root)?, Vec::new()),
+    };
+
+    let (expected, parity) = internal_key.tap_tweak(&secp, merkle_root);
+    let expected = expected.to_x_only_public_key();
+    let mismatch = (expected != output_key).then(|| {
+        let tweaked = |key: UntweakedPublicKey, root: Option<TapNodeHash>| key.tap_tweak(&secp, root).0.to_x_only_public_key();
+        if output_key == internal_key {
+            return "the address uses the internal key itself, without the BIP341 tweak".to_string();
+        }
+        if merkle_root.is_some() && tweaked(internal_key, None) == output_key {
+            return "the address has the claimed internal key but no script tree: the claimed leaves aren't in it".to_string();
+        }
+        if let Some(keys) = &participants {
+            // The order that wasn't used: as given when sorting, sorted when not.
+            let mut other = keys.clone();
+            if !sorted {
+                key_sort(&mut other);
+            }
+            if musig_keys.as_ref() != Some(&other) {
+                if let Ok(aggregate) = key_agg(&secp, &other) {
+                    if tweaked(aggregate.x_only_public_key().0, merkle_root) == output_key {
+                        return if sorted {
+                            "the MuSig2 keys were aggregated in the order given, not sorted".to_string()
+                        } else {
+                            "the MuSig2 keys were sorted before aggregation, not used in the order given".to_string()
+                        };
+                    }
+                }
+            }
+            for (index, key) in keys.iter().enumerate() {
+                let alone = key.x_only_public_key().0;
+                if alone == output_key || tweaked(alone, merkle_root) == output_key || tweaked(alone, None) == output_key {
+                    return format!("the address is musig[{}]'s key alone, not the MuSig2 aggregate", index);
+                }
+            }
+        }
+        match merkle_root {
+            Some(_) => "the output key doesn't match: the internal key, the script tree, or both differ from the claim".to_string(),
+            None => "the output key doesn't match: the internal key differs, or the address commits to a script tree the claim doesn't list".to_string(),
+        }
+    });
+
+    let result = TaprootAddressVerification {
+        matches: mismatch.is_none(),
+        output_key: bytes_to_hex(&output_key.serialize()),
+        expected_output_key: bytes_to_hex(&expected.serialize()),
+        internal_key: bytes_to_hex(&internal_key.serialize()),
+        internal_key_kind: internal_key_kind.to_string(),
+        musig_keys: musig_keys.map(|keys| keys.iter().map(|key| bytes_to_hex(&key.serialize())).collect()),
+        merkle_root: merkle_root.map(|root| root.to_string()),
+        leaves,
+        tweak: tweak_hex(internal_key, merkle_root),
+        parity: parity_bit(parity),
+        mismatch,
+    };
+    to_json(&result, compact)
+}
+
+#[wasm_bindgen]
+pub fn taproot_tweak_pubkey(internal_xonly_hex: &str, merkle_root_hex: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::verification_only();
+    let internal_key = parse_xonly(internal_xonly_hex)?;
+    let merkle_root = parse_merkle_root(merkle_root_hex)?;
+
+    let (output_key, parity) = internal_key.tap_tweak(&secp, merkle_root);
+    let result = TweakedKey {
+        output_key: bytes_to_hex(&output_key.to_x_only_public_key().serialize()),
+        parity: parity_bit(parity),
+        tweak: tweak_hex(internal_key, merkle_root),
+        tweaked_private_key: None,
+        address: None,
+    };
+
+    to_json(&result, compact)
+}
+
+#[wasm_bindgen]
+pub fn taproot_tweak_privkey(privkey_hex: &str, merkle_root_hex: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let private_key = parse_private_key(privkey_hex, None, Network::Testnet)?;
+    let merkle_root = parse_merkle_root(merkle_root_hex)?;
+
+    let keypair = Keypair::from_secret_key(&secp, &private_key.inner);
+    let (internal_key, _) = keypair.x_only_public_key();
+    let tweaked = keypair.tap_tweak(&secp, merkle_root).to_keypair();
+    let (output_key, parity) = tweaked.x_only_public_key();
+
+    let result = TweakedKey {
+        output_key: bytes_to_hex(&output_key.serialize()),
+        parity: parity_bit(parity),
+        tweak: tweak_hex(internal_key, merkle_root),
+        tweaked_private_key: Some(bytes_to_hex(&tweaked.secret_bytes())),
+        address: None,
+    };
+
+    to_json(&result, compact)
+}
+
+/// Commits to `data_hex` in a P2TR output by tweaking the internal key with
+/// `tagged_hash("PayToContract", internal_key || data)` instead of a script tree root. The
+/// output looks like any key-path-only taproot output; `commit_privkey_tweak` gives the
+/// key that spends it.
+#[wasm_bindgen]
+pub fn commit_to_data(internal_pubkey_hex: &str, data_hex: &str, network: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+    let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+    let secp = Secp256k1::verification_only();
+    let internal_key = parse_xonly(internal_pubkey_hex)?;
+    let tweak = contract_tweak(&internal_key, &parse_data(data_hex)?)?;
+
+    let (output_key, parity) = internal_key
+        .add_tweak(&secp, &tweak)
+        .map_err(|e| JsValue::from_str(&format!("Failed to tweak key: {}", e)))?;
+    let script_pubkey = ScriptBuf::new_p2tr_tweaked(bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(output_key));
+    let result = TweakedKey {
+        output_key: bytes_to_hex(&output_key.serialize()),
+        parity: parity_bit(parity),
+        tweak: bytes_to_hex(&tweak.to_be_bytes()),
+        tweaked_private_key: None,
+        address: network.address(&script_pubkey),
+    };
+
+    to_json(&result, compact)
+}
+
+/// Checks that a P2TR address (of any network) or 32-byte output key commits to
+/// `data_hex` under `internal_pubkey_hex`, as `commit_to_data` builds it.
+#[wasm_bindgen]
+pub fn verify_commitment(address_or_output_key: &str, internal_pubkey_hex: &str, data_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::verification_only();
+    let given = address_or_output_key.trim();
+    let output_key = match hex_to_bytes(given, HexMode::Lenient) {
+        Ok(bytes) if bytes.len() == 32 => parse_xonly(given)?,
+        _ => {
+            let script_pubkey = address_script(given, None)
+                .map_err(|_| JsValue::from_str("Expected a P2TR address or a 32-byte x-only output key as hex"))?;
+            if !script_pubkey.is_p2tr() {
+                return Err(JsValue::from_str("Address is not P2TR, so it can't hold a taproot commitment"));
+            }
+            parse_xonly(&bytes_to_hex(&script_pubkey.as_bytes()[2..]))?
+        }
+    };
+    let internal_key = parse_xonly(internal_pubkey_hex)?;
+    let tweak = contract_tweak(&internal_key, &parse_data(data_hex)?)?;
+    let (expected, _) = internal_key
+        .add_tweak(&secp, &tweak)
+        .map_err(|e| JsValue::from_str(&format!("Failed to tweak key: {}", e)))?;
+
+    let result = CommitmentVerification {
+        valid: expected == output_key,
+        expected_output_key: bytes_to_hex(&expected.serialize()),
+        output_key: bytes_to_hex(&output_key.serialize()),
+    };
+    to_json(&result, compact)
+}
+
+/// The private key that spends a `commit_to_data` output on the key path: the internal
+/// private key, negated if its public key has an odd y, plus the tweak.
+#[wasm_bindgen]
+pub fn commit_privkey_tweak(privkey_hex: &str, data_hex: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let secp = Secp256k1::new();
+    let private_key = parse_private_key(privkey_hex, None, Network::Testnet)?;
+    let keypair = Keypair::from_secret_key(&secp, &private_key.inner);
+    let (internal_key, _) = keypair.x_only_public_key();
+    let tweak = contract_tweak(&internal_key, &parse_data(data_hex)?)?;
+
+    let tweaked = keypair
+        .add_xonly_tweak(&secp, &tweak)
+        .map_err(|e| JsValue::from_str(&format!("Failed to tweak key: {}", e)))?;
+    let (output_key, parity) = tweaked.x_only_public_key();
+    let result = TweakedKey {
+        output_key: bytes_to_hex(&output_key.serialize()),
+        parity: parity_bit(parity),
+        tweak: bytes_to_hex(&tweak.to_be_bytes()),
+        tweaked_private_key: Some(bytes_to_hex(&tweaked.secret_bytes())),
+        address: None,
+    };
+
+    to_json(&result, compact)
+}
+
+#[wasm_bindgen]
+pub fn xonly_from_pubkey(pubkey_hex: &str) -> Result<String, JsValue> {
+    let pubkey = parse_public_key(pubkey_hex)?;
+    Ok(bytes_to_hex(&pubkey.inner.x_only_public_key().0.serialize()))
+}
+
+pub fn parse_xonly(xonly_hex: &str) -> Result<UntweakedPublicKey, JsValue> {
+    let bytes = hex_to_bytes(xonly_hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid x-only key hex: {}", e)))?;
+    XOnlyPublicKey::from_slice(&bytes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid x-only key (expected 32 bytes on the curve): {}", e)))
+}
+
+pub fn parse_merkle_root(merkle_root_hex: Option<String>) -> Result<Option<TapNodeHash>, JsValue> {
+    let Some(hex) = merkle_root_hex.filter(|hex| !hex.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let bytes: [u8; 32] = hex_to_bytes(&hex, HexMode::Lenient)
+        .map_err(|e| JsValue::from_str(&format!("Invalid merkle root hex: {}", e)))?
+        .try_into()
+        .map_err(|_| JsValue::from_str("Merkle root must be 32 bytes"))?;
+    Ok(Some(TapNodeHash::from_byte_array(bytes)))
+}
+
+/// An x-only key, or a compressed one whose x coordinate is the internal key.
+fn parse_internal_key(key: &str) -> Result<UntweakedPublicKey, JsValue> {
+    match hex_to_bytes(key, HexMode::Lenient) {
+        Ok(bytes) if bytes.len() == 33 => Ok(parse_public_key(key)?.inner.x_only_public_key().0),
+        _ => parse_xonly(key),
+    }
+}
+
+/// Builds the claimed script tree and returns its merkle root with each leaf's hash.
+fn claimed_tree<C: Verification>(
+    secp: &Secp256k1<C>,
+    internal_key: UntweakedPublicKey,
+    leaves: &[ClaimedLeaf],
+) -> Result<(Option<TapNodeHash>, Vec<VerifiedLeaf>), String> {
+    if leaves.is_empty() {
+        return Ok((None, Vec::new()));
+    }
+    let default_depth = match leaves.len() {
+        1 => Some(0),
+        2 => Some(1),
+        _ => None,
+    };
+    let mut builder = TaprootBuilder::new();
+    let mut verified = Vec::with_capacity(leaves.len());
+    for (index, leaf) in leaves.iter().enumerate() {
+        let depth = leaf
+            .depth
+            .or(default_depth)
+            .ok_or_else(|| format!("leaves[{}] needs a depth: with more than two leaves the tree's shape isn't implied", index))?;
+        let script = ScriptBuf::from_bytes(
+            hex_to_bytes(&leaf.script, HexMode::Lenient).map_err(|e| format!("leaves[{}]: Invalid script hex: {}", index, e))?,
+        );
+        let version = LeafVersion::from_consensus(leaf.leaf_version.unwrap_or(0xc0))
+            .map_err(|e| format!("leaves[{}]: Invalid leaf version: {}", index, e))?;
+        verified.push(VerifiedLeaf {
+            script: bytes_to_hex(script.as_bytes()),
+            depth,
+            leaf_version: version.to_consensus(),
+            leaf_hash: TapLeafHash::from_script(&script, version).to_string(),
+        });
+        builder = builder
+            .add_leaf_with_ver(depth, script, version)
+            .map_err(|e| format!("leaves[{}]: {}", index, e))?;
+    }
+    let spend_info = builder
+        .finalize(secp, internal_key)
+        .map_err(|_| "The leaf depths don't make a complete tree: every branch needs two children".to_string())?;
+    Ok((spend_info.merkle_root(), verified))
+}
+
+fn parse_data(data_hex: &str) -> Result<Vec<u8>, JsValue> {
+    hex_to_bytes(data_hex, HexMode::Lenient).map_err(|e| JsValue::from_str(&format!("Invalid data hex: {}", e)))
+}
+
+/// BIP340-style tagged hash of the internal key and the data. Committing to the key too
+/// stops the same data tweak being replayed onto a different key.
+fn contract_tweak(internal_key: &XOnlyPublicKey, data: &[u8]) -> Result<Scalar, JsValue> {
+    let tag = sha256::Hash::hash(P2C_TAG.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(&internal_key.serialize());
+    engine.input(data);
+    Scalar::from_be_bytes(sha256::Hash::from_engine(engine).to_byte_array())
+        .map_err(|_| JsValue::from_str("Commitment tweak is not a valid scalar; vary the data"))
+}
+
+fn tweak_hex(internal_key: UntweakedPublicKey, merkle_root: Option<TapNodeHash>) -> String {
+    bytes_to_hex(&TapTweakHash::from_key_and_tweak(internal_key, merkle_root).to_byte_array())
+}
+
+fn parity_bit(parity: Parity) -> u8 {
+    match parity {
+        Parity::Even => 0,
+        Parity::Odd => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tweaked(json: &str) -> TweakedKey {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// BIP341 wallet-test-vectors.json, scriptPubKey 0: no script tree.
+    #[test]
+    fn bip341_key_path_only_vector() {
+        let result = tweaked(&taproot_tweak_pubkey("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d", None, None).unwrap());
+        assert_eq!(result.tweak, "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70");
+        assert_eq!(result.output_key, "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+    }
+
+    /// BIP341 wallet-test-vectors.json, scriptPubKey 1: a single leaf.
+    #[test]
+    fn bip341_script_tree_vector() {
+        let result = tweaked(
+            &taproot_tweak_pubkey(
+                "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+                Some("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21".to_string()),
+                None,
+            )
+            .unwrap(),
+        );
+        assert_eq!(result.tweak, "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001");
+        assert_eq!(result.output_key, "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+    }
+
+    /// BIP341 wallet-test-vectors.json, keyPathSpending input 0.
+    #[test]
+    fn bip341_private_key_vector() {
+        let result = tweaked(&taproot_tweak_privkey("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa", None, None).unwrap());
+        assert_eq!(result.tweaked_private_key.unwrap(), "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9");
+    }
+
+    #[test]
+    fn private_and_public_tweaks_agree() {
+        let root = Some("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21".to_string());
+        let secret = "11".repeat(32);
+        let pubkey = Keypair::from_seckey_slice(&Secp256k1::new(), &hex_to_bytes(&secret, HexMode::Strict).unwrap()).unwrap().public_key();
+        let xonly = xonly_from_pubkey(&bytes_to_hex(&pubkey.serialize())).unwrap();
+
+        let private = tweaked(&taproot_tweak_privkey(&secret, root.clone(), None).unwrap());
+        let public = tweaked(&taproot_tweak_pubkey(&xonly, root, None).unwrap());
+        assert_eq!((private.output_key, private.parity, private.tweak), (public.output_key, public.parity, public.tweak));
+    }
+}
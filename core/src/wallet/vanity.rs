@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::key::CompressedPublicKey;
+use bitcoin::secp256k1::{All, Secp256k1, SecretKey};
+use bitcoin::{base58, PrivateKey, ScriptBuf};
+use crate::utils::bytes_to_hex;
+use crate::utils::network::{network_or_default, NetworkParams};
+use crate::utils::rng::with_rng;
+use crate::utils::step::{check_work_units, step_json, to_result, Progress, RawValue};
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BASE58_CHARSET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Clone, Copy)]
+enum VanityType {
+    Legacy,
+    Segwit,
+    NestedSegwit,
+    Taproot,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VanityMatch {
+    pub address: String,
+    #[serde(alias = "private_key")]
+    pub private_key: String,
+    pub wif: String,
+    pub attempts: u64,
+}
+
+/// Searches random keys for an address starting with `pattern`. Each
+/// `step(max_work_units)` tries up to that many keys; progress has no `total` but an
+/// `expected` number of attempts. Bech32 patterns include the fixed `bc1q`/`bc1p` part
+/// and match case-insensitively; base58 patterns are case-sensitive.
+#[wasm_bindgen]
+pub struct VanitySearch {
+    secp: Secp256k1<All>,
+    address_type: VanityType,
+    network: NetworkParams,
+    pattern: String,
+    expected: f64,
+    attempts: u64,
+    result: Option<Box<RawValue>>,
+}
+
+#[wasm_bindgen]
+impl VanitySearch {
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: &str, address_type: &str, network: Option<String>) -> Result<VanitySearch, JsValue> {
+        let network = network_or_default(network).map_err(|e| JsValue::from_str(&e))?;
+        let address_type = match address_type {
+            "legacy" | "p2pkh" => VanityType::Legacy,
+            "segwit" | "p2wpkh" => VanityType::Segwit,
+            "nested_segwit" | "p2sh-p2wpkh" => VanityType::NestedSegwit,
+            "taproot" | "p2tr" => VanityType::Taproot,
+            other => {
+                return Err(JsValue::from_str(&format!(
+                    "Unknown address type '{}' (expected legacy, segwit, nested_segwit or taproot)",
+                    other
+                )))
+            }
+        };
+        let bech32 = matches!(address_type, VanityType::Segwit | VanityType::Taproot);
+        let pattern = pattern.trim();
+        let (pattern, expected) = if bech32 {
+            bech32_pattern(pattern, address_type, &network)
+        } else {
+            base58_pattern(pattern, address_type, &network)
+        }
+        .map_err(|e| JsValue::from_str(&e))?;
+
+        Ok(VanitySearch {
+            secp: Secp256k1::new(),
+            address_type,
+            network,
+            pattern,
+            expected,
+            attempts: 0,
+            result: None,
+        })
+    }
+
+    pub fn step(&mut self, max_work_units: u32, compact: Option<bool>) -> Result<String, JsValue> {
+        let units = check_work_units(max_work_units)?;
+        for _ in 0..units {
+            if self.result.is_some() {
+                break;
+            }
+            let secret_key = random_secret_key();
+            self.attempts += 1;
+            let Some(address) = self.address(&secret_key) else { continue };
+            if address.starts_with(&self.pattern) {
+                let found = VanityMatch {
+                    address,
+                    private_key: bytes_to_hex(&secret_key.secret_bytes()),
+                    wif: PrivateKey::new(secret_key, self.network.network).to_wif(),
+                    attempts: self.attempts,
+                };
+                self.result = Some(to_result(&found)?);
+            }
+        }
+        let progress = Progress { completed: self.attempts, total: None, expected: Some(self.expected) };
+        step_json(progress, self.result.as_deref(), compact)
+    }
+}
+
+impl VanitySearch {
+    fn address(&self, secret_key: &SecretKey) -> Option<String> {
+        let pubkey = CompressedPublicKey(secret_key.public_key(&self.secp));
+        let script = match self.address_type {
+            VanityType::Legacy => ScriptBuf::new_p2pkh(&bitcoin::PublicKey::from(pubkey).pubkey_hash()),
+            VanityType::Segwit => ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()),
+            VanityType::NestedSegwit => ScriptBuf::new_p2sh(&ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()).script_hash()),
+            VanityType::Taproot => ScriptBuf::new_p2tr(&self.secp, pubkey.0.x_only_public_key().0, None),
+        };
+        self.network.address(&script)
+    }
+}
+
+fn random_secret_key() -> SecretKey {
+    loop {
+        let mut bytes = [0u8; 32];
+        with_rng(|rng| rng.fill_bytes(&mut bytes));
+        if let Ok(secret_key) = SecretKey::from_slice(&bytes) {
+            return secret_key;
+        }
+    }
+}
+
+/// The lowercased pattern and the attempts a match is expected to take: every character
+/// after `<hrp>1<version>` is one of 32.
+fn bech32_pattern(pattern: &str, address_type: VanityType, network: &NetworkParams) -> Result<(String, f64), String> {
+    let pattern = pattern.to_ascii_lowercase();
+    let version = if matches!(address_type, VanityType::Taproot) { 'p' } else { 'q' };
+    let fixed = format!("{}1{}", network.bech32_hrp.to_lowercase(), version);
+    let Some(free) = pattern.strip_prefix(&fixed) else {
+        return Err(format!("Pattern must start with \"{}\": every address of this type does", fixed));
+    };
+    if let Some(c) = free.chars().find(|&c| !BECH32_CHARSET.contains(c)) {
+        return Err(format!("'{}' can't appear in a bech32 address (allowed: {})", c, BECH32_CHARSET));
+    }
+    // Leave room for the checksum: the last six characters aren't free to choose.
+    let program_chars = if matches!(address_type, VanityType::Taproot) { 52 } else { 32 };
+    if free.len() > program_chars {
+        return Err(format!("Pattern is longer than the {} characters an address of this type has", fixed.len() + program_chars));
+    }
+    let expected = 32f64.powi(free.len() as i32);
+    Ok((pattern, expected))
+}
+
+/// The pattern and the attempts a match is expected to take. Addresses are a version
+/// byte and 24 payload bytes read as one number, so a pattern is a range of such numbers
+/// and the odds are how much of the version's range it covers. A zero version byte
+/// always gives a leading '1' followed by a number of varying length; there each
+/// character counts roughly as one of 58.
+fn base58_pattern(pattern: &str, address_type: VanityType, network: &NetworkParams) -> Result<(String, f64), String> {
+    if pattern.is_empty() {
+        return Err("Pattern must not be empty".to_string());
+    }
+    if let Some(c) = pattern.chars().find(|&c| !BASE58_CHARSET.contains(c)) {
+        return Err(format!("'{}' can't appear in a base58 address (0, O, I and l are excluded)", c));
+    }
+    let version = match address_type {
+        VanityType::Legacy => network.pubkey_hash,
+        _ => network.script_hash,
+    };
+    let unmatchable = || format!("No address of this type on {} starts with \"{}\"", network.name, pattern);
+    if version == 0 {
+        if !pattern.starts_with('1') || pattern.len() > 34 {
+            return Err(unmatchable());
+        }
+        return Ok((pattern.to_string(), 58f64.powi(pattern.len() as i32 - 1)));
+    }
+
+    let encoded_len = |fill: u8| base58::encode(&[&[version][..], &[fill; 24]].concat()).len();
+    let length = encoded_len(0xff);
+    let (low, high) = (version as f64 * 2f64.powi(192), (version as f64 + 1.0) * 2f64.powi(192));
+    if encoded_len(0x00) != length || pattern.len() > length {
+        // Versions 1 and 2 give addresses of two lengths; only a length check is made.
+        if pattern.len() > length {
+            return Err(unmatchable());
+        }
+        return Ok((pattern.to_string(), 58f64.powi(pattern.len() as i32)));
+    }
+    let value = pattern.chars().fold(0f64, |value, c| value * 58.0 + BASE58_CHARSET.find(c).unwrap_or_default() as f64);
+    let scale = 58f64.powi((length - pattern.len()) as i32);
+    let covered = high.min((value + 1.0) * scale) - low.max(value * scale);
+    if covered <= 0.0 {
+        return Err(unmatchable());
+    }
+    Ok((pattern.to_string(), (high - low) / covered))
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::ScriptBuf;
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+use crate::descriptor::{derive_script, parse_descriptors};
+use crate::utils::{hex_to_bytes, HexMode};
+use crate::utils::amount::AmountFormat;
+use crate::utils::json::{parse_json_value, to_json_with_amounts};
+
+const DEFAULT_GAP_LIMIT: u32 = 20;
+const STATE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedUtxo {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "amountSat", alias = "amount_sat", alias = "amount", with = "crate::utils::amount")]
+    pub amount: u64,
+    #[serde(alias = "script_pubkey")]
+    pub script_pubkey: String,
+    pub height: Option<u32>,
+    #[serde(alias = "descriptor_index")]
+    pub descriptor_index: usize,
+    #[serde(alias = "derivation_index")]
+    pub derivation_index: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WatchBalance {
+    #[serde(with = "crate::utils::amount")]
+    pub confirmed: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub unconfirmed: u64,
+    #[serde(with = "crate::utils::amount")]
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchWalletState {
+    pub version: u32,
+    pub descriptors: Vec<String>,
+    #[serde(alias = "gap_limit")]
+    pub gap_limit: u32,
+    pub derived: Vec<u32>,
+    pub utxos: Vec<WatchedUtxo>,
+}
+
+#[wasm_bindgen]
+pub struct WatchWallet {
+    sources: Vec<String>,
+    gap_limit: u32,
+    descriptors: Vec<Descriptor<DescriptorPublicKey>>,
+    derived: Vec<u32>,
+    scripts: HashMap<ScriptBuf, (usize, Option<u32>)>,
+    utxos: BTreeMap<(String, u32), WatchedUtxo>,
+}
+
+#[wasm_bindgen]
+impl WatchWallet {
+    #[wasm_bindgen(constructor)]
+    pub fn new(descriptors_json: &str, gap_limit: Option<u32>) -> Result<WatchWallet, JsValue> {
+        let sources: Vec<String> = serde_json::from_str(descriptors_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid descriptors JSON: {}", e)))?;
+        WatchWallet::from_sources(sources, gap_limit.unwrap_or(DEFAULT_GAP_LIMIT)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn register_utxo(
+        &mut self,
+        txid: &str,
+        vout: u32,
+        amount: u64,
+        script_pubkey: &str,
+        height: Option<u32>,
+    ) -> Result<(), JsValue> {
+        let script = parse_script(script_pubkey)?;
+        let (descriptor_index, derivation_index) = *self
+            .scripts
+            .get(&script)
+            .ok_or_else(|| JsValue::from_str("script_pubkey is not watched by this wallet"))?;
+
+        if let Some(index) = derivation_index {
+            self.derive_up_to(descriptor_index, index.saturating_add(1).saturating_add(self.gap_limit))
+                .map_err(|e| JsValue::from_str(&e))?;
+        }
+
+        self.utxos.insert(
+            (txid.to_ascii_lowercase(), vout),
+            WatchedUtxo {
+                txid: txid.to_ascii_lowercase(),
+                vout,
+                amount,
+                script_pubkey: script.to_hex_string(),
+                height,
+                descriptor_index,
+                derivation_index,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove_spent(&mut self, txid: &str, vout: u32) -> bool {
+        self.utxos.remove(&(txid.to_ascii_lowercase(), vout)).is_some()
+    }
+
+    pub fn balance(&self, tip_height: u32, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+        let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let mut balance = WatchBalance {
+            confirmed: 0,
+            unconfirmed: 0,
+            total: 0,
+        };
+        for utxo in self.utxos.values() {
+            if confirmations(utxo, tip_height) > 0 {
+                balance.confirmed += utxo.amount;
+            } else {
+                balance.unconfirmed += utxo.amount;
+            }
+            balance.total += utxo.amount;
+        }
+
+        to_json_with_amounts(&balance, amount_format, compact)
+    }
+
+    pub fn list_utxos(&self, min_confirmations: u32, tip_height: u32, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+        let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let utxos: Vec<&WatchedUtxo> = self
+            .utxos
+            .values()
+            .filter(|utxo| confirmations(utxo, tip_height) >= min_confirmations)
+            .collect();
+
+        to_json_with_amounts(&utxos, amount_format, compact)
+    }
+
+    pub fn owns_script(&self, script_hex: &str) -> Result<bool, JsValue> {
+        Ok(self.scripts.contains_key(&parse_script(script_hex)?))
+    }
+
+    pub fn export_state(&self, amount_format: Option<String>, compact: Option<bool>) -> Result<String, JsValue> {
+        let amount_format = AmountFormat::parse(amount_format.as_deref()).map_err(|e| JsValue::from_str(&e))?;
+        let state = WatchWalletState {
+            version: STATE_VERSION,
+            descriptors: self.sources.clone(),
+            gap_limit: self.gap_limit,
+            derived: self.derived.clone(),
+            utxos: self.utxos.values().cloned().collect(),
+        };
+
+        to_json_with_amounts(&state, amount_format, compact)
+    }
+
+    pub fn import_state(state_json: &str) -> Result<WatchWallet, JsValue> {
+        let state: WatchWalletState = parse_json_value(state_json, "wallet state").map_err(|e| JsValue::from_str(&e))?;
+        if state.version != STATE_VERSION {
+            return Err(JsValue::from_str(&format!("Unsupported wallet state version {}", state.version)));
+        }
+
+        let mut wallet = WatchWallet::from_sources(state.descriptors, state.gap_limit).map_err(|e| JsValue::from_str(&e))?;
+        for (descriptor_index, count) in state.derived.into_iter().enumerate() {
+            if descriptor_index < wallet.descriptors.len() {
+                wallet.derive_up_to(descriptor_index, count).map_err(|e| JsValue::from_str(&e))?;
+            }
+        }
+        for utxo in state.utxos {
+            wallet.register_utxo(&utxo.txid, utxo.vout, utxo.amount, &utxo.script_pubkey, utxo.height)?;
+        }
+        Ok(wallet)
+    }
+}
+
+impl WatchWallet {
+    fn from_sources(sources: Vec<String>, gap_limit: u32) -> Result<WatchWallet, String> {
+        if sources.is_empty() {
+            return Err("At least one descriptor or xpub is required".to_string());
+        }
+
+        let mut descriptors = Vec::new();
+        for source in &sources {
+            descriptors.extend(parse_descriptors(source)?);
+        }
+
+        let mut wallet = WatchWallet {
+            sources,
+            gap_limit,
+            derived: vec![0; descriptors.len()],
+            descriptors,
+            scripts: HashMap::new(),
+            utxos: BTreeMap::new(),
+        };
+        for descriptor_index in 0..wallet.descriptors.len() {
+            wallet.derive_up_to(descriptor_index, gap_limit.max(1))?;
+        }
+        Ok(wallet)
+    }
+
+    /// Derives scripts for indexes below `count`; descriptors without a wildcard have a single script.
+    fn derive_up_to(&mut self, descriptor_index: usize, count: u32) -> Result<(), String> {
+        let descriptor = &self.descriptors[descriptor_index];
+        if !descriptor.has_wildcard() {
+            if self.derived[descriptor_index] == 0 {
+                self.scripts.insert(derive_script(descriptor, 0)?, (descriptor_index, None));
+                self.derived[descriptor_index] = 1;
+            }
+            return Ok(());
+        }
+
+        for index in self.derived[descriptor_index]..count {
+            self.scripts
+                .insert(derive_script(descriptor, index)?, (descriptor_index, Some(index)));
+        }
+        self.derived[descriptor_index] = self.derived[descriptor_index].max(count);
+        Ok(())
+    }
+}
+
+fn confirmations(utxo: &WatchedUtxo, tip_height: u32) -> u32 {
+    match utxo.height {
+        Some(height) if height <= tip_height => tip_height - height + 1,
+        _ => 0,
+    }
+}
+
+fn parse_script(script_hex: &str) -> Result<ScriptBuf, JsValue> {
+    hex_to_bytes(script_hex, HexMode::Lenient)
+        .map(ScriptBuf::from_bytes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid script pubkey: {}", e)))
+}
@@ -0,0 +1,460 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use bitcoin::base58;
+use bitcoin::bip32::{ChildNumber, Xpriv, Xpub};
+use bitcoin::hashes::{hash160, sha256d, Hash};
+use bitcoin::secp256k1::{All, PublicKey, Secp256k1, SecretKey};
+use crate::utils::bytes_to_hex;
+use crate::utils::json::{parse_json_list, to_json};
+use crate::wallet::path::parse_path;
+use crate::utils::metrics::timed;
+
+const HARDENED_BIT: u32 = 0x8000_0000;
+
+/// A BIP32 or SLIP-132 version pair. The stem is the prefix without its `pub`/`prv` ending.
+struct KeyVersion {
+    public: [u8; 4],
+    private: [u8; 4],
+    stem: &'static str,
+    network: &'static str,
+    script_type: &'static str,
+}
+
+const VERSIONS: [KeyVersion; 10] = [
+    KeyVersion { public: [0x04, 0x88, 0xb2, 0x1e], private: [0x04, 0x88, 0xad, 0xe4], stem: "x", network: "mainnet", script_type: "p2pkh" },
+    KeyVersion { public: [0x04, 0x9d, 0x7c, 0xb2], private: [0x04, 0x9d, 0x78, 0x78], stem: "y", network: "mainnet", script_type: "p2sh-p2wpkh" },
+    KeyVersion { public: [0x04, 0xb2, 0x47, 0x46], private: [0x04, 0xb2, 0x43, 0x0c], stem: "z", network: "mainnet", script_type: "p2wpkh" },
+    KeyVersion { public: [0x02, 0x95, 0xb4, 0x3f], private: [0x02, 0x95, 0xb0, 0x05], stem: "Y", network: "mainnet", script_type: "p2sh-p2wsh" },
+    KeyVersion { public: [0x02, 0xaa, 0x7e, 0xd3], private: [0x02, 0xaa, 0x7a, 0x99], stem: "Z", network: "mainnet", script_type: "p2wsh" },
+    KeyVersion { public: [0x04, 0x35, 0x87, 0xcf], private: [0x04, 0x35, 0x83, 0x94], stem: "t", network: "testnet", script_type: "p2pkh" },
+    KeyVersion { public: [0x04, 0x4a, 0x52, 0x62], private: [0x04, 0x4a, 0x4e, 0x28], stem: "u", network: "testnet", script_type: "p2sh-p2wpkh" },
+    KeyVersion { public: [0x04, 0x5f, 0x1c, 0xf6], private: [0x04, 0x5f, 0x18, 0xbc], stem: "v", network: "testnet", script_type: "p2wpkh" },
+    KeyVersion { public: [0x02, 0x42, 0x89, 0xef], private: [0x02, 0x42, 0x85, 0xb5], stem: "U", network: "testnet", script_type: "p2sh-p2wsh" },
+    KeyVersion { public: [0x02, 0x57, 0x54, 0x83], private: [0x02, 0x57, 0x50, 0x48], stem: "V", network: "testnet", script_type: "p2wsh" },
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtendedKeyInfo {
+    pub valid: bool,
+    #[serde(alias = "checksum_valid")]
+    pub checksum_valid: bool,
+    #[serde(alias = "key_valid")]
+    pub key_valid: bool,
+    pub version: String,
+    pub prefix: Option<String>,
+    pub network: Option<String>,
+    #[serde(alias = "script_type")]
+    pub script_type: Option<String>,
+    #[serde(alias = "key_type")]
+    pub key_type: String,
+    pub depth: u8,
+    #[serde(alias = "parent_fingerprint")]
+    pub parent_fingerprint: String,
+    #[serde(alias = "child_number")]
+    pub child_number: u32,
+    #[serde(alias = "child_index")]
+    pub child_index: u32,
+    pub hardened: bool,
+    #[serde(alias = "chain_code")]
+    pub chain_code: String,
+    #[serde(alias = "public_key")]
+    pub public_key: Option<String>,
+    pub fingerprint: Option<String>,
+    pub problems: Vec<String>,
+}
+
+/// Takes an extended key apart field by field without trusting it, so a pasted key
+/// can be checked before anything is derived from it. A typo shows up as a checksum
+/// mismatch in `problems` instead of an opaque parse error; only text that isn't
+/// base58 at all, or doesn't decode to 82 bytes, is an error.
+#[wasm_bindgen]
+pub fn inspect_xkey(xprv_or_xpub: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    let (payload, checksum_valid) = decode_xkey(xprv_or_xpub).map_err(|e| JsValue::from_str(&e))?;
+    let payload = &payload[..];
+    let mut problems = Vec::new();
+    if !checksum_valid {
+        problems.push("checksum mismatch: the key was mistyped or truncated".to_string());
+    }
+
+    let version: [u8; 4] = payload[..4].try_into().expect("4 bytes");
+    let known = VERSIONS.iter().find_map(|known| {
+        if version == known.public {
+            Some((format!("{}pub", known.stem), known.network, known.script_type, "public"))
+        } else if version == known.private {
+            Some((format!("{}prv", known.stem), known.network, known.script_type, "private"))
+        } else {
+            None
+        }
+    });
+    if known.is_none() {
+        problems.push(format!("unknown version bytes {}", bytes_to_hex(&version)));
+    }
+
+    let depth = payload[4];
+    let parent_fingerprint = &payload[5..9];
+    let child_number = u32::from_be_bytes(payload[9..13].try_into().expect("4 bytes"));
+    if depth == 0 && (parent_fingerprint != [0; 4] || child_number != 0) {
+        problems.push("a depth-0 (master) key must have a zero parent fingerprint and child number".to_string());
+    }
+
+    let key_data = &payload[45..78];
+    let key_type = if key_data[0] == 0x00 { "private" } else { "public" };
+    if let Some((prefix, _, _, expected)) = &known {
+        if *expected != key_type {
+            problems.push(format!("{} version bytes but the key data is a {} key", prefix, key_type));
+        }
+    }
+    let public_key = if key_type == "private" {
+        SecretKey::from_slice(&key_data[1..])
+            .map(|secret| secret.public_key(&Secp256k1::signing_only()))
+            .map_err(|_| "private key is zero or not below the curve order")
+    } else {
+        PublicKey::from_slice(key_data).map_err(|_| "public key is not a valid compressed point")
+    };
+    let public_key = match public_key {
+        Ok(public_key) => Some(public_key.serialize()),
+        Err(problem) => {
+            problems.push(problem.to_string());
+            None
+        }
+    };
+
+    let info = ExtendedKeyInfo {
+        valid: problems.is_empty(),
+        checksum_valid,
+        key_valid: public_key.is_some(),
+        version: bytes_to_hex(&version),
+        prefix: known.as_ref().map(|(prefix, ..)| prefix.clone()),
+        network: known.as_ref().map(|(_, network, ..)| network.to_string()),
+        script_type: known.as_ref().map(|(_, _, script_type, _)| script_type.to_string()),
+        key_type: key_type.to_string(),
+        depth,
+        parent_fingerprint: bytes_to_hex(parent_fingerprint),
+        child_number,
+        child_index: child_number & !HARDENED_BIT,
+        hardened: child_number & HARDENED_BIT != 0,
+        chain_code: bytes_to_hex(&payload[13..45]),
+        public_key: public_key.map(|key| bytes_to_hex(&key)),
+        fingerprint: public_key.map(|key| bytes_to_hex(&hash160::Hash::hash(&key)[..4])),
+        problems,
+    };
+
+    to_json(&info, compact)
+}
+
+/// The prefix (`xpub`, `zprv`, ...), network and script type of a checksum-valid extended
+/// key with known version bytes, and whether it is private. `None` for anything else.
+pub fn xkey_flavour(key: &str) -> Option<(String, &'static str, &'static str, bool)> {
+    let (payload, checksum_valid) = decode_xkey(key).ok()?;
+    if !checksum_valid {
+        return None;
+    }
+    let version: [u8; 4] = payload[..4].try_into().expect("4 bytes");
+    VERSIONS.iter().find_map(|known| {
+        if version == known.public {
+            Some((format!("{}pub", known.stem), known.network, known.script_type, false))
+        } else if version == known.private {
+            Some((format!("{}prv", known.stem), known.network, known.script_type, true))
+        } else {
+            None
+        }
+    })
+}
+
+/// The xpub for an xprv, keeping its SLIP-132 flavour (a `zprv` gives a `zpub`) and every
+/// other field. Only the private key is dropped, so the result is safe to share.
+#[wasm_bindgen]
+pub fn xprv_to_xpub(xprv: &str) -> Result<String, JsValue> {
+    timed("xprv_to_xpub", xprv.len(), || {
+        neuter(xprv).map_err(|e| JsValue::from_str(&e))
+    })
+}
+
+pub fn neuter(xprv: &str) -> Result<String, String> {
+    let (mut payload, checksum_valid) = decode_xkey(xprv)?;
+    if !checksum_valid {
+        return Err("Invalid extended key: checksum mismatch".to_string());
+    }
+    let version: [u8; 4] = payload[..4].try_into().expect("4 bytes");
+    let known = match VERSIONS.iter().find(|known| version == known.private || version == known.public) {
+        Some(known) if version == known.public => {
+            return Err(format!("This is already a public key ({}pub)", known.stem))
+        }
+        Some(known) => known,
+        None => return Err(format!("Unknown extended private key version {}", bytes_to_hex(&version))),
+    };
+    if payload[45] != 0x00 {
+        return Err(format!("{}prv version bytes but the key data is not a private key", known.stem));
+    }
+    let secret = SecretKey::from_slice(&payload[46..78])
+        .map_err(|_| "Private key is zero or not below the curve order".to_string())?;
+
+    payload[..4].copy_from_slice(&known.public);
+    payload[45..78].copy_from_slice(&secret.public_key(&Secp256k1::signing_only()).serialize());
+    Ok(base58::encode_check(&payload))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedChild {
+    pub xkey: String,
+    pub index: u32,
+    pub hardened: bool,
+    /// The index as serialized, with 2^31 added for hardened children.
+    #[serde(alias = "child_number")]
+    pub child_number: u32,
+    pub depth: u8,
+    pub fingerprint: String,
+    #[serde(alias = "parent_fingerprint")]
+    pub parent_fingerprint: String,
+    #[serde(alias = "public_key")]
+    pub public_key: String,
+}
+
+/// A child index in `derive_children`: a plain number is unhardened, a string may mark
+/// the step hardened the way paths do (`"5'"`, `"5h"`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChildIndex {
+    Number(u64),
+    Text(String),
+}
+
+/// A key decoded for derivation, remembering its SLIP-132 flavour so children keep it.
+enum ParentKey {
+    Private(Xpriv, &'static KeyVersion),
+    Public(Xpub, &'static KeyVersion),
+}
+
+/// One derivation step below `xkey`, for walking a tree a child at a time instead of
+/// by path. `index` is below 2^31 either way; `hardened` adds the 2^31 itself. Children
+/// keep the parent's prefix, so a `zprv` gives a `zprv`.
+#[wasm_bindgen]
+pub fn derive_child(xkey: &str, index: u32, hardened: bool, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("derive_child", xkey.len(), || {
+        let secp = Secp256k1::new();
+        let parent = parent_key(xkey).map_err(|e| JsValue::from_str(&e))?;
+        let child = child_number(index as u64, hardened).map_err(|e| JsValue::from_str(&e))?;
+        let derived = derive(&secp, &parent, child).map_err(|e| JsValue::from_str(&e))?;
+
+        to_json(&derived, compact)
+    })
+}
+
+/// Several siblings of one parent in a single call. `indexes_json` is an array of
+/// numbers (unhardened) and strings such as `"0'"` or `"7h"` (hardened); the children
+/// come back in the same order.
+#[wasm_bindgen]
+pub fn derive_children(xkey: &str, indexes_json: &str, compact: Option<bool>) -> Result<String, JsValue> {
+    timed("derive_children", xkey.len() + indexes_json.len(), || {
+        let secp = Secp256k1::new();
+        let parent = parent_key(xkey).map_err(|e| JsValue::from_str(&e))?;
+        let indexes: Vec<ChildIndex> = parse_json_list(indexes_json, "indexes").map_err(|e| JsValue::from_str(&e))?;
+
+        let children = indexes
+            .iter()
+            .enumerate()
+            .map(|(position, index)| {
+                let child = match index {
+                    ChildIndex::Number(index) => child_number(*index, false),
+                    ChildIndex::Text(text) => text_child_number(text),
+                };
+                child
+                    .and_then(|child| derive(&secp, &parent, child))
+                    .map_err(|e| format!("indexes[{}]: {}", position, e))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        to_json(&children, compact)
+    })
+}
+
+fn child_number(index: u64, hardened: bool) -> Result<ChildNumber, String> {
+    let index = u32::try_from(index).ok().filter(|&index| index < HARDENED_BIT).ok_or_else(|| {
+        let hint = if hardened { "" } else { "; pass hardened instead of adding 2^31" };
+        format!("Child index {} is out of range, indexes must be below 2^31 (2147483648){}", index, hint)
+    })?;
+    Ok(if hardened { ChildNumber::Hardened { index } } else { ChildNumber::Normal { index } })
+}
+
+fn text_child_number(text: &str) -> Result<ChildNumber, String> {
+    let path = parse_path(text, false)?;
+    match path.path.as_ref() {
+        [child] => Ok(*child),
+        _ => Err(format!("\"{}\" is not a single child index", text.trim())),
+    }
+}
+
+fn parent_key(xkey: &str) -> Result<ParentKey, String> {
+    let (mut payload, checksum_valid) = decode_xkey(xkey)?;
+    if !checksum_valid {
+        return Err("Invalid extended key: checksum mismatch".to_string());
+    }
+    let version: [u8; 4] = payload[..4].try_into().expect("4 bytes");
+    let known = VERSIONS
+        .iter()
+        .find(|known| version == known.private || version == known.public)
+        .ok_or_else(|| format!("Unknown extended key version {}", bytes_to_hex(&version)))?;
+    // rust-bitcoin only reads the plain xpub/tpub versions, so decode under those.
+    let standard = if known.network == "mainnet" { &VERSIONS[0] } else { &VERSIONS[5] };
+    if version == known.private {
+        payload[..4].copy_from_slice(&standard.private);
+        Xpriv::decode(&payload)
+            .map(|key| ParentKey::Private(key, known))
+            .map_err(|e| format!("Invalid extended private key: {}", e))
+    } else {
+        payload[..4].copy_from_slice(&standard.public);
+        Xpub::decode(&payload)
+            .map(|key| ParentKey::Public(key, known))
+            .map_err(|e| format!("Invalid extended public key: {}", e))
+    }
+}
+
+fn derive(secp: &Secp256k1<All>, parent: &ParentKey, child: ChildNumber) -> Result<DerivedChild, String> {
+    let (mut payload, version, public_key) = match parent {
+        ParentKey::Private(key, known) => {
+            let derived = key.derive_priv(secp, &[child]).map_err(|e| format!("Derivation failed: {}", e))?;
+            (derived.encode(), known.private, derived.private_key.public_key(secp))
+        }
+        ParentKey::Public(key, known) => {
+            if child.is_hardened() {
+                return Err(format!(
+                    "Hardened child {} needs the private key; a {}pub only derives unhardened children",
+                    child, known.stem
+                ));
+            }
+            let derived = key.ckd_pub(secp, child).map_err(|e| format!("Derivation failed: {}", e))?;
+            (derived.encode(), known.public, derived.public_key)
+        }
+    };
+    payload[..4].copy_from_slice(&version);
+    let public_key = public_key.serialize();
+    let (index, hardened) = match child {
+        ChildNumber::Normal { index } => (index, false),
+        ChildNumber::Hardened { index } => (index, true),
+    };
+
+    Ok(DerivedChild {
+        xkey: base58::encode_check(&payload),
+        index,
+        hardened,
+        child_number: u32::from(child),
+        depth: payload[4],
+        fingerprint: bytes_to_hex(&hash160::Hash::hash(&public_key)[..4]),
+        parent_fingerprint: bytes_to_hex(&payload[5..9]),
+        public_key: bytes_to_hex(&public_key),
+    })
+}
+
+/// An extended public key with any known prefix, read as the plain xpub/tpub it encodes,
+/// plus the network and script type the prefix stands for. Private keys are refused.
+#[cfg(feature = "wallet")]
+pub fn standard_xpub(key: &str) -> Result<(Xpub, &'static str, &'static str), String> {
+    match parent_key(key)? {
+        ParentKey::Public(xpub, known) => Ok((xpub, known.network, known.script_type)),
+        ParentKey::Private(_, known) => Err(format!("This is a private key ({}prv); only public keys belong here", known.stem)),
+    }
+}
+
+/// The 78-byte payload and whether its checksum matched. Only text that isn't base58, or
+/// doesn't decode to 82 bytes, is an error.
+fn decode_xkey(key: &str) -> Result<(Vec<u8>, bool), String> {
+    let mut data = base58::decode(key.trim()).map_err(|e| format!("Not base58: {}", e))?;
+    if data.len() != 82 {
+        return Err(format!("An extended key decodes to 78 bytes plus a 4-byte checksum, got {} bytes", data.len()));
+    }
+    let checksum = data.split_off(78);
+    let checksum_valid = sha256d::Hash::hash(&data)[..4] == checksum[..];
+    Ok((data, checksum_valid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::bip32::DerivationPath;
+    use bitcoin::NetworkKind;
+    use std::str::FromStr;
+
+    /// BIP32 test vector 1.
+    const SEED: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+    const MASTER_XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+    const HARDENED_CHILD_XPUB: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+
+    fn master(network: NetworkKind) -> Xpriv {
+        Xpriv::new_master(network, &SEED).unwrap()
+    }
+
+    fn assert_public_only(output: &str) {
+        assert!(!output.contains("xprv") && !output.contains("tprv"), "private key in {}", output);
+    }
+
+    #[test]
+    fn neuters_bip32_vector_one() {
+        let secp = Secp256k1::new();
+        assert_eq!(neuter(&master(NetworkKind::Main).to_string()).unwrap(), MASTER_XPUB);
+        let child = master(NetworkKind::Main).derive_priv(&secp, &DerivationPath::from_str("m/0h").unwrap()).unwrap();
+        assert_eq!(neuter(&child.to_string()).unwrap(), HARDENED_CHILD_XPUB);
+        assert!(neuter(MASTER_XPUB).unwrap_err().contains("already a public key"));
+    }
+
+    #[test]
+    fn one_character_typo_is_a_checksum_mismatch() {
+        let info: ExtendedKeyInfo = serde_json::from_str(&inspect_xkey(MASTER_XPUB, None).unwrap()).unwrap();
+        assert!(info.valid && info.checksum_valid, "{:?}", info.problems);
+
+        // The last character changed from 8 to 9, which only touches the checksum bytes.
+        let typo = format!("{}9", &MASTER_XPUB[..MASTER_XPUB.len() - 1]);
+        let info: ExtendedKeyInfo = serde_json::from_str(&inspect_xkey(&typo, None).unwrap()).unwrap();
+        assert!(!info.valid);
+        assert!(!info.checksum_valid);
+        assert_eq!(info.problems, ["checksum mismatch: the key was mistyped or truncated"]);
+        // The fields are still shown, so the reader can see which part looks off.
+        assert_eq!(info.prefix.as_deref(), Some("xpub"));
+        assert_eq!(info.depth, 0);
+
+        // A typo further in can also garble the key data, but the checksum is reported first.
+        let typo_in_key = MASTER_XPUB.replacen("Nqtwyb", "Nqtwzb", 1);
+        let info: ExtendedKeyInfo = serde_json::from_str(&inspect_xkey(&typo_in_key, None).unwrap()).unwrap();
+        assert!(!info.checksum_valid);
+        assert_eq!(info.problems[0], "checksum mismatch: the key was mistyped or truncated");
+
+        assert_eq!(xkey_flavour(&typo), None);
+        assert_eq!(parent_key(&typo).err().unwrap(), "Invalid extended key: checksum mismatch");
+    }
+
+    #[test]
+    fn neutered_keys_carry_no_private_prefix() {
+        for network in [NetworkKind::Main, NetworkKind::Test] {
+            let xprv = master(network).to_string();
+            let xpub = neuter(&xprv).unwrap();
+            assert_public_only(&xpub);
+            assert_eq!(xpub, Xpub::from_priv(&Secp256k1::new(), &master(network)).to_string());
+        }
+    }
+
+    #[cfg(feature = "wallet")]
+    #[test]
+    fn public_variants_never_return_private_keys() {
+        use crate::descriptor::export_descriptors;
+        use crate::descriptor::parse_descriptors;
+        use crate::wallet::hd::Wallet;
+        use crate::wallet::watch::WatchWallet;
+
+        let tprv = master(NetworkKind::Test).to_string();
+        let tpub = neuter(&tprv).unwrap();
+        let descriptor = format!("wpkh({}/<0;1>/*)", tpub);
+
+        let mut wallet = Wallet::new(&descriptor, Some("testnet".to_string()), None).unwrap();
+        assert_public_only(&wallet.next_receive_address(None).unwrap());
+        assert_public_only(&wallet.export_state(None).unwrap());
+        assert_public_only(&export_descriptors(&descriptor, None, None).unwrap());
+        assert_public_only(&export_descriptors(&tpub, Some(r#"{"format": "pair"}"#.to_string()), None).unwrap());
+        let watch = WatchWallet::new(&serde_json::json!([descriptor]).to_string(), None).unwrap();
+        assert_public_only(&watch.export_state(None, None).unwrap());
+
+        // Private keys are refused where descriptors come in, so they can't come out.
+        assert!(parse_descriptors(&format!("wpkh({}/<0;1>/*)", tprv)).is_err());
+    }
+}
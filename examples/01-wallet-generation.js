@@ -27,18 +27,19 @@ async function main() {
   const wallet = JSON.parse(walletJson);
 
   console.log('Wallet Details:');
-  console.log(`  Private Key: ${wallet.private_key}`);
-  console.log(`  Public Key: ${wallet.public_key}`);
-  console.log(`  Legacy Address (P2PKH): ${wallet.addresses.legacy}`);
-  console.log(`  SegWit Address (P2WPKH): ${wallet.addresses.segwit}`);
-  console.log(`  Taproot Address (P2TR): ${wallet.addresses.taproot}\n`);
+  console.log(`  Private Key: ${wallet.privateKey}`);
+  console.log(`  Public Key: ${wallet.publicKey}`);
+  console.log(`  Legacy Address (P2PKH): ${wallet.addresses.legacy.address}`);
+  console.log(`  SegWit Address (P2WPKH): ${wallet.addresses.segwit.address}`);
+  console.log(`  Nested SegWit Address (P2SH-P2WPKH): ${wallet.addresses.nestedSegwit.address}`);
+  console.log(`  Taproot Address (P2TR): ${wallet.addresses.taproot.address}\n`);
 
   // Step 3: Generate multiple wallets
   console.log('Step 3: Generating multiple wallets...');
   for (let i = 0; i < 3; i++) {
     const key = generate_private_key();
     const w = JSON.parse(derive_addresses_from_key(key));
-    console.log(`Wallet ${i + 1}: ${w.addresses.legacy}`);
+    console.log(`Wallet ${i + 1}: ${w.addresses.legacy.address}`);
   }
 }
 
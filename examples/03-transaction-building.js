@@ -21,13 +21,13 @@ async function main() {
       txid: 'abc123def456abc123def456abc123def456abc123def456abc123def456abc1',
       vout: 0,
       amount: 50000000, // 0.5 BTC in satoshis
-      script_pubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
+      scriptPubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
     },
     {
       txid: 'def456abc123def456abc123def456abc123def456abc123def456abc123def4',
       vout: 1,
       amount: 30000000, // 0.3 BTC in satoshis
-      script_pubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
+      scriptPubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
     }
   ];
 
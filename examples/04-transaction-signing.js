@@ -27,7 +27,7 @@ async function main() {
   console.log('Step 2: Deriving wallet addresses...');
   const walletJson = derive_addresses_from_key(privateKey);
   const wallet = JSON.parse(walletJson);
-  console.log(`Address: ${wallet.addresses.legacy}\n`);
+  console.log(`Address: ${wallet.addresses.legacy.address}\n`);
 
   // Step 3: Create a transaction to sign
   console.log('Step 3: Building transaction...');
@@ -36,7 +36,7 @@ async function main() {
       txid: 'abc123def456abc123def456abc123def456abc123def456abc123def456abc1',
       vout: 0,
       amount: 50000000,
-      script_pubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
+      scriptPubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
     }
   ];
 
@@ -61,7 +61,7 @@ async function main() {
     txHex,
     privateKey,
     0, // input index
-    inputs[0].script_pubkey,
+    inputs[0].scriptPubkey,
     inputs[0].amount
   );
 
@@ -30,8 +30,8 @@ async function main() {
 
   console.log(`Generated new wallet:`);
   console.log(`  Private Key: ${privateKey.substring(0, 16)}...`);
-  console.log(`  Public Key: ${wallet.public_key.substring(0, 16)}...`);
-  console.log(`  Address: ${wallet.addresses.legacy}\n`);
+  console.log(`  Public Key: ${wallet.publicKey.substring(0, 16)}...`);
+  console.log(`  Address: ${wallet.addresses.legacy.address}\n`);
 
   // Phase 2: Transaction Planning
   console.log('PHASE 2: Transaction Planning');
@@ -60,7 +60,7 @@ async function main() {
       txid: 'abc123def456abc123def456abc123def456abc123def456abc123def456abc1',
       vout: 0,
       amount: btc_to_satoshi(1.0), // 1 BTC UTXO
-      script_pubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
+      scriptPubkey: '76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac'
     }
   ];
 
@@ -73,7 +73,7 @@ async function main() {
       amount: sendAmountSats
     },
     {
-      address: wallet.addresses.legacy, // Change back to sender
+      address: wallet.addresses.legacy.address, // Change back to sender
       amount: changeAmount
     }
   ];
@@ -100,7 +100,7 @@ async function main() {
     txHex,
     privateKey,
     0,
-    inputs[0].script_pubkey,
+    inputs[0].scriptPubkey,
     inputs[0].amount
   );
 
@@ -118,7 +118,7 @@ async function main() {
   // Summary
   console.log('SUMMARY');
   console.log('─'.repeat(50));
-  console.log(`Wallet Address: ${wallet.addresses.legacy}`);
+  console.log(`Wallet Address: ${wallet.addresses.legacy.address}`);
   console.log(`Transaction ID: ${txid}`);
   console.log(`Amount Sent: ${sendAmount} BTC`);
   console.log(`Fee Paid: ${satoshi_to_btc(feeSats)} BTC`);